@@ -0,0 +1,147 @@
+//! `backup` subcommand.
+
+use anyhow::{bail, Context as _};
+use magro::{backup, remote::url_host, runner::HostLimiter, view::RepoView, Context};
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::GlobalOpt,
+    logging,
+    resume::{self, ResumeOpt},
+    select::RepoSelectOpt,
+};
+
+/// Options for `backup` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct BackupOpt {
+    /// Target repositories.
+    #[structopt(flatten)]
+    select: RepoSelectOpt,
+    /// Only checks and reports repositories out of sync with the backup remote.
+    ///
+    /// Remotes are still created or updated to point to the backup URL.
+    #[structopt(long)]
+    check: bool,
+    /// Runs the operation as possible even when errors are detected.
+    ///
+    /// Note that errors are ignored during the operation, but the program will
+    /// exit with failure (i.e. errors won't be completely ignored).
+    #[structopt(long)]
+    keep_going: bool,
+    /// Resuming options.
+    #[structopt(flatten)]
+    resume: ResumeOpt,
+}
+
+impl BackupOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &Context, global: &GlobalOpt) -> anyhow::Result<()> {
+        log::trace!(
+            "backup select={:?}, check={}, keep_going={}",
+            self.select,
+            self.check,
+            self.keep_going
+        );
+
+        let backup_conf = context
+            .config()
+            .main()
+            .backup()
+            .context("Backup is not configured (`[backup]` section of the main config)")?;
+        let repos = self.select.select(context)?;
+        let operation = if self.check { "backup-check" } else { "backup" };
+        let journal =
+            self.resume
+                .open_journal(context, operation, repos.iter().map(resume::repo_job_key))?;
+        let repos = resume::skip_completed(journal.as_deref(), repos);
+        let num_repos = repos.len();
+
+        let url_template = backup_conf.url_template().to_owned();
+        let remote = backup_conf.remote().to_owned();
+        let check = self.check;
+        let keep_going = self.keep_going;
+        let limiter = global.host_limiter(context);
+        let job_journal = journal.clone();
+        let envs = logging::CollectionEnvs::new(context);
+        let results = global.runner(context).run_until(
+            repos,
+            move |repo| {
+                logging::with_repo(repo, &envs, || {
+                    let unsynced =
+                        backup_repo(repo, &url_template, &remote, check, limiter.as_ref())?;
+                    resume::complete(job_journal.as_deref(), &resume::repo_job_key(repo));
+                    Ok(unsynced)
+                })
+            },
+            move |result| !keep_going && result.is_err(),
+        );
+
+        let mut num_errors = 0_usize;
+        let mut num_unsynced = 0_usize;
+        for (repo, result) in results {
+            match result {
+                Ok(unsynced) if unsynced.is_empty() => {}
+                Ok(unsynced) => {
+                    num_unsynced += 1;
+                    println!("{}: {}", repo.path().display(), unsynced.join(", "));
+                }
+                Err(e) => {
+                    if !self.keep_going {
+                        return Err(e);
+                    }
+                    log::error!("{:#}", e);
+                    num_errors += 1;
+                }
+            }
+        }
+
+        if self.check {
+            log::info!(
+                "{} of {} repositories are out of sync",
+                num_unsynced,
+                num_repos
+            );
+        }
+        if num_errors != 0 {
+            bail!("Backup failed for {} repositories", num_errors);
+        }
+        resume::finish(journal);
+
+        Ok(())
+    }
+}
+
+/// Backs up the repository, or checks the backup status if `check_only` is true.
+///
+/// Returns the names of branches out of sync with the backup remote.
+fn backup_repo(
+    repo: &RepoView,
+    url_template: &str,
+    remote: &str,
+    check_only: bool,
+    limiter: Option<&HostLimiter>,
+) -> anyhow::Result<Vec<String>> {
+    let vcs = repo.vcs();
+    let path = repo.path();
+    let url = backup::remote_url(url_template, repo)
+        .context("Failed to expand the backup URL template")?;
+    if vcs
+        .set_remote_url(path, remote, &url)
+        .with_context(|| format!("Failed to set the backup remote for {:?}", path))?
+    {
+        log::info!("Set backup remote of {:?} to {:?}", path, url);
+    }
+
+    let _permit = limiter
+        .zip(url_host(&url))
+        .map(|(limiter, host)| limiter.acquire(host));
+    if !check_only {
+        log::debug!("Pushing {:?} to {:?}", path, url);
+        vcs.push_all(path, remote)
+            .with_context(|| format!("Failed to push {:?} to {:?}", path, url))?;
+    }
+
+    vcs.unsynced_branches(path, remote)
+        .with_context(|| format!("Failed to compare {:?} with {:?}", path, url))
+}
@@ -0,0 +1,209 @@
+//! `branches` subcommand.
+
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use anyhow::bail;
+use magro::{branch::Branch, collection::CollectionName, view::RepoView, Context};
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::{Age, GlobalOpt},
+    logging,
+    output::{write_csv_record, write_json, OutputFormat},
+    select::RepoSelectOpt,
+};
+
+/// Options for `branches` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct BranchesOpt {
+    /// Target repositories.
+    #[structopt(flatten)]
+    select: RepoSelectOpt,
+    /// Shows only branches without upstreams.
+    #[structopt(long)]
+    no_upstream: bool,
+    /// Shows only branches merged into HEAD (excluding the one checked out).
+    #[structopt(long)]
+    merged: bool,
+    /// Shows only branches whose last commits are older than the age (such
+    /// as `30d`, `2w`, `6mo`, or `1y`).
+    #[structopt(long, value_name = "age")]
+    stale: Option<Age>,
+    /// Runs the operation as possible even when errors are detected.
+    ///
+    /// Note that errors are ignored during the operation, but the program will
+    /// exit with failure (i.e. errors won't be completely ignored).
+    #[structopt(long)]
+    keep_going: bool,
+}
+
+impl BranchesOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &Context, global: &GlobalOpt) -> anyhow::Result<()> {
+        log::trace!(
+            "branches select={:?}, no_upstream={}, merged={}, stale={:?}, keep_going={}",
+            self.select,
+            self.no_upstream,
+            self.merged,
+            self.stale,
+            self.keep_going
+        );
+
+        let repos = self.select.select(context)?;
+        let keep_going = self.keep_going;
+        let envs = logging::CollectionEnvs::new(context);
+        let results = global.runner(context).run_until(
+            repos,
+            move |repo| logging::with_repo(repo, &envs, || repo.vcs().branches(repo.path())),
+            move |result| !keep_going && result.is_err(),
+        );
+
+        let mut listed = Vec::with_capacity(results.len());
+        let mut num_errors = 0_usize;
+        for (repo, result) in results {
+            match result {
+                Ok(branches) => listed.push((repo, branches)),
+                Err(e) => {
+                    if !self.keep_going {
+                        return Err(anyhow::Error::new(e)
+                            .context(format!("Failed to list branches of {:?}", repo.path())));
+                    }
+                    log::error!("Failed to list branches of {:?}: {}", repo.path(), e);
+                    num_errors += 1;
+                }
+            }
+        }
+
+        let entries = listed
+            .iter()
+            .flat_map(|(repo, branches)| {
+                branches
+                    .iter()
+                    .map(move |branch| BranchEntry::new(repo, branch))
+            })
+            .filter(|entry| self.matches(entry.branch))
+            .collect::<Vec<_>>();
+
+        let stdout = io::stdout();
+        let mut handle = stdout.lock();
+        match global.output {
+            OutputFormat::Text => {
+                let now = magro::metadata::unix_now();
+                for entry in &entries {
+                    let branch = entry.branch;
+                    write!(
+                        handle,
+                        "{} {}/{}: {}",
+                        if branch.is_head() { '*' } else { ' ' },
+                        entry.collection,
+                        entry.name,
+                        branch.name()
+                    )?;
+                    if let Some(upstream) = branch.upstream() {
+                        write!(handle, " -> {}", upstream)?;
+                    }
+                    if branch.is_merged() && !branch.is_head() {
+                        write!(handle, " [merged]")?;
+                    }
+                    if let Some(committed_at) = branch.committed_at() {
+                        /// Seconds per day.
+                        const SECS_PER_DAY: u64 = 24 * 60 * 60;
+                        let days = now.saturating_sub(committed_at) / SECS_PER_DAY;
+                        write!(handle, " ({} days ago)", days)?;
+                    }
+                    writeln!(handle)?;
+                }
+            }
+            OutputFormat::Json => write_json(&mut handle, &entries)?,
+            OutputFormat::Csv => {
+                write_csv_record(
+                    &mut handle,
+                    &[
+                        "collection",
+                        "name",
+                        "path",
+                        "branch",
+                        "upstream",
+                        "is_head",
+                        "merged",
+                        "committed_at",
+                    ],
+                )?;
+                for entry in &entries {
+                    let branch = entry.branch;
+                    write_csv_record(
+                        &mut handle,
+                        &[
+                            entry.collection.as_str(),
+                            &entry.name,
+                            &entry.path.to_string_lossy(),
+                            branch.name(),
+                            branch.upstream().unwrap_or_default(),
+                            if branch.is_head() { "true" } else { "false" },
+                            if branch.is_merged() { "true" } else { "false" },
+                            &branch
+                                .committed_at()
+                                .map_or_else(String::new, |time| time.to_string()),
+                        ],
+                    )?;
+                }
+            }
+        }
+
+        if num_errors != 0 {
+            bail!("Failed to list branches of {} repositories", num_errors);
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if the branch matches the filters.
+    fn matches(&self, branch: &Branch) -> bool {
+        if self.no_upstream && branch.upstream().is_some() {
+            return false;
+        }
+        if self.merged && (!branch.is_merged() || branch.is_head()) {
+            return false;
+        }
+        if let Some(stale) = self.stale {
+            if !branch
+                .committed_at()
+                .map_or(false, |time| stale.is_older(time))
+            {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Branch entry of the report.
+#[derive(Debug, Serialize)]
+struct BranchEntry<'a> {
+    /// Collection name.
+    collection: &'a CollectionName,
+    /// Repository name.
+    name: String,
+    /// Absolute path of the repository.
+    path: &'a Path,
+    /// Branch.
+    branch: &'a Branch,
+}
+
+impl<'a> BranchEntry<'a> {
+    /// Creates a new entry.
+    fn new(repo: &'a RepoView, branch: &'a Branch) -> Self {
+        Self {
+            collection: repo.collection(),
+            name: repo.name_string(),
+            path: repo.path(),
+            branch,
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! `cache` subcommand.
+
+use std::{collections::HashMap, path::PathBuf};
+
+use anyhow::{bail, Context as _};
+use magro::{collection::CollectionName, metadata::Resolved, Context};
+use structopt::StructOpt;
+
+use crate::{cli_opt::GlobalOpt, logging, select::RepoSelectOpt};
+
+/// Options for `cache` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct CacheOpt {
+    /// Subcommand.
+    #[structopt(subcommand)]
+    subcommand: Subcommand,
+}
+
+impl CacheOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &mut Context, global: &GlobalOpt) -> anyhow::Result<()> {
+        match &self.subcommand {
+            Subcommand::Warm { select, keep_going } => {
+                log::trace!("cache warm select={:?}, keep_going={}", select, keep_going);
+                warm(context, global, select, *keep_going)
+            }
+        }
+    }
+}
+
+/// Subcommand of `cache`.
+#[derive(Debug, Clone, StructOpt)]
+pub enum Subcommand {
+    /// Resolves derived data of repositories into the cache.
+    ///
+    /// Working directories and branches checked out at HEAD are resolved and
+    /// stored, so that `list --workdir` and `locate` need not open the
+    /// repositories. The stored data is used until resolved again.
+    Warm {
+        /// Target repositories.
+        #[structopt(flatten)]
+        select: RepoSelectOpt,
+        /// Runs the operation as possible even when errors are detected.
+        ///
+        /// Note that errors are ignored during the operation, but the program
+        /// will exit with failure (i.e. errors won't be completely ignored).
+        #[structopt(long)]
+        keep_going: bool,
+    },
+}
+
+/// Resolves derived data of the repositories, and stores them into the cache.
+fn warm(
+    context: &mut Context,
+    global: &GlobalOpt,
+    select: &RepoSelectOpt,
+    keep_going: bool,
+) -> anyhow::Result<()> {
+    context.ensure_writable().context("Cannot warm the cache")?;
+    let repos = select.select(context)?;
+    let envs = logging::CollectionEnvs::new(context);
+    let results = global.runner(context).run_until(
+        repos,
+        move |repo| {
+            logging::with_repo(repo, &envs, || {
+                if !repo.path().exists() {
+                    log::debug!("Skipping the missing repository {:?}", repo.path());
+                    return Ok(None);
+                }
+                log::debug!("Resolving {:?}", repo.path());
+                Resolved::resolve(repo.vcs(), repo.path())
+                    .map(Some)
+                    .with_context(|| format!("Failed to resolve {:?}", repo.path()))
+            })
+        },
+        move |result| !keep_going && result.is_err(),
+    );
+
+    let mut resolved: HashMap<(CollectionName, PathBuf), Option<Resolved>> = HashMap::new();
+    let mut num_errors = 0_usize;
+    for (repo, result) in results {
+        match result {
+            Ok(data) => {
+                resolved.insert(
+                    (repo.collection().clone(), repo.relative_path().to_owned()),
+                    data,
+                );
+            }
+            Err(e) => {
+                if !keep_going {
+                    return Err(e);
+                }
+                log::error!("{:#}", e);
+                num_errors += 1;
+            }
+        }
+    }
+
+    log::info!("Resolved {} repositories", resolved.len());
+    context
+        .update_cache(|cache| {
+            for ((collection, path), data) in resolved {
+                if let Some(coll_cache) = cache.collection_repos_mut(&collection) {
+                    coll_cache.update_entry(&path, |entry| {
+                        entry.metadata_mut().set_resolved(data);
+                    });
+                }
+            }
+        })
+        .context("Failed to update cache file")?;
+
+    if num_errors != 0 {
+        bail!("Failed to resolve {} repositories", num_errors);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,110 @@
+//! `checkout` subcommand.
+
+use std::path::PathBuf;
+
+use anyhow::{bail, Context as _};
+use magro::{collection::CollectionName, query, Context};
+use structopt::StructOpt;
+
+use crate::{cli_opt::OptionBool, clone::ClonePlan, select::RepoSelectOpt};
+
+/// Name of the remote pointing to the mirror in the checkout.
+const MIRROR_REMOTE: &str = "mirror";
+
+/// Options for `checkout` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct CheckoutOpt {
+    /// Candidate mirror repositories.
+    #[structopt(flatten)]
+    select: RepoSelectOpt,
+    /// Collection to put the working tree.
+    ///
+    /// If not specified, the default collection is used.
+    #[structopt(long, value_name = "collection")]
+    into: Option<CollectionName>,
+    /// Query, same as `path` subcommand.
+    ///
+    /// The best matching bare repository is used.
+    query: String,
+    /// Relative path of the destination directory in the target collection.
+    ///
+    /// If not specified, the name of the mirror (without `.git` suffix) is
+    /// used.
+    #[structopt(parse(from_os_str))]
+    destination: Option<PathBuf>,
+}
+
+impl CheckoutOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &mut Context) -> anyhow::Result<()> {
+        log::trace!(
+            "checkout select={:?}, into={:?}, query={:?}, dest={:?}",
+            self.select,
+            self.into,
+            self.query,
+            self.destination
+        );
+
+        let repos = self.select.select(context)?;
+        let mut mirrors = Vec::new();
+        for repo in query::find(&repos, &self.query) {
+            if !repo.path().exists() {
+                continue;
+            }
+            let workdir = repo
+                .vcs()
+                .workdir(repo.path())
+                .with_context(|| format!("Failed to open the repository {:?}", repo.path()))?;
+            if workdir.is_none() {
+                mirrors.push(repo);
+            }
+        }
+        let mirror = match mirrors.first() {
+            Some(v) => *v,
+            None => bail!("No bare repositories matched the query {:?}", self.query),
+        };
+
+        // Record the URI the mirror was cloned from (or its `origin`) as the
+        // source URI of the working tree.
+        let mirror_path = mirror.path().to_string_lossy().into_owned();
+        let uri = match mirror.source_uri() {
+            Some(uri) => uri.to_owned(),
+            None => mirror
+                .vcs()
+                .remote_url(mirror.path(), "origin")
+                .context("Failed to get the URL of the remote `origin`")?
+                .unwrap_or_else(|| mirror_path.clone()),
+        };
+        let reldest = self.destination.as_deref().unwrap_or_else(|| mirror.name());
+        let plan = ClonePlan::new(
+            context,
+            &uri,
+            self.into.as_ref(),
+            Some(reldest),
+            Some(mirror.vcs()),
+            OptionBool::No,
+        )?
+        .local_source(mirror.path().to_owned());
+        let dest = plan.destination().to_owned();
+        if dest.exists() {
+            bail!("Destination {:?} already exists", dest);
+        }
+
+        log::info!("Checking out {:?} at {:?}", mirror.path(), dest);
+        plan.run(context)?;
+        mirror
+            .vcs()
+            .set_remote_url(&dest.join(".git"), MIRROR_REMOTE, &mirror_path)
+            .with_context(|| {
+                format!(
+                    "Failed to add the remote `{}` pointing to the mirror",
+                    MIRROR_REMOTE
+                )
+            })?;
+
+        println!("{}", dest.display());
+
+        Ok(())
+    }
+}
@@ -1,62 +1,658 @@
 //! CLI options.
 
-use std::{convert::TryFrom, fmt, str};
+use std::{
+    collections::HashSet, convert::TryFrom, ffi::OsString, fmt, num::NonZeroUsize, path::PathBuf,
+    str,
+};
 
-use anyhow::anyhow;
+use anyhow::{anyhow, bail};
 use magro::{
-    collection::{CollectionName, CollectionNameError},
+    cache::CacheNamespace,
+    collection::{CollectionName, CollectionNameError, Collections},
+    glob::Glob,
+    profile::ProfileName,
+    runner::{HostLimiter, Runner},
     vcs::{Vcs, VcsParseError},
     Context,
 };
 use structopt::StructOpt;
 
-use crate::{clone::CloneOpt, collection::CollectionOpt, list::ListOpt, refresh::RefreshOpt};
+use crate::{
+    backup::BackupOpt, branches::BranchesOpt, cache::CacheOpt, checkout::CheckoutOpt,
+    clone::CloneOpt, collection::CollectionOpt, config::ConfigOpt, copy::CopyOpt,
+    doctor::DoctorOpt, du::DuOpt, env_info::EnvOpt, exec::ExecOpt, export::ExportOpt,
+    fetch::FetchOpt, grep::GrepOpt, health::HealthOpt, import::ImportOpt, linkfarm::LinkfarmOpt,
+    list::ListOpt, locate::LocateOpt, logging::LogFormat, new::NewOpt, outdated::OutdatedOpt,
+    output::OutputFormat, path::PathOpt, plugin, progress::ProgressFormat, reclone::RecloneOpt,
+    refresh::RefreshOpt, remote::RemoteOpt, repo::RepoOpt, rm::RmOpt, scratch::TryOpt,
+    shell_init::ShellInitOpt, snapshot::SnapshotOpt, stats::StatsOpt, status::StatusOpt,
+    submodules::SubmodulesOpt, tag::TagOpt, tmux::TmuxOpt, trash::TrashOpt, verify::VerifyOpt,
+    which::WhichOpt, workspace::WorkspaceOpt, worktree::WorktreeOpt,
+};
 
 /// CLI options.
 #[derive(Debug, Clone, StructOpt)]
 #[non_exhaustive]
 pub struct Opt {
+    /// Global options.
+    #[structopt(flatten)]
+    global: GlobalOpt,
     /// Subcommand.
     #[structopt(subcommand)]
     subcommand: Subcommand,
 }
 
 impl Opt {
+    /// Returns the verbosity.
+    #[inline]
+    #[must_use]
+    pub fn verbosity(&self) -> Verbosity {
+        self.global.verbosity()
+    }
+
+    /// Returns the log format.
+    #[inline]
+    #[must_use]
+    pub(crate) fn log_format(&self) -> LogFormat {
+        self.global.log_format
+    }
+
+    /// Returns the output format.
+    #[inline]
+    #[must_use]
+    pub(crate) fn output(&self) -> OutputFormat {
+        self.global.output
+    }
+
+    /// Returns the progress format.
+    #[inline]
+    #[must_use]
+    pub(crate) fn progress(&self) -> ProgressFormat {
+        self.global.progress
+    }
+
+    /// Returns whether to print the breakdown of time spent.
+    #[inline]
+    #[must_use]
+    pub(crate) fn timings(&self) -> bool {
+        self.global.timings
+    }
+
+    /// Returns whether to cache credentials entered at prompts.
+    #[cfg(feature = "prompt")]
+    #[inline]
+    #[must_use]
+    pub(crate) fn cache_credentials(&self) -> bool {
+        self.global.cache_credentials
+    }
+
+    /// Creates a context for the options.
+    pub fn context(&self) -> anyhow::Result<Context> {
+        self.global.context()
+    }
+
     /// Runs the actual operation.
     pub fn run(&self, context: &mut Context) -> anyhow::Result<()> {
         match &self.subcommand {
+            Subcommand::Backup(opt) => opt.run(context, &self.global),
+            Subcommand::Branches(opt) => opt.run(context, &self.global),
+            Subcommand::Cache(opt) => opt.run(context, &self.global),
+            Subcommand::Checkout(opt) => opt.run(context),
             Subcommand::Clone(opt) => opt.run(context),
-            Subcommand::Collection(opt) => opt.run(context),
-            Subcommand::List(opt) => opt.run(context),
-            Subcommand::Refresh(opt) => opt.run(context),
+            Subcommand::Collection(opt) => opt.run(context, &self.global),
+            Subcommand::Config(opt) => opt.run(context, &self.global),
+            Subcommand::Copy(opt) => opt.run(context),
+            Subcommand::Doctor(opt) => opt.run(context),
+            Subcommand::Du(opt) => opt.run(context, &self.global),
+            Subcommand::Env(opt) => opt.run(context, &self.global),
+            Subcommand::Exec(opt) => opt.run(context, &self.global),
+            Subcommand::Export(opt) => opt.run(context),
+            Subcommand::Fetch(opt) => opt.run(context, &self.global),
+            #[cfg(feature = "forge")]
+            Subcommand::Forge(opt) => opt.run(context, &self.global),
+            Subcommand::Grep(opt) => opt.run(context, &self.global),
+            Subcommand::Health(opt) => opt.run(context, &self.global),
+            Subcommand::Import(opt) => opt.run(context),
+            Subcommand::Linkfarm(opt) => opt.run(context),
+            Subcommand::List(opt) => opt.run(context, &self.global),
+            Subcommand::Locate(opt) => opt.run(context, &self.global),
+            Subcommand::New(opt) => opt.run(context),
+            Subcommand::Outdated(opt) => opt.run(context, &self.global),
+            Subcommand::Path(opt) => opt.run(context),
+            #[cfg(feature = "pick")]
+            Subcommand::Pick(opt) => opt.run(context),
+            Subcommand::Reclone(opt) => opt.run(context),
+            Subcommand::Refresh(opt) => opt.run(context, &self.global),
+            Subcommand::Remote(opt) => opt.run(context, &self.global),
+            Subcommand::Repo(opt) => opt.run(context, &self.global),
+            Subcommand::Rm(opt) => opt.run(context),
+            #[cfg(feature = "serve")]
+            Subcommand::Serve(opt) => opt.run(context),
+            Subcommand::ShellInit(opt) => opt.run(),
+            Subcommand::Snapshot(opt) => opt.run(context),
+            Subcommand::Stats(opt) => opt.run(context, &self.global),
+            Subcommand::Status(opt) => opt.run(context, &self.global),
+            Subcommand::Submodules(opt) => opt.run(context, &self.global),
+            Subcommand::Tag(opt) => opt.run(context, &self.global),
+            Subcommand::Tmux(opt) => opt.run(context),
+            Subcommand::Trash(opt) => opt.run(context, &self.global),
+            Subcommand::Try(opt) => opt.run(context),
+            Subcommand::Verify(opt) => opt.run(context, &self.global),
+            Subcommand::Which(opt) => opt.run(context, &self.global),
+            Subcommand::Workspace(opt) => opt.run(context),
+            Subcommand::Worktree(opt) => opt.run(context, &self.global),
+            Subcommand::External(args) => plugin::run(context, args),
+        }
+    }
+}
+
+/// Options common to all subcommands.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub(crate) struct GlobalOpt {
+    /// Output format.
+    ///
+    /// Note that some subcommands only support `text` output.
+    #[structopt(
+        long,
+        global = true,
+        possible_values = OutputFormat::possible_opt_values(),
+        default_value = "text"
+    )]
+    pub(crate) output: OutputFormat,
+    /// Maximum number of parallel jobs for per-repository operations.
+    ///
+    /// If not specified, `jobs` in the main config is used (default: 1).
+    #[structopt(long, short, global = true)]
+    jobs: Option<NonZeroUsize>,
+    /// Maximum number of parallel network operations per host.
+    ///
+    /// This limits `--jobs` for bulk network operations (such as `backup` and
+    /// `fetch`) to avoid tripping rate limits of forges.
+    /// If not specified, `jobs-per-host` in the main config is used (default:
+    /// unlimited).
+    #[structopt(long, global = true, value_name = "jobs")]
+    jobs_per_host: Option<NonZeroUsize>,
+    /// Shows more messages.
+    ///
+    /// This can be specified multiple times to show even more messages.
+    #[structopt(long, short, global = true, parse(from_occurrences))]
+    verbose: u8,
+    /// Shows fewer messages.
+    ///
+    /// `-q` hides warnings, and `-qq` hides errors as well.
+    /// This cancels `--verbose` given the same number of times.
+    #[structopt(long, short, global = true, parse(from_occurrences))]
+    quiet: u8,
+    /// Log format.
+    ///
+    /// `json` emits a JSON object per line with `timestamp`, `level`,
+    /// `target`, and `message` fields, and `collection` and `repo` fields for
+    /// messages about a repository being processed.
+    #[structopt(
+        long,
+        global = true,
+        env = "MAGRO_LOG_FORMAT",
+        possible_values = LogFormat::possible_opt_values(),
+        default_value = "text"
+    )]
+    pub(crate) log_format: LogFormat,
+    /// Prints the breakdown of time spent to stderr at exit.
+    ///
+    /// Time for config and cache loading, repository discovery for each
+    /// collection, cache serialization, and network operations is reported.
+    /// Durations of parallel operations are summed up, so they can exceed
+    /// the total time.
+    #[structopt(long, global = true)]
+    timings: bool,
+    /// Progress format of `clone`, `refresh`, and `fetch`.
+    ///
+    /// `json` emits a JSON object per line to stderr for each event, with
+    /// `event` (`started`, `percent`, `finished`, or `failed`), `operation`,
+    /// `collection`, and `repo` fields, and `percent` or `error` fields
+    /// depending on the event.
+    /// `percent` events are emitted only when built with libgit2.
+    #[structopt(
+        long,
+        global = true,
+        env = "MAGRO_PROGRESS",
+        possible_values = ProgressFormat::possible_opt_values(),
+        default_value = "none"
+    )]
+    progress: ProgressFormat,
+    /// Config directory to use instead of the default.
+    ///
+    /// Set `MAGRO_USE_XDG=1` to make the default directories follow the XDG
+    /// base directories on every platform.
+    #[structopt(long, global = true, env = "MAGRO_CONFIG_DIR", parse(from_os_str))]
+    config_dir: Option<PathBuf>,
+    /// Cache directory to use instead of the default.
+    #[structopt(long, global = true, env = "MAGRO_CACHE_DIR", parse(from_os_str))]
+    cache_dir: Option<PathBuf>,
+    /// Cache namespace to use instead of `cache-namespace` in the main config.
+    ///
+    /// Each namespace has its own cache file (`cache-<NAMESPACE>.toml`) in
+    /// the cache directory of the profile, so that independent caches can be
+    /// maintained over the same collections (for example, `--cache-namespace
+    /// ci` for CI pipelines).
+    /// Empty value means the default cache file.
+    #[structopt(long, global = true, env = "MAGRO_CACHE_NAMESPACE")]
+    cache_namespace: Option<String>,
+    /// Never writes the config and the cache.
+    ///
+    /// Operations which need to write them fail.
+    /// This can be enabled by default by `read-only = true` in the main config.
+    #[structopt(long, global = true)]
+    read_only: bool,
+    /// Profile to use.
+    ///
+    /// Each profile has its own collections, tags, and cache, stored in
+    /// `profiles/<PROFILE>/` under the config and cache directories.
+    /// The main config is shared among profiles.
+    /// Empty value means the default profile.
+    #[structopt(long, global = true, env = "MAGRO_PROFILE")]
+    profile: Option<String>,
+    /// Reuses credentials entered at prompts for remotes on the same host.
+    ///
+    /// Credentials are kept only in memory until the command exits.
+    #[cfg(feature = "prompt")]
+    #[structopt(long, global = true)]
+    cache_credentials: bool,
+}
+
+impl GlobalOpt {
+    /// Creates a context for the options.
+    pub(crate) fn context(&self) -> anyhow::Result<Context> {
+        let profile = self
+            .profile
+            .as_deref()
+            .filter(|s| !s.is_empty())
+            .map(ProfileName::try_from)
+            .transpose()?;
+        let mut context =
+            Context::with_profile(self.config_dir.clone(), self.cache_dir.clone(), profile)?;
+        if let Some(namespace) = &self.cache_namespace {
+            let namespace = Some(namespace.as_str())
+                .filter(|s| !s.is_empty())
+                .map(CacheNamespace::try_from)
+                .transpose()?;
+            context.set_cache_namespace(namespace);
+        }
+        if self.read_only {
+            context.set_read_only(true);
+        }
+        Ok(context)
+    }
+
+    /// Returns the number of parallel jobs specified by the command line option.
+    #[inline]
+    #[must_use]
+    pub(crate) fn jobs(&self) -> Option<NonZeroUsize> {
+        self.jobs
+    }
+
+    /// Returns the maximum number of concurrent network operations per host
+    /// specified by the command line option.
+    #[inline]
+    #[must_use]
+    pub(crate) fn jobs_per_host(&self) -> Option<NonZeroUsize> {
+        self.jobs_per_host
+    }
+
+    /// Returns the runner for per-repository operations.
+    pub(crate) fn runner(&self, context: &Context) -> Runner {
+        self.jobs
+            .or_else(|| context.config().main().jobs())
+            .map_or_else(Runner::sequential, Runner::new)
+    }
+
+    /// Returns the limiter of network operations per host, if limited.
+    pub(crate) fn host_limiter(&self, context: &Context) -> Option<HostLimiter> {
+        self.jobs_per_host
+            .or_else(|| context.config().main().jobs_per_host())
+            .map(HostLimiter::new)
+    }
+
+    /// Returns the verbosity.
+    #[must_use]
+    pub(crate) fn verbosity(&self) -> Verbosity {
+        match i16::from(self.verbose) - i16::from(self.quiet) {
+            i16::MIN..=-2 => Verbosity::Silent,
+            -1 => Verbosity::Quiet,
+            0 => Verbosity::Normal,
+            1 => Verbosity::Verbose,
+            2 => Verbosity::Debug,
+            _ => Verbosity::Trace,
         }
     }
 }
 
+/// Verbosity of messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// No messages.
+    Silent,
+    /// Only errors.
+    Quiet,
+    /// Default.
+    Normal,
+    /// Informative messages.
+    Verbose,
+    /// Debug messages.
+    Debug,
+    /// Trace messages.
+    Trace,
+}
+
+impl Verbosity {
+    /// Returns the log level filter.
+    ///
+    /// Returns `None` for `Normal`, where the default filter should be used.
+    #[must_use]
+    pub fn log_level_filter(self) -> Option<log::LevelFilter> {
+        match self {
+            Self::Silent => Some(log::LevelFilter::Off),
+            Self::Quiet => Some(log::LevelFilter::Error),
+            Self::Normal => None,
+            Self::Verbose => Some(log::LevelFilter::Info),
+            Self::Debug => Some(log::LevelFilter::Debug),
+            Self::Trace => Some(log::LevelFilter::Trace),
+        }
+    }
+
+    /// Returns true if informative messages should be shown.
+    #[inline]
+    #[must_use]
+    pub(crate) fn is_verbose(self) -> bool {
+        self >= Self::Verbose
+    }
+}
+
 /// Subcommand.
 #[derive(Debug, Clone, StructOpt)]
 pub enum Subcommand {
+    /// Push repositories to backup remotes.
+    ///
+    /// Backup remote URL is configured by `url-template` in `[backup]` section
+    /// of the main config.
+    Backup(BackupOpt),
+    /// List local branches across repositories.
+    ///
+    /// Filters such as `--no-upstream`, `--merged`, and `--stale` help finding
+    /// forgotten topic branches.
+    /// The branch checked out at HEAD is marked with `*`.
+    Branches(BranchesOpt),
+    /// Manage the cache.
+    ///
+    /// `cache warm` resolves working directories and branches of repositories
+    /// beforehand, so that `list --workdir` and `locate` need not open the
+    /// repositories.
+    Cache(CacheOpt),
+    /// Check out a bare mirror repository as a working tree.
+    ///
+    /// The working tree is created by a local clone of the mirror, and
+    /// registered in the cache of the target collection.
+    /// The remote `mirror` pointing to the mirror is added to the working
+    /// tree, and the remotes of the mirror are copied.
+    /// The path of the created working tree is printed.
+    Checkout(CheckoutOpt),
     /// Clone repository.
     Clone(CloneOpt),
     /// Modify collections.
     Collection(CollectionOpt),
+    /// Show the config.
+    ///
+    /// `config show --effective` shows the fully resolved config with the
+    /// source of each value, which is useful when a setting is not taking
+    /// effect.
+    Config(ConfigOpt),
+    /// Copy a repository into another collection by a local clone.
+    ///
+    /// Objects are hardlinked where possible, and local branches, remotes,
+    /// and upstreams of branches are copied from the source.
+    /// The copy is registered in the cache of the target collection.
+    Copy(CopyOpt),
+    /// Check configuration problems.
+    ///
+    /// Currently this checks whether the default collection exists and
+    /// whether collection directories overlap.
+    Doctor(DoctorOpt),
+    /// Show disk usage of repositories.
+    ///
+    /// Results are cached, and measured again only when `--refresh` is
+    /// specified or no cached results are available.
+    Du(DuOpt),
+    /// Show the environment and paths used by the program.
+    ///
+    /// The version, enabled features, home directory, profile, config and
+    /// cache paths, and the default collection are shown, which is useful for
+    /// scripts and bug reports.
+    /// `--shell` prints them as `MAGRO_<KEY>=<value>` lines.
+    Env(EnvOpt),
+    /// Run a command for each repository.
+    ///
+    /// Variables such as `{path}` and `{name}` in the arguments are expanded
+    /// for each repository (for example, `exec -- cp ~/config {path}/.envrc`).
+    /// The command runs in the working directory of each repository (unless
+    /// `--no-chdir` is specified), and the output is printed for each
+    /// repository after all commands finish.
+    Exec(ExecOpt),
+    /// Export repository lists as configuration of other multi-repo tools.
+    Export(ExportOpt),
+    /// Fetch remotes of repositories.
+    ///
+    /// All remotes of each repository are fetched (as `git fetch --all`).
+    /// Fetches are run in parallel (see `--jobs`), and the number of
+    /// concurrent fetches from the same host can be limited by
+    /// `--jobs-per-host`.
+    /// The result of each repository is shown at the end.
+    /// An interrupted run is resumed by running the same command again.
+    Fetch(FetchOpt),
+    /// Operate on repositories hosted on software forges.
+    #[cfg(feature = "forge")]
+    Forge(crate::forge::ForgeOpt),
+    /// Search file contents across repositories.
+    ///
+    /// `git grep` is run in the working directory of each repository, and the
+    /// matches are printed with the collection and repository names.
+    /// Bare repositories are skipped.
+    Grep(GrepOpt),
+    /// Report repositories with states which can lose work.
+    ///
+    /// Uncommitted changes, unpushed commits, stashes, branches without
+    /// upstream, tags on commits not in remote-tracking branches, detached
+    /// HEAD, and repositories without remotes are reported, most severe first.
+    /// Remotes are not accessed (unless `--check-remote-tags` is specified),
+    /// so run `git fetch` beforehand for accurate results.
+    Health(HealthOpt),
+    /// Mirror repositories listed in a manifest into a collection.
+    ///
+    /// The manifest is a list of clone URLs (one per line), ghorg output, or
+    /// repository names (such as `gitolite info` output) with `--base-url`.
+    /// Missing repositories are cloned, and repositories in the collection but
+    /// not in the manifest are reported as extraneous (they are not removed).
+    Import(ImportOpt),
+    /// Generate a flat directory of symbolic links to repositories.
+    ///
+    /// A link to the working directory (or the repository directory for bare
+    /// repositories) is created for each selected repository, named after the
+    /// last component of the repository name (the whole name is used if
+    /// ambiguous).
+    /// Links of repositories no longer selected are removed, so running this
+    /// again updates the directory from the cache.
+    Linkfarm(LinkfarmOpt),
     /// List repositories.
     ///
     /// Note that this lists the cached repositories.
     /// To make the cache up to date, use `refresh` subcommand.
     List(ListOpt),
+    /// Show the repository containing the path, using the cache.
+    ///
+    /// This is fast enough to be called from shell prompts (for example,
+    /// `magro locate --cwd`).
+    /// Exits with status 1 if the path is not in any cached repository.
+    Locate(LocateOpt),
+    /// Create a repository from a template repository.
+    ///
+    /// The template is cloned into the collection, its history is removed
+    /// (unless `--keep-history` is specified), and the placeholders in file
+    /// contents and file names are replaced.
+    New(NewOpt),
+    /// Show branches behind, ahead of, or diverged from their upstreams.
+    ///
+    /// Local branches are compared with the remote-tracking branches fetched
+    /// last time, or with the latest ones if `--fetch` is specified.
+    /// Branches without upstreams are not shown.
+    Outdated(OutdatedOpt),
+    /// Show the path to the repository matching the query.
+    ///
+    /// This prints the working directory if available, or the repository
+    /// directory (for bare repositories).
+    Path(PathOpt),
+    /// Interactively pick a repository and show the path.
+    ///
+    /// The picker is shown on stderr, and the path to the picked repository
+    /// is printed to stdout (for example, `cd "$(magro pick)"`).
+    #[cfg(feature = "pick")]
+    Pick(crate::pick::PickOpt),
+    /// Clone missing repositories again from the recorded source URIs.
+    ///
+    /// Repositories are cloned into the original paths in the collections.
+    /// Source URIs are recorded in the cache by `clone` subcommand.
+    Reclone(RecloneOpt),
     /// Refresh collections.
     Refresh(RefreshOpt),
+    /// Modify remotes of repositories.
+    Remote(RemoteOpt),
+    /// Show details of repositories.
+    ///
+    /// `repo info` shows everything known about a repository, such as the
+    /// current branch, remotes, tags, and cached metadata.
+    Repo(RepoOpt),
+    /// Remove repositories.
+    ///
+    /// Repositories which can lose work are not removed unless `--force` is
+    /// specified.
+    /// If `enabled` in `[trash]` section of the main config is true,
+    /// repositories are moved to the trash and can be restored by
+    /// `trash restore`.
+    Rm(RmOpt),
+    /// Serve read-only JSON API over HTTP.
+    ///
+    /// Endpoints are `GET /repos`, `GET /search?q=<query>`, `GET /collections`,
+    /// `GET /collections/<name>`, and `POST /refresh`.
+    /// `/repos` and `/search` accept `collection`, `vcs`, and `lang` parameters.
+    #[cfg(feature = "serve")]
+    Serve(crate::serve::ServeOpt),
+    /// Print shell integration script.
+    ///
+    /// This defines a function to change the directory to a repository (`mcd`
+    /// by default), and completions.
+    /// Add `eval "$(magro shell-init bash)"` to `~/.bashrc` (or `~/.zshrc` with
+    /// `zsh`), or `magro shell-init fish | source` to `config.fish`.
+    ShellInit(ShellInitOpt),
+    /// Record and restore states of repositories.
+    ///
+    /// Snapshots record the remote URL and the HEAD commit of each repository,
+    /// and are saved to `snapshots/<name>.toml` in the config directory.
+    Snapshot(SnapshotOpt),
+    /// Show statistics of repositories.
+    ///
+    /// Repositories are counted for each collection, VCS, and language.
+    Stats(StatsOpt),
+    /// Show whether working trees are clean, dirty, or have untracked files.
+    ///
+    /// Ignored files are not counted, and bare repositories are skipped.
+    Status(StatusOpt),
+    /// Show submodules of repositories.
+    ///
+    /// Submodules which are uninitialized, checked out at commits different
+    /// from the ones recorded in the superprojects, or modified are flagged.
+    Submodules(SubmodulesOpt),
+    /// Manage tags of repositories.
+    ///
+    /// Tags are labels such as `wip` and `fork`, and can be used to filter
+    /// repositories by `list --tag`.
+    Tag(TagOpt),
+    /// Create a tmux session with a window for each repository.
+    ///
+    /// Windows are started at the working directories of repositories.
+    Tmux(TmuxOpt),
+    /// Manage repositories removed into the trash.
+    ///
+    /// Repositories in the trash are deleted permanently by `trash empty`, or
+    /// automatically after the retention period (`retention-days` in `[trash]`
+    /// section of the main config, 30 days by default).
+    Trash(TrashOpt),
+    /// Clone a repository into the scratch collection temporarily.
+    ///
+    /// The clone expires after `--ttl-days` days, and `try --clean` removes
+    /// expired clones without local modifications.
+    /// The scratch collection is `scratch` by default, and can be changed by
+    /// `collection` in `[scratch]` section of the main config.
+    Try(TryOpt),
+    /// Check integrity of repositories.
+    ///
+    /// All objects in the repositories are read and verified, and objects
+    /// reachable from references are checked to exist (similar to `git fsck`).
+    /// Exits with failure if any problems are found.
+    Verify(VerifyOpt),
+    /// Show the collection and the repository owning the path.
+    ///
+    /// The path defaults to the current directory, and is looked up using
+    /// the cache.
+    /// Unlike `locate`, the collection is shown even if the path is not
+    /// inside any repository (the repository columns are omitted then).
+    /// Exits with failure if the path is not inside any collection.
+    Which(WhichOpt),
+    /// Generate an editor workspace containing working directories of repositories.
+    Workspace(WorkspaceOpt),
+    /// Manage linked worktrees of repositories.
+    ///
+    /// Worktrees are created under the directory configured by `dir` in
+    /// `[worktree]` section of the main config (`worktrees` in the home
+    /// directory by default), and registered in the cache.
+    Worktree(WorktreeOpt),
+    /// Run `magro-<name>` found in `PATH` (external subcommand).
+    #[structopt(external_subcommand)]
+    External(Vec<OsString>),
 }
 
-/// Space- or comma-separated collection names.
+/// Space- or comma-separated collection names or glob patterns of them.
+///
+/// Items containing `*` or `?` are glob patterns (such as `work-*`).
 #[derive(Debug, Clone)]
-pub(crate) struct CollectionNameList(Vec<CollectionName>);
+pub(crate) struct CollectionNameList(Vec<CollectionSelector>);
 
-impl AsRef<[CollectionName]> for CollectionNameList {
-    #[inline]
-    fn as_ref(&self) -> &[CollectionName] {
-        &self.0
+impl CollectionNameList {
+    /// Expands the names in the lists against the configured collections.
+    ///
+    /// Names are returned as is even if the collections do not exist, and
+    /// glob patterns are replaced by the names of the matching collections.
+    /// Duplicate names are removed.
+    ///
+    /// Fails if a pattern matches no collections.
+    pub(crate) fn expand(
+        lists: &[Self],
+        collections: &Collections,
+    ) -> anyhow::Result<Vec<CollectionName>> {
+        let mut names: Vec<CollectionName> = Vec::new();
+        for selector in lists.iter().flat_map(|list| &list.0) {
+            match selector {
+                CollectionSelector::Name(name) => names.push(name.clone()),
+                CollectionSelector::Pattern(pattern) => {
+                    let len = names.len();
+                    names.extend(collections.matching(pattern).map(|c| c.name().clone()));
+                    if names.len() == len {
+                        bail!("No collections matched the pattern `{}`", pattern);
+                    }
+                }
+            }
+        }
+        let mut seen = HashSet::new();
+        names.retain(|name| seen.insert(name.clone()));
+
+        Ok(names)
     }
 }
 
@@ -66,33 +662,24 @@ impl str::FromStr for CollectionNameList {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         s.split(|c: char| c.is_ascii_whitespace() || c == ',')
             .filter(|s| !s.is_empty())
-            .map(CollectionName::try_from)
+            .map(|s| match Glob::new(s) {
+                Ok(pattern) if s.contains(|c| c == '*' || c == '?') => {
+                    Ok(CollectionSelector::Pattern(pattern))
+                }
+                _ => CollectionName::try_from(s).map(CollectionSelector::Name),
+            })
             .collect::<Result<Vec<_>, _>>()
             .map(Self)
     }
 }
 
-impl<'a> IntoIterator for &'a CollectionNameList {
-    type IntoIter = CollectionNameListIter<'a>;
-    type Item = &'a CollectionName;
-
-    #[inline]
-    fn into_iter(self) -> Self::IntoIter {
-        CollectionNameListIter(self.0.iter())
-    }
-}
-
-/// Iterator over `CollectionNameList`.
-#[derive(Debug)]
-pub(crate) struct CollectionNameListIter<'a>(std::slice::Iter<'a, CollectionName>);
-
-impl<'a> Iterator for CollectionNameListIter<'a> {
-    type Item = &'a CollectionName;
-
-    #[inline]
-    fn next(&mut self) -> Option<Self::Item> {
-        self.0.next()
-    }
+/// Collection name or glob pattern of collection names.
+#[derive(Debug, Clone)]
+enum CollectionSelector {
+    /// Collection name.
+    Name(CollectionName),
+    /// Glob pattern of collection names.
+    Pattern(Glob),
 }
 
 /// Space- or comma-separated VCS types.
@@ -141,6 +728,128 @@ impl<'a> Iterator for VcsListIter<'a> {
     }
 }
 
+/// Age (duration in the past), such as `30d` or `2w`.
+///
+/// Supported units are `h` (hours), `d` (days), `w` (weeks), `mo` (30 days),
+/// and `y` (365 days). A number without units is treated as days.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Age(u64);
+
+impl Age {
+    /// Returns the age in seconds.
+    #[inline]
+    #[must_use]
+    pub(crate) fn as_secs(&self) -> u64 {
+        self.0
+    }
+
+    /// Returns true if the given time (seconds since the UNIX epoch) is older
+    /// than the age.
+    #[must_use]
+    pub(crate) fn is_older(&self, time: u64) -> bool {
+        time.saturating_add(self.as_secs()) < magro::metadata::unix_now()
+    }
+}
+
+impl str::FromStr for Age {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        /// Seconds per hour.
+        const HOUR: u64 = 60 * 60;
+        /// Seconds per day.
+        const DAY: u64 = 24 * HOUR;
+
+        let digits_end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (num, unit) = s.split_at(digits_end);
+        let num = num
+            .parse::<u64>()
+            .map_err(|_| anyhow!("Invalid age {:?} (expected such as `30d`)", s))?;
+        let unit_secs = match unit {
+            "h" => HOUR,
+            "" | "d" => DAY,
+            "w" => 7 * DAY,
+            "mo" => 30 * DAY,
+            "y" => 365 * DAY,
+            _ => return Err(anyhow!("Unknown unit {:?} in the age {:?}", unit, s)),
+        };
+
+        Ok(Self(num.saturating_mul(unit_secs)))
+    }
+}
+
+/// Point in time, given as a date (such as `2024-01-31`) or an age (such as `90d`).
+///
+/// Dates are interpreted as the beginning of the day in UTC.
+/// Ages are interpreted as the time before now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Since(u64);
+
+impl Since {
+    /// Returns true if the given time (seconds since the UNIX epoch) is at or
+    /// after this time.
+    #[inline]
+    #[must_use]
+    pub(crate) fn includes(&self, time: u64) -> bool {
+        time >= self.0
+    }
+}
+
+impl str::FromStr for Since {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.contains('-') {
+            let age = s.parse::<Age>()?;
+            return Ok(Self(
+                magro::metadata::unix_now().saturating_sub(age.as_secs()),
+            ));
+        }
+
+        let invalid = || anyhow!("Invalid date {:?} (expected such as `2024-01-31`)", s);
+        let mut parts = s.splitn(3, '-');
+        let mut next_num = |len: usize| {
+            parts
+                .next()
+                .filter(|part| part.len() == len && part.bytes().all(|b| b.is_ascii_digit()))
+                .and_then(|part| part.parse::<u32>().ok())
+                .ok_or_else(invalid)
+        };
+        let (year, month, day) = (next_num(4)?, next_num(2)?, next_num(2)?);
+        let days = days_from_civil(year, month, day).ok_or_else(invalid)?;
+
+        Ok(Self(days * 24 * 60 * 60))
+    }
+}
+
+/// Returns the number of days from 1970-01-01 to the given date.
+///
+/// Returns `None` if the date is invalid or before 1970-01-01.
+fn days_from_civil(year: u32, month: u32, day: u32) -> Option<u64> {
+    let is_leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    let month_days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap => 29,
+        2 => 28,
+        _ => return None,
+    };
+    if year < 1970 || day == 0 || day > month_days {
+        return None;
+    }
+
+    // See <http://howardhinnant.github.io/date_algorithms.html#days_from_civil>.
+    let (year, month) = (u64::from(year), u64::from(month));
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let month_from_march = (month + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + u64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    Some(era * 146_097 + day_of_era - 719_468)
+}
+
 /// Optional boolean.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) enum OptionBool {
@@ -191,3 +900,34 @@ impl fmt::Display for OptionBool {
         f.write_str(self.as_str())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_age() {
+        let day = 24 * 60 * 60;
+        assert_eq!("3".parse::<Age>().unwrap().as_secs(), 3 * day);
+        assert_eq!("12h".parse::<Age>().unwrap().as_secs(), 12 * 60 * 60);
+        assert_eq!("2w".parse::<Age>().unwrap().as_secs(), 14 * day);
+        assert_eq!("1mo".parse::<Age>().unwrap().as_secs(), 30 * day);
+        assert_eq!("1y".parse::<Age>().unwrap().as_secs(), 365 * day);
+        assert!("d".parse::<Age>().is_err());
+        assert!("3m".parse::<Age>().is_err());
+    }
+
+    #[test]
+    fn parse_since() {
+        let day = 24 * 60 * 60;
+        assert_eq!("1970-01-01".parse::<Since>().unwrap(), Since(0));
+        assert_eq!("2000-03-01".parse::<Since>().unwrap(), Since(11_017 * day));
+        assert_eq!("2024-02-29".parse::<Since>().unwrap(), Since(19_782 * day));
+        assert!("2023-02-29".parse::<Since>().is_err());
+        assert!("2024-1-31".parse::<Since>().is_err());
+        assert!("1969-12-31".parse::<Since>().is_err());
+        let since = "2d".parse::<Since>().unwrap();
+        assert!(since.includes(magro::metadata::unix_now() - day));
+        assert!(!since.includes(magro::metadata::unix_now() - 3 * day));
+    }
+}
@@ -2,15 +2,25 @@
 
 use std::{
     borrow::Cow,
-    iter,
+    collections::BTreeMap,
     path::{Path, PathBuf},
 };
 
 use anyhow::{bail, Context as _};
-use magro::{cache::RepoCacheEntry, collection::CollectionName, vcs::Vcs, Context};
+use magro::{
+    cache::{Provenance, RepoCacheEntry},
+    collection::CollectionName,
+    hooks::{self, HookEnv, HookEvent},
+    permissions,
+    vcs::{self, Vcs},
+    view, Context,
+};
 use structopt::StructOpt;
 
-use crate::cli_opt::OptionBool;
+use crate::{
+    cli_opt::OptionBool,
+    progress::{Event, EventKind},
+};
 
 /// Options for `clone` subcommand.
 #[derive(Debug, Clone, StructOpt)]
@@ -39,114 +49,309 @@ pub struct CloneOpt {
         default_value = "auto",
     )]
     bare: OptionBool,
+    /// Checks out only the files in the directories (sparse checkout).
+    ///
+    /// Directories are relative to the repository root (for example,
+    /// `--sparse docs crates/foo`). Files directly in the root directory are
+    /// also checked out, same as `git sparse-checkout set` in cone mode.
+    #[structopt(long, min_values = 1)]
+    sparse: Vec<String>,
 }
 
 impl CloneOpt {
     /// Runs the actual operation.
     pub fn run(&self, context: &mut Context) -> anyhow::Result<()> {
         log::trace!(
-            "clone uri={:?}, collection={:?}, dest={:?}, vcs={:?}, bare={}",
+            "clone uri={:?}, collection={:?}, dest={:?}, vcs={:?}, bare={}, sparse={:?}",
             self.uri,
             self.collection,
             self.destination,
             self.vcs,
-            self.bare
+            self.bare,
+            self.sparse
         );
 
-        clone_repo(
+        if !self.sparse.is_empty() && self.bare == OptionBool::Yes {
+            bail!("`--sparse` cannot be used for bare repositories");
+        }
+
+        ClonePlan::new(
             context,
             &self.uri,
             self.collection.as_ref(),
             self.destination.as_deref(),
             self.vcs,
             self.bare,
-        )
+        )?
+        .sparse(self.sparse.clone())
+        .run(context)
     }
 }
 
-/// Clones the repository.
-fn clone_repo(
-    context: &mut Context,
-    uri: &str,
-    collection_name: Option<&CollectionName>,
-    cliopt_dest: Option<&Path>,
-    vcs_opt: Option<Vcs>,
-    bare: OptionBool,
-) -> anyhow::Result<()> {
-    let collection = if let Some(name) = collection_name {
-        context
-            .config()
-            .collections()
-            .get(name)
-            .with_context(|| format!("Collection `{}` not found", name))?
-    } else if let Some(name) = context.config().default_collection() {
+/// Resolved parameters for a clone.
+#[derive(Debug, Clone)]
+pub(crate) struct ClonePlan {
+    /// URI to clone from.
+    ///
+    /// This is converted to the preferred protocol of the collection.
+    uri: String,
+    /// Target collection name.
+    collection_name: CollectionName,
+    /// Absolute path of the collection directory.
+    collection_dir: PathBuf,
+    /// VCS type.
+    vcs: Vcs,
+    /// Whether to clone a bare repository.
+    bare: bool,
+    /// Absolute path of the destination directory.
+    absdest: PathBuf,
+    /// Path of the repository relative to the collection directory.
+    relative_rawdir: PathBuf,
+    /// Directories to check out by sparse checkout.
+    ///
+    /// Empty if all files should be checked out.
+    sparse: Vec<String>,
+    /// Expiry time of the scratch clone in seconds since the UNIX epoch.
+    expires_at: Option<u64>,
+    /// Path of the local repository to clone instead of the URI.
+    local_source: Option<PathBuf>,
+    /// Environment variables of the collection.
+    env: BTreeMap<String, String>,
+}
+
+impl ClonePlan {
+    /// Resolves the target collection and the destination.
+    pub(crate) fn new(
+        context: &Context,
+        uri: &str,
+        collection_name: Option<&CollectionName>,
+        cliopt_dest: Option<&Path>,
+        vcs_opt: Option<Vcs>,
+        bare: OptionBool,
+    ) -> anyhow::Result<Self> {
+        let collection = context.config().target_collection(collection_name)?;
+
+        let uri = match collection
+            .clone_protocol()
+            .and_then(|protocol| protocol.convert_url(uri))
+        {
+            Some(converted) => {
+                log::debug!("Using {:?} instead of {:?}", converted, uri);
+                converted
+            }
+            None => uri.to_owned(),
+        };
+        let uri = uri.as_str();
+
+        let vcs = vcs_opt
+            .or_else(|| suppose_vcs_from_uri(uri))
+            .with_context(|| format!("Failed to get VCS type for URI {:?}", uri))?;
+        log::debug!("Assumed VCS is {}", vcs.name_lower());
+
+        let bare = bare == OptionBool::Yes;
+
+        let collection_base_dir = collection.abspath(context);
+        let reldest = match cliopt_dest {
+            Some(dest) if dest.is_relative() => Cow::Borrowed(dest),
+            Some(dest) => match dest.strip_prefix(&collection_base_dir) {
+                Ok(reldest) => Cow::Borrowed(reldest),
+                Err(_) => bail!(
+                    "destination path ({}) should be relative path \
+                    or inside collection directory ({}), but it was not",
+                    dest.display(),
+                    collection_base_dir.display()
+                ),
+            },
+            // Mercurial, Pijul, and Darcs URIs have no `.git` suffix to be removed.
+            // Jujutsu clones git repositories.
+            None => git_dest_relpath(uri, bare || !matches!(vcs, Vcs::Git | Vcs::Jj))
+                .context("Failed to determine clone destination path")?,
+        };
+        let relative_rawdir = match vcs {
+            Vcs::Git if bare => reldest.to_path_buf(),
+            Vcs::Git => reldest.join(".git"),
+            // Mercurial has no bare repositories.
+            Vcs::Hg => reldest.join(".hg"),
+            // Jujutsu has no bare repositories.
+            Vcs::Jj => reldest.join(".jj"),
+            // Pijul has no bare repositories.
+            Vcs::Pijul => reldest.join(".pijul"),
+            // Darcs has no bare repositories.
+            Vcs::Darcs => reldest.join("_darcs"),
+            vcs => {
+                // This should not happen because `magro-cli` implementation is
+                // devloped at the same time with `magro` backend.
+                unreachable!("Got unknown VCS {}", vcs.name_lower());
+            }
+        };
+        assert!(reldest.is_relative());
+
+        let absdest = collection_base_dir.join(&reldest);
+        log::debug!("Destination directory is {:?}", absdest);
+
+        Ok(Self {
+            uri: uri.to_owned(),
+            collection_name: collection.name().to_owned(),
+            collection_dir: collection_base_dir.into_owned(),
+            vcs,
+            bare,
+            absdest,
+            relative_rawdir,
+            sparse: Vec::new(),
+            expires_at: None,
+            local_source: None,
+            env: collection.env().clone(),
+        })
+    }
+
+    /// Sets the directories to check out by sparse checkout.
+    #[inline]
+    #[must_use]
+    pub(crate) fn sparse(mut self, dirs: Vec<String>) -> Self {
+        self.sparse = dirs;
+        self
+    }
+
+    /// Sets the expiry time of the scratch clone in seconds since the UNIX epoch.
+    #[inline]
+    #[must_use]
+    pub(crate) fn expires_at(mut self, expires_at: u64) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    /// Sets the local repository to clone instead of the URI.
+    ///
+    /// The URI is still recorded in the cache as the source URI.
+    #[inline]
+    #[must_use]
+    pub(crate) fn local_source(mut self, repo_path: PathBuf) -> Self {
+        self.local_source = Some(repo_path);
+        self
+    }
+
+    /// Returns the URI to clone from.
+    #[cfg(feature = "forge")]
+    #[inline]
+    #[must_use]
+    pub(crate) fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// Returns the absolute path of the destination directory.
+    #[inline]
+    #[must_use]
+    pub(crate) fn destination(&self) -> &Path {
+        &self.absdest
+    }
+
+    /// Clones the repository and updates the cache.
+    ///
+    /// `pre-clone` and `post-clone` hooks are also run.
+    pub(crate) fn run(self, context: &mut Context) -> anyhow::Result<()> {
+        let Self {
+            uri,
+            collection_name,
+            collection_dir,
+            vcs,
+            bare,
+            absdest,
+            relative_rawdir,
+            sparse,
+            expires_at,
+            local_source,
+            env,
+        } = self;
         context
-            .config()
-            .collections()
-            .get(name)
-            .with_context(|| format!("Default collection `{}` not found", name))?
-    } else {
-        bail!("No target collection specified");
-    };
+            .ensure_writable()
+            .context("Cannot clone a repository")?;
+
+        let hook_env = HookEnv::new()
+            .with_vars(&env)
+            .with_var("MAGRO_COLLECTION", collection_name.as_str())
+            .with_var("MAGRO_COLLECTION_PATH", &collection_dir)
+            .with_var("MAGRO_REPO_PATH", &absdest)
+            .with_var("MAGRO_REPO_URI", &uri);
+        let pre_clone_env = if collection_dir.is_dir() {
+            hook_env.clone().with_cwd(&collection_dir)
+        } else {
+            hook_env.clone()
+        };
+        hooks::run(context, HookEvent::PreClone, &pre_clone_env)?;
+
+        if let Some(mode) = context.config().main().dir_mode() {
+            log::debug!("Creating {:?} with mode {}", absdest, mode);
+            permissions::create_dir_all(&absdest, Some(mode)).with_context(|| {
+                format!("Failed to create the destination directory {:?}", absdest)
+            })?;
+        }
 
-    let vcs = vcs_opt
-        .or_else(|| suppose_vcs_from_uri(uri))
-        .with_context(|| format!("Failed to get VCS type for URI {:?}", uri))?;
-    log::debug!("Assumed VCS is {}", vcs.name_lower());
-
-    let bare = bare == OptionBool::Yes;
-
-    let collection_base_dir = collection.abspath(context);
-    let (reldest, relative_rawdir) = match vcs {
-        Vcs::Git => {
-            let reldest = match cliopt_dest {
-                Some(dest) if dest.is_relative() => Cow::Borrowed(dest),
-                Some(dest) => match dest.strip_prefix(&collection_base_dir) {
-                    Ok(reldest) => Cow::Borrowed(reldest),
-                    Err(_) => bail!(
-                        "destination path ({}) should be relative path \
-                        or inside collection directory ({}), but it was not",
-                        dest.display(),
-                        collection_base_dir.display()
-                    ),
-                },
-                None => git_dest_relpath(uri, bare)
-                    .context("Failed to determine clone destination path")?,
-            };
-            let rawdir = reldest.join(".git");
-            (reldest, rawdir)
+        let repo_name = view::repo_name(&relative_rawdir)
+            .iter()
+            .map(|c| c.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let event = |kind| Event::new(kind, "clone", &collection_name).repo(&repo_name);
+        event(EventKind::Started).emit();
+        let report_percent = {
+            let (collection_name, repo_name) = (collection_name.clone(), repo_name.clone());
+            move |percent| {
+                Event::new(EventKind::Percent, "clone", &collection_name)
+                    .repo(&repo_name)
+                    .percent(percent)
+                    .emit()
+            }
+        };
+        let cloned = vcs::with_progress(report_percent, || {
+            vcs::with_env(&env, || match &local_source {
+                Some(src) => vcs.clone_local(src, &absdest, bare).with_context(|| {
+                    format!("Failed to copy repository {:?} into {:?}", src, absdest)
+                }),
+                None if sparse.is_empty() => vcs.clone(&uri, &absdest, bare).with_context(|| {
+                    format!("Failed to clone repository {:?} into {:?}", uri, absdest)
+                }),
+                None => vcs.clone_sparse(&uri, &absdest, &sparse).with_context(|| {
+                    format!("Failed to clone repository {:?} into {:?}", uri, absdest)
+                }),
+            })
+        });
+        if let Err(e) = cloned {
+            event(EventKind::Failed).error(&e).emit();
+            return Err(e);
         }
-        vcs => {
-            // This should not happen because `magro-cli` implementation is
-            // devloped at the same time with `magro` backend.
-            unreachable!("Got unknown VCS {}", vcs.name_lower());
+
+        // Update cache.
+        // Metadata of the existing entry (for example, of the
+        // repository recloned by `reclone` subcommand) are kept.
+        let updated = context
+            .update_cache(|cache| {
+                if let Some(repos) = cache.collection_repos_mut(&collection_name) {
+                    let source_uri = Some(uri);
+                    let update = |entry: &mut RepoCacheEntry| {
+                        entry.set_source_uri(source_uri.clone());
+                        entry.set_provenance(Provenance::Cloned);
+                        if expires_at.is_some() {
+                            entry.metadata_mut().set_expires_at(expires_at);
+                        }
+                    };
+                    if !repos.update_entry(&relative_rawdir, update) {
+                        let mut entry = RepoCacheEntry::new(vcs, relative_rawdir);
+                        update(&mut entry);
+                        repos.insert(entry);
+                    }
+                }
+            })
+            .context("Failed to update cache file");
+        if let Err(e) = updated {
+            event(EventKind::Failed).error(&e).emit();
+            return Err(e);
         }
-    };
-    assert!(reldest.is_relative());
-
-    let absdest = collection_base_dir.join(&reldest);
-    log::debug!("Destination directory is {:?}", absdest);
-    let collection_name = collection.name().to_owned();
-
-    vcs.clone(uri, &absdest, bare)
-        .with_context(|| format!("Failed to clone repository {:?} into {:?}", uri, absdest))?;
-
-    // Update cache.
-    let cache = context
-        .get_or_load_cache_mut()
-        .context("Failed to load cache file")?;
-    if let Some(mut repos) = cache.remove_collection_repos_cache(&collection_name) {
-        let entry = RepoCacheEntry::new(vcs, relative_rawdir);
-        // Use `extend_one` once stabilized.
-        // See <https://github.com/rust-lang/rust/issues/72631>.
-        repos.extend(iter::once(entry));
-        cache.cache_collection_repos(collection_name, repos);
-    }
+        event(EventKind::Finished).emit();
 
-    // Save the cache file.
-    context.save_cache().context("Failed to save cache file")?;
+        hooks::run(context, HookEvent::PostClone, &hook_env.with_cwd(absdest))?;
 
-    Ok(())
+        Ok(())
+    }
 }
 
 /// Tries to suppose VCS type for the given URI.
@@ -178,6 +383,15 @@ fn suppose_vcs_from_uri(uri: &str) -> Option<Vcs> {
             if hostname.starts_with("git") {
                 return Some(Vcs::Git);
             }
+            if hostname.starts_with("hg") {
+                return Some(Vcs::Hg);
+            }
+            if hostname == "nest.pijul.com" {
+                return Some(Vcs::Pijul);
+            }
+            if hostname == "hub.darcs.net" {
+                return Some(Vcs::Darcs);
+            }
         }
     }
 
@@ -1,18 +1,32 @@
 //! `collection` subcommand.
 
 use std::{
+    collections::BTreeMap,
+    fs,
     io::{self, Write},
     path::{Path, PathBuf},
 };
 
-use anyhow::{anyhow, bail, Context as _};
+use anyhow::{bail, Context as _};
 use magro::{
+    cache::CollectionReposCache,
     collection::{Collection, CollectionName},
+    hooks::{self, HookEnv, HookEvent},
+    permissions, relocate,
+    view::{CollectionView, RepoView},
     Context,
 };
+use serde::Serialize;
 use structopt::StructOpt;
 
-use crate::refresh::generate_collection_repos_cache;
+use crate::{
+    cli_opt::GlobalOpt,
+    error::CollectionNotFound,
+    output::{write_csv_record, write_json, OutputFormat},
+    refresh::generate_collection_repos_cache,
+    repo::Timestamp,
+    rm::lost_work,
+};
 
 /// Options for `collection` subcommand.
 #[derive(Debug, Clone, StructOpt)]
@@ -25,8 +39,12 @@ pub struct CollectionOpt {
 
 impl CollectionOpt {
     /// Runs the actual operation.
-    pub fn run(&self, context: &mut Context) -> anyhow::Result<()> {
+    pub fn run(&self, context: &mut Context, global: &GlobalOpt) -> anyhow::Result<()> {
         match &self.subcommand {
+            Subcommand::GetDefault {} => {
+                log::trace!("collection get-default");
+                get_default(context, global.output)
+            }
             Subcommand::SetDefault {
                 name,
                 unset: _unset,
@@ -44,38 +62,79 @@ impl CollectionOpt {
                 path,
                 refresh,
                 set_default,
+                allow_nested,
             } => {
-                log::trace!("collection add name={:?} path={:?}", name, path);
-                add_collection(context, name, path, *refresh, *set_default)
+                log::trace!(
+                    "collection add name={:?} path={:?}, allow_nested={}",
+                    name,
+                    path,
+                    allow_nested
+                );
+                add_collection(context, name, path, *refresh, *set_default, *allow_nested)
             }
-            Subcommand::Del {
-                names,
-                allow_remove_nothing,
+            Subcommand::Import {
+                path,
+                name,
+                set_default,
+                allow_nested,
             } => {
                 log::trace!(
-                    "collection del name={:?}, allow_remove_nothing={}",
-                    names,
-                    allow_remove_nothing
+                    "collection import path={:?}, name={:?}, set_default={}, allow_nested={}",
+                    path,
+                    name,
+                    set_default,
+                    allow_nested
                 );
-                unregister_collection(context, names, *allow_remove_nothing)
+                import_collection(context, path, name.as_ref(), *set_default, *allow_nested)
             }
-            Subcommand::Show {
-                collections: names,
-                verbose,
+            Subcommand::Del {
+                names,
+                allow_remove_nothing,
+                purge,
+                force,
             } => {
                 log::trace!(
-                    "collection show collections={:?}, verbose={}",
+                    "collection del name={:?}, allow_remove_nothing={}, purge={}, force={}",
                     names,
-                    verbose
+                    allow_remove_nothing,
+                    purge,
+                    force
                 );
+                if *purge {
+                    purge_collections(context, names, *force)
+                } else {
+                    unregister_collection(context, names, *allow_remove_nothing)
+                }
+            }
+            Subcommand::Show { collections: names } => {
+                log::trace!("collection show collections={:?}", names);
+                let verbose = global.verbosity().is_verbose();
                 let collections = context.config().collections();
                 if names.is_empty() {
-                    show_collections(context, &mut collections.iter().map(Ok), *verbose)
+                    show_collections(
+                        context,
+                        &mut collections.iter().map(Ok),
+                        verbose,
+                        global.output,
+                    )
                 } else {
                     let mut targets = names.iter().map(|name| collections.get(name).ok_or(name));
-                    show_collections(context, &mut targets, *verbose)
+                    show_collections(context, &mut targets, verbose, global.output)
                 }
             }
+            Subcommand::Merge {
+                from,
+                into,
+                move_repos,
+            } => {
+                log::trace!(
+                    "collection merge from={:?}, into={:?}, move_repos={}",
+                    from,
+                    into,
+                    move_repos
+                );
+                merge_collection(context, from, into, *move_repos)
+            }
             Subcommand::Rename { old_name, new_name } => {
                 log::trace!(
                     "collection rename old_name={:?}, new_name={:?}",
@@ -88,9 +147,20 @@ impl CollectionOpt {
                 log::trace!("collection get-path name={:?}", name);
                 get_path(context, name)
             }
-            Subcommand::SetPath { name, path } => {
-                log::trace!("collection set-path name={:?}, path={:?}", name, path);
-                set_path(context, name, path)
+            Subcommand::SetPath {
+                name,
+                path,
+                allow_nested,
+                move_dir,
+            } => {
+                log::trace!(
+                    "collection set-path name={:?}, path={:?}, allow_nested={}, move_dir={}",
+                    name,
+                    path,
+                    allow_nested,
+                    move_dir
+                );
+                set_path(context, name, path, *allow_nested, *move_dir)
             }
         }
     }
@@ -99,6 +169,10 @@ impl CollectionOpt {
 /// Subcommand of `collection`.
 #[derive(Debug, Clone, StructOpt)]
 pub enum Subcommand {
+    /// Shows the default collection.
+    ///
+    /// This fails if the default collection is not set.
+    GetDefault {},
     /// Sets the default collection for some operations.
     SetDefault {
         /// Collection name.
@@ -128,28 +202,95 @@ pub enum Subcommand {
         /// Sets the newly created directory to the default collection.
         #[structopt(long)]
         set_default: bool,
+        /// Allows the directory to overlap with other collections.
+        ///
+        /// Repositories in overlapping collections are cached twice.
+        #[structopt(long)]
+        allow_nested: bool,
+    },
+    /// Registers an existing directory as a new collection, and refreshes it.
+    ///
+    /// This is a shorthand for `collection add --refresh` with the collection
+    /// name derived from the directory name.
+    Import {
+        /// Path to the existing directory.
+        ///
+        /// If the path is relative, it is resolved using the current directory
+        /// as the base.
+        #[structopt(parse(from_os_str))]
+        path: PathBuf,
+        /// Collection name.
+        ///
+        /// If not specified, the name is derived from the directory name.
+        #[structopt(long, parse(try_from_str))]
+        name: Option<CollectionName>,
+        /// Sets the newly created directory to the default collection.
+        #[structopt(long)]
+        set_default: bool,
+        /// Allows the directory to overlap with other collections.
+        ///
+        /// Repositories in overlapping collections are cached twice.
+        #[structopt(long)]
+        allow_nested: bool,
     },
     /// Unregisters a new collection.
     ///
-    /// This just make magro forget about the collection, and never removes files from storage.
+    /// This just make magro forget about the collection, and never removes
+    /// files from storage unless `--purge` is specified.
     Del {
         /// Collection names.
         // Use permissive types. Any invalid collection names won't break consistency of the config.
         #[structopt(required = true, min_values = 1)]
         names: Vec<String>,
         /// Do not emit an error if the collection does not exist.
-        #[structopt(long = "allow-remove-nothing")]
+        #[structopt(long = "allow-remove-nothing", conflicts_with = "purge")]
         allow_remove_nothing: bool,
+        /// Also removes the collection directories and all their contents.
+        ///
+        /// This refuses to remove anything if any repository in the
+        /// collections has uncommitted changes, unpushed commits, stashes,
+        /// branches without upstream, tags not pushed to any remote, or no
+        /// remotes. Tags are compared with the tags on the remotes, so the
+        /// remotes are contacted.
+        /// Confirmation is asked unless `--force` is specified.
+        #[structopt(long)]
+        purge: bool,
+        /// Removes the directories without confirmation.
+        #[structopt(long, requires = "purge")]
+        force: bool,
     },
     /// Shows the collections.
     Show {
         /// Collection name.
         ///
         /// If not specified, it is treated as all collections are specified.
+        ///
+        /// With the global `--verbose` option, paths, the numbers of cached
+        /// repositories (in total and per VCS), and the last refresh times are
+        /// also shown.
+        /// These are read from the cache, so the collection directories are
+        /// not scanned.
         collections: Vec<CollectionName>,
-        /// Shows verbose information.
-        #[structopt(long = "verbose", short = "v")]
-        verbose: bool,
+    },
+    /// Merges the collection into another collection.
+    ///
+    /// Cached repositories of the source collection are added to the
+    /// destination collection, and the source collection is removed from the
+    /// config (its directory is kept).
+    /// Nothing is modified if any repositories would have the same paths in
+    /// the destination collection.
+    Merge {
+        /// Collection to merge (and remove).
+        from: CollectionName,
+        /// Collection to merge into.
+        into: CollectionName,
+        /// Moves the repository directories into the destination collection
+        /// directory, keeping the relative paths.
+        ///
+        /// Without this, the source collection directory should be inside the
+        /// destination collection directory.
+        #[structopt(long = "move")]
+        move_repos: bool,
     },
     /// Renames the collection.
     Rename {
@@ -173,17 +314,60 @@ pub enum Subcommand {
         /// If the path is absolute, it is used as is.
         #[structopt(parse(from_os_str))]
         path: PathBuf,
+        /// Allows the directory to overlap with other collections.
+        ///
+        /// Repositories in overlapping collections are cached twice.
+        #[structopt(long)]
+        allow_nested: bool,
+        /// Moves the existing collection directory to the new path.
+        ///
+        /// If the new path is on another filesystem, the directory tree is
+        /// copied, verified, and then removed.
+        /// Cached repositories are kept since they are relative to the
+        /// collection directory.
+        #[structopt(long = "move")]
+        move_dir: bool,
     },
 }
 
-/// Sets the default collection.
-fn set_default(context: &mut Context, name: Option<&CollectionName>) -> anyhow::Result<()> {
-    if let Some(name) = name {
-        if context.config().collections().get(name).is_none() {
-            bail!("Collection named `{}` not found", name);
+/// Shows the default collection.
+fn get_default(context: &Context, output: OutputFormat) -> anyhow::Result<()> {
+    let config = context.config();
+    let name = match config.default_collection() {
+        Some(name) => name,
+        None => bail!("Default collection is not set"),
+    };
+    if config.stale_default_collection().is_some() {
+        return Err(CollectionNotFound(name.clone()))
+            .context("Default collection refers to a non-existent collection");
+    }
+
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    match output {
+        OutputFormat::Text => writeln!(handle, "{}", name)?,
+        OutputFormat::Json => write_json(&mut handle, name)?,
+        OutputFormat::Csv => {
+            write_csv_record(&mut handle, &["name"])?;
+            write_csv_record(&mut handle, &[name])?;
         }
     }
-    context.config_mut().set_default_collection(name.cloned());
+
+    Ok(())
+}
+
+/// Sets the default collection.
+fn set_default(context: &mut Context, name: Option<&CollectionName>) -> anyhow::Result<()> {
+    // Use the actual name, since it can differ from the given one when the
+    // names are matched loosely.
+    let name = match name {
+        Some(name) => match context.config().collections().get(name) {
+            Some(collection) => Some(collection.name().clone()),
+            None => bail!("Collection named `{}` not found", name),
+        },
+        None => None,
+    };
+    context.config_mut().set_default_collection(name.clone());
     context
         .save_config_if_dirty()
         .context("Failed to save config")?;
@@ -203,12 +387,14 @@ fn add_collection(
     path: &Path,
     refresh: bool,
     set_default: bool,
+    allow_nested: bool,
 ) -> anyhow::Result<()> {
     let collection = Collection::new(name.clone(), path.to_owned());
+    check_overlaps(context, &collection, allow_nested)?;
     let has_conflict = context
         .config_mut()
         .collections_mut()
-        .insert(collection)
+        .try_insert(collection)?
         .is_some();
     if has_conflict {
         bail!("Collection `{}` already exists", name);
@@ -239,18 +425,74 @@ fn add_collection(
     };
 
     context
-        .get_or_load_cache_mut()
-        .context("Failed to load cache")?
-        .cache_collection_repos(name.clone(), coll_cache);
-
-    // Save the cache.
-    context.save_cache().context("Failed to save cache file")?;
+        .update_cache(|cache| cache.cache_collection_repos(name.clone(), coll_cache))
+        .context("Failed to update cache file")?;
 
     log::debug!("Added the collection `{}`", name);
 
     Ok(())
 }
 
+/// Imports the existing directory as a collection.
+fn import_collection(
+    context: &mut Context,
+    path: &Path,
+    name: Option<&CollectionName>,
+    set_default: bool,
+    allow_nested: bool,
+) -> anyhow::Result<()> {
+    let path = path
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve the directory {:?}", path))?;
+    if !path.is_dir() {
+        bail!("{:?} is not a directory", path);
+    }
+    let name = match name {
+        Some(name) => name.clone(),
+        None => path
+            .file_name()
+            .and_then(|s| CollectionName::sanitized(&s.to_string_lossy()))
+            .with_context(|| {
+                format!(
+                    "Failed to derive the collection name from {:?} (use `--name` to specify)",
+                    path
+                )
+            })?,
+    };
+    // Keep paths under the home directory relative, as they are resolved using
+    // the home directory.
+    let stored_path = path
+        .strip_prefix(context.home_dir())
+        .ok()
+        .filter(|relpath| relpath.parent().is_some())
+        .unwrap_or(&path)
+        .to_owned();
+
+    add_collection(
+        context,
+        &name,
+        &stored_path,
+        true,
+        set_default,
+        allow_nested,
+    )?;
+
+    let num_repos = context
+        .get_or_load_cache()
+        .context("Failed to load cache file")?
+        .collection_repos(&name)
+        .map_or(0, |cache| cache.repositories().count());
+    writeln!(
+        io::stdout(),
+        "Imported collection `{}` ({}) with {} repositories",
+        name,
+        path.display(),
+        num_repos
+    )?;
+
+    Ok(())
+}
+
 /// Unregister the collection.
 ///
 /// This operation is idempotent when `allow_remove_nothing` is `true`.
@@ -259,24 +501,28 @@ fn unregister_collection(
     names: &[String],
     allow_remove_nothing: bool,
 ) -> anyhow::Result<()> {
+    let mut removed_names = Vec::with_capacity(names.len());
     for name in names {
-        let is_removed = context
-            .config_mut()
-            .collections_mut()
-            .remove(name)
-            .is_some();
-        if !is_removed {
-            if allow_remove_nothing {
+        let removed = context.config_mut().collections_mut().remove(name);
+        // Use the actual name, since it can differ from the given one when
+        // the names are matched loosely.
+        let name = match &removed {
+            Some(collection) => collection.name().as_str(),
+            None if allow_remove_nothing => {
                 log::debug!("Collection named {:?} does not exist", name);
-            } else {
-                bail!("Collection named {:?} does not exist", name);
+                name
             }
+            None => bail!("Collection named {:?} does not exist", name),
+        };
+        if context
+            .config()
+            .default_collection()
+            .map_or(false, |default| default.as_str() == name)
+        {
+            context.config_mut().set_default_collection(None);
+            log::info!("Unset the default collection `{}`", name);
         }
-
-        context
-            .get_or_load_cache_mut()
-            .context("Failed to load cache")?
-            .remove_collection_repos_cache(name);
+        removed_names.push(name.to_owned());
     }
 
     // Save the config.
@@ -284,8 +530,95 @@ fn unregister_collection(
         .save_config_if_dirty()
         .context("Failed to save config")?;
 
-    // Save the cache.
-    context.save_cache().context("Failed to save cache file")?;
+    // Update the cache.
+    context
+        .update_cache(|cache| {
+            for name in &removed_names {
+                cache.remove_collection_repos_cache(name);
+            }
+        })
+        .context("Failed to update cache file")?;
+
+    Ok(())
+}
+
+/// Unregisters the collections and removes their directories.
+///
+/// Nothing is modified if any repository in the collections can lose work.
+fn purge_collections(context: &mut Context, names: &[String], force: bool) -> anyhow::Result<()> {
+    let mut dirs = Vec::with_capacity(names.len());
+    for name in names {
+        let collection = context
+            .config()
+            .collections()
+            .get(name)
+            .with_context(|| format!("Collection named {:?} does not exist", name))?;
+        let dir = collection.abspath(context).into_owned();
+        check_purgeable(context, collection)?;
+        dirs.push(dir);
+    }
+
+    if !force {
+        let stderr = io::stderr();
+        let mut handle = stderr.lock();
+        writeln!(
+            handle,
+            "The following directories and all their contents will be removed:"
+        )?;
+        for dir in &dirs {
+            writeln!(handle, "    {}", dir.display())?;
+        }
+        write!(handle, "Continue? [y/N] ")?;
+        handle.flush()?;
+        let mut answer = String::new();
+        io::stdin()
+            .read_line(&mut answer)
+            .context("Failed to read the answer")?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            bail!("Aborted");
+        }
+    }
+
+    unregister_collection(context, names, false)?;
+    for dir in &dirs {
+        if !dir.exists() {
+            log::debug!("Directory {:?} does not exist", dir);
+            continue;
+        }
+        fs::remove_dir_all(dir).with_context(|| {
+            format!(
+                "Failed to remove the directory {:?} (the collection is already unregistered)",
+                dir
+            )
+        })?;
+        log::info!("Removed the directory {:?}", dir);
+    }
+
+    Ok(())
+}
+
+/// Checks that no repository in the collection can lose work by removal.
+///
+/// Repositories are discovered again, since the cache may be outdated.
+fn check_purgeable(context: &Context, collection: &Collection) -> anyhow::Result<()> {
+    let repos =
+        generate_collection_repos_cache(context, collection, false, false)?.unwrap_or_default();
+    let mut num_unsafe = 0_usize;
+    for repo in repos.repositories() {
+        let repo = RepoView::new(context, collection, repo);
+        let issues = lost_work(&repo, &[])?;
+        if !issues.is_empty() {
+            log::error!("{}: {}", repo.path().display(), issues.join(", "));
+            num_unsafe += 1;
+        }
+    }
+    if num_unsafe != 0 {
+        bail!(
+            "Refusing to purge collection `{}`: {} repositories can lose work",
+            collection.name(),
+            num_unsafe
+        );
+    }
 
     Ok(())
 }
@@ -297,77 +630,224 @@ fn show_collections(
     context: &Context,
     collections: &mut dyn Iterator<Item = Result<&Collection, &CollectionName>>,
     verbose: bool,
+    output: OutputFormat,
 ) -> anyhow::Result<()> {
     let stdout = io::stdout();
     let mut handle = stdout.lock();
 
-    if verbose {
-        for collection in collections {
-            let collection = collection.map_err(|name| anyhow!("No such collection `{}`", name))?;
-
-            writeln!(handle, "collection: {}", collection.name())?;
-            writeln!(
-                handle,
-                "    path: {}",
-                collection.abspath(context).display()
-            )?;
-        }
+    let cache = if verbose {
+        Some(
+            context
+                .get_or_load_cache()
+                .context("Failed to load cache file")?,
+        )
     } else {
-        for collection in collections {
-            let collection =
-                collection.map_err(|name| anyhow!("Collection named `{}` does not exist", name))?;
-
-            writeln!(handle, "{}", collection.name())?;
+        None
+    };
+    let details = collections
+        .map(|collection| {
+            let collection = collection.map_err(|name| CollectionNotFound(name.clone()))?;
+            Ok(CollectionDetails {
+                view: CollectionView::new(context, collection),
+                repos: cache
+                    .and_then(|cache| cache.collection_repos(collection.name()))
+                    .map(ReposSummary::new),
+            })
+        })
+        .collect::<Result<Vec<_>, CollectionNotFound>>()?;
+
+    match output {
+        OutputFormat::Json if verbose => write_json(&mut handle, &details)?,
+        OutputFormat::Json => {
+            let views = details
+                .iter()
+                .map(|details| &details.view)
+                .collect::<Vec<_>>();
+            write_json(&mut handle, &views)?;
+        }
+        OutputFormat::Csv => {
+            if verbose {
+                write_csv_record(
+                    &mut handle,
+                    &[
+                        "name",
+                        "path",
+                        "is_default",
+                        "repositories",
+                        "vcs",
+                        "refreshed_at",
+                    ],
+                )?;
+            } else {
+                write_csv_record(&mut handle, &["name", "path", "is_default"])?;
+            }
+            for details in &details {
+                let view = &details.view;
+                let mut record = vec![
+                    view.name().to_string(),
+                    view.path().to_string_lossy().into_owned(),
+                    view.is_default().to_string(),
+                ];
+                if verbose {
+                    let repos = details.repos.as_ref();
+                    record.push(repos.map_or_else(String::new, |repos| repos.count.to_string()));
+                    record.push(repos.map_or_else(String::new, ReposSummary::vcs_string));
+                    record.push(
+                        repos
+                            .and_then(|repos| repos.refreshed_at)
+                            .map_or_else(String::new, |time| time.to_string()),
+                    );
+                }
+                let record = record.iter().map(String::as_str).collect::<Vec<_>>();
+                write_csv_record(&mut handle, &record)?;
+            }
+        }
+        OutputFormat::Text if verbose => {
+            for details in &details {
+                let view = &details.view;
+                writeln!(handle, "collection: {}", view.name())?;
+                writeln!(handle, "    path: {}", view.path().display())?;
+                match &details.repos {
+                    Some(repos) => {
+                        writeln!(handle, "    repositories: {}", repos.count)?;
+                        if !repos.vcs.is_empty() {
+                            writeln!(handle, "    vcs: {}", repos.vcs_string())?;
+                        }
+                        match repos.refreshed_at {
+                            Some(time) => writeln!(handle, "    refreshed: {}", Timestamp(time))?,
+                            None => writeln!(handle, "    refreshed: (unknown)")?,
+                        }
+                    }
+                    None => writeln!(handle, "    repositories: (not cached)")?,
+                }
+            }
+        }
+        OutputFormat::Text => {
+            for details in &details {
+                writeln!(handle, "{}", details.view.name())?;
+            }
         }
     }
 
     Ok(())
 }
 
+/// Collection with the summary of the cached repositories.
+#[derive(Serialize)]
+struct CollectionDetails {
+    /// Collection.
+    #[serde(flatten)]
+    view: CollectionView,
+    /// Summary of the cached repositories, or `None` if not cached.
+    repos: Option<ReposSummary>,
+}
+
+/// Summary of the cached repositories in a collection.
+#[derive(Serialize)]
+struct ReposSummary {
+    /// Number of the repositories.
+    count: usize,
+    /// Number of the repositories for each VCS.
+    vcs: BTreeMap<&'static str, usize>,
+    /// Time of the last refresh in seconds since the UNIX epoch.
+    refreshed_at: Option<u64>,
+}
+
+impl ReposSummary {
+    /// Creates a summary from the collection cache.
+    fn new(cache: &CollectionReposCache) -> Self {
+        let mut vcs = BTreeMap::new();
+        for repo in cache.repositories() {
+            *vcs.entry(repo.vcs().name_lower()).or_insert(0) += 1;
+        }
+        Self {
+            count: cache.len(),
+            vcs,
+            refreshed_at: cache.refreshed_at(),
+        }
+    }
+
+    /// Returns the per-VCS counts as a string such as `git=3,hg=1`.
+    fn vcs_string(&self) -> String {
+        self.vcs
+            .iter()
+            .map(|(vcs, count)| format!("{}={}", vcs, count))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
 /// Renames the collection.
 fn rename_collection(
     context: &mut Context,
     old_name: &CollectionName,
     new_name: &CollectionName,
 ) -> anyhow::Result<()> {
-    let collections = context.config_mut().collections_mut();
-    let mut collection = collections
-        .remove(old_name)
-        .ok_or_else(|| anyhow!("Collection named `{}` does not exist", old_name))?;
-    collection.set_name(new_name.clone());
-    let has_conflict = collections.insert(collection).is_some();
-    if has_conflict {
-        bail!("Collection `{}` already exists", new_name);
-    }
-
-    // Save the config.
-    context
-        .save_config_if_dirty()
-        .context("Failed to save config")?;
+    context.rename_collection(old_name, new_name)?;
     log::debug!("Renamed the collection `{}` to `{}`", old_name, new_name);
 
-    // Update cache.
-    let cache = context
-        .get_or_load_cache_mut()
-        .context("Failed to load cache")?;
-    let coll_cache = cache
-        .remove_collection_repos_cache(old_name)
-        .unwrap_or_default();
-    cache.cache_collection_repos(new_name.clone(), coll_cache);
+    Ok(())
+}
+
+/// Merges the collection into another.
+///
+/// If `move_repos` is true, `pre-move` and `post-move` hooks are also run.
+fn merge_collection(
+    context: &mut Context,
+    from: &CollectionName,
+    into: &CollectionName,
+    move_repos: bool,
+) -> anyhow::Result<()> {
+    let hook_envs = if move_repos {
+        let collections = context.config().collections();
+        let from_collection = collections
+            .get(from)
+            .ok_or_else(|| CollectionNotFound(from.clone()))?;
+        let into_dir = collections
+            .get(into)
+            .ok_or_else(|| CollectionNotFound(into.clone()))?
+            .abspath(context);
+        let from_dir = from_collection.abspath(context);
+        let envs = move_hook_envs(from_collection, &from_dir, &into_dir);
+        hooks::run(context, HookEvent::PreMove, &envs.0)?;
+        Some(envs)
+    } else {
+        None
+    };
 
-    // Save the cache.
-    context.save_cache().context("Failed to save cache file")?;
+    context.merge_collection(into, from, move_repos)?;
+    log::debug!("Merged the collection `{}` into `{}`", from, into);
+
+    if let Some((_, post_move_env)) = hook_envs {
+        hooks::run(context, HookEvent::PostMove, &post_move_env)?;
+    }
 
     Ok(())
 }
 
+/// Returns the environments of `pre-move` and `post-move` hooks for moving
+/// the contents of the collection directory.
+fn move_hook_envs(collection: &Collection, from: &Path, to: &Path) -> (HookEnv, HookEnv) {
+    let hook_env = HookEnv::new()
+        .with_vars(collection.env())
+        .with_var("MAGRO_COLLECTION", collection.name().as_str())
+        .with_var("MAGRO_MOVE_SOURCE", from)
+        .with_var("MAGRO_MOVE_DEST", to);
+    let pre_move_env = if from.is_dir() {
+        hook_env.clone().with_cwd(from)
+    } else {
+        hook_env.clone()
+    };
+    (pre_move_env, hook_env.with_cwd(to))
+}
+
 /// Shows the path to the collection directory.
 fn get_path(context: &Context, name: &CollectionName) -> anyhow::Result<()> {
     let path = context
         .config()
         .collections()
         .get(name)
-        .ok_or_else(|| anyhow!("Collection named `{}` does not exist", name))?
+        .ok_or_else(|| CollectionNotFound(name.clone()))?
         .abspath(context);
     writeln!(io::stdout(), "{}", path.display())?;
 
@@ -375,19 +855,113 @@ fn get_path(context: &Context, name: &CollectionName) -> anyhow::Result<()> {
 }
 
 /// Sets the path to the collection directory.
-fn set_path(context: &mut Context, name: &CollectionName, path: &Path) -> anyhow::Result<()> {
-    context
-        .config_mut()
-        .collections_mut()
-        .get_mut(name)
-        .ok_or_else(|| anyhow!("Collection named `{}` does not exist", name))?
-        .set_path(path);
+///
+/// If `move_dir` is true, the existing directory is moved to the new path.
+fn set_path(
+    context: &mut Context,
+    name: &CollectionName,
+    path: &Path,
+    allow_nested: bool,
+    move_dir: bool,
+) -> anyhow::Result<()> {
+    let mut collection = context
+        .config()
+        .collections()
+        .get(name)
+        .ok_or_else(|| CollectionNotFound(name.clone()))?
+        .clone();
+    let old_abspath = collection.abspath(context).into_owned();
+    collection.set_path(path);
+    check_overlaps(context, &collection, allow_nested)?;
+    let new_abspath = collection.abspath(context).into_owned();
+    let post_move_env = if move_dir {
+        context
+            .ensure_writable()
+            .context("Cannot move the collection directory")?;
+        let (pre_move_env, post_move_env) = move_hook_envs(&collection, &old_abspath, &new_abspath);
+        hooks::run(context, HookEvent::PreMove, &pre_move_env)?;
+        move_collection_dir(context, &old_abspath, &new_abspath)?;
+        Some(post_move_env)
+    } else {
+        None
+    };
+    context.config_mut().collections_mut().insert(collection);
 
     // Save the config.
-    context
-        .save_config_if_dirty()
-        .context("Failed to save config")?;
+    if let Err(e) = context.save_config_if_dirty() {
+        if move_dir {
+            log::warn!(
+                "Moving the directory back to {:?} since the config is not saved",
+                old_abspath
+            );
+            if let Err(e) = relocate::move_dir(&new_abspath, &old_abspath) {
+                log::error!(
+                    "Failed to move {:?} back to {:?}: {}",
+                    new_abspath,
+                    old_abspath,
+                    e
+                );
+            }
+        }
+        return Err(anyhow::Error::new(e).context("Failed to save config"));
+    }
     log::debug!("Set the path of the collection {:?} to {:?}", name, path);
 
+    if let Some(post_move_env) = post_move_env {
+        hooks::run(context, HookEvent::PostMove, &post_move_env)?;
+    }
+
+    Ok(())
+}
+
+/// Moves the collection directory to the new path.
+fn move_collection_dir(context: &Context, from: &Path, to: &Path) -> anyhow::Result<()> {
+    if from == to {
+        return Ok(());
+    }
+    if !from.is_dir() {
+        bail!("Collection directory {:?} does not exist", from);
+    }
+    if to.starts_with(from) {
+        bail!("Cannot move {:?} into its subdirectory {:?}", from, to);
+    }
+    if to.symlink_metadata().is_ok() {
+        bail!("Destination {:?} already exists", to);
+    }
+    if let Some(parent) = to.parent() {
+        permissions::create_dir_all(parent, context.config().main().dir_mode())
+            .with_context(|| format!("Failed to create directory {:?}", parent))?;
+    }
+    log::info!("Moving {:?} to {:?}", from, to);
+    relocate::move_dir(from, to).with_context(|| format!("Failed to move {:?} to {:?}", from, to))
+}
+
+/// Checks that the collection directory does not overlap with other collections.
+///
+/// If `allow_nested` is `true`, overlaps are reported as warnings.
+fn check_overlaps(
+    context: &Context,
+    collection: &Collection,
+    allow_nested: bool,
+) -> anyhow::Result<()> {
+    let overlapping = context
+        .config()
+        .collections()
+        .overlapping_with(context, collection);
+    for (other, relation) in overlapping {
+        let message = format!(
+            "Collection `{}` ({:?}) {} collection `{}` ({:?})",
+            collection.name(),
+            collection.abspath(context),
+            relation,
+            other.name(),
+            other.abspath(context)
+        );
+        if !allow_nested {
+            bail!("{} (use `--allow-nested` to allow this)", message);
+        }
+        log::warn!("{}", message);
+    }
+
     Ok(())
 }
@@ -0,0 +1,107 @@
+//! `__complete` subcommand.
+//!
+//! This is a hidden subcommand used by shell completions, to complete
+//! arguments which cannot be known statically (such as repository names).
+//!
+//! This is not a part of the usual subcommands, so that it is not shown in
+//! help messages and completions.
+
+use std::{
+    collections::HashSet,
+    io::{self, Write},
+};
+
+use magro::{query, Context};
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::{GlobalOpt, Verbosity},
+    logging::LogFormat,
+    select::select_repos,
+};
+
+/// Name of the subcommand.
+pub(crate) const SUBCOMMAND_NAME: &str = "__complete";
+
+/// Options for `__complete` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[structopt(name = "__complete", bin_name = "magro __complete")]
+#[non_exhaustive]
+pub struct CompleteOpt {
+    /// Global options.
+    #[structopt(flatten)]
+    global: GlobalOpt,
+    /// Subcommand.
+    #[structopt(subcommand)]
+    subcommand: Subcommand,
+}
+
+impl CompleteOpt {
+    /// Returns the verbosity.
+    #[inline]
+    #[must_use]
+    pub fn verbosity(&self) -> Verbosity {
+        self.global.verbosity()
+    }
+
+    /// Returns the log format.
+    #[inline]
+    #[must_use]
+    pub(crate) fn log_format(&self) -> LogFormat {
+        self.global.log_format
+    }
+
+    /// Runs the actual operation.
+    pub fn run(&self) -> anyhow::Result<()> {
+        let context = self.global.context()?;
+        match &self.subcommand {
+            Subcommand::Repos { prefix } => {
+                log::trace!("__complete repos prefix={:?}", prefix);
+                complete_repos(&context, prefix.as_deref().unwrap_or_default())
+            }
+        }
+    }
+}
+
+/// Prints candidates for shell completions.
+#[derive(Debug, Clone, StructOpt)]
+pub enum Subcommand {
+    /// Prints names of the cached repositories matching the prefix.
+    ///
+    /// If no names start with the prefix, names matching the prefix as a
+    /// query of `path` subcommand are printed.
+    Repos {
+        /// Prefix of the repository names.
+        prefix: Option<String>,
+    },
+}
+
+/// Prints names of the repositories matching the prefix.
+fn complete_repos(context: &Context, prefix: &str) -> anyhow::Result<()> {
+    let repos = select_repos(context, &[], &[], &[])?;
+    let mut names = repos
+        .iter()
+        .map(|repo| repo.name_string())
+        .filter(|name| name.starts_with(prefix))
+        .collect::<Vec<_>>();
+    if names.is_empty() {
+        names = query::find(&repos, prefix)
+            .into_iter()
+            .map(|repo| repo.name_string())
+            .collect();
+    } else {
+        names.sort();
+    }
+
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    // Names can be duplicate among collections.
+    let mut printed = HashSet::new();
+    for name in &names {
+        if printed.insert(name) {
+            writeln!(handle, "{}", name)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,138 @@
+//! `config` subcommand.
+
+use std::{
+    fs,
+    io::{self, Write},
+};
+
+use anyhow::Context as _;
+use magro::{config::ValueSource, Context};
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::GlobalOpt,
+    output::{write_csv_record, write_json, OutputFormat},
+};
+
+/// Options for `config` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct ConfigOpt {
+    /// Subcommand.
+    #[structopt(subcommand)]
+    subcommand: Subcommand,
+}
+
+impl ConfigOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &Context, global: &GlobalOpt) -> anyhow::Result<()> {
+        match &self.subcommand {
+            Subcommand::Show { effective } => {
+                log::trace!("config show effective={}", effective);
+                if *effective {
+                    show_effective(context, global)
+                } else {
+                    show_files(context)
+                }
+            }
+        }
+    }
+}
+
+/// Subcommand of `config`.
+#[derive(Debug, Clone, StructOpt)]
+pub enum Subcommand {
+    /// Shows the config files.
+    Show {
+        /// Shows the fully resolved config with the source of each value.
+        ///
+        /// Values from built-in defaults, config files, and command line
+        /// options are merged.
+        #[structopt(long)]
+        effective: bool,
+    },
+}
+
+/// Shows the content of the config files.
+fn show_files(context: &Context) -> anyhow::Result<()> {
+    let config = context.config();
+    let paths = config
+        .main_path()
+        .into_iter()
+        .chain(config.main_local_path())
+        .chain(Some(config.collections_path()).filter(|path| path.is_file()))
+        .chain(config.collections_local_path());
+
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    for (i, path) in paths.enumerate() {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read the config file {:?}", path))?;
+        if i != 0 {
+            writeln!(handle)?;
+        }
+        writeln!(handle, "# {}", path.display())?;
+        handle.write_all(content.as_bytes())?;
+        if !content.is_empty() && !content.ends_with('\n') {
+            writeln!(handle)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Shows the fully resolved config with the source of each value.
+fn show_effective(context: &Context, global: &GlobalOpt) -> anyhow::Result<()> {
+    let mut effective = context.config().effective();
+    if let Some(jobs) = global.jobs() {
+        effective.set(
+            "jobs".to_owned(),
+            toml::Value::Integer(jobs.get() as i64),
+            ValueSource::CommandLine {
+                option: "--jobs".to_owned(),
+            },
+        );
+    }
+    if let Some(jobs_per_host) = global.jobs_per_host() {
+        effective.set(
+            "jobs-per-host".to_owned(),
+            toml::Value::Integer(jobs_per_host.get() as i64),
+            ValueSource::CommandLine {
+                option: "--jobs-per-host".to_owned(),
+            },
+        );
+    }
+    let values = effective.values().collect::<Vec<_>>();
+
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    match global.output {
+        OutputFormat::Text => {
+            for value in &values {
+                writeln!(
+                    handle,
+                    "{} = {}  # {}",
+                    value.key(),
+                    value.value(),
+                    value.source()
+                )?;
+            }
+        }
+        OutputFormat::Json => write_json(&mut handle, &values)?,
+        OutputFormat::Csv => {
+            write_csv_record(&mut handle, &["key", "value", "source"])?;
+            for value in &values {
+                write_csv_record(
+                    &mut handle,
+                    &[
+                        value.key(),
+                        &value.value().to_string(),
+                        &value.source().to_string(),
+                    ],
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
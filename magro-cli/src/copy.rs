@@ -0,0 +1,96 @@
+//! `copy` subcommand.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context as _};
+use magro::{collection::CollectionName, Context};
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::OptionBool,
+    clone::ClonePlan,
+    select::{find_by_name, RepoSelectOpt},
+};
+
+/// Options for `copy` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct CopyOpt {
+    /// Source repositories.
+    #[structopt(flatten)]
+    select: RepoSelectOpt,
+    /// Collection to put the copy.
+    ///
+    /// If not specified, the default collection is used.
+    #[structopt(long, value_name = "collection")]
+    into: Option<CollectionName>,
+    /// Relative path of the destination directory in the target collection.
+    ///
+    /// If not specified, the same relative path as the source is used.
+    #[structopt(long, short, parse(from_os_str))]
+    destination: Option<PathBuf>,
+    /// Name of the repository to copy.
+    ///
+    /// Names are paths relative to the collection directory without `.git`
+    /// suffix (for example, `github.com/lo48576/magro`).
+    name: String,
+}
+
+impl CopyOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &mut Context) -> anyhow::Result<()> {
+        log::trace!(
+            "copy select={:?}, into={:?}, dest={:?}, name={:?}",
+            self.select,
+            self.into,
+            self.destination,
+            self.name
+        );
+
+        let repos = self.select.select(context)?;
+        let repo = find_by_name(&repos, &self.name)?;
+        if !repo.path().exists() {
+            bail!(
+                "Repository `{}` does not exist at {:?}",
+                self.name,
+                repo.path()
+            );
+        }
+
+        let relpath = repo.relative_path();
+        let (reldest, bare) = if relpath.file_name() == Some(".git".as_ref()) {
+            (
+                relpath.parent().unwrap_or_else(|| Path::new("")),
+                OptionBool::No,
+            )
+        } else {
+            (relpath, OptionBool::Yes)
+        };
+        let reldest = self.destination.as_deref().unwrap_or(reldest);
+        // Record the URI the source was cloned from (or its `origin`) as the
+        // source URI of the copy, not the path of the source.
+        let uri = match repo.source_uri() {
+            Some(uri) => uri.to_owned(),
+            None => repo
+                .vcs()
+                .remote_url(repo.path(), "origin")
+                .context("Failed to get the URL of the remote `origin`")?
+                .unwrap_or_else(|| repo.path().to_string_lossy().into_owned()),
+        };
+        let plan = ClonePlan::new(
+            context,
+            &uri,
+            self.into.as_ref(),
+            Some(reldest),
+            Some(repo.vcs()),
+            bare,
+        )?
+        .local_source(repo.path().to_owned());
+        if plan.destination().exists() {
+            bail!("Destination {:?} already exists", plan.destination());
+        }
+
+        log::info!("Copying {:?} to {:?}", repo.path(), plan.destination());
+        plan.run(context)
+    }
+}
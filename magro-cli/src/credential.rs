@@ -0,0 +1,143 @@
+//! Interactive credential prompt.
+
+use std::{
+    io::{self, BufRead, Write},
+    path::Path,
+};
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    terminal,
+    tty::IsTty,
+};
+use magro::credential::{self, CredentialPrompt, UserPass};
+
+/// Registers the terminal prompt for credentials of remotes.
+///
+/// If `cache` is true, entered credentials are reused for the same host
+/// during the command.
+pub(crate) fn register(cache: bool) {
+    if let Err(e) = credential::set_prompt(Box::new(TerminalPrompt), cache) {
+        log::warn!("{}", e);
+    }
+}
+
+/// Credential prompt on the terminal.
+///
+/// Prompts are written to stderr, so that outputs to stdout are not broken.
+#[derive(Debug, Clone, Copy)]
+struct TerminalPrompt;
+
+impl CredentialPrompt for TerminalPrompt {
+    fn prompt_userpass(&self, url: &str, username: Option<&str>) -> Option<UserPass> {
+        if !is_interactive() {
+            return None;
+        }
+        match prompt_userpass(url, username) {
+            Ok(v) => v,
+            Err(e) => {
+                log::warn!("Failed to prompt for credentials: {}", e);
+                None
+            }
+        }
+    }
+
+    fn prompt_passphrase(&self, key_path: &Path) -> Option<String> {
+        if !is_interactive() {
+            return None;
+        }
+        match prompt_passphrase(key_path) {
+            Ok(v) => v,
+            Err(e) => {
+                log::warn!("Failed to prompt for the passphrase: {}", e);
+                None
+            }
+        }
+    }
+}
+
+/// Returns true if the user can be asked on the terminal.
+fn is_interactive() -> bool {
+    let interactive = io::stdin().is_tty() && io::stderr().is_tty();
+    if !interactive {
+        log::debug!("Not prompting for credentials because stdin or stderr is not a tty");
+    }
+    interactive
+}
+
+/// Asks the user for the username and the password.
+///
+/// Returns `Ok(None)` if cancelled.
+fn prompt_userpass(url: &str, username: Option<&str>) -> io::Result<Option<UserPass>> {
+    let stderr = io::stderr();
+    let mut handle = stderr.lock();
+    writeln!(handle, "Credentials are required for {}", url)?;
+
+    let username = match username {
+        Some(username) => username.to_owned(),
+        None => {
+            write!(handle, "Username: ")?;
+            handle.flush()?;
+            let mut line = String::new();
+            io::stdin().lock().read_line(&mut line)?;
+            let username = line.trim_end_matches(&['\r', '\n'][..]);
+            if username.is_empty() {
+                return Ok(None);
+            }
+            username.to_owned()
+        }
+    };
+
+    write!(handle, "Password (or token) for {}: ", username)?;
+    handle.flush()?;
+    let password = read_hidden_line();
+    writeln!(handle)?;
+
+    Ok(password?.map(|password| UserPass::new(username, password)))
+}
+
+/// Asks the user for the passphrase of the SSH private key file.
+///
+/// Returns `Ok(None)` if cancelled.
+fn prompt_passphrase(key_path: &Path) -> io::Result<Option<String>> {
+    let stderr = io::stderr();
+    let mut handle = stderr.lock();
+    write!(handle, "Enter passphrase for key {:?}: ", key_path)?;
+    handle.flush()?;
+    let passphrase = read_hidden_line();
+    writeln!(handle)?;
+
+    passphrase
+}
+
+/// Reads a line without echoing.
+///
+/// Returns `Ok(None)` if cancelled by Ctrl-C or Esc.
+fn read_hidden_line() -> io::Result<Option<String>> {
+    terminal::enable_raw_mode()?;
+    let result = read_hidden_line_raw();
+    // The terminal would be broken if this fails, but nothing can be done.
+    let _ = terminal::disable_raw_mode();
+    result
+}
+
+/// Reads a line in the raw mode.
+fn read_hidden_line_raw() -> io::Result<Option<String>> {
+    let mut line = String::new();
+    loop {
+        let KeyEvent { code, modifiers } = match event::read()? {
+            Event::Key(v) => v,
+            _ => continue,
+        };
+        match code {
+            KeyCode::Enter => return Ok(Some(line)),
+            KeyCode::Esc => return Ok(None),
+            KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => return Ok(None),
+            KeyCode::Backspace => {
+                line.pop();
+            }
+            KeyCode::Char(c) => line.push(c),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! `doctor` subcommand.
+
+use std::io::{self, Write};
+
+use anyhow::bail;
+use magro::Context;
+use structopt::StructOpt;
+
+/// Options for `doctor` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct DoctorOpt {}
+
+impl DoctorOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &Context) -> anyhow::Result<()> {
+        log::trace!("doctor");
+
+        let mut problems = Vec::new();
+        check_default_collection(context, &mut problems);
+        check_collection_overlaps(context, &mut problems);
+
+        let stdout = io::stdout();
+        let mut handle = stdout.lock();
+        for problem in &problems {
+            writeln!(handle, "{}", problem)?;
+        }
+
+        if !problems.is_empty() {
+            bail!("Found {} problem(s)", problems.len());
+        }
+        log::info!("No problems found");
+
+        Ok(())
+    }
+}
+
+/// Checks whether the default collection exists.
+fn check_default_collection(context: &Context, problems: &mut Vec<String>) {
+    if let Some(name) = context.config().stale_default_collection() {
+        problems.push(format!(
+            "Default collection `{}` does not exist (use `collection set-default` to fix)",
+            name
+        ));
+    }
+}
+
+/// Checks whether collection directories overlap.
+///
+/// Repositories in overlapping collections are cached twice, and the result
+/// depends on the order of refresh.
+fn check_collection_overlaps(context: &Context, problems: &mut Vec<String>) {
+    for overlap in context.config().collections().overlaps(context) {
+        let (first, second) = (overlap.first(), overlap.second());
+        problems.push(format!(
+            "Collection `{}` ({:?}) {} collection `{}` ({:?})",
+            first.name(),
+            first.abspath(context),
+            overlap.relation(),
+            second.name(),
+            second.abspath(context)
+        ));
+    }
+}
@@ -0,0 +1,337 @@
+//! `du` subcommand.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str,
+};
+
+use anyhow::{anyhow, bail, Context as _};
+use magro::{collection::CollectionName, metadata::DiskUsage, view::RepoView, Context};
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::GlobalOpt,
+    logging,
+    output::{write_csv_record, write_json, OutputFormat},
+    select::RepoSelectOpt,
+};
+
+/// Sort key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortKey {
+    /// Collection and repository name.
+    Name,
+    /// Total size (largest first).
+    Size,
+}
+
+impl SortKey {
+    /// Returns a list of possible options.
+    #[inline]
+    #[must_use]
+    fn possible_opt_values() -> &'static [&'static str] {
+        &["name", "size"]
+    }
+
+    /// Returns the option value.
+    #[inline]
+    #[must_use]
+    fn as_opt_value(&self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Size => "size",
+        }
+    }
+
+    /// Parses the option value.
+    #[inline]
+    #[must_use]
+    fn from_opt_value(s: &str) -> Option<Self> {
+        match s {
+            "name" => Some(Self::Name),
+            "size" => Some(Self::Size),
+            _ => None,
+        }
+    }
+}
+
+impl str::FromStr for SortKey {
+    type Err = anyhow::Error;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_opt_value(s).ok_or_else(|| anyhow!("Unknown sort key {:?}", s))
+    }
+}
+
+impl fmt::Display for SortKey {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_opt_value())
+    }
+}
+
+/// Options for `du` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct DuOpt {
+    /// Target repositories.
+    #[structopt(flatten)]
+    select: RepoSelectOpt,
+    /// Measures disk usage again even if the cached result is available.
+    #[structopt(long)]
+    refresh: bool,
+    /// Shows sizes of repository directories (such as `.git`) and working
+    /// directories separately.
+    #[structopt(long)]
+    split: bool,
+    /// Sort key.
+    #[structopt(
+        long,
+        possible_values = SortKey::possible_opt_values(),
+        default_value = "name"
+    )]
+    sort: SortKey,
+    /// Runs the operation as possible even when errors are detected.
+    ///
+    /// Note that errors are ignored during the operation, but the program will
+    /// exit with failure (i.e. errors won't be completely ignored).
+    #[structopt(long)]
+    keep_going: bool,
+}
+
+impl DuOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &mut Context, global: &GlobalOpt) -> anyhow::Result<()> {
+        log::trace!(
+            "du select={:?}, refresh={}, split={}, sort={}, keep_going={}",
+            self.select,
+            self.refresh,
+            self.split,
+            self.sort,
+            self.keep_going
+        );
+
+        let repos = self.select.select(context)?;
+        let refresh = self.refresh;
+        let keep_going = self.keep_going;
+        let envs = logging::CollectionEnvs::new(context);
+        let results = global.runner(context).run_until(
+            repos,
+            move |repo| {
+                logging::with_repo(repo, &envs, || match repo.metadata().disk_usage() {
+                    Some(usage) if !refresh => Ok((*usage, false)),
+                    _ => measure(repo).map(|usage| (usage, true)),
+                })
+            },
+            move |result| !keep_going && result.is_err(),
+        );
+
+        let (repos, results): (Vec<_>, Vec<_>) = results.into_iter().unzip();
+        let mut entries = Vec::with_capacity(repos.len());
+        let mut measured: HashMap<(CollectionName, PathBuf), DiskUsage> = HashMap::new();
+        let mut num_errors = 0_usize;
+        for (repo, result) in repos.iter().zip(results) {
+            match result {
+                Ok((usage, is_measured)) => {
+                    if is_measured {
+                        measured.insert(
+                            (repo.collection().clone(), repo.relative_path().to_owned()),
+                            usage,
+                        );
+                    }
+                    entries.push(Entry::new(repo, usage));
+                }
+                Err(e) => {
+                    if !self.keep_going {
+                        return Err(e);
+                    }
+                    log::error!("{:#}", e);
+                    num_errors += 1;
+                }
+            }
+        }
+
+        if context.is_read_only() {
+            log::debug!("Not storing the measured disk usages in read-only mode");
+        } else if !measured.is_empty() {
+            store_measured(context, measured)?;
+        }
+
+        match self.sort {
+            SortKey::Name => {}
+            SortKey::Size => entries.sort_by_key(|entry| std::cmp::Reverse(entry.total)),
+        }
+        let mut totals: BTreeMap<&str, u64> = BTreeMap::new();
+        for entry in &entries {
+            *totals.entry(entry.collection.as_str()).or_default() += entry.total;
+        }
+
+        let stdout = io::stdout();
+        let mut handle = stdout.lock();
+        match global.output {
+            OutputFormat::Text => {
+                for entry in &entries {
+                    if self.split {
+                        write!(
+                            handle,
+                            "{:>10}  {:>10}  ",
+                            HumanSize(entry.git_dir),
+                            HumanSize(entry.worktree)
+                        )?;
+                    }
+                    writeln!(
+                        handle,
+                        "{:>10}  {}/{}",
+                        HumanSize(entry.total),
+                        entry.collection,
+                        entry.name
+                    )?;
+                }
+                for (collection, total) in &totals {
+                    if self.split {
+                        write!(handle, "{:>10}  {:>10}  ", "", "")?;
+                    }
+                    writeln!(handle, "{:>10}  {} (total)", HumanSize(*total), collection)?;
+                }
+            }
+            OutputFormat::Json => write_json(&mut handle, &entries)?,
+            OutputFormat::Csv => {
+                write_csv_record(
+                    &mut handle,
+                    &[
+                        "collection",
+                        "name",
+                        "path",
+                        "git_dir",
+                        "worktree",
+                        "total",
+                        "measured_at",
+                    ],
+                )?;
+                for entry in &entries {
+                    write_csv_record(
+                        &mut handle,
+                        &[
+                            entry.collection.as_str(),
+                            &entry.name,
+                            &entry.path.to_string_lossy(),
+                            &entry.git_dir.to_string(),
+                            &entry.worktree.to_string(),
+                            &entry.total.to_string(),
+                            &entry.measured_at.to_string(),
+                        ],
+                    )?;
+                }
+            }
+        }
+
+        if num_errors != 0 {
+            bail!("Failed to measure {} repositories", num_errors);
+        }
+
+        Ok(())
+    }
+}
+
+/// Measures the disk usage of the repository.
+fn measure(repo: &RepoView) -> anyhow::Result<DiskUsage> {
+    log::debug!("Measuring disk usage of {:?}", repo.path());
+    let workdir = repo
+        .vcs()
+        .workdir(repo.path())
+        .with_context(|| format!("Failed to get working directory for {:?}", repo.path()))?;
+    DiskUsage::measure(repo.path(), workdir.as_deref())
+        .with_context(|| format!("Failed to measure disk usage of {:?}", repo.path()))
+}
+
+/// Stores the measured disk usages into the cache, and saves the cache.
+fn store_measured(
+    context: &mut Context,
+    measured: HashMap<(CollectionName, PathBuf), DiskUsage>,
+) -> anyhow::Result<()> {
+    context
+        .update_cache(|cache| {
+            for ((collection, path), usage) in measured {
+                if let Some(coll_cache) = cache.collection_repos_mut(&collection) {
+                    coll_cache.update_entry(&path, |entry| {
+                        entry.metadata_mut().set_disk_usage(usage);
+                    });
+                }
+            }
+        })
+        .context("Failed to update cache file")
+}
+
+/// Disk usage entry of the report.
+#[derive(Debug, Serialize)]
+struct Entry<'a> {
+    /// Collection name.
+    collection: &'a CollectionName,
+    /// Repository name.
+    name: String,
+    /// Absolute path of the repository.
+    path: &'a Path,
+    /// Size of the repository directory.
+    git_dir: u64,
+    /// Size of the working directory, excluding the repository directory.
+    worktree: u64,
+    /// Total size.
+    total: u64,
+    /// Measured time in seconds since the UNIX epoch.
+    measured_at: u64,
+}
+
+impl<'a> Entry<'a> {
+    /// Creates a new entry.
+    fn new(repo: &'a RepoView, usage: DiskUsage) -> Self {
+        Self {
+            collection: repo.collection(),
+            name: repo.name_string(),
+            path: repo.path(),
+            git_dir: usage.git_dir(),
+            worktree: usage.worktree(),
+            total: usage.total(),
+            measured_at: usage.measured_at(),
+        }
+    }
+}
+
+/// Size in bytes, displayed in human-readable binary units.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct HumanSize(pub(crate) u64);
+
+impl fmt::Display for HumanSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        /// Units.
+        const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB", "PiB"];
+
+        if self.0 < 1024 {
+            return f.pad(&format!("{} B", self.0));
+        }
+        let mut size = self.0 as f64 / 1024.0;
+        let mut unit = 0;
+        while size >= 1024.0 && unit + 1 < UNITS.len() {
+            size /= 1024.0;
+            unit += 1;
+        }
+        f.pad(&format!("{:.1} {}", size, UNITS[unit]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn human_size() {
+        assert_eq!(HumanSize(0).to_string(), "0 B");
+        assert_eq!(HumanSize(1023).to_string(), "1023 B");
+        assert_eq!(HumanSize(1024).to_string(), "1.0 KiB");
+        assert_eq!(HumanSize(1536 * 1024).to_string(), "1.5 MiB");
+    }
+}
@@ -0,0 +1,156 @@
+//! `env` subcommand.
+
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use magro::Context;
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::GlobalOpt,
+    output::{write_csv_record, write_json, OutputFormat, QuoteStyle},
+};
+
+/// Cargo features enabled for the build.
+const FEATURES: &[(&str, bool)] = &[
+    ("forge", cfg!(feature = "forge")),
+    ("libgit2", cfg!(feature = "libgit2")),
+    ("pick", cfg!(feature = "pick")),
+    ("prompt", cfg!(feature = "prompt")),
+    ("serve", cfg!(feature = "serve")),
+];
+
+/// Options for `env` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct EnvOpt {
+    /// Prints `MAGRO_<KEY>=<value>` lines quoted for POSIX shells.
+    ///
+    /// This is not affected by the global `--output` option.
+    #[structopt(long)]
+    shell: bool,
+}
+
+impl EnvOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &Context, global: &GlobalOpt) -> anyhow::Result<()> {
+        log::trace!("env shell={}", self.shell);
+
+        let env = Env::new(context);
+        let stdout = io::stdout();
+        let mut handle = stdout.lock();
+        if self.shell {
+            for (key, value) in env.entries() {
+                write!(handle, "MAGRO_{}=", key.to_ascii_uppercase())?;
+                handle.write_all(&QuoteStyle::Shell.quote(value.as_bytes()))?;
+                writeln!(handle)?;
+            }
+            return Ok(());
+        }
+        match global.output {
+            OutputFormat::Text => {
+                for (key, value) in env.entries() {
+                    writeln!(handle, "{}: {}", key, value)?;
+                }
+            }
+            OutputFormat::Json => write_json(&mut handle, &env)?,
+            OutputFormat::Csv => {
+                write_csv_record(&mut handle, &["key", "value"])?;
+                for (key, value) in env.entries() {
+                    write_csv_record(&mut handle, &[key, &value])?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Environment and paths used by the program.
+#[derive(Debug, Serialize)]
+struct Env<'a> {
+    /// Version of the program.
+    version: &'static str,
+    /// Enabled cargo features.
+    features: Vec<&'static str>,
+    /// Home directory.
+    home_dir: &'a Path,
+    /// Profile name, or `None` for the default profile.
+    profile: Option<&'a str>,
+    /// Config directory.
+    config_dir: &'a Path,
+    /// Loaded main config file.
+    main_config: Option<&'a Path>,
+    /// Collections config file.
+    collections_config: &'a Path,
+    /// Cache directory.
+    cache_dir: &'a Path,
+    /// Cache namespace, or `None` for the default cache file.
+    cache_namespace: Option<&'a str>,
+    /// Cache file of the profile.
+    cache_path: &'a Path,
+    /// Trash directory of the profile.
+    trash_dir: &'a Path,
+    /// Default collection.
+    default_collection: Option<&'a str>,
+}
+
+impl<'a> Env<'a> {
+    /// Creates a new `Env` from the context.
+    fn new(context: &'a Context) -> Self {
+        let config = context.config();
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            features: FEATURES
+                .iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(name, _)| *name)
+                .collect(),
+            home_dir: context.home_dir(),
+            profile: context.profile().map(|profile| profile.as_str()),
+            config_dir: context.config_dir(),
+            main_config: config.main_path(),
+            collections_config: config.collections_path(),
+            cache_dir: context.cache_dir(),
+            cache_namespace: context
+                .cache_namespace()
+                .map(|namespace| namespace.as_str()),
+            cache_path: context.cache_path(),
+            trash_dir: context.trash_dir(),
+            default_collection: config.default_collection().map(|name| name.as_str()),
+        }
+    }
+
+    /// Returns the keys and the values as strings.
+    ///
+    /// Unavailable values are empty strings.
+    fn entries(&self) -> Vec<(&'static str, String)> {
+        let path = |path: &Path| path.to_string_lossy().into_owned();
+        vec![
+            ("version", self.version.to_owned()),
+            ("features", self.features.join(",")),
+            ("home_dir", path(self.home_dir)),
+            ("profile", self.profile.unwrap_or_default().to_owned()),
+            ("config_dir", path(self.config_dir)),
+            (
+                "main_config",
+                self.main_config.map(path).unwrap_or_default(),
+            ),
+            ("collections_config", path(self.collections_config)),
+            ("cache_dir", path(self.cache_dir)),
+            (
+                "cache_namespace",
+                self.cache_namespace.unwrap_or_default().to_owned(),
+            ),
+            ("cache_path", path(self.cache_path)),
+            ("trash_dir", path(self.trash_dir)),
+            (
+                "default_collection",
+                self.default_collection.unwrap_or_default().to_owned(),
+            ),
+        ]
+    }
+}
@@ -0,0 +1,244 @@
+//! Errors and error reports.
+
+use std::{
+    error, fmt,
+    io::{self, Write},
+    path::Path,
+};
+
+use magro::{
+    collection::{CollectionName, CollectionNameError},
+    config::{LoadError, TargetCollectionError},
+    context::{self, MergeCollectionError, RenameCollectionError},
+    discovery,
+    hooks::HookError,
+    template::TemplateError,
+    vcs::VcsError,
+};
+use serde::Serialize;
+
+use crate::output::{write_json, OutputFormat};
+
+/// Error for a collection which does not exist.
+#[derive(Debug, Clone)]
+pub(crate) struct CollectionNotFound(pub(crate) CollectionName);
+
+impl fmt::Display for CollectionNotFound {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Collection named `{}` does not exist", self.0)
+    }
+}
+
+impl error::Error for CollectionNotFound {}
+
+/// Reports the error to stderr in the given format.
+///
+/// For JSON output, an object with `error` field is written.
+/// For other formats, the error is written in the same way as `anyhow` does.
+pub(crate) fn report(e: &anyhow::Error, output: OutputFormat) {
+    /// Wrapper of the error report.
+    #[derive(Serialize)]
+    struct Output<'a> {
+        /// Error report.
+        error: ErrorReport<'a>,
+    }
+
+    let stderr = io::stderr();
+    let mut handle = stderr.lock();
+    if output == OutputFormat::Json {
+        let result = write_json(
+            &mut handle,
+            &Output {
+                error: ErrorReport::new(e),
+            },
+        );
+        if result.is_ok() {
+            return;
+        }
+    }
+    // Nothing can be done if writing to stderr fails.
+    let _ = writeln!(handle, "Error: {:?}", e);
+}
+
+/// Machine-readable error report.
+#[derive(Debug, Serialize)]
+pub(crate) struct ErrorReport<'a> {
+    /// Error kind.
+    ///
+    /// This is determined by the outermost error of known types.
+    kind: &'static str,
+    /// Error message.
+    message: String,
+    /// Messages of the underlying errors, outermost first.
+    causes: Vec<String>,
+    /// Path related to the error, if available.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    /// Collection related to the error, if available.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    collection: Option<&'a CollectionName>,
+}
+
+impl<'a> ErrorReport<'a> {
+    /// Creates a new error report.
+    #[must_use]
+    pub(crate) fn new(e: &'a anyhow::Error) -> Self {
+        let mut kind = None;
+        let mut path = None;
+        let mut collection = None;
+        for cause in e.chain() {
+            let info = ErrorInfo::new(cause);
+            kind = kind.or(info.kind);
+            path = path.or(info.path);
+            collection = collection.or(info.collection);
+        }
+
+        Self {
+            kind: kind.unwrap_or("other"),
+            message: e.to_string(),
+            causes: e.chain().skip(1).map(ToString::to_string).collect(),
+            path: path.map(|path| path.to_string_lossy().into_owned()),
+            collection,
+        }
+    }
+}
+
+/// Information extracted from an error of known types.
+#[derive(Default)]
+struct ErrorInfo<'a> {
+    /// Error kind.
+    kind: Option<&'static str>,
+    /// Path related to the error.
+    path: Option<&'a Path>,
+    /// Collection related to the error.
+    collection: Option<&'a CollectionName>,
+}
+
+impl<'a> ErrorInfo<'a> {
+    /// Extracts information from the error.
+    fn new(e: &'a (dyn error::Error + 'static)) -> Self {
+        let with_kind = |kind| Self {
+            kind: Some(kind),
+            ..Default::default()
+        };
+
+        if let Some(e) = e.downcast_ref::<CollectionNotFound>() {
+            return Self {
+                collection: Some(&e.0),
+                ..with_kind("collection-not-found")
+            };
+        }
+        if let Some(e) = e.downcast_ref::<RenameCollectionError>() {
+            return match e {
+                RenameCollectionError::NotFound(name) => Self {
+                    collection: Some(name),
+                    ..with_kind("collection-not-found")
+                },
+                RenameCollectionError::AlreadyExists(name) => Self {
+                    collection: Some(name),
+                    ..with_kind("collection-exists")
+                },
+                _ => with_kind("io"),
+            };
+        }
+        if let Some(e) = e.downcast_ref::<MergeCollectionError>() {
+            return match e {
+                MergeCollectionError::NotFound(name) => Self {
+                    collection: Some(name),
+                    ..with_kind("collection-not-found")
+                },
+                MergeCollectionError::SameCollection(_)
+                | MergeCollectionError::NotNested { .. }
+                | MergeCollectionError::Conflict(_) => with_kind("merge-conflict"),
+                MergeCollectionError::Move { from, .. } => Self {
+                    path: Some(from),
+                    ..with_kind("io")
+                },
+                _ => with_kind("io"),
+            };
+        }
+        if let Some(e) = e.downcast_ref::<TargetCollectionError>() {
+            return match e {
+                TargetCollectionError::NotFound(name)
+                | TargetCollectionError::StaleDefault(name) => Self {
+                    collection: Some(name),
+                    ..with_kind("collection-not-found")
+                },
+                _ => with_kind("no-target-collection"),
+            };
+        }
+        if e.is::<CollectionNameError>() {
+            return with_kind("invalid-collection-name");
+        }
+        if let Some(e) = e.downcast_ref::<context::Error>() {
+            return match e {
+                context::Error::Config(_) => with_kind("config"),
+                _ => with_kind("environment"),
+            };
+        }
+        if let Some(e) = e.downcast_ref::<LoadError>() {
+            return Self {
+                path: e.path(),
+                ..with_kind("config")
+            };
+        }
+        if e.is::<HookError>() {
+            return with_kind("hook");
+        }
+        if e.is::<TemplateError>() {
+            return with_kind("config");
+        }
+        if let Some(e) = e.downcast_ref::<discovery::Error>() {
+            return Self {
+                path: e.path(),
+                ..with_kind("discovery")
+            };
+        }
+        if let Some(e) = e.downcast_ref::<VcsError>() {
+            let kind = match e {
+                VcsError::NotFound { .. } => "vcs-not-found",
+                VcsError::PermissionDenied { .. } => "vcs-permission-denied",
+                VcsError::Network { .. } => "vcs-network",
+                VcsError::Auth { .. } => "vcs-auth",
+                _ => "vcs",
+            };
+            return Self {
+                path: e.path(),
+                ..with_kind(kind)
+            };
+        }
+        #[cfg(feature = "forge")]
+        if e.is::<magro::forge::Error>() {
+            return with_kind("forge");
+        }
+        if e.is::<io::Error>() {
+            return with_kind("io");
+        }
+
+        Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    use anyhow::Context as _;
+
+    #[test]
+    fn report_collection_not_found() {
+        let name = CollectionName::try_from("foo").unwrap();
+        let e = Err::<(), _>(CollectionNotFound(name.clone()))
+            .context("Failed to select")
+            .unwrap_err();
+        let report = ErrorReport::new(&e);
+        assert_eq!(report.kind, "collection-not-found");
+        assert_eq!(report.message, "Failed to select");
+        assert_eq!(report.causes, ["Collection named `foo` does not exist"]);
+        assert_eq!(report.collection, Some(&name));
+        assert_eq!(report.path, None);
+    }
+}
@@ -0,0 +1,176 @@
+//! `exec` subcommand.
+
+use std::{
+    borrow::Cow,
+    ffi::{OsStr, OsString},
+    io::{self, Write},
+    path::Path,
+    process::{Command, Output},
+    sync::Arc,
+};
+
+use anyhow::{bail, ensure, Context as _};
+use magro::{template, view::RepoView, Context};
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::GlobalOpt,
+    logging::{self, CollectionEnvs},
+    select::RepoSelectOpt,
+};
+
+/// Options for `exec` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct ExecOpt {
+    /// Target repositories.
+    #[structopt(flatten)]
+    select: RepoSelectOpt,
+    /// Runs the operation as possible even when errors are detected.
+    ///
+    /// Note that errors are ignored during the operation, but the program will
+    /// exit with failure (i.e. errors won't be completely ignored).
+    #[structopt(long)]
+    keep_going: bool,
+    /// Runs the command in the current directory instead of `{path}`.
+    #[structopt(long)]
+    no_chdir: bool,
+    /// Command and its arguments.
+    ///
+    /// Available variables are `{collection}`, `{name}` (repository path
+    /// relative to the collection directory, without `.git` suffix), `{path}`
+    /// (absolute path of the working directory, or the repository for bare
+    /// repositories), and `{remote}` (URL of `origin` remote; empty if not
+    /// exist). `{{` and `}}` are expanded to `{` and `}`.
+    /// Other braces (such as `{}` for `find -exec`) are kept as is.
+    #[structopt(required = true, last = true)]
+    command: Vec<String>,
+}
+
+impl ExecOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &Context, global: &GlobalOpt) -> anyhow::Result<()> {
+        log::trace!(
+            "exec select={:?}, keep_going={}, no_chdir={}, command={:?}",
+            self.select,
+            self.keep_going,
+            self.no_chdir,
+            self.command
+        );
+
+        let repos = self.select.select(context)?;
+        let keep_going = self.keep_going;
+        let chdir = !self.no_chdir;
+        let command = Arc::new(self.command.clone());
+        let envs = logging::CollectionEnvs::new(context);
+        let results = global.runner(context).run_until(
+            repos,
+            move |repo| logging::with_repo(repo, &envs, || exec_repo(repo, &envs, &command, chdir)),
+            move |result| !keep_going && result.is_err(),
+        );
+
+        let stdout = io::stdout();
+        let mut handle = stdout.lock();
+        let mut num_errors = 0_usize;
+        for (repo, result) in results {
+            let result = result.and_then(|output| {
+                if !output.stdout.is_empty() || !output.stderr.is_empty() {
+                    writeln!(
+                        handle,
+                        "==> {}/{} <==",
+                        repo.collection(),
+                        repo.name_string()
+                    )?;
+                    handle.write_all(&output.stdout)?;
+                    handle.flush()?;
+                    io::stderr().write_all(&output.stderr)?;
+                }
+                ensure!(
+                    output.status.success(),
+                    "Command failed for {:?} ({})",
+                    repo.path(),
+                    output.status
+                );
+                Ok(())
+            });
+            if let Err(e) = result {
+                if !self.keep_going {
+                    return Err(e);
+                }
+                log::error!("{:#}", e);
+                num_errors += 1;
+            }
+        }
+
+        if num_errors != 0 {
+            bail!("Command failed for {} repositories", num_errors);
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs the command with the arguments expanded for the repository.
+///
+/// If `chdir` is true, the command runs in the directory of `{path}`.
+fn exec_repo(
+    repo: &RepoView,
+    envs: &CollectionEnvs,
+    command: &[String],
+    chdir: bool,
+) -> anyhow::Result<Output> {
+    let mut repo = repo.clone();
+    let has_workdir = repo
+        .resolve_workdir()
+        .context("Failed to open the repository")?;
+    let dir = match repo.workdir() {
+        Some(dir) if has_workdir => dir,
+        _ => repo.path(),
+    };
+    let args = command
+        .iter()
+        .map(|arg| expand_arg(arg, &repo, dir))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let (program, args) = args
+        .split_first()
+        .expect("Should never fail: the command is required");
+
+    let mut command = Command::new(program);
+    if let Some(env) = envs.get(repo.collection()) {
+        command.envs(env);
+    }
+    if chdir {
+        command.current_dir(dir);
+    }
+    command.args(args);
+    log::debug!("Running {:?}", command);
+    command
+        .output()
+        .with_context(|| format!("Failed to run {:?} for {:?}", program, dir))
+}
+
+/// Expands the variables in the command argument.
+///
+/// Unknown variables are kept as is.
+/// The URL of the remote is looked up only when `{remote}` is used.
+fn expand_arg(arg: &str, repo: &RepoView, dir: &Path) -> anyhow::Result<OsString> {
+    let mut remote_error = None;
+    let expanded = template::expand_os_lenient(arg, |var| match var {
+        "collection" => Some(Cow::Borrowed(OsStr::new(repo.collection().as_str()))),
+        "name" => Some(Cow::Owned(repo.name_string().into())),
+        "path" => Some(Cow::Borrowed(dir.as_os_str())),
+        "remote" => match repo.vcs().remote_url(repo.path(), "origin") {
+            Ok(url) => Some(Cow::Owned(url.unwrap_or_default().into())),
+            Err(e) => {
+                remote_error = Some(e);
+                Some(Cow::Borrowed(OsStr::new("")))
+            }
+        },
+        _ => None,
+    });
+    if let Some(e) = remote_error {
+        return Err(anyhow::Error::new(e).context("Failed to get the URL of `origin` remote"));
+    }
+
+    Ok(expanded)
+}
@@ -0,0 +1,238 @@
+//! `export` subcommand.
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt, fs,
+    io::{self, Write},
+    path::PathBuf,
+    str,
+};
+
+use anyhow::{anyhow, Context as _};
+use magro::{view::RepoView, Context};
+use structopt::StructOpt;
+
+use crate::{output::write_csv_record, path::repo_dir, select::RepoSelectOpt};
+
+/// Export format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExportFormat {
+    /// gita (`repos.csv`).
+    Gita,
+    /// ghorg (target repos file for `--target-repos-path`).
+    Ghorg,
+    /// myrepos (`.mrconfig`).
+    Myrepos,
+}
+
+impl ExportFormat {
+    /// Returns a list of possible options.
+    #[inline]
+    #[must_use]
+    fn possible_opt_values() -> &'static [&'static str] {
+        &["gita", "ghorg", "myrepos"]
+    }
+
+    /// Returns the option value.
+    #[inline]
+    #[must_use]
+    fn as_opt_value(&self) -> &'static str {
+        match self {
+            Self::Gita => "gita",
+            Self::Ghorg => "ghorg",
+            Self::Myrepos => "myrepos",
+        }
+    }
+
+    /// Parses the option value.
+    #[inline]
+    #[must_use]
+    fn from_opt_value(s: &str) -> Option<Self> {
+        match s {
+            "gita" => Some(Self::Gita),
+            "ghorg" => Some(Self::Ghorg),
+            "myrepos" => Some(Self::Myrepos),
+            _ => None,
+        }
+    }
+}
+
+impl str::FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_opt_value(s).ok_or_else(|| anyhow!("Unknown export format {:?}", s))
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_opt_value())
+    }
+}
+
+/// Options for `export` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct ExportOpt {
+    /// Export format.
+    ///
+    /// * `gita`: `repos.csv` for gita.
+    /// * `ghorg`: repository names list for `ghorg clone --target-repos-path`.
+    /// * `myrepos`: `.mrconfig` for myrepos.
+    #[structopt(possible_values = ExportFormat::possible_opt_values(), verbatim_doc_comment)]
+    format: ExportFormat,
+    /// Target repositories.
+    #[structopt(flatten)]
+    select: RepoSelectOpt,
+    /// Remote to get the URLs from.
+    #[structopt(long, default_value = "origin")]
+    remote: String,
+    /// File to write to.
+    ///
+    /// If not specified, the result is written to stdout.
+    #[structopt(long, short, parse(from_os_str))]
+    file: Option<PathBuf>,
+}
+
+impl ExportOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &Context) -> anyhow::Result<()> {
+        log::trace!(
+            "export format={}, select={:?}, remote={:?}, file={:?}",
+            self.format,
+            self.select,
+            self.remote,
+            self.file
+        );
+
+        let repos = self.select.select(context)?;
+        let mut content = Vec::new();
+        match self.format {
+            ExportFormat::Gita => export_gita(&mut content, &repos)?,
+            ExportFormat::Ghorg => export_ghorg(&mut content, &repos, &self.remote)?,
+            ExportFormat::Myrepos => export_myrepos(&mut content, &repos, &self.remote)?,
+        }
+
+        match &self.file {
+            Some(path) => fs::write(path, content)
+                .with_context(|| format!("Failed to write to {:?}", path))?,
+            None => io::stdout().write_all(&content)?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the URL of the remote, or logs a warning if unavailable.
+fn remote_url(repo: &RepoView, remote: &str) -> anyhow::Result<Option<String>> {
+    let url = repo
+        .vcs()
+        .remote_url(repo.path(), remote)
+        .with_context(|| format!("Failed to get the remote URL for {:?}", repo.path()))?;
+    if url.is_none() {
+        log::warn!("No remote {:?} found for {:?}", remote, repo.path());
+    }
+    Ok(url)
+}
+
+/// Writes gita `repos.csv`.
+///
+/// Repository names are basenames, or full names (with `/` replaced by `-`)
+/// if basenames conflict.
+fn export_gita<W: Write>(out: &mut W, repos: &[RepoView]) -> anyhow::Result<()> {
+    let basename = |repo: &RepoView| {
+        repo.name()
+            .file_name()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| repo.name_string())
+    };
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for repo in repos {
+        *counts.entry(basename(repo)).or_default() += 1;
+    }
+
+    for repo in repos {
+        let name = basename(repo);
+        let name = if counts[&name] > 1 {
+            repo.name_string().replace('/', "-")
+        } else {
+            name
+        };
+        // Columns are `path`, `name`, `type`, and `flags`.
+        write_gita_record(out, &[&repo_dir(repo)?.to_string_lossy(), &name, "", ""])?;
+    }
+
+    Ok(())
+}
+
+/// Writes a gita CSV record.
+///
+/// gita uses LF as the line separator, unlike `write_csv_record()`.
+fn write_gita_record<W: Write>(out: &mut W, fields: &[&str]) -> io::Result<()> {
+    let mut record = Vec::new();
+    write_csv_record(&mut record, fields)?;
+    if record.ends_with(b"\r\n") {
+        record.truncate(record.len() - 2);
+        record.push(b'\n');
+    }
+    out.write_all(&record)
+}
+
+/// Writes repository names for ghorg `--target-repos-path`.
+///
+/// Names are taken from the remote URLs, since ghorg refers to repositories
+/// by names on the forge.
+fn export_ghorg<W: Write>(out: &mut W, repos: &[RepoView], remote: &str) -> anyhow::Result<()> {
+    for repo in repos {
+        let url = match remote_url(repo, remote)? {
+            Some(v) => v,
+            None => continue,
+        };
+        let name = url
+            .trim_end_matches('/')
+            .rsplit(&['/', ':'][..])
+            .next()
+            .unwrap_or(&url);
+        let name = name.strip_suffix(".git").unwrap_or(name);
+        writeln!(out, "{}", name)?;
+    }
+
+    Ok(())
+}
+
+/// Writes myrepos `.mrconfig`.
+fn export_myrepos<W: Write>(out: &mut W, repos: &[RepoView], remote: &str) -> anyhow::Result<()> {
+    for (i, repo) in repos.iter().enumerate() {
+        let dir = repo_dir(repo)?;
+        if i != 0 {
+            writeln!(out)?;
+        }
+        writeln!(out, "[{}]", dir.display())?;
+        let url = match remote_url(repo, remote)? {
+            Some(v) => v,
+            None => continue,
+        };
+        let basename = dir
+            .file_name()
+            .map_or(Cow::Borrowed(""), |s| s.to_string_lossy());
+        let bare = dir == repo.path();
+        writeln!(
+            out,
+            "checkout = git clone {}{} {}",
+            if bare { "--bare " } else { "" },
+            shell_quote(&url),
+            shell_quote(&basename)
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Quotes the string for POSIX shells.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
@@ -0,0 +1,275 @@
+//! `fetch` subcommand.
+
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use anyhow::{bail, Context as _};
+use magro::{
+    collection::CollectionName, remote::url_host, runner::HostLimiter, vcs, view::RepoView, Context,
+};
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::GlobalOpt,
+    logging,
+    output::{write_csv_record, write_json, OutputFormat},
+    progress::{Event, EventKind},
+    resume::{self, ResumeOpt},
+    select::RepoSelectOpt,
+};
+
+/// Options for `fetch` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct FetchOpt {
+    /// Target repositories.
+    #[structopt(flatten)]
+    select: RepoSelectOpt,
+    /// Prunes remote-tracking branches deleted on the remotes.
+    #[structopt(long, short)]
+    prune_remotes: bool,
+    /// Fetches also frozen repositories (see `repo freeze`).
+    #[structopt(long)]
+    include_frozen: bool,
+    /// Runs the operation as possible even when errors are detected.
+    ///
+    /// Note that errors are ignored during the operation, but the program will
+    /// exit with failure (i.e. errors won't be completely ignored).
+    #[structopt(long)]
+    keep_going: bool,
+    /// Resuming options.
+    #[structopt(flatten)]
+    resume: ResumeOpt,
+}
+
+impl FetchOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &Context, global: &GlobalOpt) -> anyhow::Result<()> {
+        log::trace!(
+            "fetch select={:?}, prune_remotes={}, include_frozen={}, keep_going={}",
+            self.select,
+            self.prune_remotes,
+            self.include_frozen,
+            self.keep_going
+        );
+
+        let mut repos = self.select.select(context)?;
+        if !self.include_frozen {
+            skip_frozen(&mut repos);
+        }
+        let journal =
+            self.resume
+                .open_journal(context, "fetch", repos.iter().map(resume::repo_job_key))?;
+        let repos = resume::skip_completed(journal.as_deref(), repos);
+
+        let prune = self.prune_remotes;
+        let keep_going = self.keep_going;
+        let limiter = global.host_limiter(context);
+        let job_journal = journal.clone();
+        let envs = logging::CollectionEnvs::new(context);
+        let results = global.runner(context).run_until(
+            repos,
+            move |repo| {
+                logging::with_repo(repo, &envs, || {
+                    let num_pruned = fetch_repo(repo, prune, limiter.as_ref())?;
+                    resume::complete(job_journal.as_deref(), &resume::repo_job_key(repo));
+                    Ok(num_pruned)
+                })
+            },
+            move |result| !keep_going && result.is_err(),
+        );
+
+        let entries = results
+            .iter()
+            .map(|(repo, result)| FetchEntry::new(repo, result))
+            .collect::<Vec<_>>();
+        write_summary(&entries, global.output)?;
+
+        let num_errors = entries.iter().filter(|entry| entry.error.is_some()).count();
+        let num_pruned = entries.iter().map(|entry| entry.pruned).sum::<usize>();
+        log::info!(
+            "Fetched {} repositories ({} failed), and pruned {} remote-tracking branches",
+            entries.len() - num_errors,
+            num_errors,
+            num_pruned
+        );
+        if num_errors != 0 {
+            if !self.keep_going {
+                // The operation stopped at the first error.
+                let (_, result) = results
+                    .into_iter()
+                    .find(|(_, result)| result.is_err())
+                    .expect("Should never fail: an error exists");
+                return result.map(drop);
+            }
+            bail!("Failed to fetch {} repositories", num_errors);
+        }
+        resume::finish(journal);
+
+        Ok(())
+    }
+}
+
+/// Fetch result entry of the summary.
+#[derive(Debug, Serialize)]
+struct FetchEntry<'a> {
+    /// Collection name.
+    collection: &'a CollectionName,
+    /// Repository name.
+    name: String,
+    /// Absolute path of the repository.
+    path: &'a Path,
+    /// Number of pruned remote-tracking branches.
+    pruned: usize,
+    /// Error message, or `None` on success.
+    error: Option<String>,
+}
+
+impl<'a> FetchEntry<'a> {
+    /// Creates a new entry.
+    fn new(repo: &'a RepoView, result: &anyhow::Result<usize>) -> Self {
+        let (pruned, error) = match result {
+            Ok(num_pruned) => (*num_pruned, None),
+            Err(e) => (0, Some(format!("{:#}", e))),
+        };
+        Self {
+            collection: repo.collection(),
+            name: repo.name_string(),
+            path: repo.path(),
+            pruned,
+            error,
+        }
+    }
+}
+
+/// Writes the per-repository results to stdout.
+fn write_summary(entries: &[FetchEntry<'_>], format: OutputFormat) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    match format {
+        OutputFormat::Text => {
+            for entry in entries {
+                match &entry.error {
+                    None => write!(handle, "ok      {}/{}", entry.collection, entry.name)?,
+                    Some(e) => {
+                        write!(handle, "failed  {}/{}: {}", entry.collection, entry.name, e)?
+                    }
+                }
+                match entry.pruned {
+                    0 => writeln!(handle)?,
+                    pruned => writeln!(handle, " ({} pruned)", pruned)?,
+                }
+            }
+            let num_errors = entries.iter().filter(|entry| entry.error.is_some()).count();
+            let num_pruned = entries.iter().map(|entry| entry.pruned).sum::<usize>();
+            writeln!(
+                handle,
+                "total: {} ok, {} failed, {} pruned",
+                entries.len() - num_errors,
+                num_errors,
+                num_pruned
+            )?;
+        }
+        OutputFormat::Json => write_json(&mut handle, entries)?,
+        OutputFormat::Csv => {
+            write_csv_record(
+                &mut handle,
+                &["collection", "name", "path", "result", "pruned", "error"],
+            )?;
+            for entry in entries {
+                write_csv_record(
+                    &mut handle,
+                    &[
+                        entry.collection.as_str(),
+                        &entry.name,
+                        &entry.path.to_string_lossy(),
+                        if entry.error.is_some() {
+                            "failed"
+                        } else {
+                            "ok"
+                        },
+                        &entry.pruned.to_string(),
+                        entry.error.as_deref().unwrap_or_default(),
+                    ],
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Fetches the repository, and returns the number of pruned remote-tracking
+/// branches.
+///
+/// Concurrency is limited by the host of the first remote with a URL.
+fn fetch_repo(
+    repo: &RepoView,
+    prune: bool,
+    limiter: Option<&HostLimiter>,
+) -> anyhow::Result<usize> {
+    let collection_name = repo.collection();
+    let repo_name = repo.name_string();
+    let event = |kind| Event::new(kind, "fetch", collection_name).repo(&repo_name);
+    event(EventKind::Started).emit();
+    let result = fetch_repo_impl(repo, prune, limiter);
+    match &result {
+        Ok(_) => event(EventKind::Finished).emit(),
+        Err(e) => event(EventKind::Failed).error(e).emit(),
+    }
+
+    result
+}
+
+/// Fetches the repository without emitting progress events except percents.
+fn fetch_repo_impl(
+    repo: &RepoView,
+    prune: bool,
+    limiter: Option<&HostLimiter>,
+) -> anyhow::Result<usize> {
+    let vcs = repo.vcs();
+    let path = repo.path();
+    let _permit = match limiter {
+        Some(limiter) => {
+            let remotes = vcs
+                .remotes(path)
+                .with_context(|| format!("Failed to get remotes of {:?}", path))?;
+            remotes
+                .iter()
+                .find_map(|remote| remote.url().and_then(url_host))
+                .map(|host| limiter.acquire(host))
+        }
+        None => None,
+    };
+
+    log::debug!("Fetching {:?}", path);
+    let report_percent = {
+        let (collection_name, repo_name) = (repo.collection().clone(), repo.name_string());
+        move |percent| {
+            Event::new(EventKind::Percent, "fetch", &collection_name)
+                .repo(&repo_name)
+                .percent(percent)
+                .emit()
+        }
+    };
+    vcs::with_progress(report_percent, || {
+        if prune {
+            vcs.fetch_and_prune(path)
+        } else {
+            vcs.fetch(path).map(|()| 0)
+        }
+    })
+    .with_context(|| format!("Failed to fetch {:?}", path))
+}
+
+/// Removes frozen repositories from the targets.
+fn skip_frozen(repos: &mut Vec<RepoView>) {
+    let num_repos = repos.len();
+    repos.retain(|repo| !repo.metadata().is_frozen());
+    if repos.len() != num_repos {
+        log::info!("Skipping {} frozen repositories", num_repos - repos.len());
+    }
+}
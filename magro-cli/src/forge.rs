@@ -0,0 +1,307 @@
+//! `forge` subcommand.
+
+use std::{collections::HashMap, path::PathBuf};
+
+use anyhow::{bail, Context as _};
+use magro::{
+    collection::CollectionName, forge::github, metadata::Upstream, vcs::Vcs, view::RepoView,
+    Context,
+};
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::{GlobalOpt, OptionBool},
+    clone::ClonePlan,
+    logging,
+    resume::{self, ResumeOpt},
+    select::RepoSelectOpt,
+};
+
+/// Options for `forge` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct ForgeOpt {
+    /// Subcommand.
+    #[structopt(subcommand)]
+    subcommand: Subcommand,
+}
+
+impl ForgeOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &mut Context, global: &GlobalOpt) -> anyhow::Result<()> {
+        match &self.subcommand {
+            Subcommand::Clone(opt) => opt.run(context),
+            Subcommand::Enrich { select, keep_going } => {
+                log::trace!(
+                    "forge enrich select={:?}, keep_going={}",
+                    select,
+                    keep_going
+                );
+                enrich(context, global, select, *keep_going)
+            }
+        }
+    }
+}
+
+/// Subcommand of `forge`.
+#[derive(Debug, Clone, StructOpt)]
+pub enum Subcommand {
+    /// Clones repositories listed on GitHub.
+    ///
+    /// Repositories which already exist at the destination are skipped.
+    ///
+    /// API token is read from `GITHUB_TOKEN` environment variable.
+    Clone(ForgeCloneOpt),
+    /// Fetches metadata of the upstream repositories on GitHub into the cache.
+    ///
+    /// Repositories whose `origin` remotes (or source URIs) point to GitHub
+    /// are enriched with their descriptions, stars, and whether they are
+    /// archived or forks. The stored metadata is used by `list
+    /// --exclude-archived-upstream` and `stats` until fetched again.
+    ///
+    /// API token is read from `GITHUB_TOKEN` environment variable.
+    Enrich {
+        /// Target repositories.
+        #[structopt(flatten)]
+        select: RepoSelectOpt,
+        /// Runs the operation as possible even when errors are detected.
+        ///
+        /// Note that errors are ignored during the operation, but the program
+        /// will exit with failure (i.e. errors won't be completely ignored).
+        #[structopt(long)]
+        keep_going: bool,
+    },
+}
+
+/// Options for `forge clone` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+pub struct ForgeCloneOpt {
+    /// Clones repositories starred by the user.
+    ///
+    /// If the user name is omitted, the authenticated user (by the token) is used.
+    #[structopt(long, required_unless = "query", conflicts_with = "query")]
+    starred: Option<Option<String>>,
+    /// Clones repositories matching the search query (for example, `topic:rust`).
+    ///
+    /// GitHub serves only the first 1000 results.
+    #[structopt(long)]
+    query: Option<String>,
+    /// Collection to put the cloned repositories.
+    #[structopt(long, short)]
+    collection: Option<CollectionName>,
+    /// Uses SSH URLs instead of HTTPS URLs.
+    ///
+    /// `clone-protocol` setting of the collection takes precedence over this.
+    #[structopt(long)]
+    ssh: bool,
+    /// Whether to clone bare repositories.
+    #[structopt(
+        long,
+        possible_values = OptionBool::possible_opt_values(),
+        default_value = "auto",
+    )]
+    bare: OptionBool,
+    /// Runs the operation as possible even when errors are detected.
+    ///
+    /// Note that errors are ignored during the operation, but the program will
+    /// exit with failure (i.e. errors won't be completely ignored).
+    #[structopt(long)]
+    keep_going: bool,
+    /// Only prints the repositories to be cloned.
+    #[structopt(long)]
+    dry_run: bool,
+    /// Resuming options.
+    #[structopt(flatten)]
+    resume: ResumeOpt,
+}
+
+impl ForgeCloneOpt {
+    /// Runs the actual operation.
+    fn run(&self, context: &mut Context) -> anyhow::Result<()> {
+        log::trace!(
+            "forge clone starred={:?}, query={:?}, collection={:?}, ssh={}, bare={}, \
+             keep_going={}, dry_run={}",
+            self.starred,
+            self.query,
+            self.collection,
+            self.ssh,
+            self.bare,
+            self.keep_going,
+            self.dry_run
+        );
+
+        let client = github::Client::from_env();
+        let repos = match (&self.starred, &self.query) {
+            (Some(user), _) => client.starred(user.as_deref()),
+            (None, Some(query)) => client.search(query),
+            (None, None) => unreachable!("Either `--starred` or `--query` is required"),
+        }
+        .context("Failed to get the list of repositories")?;
+        log::debug!("Found {} repositories", repos.len());
+        let journal = if self.dry_run {
+            None
+        } else {
+            self.resume.open_journal(
+                context,
+                "forge-clone",
+                repos.iter().map(|repo| repo.full_name()),
+            )?
+        };
+
+        let mut num_errors = 0_usize;
+        for repo in &repos {
+            if journal
+                .as_ref()
+                .map_or(false, |journal| journal.is_completed(repo.full_name()))
+            {
+                continue;
+            }
+            let uri = if self.ssh {
+                repo.ssh_url()
+            } else {
+                repo.clone_url()
+            };
+            let result = ClonePlan::new(
+                context,
+                uri,
+                self.collection.as_ref(),
+                None,
+                Some(Vcs::Git),
+                self.bare,
+            )
+            .and_then(|plan| {
+                if plan.destination().exists() {
+                    log::debug!(
+                        "Skipping {} because {:?} already exists",
+                        repo.full_name(),
+                        plan.destination()
+                    );
+                    return Ok(());
+                }
+                if self.dry_run {
+                    println!("{} -> {}", plan.uri(), plan.destination().display());
+                    return Ok(());
+                }
+                log::info!("Cloning {}", repo.full_name());
+                plan.run(context)
+            });
+            match result {
+                Ok(()) => resume::complete(journal.as_deref(), repo.full_name()),
+                Err(e) => {
+                    if !self.keep_going {
+                        return Err(e);
+                    }
+                    log::error!("Failed to clone {}: {:#}", repo.full_name(), e);
+                    num_errors += 1;
+                }
+            }
+        }
+
+        if num_errors != 0 {
+            bail!("Failed to clone {} repositories", num_errors);
+        }
+        resume::finish(journal);
+
+        Ok(())
+    }
+}
+
+/// Fetches metadata of the upstream repositories, and stores them into the cache.
+fn enrich(
+    context: &mut Context,
+    global: &GlobalOpt,
+    select: &RepoSelectOpt,
+    keep_going: bool,
+) -> anyhow::Result<()> {
+    context
+        .ensure_writable()
+        .context("Cannot enrich the cache")?;
+    let repos = select.select(context)?;
+    let client = github::Client::from_env();
+    let envs = logging::CollectionEnvs::new(context);
+    let results = global.runner(context).run_until(
+        repos,
+        move |repo| {
+            logging::with_repo(repo, &envs, || {
+                let full_name = match github_full_name(repo)? {
+                    Some(v) => v,
+                    None => {
+                        log::debug!("Skipping {:?} not hosted on GitHub", repo.path());
+                        return Ok(None);
+                    }
+                };
+                log::debug!("Fetching metadata of {} for {:?}", full_name, repo.path());
+                let remote = client
+                    .repo(&full_name)
+                    .with_context(|| format!("Failed to fetch metadata of {}", full_name))?;
+                Ok(Some(Upstream::from_remote(&remote)))
+            })
+        },
+        move |result| !keep_going && result.is_err(),
+    );
+
+    let mut enriched: HashMap<(CollectionName, PathBuf), Option<Upstream>> = HashMap::new();
+    let mut num_errors = 0_usize;
+    for (repo, result) in results {
+        match result {
+            Ok(upstream) => {
+                enriched.insert(
+                    (repo.collection().clone(), repo.relative_path().to_owned()),
+                    upstream,
+                );
+            }
+            Err(e) => {
+                if !keep_going {
+                    return Err(e);
+                }
+                log::error!("{:#}", e);
+                num_errors += 1;
+            }
+        }
+    }
+
+    log::info!(
+        "Fetched metadata of {} repositories",
+        enriched
+            .values()
+            .filter(|upstream| upstream.is_some())
+            .count()
+    );
+    context
+        .update_cache(|cache| {
+            for ((collection, path), upstream) in enriched {
+                if let Some(coll_cache) = cache.collection_repos_mut(&collection) {
+                    coll_cache.update_entry(&path, |entry| {
+                        entry.metadata_mut().set_upstream(upstream);
+                    });
+                }
+            }
+        })
+        .context("Failed to update cache file")?;
+
+    if num_errors != 0 {
+        bail!("Failed to enrich {} repositories", num_errors);
+    }
+
+    Ok(())
+}
+
+/// Returns the full name of the upstream repository on GitHub, if available.
+///
+/// The URL of `origin` remote is used if available, and the source URI
+/// recorded on clone is used otherwise.
+fn github_full_name(repo: &RepoView) -> anyhow::Result<Option<String>> {
+    let url = if repo.path().exists() {
+        repo.vcs()
+            .remote_url(repo.path(), "origin")
+            .with_context(|| format!("Failed to get the URL of `origin` of {:?}", repo.path()))?
+    } else {
+        None
+    };
+    let url = match url.as_deref().or_else(|| repo.source_uri()) {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+
+    Ok(github::repo_full_name(url))
+}
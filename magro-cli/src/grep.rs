@@ -0,0 +1,295 @@
+//! `grep` subcommand.
+
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Arc,
+};
+
+use anyhow::{bail, Context as _};
+use magro::{collection::CollectionName, view::RepoView, Context};
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::GlobalOpt,
+    logging::{self, CollectionEnvs},
+    output::{write_csv_record, write_json, OutputFormat},
+    select::RepoSelectOpt,
+};
+
+/// Options for `grep` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct GrepOpt {
+    /// Target repositories.
+    #[structopt(flatten)]
+    select: RepoSelectOpt,
+    /// Ignores case differences.
+    #[structopt(long, short)]
+    ignore_case: bool,
+    /// Treats the pattern as a fixed string, not a regular expression.
+    #[structopt(long, short = "F")]
+    fixed_strings: bool,
+    /// Matches the pattern only at word boundaries.
+    #[structopt(long, short)]
+    word_regexp: bool,
+    /// Shows only the names of files with matches.
+    #[structopt(long, short = "l")]
+    files_with_matches: bool,
+    /// Searches also untracked files (excluding ignored files).
+    #[structopt(long)]
+    untracked: bool,
+    /// Runs the operation as possible even when errors are detected.
+    ///
+    /// Note that errors are ignored during the operation, but the program will
+    /// exit with failure (i.e. errors won't be completely ignored).
+    #[structopt(long)]
+    keep_going: bool,
+    /// Pattern (extended regular expression unless `--fixed-strings` is specified).
+    pattern: String,
+    /// Limits the search to the paths (git pathspecs) in each repository.
+    #[structopt(last = true)]
+    pathspecs: Vec<String>,
+}
+
+impl GrepOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &Context, global: &GlobalOpt) -> anyhow::Result<()> {
+        log::trace!(
+            "grep select={:?}, ignore_case={}, fixed_strings={}, word_regexp={}, \
+             files_with_matches={}, untracked={}, keep_going={}, pattern={:?}, pathspecs={:?}",
+            self.select,
+            self.ignore_case,
+            self.fixed_strings,
+            self.word_regexp,
+            self.files_with_matches,
+            self.untracked,
+            self.keep_going,
+            self.pattern,
+            self.pathspecs
+        );
+
+        let repos = self.select.select(context)?;
+        let keep_going = self.keep_going;
+        let opt = Arc::new(self.clone());
+        let envs = logging::CollectionEnvs::new(context);
+        let results = global.runner(context).run_until(
+            repos,
+            move |repo| logging::with_repo(repo, &envs, || grep_repo(repo, &envs, &opt)),
+            move |result| !keep_going && result.is_err(),
+        );
+
+        let mut num_errors = 0_usize;
+        let mut searched = Vec::with_capacity(results.len());
+        for (repo, result) in results {
+            match result {
+                Ok(matches) => searched.push((repo, matches)),
+                Err(e) => {
+                    if !self.keep_going {
+                        return Err(e.context(format!("Failed to search {:?}", repo.path())));
+                    }
+                    log::error!("Failed to search {:?}: {:#}", repo.path(), e);
+                    num_errors += 1;
+                }
+            }
+        }
+
+        let entries = searched
+            .iter()
+            .flat_map(|(repo, matches)| matches.iter().map(move |m| MatchEntry::new(repo, m)))
+            .collect::<Vec<_>>();
+
+        let stdout = io::stdout();
+        let mut handle = stdout.lock();
+        match global.output {
+            OutputFormat::Text => {
+                for entry in &entries {
+                    write!(
+                        handle,
+                        "{}/{}/{}",
+                        entry.collection,
+                        entry.name,
+                        entry.file.display()
+                    )?;
+                    match (entry.line, entry.text) {
+                        (Some(line), Some(text)) => writeln!(handle, ":{}:{}", line, text)?,
+                        _ => writeln!(handle)?,
+                    }
+                }
+            }
+            OutputFormat::Json => write_json(&mut handle, &entries)?,
+            OutputFormat::Csv => {
+                write_csv_record(
+                    &mut handle,
+                    &["collection", "name", "path", "file", "line", "text"],
+                )?;
+                for entry in &entries {
+                    write_csv_record(
+                        &mut handle,
+                        &[
+                            entry.collection.as_str(),
+                            &entry.name,
+                            &entry.path.to_string_lossy(),
+                            &entry.file.to_string_lossy(),
+                            &entry.line.map_or_else(String::new, |line| line.to_string()),
+                            entry.text.unwrap_or_default(),
+                        ],
+                    )?;
+                }
+            }
+        }
+
+        if num_errors != 0 {
+            bail!("Failed to search {} repositories", num_errors);
+        }
+
+        Ok(())
+    }
+}
+
+/// Match in a repository.
+#[derive(Debug)]
+struct Match {
+    /// File path relative to the working directory.
+    file: PathBuf,
+    /// Line number and the content, or `None` for `--files-with-matches`.
+    line: Option<(u64, String)>,
+}
+
+/// Searches the working directory of the repository with `git grep`.
+///
+/// Bare repositories are skipped.
+fn grep_repo(repo: &RepoView, envs: &CollectionEnvs, opt: &GrepOpt) -> anyhow::Result<Vec<Match>> {
+    let mut repo = repo.clone();
+    let has_workdir = repo
+        .resolve_workdir()
+        .context("Failed to open the repository")?;
+    let workdir = match repo.workdir() {
+        Some(dir) if has_workdir => dir,
+        _ => {
+            log::debug!("Skipping the bare repository {:?}", repo.path());
+            return Ok(Vec::new());
+        }
+    };
+
+    let mut command = Command::new("git");
+    if let Some(env) = envs.get(repo.collection()) {
+        command.envs(env);
+    }
+    command
+        .arg("-C")
+        .arg(workdir)
+        .args(["grep", "--no-color", "-I", "-z", "-E"]);
+    if opt.files_with_matches {
+        command.arg("-l");
+    } else {
+        command.arg("-n");
+    }
+    if opt.ignore_case {
+        command.arg("-i");
+    }
+    if opt.fixed_strings {
+        command.arg("-F");
+    }
+    if opt.word_regexp {
+        command.arg("-w");
+    }
+    if opt.untracked {
+        command.arg("--untracked");
+    }
+    command
+        .arg("-e")
+        .arg(&opt.pattern)
+        .arg("--")
+        .args(&opt.pathspecs);
+    log::debug!("Running {:?}", command);
+    let output = command.output().context("Failed to run git")?;
+    // `git grep` exits with 1 when nothing matched.
+    if output.status.code() == Some(1) && output.stderr.is_empty() {
+        return Ok(Vec::new());
+    }
+    if !output.status.success() {
+        bail!(
+            "git grep failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let matches = if opt.files_with_matches {
+        stdout
+            .split('\0')
+            .filter(|file| !file.is_empty())
+            .map(|file| Match {
+                file: file.into(),
+                line: None,
+            })
+            .collect()
+    } else {
+        stdout.lines().filter_map(parse_match_line).collect()
+    };
+
+    Ok(matches)
+}
+
+/// Parses a line of `git grep -n -z` output.
+fn parse_match_line(line: &str) -> Option<Match> {
+    let mut fields = line.splitn(3, '\0');
+    let file = fields.next()?;
+    let line = fields.next()?.parse().ok()?;
+    let text = fields.next()?;
+
+    Some(Match {
+        file: file.into(),
+        line: Some((line, text.to_owned())),
+    })
+}
+
+/// Match entry of the report.
+#[derive(Debug, Serialize)]
+struct MatchEntry<'a> {
+    /// Collection name.
+    collection: &'a CollectionName,
+    /// Repository name.
+    name: String,
+    /// Absolute path of the repository.
+    path: &'a Path,
+    /// File path relative to the working directory.
+    file: &'a Path,
+    /// Line number.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<u64>,
+    /// Content of the line.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<&'a str>,
+}
+
+impl<'a> MatchEntry<'a> {
+    /// Creates a new entry.
+    fn new(repo: &'a RepoView, m: &'a Match) -> Self {
+        Self {
+            collection: repo.collection(),
+            name: repo.name_string(),
+            path: repo.path(),
+            file: &m.file,
+            line: m.line.as_ref().map(|(line, _)| *line),
+            text: m.line.as_ref().map(|(_, text)| text.as_str()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_grep_output() {
+        let m = parse_match_line("src/main.rs\u{0}12\u{0}fn main() {\u{0}}").unwrap();
+        assert_eq!(m.file, Path::new("src/main.rs"));
+        assert_eq!(m.line, Some((12, "fn main() {\u{0}}".to_owned())));
+        assert!(parse_match_line("Binary file matches").is_none());
+    }
+}
@@ -0,0 +1,172 @@
+//! `health` subcommand.
+
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use anyhow::bail;
+use magro::{
+    collection::CollectionName,
+    health::{HealthIssue, Severity, TagCheck},
+    view::RepoView,
+    Context,
+};
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::GlobalOpt,
+    logging,
+    output::{write_csv_record, write_json, OutputFormat},
+    select::RepoSelectOpt,
+};
+
+/// Options for `health` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct HealthOpt {
+    /// Target repositories.
+    #[structopt(flatten)]
+    select: RepoSelectOpt,
+    /// Runs the operation as possible even when errors are detected.
+    ///
+    /// Note that errors are ignored during the operation, but the program will
+    /// exit with failure (i.e. errors won't be completely ignored).
+    #[structopt(long)]
+    keep_going: bool,
+    /// Compares tags with the tags on the remotes.
+    ///
+    /// By default, tags pointing to commits not reachable from any
+    /// remote-tracking branch are reported, without accessing the remotes.
+    /// With this option, tags missing on all remotes (or pointing to
+    /// different objects there) are reported, and remotes whose tags cannot
+    /// be listed are also reported.
+    #[structopt(long)]
+    check_remote_tags: bool,
+}
+
+impl HealthOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &Context, global: &GlobalOpt) -> anyhow::Result<()> {
+        log::trace!(
+            "health select={:?}, keep_going={}, check_remote_tags={}",
+            self.select,
+            self.keep_going,
+            self.check_remote_tags
+        );
+
+        let repos = self.select.select(context)?;
+        let keep_going = self.keep_going;
+        let tags = if self.check_remote_tags {
+            TagCheck::Remote
+        } else {
+            TagCheck::Local
+        };
+        let envs = logging::CollectionEnvs::new(context);
+        let results = global.runner(context).run_until(
+            repos,
+            move |repo| {
+                logging::with_repo(repo, &envs, || repo.vcs().health_issues(repo.path(), tags))
+            },
+            move |result| !keep_going && result.is_err(),
+        );
+
+        let mut checked = Vec::with_capacity(results.len());
+        let mut num_errors = 0_usize;
+        for (repo, result) in results {
+            match result {
+                Ok(issues) => checked.push((repo, issues)),
+                Err(e) => {
+                    if !self.keep_going {
+                        return Err(anyhow::Error::new(e)
+                            .context(format!("Failed to check the repository {:?}", repo.path())));
+                    }
+                    log::error!("Failed to check the repository {:?}: {}", repo.path(), e);
+                    num_errors += 1;
+                }
+            }
+        }
+
+        let mut entries = checked
+            .iter()
+            .flat_map(|(repo, issues)| issues.iter().map(move |issue| IssueEntry::new(repo, issue)))
+            .collect::<Vec<_>>();
+        // Most severe issues first. Note that the sort is stable.
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.severity));
+
+        let stdout = io::stdout();
+        let mut handle = stdout.lock();
+        match global.output {
+            OutputFormat::Text => {
+                for entry in &entries {
+                    writeln!(
+                        handle,
+                        "{:<6}  {}/{}: {}",
+                        entry.severity.name_lower(),
+                        entry.collection,
+                        entry.name,
+                        entry.message
+                    )?;
+                }
+            }
+            OutputFormat::Json => write_json(&mut handle, &entries)?,
+            OutputFormat::Csv => {
+                write_csv_record(
+                    &mut handle,
+                    &["collection", "name", "path", "severity", "message"],
+                )?;
+                for entry in &entries {
+                    write_csv_record(
+                        &mut handle,
+                        &[
+                            entry.collection.as_str(),
+                            &entry.name,
+                            &entry.path.to_string_lossy(),
+                            entry.severity.name_lower(),
+                            &entry.message,
+                        ],
+                    )?;
+                }
+            }
+        }
+
+        if num_errors != 0 {
+            bail!("Failed to check {} repositories", num_errors);
+        }
+
+        Ok(())
+    }
+}
+
+/// Health issue entry of the report.
+#[derive(Debug, Serialize)]
+struct IssueEntry<'a> {
+    /// Collection name.
+    collection: &'a CollectionName,
+    /// Repository name.
+    name: String,
+    /// Absolute path of the repository.
+    path: &'a Path,
+    /// Severity.
+    severity: Severity,
+    /// Issue.
+    #[serde(flatten)]
+    issue: &'a HealthIssue,
+    /// Human-readable message.
+    message: String,
+}
+
+impl<'a> IssueEntry<'a> {
+    /// Creates a new entry.
+    fn new(repo: &'a RepoView, issue: &'a HealthIssue) -> Self {
+        Self {
+            collection: repo.collection(),
+            name: repo.name_string(),
+            path: repo.path(),
+            severity: issue.severity(),
+            issue,
+            message: issue.to_string(),
+        }
+    }
+}
@@ -0,0 +1,292 @@
+//! `import` subcommand.
+
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context as _};
+use magro::{collection::CollectionName, vcs::Vcs, view::RepoView, Context};
+use structopt::StructOpt;
+
+use crate::{cli_opt::OptionBool, clone::ClonePlan, select::select_repos};
+
+/// Options for `import` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct ImportOpt {
+    /// Manifest file to read.
+    ///
+    /// If not specified or `-`, the manifest is read from stdin.
+    #[structopt(parse(from_os_str))]
+    file: Option<PathBuf>,
+    /// Collection to mirror the manifest into.
+    #[structopt(long, short)]
+    collection: Option<CollectionName>,
+    /// Base URL of repositories listed by names (for example,
+    /// `git@gitolite.example.com:`).
+    ///
+    /// Lines without URLs (such as `gitolite info` output and ghorg target
+    /// repos files) are appended to the base URL.
+    #[structopt(long)]
+    base_url: Option<String>,
+    /// VCS of the repositories.
+    #[structopt(
+        long,
+        possible_values = &Vcs::variants().map(|v| v.name_lower()).collect::<Vec<_>>(),
+        default_value = "git",
+    )]
+    vcs: Vcs,
+    /// Whether to clone bare repositories.
+    #[structopt(
+        long,
+        possible_values = OptionBool::possible_opt_values(),
+        default_value = "auto",
+    )]
+    bare: OptionBool,
+    /// Runs the operation as possible even when errors are detected.
+    ///
+    /// Note that errors are ignored during the operation, but the program will
+    /// exit with failure (i.e. errors won't be completely ignored).
+    #[structopt(long)]
+    keep_going: bool,
+    /// Only prints the repositories to be cloned and the extraneous ones.
+    #[structopt(long)]
+    dry_run: bool,
+}
+
+impl ImportOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &mut Context) -> anyhow::Result<()> {
+        log::trace!(
+            "import file={:?}, collection={:?}, base_url={:?}, vcs={}, bare={}, keep_going={}, \
+             dry_run={}",
+            self.file,
+            self.collection,
+            self.base_url,
+            self.vcs.name_lower(),
+            self.bare,
+            self.keep_going,
+            self.dry_run
+        );
+
+        let content = read_manifest(self.file.as_deref())?;
+        let urls = parse_manifest(&content, self.base_url.as_deref())?;
+        log::debug!("Found {} repositories in the manifest", urls.len());
+
+        let mut expected = HashSet::new();
+        let mut num_errors = 0_usize;
+        for url in &urls {
+            let result = ClonePlan::new(
+                context,
+                url,
+                self.collection.as_ref(),
+                None,
+                Some(self.vcs),
+                self.bare,
+            )
+            .and_then(|plan| {
+                expected.insert(plan.destination().to_owned());
+                if plan.destination().exists() {
+                    log::debug!(
+                        "Skipping {} because {:?} already exists",
+                        url,
+                        plan.destination()
+                    );
+                    return Ok(());
+                }
+                if self.dry_run {
+                    println!("{} -> {}", url, plan.destination().display());
+                    return Ok(());
+                }
+                log::info!("Cloning {}", url);
+                plan.run(context)
+            });
+            if let Err(e) = result {
+                if !self.keep_going {
+                    return Err(e);
+                }
+                log::error!("Failed to clone {}: {:#}", url, e);
+                num_errors += 1;
+            }
+        }
+
+        let collection = context
+            .config()
+            .target_collection(self.collection.as_ref())?;
+        let repos = select_repos(context, &[collection.name()], &[], &[])?;
+        let extraneous = repos
+            .iter()
+            .filter(|repo| !expected.contains(&repo_dest(repo)))
+            .collect::<Vec<_>>();
+        if !extraneous.is_empty() {
+            log::warn!(
+                "{} repositories in `{}` collection are not in the manifest",
+                extraneous.len(),
+                collection.name()
+            );
+            for repo in extraneous {
+                println!("extraneous: {}", repo_dest(repo).display());
+            }
+        }
+
+        if num_errors != 0 {
+            bail!("Failed to clone {} repositories", num_errors);
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads the manifest from the file, or from stdin if the path is `None` or `-`.
+fn read_manifest(path: Option<&Path>) -> anyhow::Result<String> {
+    match path {
+        Some(path) if path != Path::new("-") => fs::read_to_string(path)
+            .with_context(|| format!("Failed to read the manifest {:?}", path)),
+        _ => {
+            let mut content = String::new();
+            io::stdin()
+                .read_to_string(&mut content)
+                .context("Failed to read the manifest from stdin")?;
+            Ok(content)
+        }
+    }
+}
+
+/// Returns the clone destination of the repository, i.e. the working
+/// directory or the bare repository itself.
+///
+/// The cached path is used as is, so that missing repositories are also
+/// compared.
+fn repo_dest(repo: &RepoView) -> PathBuf {
+    let path = repo.path();
+    match path.file_name().and_then(|name| name.to_str()) {
+        Some(".git" | ".hg" | ".jj" | ".pijul" | "_darcs") => {
+            path.parent().unwrap_or(path).to_owned()
+        }
+        _ => path.to_owned(),
+    }
+}
+
+/// Extracts clone URLs from the manifest.
+///
+/// Each line is a URL, or a line with a URL as a field (such as ghorg
+/// output). Empty lines, lines starting with `#`, and the greeting line of
+/// `gitolite info` are ignored.
+/// If the base URL is given, the last field of lines without URLs is treated
+/// as a repository name relative to the base URL.
+fn parse_manifest(content: &str, base_url: Option<&str>) -> anyhow::Result<Vec<String>> {
+    let mut urls = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || is_gitolite_greeting(line) {
+            continue;
+        }
+        let fields = || {
+            line.split_whitespace()
+                .map(|field| field.trim_end_matches(&[',', ';'][..]))
+        };
+        let url = match (fields().find(|field| is_url(field)), base_url) {
+            (Some(url), _) => url.to_owned(),
+            (None, Some(base_url)) => {
+                let name = fields()
+                    .next_back()
+                    .expect("Should never fail: the line is not empty");
+                join_url(base_url, name)
+            }
+            (None, None) => bail!(
+                "No URL found at line {} of the manifest (use `--base-url` for names): {:?}",
+                i + 1,
+                line
+            ),
+        };
+        if !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+
+    Ok(urls)
+}
+
+/// Returns true if the line is the greeting line of `gitolite info`.
+fn is_gitolite_greeting(line: &str) -> bool {
+    line.starts_with("hello ") && line.contains(" running gitolite")
+}
+
+/// Returns true if the string looks like a clone URL.
+///
+/// URLs with schemes (such as `https://`) and scp-like syntax
+/// (`user@host:path`) are recognized.
+fn is_url(s: &str) -> bool {
+    if let Some(scheme_end) = s.find("://") {
+        return scheme_end != 0
+            && s[..scheme_end]
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    }
+    match (s.find('@'), s.find(':')) {
+        (Some(at), Some(colon)) => {
+            at != 0 && at + 1 < colon && colon + 1 < s.len() && !s[..colon].contains('/')
+        }
+        _ => false,
+    }
+}
+
+/// Appends the repository name to the base URL.
+fn join_url(base_url: &str, name: &str) -> String {
+    if base_url.ends_with(&['/', ':'][..]) {
+        format!("{}{}", base_url, name)
+    } else {
+        format!("{}/{}", base_url, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url_lists() {
+        let content = "\
+# Comment.
+https://github.com/lo48576/magro.git
+
+git@github.com:lo48576/dotfiles.git
+https://github.com/lo48576/magro.git
+";
+        assert_eq!(
+            parse_manifest(content, None).unwrap(),
+            [
+                "https://github.com/lo48576/magro.git",
+                "git@github.com:lo48576/dotfiles.git"
+            ]
+        );
+    }
+
+    #[test]
+    fn urls_in_fields() {
+        let content = "Success cloning repo: https://github.com/org/foo.git, branch: main\n";
+        assert_eq!(
+            parse_manifest(content, None).unwrap(),
+            ["https://github.com/org/foo.git"]
+        );
+    }
+
+    #[test]
+    fn names_with_base_url() {
+        // `ssh git@host info` output of gitolite.
+        let content = "hello admin, this is git@host running gitolite3\n\n R W\tfoo\n R\tbar/baz\n";
+        assert!(parse_manifest(content, None).is_err());
+        assert!(parse_manifest("foo\n", None).is_err());
+        assert_eq!(
+            parse_manifest(content, Some("git@host:")).unwrap(),
+            ["git@host:foo", "git@host:bar/baz"]
+        );
+        assert_eq!(
+            parse_manifest("foo\n", Some("https://example.com/org")).unwrap(),
+            ["https://example.com/org/foo"]
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! `linkfarm` subcommand.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context as _};
+use magro::{permissions, query, Context};
+use structopt::StructOpt;
+
+use crate::{path::repo_dir, select::RepoSelectOpt};
+
+/// Options for `linkfarm` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct LinkfarmOpt {
+    /// Target repositories.
+    #[structopt(flatten)]
+    select: RepoSelectOpt,
+    /// Only prints the changes to the directory.
+    #[structopt(long)]
+    dry_run: bool,
+    /// Directory to put symbolic links.
+    ///
+    /// The directory is created if it does not exist.
+    #[structopt(parse(from_os_str))]
+    dir: PathBuf,
+    /// Targets only repositories matching the query.
+    filter: Option<String>,
+}
+
+impl LinkfarmOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &Context) -> anyhow::Result<()> {
+        log::trace!(
+            "linkfarm select={:?}, dry_run={}, dir={:?}, filter={:?}",
+            self.select,
+            self.dry_run,
+            self.dir,
+            self.filter
+        );
+
+        let mut repos = self.select.select(context)?;
+        if let Some(filter) = &self.filter {
+            repos = query::find(&repos, filter).into_iter().cloned().collect();
+        }
+        repos.retain(|repo| {
+            let exists = repo.path().exists();
+            if !exists {
+                log::debug!("Skipping the missing repository {:?}", repo.path());
+            }
+            exists
+        });
+        let names = flat_names(
+            &repos
+                .iter()
+                .map(|repo| (repo.collection().as_str(), repo.name_string()))
+                .collect::<Vec<_>>(),
+        );
+        let mut links = BTreeMap::new();
+        for (repo, name) in repos.iter().zip(names) {
+            links.insert(OsString::from(name), repo_dir(repo)?);
+        }
+
+        if !self.dry_run {
+            permissions::create_dir_all(&self.dir, context.config().main().dir_mode())
+                .with_context(|| format!("Failed to create directory {:?}", self.dir))?;
+        }
+        let existing = existing_entries(&self.dir)
+            .with_context(|| format!("Failed to read directory {:?}", self.dir))?;
+
+        // Remove stale links.
+        for (name, target) in &existing {
+            if target.is_none() || links.contains_key(name) {
+                continue;
+            }
+            let path = self.dir.join(name);
+            if self.dry_run {
+                println!("remove {}", path.display());
+                continue;
+            }
+            log::info!("Removing the stale link {:?}", path);
+            remove_link(&path).with_context(|| format!("Failed to remove {:?}", path))?;
+        }
+
+        // Create or update links.
+        let mut num_skipped = 0_usize;
+        for (name, target) in &links {
+            let path = self.dir.join(name);
+            match existing.get(name) {
+                Some(Some(current)) if current == target => continue,
+                Some(Some(_)) => {
+                    if self.dry_run {
+                        println!("update {} -> {}", path.display(), target.display());
+                        continue;
+                    }
+                    log::info!("Updating the link {:?} to {:?}", path, target);
+                    remove_link(&path).with_context(|| format!("Failed to remove {:?}", path))?;
+                }
+                Some(None) => {
+                    log::warn!("Skipping {:?} because it exists and is not a link", path);
+                    num_skipped += 1;
+                    continue;
+                }
+                None => {
+                    if self.dry_run {
+                        println!("create {} -> {}", path.display(), target.display());
+                        continue;
+                    }
+                    log::info!("Creating the link {:?} to {:?}", path, target);
+                }
+            }
+            create_link(target, &path)
+                .with_context(|| format!("Failed to create the link {:?}", path))?;
+        }
+
+        if num_skipped != 0 {
+            bail!(
+                "Failed to create {} links because of existing files",
+                num_skipped
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the names of links for the repositories.
+///
+/// The last component of the repository name is used if unique.
+/// Otherwise the whole name is used with `/` replaced by `-`, prefixed by the
+/// collection name if it is still ambiguous.
+fn flat_names(repos: &[(&str, String)]) -> Vec<String> {
+    let count = |names: &[String]| {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for name in names {
+            *counts.entry(name.clone()).or_default() += 1;
+        }
+        counts
+    };
+
+    let full_names = repos
+        .iter()
+        .map(|(_, name)| name.replace('/', "-"))
+        .collect::<Vec<_>>();
+    let full_counts = count(&full_names);
+    let base_names = repos
+        .iter()
+        .map(|(_, name)| name.rsplit('/').next().unwrap_or(name).to_owned())
+        .collect::<Vec<_>>();
+    let base_counts = count(&base_names);
+
+    repos
+        .iter()
+        .zip(base_names)
+        .zip(full_names)
+        .map(|(((collection, _), base), full)| {
+            if base_counts[&base] == 1 {
+                base
+            } else if full_counts[&full] == 1 {
+                full
+            } else {
+                format!("{}-{}", collection, full)
+            }
+        })
+        .collect()
+}
+
+/// Returns the entries in the directory with the link targets.
+///
+/// Entries which are not symbolic links have `None` as targets.
+/// Returns an empty map if the directory does not exist.
+fn existing_entries(dir: &Path) -> io::Result<BTreeMap<OsString, Option<PathBuf>>> {
+    let mut entries = BTreeMap::new();
+    let read_dir = match fs::read_dir(dir) {
+        Ok(v) => v,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(entries),
+        Err(e) => return Err(e),
+    };
+    for entry in read_dir {
+        let entry = entry?;
+        let target = if entry.file_type()?.is_symlink() {
+            Some(fs::read_link(entry.path())?)
+        } else {
+            None
+        };
+        entries.insert(entry.file_name(), target);
+    }
+
+    Ok(entries)
+}
+
+/// Creates a symbolic link to the directory.
+fn create_link(target: &Path, link: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(target, link)
+    }
+    #[cfg(windows)]
+    {
+        std::os::windows::fs::symlink_dir(target, link)
+    }
+}
+
+/// Removes the symbolic link.
+fn remove_link(link: &Path) -> io::Result<()> {
+    // Directory symbolic links on Windows are removed as directories.
+    #[cfg(windows)]
+    {
+        if fs::remove_dir(link).is_ok() {
+            return Ok(());
+        }
+    }
+    fs::remove_file(link)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disambiguate_names() {
+        let repos = [
+            ("src", "github.com/foo/magro".to_owned()),
+            ("src", "github.com/foo/bar".to_owned()),
+            ("src", "gitlab.com/baz/bar".to_owned()),
+            ("src", "example.com/qux".to_owned()),
+            ("work", "example.com/qux".to_owned()),
+        ];
+        assert_eq!(
+            flat_names(&repos),
+            [
+                "magro",
+                "github.com-foo-bar",
+                "gitlab.com-baz-bar",
+                "src-example.com-qux",
+                "work-example.com-qux",
+            ]
+        );
+    }
+}
@@ -11,13 +11,24 @@ use std::{
 
 use anyhow::{anyhow, Context as _};
 use magro::{
+    cache::{Provenance, RepoCacheEntry},
     collection::{Collection, CollectionName},
+    metadata::Upstream,
+    tag::Tag,
     vcs::Vcs,
+    view::RepoView,
     Context,
 };
+use serde::Serialize;
 use structopt::StructOpt;
 
-use crate::cli_opt::{CollectionNameList, VcsList};
+use crate::{
+    cli_opt::{CollectionNameList, GlobalOpt, Since, VcsList},
+    error::CollectionNotFound,
+    output::{write_csv_record, write_json, OutputFormat, QuoteStyle},
+    refresh::refresh_collections,
+    select::{lang_matches, license_matches},
+};
 
 /// Path base.
 #[derive(Debug, Clone, Copy)]
@@ -80,7 +91,62 @@ impl str::FromStr for PathBase {
 
 impl fmt::Display for PathBase {
     #[inline]
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_opt_value())
+    }
+}
+
+/// How to treat missing paths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VerifyMode {
+    /// Skips missing paths.
+    Skip,
+    /// Shows missing paths with annotations.
+    Annotate,
+}
+
+impl VerifyMode {
+    /// Returns a list of possible options.
+    #[inline]
+    #[must_use]
+    fn possible_opt_values() -> &'static [&'static str] {
+        &["skip", "annotate"]
+    }
+
+    /// Returns the option value.
+    #[inline]
+    #[must_use]
+    fn as_opt_value(&self) -> &'static str {
+        match self {
+            Self::Skip => "skip",
+            Self::Annotate => "annotate",
+        }
+    }
+
+    /// Parses the option value.
+    #[inline]
+    #[must_use]
+    fn from_opt_value(s: &str) -> Option<Self> {
+        match s {
+            "skip" => Some(Self::Skip),
+            "annotate" => Some(Self::Annotate),
+            _ => None,
+        }
+    }
+}
+
+impl str::FromStr for VerifyMode {
+    type Err = anyhow::Error;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_opt_value(s).ok_or_else(|| anyhow!("Unsupported verify mode {:?}", s))
+    }
+}
+
+impl fmt::Display for VerifyMode {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str(self.as_opt_value())
     }
 }
@@ -90,12 +156,25 @@ impl fmt::Display for PathBase {
 #[non_exhaustive]
 pub struct ListOpt {
     /// Separates lines by NUL characters.
+    ///
+    /// This is only effective for `text` output.
     #[structopt(long, short = "z")]
     null_data: bool,
+    /// Quotes paths in the specified style.
+    ///
+    /// `shell` quotes paths for POSIX shells (for `eval` and `xargs`), and
+    /// `c` prints paths as C-style string literals.
+    /// Paths without special characters are not quoted in `shell` style.
+    ///
+    /// This is only effective for `text` output.
+    #[structopt(long, possible_values = QuoteStyle::possible_opt_values())]
+    quote: Option<QuoteStyle>,
     /// Prints relativized paths using the specified base directory.
     ///
     /// Note that relativization can fail for some paths. In such case, `root`
     /// is used as fallback.
+    ///
+    /// This is only effective for `text` output.
     #[structopt(
         long,
         possible_values = PathBase::possible_opt_values(),
@@ -105,145 +184,392 @@ pub struct ListOpt {
     /// Prints working directory
     #[structopt(long)]
     workdir: bool,
+    /// Fails immediately if some repository cannot be read.
+    ///
+    /// By default, such repositories are skipped (with error logs), and the
+    /// number of skipped repositories are reported at the end.
+    #[structopt(long)]
+    strict: bool,
+    /// Checks that the paths to print exist.
+    ///
+    /// Missing paths are skipped (`--verify` or `--verify=skip`), or printed
+    /// with annotations (`--verify=annotate`).
+    /// For `text` output, annotated paths are followed by a tab and `(missing)`.
+    /// For `json` and `csv` outputs, `exists` field is added.
+    #[structopt(
+        long,
+        possible_values = VerifyMode::possible_opt_values(),
+        require_equals = true,
+        min_values = 0
+    )]
+    #[allow(clippy::option_option)]
+    verify: Option<Option<VerifyMode>>,
+    /// Refreshes the target collections before listing.
+    ///
+    /// Collections are discovered again even if they seem unchanged, so that
+    /// the output is never stale, at the cost of a slower invocation.
+    /// This can be enabled by default by `refresh = true` in `[list]` section
+    /// of the main config.
+    #[structopt(long, overrides_with = "no-refresh")]
+    refresh: bool,
+    /// Does not refresh the collections, even if enabled in the config.
+    #[structopt(long, overrides_with = "refresh")]
+    no_refresh: bool,
     /// Prints only repositories of specified VCS's.
     ///
-    /// Currently, only `git` is supported.
+    /// Supported VCS's are `darcs`, `git`, `hg`, `jj`, and `pijul`.
     // Not using `-v` for this, as it can be confused with `--verbose`.
     #[structopt(long, parse(try_from_str), multiple = true)]
     vcs: Vec<VcsList>,
     /// Prints only repositories of the specified collections.
     ///
+    /// Glob patterns such as `work-*` are expanded to the matching collections.
     /// If no collections are specified, it behaves as all collections are given.
     #[structopt(long, short, parse(try_from_str))]
     collections: Vec<CollectionNameList>,
+    /// Prints only repositories of the specified languages (case insensitive).
+    ///
+    /// Languages are detected by `refresh --metadata`.
+    #[structopt(long = "lang", use_delimiter = true)]
+    langs: Vec<String>,
+    /// Prints only repositories under the specified licenses (case insensitive
+    /// SPDX license identifiers, such as `mit` or `gpl-3.0`).
+    ///
+    /// Licenses are detected by `refresh --metadata`.
+    #[structopt(long = "license", use_delimiter = true)]
+    licenses: Vec<String>,
+    /// Prints only repositories with any of the specified tags.
+    ///
+    /// Tags are attached by `tag add`.
+    #[structopt(long = "tag", use_delimiter = true)]
+    tags: Vec<Tag>,
+    /// Prints only repositories with commits at or after the date (such as
+    /// `2024-01-31`, in UTC) or within the age (such as `90d`).
+    ///
+    /// Last commit times are collected by `refresh --metadata`.
+    /// Repositories without collected times are not printed.
+    #[structopt(long, value_name = "date|age")]
+    active_since: Option<Since>,
+    /// Prints only repositories cloned by magro (`cloned`) or only found in
+    /// the collection directories (`discovered`).
+    #[structopt(long, possible_values = &["cloned", "discovered"])]
+    provenance: Option<Provenance>,
+    /// Does not print repositories whose upstreams are archived.
+    ///
+    /// Upstream metadata is fetched by `forge enrich`.
+    /// Repositories without fetched metadata are printed.
+    #[structopt(long)]
+    exclude_archived_upstream: bool,
 }
 
 impl ListOpt {
     /// Runs the actual operation.
-    pub fn run(&self, context: &Context) -> anyhow::Result<()> {
+    pub fn run(&self, context: &mut Context, global: &GlobalOpt) -> anyhow::Result<()> {
         log::trace!(
-            "list vcs={:?} collections={:?} null_data={} path_base={} workdir={}",
+            "list vcs={:?} collections={:?} langs={:?} licenses={:?} tags={:?} active_since={:?} \
+             provenance={:?} exclude_archived_upstream={} null_data={} quote={:?} path_base={} workdir={} strict={} verify={:?} refresh={} no_refresh={} output={}",
             self.vcs,
             self.collections,
+            self.langs,
+            self.licenses,
+            self.tags,
+            self.active_since,
+            self.provenance,
+            self.exclude_archived_upstream,
             self.null_data,
+            self.quote,
             self.path_base,
-            self.workdir
+            self.workdir,
+            self.strict,
+            self.verify,
+            self.refresh,
+            self.no_refresh,
+            global.output
         );
 
+        let refresh = if self.refresh || self.no_refresh {
+            self.refresh
+        } else {
+            // Refreshing by default is skipped, since the cache cannot be saved.
+            context.config().main().list().refresh() && !context.is_read_only()
+        };
+        if refresh {
+            self.refresh_targets(context, global)?;
+        }
+
         let target_vcs: Option<HashSet<Vcs>> = match self.vcs.as_slice() {
             [] => None,
             vcs => Some(vcs.iter().flatten().collect()),
         };
+        let filter = |repo: &RepoCacheEntry| {
+            target_vcs
+                .as_ref()
+                .map_or(true, |targets| targets.contains(&repo.vcs()))
+                && lang_matches(&self.langs, repo.metadata().language())
+                && license_matches(&self.licenses, repo.metadata().license())
+                && self.active_since.map_or(true, |since| {
+                    repo.metadata()
+                        .last_commit_at()
+                        .map_or(false, |time| since.includes(time))
+                })
+                && self
+                    .provenance
+                    .map_or(true, |provenance| repo.provenance() == provenance)
+                && !(self.exclude_archived_upstream
+                    && repo
+                        .metadata()
+                        .upstream()
+                        .map_or(false, Upstream::is_archived))
+        };
         let collections = context.config().collections();
-        let mut targets = self
-            .collections
+        let names = CollectionNameList::expand(&self.collections, collections)?;
+        let mut targets = names
             .iter()
-            .flatten()
             .map(|name| collections.get(name).ok_or(name))
             .peekable();
 
         if targets.peek().is_none() {
-            list_repos(
+            self.list_repos(
                 context,
                 &mut collections.iter().map(Ok),
-                target_vcs.as_ref(),
-                self.workdir,
-                self.null_data,
-                self.path_base,
+                &filter,
+                global.output,
             )
         } else {
-            list_repos(
-                context,
-                &mut targets,
-                target_vcs.as_ref(),
-                self.workdir,
-                self.null_data,
-                self.path_base,
-            )
+            self.list_repos(context, &mut targets, &filter, global.output)
         }
     }
-}
 
-/// List repositories.
-// Using `dyn Iterator` won't be problem, because the number of collections is
-// expected to be small (for usual usage).
-fn list_repos(
-    context: &Context,
-    collections: &mut dyn Iterator<Item = Result<&Collection, &CollectionName>>,
-    target_vcs: Option<&HashSet<Vcs>>,
-    show_workdir: bool,
-    null_data: bool,
-    path_base: PathBase,
-) -> anyhow::Result<()> {
-    let cache = context
-        .get_or_load_cache()
-        .context("Failed to load cache file")?
-        .clone();
-
-    let stdout = io::stdout();
-    let mut handle = stdout.lock();
-    let newline = if null_data { b"\0" } else { b"\n" };
-    let home_dir = context.home_dir();
-
-    for collection in collections {
-        let collection =
-            collection.map_err(|name| anyhow!("Collection named `{}` does not exist", name))?;
-
-        let coll_name = collection.name();
-        let coll_base_path = collection.abspath(context);
-
-        log::trace!("Listing repositories in the collection `{}`", coll_name);
-
-        let coll_cache = match cache.collection_repos(coll_name) {
-            Some(v) => v,
-            None => {
-                log::info!("No cache found for collection `{}`", coll_name);
-                continue;
-            }
+    /// Refreshes the target collections.
+    fn refresh_targets(&self, context: &mut Context, global: &GlobalOpt) -> anyhow::Result<()> {
+        let names = match self.collections.as_slice() {
+            [] => context
+                .config()
+                .collections()
+                .iter()
+                .map(|coll| coll.name().to_owned())
+                .collect(),
+            lists => CollectionNameList::expand(lists, context.config().collections())?,
         };
+        let collections = context.config().collections();
+        let mut targets = names
+            .into_iter()
+            .map(|name| {
+                if collections.get(&name).is_some() {
+                    Ok(name)
+                } else {
+                    Err(name)
+                }
+            })
+            .collect::<Vec<_>>()
+            .into_iter();
+        refresh_collections(
+            context,
+            &mut targets,
+            global.verbosity().is_verbose(),
+            true,
+            !self.strict,
+            false,
+        )
+    }
 
-        for repo in coll_cache.repositories() {
-            let vcs = repo.vcs();
-            let abspath = coll_base_path.join(repo.path());
+    /// List repositories.
+    // Using `dyn Iterator` won't be problem, because the number of collections is
+    // expected to be small (for usual usage).
+    fn list_repos(
+        &self,
+        context: &Context,
+        collections: &mut dyn Iterator<Item = Result<&Collection, &CollectionName>>,
+        filter: &dyn Fn(&RepoCacheEntry) -> bool,
+        output: OutputFormat,
+    ) -> anyhow::Result<()> {
+        let cache = context
+            .get_or_load_cache()
+            .context("Failed to load cache file")?;
+        let tags = if self.tags.is_empty() {
+            None
+        } else {
+            Some(
+                context
+                    .get_or_load_tags()
+                    .context("Failed to load tags file")?,
+            )
+        };
+        let verify = self.verify.map(|mode| mode.unwrap_or(VerifyMode::Skip));
+
+        let stdout = io::stdout();
+        let mut handle = stdout.lock();
+        let newline = if self.null_data { b"\0" } else { b"\n" };
+        let home_dir = context.home_dir();
+        let mut views = Vec::new();
+        let mut num_skipped = 0_usize;
+        let mut num_missing = 0_usize;
+
+        if output == OutputFormat::Csv {
+            let mut header = vec![
+                "collection",
+                "vcs",
+                "path",
+                "relative_path",
+                "workdir",
+                "language",
+                "license",
+                "source_uri",
+                "provenance",
+            ];
+            if verify == Some(VerifyMode::Annotate) {
+                header.push("exists");
+            }
+            write_csv_record(&mut handle, &header)?;
+        }
 
-            if target_vcs.map_or(true, |targets| targets.contains(&vcs)) {
-                let path_to_show = if show_workdir {
-                    // FIXME: Is it ok to return immediately if it returned error?
-                    let workdir = vcs.workdir(&abspath).with_context(|| {
-                        anyhow!(
-                            "Failed to get working directory for {} repository {:?}",
-                            vcs.name_lower(),
-                            abspath
-                        )
-                    })?;
-                    match workdir {
-                        Some(v) => v,
-                        None => {
-                            log::debug!(
-                                "No working directory for {} repository {:?}",
+        for collection in collections {
+            let collection = collection.map_err(|name| CollectionNotFound(name.clone()))?;
+
+            let coll_name = collection.name();
+            let coll_base_path = collection.abspath(context);
+
+            log::trace!("Listing repositories in the collection `{}`", coll_name);
+
+            let coll_cache = match cache.collection_repos(coll_name) {
+                Some(v) => v,
+                None => {
+                    log::info!("No cache found for collection `{}`", coll_name);
+                    continue;
+                }
+            };
+
+            for repo in coll_cache.repositories() {
+                if !filter(repo) {
+                    continue;
+                }
+                if let Some(tags) = tags {
+                    if !tags.has_any(coll_name, repo.path(), &self.tags) {
+                        continue;
+                    }
+                }
+                let vcs = repo.vcs();
+
+                let mut view = RepoView::new(context, collection, repo);
+                let repo_exists = verify.is_none() || view.path().exists();
+                if self.workdir && repo_exists {
+                    let has_workdir = match view.resolve_workdir() {
+                        Ok(v) => v,
+                        Err(e) => {
+                            let e = anyhow::Error::new(e).context(format!(
+                                "Failed to get working directory for {} repository {:?}",
                                 vcs.name_lower(),
-                                abspath
-                            );
+                                view.path()
+                            ));
+                            if self.strict {
+                                return Err(e);
+                            }
+                            log::error!("{:#}", e);
+                            num_skipped += 1;
                             continue;
                         }
+                    };
+                    if !has_workdir {
+                        log::debug!(
+                            "No working directory for {} repository {:?}",
+                            vcs.name_lower(),
+                            view.path()
+                        );
+                        continue;
+                    }
+                }
+
+                let exists = match verify {
+                    None => None,
+                    Some(mode) => {
+                        let exists = repo_exists && view.workdir().map_or(true, Path::exists);
+                        if !exists {
+                            num_missing += 1;
+                            if mode == VerifyMode::Skip {
+                                log::warn!("Skipped missing repository {:?}", view.path());
+                                continue;
+                            }
+                        }
+                        (mode == VerifyMode::Annotate).then(|| exists)
                     }
-                } else {
-                    Cow::Borrowed(abspath.as_ref())
-                };
-                debug_assert!(path_to_show.is_absolute());
-                let path_to_show: &Path = match path_base {
-                    PathBase::Root => &path_to_show,
-                    PathBase::Collection => try_relativize(&path_to_show, &coll_base_path),
-                    PathBase::Home => try_relativize(&path_to_show, home_dir),
                 };
 
-                print_raw_path(&mut handle, path_to_show)?;
-                handle.write_all(newline)?;
+                match output {
+                    OutputFormat::Text => {
+                        let path_to_show = view.workdir().unwrap_or_else(|| view.path());
+                        debug_assert!(path_to_show.is_absolute());
+                        let path_to_show: &Path = match self.path_base {
+                            PathBase::Root => path_to_show,
+                            PathBase::Collection => try_relativize(path_to_show, &coll_base_path),
+                            PathBase::Home => try_relativize(path_to_show, home_dir),
+                        };
+
+                        let path_bytes = raw_path_bytes(path_to_show);
+                        match self.quote {
+                            Some(style) => handle.write_all(&style.quote(&path_bytes))?,
+                            None => handle.write_all(&path_bytes)?,
+                        }
+                        if exists == Some(false) {
+                            handle.write_all(b"\t(missing)")?;
+                        }
+                        handle.write_all(newline)?;
+                    }
+                    OutputFormat::Json => views.push(JsonEntry { view, exists }),
+                    OutputFormat::Csv => {
+                        let path = view.path().to_string_lossy();
+                        let relative_path = view.relative_path().to_string_lossy();
+                        let workdir = view
+                            .workdir()
+                            .map_or(Cow::Borrowed(""), Path::to_string_lossy);
+                        let mut record = vec![
+                            view.collection().as_str(),
+                            vcs.name_lower(),
+                            &path,
+                            &relative_path,
+                            &workdir,
+                            view.metadata().language().unwrap_or_default(),
+                            view.metadata().license().unwrap_or_default(),
+                            view.source_uri().unwrap_or_default(),
+                            view.provenance().name_lower(),
+                        ];
+                        if let Some(exists) = exists {
+                            record.push(if exists { "true" } else { "false" });
+                        }
+                        write_csv_record(&mut handle, &record)?;
+                    }
+                }
             }
         }
+
+        if output == OutputFormat::Json {
+            write_json(&mut handle, &views)?;
+        }
+
+        if num_missing != 0 {
+            log::info!("{} repositories are missing", num_missing);
+        }
+        if num_skipped != 0 {
+            log::warn!(
+                "Skipped {} repositories which could not be read (use `--strict` to fail)",
+                num_skipped
+            );
+        }
+
+        Ok(())
     }
+}
 
-    Ok(())
+/// Entry of JSON output.
+#[derive(Debug, Serialize)]
+struct JsonEntry {
+    /// Repository.
+    #[serde(flatten)]
+    view: RepoView,
+    /// Whether the paths exist.
+    ///
+    /// This is available only when `--verify=annotate` is specified.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exists: Option<bool>,
 }
 
 /// Returns relativized path if succeeded, or returns the raw input if failed.
@@ -266,24 +592,24 @@ fn try_relativize<'a>(path: &'a Path, base: &Path) -> &'a Path {
     path
 }
 
-/// Attempts to print the raw path, even when it is invalid UTF-8 sequence.
-///
-/// This does not write the trailing newline.
+/// Returns the raw bytes of the path, even when it is invalid UTF-8 sequence.
 #[cfg(unix)]
 #[inline]
-fn print_raw_path<W: io::Write>(writer: &mut W, path: &Path) -> io::Result<()> {
+#[must_use]
+fn raw_path_bytes(path: &Path) -> Cow<'_, [u8]> {
     use std::os::unix::ffi::OsStrExt;
 
-    writer.write_all(path.as_os_str().as_bytes())
+    Cow::Borrowed(path.as_os_str().as_bytes())
 }
 
-/// Attempts to print the raw path, even when it is invalid UTF-8 sequence.
+/// Returns the bytes of the path to print.
 ///
-/// This does not write the trailing newline.
+/// Invalid UTF-8 sequences are replaced.
 #[cfg(not(unix))]
 #[inline]
-fn print_raw_path<W: io::Write>(writer: &mut W, path: &Path) -> io::Result<()> {
-    write!(writer, "{}", path.display())
+#[must_use]
+fn raw_path_bytes(path: &Path) -> Cow<'_, [u8]> {
+    Cow::Owned(path.display().to_string().into_bytes())
 }
 
 #[cfg(test)]
@@ -0,0 +1,118 @@
+//! `locate` subcommand.
+
+use std::{
+    borrow::Cow,
+    env,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+use anyhow::Context as _;
+use magro::{template, Context};
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::GlobalOpt,
+    output::{write_csv_record, write_json, OutputFormat},
+};
+
+/// Options for `locate` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct LocateOpt {
+    /// Locates the repository containing the current directory.
+    #[structopt(long, required_unless = "path", conflicts_with = "path")]
+    cwd: bool,
+    /// Path to locate the repository.
+    #[structopt(parse(from_os_str))]
+    path: Option<PathBuf>,
+    /// Format of the text output.
+    ///
+    /// Available variables are `{collection}`, `{name}` (repository path
+    /// relative to the collection directory, without `.git` suffix), `{path}`
+    /// (absolute path of the repository), `{workdir}` (absolute path of the
+    /// working directory, or the repository for bare repositories), and
+    /// `{branch}` (branch checked out at HEAD, resolved by `cache warm`;
+    /// empty if not resolved).
+    #[structopt(long, default_value = "{collection}\t{name}")]
+    format: String,
+}
+
+impl LocateOpt {
+    /// Runs the actual operation.
+    ///
+    /// If the path is not in any cached repository, exits with status 1
+    /// without messages, so that shell prompts can use it as a condition.
+    pub fn run(&self, context: &Context, global: &GlobalOpt) -> anyhow::Result<()> {
+        log::trace!(
+            "locate cwd={}, path={:?}, format={:?}",
+            self.cwd,
+            self.path,
+            self.format
+        );
+
+        let path = match &self.path {
+            Some(path) if path.is_absolute() => path.clone(),
+            path => {
+                let cwd = env::current_dir().context("Failed to get the current directory")?;
+                match path {
+                    Some(path) => cwd.join(path),
+                    None => cwd,
+                }
+            }
+        };
+        let repo =
+            match magro::locate::locate(context, &path).context("Failed to load cache file")? {
+                Some(v) => v,
+                None => {
+                    log::debug!("No cached repositories contain {:?}", path);
+                    process::exit(1);
+                }
+            };
+
+        let stdout = io::stdout();
+        let mut handle = stdout.lock();
+        let workdir = repo.workdir().unwrap_or_else(|| repo.path());
+        match global.output {
+            OutputFormat::Text => {
+                let line = template::expand(&self.format, |var| match var {
+                    "collection" => Some(Cow::Borrowed(repo.collection().as_str())),
+                    "name" => Some(Cow::Owned(repo.name_string())),
+                    "path" => Some(repo.path().to_string_lossy()),
+                    "workdir" => Some(workdir.to_string_lossy()),
+                    "branch" => Some(Cow::Borrowed(
+                        repo.metadata()
+                            .resolved()
+                            .and_then(|resolved| resolved.head_branch())
+                            .unwrap_or_default(),
+                    )),
+                    _ => None,
+                })
+                .context("Invalid format")?;
+                writeln!(handle, "{}", line)?;
+            }
+            OutputFormat::Json => write_json(&mut handle, &repo)?,
+            OutputFormat::Csv => {
+                write_csv_record(
+                    &mut handle,
+                    &["collection", "vcs", "path", "relative_path", "workdir"],
+                )?;
+                write_csv_record(
+                    &mut handle,
+                    &[
+                        repo.collection().as_str(),
+                        repo.vcs().name_lower(),
+                        &repo.path().to_string_lossy(),
+                        &repo.relative_path().to_string_lossy(),
+                        &repo
+                            .workdir()
+                            .map_or(Cow::Borrowed(""), Path::to_string_lossy),
+                    ],
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
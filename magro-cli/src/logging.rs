@@ -0,0 +1,197 @@
+//! Log formats.
+
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fmt,
+    io::{self, Write},
+    str,
+    sync::Arc,
+};
+
+use anyhow::anyhow;
+use magro::{collection::CollectionName, vcs, view::RepoView, Context};
+use serde::Serialize;
+
+/// Log format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LogFormat {
+    /// Human-readable text.
+    Text,
+    /// JSON object per line.
+    Json,
+}
+
+impl LogFormat {
+    /// Returns a list of possible options.
+    #[inline]
+    #[must_use]
+    pub(crate) fn possible_opt_values() -> &'static [&'static str] {
+        &["text", "json"]
+    }
+
+    /// Returns the option value.
+    #[inline]
+    #[must_use]
+    fn as_opt_value(&self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Json => "json",
+        }
+    }
+
+    /// Parses the option value.
+    #[inline]
+    #[must_use]
+    fn from_opt_value(s: &str) -> Option<Self> {
+        match s {
+            "text" => Some(Self::Text),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+impl Default for LogFormat {
+    #[inline]
+    fn default() -> Self {
+        Self::Text
+    }
+}
+
+impl str::FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_opt_value(s).ok_or_else(|| anyhow!("Unsupported log format {:?}", s))
+    }
+}
+
+impl fmt::Display for LogFormat {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_opt_value())
+    }
+}
+
+/// Repository being processed on the current thread.
+#[derive(Debug, Clone)]
+struct RepoScope {
+    /// Collection name.
+    collection: String,
+    /// Repository name.
+    repo: String,
+}
+
+thread_local! {
+    /// Repository being processed on the current thread.
+    static REPO_SCOPE: RefCell<Option<RepoScope>> = RefCell::new(None);
+}
+
+/// Environment variables of collections, looked up by the collection names.
+///
+/// This is cheap to clone, so that it can be moved into parallel jobs.
+#[derive(Debug, Clone)]
+pub(crate) struct CollectionEnvs(Arc<BTreeMap<CollectionName, BTreeMap<String, String>>>);
+
+impl CollectionEnvs {
+    /// Collects the environment variables of the collections in the config.
+    #[must_use]
+    pub(crate) fn new(context: &Context) -> Self {
+        let envs = context
+            .config()
+            .collections()
+            .iter()
+            .filter(|collection| !collection.env().is_empty())
+            .map(|collection| (collection.name().clone(), collection.env().clone()))
+            .collect();
+        Self(Arc::new(envs))
+    }
+
+    /// Returns the environment variables of the collection, if any.
+    #[must_use]
+    pub(crate) fn get(&self, collection: &CollectionName) -> Option<&BTreeMap<String, String>> {
+        self.0.get(collection)
+    }
+}
+
+/// Runs `f` with the repository attached to log records emitted during it.
+///
+/// In JSON log format, `collection` and `repo` fields are added to the records.
+/// Environment variables of the collection are set for external VCS commands
+/// run during it.
+pub(crate) fn with_repo<R>(repo: &RepoView, envs: &CollectionEnvs, f: impl FnOnce() -> R) -> R {
+    /// Guard to restore the previous scope even on panic.
+    struct Restore(Option<RepoScope>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let prev = self.0.take();
+            REPO_SCOPE.with(|scope| *scope.borrow_mut() = prev);
+        }
+    }
+
+    let scope = RepoScope {
+        collection: repo.collection().to_string(),
+        repo: repo.name_string(),
+    };
+    let _restore = Restore(REPO_SCOPE.with(|current| current.replace(Some(scope))));
+    match envs.get(repo.collection()) {
+        Some(env) => vcs::with_env(env, f),
+        None => f(),
+    }
+}
+
+/// Log record in JSON format.
+#[derive(Serialize)]
+struct JsonRecord<'a> {
+    /// Timestamp in RFC 3339 format.
+    timestamp: String,
+    /// Log level.
+    level: &'a str,
+    /// Target (usually the module path).
+    target: &'a str,
+    /// Message.
+    message: String,
+    /// Collection of the repository being processed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    collection: Option<String>,
+    /// Name of the repository being processed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    repo: Option<String>,
+}
+
+/// Writes the log record as a JSON object followed by a newline.
+pub(crate) fn format_json(
+    buf: &mut env_logger::fmt::Formatter,
+    record: &log::Record<'_>,
+) -> io::Result<()> {
+    let scope = REPO_SCOPE.with(|scope| scope.borrow().clone());
+    let (collection, repo) = match scope {
+        Some(RepoScope { collection, repo }) => (Some(collection), Some(repo)),
+        None => (None, None),
+    };
+    let json = JsonRecord {
+        timestamp: buf.timestamp_millis().to_string(),
+        level: record.level().as_str(),
+        target: record.target(),
+        message: record.args().to_string(),
+        collection,
+        repo,
+    };
+    serde_json::to_writer(&mut *buf, &json)?;
+    writeln!(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_format_consistent_string_conversion() {
+        for &opt in LogFormat::possible_opt_values() {
+            assert_eq!(opt, opt.parse::<LogFormat>().unwrap().to_string())
+        }
+    }
+}
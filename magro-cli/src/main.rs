@@ -1,27 +1,156 @@
 //! Command to manage git repositories.
 
+use std::{
+    env,
+    io::{self, Write},
+    process,
+    time::Instant,
+};
+
 use structopt::StructOpt;
 
-use self::cli_opt::Opt;
+use self::{
+    cli_opt::{Opt, Verbosity},
+    complete::CompleteOpt,
+    logging::LogFormat,
+};
 
+pub(crate) mod backup;
+pub(crate) mod branches;
+pub(crate) mod cache;
+pub(crate) mod checkout;
 pub(crate) mod cli_opt;
 pub(crate) mod clone;
 pub(crate) mod collection;
+pub(crate) mod complete;
+pub(crate) mod config;
+pub(crate) mod copy;
+#[cfg(feature = "prompt")]
+pub(crate) mod credential;
+pub(crate) mod doctor;
+pub(crate) mod du;
+pub(crate) mod env_info;
+pub(crate) mod error;
+pub(crate) mod exec;
+pub(crate) mod export;
+pub(crate) mod fetch;
+#[cfg(feature = "forge")]
+pub(crate) mod forge;
+pub(crate) mod grep;
+pub(crate) mod health;
+pub(crate) mod import;
+pub(crate) mod linkfarm;
 pub(crate) mod list;
+pub(crate) mod locate;
+pub(crate) mod logging;
+pub(crate) mod new;
+pub(crate) mod outdated;
+pub(crate) mod output;
+pub(crate) mod path;
+#[cfg(feature = "pick")]
+pub(crate) mod pick;
+pub(crate) mod plugin;
+pub(crate) mod progress;
+pub(crate) mod reclone;
 pub(crate) mod refresh;
+pub(crate) mod remote;
+pub(crate) mod repo;
+pub(crate) mod resume;
+pub(crate) mod rm;
+pub(crate) mod scratch;
+pub(crate) mod select;
+#[cfg(feature = "serve")]
+pub(crate) mod serve;
+pub(crate) mod shell_init;
+pub(crate) mod snapshot;
+pub(crate) mod stats;
+pub(crate) mod status;
+pub(crate) mod submodules;
+pub(crate) mod tag;
+pub(crate) mod tmux;
+pub(crate) mod trash;
+pub(crate) mod verify;
+pub(crate) mod which;
+pub(crate) mod workspace;
+pub(crate) mod worktree;
 
-fn main() -> anyhow::Result<()> {
-    init_logger();
+fn main() {
+    if env::args_os()
+        .nth(1)
+        .map_or(false, |arg| arg == complete::SUBCOMMAND_NAME)
+    {
+        complete_main();
+    }
 
-    let mut ctx = magro::Context::new()?;
     let opt = Opt::from_args();
-    opt.run(&mut ctx)?;
+    init_logger(opt.verbosity(), opt.log_format());
+    progress::init(opt.progress());
+    #[cfg(feature = "prompt")]
+    credential::register(opt.cache_credentials());
+    let start = opt.timings().then(|| {
+        magro::timing::enable();
+        Instant::now()
+    });
+
+    let result = run(&opt);
+    if let Some(start) = start {
+        report_timings(start);
+    }
+    if let Err(e) = result {
+        error::report(&e, opt.output());
+        process::exit(1);
+    }
+}
+
+/// Runs `__complete` subcommand, and exits.
+fn complete_main() -> ! {
+    let opt = CompleteOpt::from_iter(env::args_os().skip(1));
+    init_logger(opt.verbosity(), opt.log_format());
+
+    match opt.run() {
+        Ok(()) => process::exit(0),
+        Err(e) => {
+            log::error!("{:#}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Runs the command.
+fn run(opt: &Opt) -> anyhow::Result<()> {
+    let mut ctx = opt.context()?;
+    opt.run(&mut ctx)
+}
 
-    Ok(())
+/// Prints the recorded timings to stderr.
+fn report_timings(start: Instant) {
+    let total = start.elapsed();
+    let stderr = io::stderr();
+    let mut handle = stderr.lock();
+    // Nothing can be done if writing to stderr fails.
+    let _ = writeln!(handle, "Timings:");
+    for timing in magro::timing::take() {
+        let _ = writeln!(
+            handle,
+            "  {:<32} {:>6} {:>12.3} ms",
+            timing.name(),
+            format!("{}x", timing.count()),
+            timing.total().as_secs_f64() * 1000.0
+        );
+    }
+    let _ = writeln!(
+        handle,
+        "  {:<32} {:>6} {:>12.3} ms",
+        "total",
+        "",
+        total.as_secs_f64() * 1000.0
+    );
 }
 
 /// Initialize logger.
-fn init_logger() {
+///
+/// Verbosity options take precedence over `RUST_LOG` environment variable.
+fn init_logger(verbosity: Verbosity, format: LogFormat) {
     /// Default log filter for debug build.
     #[cfg(debug_assertions)]
     const DEFAULT_LOG_FILTER: &str = "magro=debug";
@@ -29,6 +158,14 @@ fn init_logger() {
     #[cfg(not(debug_assertions))]
     const DEFAULT_LOG_FILTER: &str = "magro=warn";
 
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(DEFAULT_LOG_FILTER))
-        .init();
+    let mut builder = env_logger::Builder::from_env(
+        env_logger::Env::default().default_filter_or(DEFAULT_LOG_FILTER),
+    );
+    if let Some(level) = verbosity.log_level_filter() {
+        builder.filter_module("magro", level);
+    }
+    if format == LogFormat::Json {
+        builder.format(logging::format_json);
+    }
+    builder.init();
 }
@@ -0,0 +1,148 @@
+//! `new` subcommand.
+
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{bail, Context as _};
+use magro::{
+    cache::{Provenance, RepoCacheEntry},
+    collection::CollectionName,
+    scaffold,
+    vcs::{self, Vcs},
+    Context,
+};
+use structopt::StructOpt;
+
+/// Options for `new` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct NewOpt {
+    /// Name of the template.
+    ///
+    /// Templates are defined in `[templates.<name>]` sections of the main config.
+    template: String,
+    /// Relative path of the new repository.
+    #[structopt(parse(from_os_str))]
+    destination: PathBuf,
+    /// Collection to put the new repository.
+    #[structopt(long, short)]
+    collection: Option<CollectionName>,
+    /// Keeps the history of the template repository.
+    ///
+    /// If not specified, `keep-history` of the template config is used.
+    #[structopt(long)]
+    keep_history: bool,
+}
+
+impl NewOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &mut Context) -> anyhow::Result<()> {
+        log::trace!(
+            "new template={:?}, dest={:?}, collection={:?}, keep_history={}",
+            self.template,
+            self.destination,
+            self.collection,
+            self.keep_history
+        );
+
+        let template = context
+            .config()
+            .main()
+            .template(&self.template)
+            .with_context(|| {
+                format!(
+                    "Template named `{}` is not defined \
+                     (`[templates.{}]` section of the main config)",
+                    self.template, self.template
+                )
+            })?;
+        let uri = template.uri().to_owned();
+        context
+            .ensure_writable()
+            .context("Cannot create a repository")?;
+        let keep_history = self.keep_history || template.keep_history();
+        let collection = context
+            .config()
+            .target_collection(self.collection.as_ref())?;
+        let collection_name = collection.name().to_owned();
+        let env = collection.env().clone();
+
+        let collection_base_dir = collection.abspath(context);
+        let reldest = if self.destination.is_relative() {
+            self.destination.clone()
+        } else {
+            match self.destination.strip_prefix(&collection_base_dir) {
+                Ok(reldest) => reldest.to_owned(),
+                Err(_) => bail!(
+                    "destination path ({}) should be relative path \
+                    or inside collection directory ({}), but it was not",
+                    self.destination.display(),
+                    collection_base_dir.display()
+                ),
+            }
+        };
+        if reldest
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_)))
+        {
+            bail!(
+                "destination path ({}) should not contain `.` or `..`",
+                reldest.display()
+            );
+        }
+        let absdest = collection_base_dir.join(&reldest);
+        if absdest.exists() {
+            bail!("Destination path {:?} already exists", absdest);
+        }
+        let replacements =
+            scaffold::replacements(template.placeholders(), &collection_name, &reldest)
+                .with_context(|| format!("Invalid placeholders of template `{}`", self.template))?;
+
+        vcs::with_env(&env, || {
+            create_repo(&uri, &absdest, keep_history, &replacements)
+        })?;
+
+        // Update cache.
+        context
+            .update_cache(|cache| {
+                if let Some(repos) = cache.collection_repos_mut(&collection_name) {
+                    let mut entry = RepoCacheEntry::new(Vcs::Git, reldest.join(".git"));
+                    entry.set_provenance(Provenance::Cloned);
+                    repos.insert(entry);
+                }
+            })
+            .context("Failed to update cache file")?;
+        log::info!(
+            "Created repository {:?} from template `{}`",
+            absdest,
+            self.template
+        );
+
+        Ok(())
+    }
+}
+
+/// Clones the template repository and replaces the placeholders.
+fn create_repo(
+    uri: &str,
+    absdest: &Path,
+    keep_history: bool,
+    replacements: &[(String, String)],
+) -> anyhow::Result<()> {
+    // Only git is supported for templates for now.
+    let vcs = Vcs::Git;
+    vcs.clone(uri, absdest, false).with_context(|| {
+        format!(
+            "Failed to clone template repository {:?} into {:?}",
+            uri, absdest
+        )
+    })?;
+    if !keep_history {
+        vcs.reinit(absdest)
+            .with_context(|| format!("Failed to reinitialize repository {:?}", absdest))?;
+    }
+    let num_modified = scaffold::replace_placeholders(absdest, replacements)
+        .with_context(|| format!("Failed to replace placeholders in {:?}", absdest))?;
+    log::debug!("Replaced placeholders in {} files", num_modified);
+
+    Ok(())
+}
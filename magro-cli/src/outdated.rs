@@ -0,0 +1,198 @@
+//! `outdated` subcommand.
+
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use anyhow::bail;
+use magro::{
+    collection::CollectionName,
+    upstream::{BranchUpstream, UpstreamState},
+    view::RepoView,
+    Context,
+};
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::GlobalOpt,
+    logging,
+    output::{write_csv_record, write_json, OutputFormat},
+    select::RepoSelectOpt,
+};
+
+/// Options for `outdated` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct OutdatedOpt {
+    /// Target repositories.
+    #[structopt(flatten)]
+    select: RepoSelectOpt,
+    /// Fetches all remotes before the comparison.
+    ///
+    /// Without this, the remote-tracking branches fetched last time are used.
+    /// Frozen repositories (see `repo freeze`) are not fetched.
+    #[structopt(long)]
+    fetch: bool,
+    /// Shows also branches up to date with their upstreams.
+    #[structopt(long)]
+    all: bool,
+    /// Runs the operation as possible even when errors are detected.
+    ///
+    /// Note that errors are ignored during the operation, but the program will
+    /// exit with failure (i.e. errors won't be completely ignored).
+    #[structopt(long)]
+    keep_going: bool,
+}
+
+impl OutdatedOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &Context, global: &GlobalOpt) -> anyhow::Result<()> {
+        log::trace!(
+            "outdated select={:?}, fetch={}, all={}, keep_going={}",
+            self.select,
+            self.fetch,
+            self.all,
+            self.keep_going
+        );
+
+        let repos = self.select.select(context)?;
+        let keep_going = self.keep_going;
+        let fetch = self.fetch;
+        let envs = logging::CollectionEnvs::new(context);
+        let results = global.runner(context).run_until(
+            repos,
+            move |repo| {
+                logging::with_repo(repo, &envs, || {
+                    let vcs = repo.vcs();
+                    if fetch && !repo.metadata().is_frozen() {
+                        vcs.fetch(repo.path())?;
+                    }
+                    vcs.upstream_statuses(repo.path())
+                })
+            },
+            move |result| !keep_going && result.is_err(),
+        );
+
+        let mut checked = Vec::with_capacity(results.len());
+        let mut num_errors = 0_usize;
+        for (repo, result) in results {
+            match result {
+                Ok(statuses) => checked.push((repo, statuses)),
+                Err(e) => {
+                    if !self.keep_going {
+                        return Err(anyhow::Error::new(e).context(format!(
+                            "Failed to compare branches of {:?} with upstreams",
+                            repo.path()
+                        )));
+                    }
+                    log::error!(
+                        "Failed to compare branches of {:?} with upstreams: {}",
+                        repo.path(),
+                        e
+                    );
+                    num_errors += 1;
+                }
+            }
+        }
+
+        let entries = checked
+            .iter()
+            .flat_map(|(repo, statuses)| {
+                statuses
+                    .iter()
+                    .map(move |status| BranchEntry::new(repo, status))
+            })
+            .filter(|entry| self.all || entry.status.state() != UpstreamState::UpToDate)
+            .collect::<Vec<_>>();
+
+        let stdout = io::stdout();
+        let mut handle = stdout.lock();
+        match global.output {
+            OutputFormat::Text => {
+                for entry in &entries {
+                    let status = entry.status;
+                    writeln!(
+                        handle,
+                        "{:<10}  {}/{}: {} -> {} (ahead {}, behind {})",
+                        status.state().name_lower(),
+                        entry.collection,
+                        entry.name,
+                        status.branch(),
+                        status.upstream(),
+                        status.ahead(),
+                        status.behind()
+                    )?;
+                }
+            }
+            OutputFormat::Json => write_json(&mut handle, &entries)?,
+            OutputFormat::Csv => {
+                write_csv_record(
+                    &mut handle,
+                    &[
+                        "collection",
+                        "name",
+                        "path",
+                        "branch",
+                        "upstream",
+                        "ahead",
+                        "behind",
+                        "state",
+                    ],
+                )?;
+                for entry in &entries {
+                    let status = entry.status;
+                    write_csv_record(
+                        &mut handle,
+                        &[
+                            entry.collection.as_str(),
+                            &entry.name,
+                            &entry.path.to_string_lossy(),
+                            status.branch(),
+                            status.upstream(),
+                            &status.ahead().to_string(),
+                            &status.behind().to_string(),
+                            status.state().name_lower(),
+                        ],
+                    )?;
+                }
+            }
+        }
+
+        if num_errors != 0 {
+            bail!(
+                "Failed to compare branches of {} repositories with upstreams",
+                num_errors
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Branch entry of the report.
+#[derive(Debug, Serialize)]
+struct BranchEntry<'a> {
+    /// Collection name.
+    collection: &'a CollectionName,
+    /// Repository name.
+    name: String,
+    /// Absolute path of the repository.
+    path: &'a Path,
+    /// Branch compared with the upstream.
+    #[serde(flatten)]
+    status: &'a BranchUpstream,
+}
+
+impl<'a> BranchEntry<'a> {
+    /// Creates a new entry.
+    fn new(repo: &'a RepoView, status: &'a BranchUpstream) -> Self {
+        Self {
+            collection: repo.collection(),
+            name: repo.name_string(),
+            path: repo.path(),
+            status,
+        }
+    }
+}
@@ -0,0 +1,284 @@
+//! Machine-readable outputs.
+
+use std::{borrow::Cow, fmt, io, str};
+
+use anyhow::anyhow;
+use serde::Serialize;
+
+/// Output format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OutputFormat {
+    /// Human-readable text.
+    Text,
+    /// JSON.
+    Json,
+    /// CSV.
+    Csv,
+}
+
+impl OutputFormat {
+    /// Returns a list of possible options.
+    #[inline]
+    #[must_use]
+    pub(crate) fn possible_opt_values() -> &'static [&'static str] {
+        &["text", "json", "csv"]
+    }
+
+    /// Returns the option value.
+    #[inline]
+    #[must_use]
+    fn as_opt_value(&self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Json => "json",
+            Self::Csv => "csv",
+        }
+    }
+
+    /// Parses the option value.
+    #[inline]
+    #[must_use]
+    fn from_opt_value(s: &str) -> Option<Self> {
+        match s {
+            "text" => Some(Self::Text),
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+}
+
+impl Default for OutputFormat {
+    #[inline]
+    fn default() -> Self {
+        Self::Text
+    }
+}
+
+impl str::FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_opt_value(s).ok_or_else(|| anyhow!("Unsupported output format {:?}", s))
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_opt_value())
+    }
+}
+
+/// Quoting style of paths in text output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum QuoteStyle {
+    /// Quoting for POSIX shells.
+    Shell,
+    /// C-style string literal.
+    C,
+}
+
+impl QuoteStyle {
+    /// Returns a list of possible options.
+    #[inline]
+    #[must_use]
+    pub(crate) fn possible_opt_values() -> &'static [&'static str] {
+        &["shell", "c"]
+    }
+
+    /// Returns the option value.
+    #[inline]
+    #[must_use]
+    fn as_opt_value(&self) -> &'static str {
+        match self {
+            Self::Shell => "shell",
+            Self::C => "c",
+        }
+    }
+
+    /// Parses the option value.
+    #[inline]
+    #[must_use]
+    fn from_opt_value(s: &str) -> Option<Self> {
+        match s {
+            "shell" => Some(Self::Shell),
+            "c" => Some(Self::C),
+            _ => None,
+        }
+    }
+
+    /// Quotes the given bytes.
+    #[must_use]
+    pub(crate) fn quote<'a>(&self, s: &'a [u8]) -> Cow<'a, [u8]> {
+        match self {
+            Self::Shell => shell_quote(s),
+            Self::C => Cow::Owned(c_quote(s)),
+        }
+    }
+}
+
+impl str::FromStr for QuoteStyle {
+    type Err = anyhow::Error;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_opt_value(s).ok_or_else(|| anyhow!("Unsupported quoting style {:?}", s))
+    }
+}
+
+impl fmt::Display for QuoteStyle {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_opt_value())
+    }
+}
+
+/// Quotes the bytes for POSIX shells.
+///
+/// Strings consisting only of safe characters are not quoted. Others are
+/// enclosed by single quotes, and single quotes in them are written as `'\''`.
+#[must_use]
+fn shell_quote(s: &[u8]) -> Cow<'_, [u8]> {
+    let is_safe = |b: u8| b.is_ascii_alphanumeric() || b"_@%+=:,./-".contains(&b);
+    if !s.is_empty() && s.iter().all(|&b| is_safe(b)) {
+        return Cow::Borrowed(s);
+    }
+
+    let mut quoted = Vec::with_capacity(s.len() + 2);
+    quoted.push(b'\'');
+    for &b in s {
+        if b == b'\'' {
+            quoted.extend_from_slice(b"'\\''");
+        } else {
+            quoted.push(b);
+        }
+    }
+    quoted.push(b'\'');
+    Cow::Owned(quoted)
+}
+
+/// Quotes the bytes as a C-style string literal.
+///
+/// Backslashes, double quotes, and control characters are escaped.
+/// Bytes which are not valid UTF-8 are written as octal escapes.
+#[must_use]
+fn c_quote(mut s: &[u8]) -> Vec<u8> {
+    let mut quoted = Vec::with_capacity(s.len() + 2);
+    quoted.push(b'"');
+    while !s.is_empty() {
+        let (valid, invalid) = match str::from_utf8(s) {
+            Ok(valid) => (valid, &[][..]),
+            Err(e) => {
+                let valid_len = e.valid_up_to();
+                let invalid_len = e.error_len().unwrap_or(s.len() - valid_len);
+                let valid = str::from_utf8(&s[..valid_len])
+                    .expect("Should never fail: the prefix is valid UTF-8");
+                (valid, &s[valid_len..(valid_len + invalid_len)])
+            }
+        };
+        for c in valid.chars() {
+            match c {
+                '\\' => quoted.extend_from_slice(b"\\\\"),
+                '"' => quoted.extend_from_slice(b"\\\""),
+                '\t' => quoted.extend_from_slice(b"\\t"),
+                '\n' => quoted.extend_from_slice(b"\\n"),
+                '\r' => quoted.extend_from_slice(b"\\r"),
+                c if c.is_ascii_control() => write_octal_escape(&mut quoted, c as u8),
+                c => quoted.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+            }
+        }
+        for &b in invalid {
+            write_octal_escape(&mut quoted, b);
+        }
+        s = &s[(valid.len() + invalid.len())..];
+    }
+    quoted.push(b'"');
+    quoted
+}
+
+/// Writes the byte as an octal escape sequence (such as `\033`).
+fn write_octal_escape(buf: &mut Vec<u8>, b: u8) {
+    buf.extend_from_slice(&[
+        b'\\',
+        b'0' + (b >> 6),
+        b'0' + ((b >> 3) & 7),
+        b'0' + (b & 7),
+    ]);
+}
+
+/// Writes the given value as a JSON followed by a newline.
+pub(crate) fn write_json<W: io::Write, T: Serialize + ?Sized>(
+    writer: &mut W,
+    value: &T,
+) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *writer, value)?;
+    writer.write_all(b"\n")
+}
+
+/// Writes a CSV record.
+pub(crate) fn write_csv_record<W: io::Write>(writer: &mut W, fields: &[&str]) -> io::Result<()> {
+    for (i, field) in fields.iter().enumerate() {
+        if i != 0 {
+            writer.write_all(b",")?;
+        }
+        writer.write_all(csv_escape(field).as_bytes())?;
+    }
+    writer.write_all(b"\r\n")
+}
+
+/// Escapes the field for CSV (RFC 4180).
+#[must_use]
+fn csv_escape(field: &str) -> Cow<'_, str> {
+    if !field.contains(|c| matches!(c, ',' | '"' | '\r' | '\n')) {
+        return Cow::Borrowed(field);
+    }
+    Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_format_consistent_string_conversion() {
+        for &opt in OutputFormat::possible_opt_values() {
+            assert_eq!(opt, opt.parse::<OutputFormat>().unwrap().to_string())
+        }
+    }
+
+    #[test]
+    fn csv_escape_special_chars() {
+        assert_eq!(csv_escape("foo"), "foo");
+        assert_eq!(csv_escape("foo,bar"), "\"foo,bar\"");
+        assert_eq!(csv_escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_escape("a\nb"), "\"a\nb\"");
+    }
+
+    #[test]
+    fn quote_style_consistent_string_conversion() {
+        for &opt in QuoteStyle::possible_opt_values() {
+            assert_eq!(opt, opt.parse::<QuoteStyle>().unwrap().to_string())
+        }
+    }
+
+    #[test]
+    fn shell_quote_special_chars() {
+        assert_eq!(&*shell_quote(b"/home/foo/a-b.c"), b"/home/foo/a-b.c");
+        assert_eq!(&*shell_quote(b""), b"''");
+        assert_eq!(&*shell_quote(b"a b"), b"'a b'");
+        assert_eq!(&*shell_quote(b"it's"), b"'it'\\''s'");
+        assert_eq!(&*shell_quote(b"$HOME\n"), b"'$HOME\n'");
+    }
+
+    #[test]
+    fn c_quote_special_chars() {
+        assert_eq!(c_quote(b"a b"), b"\"a b\"");
+        assert_eq!(c_quote(b"say \"hi\"\\"), b"\"say \\\"hi\\\"\\\\\"");
+        assert_eq!(c_quote(b"a\tb\nc\x1b"), b"\"a\\tb\\nc\\033\"");
+        // U+3042 followed by an invalid byte.
+        assert_eq!(c_quote(b"\xe3\x81\x82\xff"), b"\"\xe3\x81\x82\\377\"");
+        assert_eq!(c_quote(b"x\xffy"), b"\"x\\377y\"");
+    }
+}
@@ -0,0 +1,78 @@
+//! `path` subcommand.
+
+use std::io::{self, Write};
+
+use anyhow::{bail, Context as _};
+use magro::{query, view::RepoView, Context};
+use structopt::StructOpt;
+
+use crate::select::RepoSelectOpt;
+
+/// Options for `path` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct PathOpt {
+    /// Target repositories.
+    #[structopt(flatten)]
+    select: RepoSelectOpt,
+    /// Prints all matching repositories from the best match, instead of only the best one.
+    #[structopt(long, short)]
+    all: bool,
+    /// Prints repository names instead of paths.
+    #[structopt(long)]
+    name: bool,
+    /// Query.
+    ///
+    /// Repository names (paths relative to the collection directory without
+    /// `.git` suffix) are searched in a fuzzy manner.
+    /// Multiple words are joined by `/`.
+    query: Vec<String>,
+}
+
+impl PathOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &Context) -> anyhow::Result<()> {
+        log::trace!(
+            "path select={:?}, all={}, name={}, query={:?}",
+            self.select,
+            self.all,
+            self.name,
+            self.query
+        );
+
+        let repos = self.select.select(context)?;
+        let query = self.query.join("/");
+        let matched = query::find(&repos, &query);
+        if matched.is_empty() {
+            bail!("No repositories matched the query {:?}", query);
+        }
+
+        let stdout = io::stdout();
+        let mut handle = stdout.lock();
+        let count = if self.all { matched.len() } else { 1 };
+        for repo in matched.into_iter().take(count) {
+            if self.name {
+                writeln!(handle, "{}", repo.name_string())?;
+            } else {
+                writeln!(handle, "{}", repo_dir(repo)?.display())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the working directory if available, or the repository directory.
+pub(crate) fn repo_dir(repo: &RepoView) -> anyhow::Result<std::path::PathBuf> {
+    let mut repo = repo.clone();
+    let has_workdir = repo
+        .resolve_workdir()
+        .with_context(|| format!("Failed to get working directory for {:?}", repo.path()))?;
+    Ok(if has_workdir {
+        repo.workdir()
+            .expect("Working directory is resolved")
+            .to_owned()
+    } else {
+        repo.path().to_owned()
+    })
+}
@@ -0,0 +1,226 @@
+//! `pick` subcommand.
+
+use std::io::{self, Write};
+
+use anyhow::{bail, Context as _};
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyModifiers},
+    queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, ClearType},
+};
+use magro::{query, view::RepoView, Context};
+use structopt::StructOpt;
+
+use crate::{path::repo_dir, select::RepoSelectOpt};
+
+/// Options for `pick` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct PickOpt {
+    /// Target repositories.
+    #[structopt(flatten)]
+    select: RepoSelectOpt,
+    /// Prints the repository name instead of the path.
+    #[structopt(long)]
+    name: bool,
+    /// Initial query.
+    #[structopt(long, default_value = "")]
+    query: String,
+}
+
+impl PickOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &Context) -> anyhow::Result<()> {
+        log::trace!(
+            "pick select={:?}, name={}, query={:?}",
+            self.select,
+            self.name,
+            self.query
+        );
+
+        let repos = self.select.select(context)?;
+        if repos.is_empty() {
+            bail!("No repositories found");
+        }
+
+        let picked = {
+            let mut picker = Picker::new(&repos, self.query.clone());
+            let _guard = TerminalGuard::new().context("Failed to set up the terminal")?;
+            picker.run().context("Failed to run the picker")?
+        };
+        let repo = match picked {
+            Some(repo) => repo,
+            None => bail!("Cancelled"),
+        };
+
+        if self.name {
+            println!("{}", repo.name_string());
+        } else {
+            println!("{}", repo_dir(repo)?.display());
+        }
+
+        Ok(())
+    }
+}
+
+/// Guard to restore the terminal state.
+///
+/// The picker is drawn on stderr so that stdout can be captured by shells
+/// (for example, `cd "$(magro pick)"`).
+struct TerminalGuard;
+
+impl TerminalGuard {
+    /// Enters raw mode and the alternate screen.
+    fn new() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let guard = Self;
+        crossterm::execute!(io::stderr(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = crossterm::execute!(io::stderr(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Interactive picker state.
+struct Picker<'a> {
+    /// Candidates.
+    repos: &'a [RepoView],
+    /// Current query.
+    query: String,
+    /// Repositories matching the current query, from the best match.
+    matched: Vec<&'a RepoView>,
+    /// Index of the selected item in `matched`.
+    selected: usize,
+}
+
+impl<'a> Picker<'a> {
+    /// Creates a new picker.
+    fn new(repos: &'a [RepoView], query: String) -> Self {
+        let mut picker = Self {
+            repos,
+            query,
+            matched: Vec::new(),
+            selected: 0,
+        };
+        picker.update_matches();
+        picker
+    }
+
+    /// Updates the matched repositories for the current query.
+    fn update_matches(&mut self) {
+        self.matched = query::find(self.repos, &self.query);
+        self.selected = 0;
+    }
+
+    /// Runs the event loop, and returns the picked repository.
+    ///
+    /// Returns `Ok(None)` if cancelled.
+    fn run(&mut self) -> io::Result<Option<&'a RepoView>> {
+        loop {
+            self.draw(&mut io::stderr())?;
+            let key = match event::read()? {
+                Event::Key(key) => key,
+                _ => continue,
+            };
+            let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+            match key.code {
+                KeyCode::Enter => return Ok(self.matched.get(self.selected).copied()),
+                KeyCode::Esc => return Ok(None),
+                KeyCode::Char('c' | 'g') if ctrl => return Ok(None),
+                KeyCode::Up => self.move_selection(-1),
+                KeyCode::Char('p' | 'k') if ctrl => self.move_selection(-1),
+                KeyCode::Down => self.move_selection(1),
+                KeyCode::Char('n' | 'j') if ctrl => self.move_selection(1),
+                KeyCode::Char('u') if ctrl => {
+                    self.query.clear();
+                    self.update_matches();
+                }
+                KeyCode::Backspace => {
+                    self.query.pop();
+                    self.update_matches();
+                }
+                KeyCode::Char(c) if !ctrl => {
+                    self.query.push(c);
+                    self.update_matches();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Moves the selection by the given offset, clamping to the matches.
+    fn move_selection(&mut self, offset: isize) {
+        if self.matched.is_empty() {
+            return;
+        }
+        let max = self.matched.len() - 1;
+        self.selected = if offset < 0 {
+            self.selected.saturating_sub(offset.unsigned_abs())
+        } else {
+            (self.selected + offset as usize).min(max)
+        };
+    }
+
+    /// Draws the picker.
+    fn draw<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let (width, height) = terminal::size()?;
+        let width = usize::from(width);
+        // The first line is for the prompt.
+        let rows = usize::from(height).saturating_sub(1);
+        // Scroll so that the selected item is visible.
+        let first = (self.selected + 1).saturating_sub(rows);
+
+        queue!(
+            out,
+            terminal::Clear(ClearType::All),
+            cursor::MoveTo(0, 0),
+            Print(truncate(
+                &format!(
+                    "> {}  ({}/{})",
+                    self.query,
+                    self.matched.len(),
+                    self.repos.len()
+                ),
+                width
+            )),
+        )?;
+        for (row, (i, repo)) in self
+            .matched
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(rows)
+            .enumerate()
+        {
+            // `row + 1` fits in `u16` since `rows < height`.
+            queue!(out, cursor::MoveTo(0, (row + 1) as u16))?;
+            let line = format!("{}/{}", repo.collection(), repo.name_string());
+            if i == self.selected {
+                queue!(
+                    out,
+                    SetAttribute(Attribute::Reverse),
+                    Print(truncate(&line, width)),
+                    SetAttribute(Attribute::Reset)
+                )?;
+            } else {
+                queue!(out, Print(truncate(&line, width)))?;
+            }
+        }
+        out.flush()
+    }
+}
+
+/// Truncates the string to fit in the given number of characters.
+fn truncate(s: &str, width: usize) -> &str {
+    match s.char_indices().nth(width) {
+        Some((pos, _)) => &s[..pos],
+        None => s,
+    }
+}
@@ -0,0 +1,103 @@
+//! External subcommands (plugins).
+//!
+//! An unknown subcommand `foo` runs an executable `magro-foo` found in
+//! `PATH`, git-style.
+//! The resolved config and cache directories are passed via environment
+//! variables, so that plugins can call `magro` with the same config.
+
+use std::{
+    env,
+    ffi::OsString,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{anyhow, Context as _};
+use magro::Context;
+
+/// Prefix of plugin executable names.
+const PLUGIN_PREFIX: &str = "magro-";
+
+/// Runs the plugin for the external subcommand.
+///
+/// `args` is the subcommand name followed by its arguments.
+pub(crate) fn run(context: &Context, args: &[OsString]) -> anyhow::Result<()> {
+    let (name, args) = args
+        .split_first()
+        .expect("Should never fail: external subcommand has a name");
+    let name = name
+        .to_str()
+        .ok_or_else(|| anyhow!("Unknown subcommand {:?}", name))?;
+    log::trace!("plugin name={:?}, args={:?}", name, args);
+
+    let bin = find_plugin(name).ok_or_else(|| {
+        anyhow!(
+            "Unknown subcommand `{}` (no `{}{}` found in PATH)",
+            name,
+            PLUGIN_PREFIX,
+            name
+        )
+    })?;
+    log::debug!("Running plugin {:?}", bin);
+
+    let mut command = Command::new(&bin);
+    command
+        .args(args)
+        .env("MAGRO_CONFIG_DIR", context.config_dir())
+        .env("MAGRO_CACHE_DIR", context.cache_dir());
+    match context.profile() {
+        Some(profile) => command.env("MAGRO_PROFILE", profile.as_str()),
+        None => command.env_remove("MAGRO_PROFILE"),
+    };
+    if let Ok(exe) = env::current_exe() {
+        command.env("MAGRO", exe);
+    }
+
+    exec(command).with_context(|| format!("Failed to run the plugin {:?}", bin))
+}
+
+/// Returns the path to the plugin executable for the subcommand.
+fn find_plugin(name: &str) -> Option<PathBuf> {
+    // Subcommand names should not be paths.
+    if name.is_empty() || name.contains(|c| c == '/' || c == '\\') {
+        return None;
+    }
+    let filename = format!("{}{}{}", PLUGIN_PREFIX, name, env::consts::EXE_SUFFIX);
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths)
+        .map(|dir| dir.join(&filename))
+        .find(|path| is_executable(path))
+}
+
+/// Returns true if the path is an executable file.
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata().map_or(false, |meta| {
+        meta.is_file() && meta.permissions().mode() & 0o111 != 0
+    })
+}
+
+/// Returns true if the path is an executable file.
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Replaces the current process with the command.
+///
+/// This returns only on failure.
+#[cfg(unix)]
+fn exec(mut command: Command) -> anyhow::Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    Err(command.exec().into())
+}
+
+/// Runs the command, and exits with its exit status.
+#[cfg(not(unix))]
+fn exec(mut command: Command) -> anyhow::Result<()> {
+    let status = command.status()?;
+    std::process::exit(status.code().unwrap_or(1));
+}
@@ -0,0 +1,191 @@
+//! Machine-readable progress events.
+
+use std::{
+    fmt,
+    io::{self, Write},
+    str,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use anyhow::anyhow;
+use magro::collection::CollectionName;
+use serde::Serialize;
+
+/// Progress format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ProgressFormat {
+    /// No progress events.
+    None,
+    /// JSON object per line.
+    Json,
+}
+
+impl ProgressFormat {
+    /// Returns a list of possible options.
+    #[inline]
+    #[must_use]
+    pub(crate) fn possible_opt_values() -> &'static [&'static str] {
+        &["none", "json"]
+    }
+
+    /// Returns the option value.
+    #[inline]
+    #[must_use]
+    fn as_opt_value(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Json => "json",
+        }
+    }
+
+    /// Parses the option value.
+    #[inline]
+    #[must_use]
+    fn from_opt_value(s: &str) -> Option<Self> {
+        match s {
+            "none" => Some(Self::None),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+impl str::FromStr for ProgressFormat {
+    type Err = anyhow::Error;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_opt_value(s).ok_or_else(|| anyhow!("Unsupported progress format {:?}", s))
+    }
+}
+
+impl fmt::Display for ProgressFormat {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_opt_value())
+    }
+}
+
+/// Whether to emit progress events as JSON.
+static JSON_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Sets the progress format used in the process.
+pub(crate) fn init(format: ProgressFormat) {
+    JSON_ENABLED.store(format == ProgressFormat::Json, Ordering::Relaxed);
+}
+
+/// Returns true if progress events are emitted.
+#[inline]
+#[must_use]
+pub(crate) fn is_enabled() -> bool {
+    JSON_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Kind of a progress event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum EventKind {
+    /// The operation on the target started.
+    Started,
+    /// Percentage of the operation changed.
+    Percent,
+    /// The operation on the target finished successfully.
+    Finished,
+    /// The operation on the target failed.
+    Failed,
+}
+
+/// Progress event.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct Event<'a> {
+    /// Event kind.
+    event: EventKind,
+    /// Operation (such as `clone` and `refresh`).
+    operation: &'a str,
+    /// Collection.
+    collection: &'a CollectionName,
+    /// Repository name, if the target is a repository.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    repo: Option<&'a str>,
+    /// Percentage (0 to 100) for `percent` events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    percent: Option<u32>,
+    /// Error message for `failed` events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl<'a> Event<'a> {
+    /// Creates a new event for the collection.
+    #[inline]
+    #[must_use]
+    pub(crate) fn new(
+        event: EventKind,
+        operation: &'a str,
+        collection: &'a CollectionName,
+    ) -> Self {
+        Self {
+            event,
+            operation,
+            collection,
+            repo: None,
+            percent: None,
+            error: None,
+        }
+    }
+
+    /// Sets the repository name.
+    #[inline]
+    #[must_use]
+    pub(crate) fn repo(self, repo: &'a str) -> Self {
+        Self {
+            repo: Some(repo),
+            ..self
+        }
+    }
+
+    /// Sets the percentage.
+    #[inline]
+    #[must_use]
+    pub(crate) fn percent(self, percent: u32) -> Self {
+        Self {
+            percent: Some(percent),
+            ..self
+        }
+    }
+
+    /// Sets the error.
+    #[inline]
+    #[must_use]
+    pub(crate) fn error(self, error: &anyhow::Error) -> Self {
+        Self {
+            error: Some(format!("{:#}", error)),
+            ..self
+        }
+    }
+
+    /// Emits the event to stderr if enabled.
+    pub(crate) fn emit(&self) {
+        if !is_enabled() {
+            return;
+        }
+        let stderr = io::stderr();
+        let mut handle = stderr.lock();
+        // Nothing can be done if writing to stderr fails.
+        let _ = serde_json::to_writer(&mut handle, self)
+            .map_err(io::Error::from)
+            .and_then(|()| writeln!(handle));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_format_consistent_string_conversion() {
+        for &opt in ProgressFormat::possible_opt_values() {
+            assert_eq!(opt, opt.parse::<ProgressFormat>().unwrap().to_string())
+        }
+    }
+}
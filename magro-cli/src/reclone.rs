@@ -0,0 +1,136 @@
+//! `reclone` subcommand.
+
+use std::path::Path;
+
+use anyhow::{bail, Context as _};
+use magro::{view::RepoView, Context};
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::OptionBool,
+    clone::ClonePlan,
+    select::{find_by_name, RepoSelectOpt},
+};
+
+/// Options for `reclone` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct RecloneOpt {
+    /// Target repositories.
+    #[structopt(flatten)]
+    select: RepoSelectOpt,
+    /// Runs the operation as possible even when errors are detected.
+    ///
+    /// Note that errors are ignored during the operation, but the program will
+    /// exit with failure (i.e. errors won't be completely ignored).
+    #[structopt(long)]
+    keep_going: bool,
+    /// Only prints the repositories to be cloned.
+    #[structopt(long)]
+    dry_run: bool,
+    /// Names of the repositories to reclone.
+    ///
+    /// Names are paths relative to the collection directory without `.git`
+    /// suffix (for example, `github.com/lo48576/magro`).
+    /// If no names are given, all cached repositories whose directories are
+    /// missing are recloned (except for ones without recorded source URIs).
+    names: Vec<String>,
+}
+
+impl RecloneOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &mut Context) -> anyhow::Result<()> {
+        log::trace!(
+            "reclone select={:?}, keep_going={}, dry_run={}, names={:?}",
+            self.select,
+            self.keep_going,
+            self.dry_run,
+            self.names
+        );
+
+        let repos = self.select.select(context)?;
+        let targets = if self.names.is_empty() {
+            repos
+                .into_iter()
+                .filter(|repo| !repo.path().exists())
+                .filter(|repo| {
+                    let recorded = repo.source_uri().is_some();
+                    if !recorded {
+                        log::warn!(
+                            "Skipping missing repository {:?} because its source URI is not recorded",
+                            repo.path()
+                        );
+                    }
+                    recorded
+                })
+                .collect::<Vec<_>>()
+        } else {
+            self.names
+                .iter()
+                .map(|name| find_named(&repos, name))
+                .collect::<anyhow::Result<Vec<_>>>()?
+        };
+        log::debug!("{} repositories to reclone", targets.len());
+
+        let mut num_errors = 0_usize;
+        for repo in &targets {
+            if let Err(e) = reclone_repo(context, repo, self.dry_run) {
+                if !self.keep_going {
+                    return Err(e);
+                }
+                log::error!("{:#}", e);
+                num_errors += 1;
+            }
+        }
+
+        if num_errors != 0 {
+            bail!("Failed to reclone {} repositories", num_errors);
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the missing repository with the given name.
+fn find_named(repos: &[RepoView], name: &str) -> anyhow::Result<RepoView> {
+    let repo = find_by_name(repos, name)?;
+    if repo.path().exists() {
+        bail!("Repository `{}` already exists at {:?}", name, repo.path());
+    }
+
+    Ok(repo)
+}
+
+/// Clones the repository from the recorded source URI into the original path.
+fn reclone_repo(context: &mut Context, repo: &RepoView, dry_run: bool) -> anyhow::Result<()> {
+    let uri = repo.source_uri().with_context(|| {
+        format!(
+            "Cannot reclone {:?} because its source URI is not recorded",
+            repo.path()
+        )
+    })?;
+    let relpath = repo.relative_path();
+    let (reldest, bare) = if relpath.file_name() == Some(".git".as_ref()) {
+        (
+            relpath.parent().unwrap_or_else(|| Path::new("")),
+            OptionBool::No,
+        )
+    } else {
+        (relpath, OptionBool::Yes)
+    };
+    let plan = ClonePlan::new(
+        context,
+        uri,
+        Some(repo.collection()),
+        Some(reldest),
+        Some(repo.vcs()),
+        bare,
+    )?;
+
+    if dry_run {
+        println!("{} -> {}", uri, plan.destination().display());
+        return Ok(());
+    }
+    log::info!("Recloning {:?} from {:?}", plan.destination(), uri);
+    plan.run(context)
+}
@@ -1,44 +1,51 @@
 //! `refresh` subcommand.
 
-use anyhow::{bail, Context as _};
+use std::{path::Path, time::SystemTime};
+
+use anyhow::{anyhow, Context as _};
 use magro::{
-    cache::{CollectionReposCache, RepoCacheEntry},
+    cache::{CollectionFingerprint, CollectionReposCache, RepoCacheEntry},
     collection::{Collection, CollectionName},
+    hooks::{self, HookEnv, HookEvent},
+    metadata::unix_now,
     Context,
 };
 use structopt::StructOpt;
 
-use crate::cli_opt::CollectionNameList;
+use crate::{
+    cli_opt::{CollectionNameList, GlobalOpt},
+    error::CollectionNotFound,
+    progress::{Event, EventKind},
+};
 
 /// Options for `refresh` subcommand.
 #[derive(Debug, Clone, StructOpt)]
 #[non_exhaustive]
 pub struct RefreshOpt {
-    /// Show verbose log.
-    #[structopt(long, short, multiple = true)]
-    verbose: bool,
-    /// Do not show log.
-    ///
-    /// This is default, and only useful when disabling previously specified
-    /// `--verbose` option.
-    // This field only exists to make it possible to disable `--verbose`, and
-    // is not intended for use by any other code than structopt.
-    // `verbose` field should always be used to get loglevel.
-    #[structopt(
-        long = "quiet",
-        short = "q",
-        overrides_with = "verbose",
-        multiple = true
-    )]
-    _quiet: bool,
     /// Runs the operation as possible even when errors are detected.
     ///
     /// Note that errors are ignored during the operation, but the program will
     /// exit with failure (i.e. errors won't be completely ignored).
     #[structopt(long)]
     keep_going: bool,
+    /// Refreshes collections even if they seem unchanged.
+    ///
+    /// Without this option, collections are skipped if the modification times
+    /// and the numbers of entries of the directories above the repositories
+    /// (such as `host` and `host/owner` directories) are unchanged since the
+    /// last refresh. Note that changes deeper than the deepest repository are
+    /// not detected by this check.
+    #[structopt(long)]
+    force: bool,
+    /// Also collects metadata of repositories (such as languages).
+    ///
+    /// Without this option, previously collected metadata is kept for
+    /// repositories which still exist.
+    #[structopt(long)]
+    metadata: bool,
     /// Collections to refresh.
     ///
+    /// Glob patterns such as `work-*` are expanded to the matching collections.
     /// If no collections are specified, it behaves as all collections are given.
     #[structopt(long, short, parse(try_from_str), multiple = true)]
     collections: Vec<CollectionNameList>,
@@ -46,20 +53,20 @@ pub struct RefreshOpt {
 
 impl RefreshOpt {
     /// Runs the actual operation.
-    pub fn run(&self, context: &mut Context) -> anyhow::Result<()> {
+    pub fn run(&self, context: &mut Context, global: &GlobalOpt) -> anyhow::Result<()> {
         log::trace!(
-            "refresh collections={:?}, verbose={}",
+            "refresh collections={:?}, force={}, keep_going={}, metadata={}",
             self.collections,
-            self.verbose
+            self.force,
+            self.keep_going,
+            self.metadata
         );
+        let verbose = global.verbosity().is_verbose();
 
         let collections = context.config().collections();
-        let mut target_names = self
-            .collections
-            .iter()
-            .flatten()
+        let mut target_names = CollectionNameList::expand(&self.collections, collections)?
+            .into_iter()
             .map(|name| {
-                let name = name.to_owned();
                 if collections.get(&name).is_some() {
                     Ok(name)
                 } else {
@@ -85,27 +92,51 @@ impl RefreshOpt {
             refresh_collections(
                 context,
                 &mut target_names.into_iter().map(Ok),
-                self.verbose,
+                verbose,
+                self.force,
                 self.keep_going,
+                self.metadata,
             )
         } else {
-            refresh_collections(context, &mut target_names, self.verbose, self.keep_going)
+            refresh_collections(
+                context,
+                &mut target_names,
+                verbose,
+                self.force,
+                self.keep_going,
+                self.metadata,
+            )
         }
     }
 }
 
 /// Refreshes the collections.
+///
+/// Unless `force` or `metadata` is `true`, collections are skipped if their
+/// directories are unchanged since the last refresh (see
+/// [`CollectionFingerprint`]).
 // Using `dyn Iterator` won't be problem, because the number of collections is
 // expected to be small (for usual usage).
-fn refresh_collections(
+pub(crate) fn refresh_collections(
     context: &mut Context,
     target_collections: &mut dyn Iterator<Item = Result<CollectionName, CollectionName>>,
     verbose: bool,
+    force: bool,
     keep_going: bool,
+    metadata: bool,
 ) -> anyhow::Result<()> {
     use std::fmt::Write;
 
+    // Fail before discovery and hooks, since the cache is saved at last.
+    context
+        .ensure_writable()
+        .context("Cannot refresh collections")?;
+
     let mut error_collections: Vec<CollectionName> = Vec::new();
+    // Environments for `post-refresh` hooks, run after the cache is saved.
+    let mut post_refresh_envs = Vec::new();
+    // Refreshed caches, stored into the cache file at once.
+    let mut refreshed = Vec::new();
 
     for collection in target_collections {
         let (name, collection) = match collection {
@@ -115,36 +146,96 @@ fn refresh_collections(
                     .collections()
                     .get(&name)
                     .expect("Only valid collection names can be passed as `Ok` value");
-                (name, collection)
+                (collection.name().clone(), collection)
             }
             Err(name) => {
                 if keep_going {
                     log::error!("Collection named `{}` does not exist", name);
                     continue;
                 } else {
-                    bail!("Collection named `{}` does not exist", name);
+                    return Err(CollectionNotFound(name).into());
                 }
             }
         };
+        let root_dir = collection.abspath(context);
+        if !force && !metadata && is_unchanged(context, &name, &root_dir)? {
+            log::info!("Skipping unchanged collection `{}`", name);
+            continue;
+        }
         log::debug!("Refreshing collection `{}`", name);
 
+        let hook_env = HookEnv::new()
+            .with_vars(collection.env())
+            .with_var("MAGRO_COLLECTION", name.as_str())
+            .with_var("MAGRO_COLLECTION_PATH", &*root_dir);
+        let hook_env = if root_dir.is_dir() {
+            hook_env.with_cwd(&root_dir)
+        } else {
+            hook_env
+        };
+        hooks::run(context, HookEvent::PreRefresh, &hook_env)?;
+
+        // Cache before the refresh, to detect entries added by other
+        // processes during the refresh.
+        let loaded = context
+            .get_or_load_cache()
+            .context("Failed to load cache file")?
+            .collection_repos(&name)
+            .cloned();
+
+        let event = |kind| Event::new(kind, "refresh", &name);
+        event(EventKind::Started).emit();
         // `?` can be used here, because `generate_collection_repos_cache()`
         // could return `Err(_)` only when `keep_going` is false.
-        let collection_cache: Option<_> =
-            generate_collection_repos_cache(context, collection, verbose, keep_going)?;
+        let collection_cache: Option<_> = generate_collection_repos_cache(
+            context, collection, verbose, keep_going,
+        )
+        .map_err(|e| {
+            event(EventKind::Failed).error(&e).emit();
+            e
+        })?;
         if collection_cache.is_none() {
+            event(EventKind::Failed)
+                .error(&anyhow!("Failed to discover repositories"))
+                .emit();
             error_collections.push(name.clone());
         }
-        let collection_cache = collection_cache.unwrap_or_default();
+        let is_discovered = collection_cache.is_some();
+        let mut collection_cache = collection_cache.unwrap_or_default();
+        if metadata {
+            let _span = magro::timing::span(format!("metadata: {}", name));
+            collect_metadata(&mut collection_cache, &root_dir, keep_going).map_err(|e| {
+                event(EventKind::Failed).error(&e).emit();
+                e
+            })?;
+        }
+        if is_discovered {
+            event(EventKind::Finished).emit();
+        }
 
-        context
-            .get_or_load_cache_mut()
-            .context("Failed to load cache file")?
-            .cache_collection_repos(name, collection_cache);
+        refreshed.push((name, root_dir.into_owned(), loaded, collection_cache));
+
+        post_refresh_envs.push(hook_env);
     }
 
     // Save the cache file.
-    context.save_cache().context("Failed to save cache file")?;
+    // The latest cache is used, so that entries added by other processes
+    // during the refresh (for example, by `clone`) are inherited.
+    context
+        .update_cache(|cache| {
+            for (name, root_dir, loaded, mut collection_cache) in refreshed {
+                if let Some(latest) = cache.collection_repos(&name) {
+                    collection_cache.inherit_from(latest);
+                    collection_cache.keep_added_from(latest, loaded.as_ref(), &root_dir);
+                }
+                cache.cache_collection_repos(name, collection_cache);
+            }
+        })
+        .context("Failed to save cache file")?;
+
+    for hook_env in &post_refresh_envs {
+        hooks::run(context, HookEvent::PostRefresh, hook_env)?;
+    }
 
     if !error_collections.is_empty() {
         assert!(keep_going);
@@ -181,6 +272,7 @@ pub(crate) fn generate_collection_repos_cache(
         collection.name()
     );
 
+    let started_at = SystemTime::now();
     let repos = match discover_repositories(context, collection, verbose, keep_going) {
         Ok(v) => v,
         Err(e) => {
@@ -199,11 +291,75 @@ pub(crate) fn generate_collection_repos_cache(
 
     // Create the new collection cache.
     let mut collection_cache = CollectionReposCache::default();
+    collection_cache.set_refreshed_at(Some(unix_now()));
     collection_cache.extend(repos);
+    // Directories modified during the discovery can have changes not found
+    // by the discovery, so the collection should be refreshed next time.
+    let fingerprint =
+        CollectionFingerprint::of_collection(&collection.abspath(context), &collection_cache)
+            .ok()
+            .filter(|fingerprint| !fingerprint.is_modified_since(started_at));
+    collection_cache.set_fingerprint(fingerprint);
 
     Ok(Some(collection_cache))
 }
 
+/// Returns true if the collection directory is unchanged since the last refresh.
+fn is_unchanged(context: &Context, name: &CollectionName, root_dir: &Path) -> anyhow::Result<bool> {
+    let cache = context
+        .get_or_load_cache()
+        .context("Failed to load cache file")?
+        .collection_repos(name);
+    let (cache, cached) = match cache.and_then(|cache| Some((cache, cache.fingerprint()?))) {
+        Some(v) => v,
+        None => return Ok(false),
+    };
+
+    Ok(CollectionFingerprint::of_collection(root_dir, cache)
+        .ok()
+        .as_ref()
+        == Some(cached))
+}
+
+/// Collects metadata of the repositories in the collection cache.
+///
+/// If `keep_going` is `true`, errors are logged and the metadata of the
+/// repository is left empty.
+fn collect_metadata(
+    collection_cache: &mut CollectionReposCache,
+    root_dir: &Path,
+    keep_going: bool,
+) -> anyhow::Result<()> {
+    let mut error = None;
+    collection_cache.update_entries(|entry| {
+        if error.is_some() {
+            return;
+        }
+        let repo_path = root_dir.join(entry.path());
+        log::debug!("Collecting metadata of the repository {:?}", repo_path);
+        let vcs = entry.vcs();
+        match entry.metadata_mut().refresh(vcs, &repo_path) {
+            Ok(()) => {}
+            Err(e) if keep_going => log::error!(
+                "Failed to collect metadata of the repository {:?}: {}",
+                repo_path,
+                e
+            ),
+            Err(e) => {
+                error = Some(anyhow::Error::new(e).context(format!(
+                    "Failed to collect metadata of the repository {:?}",
+                    repo_path
+                )))
+            }
+        }
+    });
+
+    match error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
 /// Discovers the git directories.
 ///
 /// If the collection directory does not exist, this returns `Ok(_)`.
@@ -228,11 +384,13 @@ fn discover_repositories(
     keep_going: bool,
 ) -> anyhow::Result<Vec<RepoCacheEntry>> {
     let root_dir = collection.abspath(context);
+    let _span = magro::timing::span(format!("discovery: {}", collection.name()));
     let repos = match magro::discovery::RepoSeeker::new(&root_dir) {
         Ok(Some(repos)) => {
             let mut result: Vec<RepoCacheEntry> = Vec::new();
 
-            for entry in repos {
+            let colocated = context.config().main().discovery().colocated();
+            for entry in repos.colocated(colocated) {
                 let repo = match entry {
                     Ok(v) => v,
                     Err(e) => {
@@ -245,7 +403,16 @@ fn discover_repositories(
                     }
                 };
 
-                log::info!(
+                let is_excluded = repo
+                    .path()
+                    .strip_prefix(&root_dir)
+                    .map_or(false, |relpath| collection.is_excluded(relpath));
+                if is_excluded {
+                    log::debug!("Excluding the repository {:?}", repo.path());
+                    continue;
+                }
+
+                log::debug!(
                     "Found {} repository {:?}",
                     repo.vcs().name_lower(),
                     repo.path()
@@ -0,0 +1,216 @@
+//! `remote` subcommand.
+
+use anyhow::{bail, Context as _};
+use magro::{
+    remote::{self, UrlRewriteRule},
+    view::RepoView,
+    Context,
+};
+use structopt::StructOpt;
+
+use crate::{cli_opt::GlobalOpt, logging, select::RepoSelectOpt};
+
+/// Options for `remote` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct RemoteOpt {
+    /// Subcommand.
+    #[structopt(subcommand)]
+    subcommand: Subcommand,
+}
+
+impl RemoteOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &Context, global: &GlobalOpt) -> anyhow::Result<()> {
+        match &self.subcommand {
+            Subcommand::SetUrl(opt) => opt.run(context, global),
+        }
+    }
+}
+
+/// Subcommand of `remote`.
+#[derive(Debug, Clone, StructOpt)]
+pub enum Subcommand {
+    /// Rewrites remote URLs of repositories.
+    ///
+    /// The first occurrence of `<from>` in the fetch and push URLs of the
+    /// remotes is replaced with `<to>` (for example,
+    /// `magro remote set-url old-gitlab.example.com gitlab.example.com`).
+    SetUrl(RemoteSetUrlOpt),
+}
+
+/// Options for `remote set-url` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+pub struct RemoteSetUrlOpt {
+    /// Target repositories.
+    #[structopt(flatten)]
+    select: RepoSelectOpt,
+    /// Targets only the remotes with the given names.
+    ///
+    /// If not specified, all remotes are targeted.
+    #[structopt(long, use_delimiter = true)]
+    remote: Vec<String>,
+    /// Runs the operation as possible even when errors are detected.
+    ///
+    /// Note that errors are ignored during the operation, but the program will
+    /// exit with failure (i.e. errors won't be completely ignored).
+    #[structopt(long)]
+    keep_going: bool,
+    /// Only prints the URLs to be rewritten.
+    #[structopt(long)]
+    dry_run: bool,
+    /// Part of URLs to be replaced.
+    from: String,
+    /// Replacement.
+    to: String,
+}
+
+impl RemoteSetUrlOpt {
+    /// Runs the actual operation.
+    fn run(&self, context: &Context, global: &GlobalOpt) -> anyhow::Result<()> {
+        log::trace!(
+            "remote set-url select={:?}, remote={:?}, keep_going={}, dry_run={}, \
+             from={:?}, to={:?}",
+            self.select,
+            self.remote,
+            self.keep_going,
+            self.dry_run,
+            self.from,
+            self.to
+        );
+
+        if self.from.is_empty() {
+            bail!("The pattern to be replaced should not be empty");
+        }
+        let rules = vec![UrlRewriteRule::new(self.from.clone(), self.to.clone())];
+        let remotes = self.remote.clone();
+        let dry_run = self.dry_run;
+        let keep_going = self.keep_going;
+
+        let repos = self.select.select(context)?;
+        let envs = logging::CollectionEnvs::new(context);
+        let results = global.runner(context).run_until(
+            repos,
+            move |repo| {
+                logging::with_repo(repo, &envs, || {
+                    rewrite_remotes(repo, &rules, &remotes, dry_run)
+                })
+            },
+            move |result| !keep_going && result.is_err(),
+        );
+
+        let mut num_errors = 0_usize;
+        let mut num_rewritten = 0_usize;
+        for (repo, result) in results {
+            match result {
+                Ok(changes) => {
+                    for change in &changes {
+                        let kind = if change.push { " (push)" } else { "" };
+                        if self.dry_run {
+                            println!(
+                                "{}: {}{}: {} -> {}",
+                                repo.path().display(),
+                                change.remote,
+                                kind,
+                                change.old,
+                                change.new
+                            );
+                        } else {
+                            log::info!(
+                                "Rewrote {:?} remote{} of {:?} from {:?} to {:?}",
+                                change.remote,
+                                kind,
+                                repo.path(),
+                                change.old,
+                                change.new
+                            );
+                        }
+                    }
+                    num_rewritten += changes.len();
+                }
+                Err(e) => {
+                    if !self.keep_going {
+                        return Err(e);
+                    }
+                    log::error!("{:#}", e);
+                    num_errors += 1;
+                }
+            }
+        }
+
+        log::info!("{} remote URLs are rewritten", num_rewritten);
+        if num_errors != 0 {
+            bail!("Failed to rewrite remotes of {} repositories", num_errors);
+        }
+
+        Ok(())
+    }
+}
+
+/// Change of a remote URL.
+#[derive(Debug, Clone)]
+struct UrlChange {
+    /// Remote name.
+    remote: String,
+    /// Whether the URL is the push URL.
+    push: bool,
+    /// Old URL.
+    old: String,
+    /// New URL.
+    new: String,
+}
+
+/// Rewrites the remote URLs of the repository, or only computes the changes if `dry_run` is true.
+///
+/// If `targets` is empty, all remotes are targeted.
+fn rewrite_remotes(
+    repo: &RepoView,
+    rules: &[UrlRewriteRule],
+    targets: &[String],
+    dry_run: bool,
+) -> anyhow::Result<Vec<UrlChange>> {
+    let vcs = repo.vcs();
+    let path = repo.path();
+    let remotes = vcs
+        .remotes(path)
+        .with_context(|| format!("Failed to get remotes of {:?}", path))?;
+
+    let mut changes = Vec::new();
+    for urls in remotes {
+        let name = urls.name();
+        if !targets.is_empty() && !targets.iter().any(|target| target == name) {
+            continue;
+        }
+        for (push, url) in [(false, urls.url()), (true, urls.push_url())] {
+            let old = match url {
+                Some(v) => v,
+                None => continue,
+            };
+            let new = match remote::rewrite_url(rules, old) {
+                Some(v) => v,
+                None => continue,
+            };
+            if !dry_run {
+                if push {
+                    vcs.set_remote_push_url(path, name, &new)
+                } else {
+                    vcs.set_remote_url(path, name, &new).map(drop)
+                }
+                .with_context(|| {
+                    format!(
+                        "Failed to set the URL of the remote {:?} of {:?}",
+                        name, path
+                    )
+                })?;
+            }
+            changes.push(UrlChange {
+                remote: name.to_owned(),
+                push,
+                old: old.to_owned(),
+                new,
+            });
+        }
+    }
+
+    Ok(changes)
+}
@@ -0,0 +1,360 @@
+//! `repo` subcommand.
+
+use std::{
+    fmt,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use anyhow::{bail, Context as _};
+use magro::{metadata::unix_now, query, remote::RemoteUrls, tag::Tag, view::RepoView, Context};
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::GlobalOpt,
+    du::HumanSize,
+    output::{write_csv_record, write_json, OutputFormat},
+    select::{locate_repo, select_repos},
+};
+
+/// Options for `repo` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct RepoOpt {
+    /// Subcommand.
+    #[structopt(subcommand)]
+    subcommand: Subcommand,
+}
+
+impl RepoOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &mut Context, global: &GlobalOpt) -> anyhow::Result<()> {
+        match &self.subcommand {
+            Subcommand::Info { path, query } => {
+                log::trace!("repo info path={:?}, query={:?}", path, query);
+                let repo = target_repo(context, path.as_deref(), query)?;
+                show_info(context, repo, global.output)
+            }
+            Subcommand::Freeze { path, query } => {
+                log::trace!("repo freeze path={:?}, query={:?}", path, query);
+                let repo = target_repo(context, path.as_deref(), query)?;
+                set_frozen(context, &repo, true)
+            }
+            Subcommand::Unfreeze { path, query } => {
+                log::trace!("repo unfreeze path={:?}, query={:?}", path, query);
+                let repo = target_repo(context, path.as_deref(), query)?;
+                set_frozen(context, &repo, false)
+            }
+        }
+    }
+}
+
+/// Subcommand of `repo`.
+#[derive(Debug, Clone, StructOpt)]
+pub enum Subcommand {
+    /// Shows everything known about the repository.
+    ///
+    /// The collection, VCS, paths, current branch, remotes, tags, and cached
+    /// metadata (such as disk usage measured by `du`) are shown.
+    /// If neither the path nor the query is specified, the repository
+    /// containing the current directory is used.
+    Info {
+        /// Path to (or inside) the repository.
+        #[structopt(long, parse(from_os_str), conflicts_with = "query")]
+        path: Option<PathBuf>,
+        /// Query, same as `path` subcommand.
+        ///
+        /// The best matching repository is used.
+        query: Vec<String>,
+    },
+    /// Freezes the repository, so that bulk operations skip it.
+    ///
+    /// Frozen repositories are pinned at a specific state (for example,
+    /// checked out at a historical commit). They are skipped by `fetch`
+    /// (unless `--include-frozen` is specified) and `outdated --fetch`.
+    /// The repository is specified in the same way as `repo info`.
+    Freeze {
+        /// Path to (or inside) the repository.
+        #[structopt(long, parse(from_os_str), conflicts_with = "query")]
+        path: Option<PathBuf>,
+        /// Query, same as `path` subcommand.
+        query: Vec<String>,
+    },
+    /// Unfreezes the repository frozen by `repo freeze`.
+    Unfreeze {
+        /// Path to (or inside) the repository.
+        #[structopt(long, parse(from_os_str), conflicts_with = "query")]
+        path: Option<PathBuf>,
+        /// Query, same as `path` subcommand.
+        query: Vec<String>,
+    },
+}
+
+/// Returns the repository specified by the path or the query.
+///
+/// If neither is specified, the repository containing the current directory
+/// is returned.
+fn target_repo(
+    context: &Context,
+    path: Option<&Path>,
+    query: &[String],
+) -> anyhow::Result<RepoView> {
+    if query.is_empty() {
+        locate_repo(context, path)
+    } else {
+        find_repo(context, &query.join("/"))
+    }
+}
+
+/// Sets whether the repository is frozen.
+fn set_frozen(context: &mut Context, repo: &RepoView, frozen: bool) -> anyhow::Result<()> {
+    context
+        .ensure_writable()
+        .context("Cannot update the cache")?;
+    if repo.metadata().is_frozen() == frozen {
+        log::info!(
+            "{}/{} is already {}",
+            repo.collection(),
+            repo.name_string(),
+            if frozen { "frozen" } else { "unfrozen" }
+        );
+        return Ok(());
+    }
+    let updated = context
+        .update_cache(|cache| {
+            cache
+                .collection_repos_mut(repo.collection())
+                .map_or(false, |coll_cache| {
+                    coll_cache.update_entry(repo.relative_path(), |entry| {
+                        entry.metadata_mut().set_frozen(frozen);
+                    })
+                })
+        })
+        .context("Failed to update cache file")?;
+    if !updated {
+        bail!(
+            "{}/{} is no longer in the cache",
+            repo.collection(),
+            repo.name_string()
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns the cached repository best matching the query.
+fn find_repo(context: &Context, query: &str) -> anyhow::Result<RepoView> {
+    let repos = select_repos(context, &[], &[], &[])?;
+    match query::find(&repos, query).into_iter().next() {
+        Some(repo) => Ok(repo.clone()),
+        None => bail!("No repositories matched the query {:?}", query),
+    }
+}
+
+/// Details of a repository.
+#[derive(Serialize)]
+struct Info<'a> {
+    /// Cached information.
+    #[serde(flatten)]
+    repo: &'a RepoView,
+    /// Whether the repository is bare.
+    bare: bool,
+    /// Branch checked out at HEAD, or `None` if detached.
+    branch: Option<String>,
+    /// Remotes.
+    remotes: Vec<Remote>,
+    /// Tags.
+    tags: Vec<&'a Tag>,
+    /// Modification time of the cache file in seconds since the UNIX epoch.
+    cache_updated_at: Option<u64>,
+}
+
+impl Info<'_> {
+    /// Returns the fields as key-value pairs for text and CSV outputs.
+    ///
+    /// If `human` is true, sizes and timestamps are formatted for humans.
+    fn fields(&self, human: bool) -> Vec<(String, String)> {
+        let timestamp = |secs: u64| {
+            if human {
+                Timestamp(secs).to_string()
+            } else {
+                secs.to_string()
+            }
+        };
+        let size = |bytes: u64| {
+            if human {
+                HumanSize(bytes).to_string()
+            } else {
+                bytes.to_string()
+            }
+        };
+        let repo = self.repo;
+        let mut fields = vec![
+            ("collection".to_owned(), repo.collection().to_string()),
+            ("name".to_owned(), repo.name_string()),
+            ("vcs".to_owned(), repo.vcs().name_lower().to_owned()),
+            ("path".to_owned(), repo.path().display().to_string()),
+            (
+                "workdir".to_owned(),
+                repo.workdir()
+                    .map_or_else(String::new, |dir| dir.display().to_string()),
+            ),
+            ("bare".to_owned(), self.bare.to_string()),
+            (
+                "branch".to_owned(),
+                self.branch
+                    .clone()
+                    .unwrap_or_else(|| "(detached)".to_owned()),
+            ),
+        ];
+        for remote in &self.remotes {
+            fields.push((
+                format!("remote.{}.url", remote.name),
+                remote.url.clone().unwrap_or_default(),
+            ));
+            if let Some(push_url) = &remote.push_url {
+                fields.push((format!("remote.{}.push_url", remote.name), push_url.clone()));
+            }
+        }
+        fields.push((
+            "tags".to_owned(),
+            self.tags
+                .iter()
+                .map(|tag| tag.as_str())
+                .collect::<Vec<_>>()
+                .join(","),
+        ));
+        let metadata = repo.metadata();
+        if let Some(language) = metadata.language() {
+            fields.push(("language".to_owned(), language.to_owned()));
+        }
+        if let Some(license) = metadata.license() {
+            fields.push(("license".to_owned(), license.to_owned()));
+        }
+        if let Some(uri) = repo.source_uri() {
+            fields.push(("source_uri".to_owned(), uri.to_owned()));
+        }
+        fields.push(("provenance".to_owned(), repo.provenance().to_string()));
+        fields.push(("frozen".to_owned(), metadata.is_frozen().to_string()));
+        if let Some(last_commit_at) = metadata.last_commit_at() {
+            fields.push(("last_commit_at".to_owned(), timestamp(last_commit_at)));
+        }
+        if let Some(expires_at) = metadata.expires_at() {
+            fields.push(("expires_at".to_owned(), timestamp(expires_at)));
+        }
+        if let Some(usage) = metadata.disk_usage() {
+            fields.push(("disk_usage.git_dir".to_owned(), size(usage.git_dir())));
+            fields.push(("disk_usage.worktree".to_owned(), size(usage.worktree())));
+            fields.push(("disk_usage.total".to_owned(), size(usage.total())));
+            fields.push((
+                "disk_usage.measured_at".to_owned(),
+                timestamp(usage.measured_at()),
+            ));
+        }
+        if let Some(updated_at) = self.cache_updated_at {
+            fields.push(("cache_updated_at".to_owned(), timestamp(updated_at)));
+        }
+
+        fields
+    }
+}
+
+/// Remote of a repository.
+#[derive(Serialize)]
+struct Remote {
+    /// Remote name.
+    name: String,
+    /// Fetch URL.
+    url: Option<String>,
+    /// Push URL, if set separately from the fetch URL.
+    push_url: Option<String>,
+}
+
+impl From<RemoteUrls> for Remote {
+    #[inline]
+    fn from(remote: RemoteUrls) -> Self {
+        Self {
+            name: remote.name().to_owned(),
+            url: remote.url().map(ToOwned::to_owned),
+            push_url: remote.push_url().map(ToOwned::to_owned),
+        }
+    }
+}
+
+/// Timestamp in seconds since the UNIX epoch, displayed with the elapsed time.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Timestamp(pub(crate) u64);
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        /// Seconds per day.
+        const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+        let now = unix_now();
+        if self.0 <= now {
+            write!(f, "{} ({} days ago)", self.0, (now - self.0) / SECS_PER_DAY)
+        } else {
+            write!(f, "{} (in {} days)", self.0, (self.0 - now) / SECS_PER_DAY)
+        }
+    }
+}
+
+/// Shows the details of the repository.
+fn show_info(context: &Context, mut repo: RepoView, output: OutputFormat) -> anyhow::Result<()> {
+    let vcs = repo.vcs();
+    let path = repo.path().to_owned();
+    let has_workdir = repo
+        .resolve_workdir()
+        .with_context(|| format!("Failed to open the repository {:?}", path))?;
+    let branch = vcs
+        .head_branch(&path)
+        .with_context(|| format!("Failed to get the HEAD of {:?}", path))?;
+    let remotes = vcs
+        .remotes(&path)
+        .with_context(|| format!("Failed to get remotes of {:?}", path))?;
+    let tags = context
+        .get_or_load_tags()
+        .context("Failed to load tags file")?
+        .get(repo.collection(), repo.relative_path())
+        .map(|tags| tags.iter().collect())
+        .unwrap_or_default();
+    let cache_updated_at = context
+        .cache_path()
+        .metadata()
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+        .map(|elapsed| elapsed.as_secs());
+
+    let info = Info {
+        repo: &repo,
+        bare: !has_workdir,
+        branch,
+        remotes: remotes.into_iter().map(Remote::from).collect(),
+        tags,
+        cache_updated_at,
+    };
+
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    match output {
+        OutputFormat::Text => {
+            let fields = info.fields(true);
+            let width = fields.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+            for (key, value) in &fields {
+                writeln!(handle, "{:<width$}  {}", key, value, width = width)?;
+            }
+        }
+        OutputFormat::Json => write_json(&mut handle, &info)?,
+        OutputFormat::Csv => {
+            write_csv_record(&mut handle, &["key", "value"])?;
+            for (key, value) in &info.fields(false) {
+                write_csv_record(&mut handle, &[key, value])?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,100 @@
+//! Resumable bulk operations.
+
+use std::sync::Arc;
+
+use anyhow::Context as _;
+use magro::{jobs::JobJournal, view::RepoView, Context};
+use structopt::StructOpt;
+
+/// Options for resumable bulk operations.
+#[derive(Debug, Clone, StructOpt)]
+pub(crate) struct ResumeOpt {
+    /// Starts over, discarding the progress of the interrupted run.
+    ///
+    /// Without this, jobs completed by the interrupted run on the same targets
+    /// are skipped.
+    #[structopt(long)]
+    restart: bool,
+}
+
+impl ResumeOpt {
+    /// Opens the journal of the operation on the given jobs.
+    ///
+    /// Returns `None` in read-only mode, since the progress cannot be saved.
+    pub(crate) fn open_journal<I, S>(
+        &self,
+        context: &Context,
+        operation: &str,
+        jobs: I,
+    ) -> anyhow::Result<Option<Arc<JobJournal>>>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        if context.is_read_only() {
+            log::debug!("Progress of `{}` is not saved in read-only mode", operation);
+            return Ok(None);
+        }
+        let journal = JobJournal::open(
+            context.jobs_dir(),
+            operation,
+            jobs,
+            !self.restart,
+            context.config().main().dir_mode(),
+        )
+        .with_context(|| format!("Failed to open the journal of `{}`", operation))?;
+        if journal.num_completed() != 0 {
+            log::info!(
+                "Resuming the interrupted `{}`: {} jobs are already completed",
+                operation,
+                journal.num_completed()
+            );
+        }
+
+        Ok(Some(Arc::new(journal)))
+    }
+}
+
+/// Returns the job key of the repository.
+#[must_use]
+pub(crate) fn repo_job_key(repo: &RepoView) -> String {
+    format!("{}/{}", repo.collection(), repo.relative_path().display())
+}
+
+/// Returns the repositories not completed by the interrupted run.
+#[must_use]
+pub(crate) fn skip_completed(
+    journal: Option<&JobJournal>,
+    mut repos: Vec<RepoView>,
+) -> Vec<RepoView> {
+    if let Some(journal) = journal {
+        repos.retain(|repo| !journal.is_completed(&repo_job_key(repo)));
+    }
+    repos
+}
+
+/// Records the completion of the job.
+///
+/// Failures are only logged, since they only affect resuming.
+pub(crate) fn complete(journal: Option<&JobJournal>, job: &str) {
+    if let Some(journal) = journal {
+        if let Err(e) = journal.complete(job) {
+            log::warn!("Failed to record the completion of {:?}: {}", job, e);
+        }
+    }
+}
+
+/// Removes the journal, as all jobs are completed.
+pub(crate) fn finish(journal: Option<Arc<JobJournal>>) {
+    let journal = match journal.map(Arc::try_unwrap) {
+        Some(Ok(v)) => v,
+        Some(Err(_)) => {
+            log::debug!("The journal is still in use, and not removed");
+            return;
+        }
+        None => return,
+    };
+    if let Err(e) = journal.finish() {
+        log::warn!("Failed to remove the journal: {}", e);
+    }
+}
@@ -0,0 +1,245 @@
+//! `rm` subcommand.
+
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, bail, Context as _};
+use magro::{
+    hooks::{self, HookEnv, HookEvent},
+    safety::{self, RiskCategory},
+    view::RepoView,
+    Context,
+};
+use structopt::StructOpt;
+
+use crate::{
+    path::repo_dir,
+    select::{find_by_name, RepoSelectOpt},
+    trash::purge_expired,
+};
+
+/// Options for `rm` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct RmOpt {
+    /// Target repositories.
+    #[structopt(flatten)]
+    select: RepoSelectOpt,
+    /// Removes repositories even if they can lose work.
+    ///
+    /// By default, repositories with uncommitted changes (`dirty`), stashes
+    /// (`stashes`), unpushed branches (`unpushed-branches`), or tags not
+    /// pushed to any remote (`unpushed-tags`) are not removed.
+    /// Tags are compared with the tags on the remotes, so the remotes are
+    /// contacted unless `unpushed-tags` risks are ignored. Remotes whose tags
+    /// cannot be listed are reported as `unpushed-tags` risks.
+    /// If categories are given (for example, `--force=dirty,stashes`), only
+    /// the risks in the categories are ignored.
+    #[structopt(
+        long,
+        require_equals = true,
+        min_values = 0,
+        use_delimiter = true,
+        possible_values = RiskCategory::names(),
+    )]
+    force: Option<Vec<RiskCategory>>,
+    /// Deletes repositories immediately even if the trash is enabled.
+    #[structopt(long)]
+    permanent: bool,
+    /// Only prints the repositories to be removed.
+    #[structopt(long)]
+    dry_run: bool,
+    /// Names of the repositories to remove.
+    ///
+    /// Names are paths relative to the collection directory without `.git`
+    /// suffix (for example, `github.com/lo48576/magro`).
+    #[structopt(required = true, min_values = 1)]
+    names: Vec<String>,
+}
+
+impl RmOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &mut Context) -> anyhow::Result<()> {
+        log::trace!(
+            "rm select={:?}, force={:?}, permanent={}, dry_run={}, names={:?}",
+            self.select,
+            self.force,
+            self.permanent,
+            self.dry_run,
+            self.names
+        );
+
+        let repos = self.select.select(context)?;
+        let targets = self
+            .names
+            .iter()
+            .map(|name| find_by_name(&repos, name))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        // `--force` without categories ignores all the risks.
+        let skip_check = self.force.as_ref().map_or(false, Vec::is_empty);
+        if !skip_check {
+            let overrides = self.force.as_deref().unwrap_or_default();
+            let num_unsafe = targets
+                .iter()
+                .filter(|repo| repo.path().exists())
+                .map(|repo| {
+                    let issues = lost_work(repo, overrides)?;
+                    if !issues.is_empty() {
+                        log::error!("{}: {}", repo.path().display(), issues.join(", "));
+                    }
+                    Ok(!issues.is_empty())
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?
+                .into_iter()
+                .filter(|&is_unsafe| is_unsafe)
+                .count();
+            if num_unsafe != 0 {
+                bail!(
+                    "Refusing to remove repositories: {} repositories can lose work \
+                     (use `--force` to remove them anyway)",
+                    num_unsafe
+                );
+            }
+        }
+
+        if !self.dry_run {
+            context
+                .ensure_writable()
+                .context("Cannot remove repositories")?;
+        }
+        let use_trash = context.config().main().trash().enabled() && !self.permanent;
+        for repo in &targets {
+            if self.dry_run {
+                println!("{}", repo.path().display());
+                continue;
+            }
+            remove_repo(context, repo, use_trash)?;
+        }
+        if use_trash && !self.dry_run {
+            purge_expired(context)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the descriptions of the work which would be lost by removal.
+///
+/// Risks in the `overrides` categories are ignored.
+pub(crate) fn lost_work(
+    repo: &RepoView,
+    overrides: &[RiskCategory],
+) -> anyhow::Result<Vec<String>> {
+    let verdict = safety::check_removable(repo, overrides)
+        .with_context(|| format!("Failed to check the repository {:?}", repo.path()))?;
+    Ok(verdict
+        .blocking(overrides)
+        .map(ToString::to_string)
+        .collect())
+}
+
+/// Removes the repository, its cache entry, and its tags.
+///
+/// If `use_trash` is true, the repository is moved to the trash instead of
+/// being deleted.
+/// Parent directories which become empty are also removed.
+/// `pre-remove` and `post-remove` hooks are also run.
+pub(crate) fn remove_repo(
+    context: &mut Context,
+    repo: &RepoView,
+    use_trash: bool,
+) -> anyhow::Result<()> {
+    let collection = context
+        .config()
+        .collections()
+        .get(repo.collection())
+        .with_context(|| format!("Collection `{}` does not exist", repo.collection()))?;
+    let collection_dir = collection.abspath(context).into_owned();
+    let exists = repo.path().exists();
+    let dir = if exists {
+        repo_dir(repo)?
+    } else {
+        repo.path().to_owned()
+    };
+
+    let hook_env = HookEnv::new()
+        .with_vars(collection.env())
+        .with_var("MAGRO_COLLECTION", repo.collection().as_str())
+        .with_var("MAGRO_COLLECTION_PATH", &collection_dir)
+        .with_var("MAGRO_REPO_PATH", &dir);
+    let hook_env = if collection_dir.is_dir() {
+        hook_env.with_cwd(&collection_dir)
+    } else {
+        hook_env
+    };
+    hooks::run(context, HookEvent::PreRemove, &hook_env)?;
+
+    if exists {
+        let reldir = dir
+            .strip_prefix(&collection_dir)
+            .with_context(|| {
+                format!(
+                    "Repository {:?} is not in the collection directory {:?}",
+                    dir, collection_dir
+                )
+            })?
+            .to_owned();
+        if use_trash {
+            let entry = context
+                .get_or_load_cache()
+                .context("Failed to load cache file")?
+                .collection_repos(repo.collection())
+                .and_then(|repos| repos.get(repo.relative_path()))
+                .cloned()
+                .ok_or_else(|| anyhow!("Repository {:?} is not in the cache", repo.path()))?;
+            let collection = repo.collection().clone();
+            let id = context
+                .update_trash(|trash| {
+                    trash
+                        .put(collection, reldir, &dir, entry)
+                        .map(|item| item.id().to_owned())
+                })
+                .context("Failed to update the trash index")?
+                .with_context(|| format!("Failed to move {:?} to the trash", dir))?;
+            log::info!("Moved {:?} to the trash (ID: {})", dir, id);
+        } else {
+            fs::remove_dir_all(&dir).with_context(|| format!("Failed to remove {:?}", dir))?;
+            log::info!("Removed {:?}", dir);
+        }
+        remove_empty_parents(&dir, &collection_dir);
+    }
+
+    context
+        .update_cache(|cache| {
+            if let Some(repos) = cache.collection_repos_mut(repo.collection()) {
+                repos.remove(repo.relative_path());
+            }
+        })
+        .context("Failed to update cache file")?;
+    let has_tags = context
+        .get_or_load_tags()
+        .context("Failed to load tags file")?
+        .get(repo.collection(), repo.relative_path())
+        .is_some();
+    if has_tags {
+        context
+            .update_tags(|tags| tags.remove_repo(repo.collection(), repo.relative_path()))
+            .context("Failed to update tags file")?;
+    }
+
+    hooks::run(context, HookEvent::PostRemove, &hook_env)?;
+
+    Ok(())
+}
+
+/// Removes empty ancestor directories of `dir` under `base`.
+fn remove_empty_parents(dir: &Path, base: &Path) {
+    for ancestor in dir.ancestors().skip(1) {
+        if ancestor == base || !ancestor.starts_with(base) {
+            break;
+        }
+        // Stop at non-empty directories.
+        if fs::remove_dir(ancestor).is_err() {
+            break;
+        }
+    }
+}
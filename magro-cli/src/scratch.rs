@@ -0,0 +1,150 @@
+//! `try` subcommand.
+
+use std::path::PathBuf;
+
+use anyhow::bail;
+use magro::{metadata, vcs::Vcs, Context};
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::OptionBool,
+    clone::ClonePlan,
+    rm::{lost_work, remove_repo},
+    select::select_repos,
+    trash::purge_expired,
+};
+
+/// Seconds in a day.
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Options for `try` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct TryOpt {
+    /// URI of the repository.
+    #[structopt(required_unless = "clean")]
+    uri: Option<String>,
+    /// Relative path of the destination directory in the scratch collection.
+    #[structopt(long, short, parse(from_os_str))]
+    destination: Option<PathBuf>,
+    /// VCS to use.
+    ///
+    /// If not specified, the program attempt to detect VCS automatically.
+    #[structopt(
+        long,
+        possible_values = &Vcs::variants().map(|v| v.name_lower()).collect::<Vec<_>>(),
+    )]
+    vcs: Option<Vcs>,
+    /// Days until the clone expires.
+    ///
+    /// If not specified, `ttl-days` in `[scratch]` section of the main config
+    /// is used (default: 7).
+    #[structopt(long)]
+    ttl_days: Option<u64>,
+    /// Removes expired scratch clones instead of cloning.
+    ///
+    /// Clones with local modifications (such as uncommitted changes, unpushed
+    /// commits, and stashes) are kept.
+    /// If the trash is enabled, expired clones are moved to the trash.
+    #[structopt(long, conflicts_with_all = &["uri", "destination", "vcs", "ttl-days"])]
+    clean: bool,
+    /// Only prints the scratch clones to be removed.
+    #[structopt(long, requires = "clean")]
+    dry_run: bool,
+}
+
+impl TryOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &mut Context) -> anyhow::Result<()> {
+        log::trace!(
+            "try uri={:?}, dest={:?}, vcs={:?}, ttl_days={:?}, clean={}, dry_run={}",
+            self.uri,
+            self.destination,
+            self.vcs,
+            self.ttl_days,
+            self.clean,
+            self.dry_run
+        );
+
+        if self.clean {
+            return clean(context, self.dry_run);
+        }
+        let uri = self
+            .uri
+            .as_deref()
+            .expect("Should never fail: URI is required unless `--clean` is specified");
+
+        let scratch = context.config().main().scratch();
+        let collection = scratch.collection();
+        let ttl_days = self.ttl_days.unwrap_or_else(|| scratch.ttl_days());
+        if context.config().collections().get(&collection).is_none() {
+            bail!(
+                "Scratch collection `{0}` does not exist \
+                 (add it by `magro collection add {0} <path>`, \
+                 or set `collection` in `[scratch]` section of the main config)",
+                collection
+            );
+        }
+
+        let expires_at = metadata::unix_now() + ttl_days.saturating_mul(SECS_PER_DAY);
+        let plan = ClonePlan::new(
+            context,
+            uri,
+            Some(&collection),
+            self.destination.as_deref(),
+            self.vcs,
+            OptionBool::No,
+        )?
+        .expires_at(expires_at);
+        let dest = plan.destination().to_owned();
+        plan.run(context)?;
+        log::info!("Cloned {:?} into {:?} for {} days", uri, dest, ttl_days);
+        println!("{}", dest.display());
+
+        Ok(())
+    }
+}
+
+/// Removes expired scratch clones without local modifications.
+///
+/// If the trash is enabled, the clones are moved to the trash.
+fn clean(context: &mut Context, dry_run: bool) -> anyhow::Result<()> {
+    let collection = context.config().main().scratch().collection();
+    if context.config().collections().get(&collection).is_none() {
+        log::info!(
+            "Scratch collection `{}` does not exist; nothing to clean",
+            collection
+        );
+        return Ok(());
+    }
+    let expired = select_repos(context, &[&collection], &[], &[])?
+        .into_iter()
+        .filter(|repo| repo.metadata().is_expired())
+        .collect::<Vec<_>>();
+
+    let use_trash = context.config().main().trash().enabled();
+    let mut num_removed = 0_usize;
+    for repo in &expired {
+        if repo.path().exists() && !lost_work(repo, &[])?.is_empty() {
+            log::warn!(
+                "Keeping expired scratch clone {:?} because it has local modifications",
+                repo.path()
+            );
+            continue;
+        }
+        if dry_run {
+            println!("{}", repo.path().display());
+            continue;
+        }
+        remove_repo(context, repo, use_trash)?;
+        num_removed += 1;
+    }
+    if !dry_run {
+        log::info!("Removed {} expired scratch clones", num_removed);
+        if use_trash {
+            purge_expired(context)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,202 @@
+//! Repository selection.
+
+use std::{collections::HashSet, env, path::Path};
+
+use anyhow::{bail, Context as _};
+use magro::{collection::CollectionName, vcs::Vcs, view::RepoView, Context};
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::{CollectionNameList, VcsList},
+    error::CollectionNotFound,
+};
+
+/// Options to select target repositories from the cache.
+#[derive(Debug, Clone, StructOpt)]
+pub(crate) struct RepoSelectOpt {
+    /// Targets only repositories of specified VCS's.
+    #[structopt(long, parse(try_from_str), number_of_values = 1)]
+    vcs: Vec<VcsList>,
+    /// Targets only repositories of the specified collections.
+    ///
+    /// Glob patterns such as `work-*` are expanded to the matching collections.
+    /// If no collections are specified, it behaves as all collections are given.
+    #[structopt(long, short, parse(try_from_str), number_of_values = 1)]
+    collections: Vec<CollectionNameList>,
+    /// Targets only repositories of the specified languages (case insensitive).
+    ///
+    /// Languages are detected by `refresh --metadata`.
+    #[structopt(long = "lang", use_delimiter = true, number_of_values = 1)]
+    langs: Vec<String>,
+}
+
+impl RepoSelectOpt {
+    /// Returns the selected repositories in the cache.
+    ///
+    /// Repositories are sorted by collection names and then by paths.
+    pub(crate) fn select(&self, context: &Context) -> anyhow::Result<Vec<RepoView>> {
+        let collections =
+            CollectionNameList::expand(&self.collections, context.config().collections())?;
+        let collections = collections.iter().collect::<Vec<_>>();
+        let vcs = self.vcs.iter().flatten().collect::<Vec<_>>();
+        select_repos(context, &collections, &vcs, &self.langs)
+    }
+}
+
+/// Returns the repositories in the cache matching the conditions.
+///
+/// Empty conditions match any repositories.
+/// Repositories are sorted by collection names and then by paths.
+pub(crate) fn select_repos(
+    context: &Context,
+    collections: &[&CollectionName],
+    vcs: &[Vcs],
+    langs: &[String],
+) -> anyhow::Result<Vec<RepoView>> {
+    let target_vcs: Option<HashSet<Vcs>> = match vcs {
+        [] => None,
+        vcs => Some(vcs.iter().copied().collect()),
+    };
+    let all_collections = context.config().collections();
+    let targets = match collections {
+        [] => all_collections.iter().collect::<Vec<_>>(),
+        names => names
+            .iter()
+            .map(|name| {
+                all_collections
+                    .get(name)
+                    .ok_or_else(|| CollectionNotFound((*name).clone()))
+            })
+            .collect::<Result<_, _>>()?,
+    };
+
+    let cache = context
+        .get_or_load_cache()
+        .context("Failed to load cache file")?;
+    let mut repos = Vec::new();
+    for collection in targets {
+        let coll_cache = match cache.collection_repos(collection.name()) {
+            Some(v) => v,
+            None => {
+                log::info!("No cache found for collection `{}`", collection.name());
+                continue;
+            }
+        };
+        repos.extend(
+            coll_cache
+                .repositories()
+                .filter(|repo| {
+                    target_vcs
+                        .as_ref()
+                        .map_or(true, |t| t.contains(&repo.vcs()))
+                        && lang_matches(langs, repo.metadata().language())
+                })
+                .map(|repo| RepoView::new(context, collection, repo)),
+        );
+    }
+
+    Ok(repos)
+}
+
+/// Returns the repository with the given name.
+///
+/// Fails if no repositories or multiple repositories match the name.
+pub(crate) fn find_by_name(repos: &[RepoView], name: &str) -> anyhow::Result<RepoView> {
+    let mut matched = repos.iter().filter(|repo| repo.name_string() == name);
+    let repo = matched
+        .next()
+        .with_context(|| format!("Repository `{}` is not found in the cache", name))?;
+    if let Some(another) = matched.next() {
+        bail!(
+            "Repository `{}` exists in multiple collections (`{}` and `{}`); \
+             specify the collection by `--collections`",
+            name,
+            repo.collection(),
+            another.collection()
+        );
+    }
+
+    Ok(repo.clone())
+}
+
+/// Returns true if the language matches any of the targets.
+///
+/// If no targets are given, any language (including unknown) matches.
+pub(crate) fn lang_matches(targets: &[String], lang: Option<&str>) -> bool {
+    if targets.is_empty() {
+        return true;
+    }
+    lang.map_or(false, |lang| {
+        targets
+            .iter()
+            .any(|target| target.eq_ignore_ascii_case(lang))
+    })
+}
+
+/// Returns true if the license matches any of the targets.
+///
+/// Targets are compared case insensitively with each license in the SPDX
+/// license expression, with or without `-only` and `-or-later` suffixes
+/// (for example, `gpl-3.0` matches `GPL-3.0-or-later` and `Apache-2.0 OR GPL-3.0`).
+/// If no targets are given, any license (including unknown) matches.
+pub(crate) fn license_matches(targets: &[String], license: Option<&str>) -> bool {
+    if targets.is_empty() {
+        return true;
+    }
+    let license = match license {
+        Some(v) => v,
+        None => return false,
+    };
+    license
+        .split(|c: char| c.is_ascii_whitespace() || c == '(' || c == ')')
+        .filter(|id| !matches!(*id, "" | "OR" | "AND" | "WITH"))
+        .any(|id| {
+            let base = id
+                .strip_suffix("-only")
+                .or_else(|| id.strip_suffix("-or-later"))
+                .unwrap_or(id);
+            targets
+                .iter()
+                .any(|target| target.eq_ignore_ascii_case(id) || target.eq_ignore_ascii_case(base))
+        })
+}
+
+/// Returns the cached repository containing the given path (or the current directory).
+pub(crate) fn locate_repo(context: &Context, path: Option<&Path>) -> anyhow::Result<RepoView> {
+    let path = match path {
+        Some(path) if path.is_absolute() => path.to_owned(),
+        path => {
+            let cwd = env::current_dir().context("Failed to get the current directory")?;
+            match path {
+                Some(path) => cwd.join(path),
+                None => cwd,
+            }
+        }
+    };
+    match magro::locate::locate(context, &path).context("Failed to load cache file")? {
+        Some(repo) => Ok(repo),
+        None => bail!(
+            "No cached repositories contain {:?} (run `refresh` if the repository is new)",
+            path
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn license_targets() {
+        let targets = ["gpl-3.0".to_owned()];
+        assert!(license_matches(&targets, Some("GPL-3.0")));
+        assert!(license_matches(&targets, Some("GPL-3.0-or-later")));
+        assert!(license_matches(
+            &targets,
+            Some("(Apache-2.0 OR GPL-3.0-only)")
+        ));
+        assert!(!license_matches(&targets, Some("LGPL-3.0")));
+        assert!(!license_matches(&targets, None));
+        assert!(license_matches(&[], None));
+    }
+}
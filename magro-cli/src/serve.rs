@@ -0,0 +1,554 @@
+//! `serve` subcommand.
+//!
+//! This is a minimal HTTP/1.1 server providing read-only JSON API (and a
+//! refresh trigger) for editor plugins and dashboards. Connections are
+//! handled one by one, and closed after each response.
+
+use std::{
+    convert::TryFrom,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    str,
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use magro::{
+    collection::CollectionName,
+    query,
+    vcs::Vcs,
+    view::{CollectionView, RepoView},
+    Context,
+};
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::{CollectionNameList, VcsList},
+    refresh::refresh_collections,
+    select::select_repos,
+};
+
+/// Maximum size of a request (including the body) in bytes.
+const MAX_REQUEST_SIZE: u64 = 64 * 1024;
+
+/// Timeout to read a request.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Options for `serve` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct ServeOpt {
+    /// Address to listen on.
+    ///
+    /// Note that the API has no authentication. Be careful when listening on
+    /// non-loopback addresses.
+    /// Requests whose `Host` header is not the address are rejected (for
+    /// loopback addresses, `localhost` is also accepted).
+    #[structopt(long, default_value = "127.0.0.1:7727")]
+    addr: String,
+}
+
+impl ServeOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &mut Context) -> anyhow::Result<()> {
+        log::trace!("serve addr={:?}", self.addr);
+
+        let listener = TcpListener::bind(&self.addr)
+            .with_context(|| format!("Failed to listen on {:?}", self.addr))?;
+        let local_addr = listener.local_addr()?;
+        eprintln!("Listening on http://{}", local_addr);
+        let allowed_hosts = allowed_hosts(local_addr);
+
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(v) => v,
+                Err(e) => {
+                    log::warn!("Failed to accept a connection: {}", e);
+                    continue;
+                }
+            };
+            if let Err(e) = handle_connection(context, &allowed_hosts, stream) {
+                log::warn!("Failed to handle a connection: {}", e);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the values of `Host` header accepted by the server at the address.
+///
+/// Requests with other hosts are rejected, so that web pages on other hosts
+/// cannot access the API by DNS rebinding.
+fn allowed_hosts(addr: SocketAddr) -> Vec<String> {
+    let mut hosts = vec![addr.to_string()];
+    if addr.ip().is_loopback() {
+        hosts.push(format!("localhost:{}", addr.port()));
+    }
+    hosts
+}
+
+/// Handles a connection.
+fn handle_connection(
+    context: &mut Context,
+    allowed_hosts: &[String],
+    stream: TcpStream,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new((&stream).take(MAX_REQUEST_SIZE));
+    let response = match read_request(&mut reader)? {
+        Some(request) => {
+            log::debug!("{} {}", request.method, request.path);
+            route(context, allowed_hosts, &request).unwrap_or_else(|e| {
+                log::error!("{:#}", e);
+                Response::error(500, &format!("{:#}", e))
+            })
+        }
+        None => Response::error(400, "Malformed request"),
+    };
+    response.write_to(&mut &stream)
+}
+
+/// HTTP request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Request {
+    /// Method.
+    method: String,
+    /// Percent-decoded path.
+    path: String,
+    /// Decoded query parameters.
+    query: Vec<(String, String)>,
+    /// Headers with lowercase names.
+    headers: Vec<(String, String)>,
+}
+
+impl Request {
+    /// Returns the value of the header.
+    ///
+    /// The name should be lowercase.
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns true if the request has a header which a web page cannot send
+    /// without a CORS preflight.
+    ///
+    /// Requiring this for state-changing requests prevents cross-site request
+    /// forgery from web pages.
+    fn has_non_simple_header(&self) -> bool {
+        let is_json = self.header("content-type").map_or(false, |value| {
+            let mime = value.split(';').next().unwrap_or_default().trim();
+            mime.eq_ignore_ascii_case("application/json")
+        });
+        is_json || self.header("x-magro") == Some("1")
+    }
+
+    /// Returns the first value of the query parameter.
+    fn param(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the values of the query parameter.
+    fn params<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.query
+            .iter()
+            .filter(move |(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Reads a request.
+///
+/// Returns `Ok(None)` if the request is malformed.
+/// The request body is read and discarded.
+fn read_request<R: BufRead>(reader: &mut R) -> io::Result<Option<Request>> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_ascii_whitespace();
+    let (method, target) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version)) if version.starts_with("HTTP/1.") => {
+            (method.to_owned(), target.to_owned())
+        }
+        _ => return Ok(None),
+    };
+
+    let mut content_length = 0;
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            let (name, value) = (name.trim().to_ascii_lowercase(), value.trim());
+            if name == "content-length" {
+                content_length = match value.parse() {
+                    Ok(v) => v,
+                    Err(_) => return Ok(None),
+                };
+            }
+            headers.push((name, value.to_owned()));
+        }
+    }
+    io::copy(&mut reader.take(content_length), &mut io::sink())?;
+
+    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+    let path = match percent_encoding::percent_decode_str(path).decode_utf8() {
+        Ok(v) => v.into_owned(),
+        Err(_) => return Ok(None),
+    };
+    let query = form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect();
+
+    Ok(Some(Request {
+        method,
+        path,
+        query,
+        headers,
+    }))
+}
+
+/// HTTP response.
+#[derive(Debug, Clone)]
+struct Response {
+    /// Status code.
+    status: u16,
+    /// JSON body.
+    body: Vec<u8>,
+}
+
+impl Response {
+    /// Creates a response with the JSON body.
+    fn json<T: Serialize>(status: u16, value: &T) -> anyhow::Result<Self> {
+        let body = serde_json::to_vec(value).context("Failed to serialize the response")?;
+        Ok(Self { status, body })
+    }
+
+    /// Creates an error response.
+    fn error(status: u16, message: &str) -> Self {
+        /// Error response body.
+        #[derive(Serialize)]
+        struct ErrorBody<'a> {
+            /// Error message.
+            error: &'a str,
+        }
+
+        let body = serde_json::to_vec(&ErrorBody { error: message })
+            .expect("Error messages should be serializable");
+        Self { status, body }
+    }
+
+    /// Writes the response.
+    fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let reason = match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Internal Server Error",
+        };
+        write!(
+            out,
+            "HTTP/1.1 {} {}\r\n\
+             Content-Type: application/json\r\n\
+             Content-Length: {}\r\n\
+             Connection: close\r\n\r\n",
+            self.status,
+            reason,
+            self.body.len()
+        )?;
+        out.write_all(&self.body)?;
+        out.flush()
+    }
+}
+
+/// Dispatches the request.
+///
+/// Requests with unexpected `Host` header, and POST requests without
+/// `X-Magro: 1` header or JSON content type are rejected.
+fn route(
+    context: &mut Context,
+    allowed_hosts: &[String],
+    request: &Request,
+) -> anyhow::Result<Response> {
+    let host_allowed = request.header("host").map_or(false, |host| {
+        allowed_hosts
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(host))
+    });
+    if !host_allowed {
+        return Ok(Response::error(403, "Unexpected `Host` header"));
+    }
+    if request.method == "POST" && !request.has_non_simple_header() {
+        return Ok(Response::error(
+            403,
+            "POST requests need `X-Magro: 1` header or JSON content type",
+        ));
+    }
+
+    let path = request.path.trim_end_matches('/');
+    match (request.method.as_str(), path) {
+        ("GET", "/repos") => get_repos(context, request),
+        ("GET", "/search") => get_search(context, request),
+        ("GET", "/collections") => get_collections(context, None),
+        ("GET", name) if name.starts_with("/collections/") => {
+            get_collections(context, Some(&name["/collections/".len()..]))
+        }
+        ("POST", "/refresh") => post_refresh(context, request),
+        (_, "/repos") | (_, "/search") | (_, "/collections") | (_, "/refresh") => {
+            Ok(Response::error(405, "Method not allowed"))
+        }
+        (_, name) if name.starts_with("/collections/") => {
+            Ok(Response::error(405, "Method not allowed"))
+        }
+        _ => Ok(Response::error(404, "Not found")),
+    }
+}
+
+/// Repository selection conditions given by query parameters.
+#[derive(Debug, Default)]
+struct Selection {
+    /// Collections.
+    collections: Vec<CollectionName>,
+    /// VCS types.
+    vcs: Vec<Vcs>,
+    /// Languages.
+    langs: Vec<String>,
+}
+
+impl Selection {
+    /// Parses `collection`, `vcs`, and `lang` query parameters.
+    ///
+    /// Returns an error response if the parameters are invalid.
+    fn from_request(context: &Context, request: &Request) -> Result<Self, Response> {
+        let mut selection = Self::default();
+        let mut lists = Vec::new();
+        for value in request.params("collection") {
+            let names = value
+                .parse::<CollectionNameList>()
+                .map_err(|e| Response::error(400, &e.to_string()))?;
+            lists.push(names);
+        }
+        selection.collections = CollectionNameList::expand(&lists, context.config().collections())
+            .map_err(|e| Response::error(404, &e.to_string()))?;
+        if let Some(name) = selection
+            .collections
+            .iter()
+            .find(|name| context.config().collections().get(name).is_none())
+        {
+            return Err(Response::error(
+                404,
+                &format!("Collection named `{}` does not exist", name),
+            ));
+        }
+        for value in request.params("vcs") {
+            let vcs = value
+                .parse::<VcsList>()
+                .map_err(|e| Response::error(400, &e.to_string()))?;
+            selection.vcs.extend(vcs.as_ref());
+        }
+        selection.langs = request
+            .params("lang")
+            .flat_map(|value| value.split(','))
+            .filter(|lang| !lang.is_empty())
+            .map(ToOwned::to_owned)
+            .collect();
+
+        Ok(selection)
+    }
+
+    /// Returns the selected repositories.
+    fn select(&self, context: &Context) -> anyhow::Result<Vec<RepoView>> {
+        let collections = self.collections.iter().collect::<Vec<_>>();
+        select_repos(context, &collections, &self.vcs, &self.langs)
+    }
+}
+
+/// `GET /repos`: lists repositories.
+fn get_repos(context: &Context, request: &Request) -> anyhow::Result<Response> {
+    let selection = match Selection::from_request(context, request) {
+        Ok(v) => v,
+        Err(resp) => return Ok(resp),
+    };
+    Response::json(200, &selection.select(context)?)
+}
+
+/// `GET /search?q=<query>`: lists repositories matching the query, best match first.
+fn get_search(context: &Context, request: &Request) -> anyhow::Result<Response> {
+    let query = match request.param("q") {
+        Some(v) if !v.is_empty() => v,
+        _ => return Ok(Response::error(400, "Query parameter `q` is required")),
+    };
+    let selection = match Selection::from_request(context, request) {
+        Ok(v) => v,
+        Err(resp) => return Ok(resp),
+    };
+    let repos = selection.select(context)?;
+    Response::json(200, &query::find(&repos, query))
+}
+
+/// Collection information.
+#[derive(Debug, Serialize)]
+struct CollectionInfo {
+    /// Collection.
+    #[serde(flatten)]
+    collection: CollectionView,
+    /// Number of the cached repositories, if the cache is available.
+    repositories: Option<usize>,
+}
+
+/// `GET /collections` and `GET /collections/<name>`: shows collections.
+fn get_collections(context: &Context, name: Option<&str>) -> anyhow::Result<Response> {
+    let cache = context
+        .get_or_load_cache()
+        .context("Failed to load cache file")?;
+    let collections = context.config().collections();
+    let info = |collection| CollectionInfo {
+        collection: CollectionView::new(context, collection),
+        repositories: cache
+            .collection_repos(collection.name())
+            .map(|repos| repos.repositories().count()),
+    };
+
+    match name {
+        Some(name) => {
+            let collection = CollectionName::try_from(name)
+                .ok()
+                .and_then(|name| collections.get(&name));
+            match collection {
+                Some(collection) => Response::json(200, &info(collection)),
+                None => Ok(Response::error(
+                    404,
+                    &format!("Collection named `{}` does not exist", name),
+                )),
+            }
+        }
+        None => Response::json(200, &collections.iter().map(info).collect::<Vec<_>>()),
+    }
+}
+
+/// `POST /refresh`: refreshes the cache.
+///
+/// Collections can be specified by `collection` parameters, and metadata is
+/// also collected if `metadata=true` is given.
+fn post_refresh(context: &mut Context, request: &Request) -> anyhow::Result<Response> {
+    /// Response body.
+    #[derive(Serialize)]
+    struct Refreshed {
+        /// Refreshed collections.
+        refreshed: Vec<CollectionName>,
+    }
+
+    let mut collections = match Selection::from_request(context, request) {
+        Ok(v) => v.collections,
+        Err(resp) => return Ok(resp),
+    };
+    if collections.is_empty() {
+        collections = context
+            .config()
+            .collections()
+            .iter()
+            .map(|coll| coll.name().to_owned())
+            .collect();
+    }
+    let metadata = match request.param("metadata") {
+        None | Some("false") => false,
+        Some("true") => true,
+        Some(v) => {
+            return Ok(Response::error(
+                400,
+                &format!("Unsupported `metadata` value {:?}", v),
+            ))
+        }
+    };
+
+    refresh_collections(
+        context,
+        &mut collections.iter().cloned().map(Ok),
+        false,
+        true,
+        true,
+        metadata,
+    )?;
+    Response::json(
+        200,
+        &Refreshed {
+            refreshed: collections,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_request() {
+        let mut raw: &[u8] = b"POST /collections/my%20coll?q=foo+bar&lang=rust HTTP/1.1\r\n\
+            Host: localhost\r\n\
+            Content-Length: 4\r\n\r\nbody";
+        let request = read_request(&mut raw).unwrap().unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/collections/my coll");
+        assert_eq!(request.param("q"), Some("foo bar"));
+        assert_eq!(request.params("lang").collect::<Vec<_>>(), ["rust"]);
+        assert_eq!(request.header("host"), Some("localhost"));
+        assert!(!request.has_non_simple_header());
+        assert!(raw.is_empty());
+
+        let mut raw: &[u8] = b"GET /\r\n\r\n";
+        assert_eq!(read_request(&mut raw).unwrap(), None);
+    }
+
+    #[test]
+    fn reject_unexpected_requests() {
+        // Nothing is read nor written since no repositories are accessed.
+        let dir = std::env::temp_dir().join("magro-serve-test-nonexistent");
+        let mut context = Context::with_dirs(Some(dir.clone()), Some(dir)).unwrap();
+        let allowed_hosts = allowed_hosts("127.0.0.1:7727".parse().unwrap());
+        let mut status = |raw: &[u8]| {
+            let request = read_request(&mut &raw[..]).unwrap().unwrap();
+            route(&mut context, &allowed_hosts, &request)
+                .unwrap()
+                .status
+        };
+
+        assert_eq!(
+            status(b"GET /nowhere HTTP/1.1\r\nHost: localhost:7727\r\n\r\n"),
+            404
+        );
+        assert_eq!(
+            status(b"GET /nowhere HTTP/1.1\r\nHost: 127.0.0.1:7727\r\n\r\n"),
+            404
+        );
+        assert_eq!(
+            status(b"GET /repos HTTP/1.1\r\nHost: attacker.example:7727\r\n\r\n"),
+            403
+        );
+        assert_eq!(status(b"GET /repos HTTP/1.1\r\n\r\n"), 403);
+        assert_eq!(
+            status(b"POST /refresh HTTP/1.1\r\nHost: localhost:7727\r\n\r\n"),
+            403
+        );
+        assert_eq!(
+            status(
+                b"POST /refresh HTTP/1.1\r\nHost: localhost:7727\r\n\
+                Content-Type: text/plain\r\n\r\n"
+            ),
+            403
+        );
+    }
+}
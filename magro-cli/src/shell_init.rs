@@ -0,0 +1,304 @@
+//! `shell-init` subcommand.
+
+use std::{
+    fmt,
+    io::{self, Write},
+    str,
+};
+
+use anyhow::anyhow;
+use structopt::{clap, StructOpt};
+
+use crate::cli_opt::Opt;
+
+/// Shell type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shell {
+    /// Bash.
+    Bash,
+    /// Zsh.
+    Zsh,
+    /// Fish.
+    Fish,
+}
+
+impl Shell {
+    /// Returns a list of possible options.
+    #[inline]
+    #[must_use]
+    fn possible_opt_values() -> &'static [&'static str] {
+        &["bash", "zsh", "fish"]
+    }
+
+    /// Returns the option value.
+    #[inline]
+    #[must_use]
+    fn as_opt_value(&self) -> &'static str {
+        match self {
+            Self::Bash => "bash",
+            Self::Zsh => "zsh",
+            Self::Fish => "fish",
+        }
+    }
+
+    /// Parses the option value.
+    #[inline]
+    #[must_use]
+    fn from_opt_value(s: &str) -> Option<Self> {
+        match s {
+            "bash" => Some(Self::Bash),
+            "zsh" => Some(Self::Zsh),
+            "fish" => Some(Self::Fish),
+            _ => None,
+        }
+    }
+}
+
+impl str::FromStr for Shell {
+    type Err = anyhow::Error;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_opt_value(s).ok_or_else(|| anyhow!("Unsupported shell {:?}", s))
+    }
+}
+
+impl fmt::Display for Shell {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_opt_value())
+    }
+}
+
+/// Options for `shell-init` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct ShellInitOpt {
+    /// Shell type.
+    #[structopt(possible_values = Shell::possible_opt_values())]
+    shell: Shell,
+    /// Name of the function to change the directory to a repository.
+    #[structopt(long, default_value = "mcd")]
+    cmd: String,
+    /// Do not emit completions for `magro` command.
+    #[structopt(long)]
+    no_completions: bool,
+}
+
+impl ShellInitOpt {
+    /// Runs the actual operation.
+    pub fn run(&self) -> anyhow::Result<()> {
+        log::trace!(
+            "shell-init shell={}, cmd={:?}, no_completions={}",
+            self.shell,
+            self.cmd,
+            self.no_completions
+        );
+
+        let stdout = io::stdout();
+        let mut handle = stdout.lock();
+
+        let script = match self.shell {
+            Shell::Bash | Shell::Zsh => POSIX_INIT,
+            Shell::Fish => FISH_INIT,
+        };
+        handle.write_all(script.replace("__MAGRO_CMD__", &self.cmd).as_bytes())?;
+        if self.shell == Shell::Bash {
+            handle.write_all(
+                BASH_CMD_COMPLETION
+                    .replace("__MAGRO_CMD__", &self.cmd)
+                    .as_bytes(),
+            )?;
+        }
+        if self.shell == Shell::Zsh {
+            handle.write_all(
+                ZSH_CMD_COMPLETION
+                    .replace("__MAGRO_CMD__", &self.cmd)
+                    .as_bytes(),
+            )?;
+        }
+
+        if !self.no_completions {
+            write_completions(&mut handle, self.shell)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes completions for `magro` command.
+fn write_completions<W: Write>(writer: &mut W, shell: Shell) -> io::Result<()> {
+    const BIN_NAME: &str = "magro";
+
+    let mut completions = Vec::new();
+    let clap_shell = match shell {
+        Shell::Bash => clap::Shell::Bash,
+        Shell::Zsh => clap::Shell::Zsh,
+        Shell::Fish => clap::Shell::Fish,
+    };
+    Opt::clap().gen_completions_to(BIN_NAME, clap_shell, &mut completions);
+    let completions = String::from_utf8_lossy(&completions);
+
+    match shell {
+        Shell::Bash => {
+            // Complete queries of `path` subcommand by repository names.
+            let completions = replace_in_section(
+                &completions,
+                "\n        magro__path)\n",
+                "\n            ;;\n",
+                &[
+                    (
+                        "if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then",
+                        "if [[ ${cur} == -* ]] ; then",
+                    ),
+                    (
+                        "            esac\n            COMPREPLY=( $(compgen -W \"${opts}\" -- \"${cur}\") )",
+                        BASH_REPOS_COMPLETION,
+                    ),
+                ],
+            );
+            writer.write_all(completions.as_bytes())
+        }
+        Shell::Zsh => {
+            writer.write_all(ZSH_REPOS_COMPLETION.as_bytes())?;
+            // Complete queries of `path` subcommand by repository names.
+            let completions = replace_in_section(
+                &completions,
+                "\n(path)\n",
+                "\n;;\n",
+                &[(
+                    "'::query -- Query:_files'",
+                    "'*::query -- Query:__magro_repos_complete'",
+                )],
+            );
+            // The generated script is intended to be put in `$fpath` as an
+            // autoloaded function, and it calls the completion function at last.
+            // Register the function by `compdef` instead.
+            let call = format!("_{} \"$@\"", BIN_NAME);
+            let body = completions.trim_end().trim_end_matches(&call);
+            writer.write_all(body.as_bytes())?;
+            writeln!(
+                writer,
+                "(( $+functions[compdef] )) && compdef _{0} {0}",
+                BIN_NAME
+            )
+        }
+        Shell::Fish => {
+            writer.write_all(completions.as_bytes())?;
+            writer.write_all(FISH_REPOS_COMPLETION.as_bytes())
+        }
+    }
+}
+
+/// Replaces the strings in the section of the generated completion script.
+///
+/// The section is the first part starting with `start` and ending with
+/// `end`. If the section or the strings to replace are not found, the script
+/// is returned without changes.
+#[must_use]
+fn replace_in_section(script: &str, start: &str, end: &str, replaces: &[(&str, &str)]) -> String {
+    let section = script.find(start).and_then(|begin| {
+        script[begin..]
+            .find(end)
+            .map(|len| begin..(begin + len + end.len()))
+    });
+    let section = match section {
+        Some(v) => v,
+        None => {
+            log::debug!("Section {:?} is not found in the completion script", start);
+            return script.to_owned();
+        }
+    };
+
+    let mut replaced = script[section.clone()].to_owned();
+    for (from, to) in replaces {
+        if !replaced.contains(from) {
+            log::debug!("{:?} is not found in the completion script", from);
+            return script.to_owned();
+        }
+        replaced = replaced.replacen(from, to, 1);
+    }
+    format!(
+        "{}{}{}",
+        &script[..section.start],
+        replaced,
+        &script[section.end..]
+    )
+}
+
+/// Initialization script for POSIX-like shells (bash and zsh).
+const POSIX_INIT: &str = r#"# Shell integration for magro.
+# Changes the directory to the repository matching the query.
+__MAGRO_CMD__() {
+    local __magro_dir
+    __magro_dir="$(command magro path -- "$@")" && builtin cd -- "$__magro_dir"
+}
+"#;
+
+/// Completion of the `cd` function for bash.
+const BASH_CMD_COMPLETION: &str = r#"__magro_cmd_complete() {
+    local IFS=$'\n'
+    COMPREPLY=($(command magro path --all --name -- "${COMP_WORDS[COMP_CWORD]}" 2>/dev/null))
+}
+complete -F __magro_cmd_complete __MAGRO_CMD__
+"#;
+
+/// Completion of the `cd` function for zsh.
+const ZSH_CMD_COMPLETION: &str = r#"__magro_cmd_complete() {
+    local -a __magro_names
+    __magro_names=("${(@f)$(command magro path --all --name -- "${words[CURRENT]}" 2>/dev/null)}")
+    compadd -U -- "${__magro_names[@]}"
+}
+(( $+functions[compdef] )) && compdef __magro_cmd_complete __MAGRO_CMD__
+"#;
+
+/// Initialization script for fish.
+const FISH_INIT: &str = r#"# Shell integration for magro.
+# Changes the directory to the repository matching the query.
+function __MAGRO_CMD__
+    set -l __magro_dir (command magro path -- $argv); and builtin cd -- $__magro_dir
+end
+complete -c __MAGRO_CMD__ -f -a '(command magro path --all --name -- (commandline -ct) 2>/dev/null)'
+"#;
+
+/// Completion of repository names for bash.
+///
+/// This replaces the fallback completion of `path` subcommand.
+const BASH_REPOS_COMPLETION: &str = r#"            esac
+            local IFS=$'\n'
+            COMPREPLY=( $(command magro __complete repos -- "${cur}" 2>/dev/null) )"#;
+
+/// Completion of repository names for zsh.
+const ZSH_REPOS_COMPLETION: &str = r#"__magro_repos_complete() {
+    local -a __magro_names
+    __magro_names=("${(@f)$(command magro __complete repos -- "${words[CURRENT]}" 2>/dev/null)}")
+    compadd -U -- "${__magro_names[@]}"
+}
+"#;
+
+/// Completion of repository names for fish.
+const FISH_REPOS_COMPLETION: &str = r#"complete -c magro -n "__fish_seen_subcommand_from path" -f -a '(command magro __complete repos -- (commandline -ct) 2>/dev/null)'
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replace_only_in_section() {
+        let script = "a)\nfoo\n;;\nb)\nfoo\n;;\n";
+        assert_eq!(
+            replace_in_section(script, "\nb)\n", "\n;;\n", &[("foo", "bar")]),
+            "a)\nfoo\n;;\nb)\nbar\n;;\n"
+        );
+        // Unchanged if not found.
+        assert_eq!(
+            replace_in_section(script, "\nb)\n", "\n;;\n", &[("baz", "bar")]),
+            script
+        );
+        assert_eq!(
+            replace_in_section(script, "\nc)\n", "\n;;\n", &[("foo", "bar")]),
+            script
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! `snapshot` subcommand.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, ensure, Context as _};
+use magro::{
+    snapshot::{self, Snapshot, SnapshotRepo},
+    Context,
+};
+use structopt::StructOpt;
+
+use crate::{cli_opt::OptionBool, clone::ClonePlan, logging, select::RepoSelectOpt};
+
+/// Options for `snapshot` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct SnapshotOpt {
+    /// Subcommand.
+    #[structopt(subcommand)]
+    subcommand: Subcommand,
+}
+
+impl SnapshotOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &mut Context) -> anyhow::Result<()> {
+        match &self.subcommand {
+            Subcommand::Create {
+                select,
+                force,
+                keep_going,
+                name,
+            } => {
+                log::trace!(
+                    "snapshot create select={:?}, force={}, keep_going={}, name={:?}",
+                    select,
+                    force,
+                    keep_going,
+                    name
+                );
+                create(context, select, *force, *keep_going, name)
+            }
+            Subcommand::List => {
+                log::trace!("snapshot list");
+                list(context)
+            }
+            Subcommand::Restore {
+                keep_going,
+                dry_run,
+                name,
+            } => {
+                log::trace!(
+                    "snapshot restore keep_going={}, dry_run={}, name={:?}",
+                    keep_going,
+                    dry_run,
+                    name
+                );
+                restore(context, *keep_going, *dry_run, name)
+            }
+        }
+    }
+}
+
+/// Subcommand of `snapshot`.
+#[derive(Debug, Clone, StructOpt)]
+pub enum Subcommand {
+    /// Records the remote URL and the HEAD commit of each repository.
+    Create {
+        /// Target repositories.
+        #[structopt(flatten)]
+        select: RepoSelectOpt,
+        /// Overwrites the existing snapshot with the same name.
+        #[structopt(long, short)]
+        force: bool,
+        /// Runs the operation as possible even when errors are detected.
+        ///
+        /// Note that errors are ignored during the operation, but the program
+        /// will exit with failure (i.e. errors won't be completely ignored).
+        #[structopt(long)]
+        keep_going: bool,
+        /// Snapshot name.
+        name: String,
+    },
+    /// Shows the snapshots.
+    List,
+    /// Clones or fetches the repositories, and checks out the recorded commits.
+    ///
+    /// Git repositories are left with detached HEAD. Repositories with local
+    /// changes conflicting with the recorded commits are not touched.
+    Restore {
+        /// Runs the operation as possible even when errors are detected.
+        ///
+        /// Note that errors are ignored during the operation, but the program
+        /// will exit with failure (i.e. errors won't be completely ignored).
+        #[structopt(long)]
+        keep_going: bool,
+        /// Only prints the repositories to be cloned or checked out.
+        #[structopt(long)]
+        dry_run: bool,
+        /// Snapshot name.
+        name: String,
+    },
+}
+
+/// Returns the path of the snapshot manifest with the given name.
+fn snapshot_path(context: &Context, name: &str) -> anyhow::Result<PathBuf> {
+    ensure!(
+        snapshot::is_valid_name(name),
+        "Invalid snapshot name {:?}",
+        name
+    );
+    Ok(context.snapshots_dir().join(format!("{}.toml", name)))
+}
+
+/// Creates a snapshot of the repositories.
+fn create(
+    context: &Context,
+    select: &RepoSelectOpt,
+    force: bool,
+    keep_going: bool,
+    name: &str,
+) -> anyhow::Result<()> {
+    let path = snapshot_path(context, name)?;
+    if !force && path.exists() {
+        bail!(
+            "Snapshot {:?} already exists (use `--force` to overwrite)",
+            name
+        );
+    }
+    context
+        .ensure_writable()
+        .context("Cannot create a snapshot")?;
+
+    let mut repos = Vec::new();
+    let mut num_errors = 0_usize;
+    let envs = logging::CollectionEnvs::new(context);
+    for repo in select.select(context)? {
+        if !repo.path().exists() {
+            log::warn!("Skipping missing repository {:?}", repo.path());
+            continue;
+        }
+        let result = logging::with_repo(&repo, &envs, || {
+            SnapshotRepo::capture(&repo)
+                .with_context(|| format!("Failed to get the state of {:?}", repo.path()))
+        });
+        match result {
+            Ok(state) => repos.push(state),
+            Err(e) => {
+                if !keep_going {
+                    return Err(e);
+                }
+                log::error!("{:#}", e);
+                num_errors += 1;
+            }
+        }
+    }
+
+    let num_repos = repos.len();
+    Snapshot::new(repos)
+        .save(&path, context.config().main().dir_mode())
+        .with_context(|| format!("Failed to save the snapshot to {:?}", path))?;
+    log::info!("Recorded {} repositories into {:?}", num_repos, path);
+
+    if num_errors != 0 {
+        bail!("Failed to record {} repositories", num_errors);
+    }
+
+    Ok(())
+}
+
+/// Shows the snapshots.
+fn list(context: &Context) -> anyhow::Result<()> {
+    let dir = context.snapshots_dir();
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read the directory {:?}", dir)),
+    };
+    let mut names = Vec::new();
+    for entry in entries {
+        let path = entry
+            .with_context(|| format!("Failed to read the directory {:?}", dir))?
+            .path();
+        if path.extension() != Some("toml".as_ref()) {
+            continue;
+        }
+        if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+            names.push(name.to_owned());
+        }
+    }
+    names.sort();
+
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    for name in &names {
+        let path = snapshot_path(context, name)?;
+        let snapshot = Snapshot::from_path(&path)
+            .with_context(|| format!("Failed to load the snapshot {:?}", path))?;
+        writeln!(
+            handle,
+            "{}  ({} repositories, created at {})",
+            name,
+            snapshot.repos().len(),
+            snapshot.created_at()
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Restores the repositories to the states recorded in the snapshot.
+fn restore(
+    context: &mut Context,
+    keep_going: bool,
+    dry_run: bool,
+    name: &str,
+) -> anyhow::Result<()> {
+    let path = snapshot_path(context, name)?;
+    let snapshot = Snapshot::from_path(&path)
+        .with_context(|| format!("Failed to load the snapshot {:?}", path))?;
+    if !dry_run {
+        context
+            .ensure_writable()
+            .context("Cannot restore a snapshot")?;
+    }
+
+    let mut num_errors = 0_usize;
+    for repo in snapshot.repos() {
+        if let Err(e) = restore_repo(context, repo, dry_run) {
+            if !keep_going {
+                return Err(e);
+            }
+            log::error!("{:#}", e);
+            num_errors += 1;
+        }
+    }
+
+    if num_errors != 0 {
+        bail!("Failed to restore {} repositories", num_errors);
+    }
+
+    Ok(())
+}
+
+/// Clones or fetches the repository, and checks out the recorded commit.
+fn restore_repo(context: &mut Context, repo: &SnapshotRepo, dry_run: bool) -> anyhow::Result<()> {
+    let collection = context
+        .config()
+        .target_collection(Some(repo.collection()))?;
+    let abspath = collection.abspath(context).join(repo.path());
+
+    if !abspath.exists() {
+        let url = repo.url().with_context(|| {
+            format!("Cannot clone {:?} because its URL is not recorded", abspath)
+        })?;
+        let (reldest, bare) = match repo.workdir_path() {
+            Some(workdir) => (workdir, OptionBool::No),
+            None => (repo.path(), OptionBool::Yes),
+        };
+        let plan = ClonePlan::new(
+            context,
+            url,
+            Some(repo.collection()),
+            Some(reldest),
+            Some(repo.vcs()),
+            bare,
+        )?;
+        if dry_run {
+            println!("{} -> {}", url, plan.destination().display());
+        } else {
+            log::info!("Cloning {:?} from {:?}", plan.destination(), url);
+            plan.run(context)?;
+        }
+    }
+
+    let commit = match repo.commit() {
+        Some(commit) => commit,
+        None => return Ok(()),
+    };
+    if dry_run {
+        println!("{} @ {}", abspath.display(), commit);
+        return Ok(());
+    }
+    checkout(repo, &abspath, commit)
+}
+
+/// Checks out the commit, fetching the repository if the commit is missing.
+fn checkout(repo: &SnapshotRepo, path: &Path, commit: &str) -> anyhow::Result<()> {
+    let vcs = repo.vcs();
+    if vcs.head_commit(path)?.as_deref() == Some(commit) {
+        log::debug!("{:?} is already at {}", path, commit);
+        return Ok(());
+    }
+    if !vcs.has_commit(path, commit)? {
+        log::debug!("Fetching {:?}", path);
+        vcs.fetch(path)
+            .with_context(|| format!("Failed to fetch {:?}", path))?;
+        ensure!(
+            vcs.has_commit(path, commit)?,
+            "Commit {} is not found in {:?}",
+            commit,
+            path
+        );
+    }
+
+    log::info!("Checking out {} in {:?}", commit, path);
+    vcs.checkout_commit(path, commit)
+        .with_context(|| format!("Failed to check out {} in {:?}", commit, path))
+}
@@ -0,0 +1,309 @@
+//! `stats` subcommand.
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+use magro::{metadata::unix_now, view::RepoView, Context};
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::GlobalOpt,
+    output::{write_csv_record, write_json, OutputFormat},
+    select::RepoSelectOpt,
+};
+
+/// Key for repositories with unknown language, license, or upstream.
+const UNKNOWN: &str = "(unknown)";
+
+/// Options for `stats` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct StatsOpt {
+    /// Target repositories.
+    #[structopt(flatten)]
+    select: RepoSelectOpt,
+    /// Reports the numbers of repositories for each collection, bucketed by
+    /// the recency of the last commits.
+    ///
+    /// Repositories committed within 7 days are "week", within 30 days are
+    /// "month", within 365 days are "year", and older ones are "dormant".
+    /// Last commit times are collected by `refresh --metadata`.
+    #[structopt(long)]
+    activity: bool,
+}
+
+impl StatsOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &Context, global: &GlobalOpt) -> anyhow::Result<()> {
+        log::trace!("stats select={:?}, activity={}", self.select, self.activity);
+
+        let repos = self.select.select(context)?;
+        let stdout = io::stdout();
+        let mut handle = stdout.lock();
+        if self.activity {
+            return write_activity(&mut handle, &repos, global.output);
+        }
+
+        let stats = Stats::new(&repos);
+        match global.output {
+            OutputFormat::Text => stats.write_text(&mut handle)?,
+            OutputFormat::Json => write_json(&mut handle, &stats)?,
+            OutputFormat::Csv => {
+                write_csv_record(&mut handle, &["category", "key", "count"])?;
+                for (category, counts) in stats.categories() {
+                    for (key, count) in counts {
+                        write_csv_record(&mut handle, &[category, key, &count.to_string()])?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Statistics of repositories.
+#[derive(Debug, Default, Serialize)]
+struct Stats<'a> {
+    /// Total number of repositories.
+    total: usize,
+    /// Number of repositories for each collection.
+    collections: BTreeMap<&'a str, usize>,
+    /// Number of repositories for each VCS.
+    vcs: BTreeMap<&'a str, usize>,
+    /// Number of repositories for each language.
+    languages: BTreeMap<&'a str, usize>,
+    /// Number of repositories for each license.
+    licenses: BTreeMap<&'a str, usize>,
+    /// Number of repositories for each upstream status.
+    ///
+    /// Upstreams are `archived`, `fork` (and not archived), or `source`
+    /// (neither archived nor a fork).
+    upstreams: BTreeMap<&'a str, usize>,
+}
+
+impl<'a> Stats<'a> {
+    /// Counts the repositories.
+    fn new(repos: &'a [RepoView]) -> Self {
+        let mut stats = Self {
+            total: repos.len(),
+            ..Default::default()
+        };
+        for repo in repos {
+            *stats
+                .collections
+                .entry(repo.collection().as_str())
+                .or_default() += 1;
+            *stats.vcs.entry(repo.vcs().name_lower()).or_default() += 1;
+            let lang = repo.metadata().language().unwrap_or(UNKNOWN);
+            *stats.languages.entry(lang).or_default() += 1;
+            let license = repo.metadata().license().unwrap_or(UNKNOWN);
+            *stats.licenses.entry(license).or_default() += 1;
+            let upstream = match repo.metadata().upstream() {
+                Some(upstream) if upstream.is_archived() => "archived",
+                Some(upstream) if upstream.is_fork() => "fork",
+                Some(_) => "source",
+                None => UNKNOWN,
+            };
+            *stats.upstreams.entry(upstream).or_default() += 1;
+        }
+        stats
+    }
+
+    /// Returns the categories and their counts.
+    fn categories(&self) -> [(&'static str, &BTreeMap<&'a str, usize>); 5] {
+        [
+            ("collection", &self.collections),
+            ("vcs", &self.vcs),
+            ("language", &self.languages),
+            ("license", &self.licenses),
+            ("upstream", &self.upstreams),
+        ]
+    }
+
+    /// Writes the statistics in human-readable form.
+    ///
+    /// Entries are sorted by counts in descending order.
+    fn write_text<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "repositories: {}", self.total)?;
+        for (category, counts) in self.categories() {
+            writeln!(out, "{}:", category)?;
+            let mut counts = counts.iter().collect::<Vec<_>>();
+            counts.sort_by_key(|(_, count)| std::cmp::Reverse(**count));
+            let width = counts.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+            for (key, count) in counts {
+                writeln!(out, "    {:<width$}  {}", key, count, width = width)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Recency of the last commit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Activity {
+    /// Committed within 7 days.
+    Week,
+    /// Committed within 30 days.
+    Month,
+    /// Committed within 365 days.
+    Year,
+    /// Committed more than 365 days ago.
+    Dormant,
+    /// Last commit time is unknown.
+    Unknown,
+}
+
+impl Activity {
+    /// All buckets in order.
+    const ALL: [Self; 5] = [
+        Self::Week,
+        Self::Month,
+        Self::Year,
+        Self::Dormant,
+        Self::Unknown,
+    ];
+
+    /// Returns the bucket for the last commit time.
+    fn new(last_commit_at: Option<u64>, now: u64) -> Self {
+        /// Seconds per day.
+        const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+        let days = match last_commit_at {
+            Some(time) => now.saturating_sub(time) / SECS_PER_DAY,
+            None => return Self::Unknown,
+        };
+        match days {
+            0..=6 => Self::Week,
+            7..=29 => Self::Month,
+            30..=364 => Self::Year,
+            _ => Self::Dormant,
+        }
+    }
+
+    /// Returns the bucket name.
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Week => "week",
+            Self::Month => "month",
+            Self::Year => "year",
+            Self::Dormant => "dormant",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// Numbers of repositories for each activity bucket.
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+struct ActivityCounts {
+    /// Committed within 7 days.
+    week: usize,
+    /// Committed within 30 days.
+    month: usize,
+    /// Committed within 365 days.
+    year: usize,
+    /// Committed more than 365 days ago.
+    dormant: usize,
+    /// Last commit time is unknown.
+    unknown: usize,
+}
+
+impl ActivityCounts {
+    /// Returns the count for the bucket.
+    fn get(&self, activity: Activity) -> usize {
+        match activity {
+            Activity::Week => self.week,
+            Activity::Month => self.month,
+            Activity::Year => self.year,
+            Activity::Dormant => self.dormant,
+            Activity::Unknown => self.unknown,
+        }
+    }
+
+    /// Returns a mutable reference to the count for the bucket.
+    fn get_mut(&mut self, activity: Activity) -> &mut usize {
+        match activity {
+            Activity::Week => &mut self.week,
+            Activity::Month => &mut self.month,
+            Activity::Year => &mut self.year,
+            Activity::Dormant => &mut self.dormant,
+            Activity::Unknown => &mut self.unknown,
+        }
+    }
+}
+
+/// Writes the activity report.
+fn write_activity<W: Write>(
+    out: &mut W,
+    repos: &[RepoView],
+    output: OutputFormat,
+) -> anyhow::Result<()> {
+    let now = unix_now();
+    let mut report: BTreeMap<&str, ActivityCounts> = BTreeMap::new();
+    for repo in repos {
+        let activity = Activity::new(repo.metadata().last_commit_at(), now);
+        *report
+            .entry(repo.collection().as_str())
+            .or_default()
+            .get_mut(activity) += 1;
+    }
+
+    match output {
+        OutputFormat::Text => {
+            let width = report
+                .keys()
+                .map(|name| name.len())
+                .chain(Some("collection".len()))
+                .max()
+                .unwrap_or(0);
+            write!(out, "{:<width$}", "collection", width = width)?;
+            for activity in Activity::ALL {
+                write!(out, "  {:>7}", activity.as_str())?;
+            }
+            writeln!(out)?;
+            for (collection, counts) in &report {
+                write!(out, "{:<width$}", collection, width = width)?;
+                for activity in Activity::ALL {
+                    write!(out, "  {:>7}", counts.get(activity))?;
+                }
+                writeln!(out)?;
+            }
+        }
+        OutputFormat::Json => write_json(out, &report)?,
+        OutputFormat::Csv => {
+            let mut header = vec!["collection"];
+            header.extend(Activity::ALL.iter().map(Activity::as_str));
+            write_csv_record(out, &header)?;
+            for (collection, counts) in &report {
+                let counts = Activity::ALL
+                    .iter()
+                    .map(|&activity| counts.get(activity).to_string())
+                    .collect::<Vec<_>>();
+                let mut record = vec![*collection];
+                record.extend(counts.iter().map(String::as_str));
+                write_csv_record(out, &record)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn activity_buckets() {
+        let day = 24 * 60 * 60;
+        let now = 1000 * day;
+        assert_eq!(Activity::new(Some(now - 6 * day), now), Activity::Week);
+        assert_eq!(Activity::new(Some(now - 7 * day), now), Activity::Month);
+        assert_eq!(Activity::new(Some(now - 100 * day), now), Activity::Year);
+        assert_eq!(Activity::new(Some(now - 365 * day), now), Activity::Dormant);
+        assert_eq!(Activity::new(None, now), Activity::Unknown);
+    }
+}
@@ -0,0 +1,169 @@
+//! `status` subcommand.
+
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use anyhow::bail;
+use magro::{
+    collection::CollectionName,
+    status::{StatusKind, WorktreeStatus},
+    view::RepoView,
+    Context,
+};
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::GlobalOpt,
+    logging,
+    output::{write_csv_record, write_json, OutputFormat},
+    select::RepoSelectOpt,
+};
+
+/// Options for `status` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct StatusOpt {
+    /// Target repositories.
+    #[structopt(flatten)]
+    select: RepoSelectOpt,
+    /// Shows only working trees which are dirty or have untracked files.
+    #[structopt(long)]
+    changed: bool,
+    /// Runs the operation as possible even when errors are detected.
+    ///
+    /// Note that errors are ignored during the operation, but the program will
+    /// exit with failure (i.e. errors won't be completely ignored).
+    #[structopt(long)]
+    keep_going: bool,
+}
+
+impl StatusOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &Context, global: &GlobalOpt) -> anyhow::Result<()> {
+        log::trace!(
+            "status select={:?}, changed={}, keep_going={}",
+            self.select,
+            self.changed,
+            self.keep_going
+        );
+
+        let repos = self.select.select(context)?;
+        let keep_going = self.keep_going;
+        let envs = logging::CollectionEnvs::new(context);
+        let results = global.runner(context).run_until(
+            repos,
+            move |repo| logging::with_repo(repo, &envs, || repo.vcs().worktree_status(repo.path())),
+            move |result| !keep_going && result.is_err(),
+        );
+
+        let mut checked = Vec::with_capacity(results.len());
+        let mut num_errors = 0_usize;
+        for (repo, result) in results {
+            match result {
+                Ok(Some(status)) => checked.push((repo, status)),
+                // Bare repositories have no working trees.
+                Ok(None) => log::debug!("Skipping bare repository {:?}", repo.path()),
+                Err(e) => {
+                    if !self.keep_going {
+                        return Err(anyhow::Error::new(e)
+                            .context(format!("Failed to read the status of {:?}", repo.path())));
+                    }
+                    log::error!("Failed to read the status of {:?}: {}", repo.path(), e);
+                    num_errors += 1;
+                }
+            }
+        }
+
+        let entries = checked
+            .iter()
+            .filter(|(_, status)| !self.changed || status.state() != StatusKind::Clean)
+            .map(|(repo, status)| StatusEntry::new(repo, status))
+            .collect::<Vec<_>>();
+
+        let stdout = io::stdout();
+        let mut handle = stdout.lock();
+        match global.output {
+            OutputFormat::Text => {
+                for entry in &entries {
+                    write!(
+                        handle,
+                        "{:<9}  {}/{}",
+                        entry.status.state().name_lower(),
+                        entry.collection,
+                        entry.name
+                    )?;
+                    match (entry.status.changed(), entry.status.untracked()) {
+                        (0, 0) => writeln!(handle)?,
+                        (changed, 0) => writeln!(handle, " ({} changed)", changed)?,
+                        (0, untracked) => writeln!(handle, " ({} untracked)", untracked)?,
+                        (changed, untracked) => {
+                            writeln!(handle, " ({} changed, {} untracked)", changed, untracked)?
+                        }
+                    }
+                }
+            }
+            OutputFormat::Json => write_json(&mut handle, &entries)?,
+            OutputFormat::Csv => {
+                write_csv_record(
+                    &mut handle,
+                    &[
+                        "collection",
+                        "name",
+                        "path",
+                        "state",
+                        "changed",
+                        "untracked",
+                    ],
+                )?;
+                for entry in &entries {
+                    write_csv_record(
+                        &mut handle,
+                        &[
+                            entry.collection.as_str(),
+                            &entry.name,
+                            &entry.path.to_string_lossy(),
+                            entry.status.state().name_lower(),
+                            &entry.status.changed().to_string(),
+                            &entry.status.untracked().to_string(),
+                        ],
+                    )?;
+                }
+            }
+        }
+
+        if num_errors != 0 {
+            bail!("Failed to read the status of {} repositories", num_errors);
+        }
+
+        Ok(())
+    }
+}
+
+/// Working tree status entry of the report.
+#[derive(Debug, Serialize)]
+struct StatusEntry<'a> {
+    /// Collection name.
+    collection: &'a CollectionName,
+    /// Repository name.
+    name: String,
+    /// Absolute path of the repository.
+    path: &'a Path,
+    /// Status.
+    #[serde(flatten)]
+    status: &'a WorktreeStatus,
+}
+
+impl<'a> StatusEntry<'a> {
+    /// Creates a new entry.
+    fn new(repo: &'a RepoView, status: &'a WorktreeStatus) -> Self {
+        Self {
+            collection: repo.collection(),
+            name: repo.name_string(),
+            path: repo.path(),
+            status,
+        }
+    }
+}
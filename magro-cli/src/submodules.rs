@@ -0,0 +1,163 @@
+//! `submodules` subcommand.
+
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use anyhow::bail;
+use magro::{collection::CollectionName, submodule::Submodule, view::RepoView, Context};
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::GlobalOpt,
+    logging,
+    output::{write_csv_record, write_json, OutputFormat},
+    select::RepoSelectOpt,
+};
+
+/// Options for `submodules` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct SubmodulesOpt {
+    /// Target repositories.
+    #[structopt(flatten)]
+    select: RepoSelectOpt,
+    /// Shows only submodules which are uninitialized, out of sync, or modified.
+    #[structopt(long)]
+    problems: bool,
+    /// Runs the operation as possible even when errors are detected.
+    ///
+    /// Note that errors are ignored during the operation, but the program will
+    /// exit with failure (i.e. errors won't be completely ignored).
+    #[structopt(long)]
+    keep_going: bool,
+}
+
+impl SubmodulesOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &Context, global: &GlobalOpt) -> anyhow::Result<()> {
+        log::trace!(
+            "submodules select={:?}, problems={}, keep_going={}",
+            self.select,
+            self.problems,
+            self.keep_going
+        );
+
+        let repos = self.select.select(context)?;
+        let keep_going = self.keep_going;
+        let envs = logging::CollectionEnvs::new(context);
+        let results = global.runner(context).run_until(
+            repos,
+            move |repo| logging::with_repo(repo, &envs, || repo.vcs().submodules(repo.path())),
+            move |result| !keep_going && result.is_err(),
+        );
+
+        let mut checked = Vec::with_capacity(results.len());
+        let mut num_errors = 0_usize;
+        for (repo, result) in results {
+            match result {
+                Ok(submodules) => checked.push((repo, submodules)),
+                Err(e) => {
+                    if !self.keep_going {
+                        return Err(anyhow::Error::new(e).context(format!(
+                            "Failed to inspect submodules of {:?}",
+                            repo.path()
+                        )));
+                    }
+                    log::error!("Failed to inspect submodules of {:?}: {}", repo.path(), e);
+                    num_errors += 1;
+                }
+            }
+        }
+
+        let entries = checked
+            .iter()
+            .flat_map(|(repo, submodules)| {
+                submodules
+                    .iter()
+                    .map(move |submodule| SubmoduleEntry::new(repo, submodule))
+            })
+            .filter(|entry| !self.problems || entry.submodule.state().is_problem())
+            .collect::<Vec<_>>();
+
+        let stdout = io::stdout();
+        let mut handle = stdout.lock();
+        match global.output {
+            OutputFormat::Text => {
+                for entry in &entries {
+                    writeln!(
+                        handle,
+                        "{:<13}  {}/{}: {}",
+                        entry.submodule.state().name_lower(),
+                        entry.collection,
+                        entry.name,
+                        entry.submodule.path().display()
+                    )?;
+                }
+            }
+            OutputFormat::Json => write_json(&mut handle, &entries)?,
+            OutputFormat::Csv => {
+                write_csv_record(
+                    &mut handle,
+                    &[
+                        "collection",
+                        "name",
+                        "path",
+                        "submodule_path",
+                        "url",
+                        "state",
+                    ],
+                )?;
+                for entry in &entries {
+                    write_csv_record(
+                        &mut handle,
+                        &[
+                            entry.collection.as_str(),
+                            &entry.name,
+                            &entry.path.to_string_lossy(),
+                            &entry.submodule.path().to_string_lossy(),
+                            entry.submodule.url().unwrap_or_default(),
+                            entry.submodule.state().name_lower(),
+                        ],
+                    )?;
+                }
+            }
+        }
+
+        if num_errors != 0 {
+            bail!(
+                "Failed to inspect submodules of {} repositories",
+                num_errors
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Submodule entry of the report.
+#[derive(Debug, Serialize)]
+struct SubmoduleEntry<'a> {
+    /// Collection name.
+    collection: &'a CollectionName,
+    /// Repository name.
+    name: String,
+    /// Absolute path of the repository.
+    path: &'a Path,
+    /// Submodule.
+    submodule: &'a Submodule,
+}
+
+impl<'a> SubmoduleEntry<'a> {
+    /// Creates a new entry.
+    fn new(repo: &'a RepoView, submodule: &'a Submodule) -> Self {
+        Self {
+            collection: repo.collection(),
+            name: repo.name_string(),
+            path: repo.path(),
+            submodule,
+        }
+    }
+}
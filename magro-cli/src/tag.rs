@@ -0,0 +1,213 @@
+//! `tag` subcommand.
+
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context as _;
+use magro::{collection::CollectionName, tag::Tag, Context};
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::GlobalOpt,
+    output::{write_csv_record, write_json, OutputFormat},
+    select::locate_repo,
+};
+
+/// Options for `tag` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct TagOpt {
+    /// Subcommand.
+    #[structopt(subcommand)]
+    subcommand: Subcommand,
+}
+
+impl TagOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &mut Context, global: &GlobalOpt) -> anyhow::Result<()> {
+        match &self.subcommand {
+            Subcommand::Add { tags, repo } => {
+                log::trace!("tag add tags={:?}, repo={:?}", tags, repo);
+                add_tags(context, tags, repo.as_deref())
+            }
+            Subcommand::Rm { tags, repo } => {
+                log::trace!("tag rm tags={:?}, repo={:?}", tags, repo);
+                remove_tags(context, tags, repo.as_deref())
+            }
+            Subcommand::List { repo } => {
+                log::trace!("tag list repo={:?}", repo);
+                match repo {
+                    Some(repo) => list_repo_tags(context, repo, global.output),
+                    None => list_all_tags(context, global.output),
+                }
+            }
+        }
+    }
+}
+
+/// Subcommand of `tag`.
+#[derive(Debug, Clone, StructOpt)]
+pub enum Subcommand {
+    /// Adds tags to the repository.
+    Add {
+        /// Tags to add.
+        #[structopt(required = true, min_values = 1)]
+        tags: Vec<Tag>,
+        /// Path to (or inside) the repository.
+        ///
+        /// If not specified, the repository containing the current directory
+        /// is used.
+        #[structopt(long, parse(from_os_str))]
+        repo: Option<PathBuf>,
+    },
+    /// Removes tags from the repository.
+    Rm {
+        /// Tags to remove.
+        #[structopt(required = true, min_values = 1)]
+        tags: Vec<Tag>,
+        /// Path to (or inside) the repository.
+        ///
+        /// If not specified, the repository containing the current directory
+        /// is used.
+        #[structopt(long, parse(from_os_str))]
+        repo: Option<PathBuf>,
+    },
+    /// Lists tags.
+    ///
+    /// If the repository is not specified, all tagged repositories are listed.
+    List {
+        /// Path to (or inside) the repository.
+        #[structopt(long, parse(from_os_str))]
+        repo: Option<PathBuf>,
+    },
+}
+
+/// Adds tags to the repository.
+fn add_tags(context: &mut Context, tags: &[Tag], repo: Option<&Path>) -> anyhow::Result<()> {
+    let repo = locate_repo(context, repo)?;
+    context
+        .update_tags(|repo_tags| {
+            for tag in tags {
+                if !repo_tags.add(repo.collection(), repo.relative_path(), tag.clone()) {
+                    log::debug!("{:?} already has tag `{}`", repo.path(), tag);
+                }
+            }
+        })
+        .context("Failed to update tags file")
+}
+
+/// Removes tags from the repository.
+fn remove_tags(context: &mut Context, tags: &[Tag], repo: Option<&Path>) -> anyhow::Result<()> {
+    let repo = locate_repo(context, repo)?;
+    context
+        .update_tags(|repo_tags| {
+            for tag in tags {
+                if !repo_tags.remove(repo.collection(), repo.relative_path(), tag) {
+                    log::debug!("{:?} does not have tag `{}`", repo.path(), tag);
+                }
+            }
+        })
+        .context("Failed to update tags file")
+}
+
+/// Lists tags of the repository.
+fn list_repo_tags(context: &Context, repo: &Path, output: OutputFormat) -> anyhow::Result<()> {
+    let repo = locate_repo(context, Some(repo))?;
+    let tags = context
+        .get_or_load_tags()
+        .context("Failed to load tags file")?
+        .get(repo.collection(), repo.relative_path())
+        .map(|tags| tags.iter().collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    match output {
+        OutputFormat::Text => {
+            for tag in tags {
+                writeln!(handle, "{}", tag)?;
+            }
+        }
+        OutputFormat::Json => write_json(&mut handle, &tags)?,
+        OutputFormat::Csv => {
+            write_csv_record(&mut handle, &["tag"])?;
+            for tag in tags {
+                write_csv_record(&mut handle, &[tag.as_str()])?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Lists all tagged repositories.
+fn list_all_tags(context: &Context, output: OutputFormat) -> anyhow::Result<()> {
+    let tags = context
+        .get_or_load_tags()
+        .context("Failed to load tags file")?;
+    let entries = tags
+        .iter()
+        .map(|(collection, relative_path, tags)| Entry {
+            collection,
+            relative_path,
+            tags: tags.iter().collect(),
+        })
+        .collect::<Vec<_>>();
+
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    match output {
+        OutputFormat::Text => {
+            for entry in &entries {
+                writeln!(
+                    handle,
+                    "{}\t{}\t{}",
+                    entry.collection,
+                    entry.relative_path.display(),
+                    entry.joined_tags()
+                )?;
+            }
+        }
+        OutputFormat::Json => write_json(&mut handle, &entries)?,
+        OutputFormat::Csv => {
+            write_csv_record(&mut handle, &["collection", "relative_path", "tags"])?;
+            for entry in &entries {
+                write_csv_record(
+                    &mut handle,
+                    &[
+                        entry.collection.as_str(),
+                        &entry.relative_path.to_string_lossy(),
+                        &entry.joined_tags(),
+                    ],
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Tagged repository entry.
+#[derive(Debug, Serialize)]
+struct Entry<'a> {
+    /// Collection name.
+    collection: &'a CollectionName,
+    /// Path of the repository relative to the collection directory.
+    relative_path: &'a Path,
+    /// Tags.
+    tags: Vec<&'a Tag>,
+}
+
+impl Entry<'_> {
+    /// Returns the comma-separated tags.
+    fn joined_tags(&self) -> String {
+        self.tags
+            .iter()
+            .map(|tag| tag.as_str())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
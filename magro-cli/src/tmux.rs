@@ -0,0 +1,260 @@
+//! `tmux` subcommand.
+
+use std::{
+    borrow::Cow,
+    fmt,
+    io::{self, Write},
+    path::Path,
+    process::Command,
+    str,
+};
+
+use anyhow::{anyhow, bail, Context as _};
+use magro::{query, view::RepoView, Context};
+use structopt::StructOpt;
+
+use crate::select::RepoSelectOpt;
+
+/// Session config format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EmitFormat {
+    /// tmuxp.
+    Tmuxp,
+    /// tmuxinator.
+    Tmuxinator,
+}
+
+impl EmitFormat {
+    /// Returns a list of possible options.
+    #[inline]
+    #[must_use]
+    fn possible_opt_values() -> &'static [&'static str] {
+        &["tmuxp", "tmuxinator"]
+    }
+
+    /// Returns the option value.
+    #[inline]
+    #[must_use]
+    fn as_opt_value(&self) -> &'static str {
+        match self {
+            Self::Tmuxp => "tmuxp",
+            Self::Tmuxinator => "tmuxinator",
+        }
+    }
+
+    /// Parses the option value.
+    #[inline]
+    #[must_use]
+    fn from_opt_value(s: &str) -> Option<Self> {
+        match s {
+            "tmuxp" => Some(Self::Tmuxp),
+            "tmuxinator" => Some(Self::Tmuxinator),
+            _ => None,
+        }
+    }
+}
+
+impl str::FromStr for EmitFormat {
+    type Err = anyhow::Error;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_opt_value(s).ok_or_else(|| anyhow!("Unknown session config format {:?}", s))
+    }
+}
+
+impl fmt::Display for EmitFormat {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_opt_value())
+    }
+}
+
+/// Options for `tmux` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct TmuxOpt {
+    /// Target repositories.
+    #[structopt(flatten)]
+    select: RepoSelectOpt,
+    /// Targets only repositories matching the query.
+    #[structopt(long)]
+    query: Option<String>,
+    /// Session name.
+    ///
+    /// If not specified, the collection name is used when exactly one
+    /// collection is specified, and `magro` is used otherwise.
+    #[structopt(long, short)]
+    session: Option<String>,
+    /// Prints the session config for the specified tool, instead of creating a session.
+    #[structopt(long, possible_values = EmitFormat::possible_opt_values())]
+    emit: Option<EmitFormat>,
+}
+
+impl TmuxOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &Context) -> anyhow::Result<()> {
+        log::trace!(
+            "tmux select={:?}, query={:?}, session={:?}, emit={:?}",
+            self.select,
+            self.query,
+            self.session,
+            self.emit
+        );
+
+        let mut repos = self.select.select(context)?;
+        if let Some(query) = &self.query {
+            repos = query::find(&repos, query).into_iter().cloned().collect();
+        }
+        let windows = repos
+            .iter()
+            .map(Window::new)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if windows.is_empty() {
+            bail!("No repositories found");
+        }
+
+        let session = match &self.session {
+            Some(v) => v.clone(),
+            None => default_session_name(&repos),
+        };
+
+        match self.emit {
+            Some(EmitFormat::Tmuxp) => emit_tmuxp(&mut io::stdout().lock(), &session, &windows)?,
+            Some(EmitFormat::Tmuxinator) => {
+                emit_tmuxinator(&mut io::stdout().lock(), &session, &windows)?
+            }
+            None => create_session(&session, &windows)?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Window to be created.
+#[derive(Debug)]
+struct Window<'a> {
+    /// Window name.
+    name: String,
+    /// Start directory.
+    dir: Cow<'a, Path>,
+}
+
+impl<'a> Window<'a> {
+    /// Creates a window for the repository.
+    fn new(repo: &'a RepoView) -> anyhow::Result<Self> {
+        let dir = repo
+            .vcs()
+            .workdir(repo.path())
+            .with_context(|| format!("Failed to get working directory for {:?}", repo.path()))?
+            .unwrap_or_else(|| repo.path().into());
+        let name = repo
+            .name()
+            .file_name()
+            .map_or_else(|| repo.name_string(), |s| s.to_string_lossy().into_owned());
+        Ok(Self { name, dir })
+    }
+}
+
+/// Returns the default session name.
+fn default_session_name(repos: &[RepoView]) -> String {
+    match repos.split_first() {
+        Some((first, rest))
+            if rest
+                .iter()
+                .all(|repo| repo.collection() == first.collection()) =>
+        {
+            // tmux does not allow `.` and `:` in session names.
+            first.collection().as_str().replace(&['.', ':'][..], "_")
+        }
+        _ => "magro".to_owned(),
+    }
+}
+
+/// Creates a tmux session.
+fn create_session(session: &str, windows: &[Window<'_>]) -> anyhow::Result<()> {
+    let has_session = Command::new("tmux")
+        .args(["has-session", "-t", &format!("={}", session)])
+        .output()
+        .context("Failed to run tmux")?
+        .status
+        .success();
+    if has_session {
+        bail!("tmux session `{}` already exists", session);
+    }
+
+    let (first, rest) = windows.split_first().expect("Windows should not be empty");
+    let mut cmd = Command::new("tmux");
+    cmd.args(["new-session", "-d", "-s", session, "-n", &first.name, "-c"])
+        .arg(&*first.dir);
+    run_tmux(cmd)?;
+    for window in rest {
+        let mut cmd = Command::new("tmux");
+        cmd.args([
+            "new-window",
+            "-d",
+            "-t",
+            &format!("={}:", session),
+            "-n",
+            &window.name,
+            "-c",
+        ])
+        .arg(&*window.dir);
+        run_tmux(cmd)?;
+    }
+    log::info!(
+        "Created tmux session `{}` with {} windows",
+        session,
+        windows.len()
+    );
+    println!("{}", session);
+
+    Ok(())
+}
+
+/// Runs a tmux command.
+fn run_tmux(mut cmd: Command) -> anyhow::Result<()> {
+    log::debug!("Running {:?}", cmd);
+    let status = cmd.status().context("Failed to run tmux")?;
+    if !status.success() {
+        bail!("tmux exited with {}", status);
+    }
+    Ok(())
+}
+
+/// Writes tmuxp session config.
+fn emit_tmuxp<W: Write>(out: &mut W, session: &str, windows: &[Window<'_>]) -> io::Result<()> {
+    writeln!(out, "session_name: {}", yaml_quote(session))?;
+    writeln!(out, "windows:")?;
+    for window in windows {
+        writeln!(out, "  - window_name: {}", yaml_quote(&window.name))?;
+        writeln!(
+            out,
+            "    start_directory: {}",
+            yaml_quote(&window.dir.to_string_lossy())
+        )?;
+        writeln!(out, "    panes:")?;
+        writeln!(out, "      - null")?;
+    }
+    Ok(())
+}
+
+/// Writes tmuxinator project config.
+fn emit_tmuxinator<W: Write>(out: &mut W, session: &str, windows: &[Window<'_>]) -> io::Result<()> {
+    writeln!(out, "name: {}", yaml_quote(session))?;
+    writeln!(out, "windows:")?;
+    for window in windows {
+        writeln!(out, "  - {}:", yaml_quote(&window.name))?;
+        writeln!(
+            out,
+            "      root: {}",
+            yaml_quote(&window.dir.to_string_lossy())
+        )?;
+    }
+    Ok(())
+}
+
+/// Quotes the string as a YAML single-quoted scalar.
+fn yaml_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
@@ -0,0 +1,320 @@
+//! `trash` subcommand.
+
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use anyhow::{bail, Context as _};
+use magro::{
+    cache::RepoCacheEntry,
+    collection::CollectionName,
+    metadata::unix_now,
+    trash::{Trash, TrashItem},
+    Context,
+};
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::GlobalOpt,
+    output::{write_csv_record, write_json, OutputFormat},
+};
+
+/// Seconds in a day.
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Options for `trash` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct TrashOpt {
+    /// Subcommand.
+    #[structopt(subcommand)]
+    subcommand: Subcommand,
+}
+
+impl TrashOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &mut Context, global: &GlobalOpt) -> anyhow::Result<()> {
+        match &self.subcommand {
+            Subcommand::Empty { expired, dry_run } => {
+                log::trace!("trash empty expired={}, dry_run={}", expired, dry_run);
+                empty(context, *expired, *dry_run)
+            }
+            Subcommand::List => {
+                log::trace!("trash list");
+                list(context, global)
+            }
+            Subcommand::Restore { items } => {
+                log::trace!("trash restore items={:?}", items);
+                restore(context, items)
+            }
+        }
+    }
+}
+
+/// Subcommand of `trash`.
+#[derive(Debug, Clone, StructOpt)]
+pub enum Subcommand {
+    /// Deletes the repositories in the trash permanently.
+    Empty {
+        /// Deletes only the repositories kept longer than the retention period.
+        ///
+        /// The retention period is configured by `retention-days` in `[trash]`
+        /// section of the main config (default: 30).
+        #[structopt(long)]
+        expired: bool,
+        /// Only prints the repositories to be deleted.
+        #[structopt(long)]
+        dry_run: bool,
+    },
+    /// Shows the repositories in the trash.
+    List,
+    /// Restores the repositories in the trash to the original paths.
+    Restore {
+        /// IDs or names of the items to restore.
+        ///
+        /// Names are the original paths relative to the collection directory,
+        /// optionally prefixed with `<collection>:`.
+        #[structopt(required = true, min_values = 1)]
+        items: Vec<String>,
+    },
+}
+
+/// Deletes the items kept longer than the retention period.
+pub(crate) fn purge_expired(context: &mut Context) -> anyhow::Result<()> {
+    let retention_days = context.config().main().trash().retention_days();
+    let expired = context
+        .get_or_load_trash()
+        .context("Failed to load the trash")?
+        .items()
+        .iter()
+        .filter(|item| item.is_expired(retention_days))
+        .map(|item| item.id().to_owned())
+        .collect::<Vec<_>>();
+    if expired.is_empty() {
+        return Ok(());
+    }
+    context
+        .update_trash(|trash| purge_items(trash, &expired))
+        .context("Failed to update the trash index")?
+}
+
+/// Deletes the items with the given IDs permanently.
+fn purge_items(trash: &mut Trash, ids: &[String]) -> anyhow::Result<()> {
+    for id in ids {
+        let item = trash
+            .purge(id)
+            .with_context(|| format!("Failed to delete the trash item {}", id))?;
+        log::info!(
+            "Deleted {}:{} (ID: {}) from the trash",
+            item.collection(),
+            item.dir().display(),
+            item.id()
+        );
+    }
+    Ok(())
+}
+
+/// Deletes the items in the trash.
+fn empty(context: &mut Context, expired_only: bool, dry_run: bool) -> anyhow::Result<()> {
+    let retention_days = context.config().main().trash().retention_days();
+    let targets = context
+        .get_or_load_trash()
+        .context("Failed to load the trash")?
+        .items()
+        .iter()
+        .filter(|item| !expired_only || item.is_expired(retention_days))
+        .map(|item| {
+            if dry_run {
+                println!(
+                    "{}  {}:{}",
+                    item.id(),
+                    item.collection(),
+                    item.dir().display()
+                );
+            }
+            item.id().to_owned()
+        })
+        .collect::<Vec<_>>();
+    if dry_run || targets.is_empty() {
+        return Ok(());
+    }
+
+    context
+        .update_trash(|trash| purge_items(trash, &targets))
+        .context("Failed to update the trash index")?
+}
+
+/// Shows the items in the trash.
+fn list(context: &Context, global: &GlobalOpt) -> anyhow::Result<()> {
+    let retention_days = context.config().main().trash().retention_days();
+    let trash = context
+        .get_or_load_trash()
+        .context("Failed to load the trash")?;
+    let now = unix_now();
+    let entries = trash
+        .items()
+        .iter()
+        .map(|item| TrashEntry {
+            id: item.id(),
+            collection: item.collection(),
+            dir: item.dir(),
+            path: trash.item_path(item).to_string_lossy().into_owned(),
+            removed_at: item.removed_at(),
+            expired: item.is_expired(retention_days),
+            age_days: now.saturating_sub(item.removed_at()) / SECS_PER_DAY,
+        })
+        .collect::<Vec<_>>();
+
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    match global.output {
+        OutputFormat::Text => {
+            for entry in &entries {
+                writeln!(
+                    handle,
+                    "{}  {}:{}  (removed {} days ago{})",
+                    entry.id,
+                    entry.collection,
+                    entry.dir.display(),
+                    entry.age_days,
+                    if entry.expired { ", expired" } else { "" }
+                )?;
+            }
+        }
+        OutputFormat::Json => write_json(&mut handle, &entries)?,
+        OutputFormat::Csv => {
+            write_csv_record(
+                &mut handle,
+                &["id", "collection", "dir", "path", "removed_at", "expired"],
+            )?;
+            for entry in &entries {
+                write_csv_record(
+                    &mut handle,
+                    &[
+                        entry.id,
+                        entry.collection.as_str(),
+                        &entry.dir.to_string_lossy(),
+                        &entry.path,
+                        &entry.removed_at.to_string(),
+                        &entry.expired.to_string(),
+                    ],
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Restores the items to the original paths.
+fn restore(context: &mut Context, keys: &[String]) -> anyhow::Result<()> {
+    let trash = context
+        .get_or_load_trash()
+        .context("Failed to load the trash")?;
+    let ids = keys
+        .iter()
+        .map(|key| find_item(trash, key).map(|item| item.id().to_owned()))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    for id in &ids {
+        let item = context
+            .get_or_load_trash()
+            .context("Failed to load the trash")?
+            .get(id)
+            .expect("Should never fail: the item is found just now")
+            .clone();
+        let collection_dir = context
+            .config()
+            .collections()
+            .get(item.collection())
+            .with_context(|| {
+                format!(
+                    "Cannot restore {} because the collection `{}` does not exist",
+                    id,
+                    item.collection()
+                )
+            })?
+            .abspath(context)
+            .into_owned();
+        let dest = collection_dir.join(item.dir());
+        context
+            .update_trash(|trash| trash.restore(id, &dest))
+            .context("Failed to update the trash index")?
+            .with_context(|| format!("Failed to restore {} to {:?}", id, dest))?;
+        log::info!("Restored {:?} from the trash (ID: {})", dest, id);
+
+        restore_cache_entry(context, item.collection(), item.entry())?;
+    }
+
+    Ok(())
+}
+
+/// Adds the cache entry of the restored repository.
+fn restore_cache_entry(
+    context: &mut Context,
+    collection: &CollectionName,
+    entry: &RepoCacheEntry,
+) -> anyhow::Result<()> {
+    let restored = context
+        .update_cache(|cache| match cache.collection_repos_mut(collection) {
+            Some(repos) => {
+                repos.insert(entry.clone());
+                true
+            }
+            None => false,
+        })
+        .context("Failed to update cache file")?;
+    if !restored {
+        log::warn!(
+            "No cache found for collection `{}`; run `magro refresh` to update the cache",
+            collection
+        );
+    }
+    Ok(())
+}
+
+/// Returns the item with the given ID or name.
+fn find_item<'a>(trash: &'a Trash, key: &str) -> anyhow::Result<&'a TrashItem> {
+    if let Some(item) = trash.get(key) {
+        return Ok(item);
+    }
+    let (collection, name) = match key.split_once(':') {
+        Some((collection, name)) => (Some(collection), name),
+        None => (None, key),
+    };
+    let mut matched = trash.items().iter().filter(|item| {
+        item.dir() == Path::new(name) && collection.map_or(true, |c| item.collection() == c)
+    });
+    let item = matched
+        .next()
+        .with_context(|| format!("Item `{}` is not found in the trash", key))?;
+    if matched.next().is_some() {
+        bail!(
+            "Multiple items named `{}` are in the trash; specify the ID instead",
+            key
+        );
+    }
+    Ok(item)
+}
+
+/// Trash item entry of the report.
+#[derive(Debug, Serialize)]
+struct TrashEntry<'a> {
+    /// ID.
+    id: &'a str,
+    /// Collection name.
+    collection: &'a CollectionName,
+    /// Original directory relative to the collection directory.
+    dir: &'a Path,
+    /// Current path in the trash.
+    path: String,
+    /// Time of removal in seconds since the UNIX epoch.
+    removed_at: u64,
+    /// Whether the item is kept longer than the retention period.
+    expired: bool,
+    /// Days since removal.
+    #[serde(skip)]
+    age_days: u64,
+}
@@ -0,0 +1,157 @@
+//! `verify` subcommand.
+
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use anyhow::bail;
+use magro::{collection::CollectionName, verify::IntegrityProblem, view::RepoView, Context};
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::GlobalOpt,
+    logging,
+    output::{write_csv_record, write_json, OutputFormat},
+    select::RepoSelectOpt,
+};
+
+/// Options for `verify` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct VerifyOpt {
+    /// Target repositories.
+    #[structopt(flatten)]
+    select: RepoSelectOpt,
+    /// Runs the operation as possible even when errors are detected.
+    ///
+    /// Note that errors are ignored during the operation, but the program will
+    /// exit with failure (i.e. errors won't be completely ignored).
+    #[structopt(long)]
+    keep_going: bool,
+}
+
+impl VerifyOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &Context, global: &GlobalOpt) -> anyhow::Result<()> {
+        log::trace!(
+            "verify select={:?}, keep_going={}",
+            self.select,
+            self.keep_going
+        );
+
+        let repos = self.select.select(context)?;
+        let num_repos = repos.len();
+        let keep_going = self.keep_going;
+        let envs = logging::CollectionEnvs::new(context);
+        let results = global.runner(context).run_until(
+            repos,
+            move |repo| logging::with_repo(repo, &envs, || repo.vcs().verify(repo.path())),
+            move |result| !keep_going && result.is_err(),
+        );
+
+        let mut checked = Vec::with_capacity(results.len());
+        let mut num_errors = 0_usize;
+        for (repo, result) in results {
+            match result {
+                Ok(problems) => checked.push((repo, problems)),
+                Err(e) => {
+                    if !self.keep_going {
+                        return Err(anyhow::Error::new(e).context(format!(
+                            "Failed to verify the repository {:?}",
+                            repo.path()
+                        )));
+                    }
+                    log::error!("Failed to verify the repository {:?}: {}", repo.path(), e);
+                    num_errors += 1;
+                }
+            }
+        }
+
+        let entries = checked
+            .iter()
+            .flat_map(|(repo, problems)| {
+                problems
+                    .iter()
+                    .map(move |problem| ProblemEntry::new(repo, problem))
+            })
+            .collect::<Vec<_>>();
+        let num_broken = checked
+            .iter()
+            .filter(|(_, problems)| !problems.is_empty())
+            .count();
+
+        let stdout = io::stdout();
+        let mut handle = stdout.lock();
+        match global.output {
+            OutputFormat::Text => {
+                for entry in &entries {
+                    writeln!(
+                        handle,
+                        "{}/{}: {}",
+                        entry.collection, entry.name, entry.message
+                    )?;
+                }
+            }
+            OutputFormat::Json => write_json(&mut handle, &entries)?,
+            OutputFormat::Csv => {
+                write_csv_record(&mut handle, &["collection", "name", "path", "message"])?;
+                for entry in &entries {
+                    write_csv_record(
+                        &mut handle,
+                        &[
+                            entry.collection.as_str(),
+                            &entry.name,
+                            &entry.path.to_string_lossy(),
+                            &entry.message,
+                        ],
+                    )?;
+                }
+            }
+        }
+
+        log::info!(
+            "{} of {} repositories have integrity problems",
+            num_broken,
+            num_repos
+        );
+        if num_errors != 0 {
+            bail!("Failed to verify {} repositories", num_errors);
+        }
+        if num_broken != 0 {
+            bail!("{} repositories have integrity problems", num_broken);
+        }
+
+        Ok(())
+    }
+}
+
+/// Integrity problem entry of the report.
+#[derive(Debug, Serialize)]
+struct ProblemEntry<'a> {
+    /// Collection name.
+    collection: &'a CollectionName,
+    /// Repository name.
+    name: String,
+    /// Absolute path of the repository.
+    path: &'a Path,
+    /// Problem.
+    #[serde(flatten)]
+    problem: &'a IntegrityProblem,
+    /// Human-readable message.
+    message: String,
+}
+
+impl<'a> ProblemEntry<'a> {
+    /// Creates a new entry.
+    fn new(repo: &'a RepoView, problem: &'a IntegrityProblem) -> Self {
+        Self {
+            collection: repo.collection(),
+            name: repo.name_string(),
+            path: repo.path(),
+            problem,
+            message: problem.to_string(),
+        }
+    }
+}
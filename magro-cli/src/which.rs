@@ -0,0 +1,94 @@
+//! `which` subcommand.
+
+use std::{
+    borrow::Cow,
+    env,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context as _};
+use magro::{collection::CollectionName, view::RepoView, Context};
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::GlobalOpt,
+    output::{write_csv_record, write_json, OutputFormat},
+};
+
+/// Options for `which` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct WhichOpt {
+    /// Path to look up.
+    ///
+    /// If not specified, the current directory is used.
+    #[structopt(parse(from_os_str))]
+    path: Option<PathBuf>,
+}
+
+impl WhichOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &Context, global: &GlobalOpt) -> anyhow::Result<()> {
+        log::trace!("which path={:?}", self.path);
+
+        let path = match &self.path {
+            Some(path) if path.is_absolute() => path.clone(),
+            path => {
+                let cwd = env::current_dir().context("Failed to get the current directory")?;
+                match path {
+                    Some(path) => cwd.join(path),
+                    None => cwd,
+                }
+            }
+        };
+        let (collection, relative_path) = match magro::locate::locate_collection(context, &path) {
+            Some(v) => v,
+            None => bail!("{:?} is not inside any collection", path),
+        };
+        let repo = magro::locate::locate(context, &path).context("Failed to load cache file")?;
+        let entry = Entry {
+            collection: collection.name(),
+            relative_path: &relative_path,
+            repository: repo.as_ref(),
+        };
+
+        let stdout = io::stdout();
+        let mut handle = stdout.lock();
+        match global.output {
+            OutputFormat::Text => match &entry.repository {
+                Some(repo) => writeln!(
+                    handle,
+                    "{}\t{}\t{}",
+                    entry.collection,
+                    repo.name_string(),
+                    repo.path().display()
+                )?,
+                None => writeln!(handle, "{}", entry.collection)?,
+            },
+            OutputFormat::Json => write_json(&mut handle, &entry)?,
+            OutputFormat::Csv => {
+                write_csv_record(&mut handle, &["collection", "name", "path"])?;
+                let (name, repo_path) = match &entry.repository {
+                    Some(repo) => (repo.name_string(), repo.path().to_string_lossy()),
+                    None => (String::new(), Cow::Borrowed("")),
+                };
+                write_csv_record(&mut handle, &[entry.collection.as_str(), &name, &repo_path])?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Result of the lookup.
+#[derive(Serialize)]
+struct Entry<'a> {
+    /// Collection containing the path.
+    collection: &'a CollectionName,
+    /// Path relative to the collection directory.
+    relative_path: &'a Path,
+    /// Cached repository containing the path.
+    repository: Option<&'a RepoView>,
+}
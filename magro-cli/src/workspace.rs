@@ -0,0 +1,217 @@
+//! `workspace` subcommand.
+
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    path::PathBuf,
+    str,
+};
+
+use anyhow::{anyhow, bail, Context as _};
+use magro::{query, vcs::Vcs, view::RepoView, Context};
+use structopt::StructOpt;
+
+use crate::select::RepoSelectOpt;
+
+/// Workspace format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WorkspaceFormat {
+    /// VS Code multi-root workspace (`.code-workspace`).
+    Vscode,
+    /// JetBrains VCS directory mappings (`.idea/vcs.xml`).
+    Jetbrains,
+}
+
+impl WorkspaceFormat {
+    /// Returns a list of possible options.
+    #[inline]
+    #[must_use]
+    fn possible_opt_values() -> &'static [&'static str] {
+        &["vscode", "jetbrains"]
+    }
+
+    /// Returns the option value.
+    #[inline]
+    #[must_use]
+    fn as_opt_value(&self) -> &'static str {
+        match self {
+            Self::Vscode => "vscode",
+            Self::Jetbrains => "jetbrains",
+        }
+    }
+
+    /// Parses the option value.
+    #[inline]
+    #[must_use]
+    fn from_opt_value(s: &str) -> Option<Self> {
+        match s {
+            "vscode" => Some(Self::Vscode),
+            "jetbrains" => Some(Self::Jetbrains),
+            _ => None,
+        }
+    }
+}
+
+impl str::FromStr for WorkspaceFormat {
+    type Err = anyhow::Error;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_opt_value(s).ok_or_else(|| anyhow!("Unknown workspace format {:?}", s))
+    }
+}
+
+impl fmt::Display for WorkspaceFormat {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_opt_value())
+    }
+}
+
+/// Options for `workspace` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct WorkspaceOpt {
+    /// Workspace format.
+    ///
+    /// `vscode` generates a `.code-workspace` file, and `jetbrains` generates
+    /// a `.idea/vcs.xml` file.
+    #[structopt(possible_values = WorkspaceFormat::possible_opt_values())]
+    format: WorkspaceFormat,
+    /// Target repositories.
+    #[structopt(flatten)]
+    select: RepoSelectOpt,
+    /// Targets only repositories matching the query.
+    #[structopt(long)]
+    query: Option<String>,
+    /// File to write the workspace to.
+    ///
+    /// If not specified, the workspace is written to stdout.
+    #[structopt(long, short, parse(from_os_str))]
+    file: Option<PathBuf>,
+}
+
+impl WorkspaceOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &Context) -> anyhow::Result<()> {
+        log::trace!(
+            "workspace format={}, select={:?}, query={:?}, file={:?}",
+            self.format,
+            self.select,
+            self.query,
+            self.file
+        );
+
+        let mut repos = self.select.select(context)?;
+        if let Some(query) = &self.query {
+            repos = query::find(&repos, query).into_iter().cloned().collect();
+        }
+
+        let mut folders = Vec::new();
+        for mut repo in repos {
+            let has_workdir = repo.resolve_workdir().with_context(|| {
+                format!("Failed to get working directory for {:?}", repo.path())
+            })?;
+            if !has_workdir {
+                log::debug!("Skipping a bare repository {:?}", repo.path());
+                continue;
+            }
+            folders.push(repo);
+        }
+        if folders.is_empty() {
+            bail!("No repositories with working directories found");
+        }
+
+        let content = match self.format {
+            WorkspaceFormat::Vscode => vscode_workspace(&folders)?,
+            WorkspaceFormat::Jetbrains => jetbrains_vcs_mappings(&folders),
+        };
+        match &self.file {
+            Some(path) => fs::write(path, content)
+                .with_context(|| format!("Failed to write the workspace to {:?}", path))?,
+            None => io::stdout().write_all(content.as_bytes())?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Generates a VS Code multi-root workspace.
+fn vscode_workspace(repos: &[RepoView]) -> serde_json::Result<String> {
+    let folders = repos
+        .iter()
+        .map(|repo| {
+            serde_json::json!({
+                "name": format!("{}/{}", repo.collection(), repo.name_string()),
+                "path": repo.workdir().expect("Working directory should be resolved"),
+            })
+        })
+        .collect::<Vec<_>>();
+    let workspace = serde_json::json!({
+        "folders": folders,
+        "settings": {},
+    });
+    let mut content = serde_json::to_string_pretty(&workspace)?;
+    content.push('\n');
+    Ok(content)
+}
+
+/// Generates JetBrains VCS directory mappings.
+fn jetbrains_vcs_mappings(repos: &[RepoView]) -> String {
+    let mut content = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <project version=\"4\">\n  \
+         <component name=\"VcsDirectoryMappings\">\n",
+    );
+    for repo in repos {
+        let vcs = match repo.vcs() {
+            Vcs::Git => "Git",
+            Vcs::Hg => "hg4idea",
+            vcs => {
+                log::warn!(
+                    "Skipping {:?}: VCS {:?} is not supported for JetBrains workspaces",
+                    repo.path(),
+                    vcs
+                );
+                continue;
+            }
+        };
+        let dir = repo
+            .workdir()
+            .expect("Working directory should be resolved");
+        content.push_str(&format!(
+            "    <mapping directory=\"{}\" vcs=\"{}\" />\n",
+            xml_escape(&dir.to_string_lossy()),
+            vcs
+        ));
+    }
+    content.push_str("  </component>\n</project>\n");
+    content
+}
+
+/// Escapes the string for XML attribute values.
+fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xml_attribute_escape() {
+        assert_eq!(xml_escape("a&b<c>\"d'"), "a&amp;b&lt;c&gt;&quot;d&apos;");
+        assert_eq!(xml_escape("/home/user/src"), "/home/user/src");
+    }
+}
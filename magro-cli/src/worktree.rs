@@ -0,0 +1,305 @@
+//! `worktree` subcommand.
+
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use anyhow::{bail, Context as _};
+use magro::{
+    cache::{RepoCacheEntry, WorktreeCacheEntry},
+    collection::CollectionName,
+    permissions, query,
+    view::RepoView,
+    Context,
+};
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    cli_opt::GlobalOpt,
+    output::{write_csv_record, write_json, OutputFormat},
+    select::RepoSelectOpt,
+};
+
+/// Options for `worktree` subcommand.
+#[derive(Debug, Clone, StructOpt)]
+#[non_exhaustive]
+pub struct WorktreeOpt {
+    /// Subcommand.
+    #[structopt(subcommand)]
+    subcommand: Subcommand,
+}
+
+impl WorktreeOpt {
+    /// Runs the actual operation.
+    pub fn run(&self, context: &mut Context, global: &GlobalOpt) -> anyhow::Result<()> {
+        match &self.subcommand {
+            Subcommand::Add {
+                select,
+                query,
+                branch,
+            } => {
+                log::trace!(
+                    "worktree add select={:?}, query={:?}, branch={:?}",
+                    select,
+                    query,
+                    branch
+                );
+                add(context, select, query, branch)
+            }
+            Subcommand::List { select } => {
+                log::trace!("worktree list select={:?}", select);
+                list(context, select, global.output)
+            }
+            Subcommand::Rm {
+                select,
+                force,
+                query,
+                branch,
+            } => {
+                log::trace!(
+                    "worktree rm select={:?}, force={}, query={:?}, branch={:?}",
+                    select,
+                    force,
+                    query,
+                    branch
+                );
+                remove(context, select, *force, query, branch)
+            }
+        }
+    }
+}
+
+/// Subcommand of `worktree`.
+#[derive(Debug, Clone, StructOpt)]
+pub enum Subcommand {
+    /// Creates a linked worktree of the repository for the branch.
+    ///
+    /// The worktree is created at `<dir>/<collection>/<repo>/<branch>`, where
+    /// `<dir>` is configured by `dir` in `[worktree]` section of the main
+    /// config (default: `worktrees` in the home directory).
+    /// `/` in the branch name is replaced by `-`.
+    ///
+    /// If the local branch does not exist, it is created from the branch of
+    /// the same name on `origin` if available, or from `HEAD` otherwise.
+    /// The path of the created worktree is printed.
+    Add {
+        /// Candidate repositories.
+        #[structopt(flatten)]
+        select: RepoSelectOpt,
+        /// Query, same as `path` subcommand.
+        ///
+        /// The best matching repository is used.
+        query: String,
+        /// Branch to check out.
+        branch: String,
+    },
+    /// Lists the linked worktrees created by magro.
+    List {
+        /// Target repositories.
+        #[structopt(flatten)]
+        select: RepoSelectOpt,
+    },
+    /// Removes the linked worktree created by magro.
+    Rm {
+        /// Candidate repositories.
+        #[structopt(flatten)]
+        select: RepoSelectOpt,
+        /// Removes the worktree even if it has uncommitted changes or is locked.
+        #[structopt(long, short)]
+        force: bool,
+        /// Query, same as `path` subcommand.
+        ///
+        /// The best matching repository is used.
+        query: String,
+        /// Branch of the worktree.
+        branch: String,
+    },
+}
+
+/// Returns the selected repository best matching the query.
+fn find_repo(context: &Context, select: &RepoSelectOpt, query: &str) -> anyhow::Result<RepoView> {
+    let repos = select.select(context)?;
+    match query::find(&repos, query).into_iter().next() {
+        Some(repo) => Ok(repo.clone()),
+        None => bail!("No repositories matched the query {:?}", query),
+    }
+}
+
+/// Returns the worktrees registered for the repository.
+fn registered_worktrees(
+    context: &Context,
+    repo: &RepoView,
+) -> anyhow::Result<Vec<WorktreeCacheEntry>> {
+    let cache = context
+        .get_or_load_cache()
+        .context("Failed to load cache file")?;
+    Ok(cache
+        .collection_repos(repo.collection())
+        .and_then(|repos| repos.get(repo.relative_path()))
+        .map(|entry| entry.worktrees().to_vec())
+        .unwrap_or_default())
+}
+
+/// Updates the cache entry of the repository, and saves the cache.
+fn update_entry(
+    context: &mut Context,
+    repo: &RepoView,
+    f: impl FnOnce(&mut RepoCacheEntry),
+) -> anyhow::Result<()> {
+    context
+        .update_cache(|cache| {
+            if let Some(repos) = cache.collection_repos_mut(repo.collection()) {
+                repos.update_entry(repo.relative_path(), f);
+            }
+        })
+        .context("Failed to update cache file")
+}
+
+/// Creates a linked worktree.
+fn add(
+    context: &mut Context,
+    select: &RepoSelectOpt,
+    query: &str,
+    branch: &str,
+) -> anyhow::Result<()> {
+    context
+        .ensure_writable()
+        .context("Cannot create a worktree")?;
+    let repo = find_repo(context, select, query)?;
+    let dest = context
+        .home_dir()
+        .join(context.config().main().worktree().dir())
+        .join(repo.collection().as_str())
+        .join(repo.name())
+        .join(branch.replace('/', "-"));
+    if dest.symlink_metadata().is_ok() {
+        bail!("Destination {:?} already exists", dest);
+    }
+    if let Some(parent) = dest.parent() {
+        permissions::create_dir_all(parent, context.config().main().dir_mode())
+            .with_context(|| format!("Failed to create directory {:?}", parent))?;
+    }
+
+    log::info!(
+        "Creating a worktree of {:?} for the branch {:?} at {:?}",
+        repo.path(),
+        branch,
+        dest
+    );
+    repo.vcs()
+        .add_worktree(repo.path(), &dest, branch)
+        .with_context(|| format!("Failed to create a worktree at {:?}", dest))?;
+    let entry = WorktreeCacheEntry::new(branch.to_owned(), dest.clone());
+    update_entry(context, &repo, |repo_entry| repo_entry.add_worktree(entry))?;
+
+    println!("{}", dest.display());
+
+    Ok(())
+}
+
+/// Removes a linked worktree.
+fn remove(
+    context: &mut Context,
+    select: &RepoSelectOpt,
+    force: bool,
+    query: &str,
+    branch: &str,
+) -> anyhow::Result<()> {
+    context
+        .ensure_writable()
+        .context("Cannot remove a worktree")?;
+    let repo = find_repo(context, select, query)?;
+    let worktree = registered_worktrees(context, &repo)?
+        .into_iter()
+        .find(|worktree| worktree.branch() == branch)
+        .with_context(|| {
+            format!(
+                "No worktree for the branch {:?} of `{}` is registered",
+                branch,
+                repo.name_string()
+            )
+        })?;
+
+    log::info!("Removing the worktree {:?}", worktree.path());
+    repo.vcs()
+        .remove_worktree(repo.path(), worktree.path(), force)
+        .with_context(|| format!("Failed to remove the worktree {:?}", worktree.path()))?;
+    update_entry(context, &repo, |repo_entry| {
+        repo_entry.remove_worktree(worktree.path());
+    })
+}
+
+/// Lists the linked worktrees.
+fn list(context: &Context, select: &RepoSelectOpt, output: OutputFormat) -> anyhow::Result<()> {
+    let mut entries = Vec::new();
+    for repo in select.select(context)? {
+        for worktree in registered_worktrees(context, &repo)? {
+            entries.push(Entry {
+                collection: repo.collection().clone(),
+                name: repo.name_string(),
+                exists: worktree.path().exists(),
+                branch: worktree.branch().to_owned(),
+                path: worktree.path().to_owned(),
+            });
+        }
+    }
+
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    match output {
+        OutputFormat::Text => {
+            for entry in &entries {
+                write!(
+                    handle,
+                    "{}:{}\t{}\t{}",
+                    entry.collection,
+                    entry.name,
+                    entry.branch,
+                    entry.path.display()
+                )?;
+                if !entry.exists {
+                    handle.write_all(b"\t(missing)")?;
+                }
+                writeln!(handle)?;
+            }
+        }
+        OutputFormat::Json => write_json(&mut handle, &entries)?,
+        OutputFormat::Csv => {
+            write_csv_record(
+                &mut handle,
+                &["collection", "name", "branch", "path", "exists"],
+            )?;
+            for entry in &entries {
+                write_csv_record(
+                    &mut handle,
+                    &[
+                        entry.collection.as_str(),
+                        &entry.name,
+                        &entry.branch,
+                        &entry.path.to_string_lossy(),
+                        if entry.exists { "true" } else { "false" },
+                    ],
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Worktree entry of the list.
+#[derive(Debug, Serialize)]
+struct Entry {
+    /// Collection name.
+    collection: CollectionName,
+    /// Repository name.
+    name: String,
+    /// Branch the worktree was created for.
+    branch: String,
+    /// Absolute path of the working directory.
+    path: PathBuf,
+    /// Whether the working directory exists.
+    exists: bool,
+}
@@ -0,0 +1,26 @@
+//! Backup to mirror remotes.
+
+use std::borrow::Cow;
+
+use crate::{
+    template::{self, TemplateError},
+    view::RepoView,
+};
+
+/// Expands the backup remote URL template for the repository.
+///
+/// Available variables are:
+///
+/// * `{collection}`: collection name.
+/// * `{path}`: repository path relative to the collection directory,
+///   without `.git` suffix (see [`RepoView::name`]).
+/// * `{basename}`: the last component of `{path}`.
+pub fn remote_url(template: &str, repo: &RepoView) -> Result<String, TemplateError> {
+    let name = repo.name();
+    template::expand(template, |var| match var {
+        "collection" => Some(Cow::Borrowed(repo.collection().as_str())),
+        "path" => Some(Cow::Owned(repo.name_string())),
+        "basename" => Some(name.file_name().unwrap_or_default().to_string_lossy()),
+        _ => None,
+    })
+}
@@ -0,0 +1,79 @@
+//! Local branches of repositories.
+
+use serde::Serialize;
+
+/// Local branch of a repository.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Branch {
+    /// Branch name.
+    name: String,
+    /// Upstream branch name (such as `origin/main`).
+    upstream: Option<String>,
+    /// Whether the branch is checked out at HEAD.
+    is_head: bool,
+    /// Whether the branch is merged into HEAD.
+    merged: bool,
+    /// Commit time of the branch tip in seconds since the UNIX epoch.
+    committed_at: Option<u64>,
+}
+
+impl Branch {
+    /// Creates a new `Branch`.
+    #[inline]
+    #[must_use]
+    pub fn new(
+        name: String,
+        upstream: Option<String>,
+        is_head: bool,
+        merged: bool,
+        committed_at: Option<u64>,
+    ) -> Self {
+        Self {
+            name,
+            upstream,
+            is_head,
+            merged,
+            committed_at,
+        }
+    }
+
+    /// Returns the branch name.
+    #[inline]
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the upstream branch name.
+    ///
+    /// Returns `None` if the branch has no upstream, or the upstream branch is
+    /// deleted on the remote.
+    #[inline]
+    #[must_use]
+    pub fn upstream(&self) -> Option<&str> {
+        self.upstream.as_deref()
+    }
+
+    /// Returns true if the branch is checked out at HEAD.
+    #[inline]
+    #[must_use]
+    pub fn is_head(&self) -> bool {
+        self.is_head
+    }
+
+    /// Returns true if the branch is merged into HEAD.
+    ///
+    /// The branch checked out at HEAD is also considered merged.
+    #[inline]
+    #[must_use]
+    pub fn is_merged(&self) -> bool {
+        self.merged
+    }
+
+    /// Returns the commit time of the branch tip in seconds since the UNIX epoch.
+    #[inline]
+    #[must_use]
+    pub fn committed_at(&self) -> Option<u64> {
+        self.committed_at
+    }
+}
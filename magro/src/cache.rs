@@ -2,14 +2,24 @@
 
 use std::{
     cmp,
-    collections::{BTreeMap, BTreeSet},
-    io, iter,
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt, fs, io, iter,
     path::{Path, PathBuf},
+    str,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
 
-use crate::{collection::CollectionName, discovery::RepoEntry, lock_fs, vcs::Vcs};
+use crate::{
+    collection::CollectionName, discovery::RepoEntry, lock_fs, metadata::RepoMetadata, vcs::Vcs,
+};
+
+mod namespace;
+pub(crate) mod path_serde;
+
+pub use self::namespace::{CacheNamespace, CacheNamespaceError};
 
 /// Global cache data.
 ///
@@ -41,15 +51,43 @@ impl Cache {
             },
         };
         let content = lock_fs::read_to_string_from_lockable_file(path, &mut file)?;
-        match toml::from_str(&content) {
-            Ok(v) => Ok(v),
+        Ok(Self::from_toml_str(&content))
+    }
+
+    /// Loads a cache from the given TOML document.
+    ///
+    /// Invalid data is reset to the default.
+    fn from_toml_str(content: &str) -> Self {
+        match toml::from_str(content) {
+            Ok(v) => v,
             Err(e) => {
                 log::error!("Cache will be reset due to invalid data: {}", e);
-                Ok(Self::default())
+                Self::default()
             }
         }
     }
 
+    /// Loads the cache from the given path, modifies it, and saves it.
+    ///
+    /// The cache file is locked during the whole operation, so concurrent
+    /// updates by other processes (for example, `refresh` and `clone` running
+    /// at the same time) are not overwritten.
+    /// Returns the updated cache and the result of the function.
+    pub(crate) fn update_path<F, R>(path: &Path, f: F) -> io::Result<(Self, R)>
+    where
+        F: FnOnce(&mut Self) -> R,
+    {
+        let mut updated = None;
+        lock_fs::update_with(path, |content, out| {
+            let mut cache = Self::from_toml_str(content);
+            let result = f(&mut cache);
+            cache.write_toml(out)?;
+            updated = Some((cache, result));
+            Ok(())
+        })?;
+        Ok(updated.expect("Should never fail: the cache is updated on success"))
+    }
+
     /// Returns the collection cache.
     #[inline]
     #[must_use]
@@ -67,16 +105,91 @@ impl Cache {
         self.collections.insert(name.into(), coll_cache)
     }
 
+    /// Returns a mutable reference to the collection cache.
+    #[inline]
+    #[must_use]
+    pub fn collection_repos_mut(
+        &mut self,
+        name: &CollectionName,
+    ) -> Option<&mut CollectionReposCache> {
+        self.collections.get_mut(name.as_str())
+    }
+
     /// Removes the collection cache.
     #[inline]
     pub fn remove_collection_repos_cache(&mut self, name: &str) -> Option<CollectionReposCache> {
         self.collections.remove(name)
     }
+
+    /// Moves the collection cache to the new name.
+    ///
+    /// Returns `false` if the cache for the old name does not exist.
+    /// If the cache for the new name already exists, it is overwritten.
+    pub fn rename_collection_repos(&mut self, old_name: &str, new_name: CollectionName) -> bool {
+        match self.collections.remove(old_name) {
+            Some(coll_cache) => {
+                self.collections.insert(new_name.into(), coll_cache);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Writes the cache as a TOML document.
+    ///
+    /// The document is serialized and written incrementally (one repository
+    /// entry at a time), so the whole document is not built in memory.
+    pub(crate) fn write_toml<W: io::Write + ?Sized>(&self, out: &mut W) -> io::Result<()> {
+        /// Cache document with (a part of) a single collection.
+        #[derive(Serialize)]
+        struct CacheChunk<'a> {
+            /// Collection.
+            collections: BTreeMap<&'a str, CollectionReposCacheView<'a, [&'a RepoCacheEntry; 1]>>,
+        }
+
+        let mut buf = String::new();
+        let mut write_chunk =
+            |name: &str, coll: CollectionReposCacheView<'_, [&RepoCacheEntry; 1]>| {
+                let chunk = CacheChunk {
+                    collections: iter::once((name, coll)).collect(),
+                };
+                buf.clear();
+                // This is expected to always success, because the cache is valid
+                // and the serialization does not perform I/O.
+                chunk
+                    .serialize(&mut toml::Serializer::new(&mut buf))
+                    .expect("Cache data should be serializable");
+                out.write_all(buf.as_bytes())?;
+                out.write_all(b"\n")
+            };
+
+        for (name, coll_cache) in &self.collections {
+            // Fields other than the repositories are written first, and then
+            // the repositories are appended to the same table one by one.
+            write_chunk(name, coll_cache.view(None))?;
+            for entry in coll_cache.repositories() {
+                write_chunk(name, CollectionReposCacheView::repos_only([entry]))?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 /// Cache of repositories in a collection.
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+///
+/// This is serialized through [`CollectionReposCacheView`].
+#[derive(Default, Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub struct CollectionReposCache {
+    /// Time of the last refresh (seconds since the UNIX epoch).
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    refreshed_at: Option<u64>,
+    /// Fingerprint of the collection directory at the time of discovery.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fingerprint: Option<CollectionFingerprint>,
     /// Repository (more precisely, git directory) paths.
     // Use `BTreeSet` here to keep things sorted.
     #[serde(default)]
@@ -84,6 +197,45 @@ pub struct CollectionReposCache {
     repos: BTreeSet<RepoCacheEntryWrapper>,
 }
 
+/// Serialized form of [`CollectionReposCache`], borrowing the data.
+///
+/// This is shared by the serialization of the whole cache and by
+/// [`Cache::write_toml`] writing the repositories one by one.
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct CollectionReposCacheView<'a, R> {
+    /// Time of the last refresh.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    refreshed_at: Option<u64>,
+    /// Fingerprint of the collection directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fingerprint: Option<&'a CollectionFingerprint>,
+    /// Repositories.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    repos: Option<R>,
+}
+
+impl<'a, R> CollectionReposCacheView<'a, R> {
+    /// Creates a view with only the given repositories.
+    fn repos_only(repos: R) -> Self {
+        Self {
+            refreshed_at: None,
+            fingerprint: None,
+            repos: Some(repos),
+        }
+    }
+}
+
+impl Serialize for CollectionReposCache {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.view(Some(&self.repos).filter(|repos| !repos.is_empty()))
+            .serialize(serializer)
+    }
+}
+
 impl Extend<RepoCacheEntry> for CollectionReposCache {
     #[inline]
     fn extend<T>(&mut self, iter: T)
@@ -96,12 +248,286 @@ impl Extend<RepoCacheEntry> for CollectionReposCache {
 }
 
 impl CollectionReposCache {
+    /// Returns the view to be serialized, with the given repositories.
+    fn view<R>(&self, repos: Option<R>) -> CollectionReposCacheView<'_, R> {
+        // Destructure all fields, so that a new field cannot be forgotten in
+        // the serialized form.
+        let Self {
+            refreshed_at,
+            fingerprint,
+            repos: _,
+        } = self;
+
+        CollectionReposCacheView {
+            refreshed_at: *refreshed_at,
+            fingerprint: fingerprint.as_ref(),
+            repos,
+        }
+    }
+
     /// Returns a sorted iterator of repository cache entries.
     #[inline]
     #[must_use]
     pub fn repositories(&self) -> CollectionRepoCacheIter<'_> {
         CollectionRepoCacheIter::new(self)
     }
+
+    /// Returns the repository cache entry with the given path.
+    #[must_use]
+    pub fn get(&self, path: &Path) -> Option<&RepoCacheEntry> {
+        // `vcs` is not used for comparison.
+        let key = RepoCacheEntryWrapper(RepoCacheEntry::new(Vcs::Git, path));
+        self.repos.get(&key).map(|wrapper| &wrapper.0)
+    }
+
+    /// Inserts the entry, and returns the replaced entry with the same path if exists.
+    pub fn insert(&mut self, entry: RepoCacheEntry) -> Option<RepoCacheEntry> {
+        self.repos
+            .replace(RepoCacheEntryWrapper(entry))
+            .map(|wrapper| wrapper.0)
+    }
+
+    /// Removes the entry with the given path.
+    pub fn remove(&mut self, path: &Path) -> Option<RepoCacheEntry> {
+        // `vcs` is not used for comparison.
+        let key = RepoCacheEntryWrapper(RepoCacheEntry::new(Vcs::Git, path));
+        self.repos.take(&key).map(|wrapper| wrapper.0)
+    }
+
+    /// Applies the given function to the entry with the given path.
+    ///
+    /// Returns `false` if the entry does not exist.
+    pub fn update_entry<F>(&mut self, path: &Path, f: F) -> bool
+    where
+        F: FnOnce(&mut RepoCacheEntry),
+    {
+        match self.remove(path) {
+            Some(mut entry) => {
+                f(&mut entry);
+                self.insert(entry);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Applies the given function to all entries.
+    pub fn update_entries<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut RepoCacheEntry),
+    {
+        self.repos = std::mem::take(&mut self.repos)
+            .into_iter()
+            .map(|mut wrapper| {
+                f(&mut wrapper.0);
+                wrapper
+            })
+            .collect();
+    }
+
+    /// Returns the fingerprint of the collection directory at the time of discovery.
+    #[inline]
+    #[must_use]
+    pub fn fingerprint(&self) -> Option<&CollectionFingerprint> {
+        self.fingerprint.as_ref()
+    }
+
+    /// Sets the fingerprint of the collection directory.
+    #[inline]
+    pub fn set_fingerprint(&mut self, fingerprint: Option<CollectionFingerprint>) {
+        self.fingerprint = fingerprint;
+    }
+
+    /// Returns the time of the last refresh in seconds since the UNIX epoch.
+    #[inline]
+    #[must_use]
+    pub fn refreshed_at(&self) -> Option<u64> {
+        self.refreshed_at
+    }
+
+    /// Sets the time of the last refresh.
+    #[inline]
+    pub fn set_refreshed_at(&mut self, refreshed_at: Option<u64>) {
+        self.refreshed_at = refreshed_at;
+    }
+
+    /// Returns the number of cached repositories.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.repos.len()
+    }
+
+    /// Returns true if no repositories are cached.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.repos.is_empty()
+    }
+
+    /// Copies metadata, source URIs, and worktrees from the old cache for
+    /// repositories without them, and keeps provenances of the old entries.
+    ///
+    /// Metadata is inherited per field (see [`RepoMetadata::inherit_from`]).
+    ///
+    /// This is useful to keep them when the repositories are rediscovered.
+    pub fn inherit_from(&mut self, old: &CollectionReposCache) {
+        self.update_entries(|entry| {
+            let old_entry = match old.get(&entry.path) {
+                Some(v) => v,
+                None => return,
+            };
+            entry.metadata.inherit_from(&old_entry.metadata);
+            if entry.source_uri.is_none() {
+                entry.source_uri = old_entry.source_uri.clone();
+            }
+            entry.provenance = Some(old_entry.provenance());
+            if entry.worktrees.is_empty() {
+                entry.worktrees = old_entry.worktrees.clone();
+            }
+        });
+    }
+
+    /// Keeps entries added to the latest cache since the cache was loaded.
+    ///
+    /// Repositories added by other processes (for example, by `clone`) during
+    /// a refresh may not be discovered, so they are kept if they exist under
+    /// `root_dir`.
+    pub fn keep_added_from(
+        &mut self,
+        latest: &CollectionReposCache,
+        loaded: Option<&CollectionReposCache>,
+        root_dir: &Path,
+    ) {
+        for entry in latest.repositories() {
+            let is_added = loaded.map_or(true, |loaded| loaded.get(entry.path()).is_none());
+            if is_added && self.get(entry.path()).is_none() && root_dir.join(entry.path()).exists()
+            {
+                log::debug!(
+                    "Keeping {:?} added during the refresh",
+                    root_dir.join(entry.path())
+                );
+                self.insert(entry.clone());
+            }
+        }
+    }
+}
+
+/// Cheap fingerprint of a collection directory.
+///
+/// This consists of the latest modification time and the total number of the
+/// entries of the directories above the repositories (for example, the
+/// collection directory, `host` directories, and `host/owner` directories for
+/// `host/owner/repo` layout). Directories of the repositories are not scanned,
+/// and neither are directories deeper than the deepest repository.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct CollectionFingerprint {
+    /// Latest modification time (seconds since the UNIX epoch).
+    mtime_secs: u64,
+    /// Latest modification time (subsecond nanoseconds).
+    mtime_nanos: u32,
+    /// Total number of entries in the scanned directories.
+    entries: u64,
+    /// Number of the scanned directories.
+    // Fingerprints recorded by old versions do not have this, and never match.
+    #[serde(default)]
+    dirs: u64,
+}
+
+impl CollectionFingerprint {
+    /// Computes the fingerprint of the collection directory with the given
+    /// repositories.
+    pub fn of_collection(root_dir: &Path, repos: &CollectionReposCache) -> io::Result<Self> {
+        let repo_dirs = repos
+            .repositories()
+            .map(|entry| repo_dir(entry.path()))
+            .collect::<HashSet<_>>();
+        // Directories at the depth of the deepest repository are not scanned.
+        let depth = repo_dirs
+            .iter()
+            .map(|dir| dir.components().count())
+            .max()
+            .unwrap_or(1);
+
+        let mut fingerprint = Self {
+            mtime_secs: 0,
+            mtime_nanos: 0,
+            entries: 0,
+            dirs: 0,
+        };
+        fingerprint.scan(root_dir, Path::new(""), depth, &repo_dirs)?;
+
+        Ok(fingerprint)
+    }
+
+    /// Adds the directory and its subdirectories to the fingerprint.
+    ///
+    /// Subdirectories are scanned down to `depth` levels, except for the
+    /// repository directories.
+    fn scan(
+        &mut self,
+        root_dir: &Path,
+        relpath: &Path,
+        depth: usize,
+        repo_dirs: &HashSet<&Path>,
+    ) -> io::Result<()> {
+        let dir = root_dir.join(relpath);
+        let mtime = fs::metadata(&dir)?
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if mtime > self.mtime() {
+            self.mtime_secs = mtime.as_secs();
+            self.mtime_nanos = mtime.subsec_nanos();
+        }
+        self.dirs += 1;
+
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            self.entries += 1;
+            let child = relpath.join(entry.file_name());
+            // Symlinks are not followed, as the repository discovery does.
+            if depth > 1 && entry.file_type()?.is_dir() && !repo_dirs.contains(child.as_path()) {
+                self.scan(root_dir, &child, depth - 1, repo_dirs)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the latest modification time as the duration since the UNIX epoch.
+    #[inline]
+    #[must_use]
+    fn mtime(&self) -> Duration {
+        Duration::new(self.mtime_secs, self.mtime_nanos)
+    }
+
+    /// Returns true if a scanned directory might have been modified at or
+    /// after the given time.
+    ///
+    /// This has a margin of a few seconds, since modification times can be
+    /// coarse on some file systems.
+    #[must_use]
+    pub fn is_modified_since(&self, time: SystemTime) -> bool {
+        const MARGIN: Duration = Duration::from_secs(2);
+        UNIX_EPOCH + self.mtime() + MARGIN >= time
+    }
+}
+
+/// Returns the directory of the repository at the given path.
+///
+/// For example, `foo` is returned for `foo/.git`, and `bar.git` is returned
+/// for `bar.git` (a bare repository).
+fn repo_dir(path: &Path) -> &Path {
+    const METADATA_DIRS: &[&str] = &[".git", ".hg", ".jj", ".pijul", "_darcs"];
+    let is_metadata_dir = path
+        .file_name()
+        .map_or(false, |name| METADATA_DIRS.iter().any(|dir| name == *dir));
+    match path.parent() {
+        Some(parent) if is_metadata_dir => parent,
+        _ => path,
+    }
 }
 
 /// A sorted iterator of repository cache entries.
@@ -166,7 +592,26 @@ pub struct RepoCacheEntry {
     /// Path.
     ///
     /// For git, `.git` directory or `*.git` directory.
+    #[serde(with = "path_serde")]
     path: PathBuf,
+    /// URI the repository was cloned from by magro.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source_uri: Option<String>,
+    /// How the repository was added.
+    ///
+    /// See [`RepoCacheEntry::provenance`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    provenance: Option<Provenance>,
+    /// Metadata.
+    ///
+    /// This should be placed after non-table fields to be serialized as TOML.
+    #[serde(default, skip_serializing_if = "RepoMetadata::is_empty")]
+    metadata: RepoMetadata,
+    /// Linked worktrees created by magro.
+    ///
+    /// This should be placed after non-table fields to be serialized as TOML.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    worktrees: Vec<WorktreeCacheEntry>,
 }
 
 impl RepoCacheEntry {
@@ -177,6 +622,10 @@ impl RepoCacheEntry {
         Self {
             vcs,
             path: path.into(),
+            metadata: Default::default(),
+            source_uri: None,
+            provenance: None,
+            worktrees: Vec::new(),
         }
     }
 
@@ -194,6 +643,79 @@ impl RepoCacheEntry {
         &self.path
     }
 
+    /// Returns the metadata.
+    #[inline]
+    #[must_use]
+    pub fn metadata(&self) -> &RepoMetadata {
+        &self.metadata
+    }
+
+    /// Returns the URI the repository was cloned from by magro.
+    ///
+    /// This is `None` for repositories only discovered by refresh.
+    #[inline]
+    #[must_use]
+    pub fn source_uri(&self) -> Option<&str> {
+        self.source_uri.as_deref()
+    }
+
+    /// Sets the URI the repository was cloned from.
+    #[inline]
+    pub fn set_source_uri(&mut self, uri: Option<String>) {
+        self.source_uri = uri;
+    }
+
+    /// Returns how the repository was added.
+    ///
+    /// If not recorded (for example, in caches written by older versions),
+    /// repositories with source URIs are considered cloned.
+    #[inline]
+    #[must_use]
+    pub fn provenance(&self) -> Provenance {
+        self.provenance.unwrap_or(if self.source_uri.is_some() {
+            Provenance::Cloned
+        } else {
+            Provenance::Discovered
+        })
+    }
+
+    /// Sets how the repository was added.
+    #[inline]
+    pub fn set_provenance(&mut self, provenance: Provenance) {
+        self.provenance = Some(provenance);
+    }
+
+    /// Returns the linked worktrees created by magro.
+    #[inline]
+    #[must_use]
+    pub fn worktrees(&self) -> &[WorktreeCacheEntry] {
+        &self.worktrees
+    }
+
+    /// Adds the linked worktree.
+    ///
+    /// The existing entry with the same path is replaced.
+    pub fn add_worktree(&mut self, worktree: WorktreeCacheEntry) {
+        self.remove_worktree(&worktree.path);
+        self.worktrees.push(worktree);
+    }
+
+    /// Removes the linked worktree at the path.
+    ///
+    /// Returns true if the worktree was registered.
+    pub fn remove_worktree(&mut self, path: &Path) -> bool {
+        let len = self.worktrees.len();
+        self.worktrees.retain(|worktree| worktree.path != path);
+        self.worktrees.len() != len
+    }
+
+    /// Returns a mutable reference to the metadata.
+    #[inline]
+    #[must_use]
+    pub fn metadata_mut(&mut self) -> &mut RepoMetadata {
+        &mut self.metadata
+    }
+
     /// Returns the owned repository path.
     #[inline]
     #[must_use]
@@ -201,6 +723,23 @@ impl RepoCacheEntry {
         self.path
     }
 
+    /// Returns a copy of the entry with the path mapped by the given function.
+    #[inline]
+    #[must_use]
+    pub fn map_ref_path<F>(&self, f: F) -> Self
+    where
+        for<'a> F: FnOnce(&'a Path) -> PathBuf,
+    {
+        Self {
+            vcs: self.vcs,
+            path: f(&self.path),
+            metadata: self.metadata.clone(),
+            source_uri: self.source_uri.clone(),
+            provenance: self.provenance,
+            worktrees: self.worktrees.clone(),
+        }
+    }
+
     /// Applies the given function to the path.
     #[inline]
     pub fn try_map_ref_path<F, E>(&self, f: F) -> Result<Self, E>
@@ -211,16 +750,308 @@ impl RepoCacheEntry {
         Ok(Self {
             vcs: self.vcs,
             path,
+            metadata: self.metadata.clone(),
+            source_uri: self.source_uri.clone(),
+            provenance: self.provenance,
+            worktrees: self.worktrees.clone(),
         })
     }
 }
 
+/// A cache entry for a linked worktree created by magro.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorktreeCacheEntry {
+    /// Branch the worktree was created for.
+    branch: String,
+    /// Absolute path of the working directory.
+    #[serde(with = "path_serde")]
+    path: PathBuf,
+}
+
+impl WorktreeCacheEntry {
+    /// Creates a new `WorktreeCacheEntry`.
+    #[inline]
+    #[must_use]
+    pub fn new(branch: String, path: PathBuf) -> Self {
+        Self { branch, path }
+    }
+
+    /// Returns the branch the worktree was created for.
+    #[inline]
+    #[must_use]
+    pub fn branch(&self) -> &str {
+        &self.branch
+    }
+
+    /// Returns the absolute path of the working directory.
+    #[inline]
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// How a repository was added to the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum Provenance {
+    /// Cloned (or created) by magro.
+    Cloned,
+    /// Found in the collection directory by refresh.
+    Discovered,
+}
+
+impl Provenance {
+    /// Returns the name in lower case.
+    #[inline]
+    #[must_use]
+    pub fn name_lower(&self) -> &'static str {
+        match self {
+            Self::Cloned => "cloned",
+            Self::Discovered => "discovered",
+        }
+    }
+}
+
+impl fmt::Display for Provenance {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name_lower())
+    }
+}
+
+impl str::FromStr for Provenance {
+    type Err = ProvenanceParseError;
+
+    /// Parses the name in lower case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cloned" => Ok(Self::Cloned),
+            "discovered" => Ok(Self::Discovered),
+            _ => Err(ProvenanceParseError(s.to_owned())),
+        }
+    }
+}
+
+/// Provenance parse error.
+#[derive(Debug, Clone, PartialEq, Eq, ThisError)]
+#[error("Unknown provenance {0:?} (expected `cloned` or `discovered`)")]
+pub struct ProvenanceParseError(String);
+
 impl From<RepoEntry> for RepoCacheEntry {
     #[inline]
     fn from(v: RepoEntry) -> Self {
         let vcs = v.vcs();
         let path = v.into_path();
 
-        Self { vcs, path }
+        let mut entry = Self::new(vcs, path);
+        entry.set_provenance(Provenance::Discovered);
+        entry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    #[test]
+    fn write_toml_roundtrip() {
+        let mut cache = Cache::default();
+        let mut repos = CollectionReposCache::default();
+        repos.set_fingerprint(Some(CollectionFingerprint {
+            mtime_secs: 1,
+            mtime_nanos: 2,
+            entries: 3,
+            dirs: 5,
+        }));
+        repos.set_refreshed_at(Some(4));
+        let mut with_worktree = RepoCacheEntry::new(Vcs::Git, "bar.git");
+        with_worktree.add_worktree(WorktreeCacheEntry::new(
+            "feat".to_owned(),
+            PathBuf::from("/worktrees/bar/feat"),
+        ));
+        repos.extend(vec![
+            RepoCacheEntry::new(Vcs::Git, "foo/.git"),
+            with_worktree,
+        ]);
+        cache.cache_collection_repos(CollectionName::try_from("a-b").unwrap(), repos);
+        cache.cache_collection_repos(
+            CollectionName::try_from("empty").unwrap(),
+            Default::default(),
+        );
+
+        let mut written = Vec::new();
+        cache.write_toml(&mut written).unwrap();
+        let written: toml::Value = toml::from_slice(&written).unwrap();
+        assert_eq!(written, toml::Value::try_from(&cache).unwrap());
+        assert_eq!(
+            written["collections"]["a-b"]["refreshed-at"].as_integer(),
+            Some(4)
+        );
+    }
+
+    #[test]
+    fn inherit_provenance() {
+        let mut cloned = RepoCacheEntry::new(Vcs::Git, "cloned/.git");
+        cloned.set_provenance(Provenance::Cloned);
+        let mut legacy = RepoCacheEntry::new(Vcs::Git, "legacy/.git");
+        legacy.set_source_uri(Some("https://example.com/legacy.git".to_owned()));
+        let mut old = CollectionReposCache::default();
+        old.extend(vec![cloned, legacy]);
+
+        let mut new = CollectionReposCache::default();
+        new.extend(
+            ["cloned/.git", "legacy/.git", "new/.git"]
+                .iter()
+                .map(|path| {
+                    let mut entry = RepoCacheEntry::new(Vcs::Git, *path);
+                    entry.set_provenance(Provenance::Discovered);
+                    entry
+                }),
+        );
+        new.inherit_from(&old);
+        let provenance = |path: &str| new.get(Path::new(path)).unwrap().provenance();
+        assert_eq!(provenance("cloned/.git"), Provenance::Cloned);
+        assert_eq!(provenance("legacy/.git"), Provenance::Cloned);
+        assert_eq!(provenance("new/.git"), Provenance::Discovered);
+    }
+
+    #[test]
+    fn concurrent_updates() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("cache.toml");
+        let name = CollectionName::try_from("src").unwrap();
+        Cache::update_path(&path, |cache| {
+            let mut repos = CollectionReposCache::default();
+            repos.insert(RepoCacheEntry::new(Vcs::Git, "old/.git"));
+            cache.cache_collection_repos(name.clone(), repos);
+        })
+        .unwrap();
+
+        // `refresh` loads the cache and takes a long time.
+        let refresh_loaded = Cache::from_path(&path).unwrap();
+        // Meanwhile, `clone` loads the cache and finishes.
+        let clone_loaded = Cache::from_path(&path).unwrap();
+        assert!(clone_loaded.collection_repos(&name).is_some());
+        Cache::update_path(&path, |cache| {
+            let repos = cache.collection_repos_mut(&name).unwrap();
+            let mut entry = RepoCacheEntry::new(Vcs::Git, "cloned/.git");
+            entry.set_provenance(Provenance::Cloned);
+            repos.insert(entry);
+        })
+        .unwrap();
+        // Then `refresh` finishes, with the stale cache loaded.
+        assert!(refresh_loaded
+            .collection_repos(&name)
+            .unwrap()
+            .get(Path::new("cloned/.git"))
+            .is_none());
+        let (updated, ()) = Cache::update_path(&path, |cache| {
+            cache
+                .collection_repos_mut(&name)
+                .unwrap()
+                .set_refreshed_at(Some(42));
+        })
+        .unwrap();
+
+        let saved = Cache::from_path(&path).unwrap();
+        for cache in &[updated, saved] {
+            let repos = cache.collection_repos(&name).unwrap();
+            assert_eq!(repos.refreshed_at(), Some(42));
+            assert!(repos.get(Path::new("old/.git")).is_some());
+            assert_eq!(
+                repos.get(Path::new("cloned/.git")).unwrap().provenance(),
+                Provenance::Cloned
+            );
+        }
+    }
+
+    #[test]
+    fn concurrent_refresh_and_clone() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("cache.toml");
+        let root_dir = tempdir.path().join("src");
+        let name = CollectionName::try_from("src").unwrap();
+        for repo in &["old/.git", "removed/.git"] {
+            fs::create_dir_all(root_dir.join(repo)).unwrap();
+        }
+        Cache::update_path(&path, |cache| {
+            let mut repos = CollectionReposCache::default();
+            repos.insert(RepoCacheEntry::new(Vcs::Git, "old/.git"));
+            repos.insert(RepoCacheEntry::new(Vcs::Git, "removed/.git"));
+            cache.cache_collection_repos(name.clone(), repos);
+        })
+        .unwrap();
+
+        // `refresh` loads the cache and starts discovery.
+        let loaded = Cache::from_path(&path)
+            .unwrap()
+            .collection_repos(&name)
+            .cloned();
+        fs::remove_dir_all(root_dir.join("removed")).unwrap();
+        let mut discovered = CollectionReposCache::default();
+        discovered.insert(RepoCacheEntry::new(Vcs::Git, "old/.git"));
+        // Meanwhile, `clone` adds a repository which discovery has missed.
+        fs::create_dir_all(root_dir.join("cloned/.git")).unwrap();
+        Cache::update_path(&path, |cache| {
+            let mut entry = RepoCacheEntry::new(Vcs::Git, "cloned/.git");
+            entry.set_provenance(Provenance::Cloned);
+            cache.collection_repos_mut(&name).unwrap().insert(entry);
+        })
+        .unwrap();
+
+        // Then `refresh` merges the stale discovery result into the latest cache.
+        Cache::update_path(&path, |cache| {
+            if let Some(latest) = cache.collection_repos(&name) {
+                discovered.inherit_from(latest);
+                discovered.keep_added_from(latest, loaded.as_ref(), &root_dir);
+            }
+            cache.cache_collection_repos(name.clone(), discovered);
+        })
+        .unwrap();
+
+        let saved = Cache::from_path(&path).unwrap();
+        let repos = saved.collection_repos(&name).unwrap();
+        assert!(repos.get(Path::new("old/.git")).is_some());
+        assert!(repos.get(Path::new("removed/.git")).is_none());
+        assert_eq!(
+            repos.get(Path::new("cloned/.git")).unwrap().provenance(),
+            Provenance::Cloned
+        );
+    }
+
+    #[test]
+    fn fingerprint_detects_changes_in_layout_dirs() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root_dir = tempdir.path();
+        fs::create_dir_all(root_dir.join("host/owner/repo/.git")).unwrap();
+        fs::create_dir_all(root_dir.join("bare.git/refs")).unwrap();
+        let mut repos = CollectionReposCache::default();
+        repos.extend(vec![
+            RepoCacheEntry::new(Vcs::Git, "host/owner/repo/.git"),
+            RepoCacheEntry::new(Vcs::Git, "bare.git"),
+        ]);
+        let fingerprint = CollectionFingerprint::of_collection(root_dir, &repos).unwrap();
+        // The root, `host`, and `host/owner` are scanned.
+        assert_eq!(fingerprint.dirs, 3);
+        assert_eq!(fingerprint.entries, 4);
+
+        // Changes inside repositories are ignored.
+        fs::create_dir(root_dir.join("host/owner/repo/src")).unwrap();
+        fs::create_dir(root_dir.join("bare.git/objects")).unwrap();
+        assert_eq!(
+            CollectionFingerprint::of_collection(root_dir, &repos).unwrap(),
+            fingerprint
+        );
+
+        // New repositories next to the known ones are detected.
+        fs::create_dir_all(root_dir.join("host/owner/new/.git")).unwrap();
+        assert_ne!(
+            CollectionFingerprint::of_collection(root_dir, &repos).unwrap(),
+            fingerprint
+        );
     }
 }
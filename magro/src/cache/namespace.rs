@@ -0,0 +1,183 @@
+//! Cache namespace.
+
+use std::{convert::TryFrom, fmt, ops, str};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+/// Cache namespace error.
+#[derive(Debug, Clone, ThisError)]
+#[error("Invalid cache namespace: {message}")]
+pub struct CacheNamespaceError {
+    /// Message.
+    message: String,
+}
+
+impl CacheNamespaceError {
+    /// Creates a new error with the given message.
+    #[inline]
+    #[must_use]
+    fn with_message(s: impl fmt::Display) -> Self {
+        Self {
+            message: s.to_string(),
+        }
+    }
+}
+
+/// Cache namespace.
+///
+/// A namespace has its own cache file (`cache-<namespace>.toml`) in the cache
+/// directory of the profile, so that independent caches can be maintained
+/// over the same collections (for example, by CI pipelines and interactive
+/// use).
+///
+/// Namespace should satisfy all restrictions below:
+///
+/// * Should not be empty.
+/// * Should consist of ASCII alphanumeric, ASCII hyphen, ASCII underscore,
+///   or ASCII period.
+/// * Should not start with an ASCII hyphen or an ASCII period.
+///
+/// # Examples
+///
+/// ```
+/// use std::convert::TryFrom;
+/// # use magro::cache::CacheNamespace;
+///
+/// assert_eq!(CacheNamespace::try_from("ci").unwrap(), "ci");
+/// assert_eq!(
+///     CacheNamespace::try_from("ci").unwrap().file_name(),
+///     "cache-ci.toml"
+/// );
+///
+/// assert!(CacheNamespace::try_from("").is_err());
+/// assert!(CacheNamespace::try_from(".ci").is_err());
+/// assert!(CacheNamespace::try_from("../ci").is_err());
+/// ```
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String")]
+#[serde(into = "String")]
+pub struct CacheNamespace(String);
+
+impl CacheNamespace {
+    /// Returns the string slice for the namespace.
+    #[inline]
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the name of the cache file for the namespace.
+    #[must_use]
+    pub fn file_name(&self) -> String {
+        format!("cache-{}.toml", self.0)
+    }
+
+    /// Validates the given string as a cache namespace.
+    fn validate(s: &str) -> Result<(), CacheNamespaceError> {
+        if s.is_empty() {
+            return Err(CacheNamespaceError::with_message("Empty namespace"));
+        }
+
+        match s.as_bytes()[0] {
+            b'-' => {
+                return Err(CacheNamespaceError::with_message(
+                    "Namespace starts with '-'",
+                ))
+            }
+            b'.' => {
+                return Err(CacheNamespaceError::with_message(
+                    "Namespace starts with '.'",
+                ))
+            }
+            _ => {}
+        }
+
+        if let Some(c) = s
+            .chars()
+            .find(|&c| !(c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.'))
+        {
+            return Err(CacheNamespaceError::with_message(format!(
+                "Invalid character {:?}",
+                c
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl PartialEq<&'_ str> for CacheNamespace {
+    #[inline]
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<str> for CacheNamespace {
+    #[inline]
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl fmt::Debug for CacheNamespace {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for CacheNamespace {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl ops::Deref for CacheNamespace {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl TryFrom<&'_ str> for CacheNamespace {
+    type Error = CacheNamespaceError;
+
+    #[inline]
+    fn try_from(s: &'_ str) -> Result<Self, Self::Error> {
+        Self::validate(s)?;
+
+        Ok(Self(s.into()))
+    }
+}
+
+impl TryFrom<String> for CacheNamespace {
+    type Error = CacheNamespaceError;
+
+    #[inline]
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::validate(&s)?;
+
+        Ok(Self(s))
+    }
+}
+
+impl str::FromStr for CacheNamespace {
+    type Err = CacheNamespaceError;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::try_from(s)
+    }
+}
+
+impl From<CacheNamespace> for String {
+    #[inline]
+    fn from(s: CacheNamespace) -> Self {
+        s.0
+    }
+}
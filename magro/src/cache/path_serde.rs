@@ -0,0 +1,214 @@
+//! Lossless serialization of paths in the cache and other data files.
+//!
+//! Paths valid as UTF-8 are serialized as plain strings.
+//! Other paths are serialized in platform-specific forms:
+//!
+//! * Unix: `{ unix = "..." }`, raw bytes with `%`, control characters, and
+//!   non-ASCII bytes percent-encoded.
+//! * Windows: `{ windows = [...] }`, UTF-16 code units (possibly including
+//!   unpaired surrogates).
+//!
+//! Paths in platform-specific forms cannot be loaded on other platforms.
+
+use std::path::{Path, PathBuf};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Serialized representation of a path.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum PathRepr<'a> {
+    /// UTF-8 path.
+    Utf8(std::borrow::Cow<'a, str>),
+    /// Percent-encoded bytes of a Unix path.
+    Unix {
+        /// Percent-encoded bytes.
+        unix: String,
+    },
+    /// UTF-16 code units of a Windows path.
+    Windows {
+        /// UTF-16 code units.
+        windows: Vec<u16>,
+    },
+}
+
+/// Serializes the path.
+pub(crate) fn serialize<S>(path: &Path, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let repr = match path.to_str() {
+        Some(s) => PathRepr::Utf8(s.into()),
+        None => to_platform_repr(path),
+    };
+    repr.serialize(serializer)
+}
+
+/// Deserializes the path.
+pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<PathBuf, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match PathRepr::deserialize(deserializer)? {
+        PathRepr::Utf8(s) => Ok(PathBuf::from(s.into_owned())),
+        PathRepr::Unix { unix } => from_unix_repr(&unix),
+        PathRepr::Windows { windows } => from_windows_repr(&windows),
+    }
+}
+
+/// Lossless serialization of optional paths.
+///
+/// Use this with `#[serde(default, skip_serializing_if = "Option::is_none")]`,
+/// since `None` cannot be represented in TOML.
+pub(crate) mod option {
+    use std::path::PathBuf;
+
+    use serde::{Deserializer, Serializer};
+
+    /// Serializes the optional path.
+    pub(crate) fn serialize<S>(path: &Option<PathBuf>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match path {
+            Some(path) => super::serialize(path, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    /// Deserializes the optional path.
+    pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<Option<PathBuf>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        super::deserialize(deserializer).map(Some)
+    }
+}
+
+/// Returns the Unix-specific representation of the path.
+#[cfg(unix)]
+fn to_platform_repr(path: &Path) -> PathRepr<'static> {
+    use std::{fmt::Write, os::unix::ffi::OsStrExt};
+
+    let mut unix = String::new();
+    for &b in path.as_os_str().as_bytes() {
+        if b == b'%' || !(0x20..0x7f).contains(&b) {
+            write!(unix, "%{:02X}", b).expect("Writing to a string never fails");
+        } else {
+            unix.push(char::from(b));
+        }
+    }
+    PathRepr::Unix { unix }
+}
+
+/// Returns the Windows-specific representation of the path.
+#[cfg(windows)]
+fn to_platform_repr(path: &Path) -> PathRepr<'static> {
+    use std::os::windows::ffi::OsStrExt;
+
+    PathRepr::Windows {
+        windows: path.as_os_str().encode_wide().collect(),
+    }
+}
+
+/// Returns the lossy representation of the path.
+#[cfg(not(any(unix, windows)))]
+fn to_platform_repr(path: &Path) -> PathRepr<'static> {
+    PathRepr::Utf8(path.to_string_lossy().into_owned().into())
+}
+
+/// Decodes the Unix-specific representation.
+#[cfg(unix)]
+fn from_unix_repr<E: de::Error>(repr: &str) -> Result<PathBuf, E> {
+    use std::{ffi::OsString, os::unix::ffi::OsStringExt};
+
+    let mut bytes = Vec::with_capacity(repr.len());
+    let mut rest = repr.as_bytes();
+    while let Some((&b, next)) = rest.split_first() {
+        if b == b'%' {
+            let byte = next
+                .get(..2)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| E::custom(format!("invalid percent-encoded path {:?}", repr)))?;
+            bytes.push(byte);
+            rest = &next[2..];
+        } else {
+            bytes.push(b);
+            rest = next;
+        }
+    }
+    Ok(OsString::from_vec(bytes).into())
+}
+
+/// Decodes the Unix-specific representation.
+#[cfg(not(unix))]
+fn from_unix_repr<E: de::Error>(repr: &str) -> Result<PathBuf, E> {
+    Err(E::custom(format!(
+        "Unix-specific path {:?} is not supported on this platform",
+        repr
+    )))
+}
+
+/// Decodes the Windows-specific representation.
+#[cfg(windows)]
+fn from_windows_repr<E: de::Error>(repr: &[u16]) -> Result<PathBuf, E> {
+    use std::{ffi::OsString, os::windows::ffi::OsStringExt};
+
+    Ok(OsString::from_wide(repr).into())
+}
+
+/// Decodes the Windows-specific representation.
+#[cfg(not(windows))]
+fn from_windows_repr<E: de::Error>(_repr: &[u16]) -> Result<PathBuf, E> {
+    Err(E::custom(
+        "Windows-specific path is not supported on this platform",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A struct containing a path.
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Entry {
+        /// Path.
+        #[serde(with = "super")]
+        path: PathBuf,
+    }
+
+    /// Serializes and deserializes the path, and returns the serialized string.
+    fn roundtrip(path: PathBuf) -> String {
+        let entry = Entry { path };
+        let s = toml::to_string(&entry).unwrap();
+        assert_eq!(toml::from_str::<Entry>(&s).unwrap(), entry);
+        s
+    }
+
+    #[test]
+    fn utf8_paths_are_plain_strings() {
+        assert_eq!(
+            roundtrip("foo/bar/.git".into()),
+            "path = \"foo/bar/.git\"\n"
+        );
+        roundtrip("100%/sp ace/\"quote\"/\n/\u{3042}.git".into());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_unix_paths() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let path = Path::new(OsStr::from_bytes(b"foo/\xff%\x01 bar.git"));
+        let s = roundtrip(path.to_owned());
+        assert!(s.contains("foo/%FF%25%01 bar.git"), "{}", s);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn invalid_percent_encoding() {
+        assert!(toml::from_str::<Entry>("[path]\nunix = \"%F\"").is_err());
+        assert!(toml::from_str::<Entry>("[path]\nunix = \"%\u{3042}\"").is_err());
+    }
+}
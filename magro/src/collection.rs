@@ -6,20 +6,23 @@
 
 use std::{
     borrow::Cow,
+    collections::BTreeMap,
+    fmt,
     path::{Path, PathBuf},
 };
 
 use serde::{Deserialize, Serialize};
 
-use crate::Context;
+use crate::{glob::Glob, remote::Protocol, Context};
 
 pub use self::{
-    collections::Collections,
+    collections::{Collections, NameConflictError, Overlap},
     name::{CollectionName, CollectionNameError},
 };
 
 pub mod collections;
 mod name;
+mod path_template;
 
 /// Repositories collection.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,7 +36,23 @@ pub struct Collection {
     /// (Base directory is currently home directory.)
     ///
     /// If the path is absolute, use it as is.
+    ///
+    /// Variables such as `{hostname}` and `{year}` are expanded when the
+    /// absolute path is resolved.
     path: PathBuf,
+    /// Preferred protocol of clone URLs.
+    #[serde(
+        rename = "clone-protocol",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    clone_protocol: Option<Protocol>,
+    /// Patterns of repository paths to exclude from the discovery results.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    exclude: Vec<Glob>,
+    /// Environment variables for commands run on repositories in the collection.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    env: BTreeMap<String, String>,
 }
 
 impl Collection {
@@ -41,7 +60,13 @@ impl Collection {
     #[inline]
     #[must_use]
     pub fn new(name: CollectionName, path: PathBuf) -> Self {
-        Self { name, path }
+        Self {
+            name,
+            path,
+            clone_protocol: None,
+            exclude: Vec::new(),
+            env: BTreeMap::new(),
+        }
     }
 
     /// Returns the collection name.
@@ -57,15 +82,79 @@ impl Collection {
         self.name = name;
     }
 
-    /// Returns the absolute path of the collection.
+    /// Returns the path of the collection as configured.
+    ///
+    /// If the path is relative, it is relative to the home directory.
+    #[inline]
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the preferred protocol of clone URLs.
+    #[inline]
+    #[must_use]
+    pub fn clone_protocol(&self) -> Option<Protocol> {
+        self.clone_protocol
+    }
+
+    /// Sets the preferred protocol of clone URLs.
+    #[inline]
+    pub fn set_clone_protocol(&mut self, protocol: Option<Protocol>) {
+        self.clone_protocol = protocol;
+    }
+
+    /// Returns the patterns of repository paths to exclude from the discovery results.
+    #[inline]
+    #[must_use]
+    pub fn exclude(&self) -> &[Glob] {
+        &self.exclude
+    }
+
+    /// Returns true if the repository at the given path should be excluded.
+    ///
+    /// The path is relative to the collection directory.
+    /// If the path ends with `.git`, the working directory (i.e. the parent
+    /// directory) is matched instead.
+    /// See [`Glob::matches_path`] for how the patterns are matched.
+    #[must_use]
+    pub fn is_excluded(&self, relpath: &Path) -> bool {
+        if self.exclude.is_empty() {
+            return false;
+        }
+        let path = match relpath.file_name() {
+            Some(name) if name == ".git" => relpath.parent().unwrap_or(relpath),
+            _ => relpath,
+        };
+        self.exclude.iter().any(|glob| glob.matches_path(path))
+    }
+
+    /// Returns the environment variables for commands run on repositories in
+    /// the collection.
+    ///
+    /// They are set for hooks and external VCS commands.
     #[inline]
     #[must_use]
+    pub fn env(&self) -> &BTreeMap<String, String> {
+        &self.env
+    }
+
+    /// Returns the absolute path of the collection.
+    ///
+    /// Variables in the path are expanded: `{hostname}` (the host name
+    /// without the domain part), `{year}`, and `{month}` (the current year and
+    /// month in UTC).
+    #[must_use]
     pub fn abspath(&self, context: &Context) -> Cow<'_, Path> {
-        if self.path.is_absolute() {
-            return Cow::Borrowed(&self.path);
+        let path = match path_template::expand(&self.path) {
+            Some(expanded) => Cow::Owned(expanded),
+            None => Cow::Borrowed(&*self.path),
+        };
+        if path.is_absolute() {
+            return path;
         }
         let base = context.home_dir();
-        Cow::Owned(base.join(&self.path))
+        Cow::Owned(base.join(path))
     }
 
     /// Sets the given path to the collection.
@@ -73,4 +162,63 @@ impl Collection {
     pub fn set_path(&mut self, path: impl Into<PathBuf>) {
         self.path = path.into();
     }
+
+    /// Returns the relation of the directories of this collection and the other.
+    ///
+    /// Returns `None` if the directories do not overlap.
+    /// Existing directories are compared after resolving symbolic links.
+    #[must_use]
+    pub fn dir_relation(&self, context: &Context, other: &Collection) -> Option<DirRelation> {
+        let this = resolve_dir(&self.abspath(context));
+        let other = resolve_dir(&other.abspath(context));
+        if this == other {
+            Some(DirRelation::Same)
+        } else if other.starts_with(&this) {
+            Some(DirRelation::Contains)
+        } else if this.starts_with(&other) {
+            Some(DirRelation::ContainedBy)
+        } else {
+            None
+        }
+    }
+}
+
+/// Relation of two overlapping collection directories.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DirRelation {
+    /// The directories are the same.
+    Same,
+    /// The directory contains the other.
+    Contains,
+    /// The directory is contained by the other.
+    ContainedBy,
+}
+
+impl DirRelation {
+    /// Returns the relation with the roles of the directories swapped.
+    #[inline]
+    #[must_use]
+    pub fn reverse(self) -> Self {
+        match self {
+            Self::Same => Self::Same,
+            Self::Contains => Self::ContainedBy,
+            Self::ContainedBy => Self::Contains,
+        }
+    }
+}
+
+impl fmt::Display for DirRelation {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Same => "has the same directory as",
+            Self::Contains => "contains",
+            Self::ContainedBy => "is inside",
+        })
+    }
+}
+
+/// Returns the canonicalized path if possible, or the given path as is.
+fn resolve_dir(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_owned())
 }
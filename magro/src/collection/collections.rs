@@ -3,8 +3,41 @@
 use std::{collections::BTreeMap, fmt, iter};
 
 use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
 
-use crate::collection::Collection;
+use crate::{
+    collection::{Collection, CollectionName, DirRelation},
+    glob::Glob,
+    Context,
+};
+
+/// Error on adding a collection whose name conflicts with an existing one.
+///
+/// This only happens when loose name matching is enabled.
+#[derive(Debug, Clone, ThisError)]
+#[error("Collection name `{new}` conflicts with the existing collection `{existing}`")]
+pub struct NameConflictError {
+    /// Name of the collection being added.
+    new: CollectionName,
+    /// Name of the existing collection.
+    existing: CollectionName,
+}
+
+impl NameConflictError {
+    /// Returns the name of the collection being added.
+    #[inline]
+    #[must_use]
+    pub fn new_name(&self) -> &CollectionName {
+        &self.new
+    }
+
+    /// Returns the name of the existing collection.
+    #[inline]
+    #[must_use]
+    pub fn existing_name(&self) -> &CollectionName {
+        &self.existing
+    }
+}
 
 /// Set of collections.
 // Note that this is serialized / deserialized as an array, rather than a map.
@@ -14,34 +47,109 @@ pub struct Collections {
     // Use `String` as keys to make it easier to query by any string (even if
     // the query string is invalid name).
     collections: BTreeMap<String, Collection>,
+    /// Whether the names are matched case-insensitively, treating `-` and `_` as the same.
+    loose_names: bool,
 }
 
 impl Collections {
-    /// Returns the collection with the given name, if available.
+    /// Returns true if loose name matching is enabled.
     #[inline]
     #[must_use]
+    pub fn loose_names(&self) -> bool {
+        self.loose_names
+    }
+
+    /// Enables or disables loose name matching.
+    ///
+    /// When enabled, names are matched case-insensitively, and `-` and `_`
+    /// are treated as the same character.
+    /// Fails if the existing collections have conflicting names in that mode.
+    pub fn set_loose_names(&mut self, loose: bool) -> Result<(), NameConflictError> {
+        if loose {
+            let mut seen: BTreeMap<String, &CollectionName> = BTreeMap::new();
+            for collection in self.collections.values() {
+                let name = collection.name();
+                if let Some(existing) = seen.insert(normalize_name(name.as_str()), name) {
+                    return Err(NameConflictError {
+                        new: name.clone(),
+                        existing: existing.clone(),
+                    });
+                }
+            }
+        }
+        self.loose_names = loose;
+        Ok(())
+    }
+
+    /// Returns the key of the collection with the given name, if available.
+    fn key_of(&self, name: &str) -> Option<String> {
+        if self.collections.contains_key(name) {
+            return Some(name.to_owned());
+        }
+        if !self.loose_names {
+            return None;
+        }
+        let normalized = normalize_name(name);
+        self.collections
+            .keys()
+            .find(|key| normalize_name(key) == normalized)
+            .cloned()
+    }
+
+    /// Returns the collection with the given name, if available.
+    ///
+    /// If loose name matching is enabled and no collection has exactly the
+    /// same name, the collection with the loosely matching name is returned.
+    #[must_use]
     pub fn get(&self, name: &str) -> Option<&Collection> {
-        self.collections.get(name)
+        self.key_of(name).and_then(|key| self.collections.get(&key))
     }
 
     /// Returns the mutable reference to the collection with the given name, if available.
-    #[inline]
+    ///
+    /// Names are matched in the same way as [`get`][`Self::get`].
     #[must_use]
     pub fn get_mut(&mut self, name: &str) -> Option<&mut Collection> {
-        self.collections.get_mut(name)
+        let key = self.key_of(name)?;
+        self.collections.get_mut(&key)
     }
 
     /// Adds the given collection to this `Collections`, and returns the old entry if exists.
+    ///
+    /// Only the collection with exactly the same name is replaced.
+    /// If loose name matching is enabled, use [`try_insert`][`Self::try_insert`]
+    /// to detect conflicts with the existing names.
     #[inline]
     pub fn insert(&mut self, collection: Collection) -> Option<Collection> {
         let name = collection.name().as_str().to_owned();
         self.collections.insert(name, collection)
     }
 
+    /// Adds the given collection to this `Collections`, and returns the old entry if exists.
+    ///
+    /// Fails without modification if loose name matching is enabled and
+    /// another collection has a loosely matching but different name.
+    pub fn try_insert(
+        &mut self,
+        collection: Collection,
+    ) -> Result<Option<Collection>, NameConflictError> {
+        if let Some(existing) = self.get(collection.name()) {
+            if existing.name() != collection.name() {
+                return Err(NameConflictError {
+                    new: collection.name().clone(),
+                    existing: existing.name().clone(),
+                });
+            }
+        }
+        Ok(self.insert(collection))
+    }
+
     /// Removes a collection with the given name and returns it, if exists.
-    #[inline]
+    ///
+    /// Names are matched in the same way as [`get`][`Self::get`].
     pub fn remove(&mut self, name: &str) -> Option<Collection> {
-        self.collections.remove(name)
+        let key = self.key_of(name)?;
+        self.collections.remove(&key)
     }
 
     /// Returns the number of the collections.
@@ -64,6 +172,97 @@ impl Collections {
     pub fn iter(&self) -> Iter<'_> {
         self.into_iter()
     }
+
+    /// Returns the collections with names matching the glob pattern.
+    ///
+    /// If loose name matching is enabled, names are matched loosely.
+    pub fn matching<'a>(&'a self, pattern: &'a Glob) -> impl Iterator<Item = &'a Collection> + 'a {
+        let loose_pattern = if self.loose_names {
+            Glob::new(normalize_name(pattern.as_str())).ok()
+        } else {
+            None
+        };
+        self.iter().filter(move |collection| {
+            let name = collection.name().as_str();
+            match &loose_pattern {
+                Some(loose_pattern) => loose_pattern.matches(&normalize_name(name)),
+                None => pattern.matches(name),
+            }
+        })
+    }
+
+    /// Returns the collections whose directories overlap with the given collection.
+    ///
+    /// The collection with the same name as the given one is ignored.
+    /// Relations are of the given collection to the returned ones.
+    #[must_use]
+    pub fn overlapping_with<'a>(
+        &'a self,
+        context: &Context,
+        collection: &Collection,
+    ) -> Vec<(&'a Collection, DirRelation)> {
+        self.iter()
+            .filter(|other| other.name() != collection.name())
+            .filter_map(|other| {
+                collection
+                    .dir_relation(context, other)
+                    .map(|rel| (other, rel))
+            })
+            .collect()
+    }
+
+    /// Returns all pairs of collections whose directories overlap.
+    #[must_use]
+    pub fn overlaps(&self, context: &Context) -> Vec<Overlap<'_>> {
+        let collections = self.collections.values().collect::<Vec<_>>();
+        let mut overlaps = Vec::new();
+        for (i, first) in collections.iter().enumerate() {
+            for second in &collections[(i + 1)..] {
+                if let Some(relation) = first.dir_relation(context, second) {
+                    overlaps.push(Overlap {
+                        first,
+                        second,
+                        relation,
+                    });
+                }
+            }
+        }
+        overlaps
+    }
+}
+
+/// Overlap of directories of two collections.
+#[derive(Debug, Clone, Copy)]
+pub struct Overlap<'a> {
+    /// First collection.
+    first: &'a Collection,
+    /// Second collection.
+    second: &'a Collection,
+    /// Relation of the first collection to the second.
+    relation: DirRelation,
+}
+
+impl<'a> Overlap<'a> {
+    /// Returns the first collection.
+    #[inline]
+    #[must_use]
+    pub fn first(&self) -> &'a Collection {
+        self.first
+    }
+
+    /// Returns the second collection.
+    #[inline]
+    #[must_use]
+    pub fn second(&self) -> &'a Collection {
+        self.second
+    }
+
+    /// Returns the relation of the first collection to the second.
+    #[inline]
+    #[must_use]
+    pub fn relation(&self) -> DirRelation {
+        self.relation
+    }
 }
 
 impl<'a> IntoIterator for &'a Collections {
@@ -84,8 +283,7 @@ impl<'de> Deserialize<'de> for Collections {
         D: serde::Deserializer<'de>,
     {
         /// Visitor type for `Collections`.
-        #[derive(Default)]
-        struct CollectionsVisitor(BTreeMap<String, Collection>);
+        struct CollectionsVisitor;
 
         impl<'de> serde::de::Visitor<'de> for CollectionsVisitor {
             type Value = Collections;
@@ -113,11 +311,14 @@ impl<'de> Deserialize<'de> for Collections {
                     }
                 }
 
-                Ok(Collections { collections: map })
+                Ok(Collections {
+                    collections: map,
+                    loose_names: false,
+                })
             }
         }
 
-        deserializer.deserialize_seq(CollectionsVisitor::default())
+        deserializer.deserialize_seq(CollectionsVisitor)
     }
 }
 
@@ -136,6 +337,11 @@ impl Serialize for Collections {
     }
 }
 
+/// Returns the name normalized for loose matching.
+fn normalize_name(name: &str) -> String {
+    name.to_lowercase().replace('_', "-")
+}
+
 /// An iterator over the collections.
 #[derive(Debug, Clone)]
 pub struct Iter<'a> {
@@ -169,3 +375,54 @@ impl<'a> ExactSizeIterator for Iter<'a> {
 }
 
 impl<'a> iter::FusedIterator for Iter<'a> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    fn collection(name: &str) -> Collection {
+        Collection::new(CollectionName::try_from(name).unwrap(), name.into())
+    }
+
+    #[test]
+    fn loose_names() {
+        let mut collections = Collections::default();
+        collections.insert(collection("my_work"));
+        assert!(collections.get("My-Work").is_none());
+
+        collections.set_loose_names(true).unwrap();
+        assert_eq!(
+            collections.get("My-Work").map(|c| c.name().as_str()),
+            Some("my_work")
+        );
+        assert!(collections.try_insert(collection("MY-WORK")).is_err());
+        assert!(collections.try_insert(collection("my_work")).is_ok());
+        assert!(collections.try_insert(collection("other")).is_ok());
+        assert!(collections.remove("OTHER").is_some());
+
+        collections.set_loose_names(false).unwrap();
+        collections.insert(collection("My-Work"));
+        assert!(collections.set_loose_names(true).is_err());
+        assert!(!collections.loose_names());
+    }
+
+    #[test]
+    fn matching_names() {
+        let mut collections = Collections::default();
+        for name in &["work-foo", "Work_Bar", "other"] {
+            collections.insert(collection(name));
+        }
+        let pattern = Glob::new("work-*").unwrap();
+        let names = |collections: &Collections| {
+            collections
+                .matching(&pattern)
+                .map(|c| c.name().as_str().to_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&collections), ["work-foo"]);
+        collections.set_loose_names(true).unwrap();
+        assert_eq!(names(&collections), ["Work_Bar", "work-foo"]);
+    }
+}
@@ -65,6 +65,42 @@ impl CollectionName {
     pub fn as_str(&self) -> &str {
         self.as_ref()
     }
+
+    /// Creates a collection name from the given string, replacing unusable
+    /// characters.
+    ///
+    /// Runs of invalid characters are replaced with a hyphen, and leading and
+    /// trailing hyphens are removed.
+    /// Returns `None` if nothing remains.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use magro::collection::CollectionName;
+    /// assert_eq!(CollectionName::sanitized("src").unwrap(), "src");
+    /// assert_eq!(CollectionName::sanitized("My Projects").unwrap(), "My-Projects");
+    /// assert_eq!(CollectionName::sanitized(".dotfiles").unwrap(), "dotfiles");
+    /// assert!(CollectionName::sanitized("\u{03B1}").is_none());
+    /// ```
+    #[must_use]
+    pub fn sanitized(s: &str) -> Option<Self> {
+        let mut name = String::with_capacity(s.len());
+        for c in s.chars() {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                name.push(c);
+            } else if !name.is_empty() && !name.ends_with('-') {
+                name.push('-');
+            }
+        }
+        let len = name.trim_end_matches('-').len();
+        name.truncate(len);
+        if name.is_empty() {
+            return None;
+        }
+        debug_assert!(Self::validate(&name).is_ok(), "Should be valid: {:?}", name);
+
+        Some(Self(name))
+    }
 }
 
 impl CollectionName {
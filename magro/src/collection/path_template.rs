@@ -0,0 +1,127 @@
+//! Variables in collection paths.
+
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use once_cell::sync::OnceCell;
+
+use crate::{metadata::unix_now, template};
+
+/// Expands variables in the collection path.
+///
+/// Returns `None` if the path has no variables.
+/// Available variables are:
+///
+/// * `{hostname}`: the host name without the domain part.
+/// * `{year}`: the current year (in UTC).
+/// * `{month}`: the current month (in UTC) as two digits.
+///
+/// If the path cannot be expanded (for example, because of unknown
+/// variables), a warning is logged and the path is used as is.
+pub(crate) fn expand(path: &Path) -> Option<PathBuf> {
+    expand_with(path, hostname, unix_now())
+}
+
+/// Expands variables in the path with the given host name and the current time.
+fn expand_with(path: &Path, hostname: fn() -> &'static str, now: u64) -> Option<PathBuf> {
+    let path_str = path.to_str()?;
+    if !path_str.contains('{') {
+        return None;
+    }
+    let (year, month, _) = civil_from_days(now / SECS_PER_DAY);
+    let expanded = template::expand(path_str, |var| match var {
+        "hostname" => Some(Cow::Borrowed(hostname())),
+        "year" => Some(Cow::Owned(year.to_string())),
+        "month" => Some(Cow::Owned(format!("{:02}", month))),
+        _ => None,
+    });
+    match expanded {
+        Ok(v) => Some(PathBuf::from(v)),
+        Err(e) => {
+            log::warn!("Failed to expand the collection path {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
+/// Seconds per day.
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Returns the host name without the domain part.
+///
+/// Returns an empty string if the host name is unavailable.
+fn hostname() -> &'static str {
+    /// Host name.
+    static HOSTNAME: OnceCell<String> = OnceCell::new();
+
+    HOSTNAME.get_or_init(|| {
+        let hostname = load_hostname().unwrap_or_else(|| {
+            log::warn!("Failed to get the host name");
+            String::new()
+        });
+        let hostname = hostname.trim();
+        hostname.split('.').next().unwrap_or(hostname).to_owned()
+    })
+}
+
+/// Loads the host name.
+fn load_hostname() -> Option<String> {
+    #[cfg(target_os = "linux")]
+    {
+        if let Ok(v) = std::fs::read_to_string("/proc/sys/kernel/hostname") {
+            return Some(v);
+        }
+    }
+    let output = Command::new("hostname").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
+
+/// Returns the year, the month, and the day of the days since the UNIX epoch.
+// See <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19_722), (2023, 12, 31));
+        assert_eq!(civil_from_days(19_723), (2024, 1, 1));
+    }
+
+    #[test]
+    fn expand_paths() {
+        let hostname = || "myhost";
+        // 2024-03-01T00:00:00Z.
+        let now = 19_783 * SECS_PER_DAY;
+        let expand = |path: &str| expand_with(Path::new(path), hostname, now);
+        assert_eq!(
+            expand("scratch-{year}/{hostname}"),
+            Some(PathBuf::from("scratch-2024/myhost"))
+        );
+        assert_eq!(expand("{year}-{month}"), Some(PathBuf::from("2024-03")));
+        assert_eq!(expand("src"), None);
+        assert_eq!(expand("{unknown}"), None);
+    }
+}
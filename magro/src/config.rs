@@ -1,11 +1,25 @@
 //! Magro config.
 
-use std::{io, mem, path::Path};
+use std::{
+    fs, io, mem,
+    path::{Path, PathBuf},
+};
 
-pub use self::{collection::CollectionsConfig, load::LoadError, main::MainConfig};
-use crate::collection::{CollectionName, Collections};
+use thiserror::Error as ThisError;
+
+pub use self::{
+    collection::CollectionsConfig,
+    effective::{EffectiveConfig, EffectiveValue, ValueSource},
+    load::{LoadError, LoadErrorKind},
+    main::{
+        BackupConfig, DiscoveryConfig, HookConfig, HooksConfig, ListConfig, MainConfig,
+        RepoTemplateConfig, ScratchConfig, TrashConfig, WorktreeConfig,
+    },
+};
+use crate::collection::{Collection, CollectionName, Collections};
 
 mod collection;
+mod effective;
 mod load;
 mod main;
 
@@ -15,37 +29,97 @@ const DEFAULT_MAIN_CONFIG_RELPATH: &str = "config.toml";
 /// Default collections config file path relative to the config directory.
 const DEFAULT_COLLECTIONS_CONFIG_RELPATH: &str = "collections.toml";
 
+/// Machine-local main config overlay file path relative to the config directory.
+const LOCAL_MAIN_CONFIG_RELPATH: &str = "config.local.toml";
+
+/// Machine-local collections config overlay file path relative to the config directory.
+const LOCAL_COLLECTIONS_CONFIG_RELPATH: &str = "collections.local.toml";
+
+/// Error on resolving the target collection.
+#[derive(Debug, Clone, ThisError)]
+#[non_exhaustive]
+pub enum TargetCollectionError {
+    /// The specified collection does not exist.
+    #[error("Collection named `{0}` does not exist")]
+    NotFound(CollectionName),
+    /// The default collection does not exist.
+    #[error(
+        "Default collection `{0}` does not exist \
+         (specify the collection, or use `collection set-default` to fix)"
+    )]
+    StaleDefault(CollectionName),
+    /// No collection is specified, and the default collection cannot be determined.
+    #[error("No target collection specified (and no default collection is set)")]
+    NotSpecified,
+}
+
 /// Magro config.
 #[derive(Debug, Clone)]
 pub struct Config {
     /// Main config.
-    #[allow(dead_code)] // The type is currently empty.
     main: MainConfig,
-    /// Collections.
+    /// Path to the loaded main config file.
+    main_path: Option<PathBuf>,
+    /// Raw content of the loaded main config file.
+    main_raw: toml::value::Table,
+    /// Path to and raw content of the loaded machine-local main config overlay.
+    main_local: Option<(PathBuf, toml::value::Table)>,
+    /// Path to the collections config file.
+    collections_path: PathBuf,
+    /// Collections, with the machine-local overlay merged.
     collections: CollectionsConfig,
+    /// Collections config as in the file, without the machine-local overlay.
+    collections_base: CollectionsConfig,
+    /// Path to and content of the loaded machine-local collections config overlay.
+    collections_local: Option<(PathBuf, CollectionsConfig)>,
     /// Whether the collections config is (possibly) modified.
     collections_is_dirty: bool,
 }
 
 impl Config {
     /// Loads config from the given directory.
-    pub(super) fn from_dir_path(conf_dir: &Path) -> Result<Self, LoadError> {
-        let main = {
+    ///
+    /// The collections config is loaded from `profile_dir`, which is the
+    /// config directory itself for the default profile.
+    /// Machine-local overlay files (`config.local.toml` and
+    /// `collections.local.toml`) are merged over the main files if exist.
+    pub(super) fn from_dir_path(conf_dir: &Path, profile_dir: &Path) -> Result<Self, LoadError> {
+        let (mut main, main_path, main_raw) = {
             let path = conf_dir.join(DEFAULT_MAIN_CONFIG_RELPATH);
             if path.is_file() {
                 let conf = MainConfig::from_path(&path).map_err(|e| e.and_path(path.clone()))?;
+                // Keep the raw content to tell which values are explicitly set.
+                let raw: toml::value::Table =
+                    load::from_path(&path).map_err(|e| e.and_path(path.clone()))?;
                 log::debug!("Loaded main config file {:?}", path);
-                conf
+                (conf, Some(path), raw)
             } else {
                 log::debug!("Main config not found. Using default data");
-                MainConfig::default()
+                (MainConfig::default(), None, Default::default())
+            }
+        };
+        let main_local = {
+            let path = conf_dir.join(LOCAL_MAIN_CONFIG_RELPATH);
+            if path.is_file() {
+                let raw: toml::value::Table =
+                    load::from_path(&path).map_err(|e| e.and_path(path.clone()))?;
+                let mut merged = main_raw.clone();
+                load::merge_tables(&mut merged, &raw);
+                main = toml::Value::Table(merged)
+                    .try_into()
+                    .map_err(|e| LoadError::from_decode(e).and_path(path.clone()))?;
+                log::debug!("Loaded machine-local main config file {:?}", path);
+                Some((path, raw))
+            } else {
+                None
             }
         };
+        let collections_path = profile_dir.join(DEFAULT_COLLECTIONS_CONFIG_RELPATH);
         let (collections, collections_is_dirty) = {
-            let path = conf_dir.join(DEFAULT_COLLECTIONS_CONFIG_RELPATH);
+            let path = &collections_path;
             if path.is_file() {
                 let conf =
-                    CollectionsConfig::from_path(&path).map_err(|e| e.and_path(path.clone()))?;
+                    CollectionsConfig::from_path(path).map_err(|e| e.and_path(path.clone()))?;
                 log::debug!("Loaded collections config file {:?}", path);
                 (conf, false)
             } else {
@@ -53,24 +127,167 @@ impl Config {
                 (CollectionsConfig::default(), true)
             }
         };
+        let collections_base = collections.clone();
+        let mut collections = collections;
+        let collections_local = {
+            let path = profile_dir.join(LOCAL_COLLECTIONS_CONFIG_RELPATH);
+            if path.is_file() {
+                let conf =
+                    CollectionsConfig::from_path(&path).map_err(|e| e.and_path(path.clone()))?;
+                collections
+                    .merge_overlay(&conf)
+                    .map_err(|e| e.and_path(path.clone()))?;
+                log::debug!("Loaded machine-local collections config file {:?}", path);
+                Some((path, conf))
+            } else {
+                None
+            }
+        };
 
-        Ok(Self {
+        let config = Self {
             main,
+            main_path,
+            main_raw,
+            main_local,
+            collections_path,
             collections,
+            collections_base,
+            collections_local,
             collections_is_dirty,
-        })
+        };
+        if let Some(name) = config.stale_default_collection() {
+            log::warn!(
+                "Default collection `{}` does not exist (use `collection set-default` to fix)",
+                name
+            );
+        }
+
+        Ok(config)
     }
 
     /// Saves the configs if possibly modified.
-    pub(super) fn save_if_dirty(&mut self, conf_dir: &Path) -> io::Result<()> {
+    ///
+    /// Values from the machine-local overlay are not saved to the main file.
+    pub(super) fn save_if_dirty(&mut self) -> io::Result<()> {
         if mem::replace(&mut self.collections_is_dirty, false) {
-            let path = conf_dir.join(DEFAULT_COLLECTIONS_CONFIG_RELPATH);
-            self.collections.save_to_path(&path)?;
+            if let Some(dir) = self.collections_path.parent() {
+                if !dir.is_dir() {
+                    log::trace!("Creating a directory {:?} to save config", dir);
+                    fs::DirBuilder::new().recursive(true).create(dir)?;
+                }
+            }
+            let to_save = match &self.collections_local {
+                Some((_, overlay)) => self
+                    .collections
+                    .without_overlay(&self.collections_base, overlay),
+                None => self.collections.clone(),
+            };
+            to_save.save_to_path(&self.collections_path)?;
+            self.collections_base = to_save;
         }
 
         Ok(())
     }
 
+    /// Returns true if the collections config is (possibly) modified and not saved yet.
+    #[inline]
+    #[must_use]
+    pub(crate) fn is_dirty(&self) -> bool {
+        self.collections_is_dirty
+    }
+
+    /// Returns a reference to the main config.
+    #[inline]
+    #[must_use]
+    pub fn main(&self) -> &MainConfig {
+        &self.main
+    }
+
+    /// Returns the path to the loaded main config file.
+    ///
+    /// Returns `None` if the main config file does not exist.
+    #[inline]
+    #[must_use]
+    pub fn main_path(&self) -> Option<&Path> {
+        self.main_path.as_deref()
+    }
+
+    /// Returns the path to the loaded machine-local main config overlay file.
+    #[inline]
+    #[must_use]
+    pub fn main_local_path(&self) -> Option<&Path> {
+        self.main_local.as_ref().map(|(path, _)| path.as_path())
+    }
+
+    /// Returns the path to the loaded machine-local collections config overlay file.
+    #[inline]
+    #[must_use]
+    pub fn collections_local_path(&self) -> Option<&Path> {
+        self.collections_local
+            .as_ref()
+            .map(|(path, _)| path.as_path())
+    }
+
+    /// Returns the path to the collections config file.
+    ///
+    /// Note that the file might not exist.
+    #[inline]
+    #[must_use]
+    pub fn collections_path(&self) -> &Path {
+        &self.collections_path
+    }
+
+    /// Returns the fully resolved config with the source of each value.
+    ///
+    /// This consists of built-in defaults, values in the main config file,
+    /// values in the collections config, and values in the machine-local
+    /// overlays.
+    /// Command line options are not included.
+    #[must_use]
+    pub fn effective(&self) -> EffectiveConfig {
+        let mut effective = EffectiveConfig::new();
+        effective.merge_table(&self.main.default_values(), &ValueSource::Default);
+        if let Some(path) = &self.main_path {
+            effective.merge_table(&self.main_raw, &ValueSource::File { path: path.clone() });
+        }
+        if let Some((path, raw)) = &self.main_local {
+            effective.merge_table(raw, &ValueSource::File { path: path.clone() });
+        }
+
+        let main_source = ValueSource::File {
+            path: self.collections_path.clone(),
+        };
+        let (local_source, overlay) = match &self.collections_local {
+            Some((path, overlay)) => (
+                Some(ValueSource::File { path: path.clone() }),
+                Some(overlay),
+            ),
+            None => (None, None),
+        };
+        let source_of = |from_overlay: bool| match &local_source {
+            Some(local_source) if from_overlay => local_source.clone(),
+            _ => main_source.clone(),
+        };
+        if let Some(name) = self.default_collection() {
+            let from_overlay = overlay.map_or(false, |o| o.default_collection() == Some(name));
+            effective.set(
+                "default-collection".to_owned(),
+                toml::Value::String(name.to_string()),
+                source_of(from_overlay),
+            );
+        }
+        for collection in self.collections().iter() {
+            let from_overlay = overlay.map_or(false, |o| o.contains_same(collection));
+            effective.set(
+                format!("collection.{}.path", collection.name()),
+                toml::Value::String(collection.path().to_string_lossy().into_owned()),
+                source_of(from_overlay),
+            );
+        }
+
+        effective
+    }
+
     /// Returns a default collection.
     #[inline]
     #[must_use]
@@ -78,6 +295,50 @@ impl Config {
         self.collections.default_collection()
     }
 
+    /// Returns the default collection name if it refers to a non-existent collection.
+    ///
+    /// This can happen when the collections config is edited manually.
+    #[must_use]
+    pub fn stale_default_collection(&self) -> Option<&CollectionName> {
+        self.default_collection()
+            .filter(|name| self.collections().get(name).is_none())
+    }
+
+    /// Resolves the target collection of operations such as clone.
+    ///
+    /// If the name is not given, the default collection is used.
+    /// If the default collection is not set either but only one collection
+    /// exists, that collection is used.
+    pub fn target_collection(
+        &self,
+        name: Option<&CollectionName>,
+    ) -> Result<&Collection, TargetCollectionError> {
+        if let Some(name) = name {
+            return self
+                .collections()
+                .get(name)
+                .ok_or_else(|| TargetCollectionError::NotFound(name.clone()));
+        }
+        if let Some(name) = self.default_collection() {
+            return self
+                .collections()
+                .get(name)
+                .ok_or_else(|| TargetCollectionError::StaleDefault(name.clone()));
+        }
+
+        let mut collections = self.collections().iter();
+        match (collections.next(), collections.next()) {
+            (Some(collection), None) => {
+                log::info!(
+                    "Using the only collection `{}` as the default collection",
+                    collection.name()
+                );
+                Ok(collection)
+            }
+            _ => Err(TargetCollectionError::NotSpecified),
+        }
+    }
+
     /// Sets default collection to the given name.
     #[inline]
     pub fn set_default_collection(&mut self, name: Option<CollectionName>) {
@@ -5,7 +5,7 @@ use std::{io, path::Path};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    collection::{CollectionName, Collections},
+    collection::{Collection, CollectionName, Collections},
     config::load::{from_path, save_to_path, LoadError},
 };
 
@@ -21,6 +21,13 @@ pub struct CollectionsConfig {
     // See <https://github.com/serde-rs/serde/issues/642> for the reason
     // the validation is not performed on this.
     default_collection: Option<CollectionName>,
+    /// Whether to match collection names loosely.
+    ///
+    /// If enabled, collection names are matched case-insensitively, and `-`
+    /// and `_` are treated as the same character.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    loose_names: bool,
     /// Collections.
     #[serde(rename = "collection")]
     #[serde(default)]
@@ -32,7 +39,18 @@ impl CollectionsConfig {
     /// Loads a config from a file at the given path.
     #[inline]
     pub(crate) fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
-        from_path(path.as_ref())
+        let mut conf: Self = from_path(path.as_ref())?;
+        conf.apply_loose_names()?;
+        Ok(conf)
+    }
+
+    /// Applies the loose name matching setting to the collections.
+    ///
+    /// Fails if some collection names conflict in the loose name matching mode.
+    fn apply_loose_names(&mut self) -> Result<(), LoadError> {
+        self.collections
+            .set_loose_names(self.loose_names)
+            .map_err(LoadError::from_name_conflict)
     }
 
     /// Saves the config to the given path.
@@ -66,4 +84,59 @@ impl CollectionsConfig {
     pub(super) fn set_default_collection(&mut self, name: Option<CollectionName>) {
         self.default_collection = name;
     }
+
+    /// Merges the overlay over this config.
+    ///
+    /// Collections in the overlay replace ones with the same names.
+    /// Loose name matching is enabled if either config enables it.
+    pub(super) fn merge_overlay(&mut self, overlay: &Self) -> Result<(), LoadError> {
+        if let Some(name) = &overlay.default_collection {
+            self.default_collection = Some(name.clone());
+        }
+        self.loose_names |= overlay.loose_names;
+        for collection in &overlay.collections {
+            self.collections.insert(collection.clone());
+        }
+        self.apply_loose_names()
+    }
+
+    /// Returns true if this config has the same collection as the given one.
+    #[must_use]
+    pub(super) fn contains_same(&self, collection: &Collection) -> bool {
+        self.collections
+            .get(collection.name())
+            .map_or(false, |c| c.path() == collection.path())
+    }
+
+    /// Returns the config without the values from the overlay.
+    ///
+    /// `base` is the config before the overlay is merged. Values unchanged
+    /// since they are merged from the overlay are restored to the ones in
+    /// `base` (or removed if they are not in `base`).
+    #[must_use]
+    pub(super) fn without_overlay(&self, base: &Self, overlay: &Self) -> Self {
+        let default_collection = match (&self.default_collection, &overlay.default_collection) {
+            (Some(current), Some(overlaid)) if current == overlaid => {
+                base.default_collection.clone()
+            }
+            _ => self.default_collection.clone(),
+        };
+        let mut collections = Collections::default();
+        collections
+            .set_loose_names(base.loose_names)
+            .expect("Should never fail: the collections are empty");
+        for collection in &self.collections {
+            if !overlay.contains_same(collection) {
+                collections.insert(collection.clone());
+            } else if let Some(orig) = base.collections.get(collection.name()) {
+                collections.insert(orig.clone());
+            }
+        }
+
+        Self {
+            default_collection,
+            loose_names: base.loose_names,
+            collections,
+        }
+    }
 }
@@ -0,0 +1,165 @@
+//! Effective config with provenance of values.
+
+use std::{collections::BTreeMap, fmt, path::PathBuf};
+
+use serde::Serialize;
+use toml::{value::Table, Value};
+
+/// Source of a config value.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum ValueSource {
+    /// Built-in default.
+    Default,
+    /// Config file.
+    File {
+        /// Path to the file.
+        path: PathBuf,
+    },
+    /// Command line option.
+    CommandLine {
+        /// Option name (such as `--jobs`).
+        option: String,
+    },
+}
+
+impl fmt::Display for ValueSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => f.write_str("default"),
+            Self::File { path } => write!(f, "file {}", path.display()),
+            Self::CommandLine { option } => write!(f, "command line ({})", option),
+        }
+    }
+}
+
+/// Effective config value.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EffectiveValue {
+    /// Dotted key.
+    key: String,
+    /// Value.
+    value: Value,
+    /// Source of the value.
+    source: ValueSource,
+}
+
+impl EffectiveValue {
+    /// Returns the dotted key (such as `scratch.ttl-days`).
+    #[inline]
+    #[must_use]
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns the value.
+    #[inline]
+    #[must_use]
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    /// Returns the source of the value.
+    #[inline]
+    #[must_use]
+    pub fn source(&self) -> &ValueSource {
+        &self.source
+    }
+}
+
+/// Fully resolved config, with the source of each value.
+///
+/// Values are set layer by layer, and values in later layers override ones
+/// in earlier layers.
+/// Tables are flattened into dotted keys, and arrays are treated as single
+/// values.
+#[derive(Default, Debug, Clone)]
+pub struct EffectiveConfig {
+    /// Values.
+    values: BTreeMap<String, EffectiveValue>,
+}
+
+impl EffectiveConfig {
+    /// Creates an empty effective config.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Merges the values in the table, with the given source.
+    pub fn merge_table(&mut self, table: &Table, source: &ValueSource) {
+        self.merge_table_with_prefix("", table, source);
+    }
+
+    /// Merges the values in the table under the key prefix.
+    fn merge_table_with_prefix(&mut self, prefix: &str, table: &Table, source: &ValueSource) {
+        for (name, value) in table {
+            let key = if prefix.is_empty() {
+                quote_key(name)
+            } else {
+                format!("{}.{}", prefix, quote_key(name))
+            };
+            match value {
+                Value::Table(table) => self.merge_table_with_prefix(&key, table, source),
+                value => self.set(key, value.clone(), source.clone()),
+            }
+        }
+    }
+
+    /// Sets the value with the given dotted key.
+    pub fn set(&mut self, key: String, value: Value, source: ValueSource) {
+        self.values
+            .insert(key.clone(), EffectiveValue { key, value, source });
+    }
+
+    /// Returns the values sorted by keys.
+    pub fn values(&self) -> impl Iterator<Item = &EffectiveValue> {
+        self.values.values()
+    }
+}
+
+/// Returns the key quoted if necessary.
+fn quote_key(name: &str) -> String {
+    let is_bare = !name.is_empty()
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+    if is_bare {
+        name.to_owned()
+    } else {
+        Value::String(name.to_owned()).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn later_layers_override() {
+        let defaults: Table = toml::from_str("jobs = 1\n[scratch]\nttl-days = 7\n").unwrap();
+        let file: Table =
+            toml::from_str("[scratch]\nttl-days = 3\n[templates.\"a.b\"]\nuri = \"x\"\n").unwrap();
+        let path = ValueSource::File {
+            path: PathBuf::from("config.toml"),
+        };
+        let mut effective = EffectiveConfig::new();
+        effective.merge_table(&defaults, &ValueSource::Default);
+        effective.merge_table(&file, &path);
+
+        let values = effective
+            .values()
+            .map(|v| (v.key(), v.value().to_string(), v.source().clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            [
+                ("jobs", "1".to_owned(), ValueSource::Default),
+                ("scratch.ttl-days", "3".to_owned(), path.clone()),
+                ("templates.\"a.b\".uri", "\"x\"".to_owned(), path),
+            ]
+        );
+    }
+}
@@ -1,35 +1,40 @@
 //! Config load.
 
 use std::{
-    io,
+    fmt, io,
     path::{Path, PathBuf},
 };
 
 use thiserror::Error as ThisError;
 
-use crate::lock_fs;
+use crate::{collection::NameConflictError, lock_fs};
 
 /// Config load error.
 #[derive(Debug, ThisError)]
-#[error("{} (at file {:?}): {}", kind.as_str(), path, source)]
 pub struct LoadError {
-    /// Error kind.
-    kind: LoadErrorKind,
     /// Filename.
     path: Option<PathBuf>,
     /// Error source.
     #[source]
-    source: anyhow::Error,
+    source: LoadErrorSource,
 }
 
 impl LoadError {
     /// Creates a new decode error.
     #[inline]
-    pub(super) fn from_decode(e: impl Into<anyhow::Error>) -> Self {
+    pub(super) fn from_decode(e: toml::de::Error) -> Self {
         Self {
-            kind: LoadErrorKind::Decode,
             path: None,
-            source: e.into(),
+            source: LoadErrorSource::Decode(e),
+        }
+    }
+
+    /// Creates a new error on conflicting collection names.
+    #[inline]
+    pub(super) fn from_name_conflict(e: NameConflictError) -> Self {
+        Self {
+            path: None,
+            source: LoadErrorSource::NameConflict(e),
         }
     }
 
@@ -41,23 +46,65 @@ impl LoadError {
             ..self
         }
     }
+
+    /// Returns the error kind.
+    #[inline]
+    #[must_use]
+    pub fn kind(&self) -> LoadErrorKind {
+        match self.source {
+            LoadErrorSource::Decode(_) | LoadErrorSource::NameConflict(_) => LoadErrorKind::Decode,
+            LoadErrorSource::Io(_) => LoadErrorKind::Io,
+        }
+    }
+
+    /// Returns the path to the file which caused the error, if available.
+    #[inline]
+    #[must_use]
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (at file {:?}): {}",
+            self.kind().as_str(),
+            self.path,
+            self.source
+        )
+    }
 }
 
 impl From<io::Error> for LoadError {
     #[inline]
     fn from(e: io::Error) -> Self {
         Self {
-            kind: LoadErrorKind::Io,
             path: None,
-            source: e.into(),
+            source: LoadErrorSource::Io(e),
         }
     }
 }
 
+/// Source error of `LoadError`.
+#[derive(Debug, ThisError)]
+enum LoadErrorSource {
+    /// Decode error.
+    #[error(transparent)]
+    Decode(toml::de::Error),
+    /// Conflicting collection names.
+    #[error(transparent)]
+    NameConflict(NameConflictError),
+    /// I/O error.
+    #[error(transparent)]
+    Io(io::Error),
+}
+
 /// Error kind for `LoadError`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[non_exhaustive]
-enum LoadErrorKind {
+pub enum LoadErrorKind {
     /// Decode error.
     ///
     /// This may be caused by syntax error and semantic error.
@@ -70,7 +117,7 @@ impl LoadErrorKind {
     /// Returns a generic error message for the error kind.
     #[inline]
     #[must_use]
-    fn as_str(&self) -> &'static str {
+    pub fn as_str(&self) -> &'static str {
         match *self {
             Self::Decode => "Decode error",
             Self::Io => "I/O error",
@@ -87,6 +134,23 @@ where
     toml::from_str::<T>(&content).map_err(LoadError::from_decode)
 }
 
+/// Merges the overlay table into the base table recursively.
+///
+/// Tables are merged key by key, and other values (including arrays) in the
+/// overlay replace the ones in the base.
+pub(super) fn merge_tables(base: &mut toml::value::Table, overlay: &toml::value::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => {
+                merge_tables(base, overlay)
+            }
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
 /// Saves the given data to a file at the given path.
 pub(super) fn save_to_path<T>(value: T, path: &Path) -> io::Result<()>
 where
@@ -105,3 +169,25 @@ where
     };
     lock_fs::write(path, &content)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_overlay_tables() {
+        let mut base: toml::value::Table =
+            toml::from_str("jobs = 2\n[backup]\nurl-template = \"a\"\nremote = \"backup\"\n")
+                .unwrap();
+        let overlay: toml::value::Table =
+            toml::from_str("[backup]\nurl-template = \"b\"\n[scratch]\nttl-days = 1\n").unwrap();
+        merge_tables(&mut base, &overlay);
+
+        let expected: toml::value::Table = toml::from_str(
+            "jobs = 2\n[backup]\nurl-template = \"b\"\nremote = \"backup\"\n\
+             [scratch]\nttl-days = 1\n",
+        )
+        .unwrap();
+        assert_eq!(base, expected);
+    }
+}
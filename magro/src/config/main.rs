@@ -1,16 +1,91 @@
 //! Main config.
 
-use std::path::Path;
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use serde::{Deserialize, Serialize};
+use toml::{value::Table, Value};
 
-use crate::config::load::{from_path, LoadError};
+use crate::{
+    cache::CacheNamespace,
+    collection::CollectionName,
+    config::load::{from_path, LoadError},
+    discovery::ColocatedPreference,
+    hooks::{FailurePolicy, HookEvent},
+    permissions::DirMode,
+};
+
+/// Default name of the backup remote.
+const DEFAULT_BACKUP_REMOTE: &str = "backup";
+
+/// Default name of the scratch collection.
+const DEFAULT_SCRATCH_COLLECTION: &str = "scratch";
+
+/// Default lifetime of scratch clones in days.
+const DEFAULT_SCRATCH_TTL_DAYS: u64 = 7;
+
+/// Default retention period of removed repositories in the trash in days.
+const DEFAULT_TRASH_RETENTION_DAYS: u64 = 30;
+
+/// Default directory to put linked worktrees, relative to the home directory.
+const DEFAULT_WORKTREE_DIR: &str = "worktrees";
 
 /// Main config.
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "kebab-case")]
-pub struct MainConfig {}
+pub struct MainConfig {
+    /// Backup config.
+    #[serde(default)]
+    backup: Option<BackupConfig>,
+    /// Cache namespace.
+    ///
+    /// If not set, the default cache file is used.
+    #[serde(default)]
+    cache_namespace: Option<CacheNamespace>,
+    /// Mode of directories created by magro (such as `"2775"`).
+    ///
+    /// If not set, directories are created with the default mode (affected by umask).
+    #[serde(default)]
+    dir_mode: Option<DirMode>,
+    /// Repositories discovery config.
+    #[serde(default)]
+    discovery: DiscoveryConfig,
+    /// Lifecycle hooks.
+    #[serde(default)]
+    hooks: HooksConfig,
+    /// Default number of parallel jobs for per-repository operations.
+    #[serde(default)]
+    jobs: Option<NonZeroUsize>,
+    /// Maximum number of parallel network operations per host.
+    ///
+    /// If not set, the number is not limited per host.
+    #[serde(default)]
+    jobs_per_host: Option<NonZeroUsize>,
+    /// `list` subcommand config.
+    #[serde(default)]
+    list: ListConfig,
+    /// Whether to never write the config and the cache.
+    #[serde(default)]
+    read_only: bool,
+    /// Scratch clones config.
+    #[serde(default)]
+    scratch: ScratchConfig,
+    /// Repository templates.
+    #[serde(default)]
+    templates: BTreeMap<String, RepoTemplateConfig>,
+    /// Trash config.
+    #[serde(default)]
+    trash: TrashConfig,
+    /// Linked worktrees config.
+    #[serde(default)]
+    worktree: WorktreeConfig,
+}
 
 impl MainConfig {
     /// Loads a config from a file at the given path.
@@ -18,4 +93,459 @@ impl MainConfig {
     pub(crate) fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
         from_path(path.as_ref())
     }
+
+    /// Returns the built-in default values as a table.
+    ///
+    /// Defaults of per-template settings are included for the templates in
+    /// this config.
+    pub(crate) fn default_values(&self) -> Table {
+        let mut table = Table::new();
+        let mut insert = |path: &[&str], value: Value| {
+            let (last, parents) = path
+                .split_last()
+                .expect("Should never fail: non-empty path");
+            let mut current = &mut table;
+            for name in parents {
+                current = match current
+                    .entry((*name).to_owned())
+                    .or_insert_with(|| Value::Table(Table::new()))
+                {
+                    Value::Table(t) => t,
+                    _ => unreachable!("Should never happen: only tables are inserted"),
+                };
+            }
+            current.insert((*last).to_owned(), value);
+        };
+        insert(
+            &["backup", "remote"],
+            Value::String(DEFAULT_BACKUP_REMOTE.to_owned()),
+        );
+        insert(
+            &["discovery", "colocated"],
+            Value::String(ColocatedPreference::default().as_str().to_owned()),
+        );
+        for event in [
+            HookEvent::PreClone,
+            HookEvent::PostClone,
+            HookEvent::PreRefresh,
+            HookEvent::PostRefresh,
+            HookEvent::PreRemove,
+            HookEvent::PostRemove,
+            HookEvent::PreMove,
+            HookEvent::PostMove,
+        ] {
+            insert(&["hooks", event.as_str()], Value::Array(Vec::new()));
+        }
+        insert(&["jobs"], Value::Integer(1));
+        insert(&["list", "refresh"], Value::Boolean(false));
+        insert(&["read-only"], Value::Boolean(false));
+        insert(
+            &["scratch", "collection"],
+            Value::String(DEFAULT_SCRATCH_COLLECTION.to_owned()),
+        );
+        insert(
+            &["scratch", "ttl-days"],
+            Value::Integer(DEFAULT_SCRATCH_TTL_DAYS as i64),
+        );
+        for name in self.templates.keys() {
+            insert(&["templates", name, "keep-history"], Value::Boolean(false));
+        }
+        insert(&["trash", "enabled"], Value::Boolean(false));
+        insert(
+            &["trash", "retention-days"],
+            Value::Integer(DEFAULT_TRASH_RETENTION_DAYS as i64),
+        );
+        insert(
+            &["worktree", "dir"],
+            Value::String(DEFAULT_WORKTREE_DIR.to_owned()),
+        );
+        table
+    }
+
+    /// Returns the backup config.
+    #[inline]
+    #[must_use]
+    pub fn backup(&self) -> Option<&BackupConfig> {
+        self.backup.as_ref()
+    }
+
+    /// Returns the cache namespace, if configured.
+    #[inline]
+    #[must_use]
+    pub fn cache_namespace(&self) -> Option<&CacheNamespace> {
+        self.cache_namespace.as_ref()
+    }
+
+    /// Returns the mode of directories created by magro, if configured.
+    #[inline]
+    #[must_use]
+    pub fn dir_mode(&self) -> Option<DirMode> {
+        self.dir_mode
+    }
+
+    /// Returns the repositories discovery config.
+    #[inline]
+    #[must_use]
+    pub fn discovery(&self) -> &DiscoveryConfig {
+        &self.discovery
+    }
+
+    /// Returns the lifecycle hooks config.
+    #[inline]
+    #[must_use]
+    pub fn hooks(&self) -> &HooksConfig {
+        &self.hooks
+    }
+
+    /// Returns the default number of parallel jobs, if configured.
+    #[inline]
+    #[must_use]
+    pub fn jobs(&self) -> Option<NonZeroUsize> {
+        self.jobs
+    }
+
+    /// Returns the maximum number of parallel network operations per host,
+    /// if configured.
+    #[inline]
+    #[must_use]
+    pub fn jobs_per_host(&self) -> Option<NonZeroUsize> {
+        self.jobs_per_host
+    }
+
+    /// Returns the `list` subcommand config.
+    #[inline]
+    #[must_use]
+    pub fn list(&self) -> &ListConfig {
+        &self.list
+    }
+
+    /// Returns true if the config and the cache should never be written.
+    #[inline]
+    #[must_use]
+    pub fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Returns the scratch clones config.
+    #[inline]
+    #[must_use]
+    pub fn scratch(&self) -> &ScratchConfig {
+        &self.scratch
+    }
+
+    /// Returns the repository templates.
+    #[inline]
+    #[must_use]
+    pub fn templates(&self) -> &BTreeMap<String, RepoTemplateConfig> {
+        &self.templates
+    }
+
+    /// Returns the repository template with the given name.
+    #[inline]
+    #[must_use]
+    pub fn template(&self, name: &str) -> Option<&RepoTemplateConfig> {
+        self.templates.get(name)
+    }
+
+    /// Returns the trash config.
+    #[inline]
+    #[must_use]
+    pub fn trash(&self) -> &TrashConfig {
+        &self.trash
+    }
+
+    /// Returns the linked worktrees config.
+    #[inline]
+    #[must_use]
+    pub fn worktree(&self) -> &WorktreeConfig {
+        &self.worktree
+    }
+}
+
+/// Backup config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "kebab-case")]
+pub struct BackupConfig {
+    /// Template of the backup remote URL.
+    ///
+    /// See [`crate::backup::remote_url`] for available variables.
+    url_template: String,
+    /// Name of the backup remote.
+    #[serde(default)]
+    remote: Option<String>,
+}
+
+impl BackupConfig {
+    /// Returns the template of the backup remote URL.
+    #[inline]
+    #[must_use]
+    pub fn url_template(&self) -> &str {
+        &self.url_template
+    }
+
+    /// Returns the name of the backup remote.
+    #[inline]
+    #[must_use]
+    pub fn remote(&self) -> &str {
+        self.remote.as_deref().unwrap_or(DEFAULT_BACKUP_REMOTE)
+    }
+}
+
+/// Scratch clones config.
+///
+/// Scratch clones are disposable clones created by `magro try`, and removed
+/// after they expire.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "kebab-case")]
+pub struct ScratchConfig {
+    /// Collection to put scratch clones.
+    #[serde(default)]
+    collection: Option<CollectionName>,
+    /// Lifetime of scratch clones in days.
+    #[serde(default)]
+    ttl_days: Option<u64>,
+}
+
+impl ScratchConfig {
+    /// Returns the name of the collection to put scratch clones.
+    #[must_use]
+    pub fn collection(&self) -> CollectionName {
+        self.collection.clone().unwrap_or_else(|| {
+            CollectionName::try_from(DEFAULT_SCRATCH_COLLECTION)
+                .expect("Should never fail: the default collection name is valid")
+        })
+    }
+
+    /// Returns the lifetime of scratch clones in days.
+    #[inline]
+    #[must_use]
+    pub fn ttl_days(&self) -> u64 {
+        self.ttl_days.unwrap_or(DEFAULT_SCRATCH_TTL_DAYS)
+    }
+}
+
+/// Repositories discovery config.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "kebab-case")]
+pub struct DiscoveryConfig {
+    /// VCS to record for colocated Jujutsu repositories (`"git"` or `"jj"`).
+    #[serde(default)]
+    colocated: Option<ColocatedPreference>,
+}
+
+impl DiscoveryConfig {
+    /// Returns the VCS to record for colocated Jujutsu repositories.
+    #[inline]
+    #[must_use]
+    pub fn colocated(&self) -> ColocatedPreference {
+        self.colocated.unwrap_or_default()
+    }
+}
+
+/// `list` subcommand config.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "kebab-case")]
+pub struct ListConfig {
+    /// Whether to refresh the collections before listing by default.
+    #[serde(default)]
+    refresh: bool,
+}
+
+impl ListConfig {
+    /// Returns true if the collections should be refreshed before listing.
+    #[inline]
+    #[must_use]
+    pub fn refresh(&self) -> bool {
+        self.refresh
+    }
+}
+
+/// Trash config.
+///
+/// If enabled, removed repositories are moved to the trash directory in the
+/// cache directory, instead of being deleted immediately.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "kebab-case")]
+pub struct TrashConfig {
+    /// Whether to move removed repositories to the trash.
+    #[serde(default)]
+    enabled: bool,
+    /// Retention period of removed repositories in days.
+    #[serde(default)]
+    retention_days: Option<u64>,
+}
+
+impl TrashConfig {
+    /// Returns true if removed repositories should be moved to the trash.
+    #[inline]
+    #[must_use]
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Returns the retention period of removed repositories in days.
+    #[inline]
+    #[must_use]
+    pub fn retention_days(&self) -> u64 {
+        self.retention_days.unwrap_or(DEFAULT_TRASH_RETENTION_DAYS)
+    }
+}
+
+/// Linked worktrees config.
+///
+/// Linked worktrees created by `magro worktree add` are put under the
+/// directory.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "kebab-case")]
+pub struct WorktreeConfig {
+    /// Directory to put linked worktrees.
+    ///
+    /// If the path is relative, it is relative to the home directory.
+    #[serde(default)]
+    dir: Option<PathBuf>,
+}
+
+impl WorktreeConfig {
+    /// Returns the directory to put linked worktrees as configured.
+    ///
+    /// If the path is relative, it is relative to the home directory.
+    #[inline]
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        self.dir
+            .as_deref()
+            .unwrap_or_else(|| Path::new(DEFAULT_WORKTREE_DIR))
+    }
+}
+
+/// Lifecycle hooks config.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "kebab-case")]
+pub struct HooksConfig {
+    /// Hooks run before cloning a repository.
+    #[serde(default)]
+    pre_clone: Vec<HookConfig>,
+    /// Hooks run after cloning a repository.
+    #[serde(default)]
+    post_clone: Vec<HookConfig>,
+    /// Hooks run before refreshing a collection.
+    #[serde(default)]
+    pre_refresh: Vec<HookConfig>,
+    /// Hooks run after refreshing a collection.
+    #[serde(default)]
+    post_refresh: Vec<HookConfig>,
+    /// Hooks run before removing a repository.
+    #[serde(default)]
+    pre_remove: Vec<HookConfig>,
+    /// Hooks run after removing a repository.
+    #[serde(default)]
+    post_remove: Vec<HookConfig>,
+    /// Hooks run before moving a collection directory or repositories.
+    #[serde(default)]
+    pre_move: Vec<HookConfig>,
+    /// Hooks run after moving a collection directory or repositories.
+    #[serde(default)]
+    post_move: Vec<HookConfig>,
+}
+
+impl HooksConfig {
+    /// Returns the hooks for the event.
+    #[must_use]
+    pub fn get(&self, event: HookEvent) -> &[HookConfig] {
+        match event {
+            HookEvent::PreClone => &self.pre_clone,
+            HookEvent::PostClone => &self.post_clone,
+            HookEvent::PreRefresh => &self.pre_refresh,
+            HookEvent::PostRefresh => &self.post_refresh,
+            HookEvent::PreRemove => &self.pre_remove,
+            HookEvent::PostRemove => &self.post_remove,
+            HookEvent::PreMove => &self.pre_move,
+            HookEvent::PostMove => &self.post_move,
+        }
+    }
+}
+
+/// Hook config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "kebab-case")]
+pub struct HookConfig {
+    /// Command and its arguments.
+    command: Vec<String>,
+    /// Timeout in seconds.
+    #[serde(default)]
+    timeout: Option<u64>,
+    /// Policy on failures.
+    #[serde(default)]
+    on_failure: FailurePolicy,
+}
+
+impl HookConfig {
+    /// Returns the command and its arguments.
+    #[inline]
+    #[must_use]
+    pub fn command(&self) -> &[String] {
+        &self.command
+    }
+
+    /// Returns the timeout, if configured.
+    #[inline]
+    #[must_use]
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout.map(Duration::from_secs)
+    }
+
+    /// Returns the policy on failures.
+    #[inline]
+    #[must_use]
+    pub fn on_failure(&self) -> FailurePolicy {
+        self.on_failure
+    }
+}
+
+/// Repository template config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "kebab-case")]
+pub struct RepoTemplateConfig {
+    /// URI of the template repository.
+    uri: String,
+    /// Whether to keep the history of the template repository.
+    #[serde(default)]
+    keep_history: bool,
+    /// Placeholders and their replacement templates.
+    ///
+    /// See [`crate::scaffold::replacements`] for available variables.
+    #[serde(default)]
+    placeholders: BTreeMap<String, String>,
+}
+
+impl RepoTemplateConfig {
+    /// Returns the URI of the template repository.
+    #[inline]
+    #[must_use]
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// Returns whether to keep the history of the template repository.
+    #[inline]
+    #[must_use]
+    pub fn keep_history(&self) -> bool {
+        self.keep_history
+    }
+
+    /// Returns the placeholders and their replacement templates.
+    #[inline]
+    #[must_use]
+    pub fn placeholders(&self) -> &BTreeMap<String, String> {
+        &self.placeholders
+    }
 }
@@ -2,39 +2,187 @@
 
 use std::{
     borrow::Cow,
-    fs, io,
+    env, io,
     path::{Path, PathBuf},
 };
 
-use anyhow::Context as _;
 use directories::{ProjectDirs, UserDirs};
 use once_cell::sync::OnceCell;
 use thiserror::Error as ThisError;
 
-use crate::{cache::Cache, config::Config, lock_fs};
+use crate::{
+    cache::{Cache, CacheNamespace},
+    collection::CollectionName,
+    config::{Config, LoadError},
+    locate::repo_root,
+    lock_fs,
+    permissions::{self, DirMode},
+    profile::ProfileName,
+    relocate,
+    tag::Tags,
+    timing,
+    trash::Trash,
+};
 
 /// Default cache file path relative to the cache directory.
 const DEFAULT_CACHE_RELPATH: &str = "cache.toml";
 
+/// Default tags file path relative to the config directory.
+const DEFAULT_TAGS_RELPATH: &str = "tags.toml";
+
+/// Default snapshots directory path relative to the config directory.
+const DEFAULT_SNAPSHOTS_RELPATH: &str = "snapshots";
+
+/// Default trash directory path relative to the cache directory.
+const DEFAULT_TRASH_RELPATH: &str = "trash";
+
+/// Default directory of job journals relative to the cache directory.
+const DEFAULT_JOBS_RELPATH: &str = "jobs";
+
+/// Environment variable to use the XDG base directories on every platform.
+///
+/// If this is set to `1`, `true`, or `yes`, the default config and cache
+/// directories are `magro` under `$XDG_CONFIG_HOME` and `$XDG_CACHE_HOME`
+/// (`~/.config` and `~/.cache` if not set), instead of the platform-specific
+/// ones (such as `~/Library/Application Support` on macOS).
+pub const USE_XDG_ENV: &str = "MAGRO_USE_XDG";
+
 /// Context error.
 #[derive(Debug, ThisError)]
-#[error(transparent)]
-pub struct Error(anyhow::Error);
+#[non_exhaustive]
+pub enum Error {
+    /// Failed to get the user directories (such as home directory).
+    #[error("Failed to get user directory")]
+    UserDirs,
+    /// Failed to get the project directories (such as config directory).
+    #[error("Failed to get project directory")]
+    ProjectDirs,
+    /// Failed to load the config.
+    #[error("Failed to load config")]
+    Config(#[source] LoadError),
+}
 
-impl Error {
-    /// Wraps the given error.
-    #[inline]
-    #[must_use]
-    fn new(e: impl Into<anyhow::Error>) -> Self {
-        Self(e.into())
+/// Error on renaming a collection.
+#[derive(Debug, ThisError)]
+#[non_exhaustive]
+pub enum RenameCollectionError {
+    /// The collection to rename does not exist.
+    #[error("Collection named `{0}` does not exist")]
+    NotFound(CollectionName),
+    /// A collection with the new name already exists.
+    #[error("Collection `{0}` already exists")]
+    AlreadyExists(CollectionName),
+    /// Failed to save the config.
+    #[error("Failed to save config")]
+    SaveConfig(#[source] io::Error),
+    /// Failed to save the cache.
+    ///
+    /// The config is restored in this case.
+    #[error("Failed to save cache file")]
+    SaveCache(#[source] io::Error),
+}
+
+/// Error on merging a collection into another.
+#[derive(Debug, ThisError)]
+#[non_exhaustive]
+pub enum MergeCollectionError {
+    /// The collection does not exist.
+    #[error("Collection named `{0}` does not exist")]
+    NotFound(CollectionName),
+    /// The collection is merged into itself.
+    #[error("Cannot merge the collection `{0}` into itself")]
+    SameCollection(CollectionName),
+    /// The source collection directory is not inside the destination, and
+    /// the repositories are not requested to be moved.
+    #[error(
+        "Directory of the collection `{from}` is not inside the collection `{into}`, \
+         so the repositories should be moved"
+    )]
+    NotNested {
+        /// Source collection.
+        from: CollectionName,
+        /// Destination collection.
+        into: CollectionName,
+    },
+    /// Repositories would have the same paths in the destination collection.
+    #[error("Repositories conflict in the destination collection: {}", display_paths(.0))]
+    Conflict(Vec<PathBuf>),
+    /// Failed to move a repository directory.
+    #[error("Failed to move {:?} to {:?}", from, to)]
+    Move {
+        /// Source path.
+        from: PathBuf,
+        /// Destination path.
+        to: PathBuf,
+        /// Source error.
+        #[source]
+        source: io::Error,
+    },
+    /// Failed to load the cache.
+    #[error("Failed to load cache file")]
+    LoadCache(#[source] io::Error),
+    /// Failed to save the config.
+    ///
+    /// Moved repositories are moved back in this case.
+    #[error("Failed to save config")]
+    SaveConfig(#[source] io::Error),
+    /// Failed to save the cache.
+    ///
+    /// The config is restored and moved repositories are moved back in this
+    /// case.
+    #[error("Failed to save cache file")]
+    SaveCache(#[source] io::Error),
+}
+
+/// Formats the paths as a comma-separated list.
+#[must_use]
+fn display_paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| format!("{:?}", path))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Returns the path to the cache file of the namespace in the given directory.
+fn cache_path(dir: &Path, namespace: Option<&CacheNamespace>) -> PathBuf {
+    match namespace {
+        Some(namespace) => dir.join(namespace.file_name()),
+        None => dir.join(DEFAULT_CACHE_RELPATH),
     }
 }
 
 /// Creates a `ProjectDirs` with the default parameters.
-fn get_project_dirs() -> anyhow::Result<ProjectDirs> {
-    ProjectDirs::from("org", "loliconduct", "magro")
-        .context("Failed to get project directory")
-        .map_err(Into::into)
+fn get_project_dirs() -> Result<ProjectDirs, Error> {
+    ProjectDirs::from("org", "loliconduct", "magro").ok_or(Error::ProjectDirs)
+}
+
+/// Returns true if the XDG base directories should be used on every platform.
+fn use_xdg() -> bool {
+    env::var(USE_XDG_ENV).map_or(false, |v| matches!(&*v, "1" | "true" | "yes"))
+}
+
+/// Returns the default config and cache directories.
+fn default_dirs(user_dirs: &UserDirs) -> Result<(PathBuf, PathBuf), Error> {
+    if use_xdg() {
+        log::debug!("Using the XDG base directories as {} is set", USE_XDG_ENV);
+        // Relative paths are invalid in the XDG base directory specification,
+        // and should be ignored.
+        let base_dir = |var: &str, default: &str| match env::var_os(var).map(PathBuf::from) {
+            Some(dir) if dir.is_absolute() => dir,
+            _ => user_dirs.home_dir().join(default),
+        };
+        return Ok((
+            base_dir("XDG_CONFIG_HOME", ".config").join("magro"),
+            base_dir("XDG_CACHE_HOME", ".cache").join("magro"),
+        ));
+    }
+
+    let project_dirs = get_project_dirs()?;
+    Ok((
+        project_dirs.config_dir().to_owned(),
+        project_dirs.cache_dir().to_owned(),
+    ))
 }
 
 /// Magro context.
@@ -44,41 +192,124 @@ fn get_project_dirs() -> anyhow::Result<ProjectDirs> {
 pub struct Context {
     /// User directories.
     user_dirs: UserDirs,
-    /// Config directory path.
+    /// Profile.
+    profile: Option<ProfileName>,
+    /// Base config directory path, shared among profiles.
     config_dir: PathBuf,
+    /// Base cache directory path, shared among profiles.
+    cache_dir: PathBuf,
+    /// Config directory path of the profile.
+    ///
+    /// This is the config directory itself for the default profile.
+    profile_config_dir: PathBuf,
     /// Config.
     config: Config,
+    /// Cache namespace, or `None` for the default cache file.
+    cache_namespace: Option<CacheNamespace>,
     /// Cache file path.
     cache_path: PathBuf,
     /// Lazily loaded cache.
     cache: OnceCell<Cache>,
+    /// Lazily loaded tags.
+    tags: OnceCell<Tags>,
+    /// Trash directory path.
+    trash_dir: PathBuf,
+    /// Lazily loaded trash.
+    trash: OnceCell<Trash>,
+    /// Directory of job journals of bulk operations.
+    jobs_dir: PathBuf,
+    /// Whether to never write the config, the cache, the tags, and the trash index.
+    read_only: bool,
 }
 
 impl Context {
     /// Creates a new context with default config path.
     #[inline]
     pub fn new() -> Result<Self, Error> {
-        let user_dirs = UserDirs::new()
-            .context("Failed to get user directory")
-            .map_err(Error::new)?;
+        Self::with_dirs(None, None)
+    }
+
+    /// Creates a new context with the given config and cache directories.
+    ///
+    /// Default directories are used for `None`.
+    /// See [`USE_XDG_ENV`] for the default directories.
+    #[inline]
+    pub fn with_dirs(
+        config_dir: Option<PathBuf>,
+        cache_dir: Option<PathBuf>,
+    ) -> Result<Self, Error> {
+        Self::with_profile(config_dir, cache_dir, None)
+    }
+
+    /// Creates a new context for the profile with the given config and cache directories.
+    ///
+    /// Default directories are used for `None`, and the default profile is
+    /// used if `profile` is `None`.
+    pub fn with_profile(
+        config_dir: Option<PathBuf>,
+        cache_dir: Option<PathBuf>,
+        profile: Option<ProfileName>,
+    ) -> Result<Self, Error> {
+        let user_dirs = UserDirs::new().ok_or(Error::UserDirs)?;
         log::debug!("Home directory: {:?}", user_dirs.home_dir());
-        let project_dirs = get_project_dirs().map_err(Error::new)?;
-        log::debug!("Config directory: {:?}", project_dirs.config_dir());
+        let default_dirs = match (&config_dir, &cache_dir) {
+            (Some(_), Some(_)) => None,
+            _ => Some(default_dirs(&user_dirs)?),
+        };
 
-        let config_dir = project_dirs.config_dir().to_owned();
-        let config = Config::from_dir_path(&config_dir)
-            .context("Failed to load config")
-            .map_err(Error::new)?;
+        let config_dir = config_dir.unwrap_or_else(|| {
+            default_dirs
+                .as_ref()
+                .expect("Should never fail: default dirs are available")
+                .0
+                .clone()
+        });
+        log::debug!("Config directory: {:?}", config_dir);
+        if let Some(profile) = &profile {
+            log::debug!("Profile: {}", profile);
+        }
+        let profile_config_dir = match &profile {
+            Some(profile) => profile.dir(&config_dir),
+            None => config_dir.clone(),
+        };
+        let config = {
+            let _span = timing::span("config load");
+            Config::from_dir_path(&config_dir, &profile_config_dir).map_err(Error::Config)?
+        };
 
-        let cache_dir = project_dirs.cache_dir();
-        let cache_path = cache_dir.join(DEFAULT_CACHE_RELPATH);
+        let cache_dir = cache_dir.unwrap_or_else(|| {
+            default_dirs
+                .as_ref()
+                .expect("Should never fail: default dirs are available")
+                .1
+                .clone()
+        });
+        let profile_cache_dir = match &profile {
+            Some(profile) => profile.dir(&cache_dir),
+            None => cache_dir.clone(),
+        };
+        log::debug!("Cache directory: {:?}", profile_cache_dir);
+        let cache_namespace = config.main().cache_namespace().cloned();
+        let cache_path = cache_path(&profile_cache_dir, cache_namespace.as_ref());
+        let trash_dir = profile_cache_dir.join(DEFAULT_TRASH_RELPATH);
+        let jobs_dir = profile_cache_dir.join(DEFAULT_JOBS_RELPATH);
+        let read_only = config.main().read_only();
 
         Ok(Self {
             user_dirs,
+            profile,
             config_dir,
+            cache_dir,
+            profile_config_dir,
             config,
+            cache_namespace,
             cache_path,
             cache: OnceCell::new(),
+            tags: OnceCell::new(),
+            trash_dir,
+            trash: OnceCell::new(),
+            jobs_dir,
+            read_only,
         })
     }
 
@@ -89,6 +320,96 @@ impl Context {
         self.user_dirs.home_dir()
     }
 
+    /// Returns the profile, or `None` for the default profile.
+    #[inline]
+    #[must_use]
+    pub fn profile(&self) -> Option<&ProfileName> {
+        self.profile.as_ref()
+    }
+
+    /// Returns the config directory.
+    ///
+    /// This is the base directory shared among profiles, not the directory
+    /// of the profile.
+    #[inline]
+    #[must_use]
+    pub fn config_dir(&self) -> &Path {
+        &self.config_dir
+    }
+
+    /// Returns the cache directory.
+    ///
+    /// This is the base directory shared among profiles, not the directory
+    /// of the profile.
+    #[inline]
+    #[must_use]
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache_dir
+    }
+
+    /// Returns the path to the cache file of the profile.
+    #[inline]
+    #[must_use]
+    pub fn cache_path(&self) -> &Path {
+        &self.cache_path
+    }
+
+    /// Returns the cache namespace, or `None` for the default cache file.
+    #[inline]
+    #[must_use]
+    pub fn cache_namespace(&self) -> Option<&CacheNamespace> {
+        self.cache_namespace.as_ref()
+    }
+
+    /// Sets the cache namespace, overriding `cache-namespace` in the main
+    /// config.
+    ///
+    /// `None` means the default cache file.
+    /// The cache loaded from the previous cache file is discarded.
+    pub fn set_cache_namespace(&mut self, namespace: Option<CacheNamespace>) {
+        let dir = self
+            .cache_path
+            .parent()
+            .expect("Should never fail: the cache path has the parent directory")
+            .to_owned();
+        self.cache_path = cache_path(&dir, namespace.as_ref());
+        log::debug!("Cache file: {:?}", self.cache_path);
+        self.cache_namespace = namespace;
+        self.cache = OnceCell::new();
+    }
+
+    /// Returns true if the context is in read-only mode.
+    ///
+    /// In read-only mode, the config, the cache, the tags, and the trash
+    /// index are never written, and operations trying to save them fail.
+    /// This is enabled by `read-only = true` in the main config.
+    #[inline]
+    #[must_use]
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Enables or disables read-only mode.
+    #[inline]
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Fails if the context is in read-only mode.
+    ///
+    /// Operations which modify repositories before saving the config or the
+    /// cache should call this beforehand, not to fail in the middle.
+    pub fn ensure_writable(&self) -> io::Result<()> {
+        if self.read_only {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "The operation needs to write the config or the cache, \
+                 but magro is in read-only mode",
+            ));
+        }
+        Ok(())
+    }
+
     /// Returns a reference to the config.
     #[inline]
     #[must_use]
@@ -106,23 +427,22 @@ impl Context {
     /// Saves the config if (possibly) dirty.
     #[inline]
     pub fn save_config_if_dirty(&mut self) -> io::Result<()> {
-        self.config.save_if_dirty(&self.config_dir)
+        if self.config.is_dirty() {
+            self.ensure_writable()?;
+        }
+        self.config.save_if_dirty()
     }
 
     /// Loads the cache if necessary, and returns the cache.
     #[inline]
     pub fn get_or_load_cache(&self) -> io::Result<&Cache> {
-        self.cache
-            .get_or_try_init(|| Cache::from_path(&self.cache_path))
+        self.cache.get_or_try_init(|| self.load_cache())
     }
 
     /// Loads the cache if necessary, and returns the cache.
     #[inline]
     pub fn get_or_load_cache_mut(&mut self) -> io::Result<&mut Cache> {
-        match self
-            .cache
-            .get_or_try_init(|| Cache::from_path(&self.cache_path))
-        {
+        match self.cache.get_or_try_init(|| self.load_cache()) {
             Ok(_) => Ok(self
                 .cache
                 .get_mut()
@@ -131,6 +451,407 @@ impl Context {
         }
     }
 
+    /// Loads the cache from the file.
+    fn load_cache(&self) -> io::Result<Cache> {
+        let _span = timing::span("cache load");
+        Cache::from_path(&self.cache_path)
+    }
+
+    /// Loads the latest cache from the file, modifies it, and saves it.
+    ///
+    /// The cache file is locked during the whole read-modify-write, so that
+    /// updates by other processes since the cache was loaded are kept.
+    /// This is preferred to modifying a copy of the cache and replacing the
+    /// whole cache, since only the touched entries are updated.
+    pub fn update_cache<F, R>(&mut self, f: F) -> io::Result<R>
+    where
+        F: FnOnce(&mut Cache) -> R,
+    {
+        self.update_cache_and_config(|cache, _| f(cache))
+    }
+
+    /// Loads the latest cache from the file, modifies it and the config, and
+    /// saves the cache.
+    ///
+    /// This is same as [`update_cache`][`Self::update_cache`], but the
+    /// function also receives the config, so that the config can be saved
+    /// while the cache file is locked.
+    fn update_cache_and_config<F, R>(&mut self, f: F) -> io::Result<R>
+    where
+        F: FnOnce(&mut Cache, &mut Config) -> R,
+    {
+        self.ensure_writable()?;
+        create_cache_dir(&self.cache_path, self.config().main().dir_mode())?;
+        let config = &mut self.config;
+        let (cache, result) = {
+            let _span = timing::span("cache update");
+            Cache::update_path(&self.cache_path, |cache| f(cache, config))?
+        };
+        self.cache = OnceCell::from(cache);
+        Ok(result)
+    }
+
+    /// Loads the tags if necessary, and returns the tags.
+    #[inline]
+    pub fn get_or_load_tags(&self) -> io::Result<&Tags> {
+        self.tags
+            .get_or_try_init(|| Tags::from_path(&self.tags_path()))
+    }
+
+    /// Loads the latest tags from the file, modifies them, and saves them.
+    ///
+    /// The tags file is locked during the whole read-modify-write, so that
+    /// updates by other processes since the tags were loaded are kept.
+    pub fn update_tags<F, R>(&mut self, f: F) -> io::Result<R>
+    where
+        F: FnOnce(&mut Tags) -> R,
+    {
+        self.ensure_writable()?;
+        if !self.profile_config_dir.is_dir() {
+            permissions::create_dir_all(&self.profile_config_dir, self.config.main().dir_mode())?;
+        }
+        let (tags, result) = Tags::update_path(&self.tags_path(), f)?;
+        self.tags = OnceCell::from(tags);
+        Ok(result)
+    }
+
+    /// Returns the path to the tags file.
+    #[inline]
+    #[must_use]
+    fn tags_path(&self) -> PathBuf {
+        self.profile_config_dir.join(DEFAULT_TAGS_RELPATH)
+    }
+
+    /// Returns the directory of snapshot manifests.
+    ///
+    /// See [`snapshot`][`crate::snapshot`] module.
+    #[inline]
+    #[must_use]
+    pub fn snapshots_dir(&self) -> PathBuf {
+        self.profile_config_dir.join(DEFAULT_SNAPSHOTS_RELPATH)
+    }
+
+    /// Returns the trash directory.
+    #[inline]
+    #[must_use]
+    pub fn trash_dir(&self) -> &Path {
+        &self.trash_dir
+    }
+
+    /// Returns the directory of job journals of bulk operations.
+    ///
+    /// See [`jobs`][`crate::jobs`] module.
+    #[inline]
+    #[must_use]
+    pub fn jobs_dir(&self) -> &Path {
+        &self.jobs_dir
+    }
+
+    /// Loads the trash if necessary, and returns the trash.
+    #[inline]
+    pub fn get_or_load_trash(&self) -> io::Result<&Trash> {
+        self.trash
+            .get_or_try_init(|| Trash::from_dir(&self.trash_dir))
+    }
+
+    /// Loads the latest trash index, modifies the trash, and saves the index.
+    ///
+    /// The index file is locked during the whole read-modify-write, so that
+    /// items put by other processes are kept.
+    /// The index is saved even if `f` fails in the middle, since items may be
+    /// already moved into or out of the trash.
+    pub fn update_trash<F, R>(&mut self, f: F) -> io::Result<R>
+    where
+        F: FnOnce(&mut Trash) -> R,
+    {
+        self.ensure_writable()?;
+        let (trash, result) = Trash::update_dir(&self.trash_dir, f)?;
+        self.trash = OnceCell::from(trash);
+        Ok(result)
+    }
+
+    /// Renames the collection, and saves the config and the cache.
+    ///
+    /// Everything is validated before any modification. The config is saved
+    /// while the cache file is locked, and the latest cache is updated, so
+    /// that concurrent updates of the cache are kept. If saving the cache
+    /// fails after the config is saved, the config is restored.
+    ///
+    /// The default collection is also updated if it is renamed.
+    /// Tags of repositories in the collection are also moved to the new name,
+    /// but failure on it is only warned.
+    pub fn rename_collection(
+        &mut self,
+        old_name: &CollectionName,
+        new_name: &CollectionName,
+    ) -> Result<(), RenameCollectionError> {
+        self.ensure_writable()
+            .map_err(RenameCollectionError::SaveConfig)?;
+        let collections = self.config.collections();
+        // Use the actual name, since it can differ from the given one when
+        // the names are matched loosely.
+        let old_name = &collections
+            .get(old_name)
+            .ok_or_else(|| RenameCollectionError::NotFound(old_name.clone()))?
+            .name()
+            .clone();
+        // Renaming to a loosely matching name (such as changing the case) is allowed.
+        let is_used = collections.get(new_name).map_or(false, |existing| {
+            existing.name() != old_name || new_name == old_name
+        });
+        if is_used {
+            return Err(RenameCollectionError::AlreadyExists(new_name.clone()));
+        }
+
+        let mut config_saved = false;
+        let updated = self.update_cache_and_config(|cache, config| {
+            rename_collection_in_config(config, old_name, new_name);
+            cache.rename_collection_repos(old_name, new_name.clone());
+            let saved = config.save_if_dirty();
+            if saved.is_ok() {
+                config_saved = true;
+            } else {
+                // Leave the cache unchanged.
+                rename_collection_in_config(config, new_name, old_name);
+                cache.rename_collection_repos(new_name, old_name.clone());
+            }
+            saved
+        });
+        match updated {
+            Ok(saved) => saved.map_err(RenameCollectionError::SaveConfig)?,
+            Err(e) => {
+                if config_saved {
+                    rename_collection_in_config(&mut self.config, new_name, old_name);
+                    if let Err(restore_err) = self.save_config_if_dirty() {
+                        log::error!("Failed to restore the config: {}", restore_err);
+                    }
+                }
+                return Err(RenameCollectionError::SaveCache(e));
+            }
+        }
+        // Tags are not essential for consistency of the config and the cache,
+        // so failure on them is only reported.
+        if let Err(e) = self.rename_collection_tags(old_name, new_name) {
+            log::warn!(
+                "Failed to rename the collection `{}` in the tags file: {}",
+                old_name,
+                e
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Merges the collection `from` into the collection `into`, and saves the
+    /// config and the cache.
+    ///
+    /// Cached repositories of `from` are added to `into`, and `from` is
+    /// removed from the config.
+    /// If `move_repos` is true, the repository directories are moved to the
+    /// same relative paths under the directory of `into`. Otherwise, the
+    /// directory of `from` should be inside the directory of `into`.
+    ///
+    /// Everything (including conflicts of moved repository paths) is
+    /// validated before any modification. The config is saved while the cache
+    /// file is locked, and the latest cache is updated, so that concurrent
+    /// updates of the cache are kept. If saving fails, moved repositories are
+    /// moved back and the config is restored.
+    /// The default collection is changed to `into` if it was `from`.
+    /// Tags of repositories are also moved, but failure on it is only warned.
+    pub fn merge_collection(
+        &mut self,
+        into: &CollectionName,
+        from: &CollectionName,
+        move_repos: bool,
+    ) -> Result<(), MergeCollectionError> {
+        self.ensure_writable()
+            .map_err(MergeCollectionError::SaveConfig)?;
+        let collections = self.config.collections();
+        let into_collection = collections
+            .get(into)
+            .ok_or_else(|| MergeCollectionError::NotFound(into.clone()))?;
+        let from_collection = collections
+            .get(from)
+            .ok_or_else(|| MergeCollectionError::NotFound(from.clone()))?
+            .clone();
+        // Use the actual names, since they can differ from the given ones when
+        // the names are matched loosely.
+        let into = &into_collection.name().clone();
+        let from = &from_collection.name().clone();
+        if into == from {
+            return Err(MergeCollectionError::SameCollection(into.clone()));
+        }
+        let into_root = into_collection.abspath(self).into_owned();
+        let from_root = from_collection.abspath(self).into_owned();
+        let prefix = if move_repos {
+            PathBuf::new()
+        } else {
+            from_root
+                .strip_prefix(&into_root)
+                .map_err(|_| MergeCollectionError::NotNested {
+                    from: from.clone(),
+                    into: into.clone(),
+                })?
+                .to_owned()
+        };
+        let dir_mode = self.config.main().dir_mode();
+
+        let cache = self
+            .get_or_load_cache_mut()
+            .map_err(MergeCollectionError::LoadCache)?;
+        let old_into_cache = cache.collection_repos(into).cloned();
+        let old_from_cache = cache.collection_repos(from).cloned();
+        let entries = old_from_cache
+            .iter()
+            .flat_map(|coll_cache| coll_cache.repositories())
+            .map(|entry| entry.map_ref_path(|path| prefix.join(path)))
+            .collect::<Vec<_>>();
+
+        let is_cached_in_into = |path: &Path| {
+            old_into_cache
+                .as_ref()
+                .map_or(false, |coll_cache| coll_cache.get(path).is_some())
+        };
+        let entries = if move_repos {
+            // Detect conflicts.
+            let conflicts = entries
+                .iter()
+                .map(|entry| entry.path())
+                .filter(|path| is_cached_in_into(path) || into_root.join(repo_root(path)).exists())
+                .map(ToOwned::to_owned)
+                .collect::<Vec<_>>();
+            if !conflicts.is_empty() {
+                return Err(MergeCollectionError::Conflict(conflicts));
+            }
+            entries
+        } else {
+            // Repositories with the same path are the same repositories
+            // (cached twice as the collections are nested).
+            entries
+                .into_iter()
+                .filter(|entry| !is_cached_in_into(entry.path()))
+                .collect()
+        };
+
+        // Move the repositories.
+        let mut moved = Vec::new();
+        if move_repos {
+            for entry in &entries {
+                let relpath = repo_root(entry.path());
+                let src = from_root.join(relpath);
+                let dest = into_root.join(relpath);
+                log::debug!("Moving {:?} to {:?}", src, dest);
+                let result = match dest.parent() {
+                    Some(parent) => permissions::create_dir_all(parent, dir_mode),
+                    None => Ok(()),
+                }
+                .and_then(|_| relocate::move_dir(&src, &dest));
+                if let Err(e) = result {
+                    move_back(&moved);
+                    return Err(MergeCollectionError::Move {
+                        from: src,
+                        to: dest,
+                        source: e,
+                    });
+                }
+                moved.push((src, dest));
+            }
+        }
+
+        // Update the config and the latest cache, and save the config while
+        // the cache file is locked.
+        let old_default = self.config.default_collection().cloned();
+        let restore_config = |config: &mut Config| {
+            config.collections_mut().insert(from_collection.clone());
+            config.set_default_collection(old_default.clone());
+        };
+        let mut config_saved = false;
+        let updated = self.update_cache_and_config(|cache, config| {
+            config.collections_mut().remove(from);
+            if old_default.as_ref() == Some(from) {
+                config.set_default_collection(Some(into.clone()));
+            }
+            let saved = config.save_if_dirty();
+            if let Err(e) = saved {
+                // Leave the cache unchanged.
+                restore_config(config);
+                return Err(e);
+            }
+            config_saved = true;
+
+            cache.remove_collection_repos_cache(from);
+            let mut into_cache = cache.collection_repos(into).cloned().unwrap_or_default();
+            into_cache.extend(entries);
+            // Force the next refresh, as the directory can be modified.
+            into_cache.set_fingerprint(None);
+            cache.cache_collection_repos(into.clone(), into_cache);
+            Ok(())
+        });
+        match updated {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                move_back(&moved);
+                return Err(MergeCollectionError::SaveConfig(e));
+            }
+            Err(e) => {
+                if config_saved {
+                    restore_config(&mut self.config);
+                    if let Err(restore_err) = self.save_config_if_dirty() {
+                        log::error!("Failed to restore the config: {}", restore_err);
+                    }
+                }
+                move_back(&moved);
+                return Err(MergeCollectionError::SaveCache(e));
+            }
+        }
+        // Tags are not essential for consistency of the config and the cache,
+        // so failure on them is only reported.
+        if let Err(e) = self.merge_collection_tags(from, into, &prefix) {
+            log::warn!(
+                "Failed to move tags of the collection `{}` in the tags file: {}",
+                from,
+                e
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Moves the tags of the collection into another, and saves them if modified.
+    fn merge_collection_tags(
+        &mut self,
+        from: &CollectionName,
+        into: &CollectionName,
+        prefix: &Path,
+    ) -> io::Result<()> {
+        if self.has_collection_tags(from)? {
+            self.update_tags(|tags| tags.merge_collection(from, into, prefix))?;
+        }
+        Ok(())
+    }
+
+    /// Renames the collection in the tags, and saves them if modified.
+    fn rename_collection_tags(
+        &mut self,
+        old_name: &CollectionName,
+        new_name: &CollectionName,
+    ) -> io::Result<()> {
+        if self.has_collection_tags(old_name)? {
+            self.update_tags(|tags| tags.rename_collection(old_name, new_name.clone()))?;
+        }
+        Ok(())
+    }
+
+    /// Returns true if any repository in the collection is tagged.
+    ///
+    /// This is used to avoid creating the tags file needlessly.
+    fn has_collection_tags(&self, collection: &CollectionName) -> io::Result<bool> {
+        Ok(self
+            .get_or_load_tags()?
+            .iter()
+            .any(|(name, _, _)| name == collection))
+    }
+
     /// Returns the cache if it is already loaded.
     #[inline]
     pub fn get_cache(&self) -> Option<&Cache> {
@@ -140,31 +861,58 @@ impl Context {
     /// Saves the given cache.
     #[inline]
     pub fn save_cache(&self) -> io::Result<()> {
+        self.ensure_writable()?;
         let cache = self
             .get_or_load_cache()
             .map_or_else(|_| Cow::Owned(Default::default()), Cow::Borrowed);
-        save_cache(&self.cache_path, &cache)
+        save_cache(&self.cache_path, &cache, self.config().main().dir_mode())
+    }
+}
+
+/// Renames the collection in the config, without saving it.
+///
+/// The old collection should exist, and the new name should not be used.
+fn rename_collection_in_config(
+    config: &mut Config,
+    old_name: &CollectionName,
+    new_name: &CollectionName,
+) {
+    let collections = config.collections_mut();
+    let mut collection = collections
+        .remove(old_name)
+        .expect("Should never fail: the collection is validated to exist");
+    collection.set_name(new_name.clone());
+    collections.insert(collection);
+    if config.default_collection() == Some(old_name) {
+        config.set_default_collection(Some(new_name.clone()));
+    }
+}
+
+/// Moves the directories back to the original paths, in reverse order.
+///
+/// Failures are only logged.
+fn move_back(moved: &[(PathBuf, PathBuf)]) {
+    for (src, dest) in moved.iter().rev() {
+        if let Err(e) = relocate::move_dir(dest, src) {
+            log::error!("Failed to move {:?} back to {:?}: {}", dest, src, e);
+        }
     }
 }
 
 /// Saves a cache to the given path.
-fn save_cache(path: &Path, cache: &Cache) -> io::Result<()> {
-    use serde::Serialize;
-
-    let content = {
-        let mut content = String::new();
-        let mut ser = toml::Serializer::new(&mut content);
-        //ser.pretty_array(true);
-        // This is expected to always success, because the config is valid and
-        // the serialization does not perform I/O.
-        cache
-            .serialize(&mut ser)
-            .expect("Default cache data should be serializable");
-        content
-    };
+///
+/// The cache directory is created with the given mode if it does not exist.
+fn save_cache(path: &Path, cache: &Cache, dir_mode: Option<DirMode>) -> io::Result<()> {
+    create_cache_dir(path, dir_mode)?;
+    let _span = timing::span("cache save");
+    lock_fs::write_with(path, |out| cache.write_toml(out))
+}
+
+/// Creates the directory of the cache file with the given mode if it does not exist.
+fn create_cache_dir(path: &Path, dir_mode: Option<DirMode>) -> io::Result<()> {
     let cache_dir = path.parent().ok_or_else(|| {
         io::Error::new(
-            io::ErrorKind::Other,
+            io::ErrorKind::InvalidInput,
             format!("Attempt to save cache file to invalid path {:?}", path),
         )
     })?;
@@ -173,7 +921,7 @@ fn save_cache(path: &Path, cache: &Cache) -> io::Result<()> {
             "Creating a directory {:?} for to save cache file",
             cache_dir
         );
-        fs::DirBuilder::new().recursive(true).create(cache_dir)?;
+        permissions::create_dir_all(cache_dir, dir_mode)?;
     }
-    lock_fs::write(path, &content)
+    Ok(())
 }
@@ -0,0 +1,199 @@
+//! Credentials for remote repositories.
+//!
+//! When a remote requires a username and a password (or a token) and no git
+//! credential helpers provide them, or an SSH private key file is encrypted,
+//! the prompt registered by [`set_prompt`] is used to ask the user.
+//!
+//! Without `libgit2` feature, the `git` command asks the user by itself and
+//! the registered prompt is not used.
+#![cfg_attr(not(feature = "libgit2"), allow(dead_code))]
+
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+
+use once_cell::sync::OnceCell;
+use thiserror::Error as ThisError;
+
+/// Registered prompt.
+static PROMPT: OnceCell<Prompt> = OnceCell::new();
+
+/// Username and password (or token).
+#[derive(Clone, PartialEq, Eq)]
+pub struct UserPass {
+    /// Username.
+    username: String,
+    /// Password or token.
+    password: String,
+}
+
+impl UserPass {
+    /// Creates a new credential.
+    #[inline]
+    #[must_use]
+    pub fn new(username: String, password: String) -> Self {
+        Self { username, password }
+    }
+
+    /// Returns the username.
+    #[inline]
+    #[must_use]
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    /// Returns the password or token.
+    #[inline]
+    #[must_use]
+    pub fn password(&self) -> &str {
+        &self.password
+    }
+}
+
+impl fmt::Debug for UserPass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UserPass")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+/// Prompt for credentials.
+pub trait CredentialPrompt: Send + Sync {
+    /// Asks the user for the credential for the URL.
+    ///
+    /// `username` is the username specified by the URL, if available.
+    /// Returns `None` if prompting is unavailable or cancelled.
+    fn prompt_userpass(&self, url: &str, username: Option<&str>) -> Option<UserPass>;
+
+    /// Asks the user for the passphrase of the SSH private key file.
+    ///
+    /// Returns `None` if prompting is unavailable or cancelled.
+    /// The default implementation always returns `None`.
+    #[inline]
+    fn prompt_passphrase(&self, key_path: &Path) -> Option<String> {
+        let _ = key_path;
+        None
+    }
+}
+
+/// Registered prompt and the credential cache.
+struct Prompt {
+    /// Prompt.
+    prompt: Box<dyn CredentialPrompt>,
+    /// Cached credentials, if caching is enabled.
+    ///
+    /// This also serializes prompts from multiple threads.
+    cache: Mutex<Option<Cache>>,
+}
+
+/// Cached credentials.
+#[derive(Default)]
+struct Cache {
+    /// Credentials for the hosts.
+    userpass: HashMap<String, UserPass>,
+    /// Passphrases for the SSH private key files.
+    passphrases: HashMap<PathBuf, String>,
+}
+
+/// Error on registering the prompt twice.
+#[derive(Debug, Clone, ThisError)]
+#[error("Credential prompt is already registered")]
+pub struct SetPromptError(());
+
+/// Registers the prompt for credentials.
+///
+/// If `cache` is true, credentials entered by the user are reused for the
+/// remotes on the same host until the process exits.
+///
+/// The prompt can be registered only once.
+pub fn set_prompt(prompt: Box<dyn CredentialPrompt>, cache: bool) -> Result<(), SetPromptError> {
+    PROMPT
+        .set(Prompt {
+            prompt,
+            cache: Mutex::new(cache.then(Cache::default)),
+        })
+        .map_err(|_| SetPromptError(()))
+}
+
+/// Returns the credential for the URL, from the cache or the prompt.
+///
+/// If `retry` is true, the cached credential for the URL is considered
+/// rejected and the user is asked again.
+pub(crate) fn userpass(url: &str, username: Option<&str>, retry: bool) -> Option<UserPass> {
+    let prompt = PROMPT.get()?;
+    let mut cache = prompt.cache.lock().unwrap_or_else(PoisonError::into_inner);
+    let key = cache_key(url);
+    if let Some(cache) = cache.as_mut() {
+        if retry {
+            cache.userpass.remove(key);
+        } else if let Some(cred) = cache.userpass.get(key) {
+            log::debug!("Using the cached credential for {:?}", key);
+            return Some(cred.clone());
+        }
+    }
+
+    let cred = prompt.prompt.prompt_userpass(url, username)?;
+    if let Some(cache) = cache.as_mut() {
+        cache.userpass.insert(key.to_owned(), cred.clone());
+    }
+    Some(cred)
+}
+
+/// Returns the passphrase for the SSH private key file, from the cache or the prompt.
+pub(crate) fn passphrase(key_path: &Path) -> Option<String> {
+    let prompt = PROMPT.get()?;
+    let mut cache = prompt.cache.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(passphrase) = cache
+        .as_ref()
+        .and_then(|cache| cache.passphrases.get(key_path))
+    {
+        log::debug!("Using the cached passphrase for {:?}", key_path);
+        return Some(passphrase.clone());
+    }
+
+    let passphrase = prompt.prompt.prompt_passphrase(key_path)?;
+    if let Some(cache) = cache.as_mut() {
+        cache
+            .passphrases
+            .insert(key_path.to_owned(), passphrase.clone());
+    }
+    Some(passphrase)
+}
+
+/// Returns the key of the credential cache for the URL.
+///
+/// Same as git does by default, the credential is shared by the scheme and
+/// the host (i.e. the path is ignored).
+fn cache_key(url: &str) -> &str {
+    let authority_start = match url.find("://") {
+        Some(pos) => pos + 3,
+        None => return url,
+    };
+    match url[authority_start..].find('/') {
+        Some(pos) => &url[..(authority_start + pos)],
+        None => url,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_key_ignores_path() {
+        assert_eq!(
+            cache_key("https://example.com/foo/bar.git"),
+            "https://example.com"
+        );
+        assert_eq!(
+            cache_key("https://user@example.com:8443/foo"),
+            "https://user@example.com:8443"
+        );
+        assert_eq!(cache_key("https://example.com"), "https://example.com");
+    }
+}
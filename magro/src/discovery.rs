@@ -1,41 +1,102 @@
 //! Repositories discovery.
 
 use std::{
-    fs, io, iter,
+    fs, io,
     path::{Path, PathBuf},
 };
 
-use anyhow::anyhow;
-use git2::{Repository, RepositoryOpenFlags};
+use serde::{Deserialize, Serialize};
 use thiserror::Error as ThisError;
 
 use crate::vcs::Vcs;
 
 /// Repository discovery error.
 #[derive(Debug, ThisError)]
-#[error(transparent)]
-pub struct Error {
-    /// Source error.
-    source: anyhow::Error,
+#[non_exhaustive]
+pub enum Error {
+    /// The root directory is a broken symlink.
+    #[error("Collection directory {} is a broken symlink", path.display())]
+    BrokenSymlink {
+        /// Path to the root directory.
+        path: PathBuf,
+    },
+    /// Failed to access the root directory.
+    #[error("Failed to access the collection directory {}", path.display())]
+    RootAccess {
+        /// Path to the root directory.
+        path: PathBuf,
+        /// Source error.
+        #[source]
+        source: io::Error,
+    },
+    /// Failed to traverse a directory.
+    #[error("Failed to traverse the directory {:?}", path)]
+    Traverse {
+        /// Path to the entry which caused the error, if available.
+        path: Option<PathBuf>,
+        /// Source error.
+        #[source]
+        source: io::Error,
+    },
 }
 
 impl Error {
-    /// Creates a new error.
-    #[inline]
+    /// Creates a new traversal error.
+    #[must_use]
+    fn traverse(e: walkdir::Error) -> Self {
+        Self::Traverse {
+            path: e.path().map(Path::to_owned),
+            source: e.into(),
+        }
+    }
+
+    /// Returns the path related to the error, if available.
+    #[must_use]
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::BrokenSymlink { path } | Self::RootAccess { path, .. } => Some(path),
+            Self::Traverse { path, .. } => path.as_deref(),
+        }
+    }
+
+    /// Returns the kind of the underlying I/O error, if available.
     #[must_use]
-    fn new(e: impl Into<anyhow::Error>) -> Self {
-        Self { source: e.into() }
+    pub fn io_error_kind(&self) -> Option<io::ErrorKind> {
+        match self {
+            Self::BrokenSymlink { .. } => None,
+            Self::RootAccess { source, .. } | Self::Traverse { source, .. } => Some(source.kind()),
+        }
+    }
+}
+
+/// VCS to record for colocated Jujutsu repositories.
+///
+/// Colocated repositories have both `.jj` and `.git` directories in the same
+/// working directory, and are recorded only once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColocatedPreference {
+    /// Records as git repositories (`.git` directories).
+    Git,
+    /// Records as Jujutsu repositories (`.jj` directories).
+    Jj,
+}
+
+impl Default for ColocatedPreference {
+    #[inline]
+    fn default() -> Self {
+        Self::Git
     }
+}
 
-    /// Creates a new error with the given context.
+impl ColocatedPreference {
+    /// Returns the config value.
     #[inline]
     #[must_use]
-    fn context<C>(e: impl Into<anyhow::Error>, context: C) -> Self
-    where
-        C: std::fmt::Display + Send + Sync + 'static,
-    {
-        Self {
-            source: e.into().context(context),
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Git => "git",
+            Self::Jj => "jj",
         }
     }
 }
@@ -48,6 +109,10 @@ pub struct RepoEntry {
     /// Path.
     ///
     /// For git, `.git` directory or `*.git` directory.
+    /// For Mercurial, `.hg` directory.
+    /// For Jujutsu, `.jj` directory.
+    /// For Pijul, `.pijul` directory.
+    /// For Darcs, `_darcs` directory.
     path: PathBuf,
 }
 
@@ -85,10 +150,16 @@ impl RepoEntry {
 }
 
 /// Repositories seeker, an iterator of repositories under a directory.
+///
+/// Symbolic links under the root directory are not followed.
+/// On Windows, junctions and other reparse points are treated in the same way
+/// as symbolic links, so that links to ancestors do not cause infinite descent.
 #[derive(Debug)]
 pub struct RepoSeeker {
     /// Walkdir iterator.
     dir_walker: walkdir::IntoIter,
+    /// VCS to record for colocated Jujutsu repositories.
+    colocated: ColocatedPreference,
 }
 
 impl RepoSeeker {
@@ -106,7 +177,7 @@ impl RepoSeeker {
     fn new_impl(root_dir: &Path) -> Result<Option<Self>, Error> {
         if !root_dir.exists() {
             // Check if the directory is symlink.
-            return match fs::symlink_metadata(&root_dir) {
+            return match fs::symlink_metadata(root_dir) {
                 Ok(meta) => {
                     assert!(
                         meta.file_type().is_symlink(),
@@ -115,37 +186,61 @@ impl RepoSeeker {
                     );
 
                     // Broken symlink.
-                    Err(Error::new(anyhow!(
-                        "Collection directory {} is a broken symlink",
-                        root_dir.display()
-                    )))
+                    Err(Error::BrokenSymlink {
+                        path: root_dir.to_owned(),
+                    })
                 }
                 Err(e) => match e.kind() {
                     io::ErrorKind::NotFound => Ok(None),
-                    _ => Err(Error::new(anyhow!(
-                        "Failed to access the collection directory {}",
-                        root_dir.display()
-                    ))),
+                    _ => Err(Error::RootAccess {
+                        path: root_dir.to_owned(),
+                        source: e,
+                    }),
                 },
             };
         }
 
-        let mut dir_walker = walkdir::WalkDir::new(root_dir).into_iter();
+        let mut dir_walker = walkdir::WalkDir::new(root_dir)
+            .follow_links(false)
+            .into_iter();
         // Skip the root directory itself.
         match dir_walker.next() {
             None => unreachable!("The first direntry should be the collection dierctory itself"),
             Some(Ok(entry)) => {
                 debug_assert_eq!(entry.path(), root_dir);
             }
-            Some(Err(e)) => {
-                return Err(Error::context(
-                    e,
-                    format!("Failed to traverse the directory {:?}", root_dir),
-                ));
-            }
+            Some(Err(e)) => return Err(Error::traverse(e)),
         }
 
-        Ok(Some(Self { dir_walker }))
+        Ok(Some(Self {
+            dir_walker,
+            colocated: ColocatedPreference::default(),
+        }))
+    }
+
+    /// Sets the VCS to record for colocated Jujutsu repositories.
+    ///
+    /// Git is preferred by default.
+    #[inline]
+    #[must_use]
+    pub fn colocated(mut self, preference: ColocatedPreference) -> Self {
+        self.colocated = preference;
+        self
+    }
+
+    /// Returns the preferred entry if the working directory is a colocated
+    /// Jujutsu repository.
+    fn colocated_entry(&self, workdir: &Path) -> Option<RepoEntry> {
+        let jjdir = workdir.join(".jj");
+        let gitdir = workdir.join(".git");
+        if Vcs::Jj.workdir(&jjdir).is_err() || Vcs::Git.workdir(&gitdir).is_err() {
+            return None;
+        }
+        log::trace!("{:?} is a colocated Jujutsu repository", workdir);
+        Some(match self.colocated {
+            ColocatedPreference::Git => RepoEntry::new(Vcs::Git, gitdir),
+            ColocatedPreference::Jj => RepoEntry::new(Vcs::Jj, jjdir),
+        })
     }
 
     /// Seeks the next repository, and returns it if found.
@@ -154,13 +249,18 @@ impl RepoSeeker {
             let entry = match self.dir_walker.next() {
                 None => return Ok(None),
                 Some(Ok(v)) => v,
-                Some(Err(e)) => return Err(Error::new(e)),
+                Some(Err(e)) => return Err(Error::traverse(e)),
             };
 
             if !entry.file_type().is_dir() {
                 // Not a directory.
                 continue;
             }
+            if is_reparse_point(&entry) {
+                log::trace!("Skipping reparse point {:?}", entry.path());
+                self.dir_walker.skip_current_dir();
+                continue;
+            }
             let path = entry.path();
             let filename = entry.path().file_name().expect(
                 "The DirEntry points to a descendant of the target directory, \
@@ -168,17 +268,16 @@ impl RepoSeeker {
             );
 
             // Check if the directory is a `.git` directory or a bare repository.
-            if filename == ".git" || path.extension().map_or(false, |ext| ext == ".git") {
-                match test_git_directory(path) {
-                    Ok(repo) => {
+            if filename == ".git" || path.extension().map_or(false, |ext| ext == "git") {
+                match Vcs::Git.workdir(path) {
+                    Ok(workdir) => {
                         // Get out of `.git` directory.
                         self.dir_walker.skip_current_dir();
 
-                        let workdir = repo.workdir();
                         let parent = path
                             .parent()
                             .expect("`path` has the seek root directory as its ancestor");
-                        if workdir == Some(parent) {
+                        if workdir.as_deref() == Some(parent) {
                             log::trace!(
                                 "Skipping {:?} as it is the working directory of {:?}",
                                 parent,
@@ -186,6 +285,9 @@ impl RepoSeeker {
                             );
                             // Get out of working directory of the repository.
                             self.dir_walker.skip_current_dir();
+                            if let Some(colocated) = self.colocated_entry(parent) {
+                                return Ok(Some(colocated));
+                            }
                         }
                         return Ok(Some(RepoEntry::new(Vcs::Git, entry.into_path())));
                     }
@@ -197,6 +299,64 @@ impl RepoSeeker {
                         );
                     }
                 }
+            } else if filename == ".hg" {
+                match Vcs::Hg.workdir(path) {
+                    Ok(_) => {
+                        // Get out of `.hg` directory.
+                        self.dir_walker.skip_current_dir();
+                        // Get out of working directory of the repository.
+                        self.dir_walker.skip_current_dir();
+                        return Ok(Some(RepoEntry::new(Vcs::Hg, entry.into_path())));
+                    }
+                    Err(e) => {
+                        log::debug!("Directory {:?} is not a Mercurial directory: {}", path, e);
+                    }
+                }
+            } else if filename == ".jj" {
+                match Vcs::Jj.workdir(path) {
+                    Ok(_) => {
+                        // Get out of `.jj` directory.
+                        self.dir_walker.skip_current_dir();
+                        // Get out of working directory of the repository.
+                        self.dir_walker.skip_current_dir();
+                        let parent = path
+                            .parent()
+                            .expect("`path` has the seek root directory as its ancestor");
+                        if let Some(colocated) = self.colocated_entry(parent) {
+                            return Ok(Some(colocated));
+                        }
+                        return Ok(Some(RepoEntry::new(Vcs::Jj, entry.into_path())));
+                    }
+                    Err(e) => {
+                        log::debug!("Directory {:?} is not a Jujutsu directory: {}", path, e);
+                    }
+                }
+            } else if filename == ".pijul" {
+                match Vcs::Pijul.workdir(path) {
+                    Ok(_) => {
+                        // Get out of `.pijul` directory.
+                        self.dir_walker.skip_current_dir();
+                        // Get out of working directory of the repository.
+                        self.dir_walker.skip_current_dir();
+                        return Ok(Some(RepoEntry::new(Vcs::Pijul, entry.into_path())));
+                    }
+                    Err(e) => {
+                        log::debug!("Directory {:?} is not a Pijul directory: {}", path, e);
+                    }
+                }
+            } else if filename == "_darcs" {
+                match Vcs::Darcs.workdir(path) {
+                    Ok(_) => {
+                        // Get out of `_darcs` directory.
+                        self.dir_walker.skip_current_dir();
+                        // Get out of working directory of the repository.
+                        self.dir_walker.skip_current_dir();
+                        return Ok(Some(RepoEntry::new(Vcs::Darcs, entry.into_path())));
+                    }
+                    Err(e) => {
+                        log::debug!("Directory {:?} is not a Darcs directory: {}", path, e);
+                    }
+                }
             }
         }
     }
@@ -211,13 +371,197 @@ impl Iterator for RepoSeeker {
     }
 }
 
-/// Tests if the directory is a git directory.
+/// Returns true if the entry is a reparse point (such as a junction).
+///
+/// Reparse points which are not reported as symlinks by `std` (for example,
+/// junctions on older toolchains and mount points of volumes) are detected
+/// here.
+#[cfg(windows)]
+fn is_reparse_point(entry: &walkdir::DirEntry) -> bool {
+    use std::os::windows::fs::MetadataExt;
+
+    /// `FILE_ATTRIBUTE_REPARSE_POINT` in Win32 API.
+    const FILE_ATTRIBUTE_REPARSE_POINT: u32 = 0x400;
+
+    match entry.metadata() {
+        Ok(meta) => meta.file_attributes() & FILE_ATTRIBUTE_REPARSE_POINT != 0,
+        Err(e) => {
+            log::debug!("Failed to get metadata of {:?}: {}", entry.path(), e);
+            false
+        }
+    }
+}
+
+/// Returns true if the entry is a reparse point (such as a junction).
+///
+/// Always returns false on non-Windows platforms.
+#[cfg(not(windows))]
 #[inline]
-fn test_git_directory(gitdir: &Path) -> Result<Repository, git2::Error> {
-    // NO_SEARCH: No need of extra traversal because we already have
-    // candidate path of the git directory.
-    // NO_DOTGIT: No need of appending `/.git` because we already have
-    // `.git` directory path.
-    let open_flags = RepositoryOpenFlags::NO_SEARCH | RepositoryOpenFlags::NO_DOTGIT;
-    Repository::open_ext(&gitdir, open_flags, iter::empty::<&str>())
+fn is_reparse_point(_entry: &walkdir::DirEntry) -> bool {
+    false
+}
+
+#[cfg(all(test, feature = "libgit2"))]
+mod tests {
+    use super::*;
+
+    /// Returns the sorted repository paths found under the directory.
+    fn seek_all(root: &Path) -> Vec<PathBuf> {
+        let mut found = RepoSeeker::new(root)
+            .expect("Should be able to seek")
+            .expect("Root directory should exist")
+            .map(|entry| entry.expect("Should not fail").into_path())
+            .collect::<Vec<_>>();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn hg_repositories_are_found() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        git2::Repository::init(root.join("git-repo")).unwrap();
+        let hgdir = root.join("hg-repo").join(".hg");
+        fs::create_dir_all(hgdir.join("store")).unwrap();
+        fs::write(hgdir.join("requires"), "revlogv1\nstore\n").unwrap();
+        // Not a Mercurial directory.
+        fs::create_dir_all(root.join("not-repo").join(".hg")).unwrap();
+
+        assert_eq!(
+            seek_all(root),
+            [root.join("git-repo").join(".git"), hgdir.clone()]
+        );
+        let entry = RepoSeeker::new(root)
+            .unwrap()
+            .unwrap()
+            .map(Result::unwrap)
+            .find(|entry| entry.path() == hgdir)
+            .unwrap();
+        assert_eq!(entry.vcs(), Vcs::Hg);
+    }
+
+    #[test]
+    fn pijul_repositories_are_found() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        let pijuldir = root.join("pijul-repo").join(".pijul");
+        fs::create_dir_all(pijuldir.join("pristine")).unwrap();
+        // Not a Pijul directory.
+        fs::create_dir_all(root.join("not-repo").join(".pijul")).unwrap();
+
+        let entries = RepoSeeker::new(root)
+            .unwrap()
+            .unwrap()
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        assert_eq!(entries, [RepoEntry::new(Vcs::Pijul, pijuldir)]);
+    }
+
+    /// Creates a `.jj` directory in the working directory.
+    fn create_jj_dir(workdir: &Path) -> PathBuf {
+        let jjdir = workdir.join(".jj");
+        fs::create_dir_all(jjdir.join("repo")).unwrap();
+        fs::create_dir_all(jjdir.join("working_copy")).unwrap();
+        jjdir
+    }
+
+    #[test]
+    fn jj_repositories_are_found() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        let jjdir = create_jj_dir(&root.join("jj-repo"));
+        // Not a Jujutsu directory.
+        fs::create_dir_all(root.join("not-repo").join(".jj")).unwrap();
+
+        let entries = RepoSeeker::new(root)
+            .unwrap()
+            .unwrap()
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        assert_eq!(entries, [RepoEntry::new(Vcs::Jj, jjdir)]);
+    }
+
+    #[test]
+    fn colocated_jj_repositories_are_found_once() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        let workdir = root.join("colocated");
+        git2::Repository::init(&workdir).unwrap();
+        let jjdir = create_jj_dir(&workdir);
+
+        let entries = RepoSeeker::new(root)
+            .unwrap()
+            .unwrap()
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        assert_eq!(entries, [RepoEntry::new(Vcs::Git, workdir.join(".git"))]);
+
+        let entries = RepoSeeker::new(root)
+            .unwrap()
+            .unwrap()
+            .colocated(ColocatedPreference::Jj)
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        assert_eq!(entries, [RepoEntry::new(Vcs::Jj, jjdir)]);
+    }
+
+    #[test]
+    fn darcs_repositories_are_found() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        let darcsdir = root.join("darcs-repo").join("_darcs");
+        fs::create_dir_all(darcsdir.join("patches")).unwrap();
+        // Not a Darcs directory.
+        fs::create_dir_all(root.join("not-repo").join("_darcs")).unwrap();
+
+        let entries = RepoSeeker::new(root)
+            .unwrap()
+            .unwrap()
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        assert_eq!(entries, [RepoEntry::new(Vcs::Darcs, darcsdir)]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_not_followed() {
+        use std::os::unix::fs::symlink;
+
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        git2::Repository::init(root.join("a/repo")).unwrap();
+        // Link to an ancestor, and link to a repository.
+        symlink(root, root.join("a/loop")).unwrap();
+        symlink(root.join("a/repo"), root.join("link")).unwrap();
+
+        assert_eq!(seek_all(root), [root.join("a/repo/.git")]);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn junctions_are_not_followed() {
+        use std::process::Command;
+
+        /// Creates a junction at `link` pointing to `target`.
+        fn junction(link: &Path, target: &Path) {
+            let status = Command::new("cmd")
+                .arg("/C")
+                .arg("mklink")
+                .arg("/J")
+                .arg(link)
+                .arg(target)
+                .status()
+                .unwrap();
+            assert!(status.success(), "Failed to create a junction {:?}", link);
+        }
+
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        git2::Repository::init(root.join("a").join("repo")).unwrap();
+        // Junction to an ancestor, and junction to a repository.
+        junction(&root.join("a").join("loop"), root);
+        junction(&root.join("link"), &root.join("a").join("repo"));
+
+        assert_eq!(seek_all(root), [root.join("a").join("repo").join(".git")]);
+    }
 }
@@ -0,0 +1,124 @@
+//! Integration with software forges.
+//!
+//! This module is available only when `forge` feature is enabled.
+
+use std::time::Duration;
+
+use serde::Deserialize;
+use thiserror::Error as ThisError;
+
+pub mod github;
+
+/// Forge operation error.
+#[derive(Debug, ThisError)]
+#[non_exhaustive]
+pub enum Error {
+    /// The server responded with an error status.
+    #[error("HTTP error {status} for {url}: {message}")]
+    Http {
+        /// URL of the request.
+        url: String,
+        /// HTTP status code.
+        status: u16,
+        /// Error message.
+        message: String,
+    },
+    /// The rate limit is exceeded and the reset is too far to wait for.
+    #[error("Rate limit exceeded (resets in {} seconds)", reset_after.as_secs())]
+    RateLimited {
+        /// Duration until the rate limit is reset.
+        reset_after: Duration,
+    },
+    /// Network or other transport error.
+    #[error("Failed to send request to {url}")]
+    Transport {
+        /// URL of the request.
+        url: String,
+        /// Source error.
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync + 'static>,
+    },
+    /// Failed to decode the response.
+    #[error("Failed to decode the response from {url}")]
+    Decode {
+        /// URL of the request.
+        url: String,
+        /// Source error.
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+/// A repository hosted on a forge.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct RemoteRepo {
+    /// Full name of the repository (such as `owner/name`).
+    full_name: String,
+    /// HTTPS clone URL.
+    clone_url: String,
+    /// SSH clone URL.
+    ssh_url: String,
+    /// Description.
+    #[serde(default)]
+    description: Option<String>,
+    /// Whether the repository is archived.
+    #[serde(default)]
+    archived: bool,
+    /// Whether the repository is a fork.
+    #[serde(default)]
+    fork: bool,
+    /// Number of stars.
+    #[serde(default)]
+    stargazers_count: u64,
+}
+
+impl RemoteRepo {
+    /// Returns the full name of the repository (such as `owner/name`).
+    #[inline]
+    #[must_use]
+    pub fn full_name(&self) -> &str {
+        &self.full_name
+    }
+
+    /// Returns the HTTPS clone URL.
+    #[inline]
+    #[must_use]
+    pub fn clone_url(&self) -> &str {
+        &self.clone_url
+    }
+
+    /// Returns the SSH clone URL.
+    #[inline]
+    #[must_use]
+    pub fn ssh_url(&self) -> &str {
+        &self.ssh_url
+    }
+
+    /// Returns the description.
+    #[inline]
+    #[must_use]
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Returns whether the repository is archived.
+    #[inline]
+    #[must_use]
+    pub fn is_archived(&self) -> bool {
+        self.archived
+    }
+
+    /// Returns whether the repository is a fork.
+    #[inline]
+    #[must_use]
+    pub fn is_fork(&self) -> bool {
+        self.fork
+    }
+
+    /// Returns the number of stars.
+    #[inline]
+    #[must_use]
+    pub fn stars(&self) -> u64 {
+        self.stargazers_count
+    }
+}
@@ -0,0 +1,344 @@
+//! GitHub.
+
+use std::{
+    cell::Cell,
+    env, thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{
+    forge::{Error, RemoteRepo},
+    timing,
+};
+
+/// Default API endpoint.
+const DEFAULT_API_BASE: &str = "https://api.github.com";
+
+/// Number of items per page.
+///
+/// This is the maximum value GitHub allows.
+const PER_PAGE: usize = 100;
+
+/// Maximum number of results the search API serves for a query.
+///
+/// Requests for pages beyond this fail with 422 Unprocessable Entity.
+const SEARCH_RESULT_LIMIT: usize = 1000;
+
+/// Environment variable name for the API token.
+const TOKEN_ENV_VAR: &str = "GITHUB_TOKEN";
+
+/// Search result.
+#[derive(Deserialize)]
+struct SearchResult {
+    /// Total number of the matching repositories.
+    total_count: usize,
+    /// Repositories.
+    items: Vec<RemoteRepo>,
+}
+
+/// GitHub API client.
+#[derive(Debug, Clone)]
+pub struct Client {
+    /// HTTP agent.
+    agent: ureq::Agent,
+    /// API endpoint.
+    api_base: String,
+    /// API token.
+    token: Option<String>,
+    /// Maximum duration to wait for the rate limit to be reset.
+    max_rate_limit_wait: Duration,
+}
+
+impl Client {
+    /// Creates a new client.
+    #[must_use]
+    pub fn new(token: Option<String>) -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new()
+                .user_agent(concat!("magro/", env!("CARGO_PKG_VERSION")))
+                .build(),
+            api_base: DEFAULT_API_BASE.to_owned(),
+            token,
+            max_rate_limit_wait: Duration::from_secs(60),
+        }
+    }
+
+    /// Creates a new client using the token in `GITHUB_TOKEN` environment variable.
+    #[must_use]
+    pub fn from_env() -> Self {
+        Self::new(env::var(TOKEN_ENV_VAR).ok().filter(|s| !s.is_empty()))
+    }
+
+    /// Sets the API endpoint (for GitHub Enterprise).
+    pub fn set_api_base(&mut self, api_base: impl Into<String>) {
+        self.api_base = api_base.into();
+    }
+
+    /// Sets the maximum duration to wait for the rate limit to be reset.
+    ///
+    /// If the rate limit will be reset after longer duration than this,
+    /// operations fail with [`Error::RateLimited`].
+    pub fn set_max_rate_limit_wait(&mut self, wait: Duration) {
+        self.max_rate_limit_wait = wait;
+    }
+
+    /// Returns the repositories starred by the user.
+    ///
+    /// If `user` is `None`, the authenticated user is used (and a token is required).
+    pub fn starred(&self, user: Option<&str>) -> Result<Vec<RemoteRepo>, Error> {
+        let path = match user {
+            Some(user) => format!("/users/{}/starred", user),
+            None => "/user/starred".to_owned(),
+        };
+        self.get_all_pages::<Vec<RemoteRepo>, _>(&path, "", None, |page| page)
+    }
+
+    /// Returns the repository with the full name (such as `owner/name`).
+    pub fn repo(&self, full_name: &str) -> Result<RemoteRepo, Error> {
+        self.get(&format!("{}/repos/{}", self.api_base, full_name))
+    }
+
+    /// Returns the repositories matching the search query.
+    ///
+    /// See <https://docs.github.com/en/search-github/searching-on-github/searching-for-repositories>
+    /// for the query syntax (for example, `topic:rust`).
+    ///
+    /// GitHub serves only the first 1000 results. If more repositories match,
+    /// the results are truncated and a warning is emitted.
+    pub fn search(&self, query: &str) -> Result<Vec<RemoteRepo>, Error> {
+        let total_count = Cell::new(0);
+        let encoded = format!("q={}&", encode_query_component(query));
+        let repos = self.get_all_pages::<SearchResult, _>(
+            "/search/repositories",
+            &encoded,
+            Some(SEARCH_RESULT_LIMIT),
+            |res| {
+                total_count.set(res.total_count);
+                res.items
+            },
+        )?;
+        if total_count.get() > repos.len() {
+            log::warn!(
+                "The search {:?} matched {} repositories, but only the first {} are available",
+                query,
+                total_count.get(),
+                repos.len()
+            );
+        }
+        Ok(repos)
+    }
+
+    /// Gets all pages of the paginated resource.
+    ///
+    /// If `limit` is given, pages after the limit are not requested.
+    fn get_all_pages<T, F>(
+        &self,
+        path: &str,
+        query: &str,
+        limit: Option<usize>,
+        extract: F,
+    ) -> Result<Vec<RemoteRepo>, Error>
+    where
+        T: DeserializeOwned,
+        F: Fn(T) -> Vec<RemoteRepo>,
+    {
+        let mut repos = Vec::new();
+        for page in 1.. {
+            let url = format!(
+                "{}{}?{}per_page={}&page={}",
+                self.api_base, path, query, PER_PAGE, page
+            );
+            let items = extract(self.get(&url)?);
+            let len = items.len();
+            repos.extend(items);
+            if len < PER_PAGE || limit.map_or(false, |limit| repos.len() >= limit) {
+                break;
+            }
+        }
+        Ok(repos)
+    }
+
+    /// Sends a GET request and decodes the response, waiting for the rate
+    /// limit reset if necessary.
+    fn get<T: DeserializeOwned>(&self, url: &str) -> Result<T, Error> {
+        loop {
+            log::debug!("Requesting {}", url);
+            let mut req = self
+                .agent
+                .get(url)
+                .set("Accept", "application/vnd.github+json");
+            if let Some(token) = &self.token {
+                req = req.set("Authorization", &format!("Bearer {}", token));
+            }
+            let res = {
+                let _span = timing::span("network: forge API");
+                req.call()
+            };
+            match res {
+                Ok(res) => {
+                    return serde_json::from_reader(res.into_reader()).map_err(|e| Error::Decode {
+                        url: url.to_owned(),
+                        source: e,
+                    })
+                }
+                Err(ureq::Error::Status(status, res)) => {
+                    if let Some(wait) = rate_limit_wait(status, &res) {
+                        if wait > self.max_rate_limit_wait {
+                            return Err(Error::RateLimited { reset_after: wait });
+                        }
+                        log::warn!(
+                            "Rate limit exceeded. Waiting for {} seconds",
+                            wait.as_secs()
+                        );
+                        thread::sleep(wait);
+                        continue;
+                    }
+                    return Err(Error::Http {
+                        url: url.to_owned(),
+                        status,
+                        message: res.into_string().unwrap_or_default(),
+                    });
+                }
+                Err(ureq::Error::Transport(e)) => {
+                    return Err(Error::Transport {
+                        url: url.to_owned(),
+                        source: e.into(),
+                    })
+                }
+            }
+        }
+    }
+}
+
+/// Returns the duration to wait if the response indicates the rate limit is exceeded.
+fn rate_limit_wait(status: u16, res: &ureq::Response) -> Option<Duration> {
+    if status != 403 && status != 429 {
+        return None;
+    }
+    // Secondary rate limit.
+    if let Some(secs) = res.header("retry-after").and_then(|v| v.parse().ok()) {
+        return Some(Duration::from_secs(secs));
+    }
+    // Primary rate limit.
+    if res.header("x-ratelimit-remaining") != Some("0") {
+        return None;
+    }
+    let reset = res.header("x-ratelimit-reset")?.parse::<u64>().ok()?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    // Add a second to avoid retrying slightly before the reset.
+    Some(Duration::from_secs(reset.saturating_sub(now) + 1))
+}
+
+/// Returns the full name (such as `owner/name`) of the repository on GitHub
+/// from its remote URL.
+///
+/// Returns `None` if the URL does not point to a repository on `github.com`.
+///
+/// # Examples
+///
+/// ```
+/// # use magro::forge::github::repo_full_name;
+/// assert_eq!(
+///     repo_full_name("https://github.com/lo48576/magro.git").as_deref(),
+///     Some("lo48576/magro")
+/// );
+/// assert_eq!(
+///     repo_full_name("git@github.com:lo48576/magro.git").as_deref(),
+///     Some("lo48576/magro")
+/// );
+/// assert_eq!(repo_full_name("https://gitlab.com/nop_thread/magro.git"), None);
+/// ```
+#[must_use]
+pub fn repo_full_name(url: &str) -> Option<String> {
+    let (host, path) = match url.find("://") {
+        // `scheme://[user@]host[:port]/path`.
+        Some(pos) => {
+            let rest = &url[(pos + 3)..];
+            let (authority, path) = rest.split_at(rest.find('/')?);
+            let host = authority.rsplit('@').next()?;
+            let host = host.split(':').next()?;
+            (host, path)
+        }
+        // scp-like syntax `[user@]host:path`.
+        None => {
+            let (userhost, path) = url.split_at(url.find(':')?);
+            (userhost.rsplit('@').next()?, &path[1..])
+        }
+    };
+    if !host.eq_ignore_ascii_case("github.com") && !host.eq_ignore_ascii_case("www.github.com") {
+        return None;
+    }
+
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    let mut components = path.split('/');
+    match (components.next(), components.next(), components.next()) {
+        (Some(owner), Some(name), None) if !owner.is_empty() && !name.is_empty() => {
+            Some(format!("{}/{}", owner, name))
+        }
+        _ => None,
+    }
+}
+
+/// Percent-encodes the query component.
+fn encode_query_component(s: &str) -> String {
+    use std::fmt::Write;
+
+    let mut encoded = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(char::from(b))
+            }
+            _ => {
+                let _ = write!(encoded, "%{:02X}", b);
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_names_from_urls() {
+        for url in &[
+            "https://github.com/owner/repo",
+            "https://github.com/owner/repo.git",
+            "https://user@github.com/owner/repo/",
+            "ssh://git@github.com:22/owner/repo.git",
+            "git://github.com/owner/repo.git",
+            "git@github.com:owner/repo.git",
+            "github.com:/owner/repo",
+        ] {
+            assert_eq!(
+                repo_full_name(url).as_deref(),
+                Some("owner/repo"),
+                "{}",
+                url
+            );
+        }
+        for url in &[
+            "https://example.com/owner/repo.git",
+            "https://github.com/owner",
+            "https://github.com/owner/repo/tree/main",
+            "/path/to/github.com/owner/repo",
+            "git@github.com.example.com:owner/repo.git",
+        ] {
+            assert_eq!(repo_full_name(url), None, "{}", url);
+        }
+    }
+
+    #[test]
+    fn query_encoding() {
+        assert_eq!(encode_query_component("topic:rust"), "topic%3Arust");
+        assert_eq!(
+            encode_query_component("language:c stars:>10"),
+            "language%3Ac%20stars%3A%3E10"
+        );
+    }
+}
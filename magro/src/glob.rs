@@ -0,0 +1,190 @@
+//! Glob patterns for paths and names.
+
+use std::{convert::TryFrom, fmt, path::Path, str};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+/// Glob pattern.
+///
+/// Patterns consist of `/`-separated components.
+/// In a component, `*` matches any (possibly empty) sequence of characters and
+/// `?` matches any single character. A component `**` matches any number of
+/// (possibly zero) components.
+///
+/// This is serialized as a string.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Glob {
+    /// Pattern string.
+    pattern: String,
+}
+
+impl Glob {
+    /// Creates a new glob pattern.
+    pub fn new(pattern: impl Into<String>) -> Result<Self, GlobError> {
+        let pattern = pattern.into();
+        if pattern.is_empty() {
+            return Err(GlobError::Empty);
+        }
+        Ok(Self { pattern })
+    }
+
+    /// Returns the pattern string.
+    #[inline]
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Returns true if the whole string matches the pattern.
+    #[must_use]
+    pub fn matches(&self, s: &str) -> bool {
+        let pattern = self.pattern.split('/').collect::<Vec<_>>();
+        let target = s.split('/').collect::<Vec<_>>();
+        match_components(&pattern, &target)
+    }
+
+    /// Returns true if the path matches the pattern.
+    ///
+    /// If the pattern starts with `/`, it should match the whole path.
+    /// Otherwise, it can also match the trailing components of the path.
+    /// For example, `vendor/**` matches `foo/vendor/bar`, but `/vendor/**`
+    /// does not.
+    #[must_use]
+    pub fn matches_path(&self, path: &Path) -> bool {
+        let (anchored, pattern) = match self.pattern.strip_prefix('/') {
+            Some(rest) => (true, rest),
+            None => (false, &*self.pattern),
+        };
+        let pattern = pattern.split('/').collect::<Vec<_>>();
+        let target = path
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>();
+        let target = target.iter().map(|c| &**c).collect::<Vec<_>>();
+        if anchored {
+            return match_components(&pattern, &target);
+        }
+        (0..=target.len()).any(|start| match_components(&pattern, &target[start..]))
+    }
+}
+
+impl fmt::Display for Glob {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.pattern)
+    }
+}
+
+impl str::FromStr for Glob {
+    type Err = GlobError;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl TryFrom<String> for Glob {
+    type Error = GlobError;
+
+    #[inline]
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::new(s)
+    }
+}
+
+impl From<Glob> for String {
+    #[inline]
+    fn from(glob: Glob) -> Self {
+        glob.pattern
+    }
+}
+
+/// Error on creating a glob pattern.
+#[derive(Debug, Clone, PartialEq, Eq, ThisError)]
+#[non_exhaustive]
+pub enum GlobError {
+    /// The pattern is empty.
+    #[error("Empty glob pattern")]
+    Empty,
+}
+
+/// Returns true if the components match the pattern components.
+fn match_components(pattern: &[&str], target: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => target.is_empty(),
+        Some((&"**", rest)) => {
+            (0..=target.len()).any(|skip| match_components(rest, &target[skip..]))
+        }
+        Some((first, rest)) => match target.split_first() {
+            Some((target_first, target_rest)) => {
+                match_component(first, target_first) && match_components(rest, target_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+/// Returns true if the component matches the pattern component.
+fn match_component(pattern: &str, target: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let target = target.chars().collect::<Vec<_>>();
+    // Position after the last `*` in the pattern, and the target position
+    // it is (tentatively) matched up to.
+    let mut backtrack: Option<(usize, usize)> = None;
+    let (mut p, mut t) = (0, 0);
+    while t < target.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                backtrack = Some((p, t));
+            }
+            Some(&c) if c == '?' || c == target[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match &mut backtrack {
+                Some((star_p, star_t)) => {
+                    *star_t += 1;
+                    p = *star_p;
+                    t = *star_t;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glob(pattern: &str) -> Glob {
+        Glob::new(pattern).unwrap()
+    }
+
+    #[test]
+    fn match_names() {
+        assert!(glob("work-*").matches("work-foo"));
+        assert!(glob("work-*").matches("work-"));
+        assert!(!glob("work-*").matches("my-work-foo"));
+        assert!(glob("*-?ar").matches("foo-bar"));
+        assert!(!glob("*-?ar").matches("foo-ar"));
+        assert!(glob("a*b*c").matches("abxbc"));
+        assert!(!glob("a*b*c").matches("abxbcd"));
+        assert!(Glob::new("").is_err());
+    }
+
+    #[test]
+    fn match_paths() {
+        assert!(glob("vendor/**").matches_path(Path::new("vendor/foo")));
+        assert!(glob("vendor/**").matches_path(Path::new("proj/vendor/foo/bar")));
+        assert!(!glob("/vendor/**").matches_path(Path::new("proj/vendor/foo")));
+        assert!(glob("/proj/*/foo").matches_path(Path::new("proj/vendor/foo")));
+        assert!(!glob("vendor/*").matches_path(Path::new("proj/vendor/foo/bar")));
+        assert!(!glob("third_party/**").matches_path(Path::new("proj/vendor")));
+    }
+}
@@ -0,0 +1,160 @@
+//! Repository health checks.
+//!
+//! Health checks detect states which can lose work when the local
+//! repository is removed, such as unpushed commits and stashes.
+
+use std::fmt;
+
+use serde::Serialize;
+
+/// Severity of a health issue.
+///
+/// Larger value means more severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum Severity {
+    /// Worth noting, but no work will be lost.
+    Low,
+    /// Work may be lost.
+    Medium,
+    /// Work will be lost.
+    High,
+}
+
+impl Severity {
+    /// Returns the severity name in lower case.
+    #[inline]
+    #[must_use]
+    pub fn name_lower(&self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name_lower())
+    }
+}
+
+/// How tags are checked by the health check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TagCheck {
+    /// Tags are not checked.
+    Skip,
+    /// Tags pointing to commits not reachable from any remote-tracking
+    /// reference are reported.
+    ///
+    /// Remotes are not accessed.
+    Local,
+    /// Tags missing on all remotes, or pointing to different objects there,
+    /// are reported.
+    ///
+    /// Tags of the remotes are listed by connecting to the remotes.
+    Remote,
+}
+
+/// Health issue of a repository.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum HealthIssue {
+    /// The repository has no remotes.
+    NoRemotes,
+    /// The branch has commits not pushed to the upstream.
+    UnpushedCommits {
+        /// Branch name.
+        branch: String,
+        /// Number of unpushed commits.
+        count: usize,
+    },
+    /// The branch has no upstream.
+    NoUpstream {
+        /// Branch name.
+        branch: String,
+    },
+    /// The tags are not pushed to any remote.
+    ///
+    /// See [`TagCheck`] for how this is checked.
+    UnpushedTags {
+        /// Tag names.
+        tags: Vec<String>,
+    },
+    /// Tags of the remotes could not be listed, so whether the tags are
+    /// pushed is unknown.
+    RemoteTagsUnknown {
+        /// Names of the remotes whose tags could not be listed.
+        remotes: Vec<String>,
+    },
+    /// The repository has stashes.
+    Stashes {
+        /// Number of stashes.
+        count: usize,
+    },
+    /// HEAD is detached.
+    DetachedHead,
+    /// The working directory has uncommitted changes (including untracked files).
+    UncommittedChanges {
+        /// Number of changed files.
+        count: usize,
+    },
+}
+
+impl HealthIssue {
+    /// Returns the severity of the issue.
+    #[must_use]
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::NoRemotes
+            | Self::UnpushedCommits { .. }
+            | Self::UnpushedTags { .. }
+            | Self::Stashes { .. }
+            | Self::UncommittedChanges { .. } => Severity::High,
+            Self::NoUpstream { .. } | Self::RemoteTagsUnknown { .. } => Severity::Medium,
+            Self::DetachedHead => Severity::Low,
+        }
+    }
+}
+
+impl fmt::Display for HealthIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoRemotes => f.write_str("no remotes"),
+            Self::UnpushedCommits { branch, count } => {
+                write!(f, "branch `{}` has {} unpushed commit(s)", branch, count)
+            }
+            Self::NoUpstream { branch } => write!(f, "branch `{}` has no upstream", branch),
+            Self::UnpushedTags { tags } => {
+                write!(f, "{} tag(s) not pushed to any remote (", tags.len())?;
+                for (i, tag) in tags.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "`{}`", tag)?;
+                }
+                f.write_str(")")
+            }
+            Self::RemoteTagsUnknown { remotes } => {
+                f.write_str("remote tags unknown (failed to list tags of ")?;
+                for (i, remote) in remotes.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "`{}`", remote)?;
+                }
+                f.write_str(")")
+            }
+            Self::Stashes { count } => write!(f, "{} stash(es)", count),
+            Self::DetachedHead => f.write_str("detached HEAD"),
+            Self::UncommittedChanges { count } => {
+                write!(f, "{} file(s) with uncommitted changes", count)
+            }
+        }
+    }
+}
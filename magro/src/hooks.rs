@@ -0,0 +1,386 @@
+//! Lifecycle hooks.
+//!
+//! Hooks are external commands configured in the main config, and run on
+//! events such as clone, refresh, removal, and moves of repositories.
+
+use std::{
+    ffi::{OsStr, OsString},
+    fmt, io,
+    io::Read,
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+    sync::{Arc, Mutex, PoisonError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use crate::{config::HookConfig, Context};
+
+/// Interval to check whether the hook process has exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Time to wait for the outputs after the hook process exits.
+///
+/// Background processes spawned by the hook can keep the output pipes open
+/// after the hook exits (or is killed on timeout), so the outputs are not
+/// waited for longer than this.
+const OUTPUT_GRACE_PERIOD: Duration = Duration::from_secs(1);
+
+/// Hook error.
+#[derive(Debug, ThisError)]
+#[non_exhaustive]
+pub enum HookError {
+    /// Failed to run or wait for the hook command.
+    #[error("Failed to run {event} hook {command:?}")]
+    Io {
+        /// Event.
+        event: HookEvent,
+        /// Command.
+        command: Vec<String>,
+        /// Source error.
+        #[source]
+        source: io::Error,
+    },
+    /// The hook command did not finish in time.
+    #[error("{event} hook {command:?} timed out after {timeout:?}")]
+    Timeout {
+        /// Event.
+        event: HookEvent,
+        /// Command.
+        command: Vec<String>,
+        /// Timeout.
+        timeout: Duration,
+    },
+    /// The hook command exited with failure.
+    #[error("{event} hook {command:?} failed ({status})")]
+    Failed {
+        /// Event.
+        event: HookEvent,
+        /// Command.
+        command: Vec<String>,
+        /// Exit status.
+        status: ExitStatus,
+    },
+}
+
+/// Hook event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum HookEvent {
+    /// Before cloning a repository.
+    PreClone,
+    /// After cloning a repository.
+    PostClone,
+    /// Before refreshing a collection.
+    PreRefresh,
+    /// After refreshing a collection.
+    PostRefresh,
+    /// Before removing a repository.
+    PreRemove,
+    /// After removing a repository.
+    PostRemove,
+    /// Before moving a collection directory or repositories.
+    PreMove,
+    /// After moving a collection directory or repositories.
+    PostMove,
+}
+
+impl HookEvent {
+    /// Returns the event name used in the config.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::PreClone => "pre-clone",
+            Self::PostClone => "post-clone",
+            Self::PreRefresh => "pre-refresh",
+            Self::PostRefresh => "post-refresh",
+            Self::PreRemove => "pre-remove",
+            Self::PostRemove => "post-remove",
+            Self::PreMove => "pre-move",
+            Self::PostMove => "post-move",
+        }
+    }
+}
+
+impl fmt::Display for HookEvent {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Policy on hook failures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailurePolicy {
+    /// Aborts the operation.
+    Abort,
+    /// Warns and continues the operation.
+    Warn,
+}
+
+impl Default for FailurePolicy {
+    #[inline]
+    fn default() -> Self {
+        Self::Abort
+    }
+}
+
+/// Environment of hook commands.
+///
+/// `MAGRO_HOOK` environment variable is always set to the event name.
+#[derive(Default, Debug, Clone)]
+pub struct HookEnv {
+    /// Working directory.
+    cwd: Option<PathBuf>,
+    /// Environment variables.
+    vars: Vec<(String, OsString)>,
+}
+
+impl HookEnv {
+    /// Creates a new empty environment.
+    ///
+    /// Hook commands inherit the working directory and environment variables
+    /// of the current process by default.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the working directory.
+    #[inline]
+    #[must_use]
+    pub fn with_cwd(mut self, cwd: impl AsRef<Path>) -> Self {
+        self.cwd = Some(cwd.as_ref().to_owned());
+        self
+    }
+
+    /// Adds an environment variable.
+    #[inline]
+    #[must_use]
+    pub fn with_var(mut self, name: &str, value: impl AsRef<OsStr>) -> Self {
+        self.vars.push((name.to_owned(), value.as_ref().to_owned()));
+        self
+    }
+
+    /// Adds environment variables.
+    #[must_use]
+    pub fn with_vars<K, V>(mut self, vars: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: AsRef<str>,
+        V: AsRef<OsStr>,
+    {
+        self.vars.extend(
+            vars.into_iter()
+                .map(|(k, v)| (k.as_ref().to_owned(), v.as_ref().to_owned())),
+        );
+        self
+    }
+}
+
+/// Runs the hooks for the event in the configured order.
+///
+/// Outputs of hook commands are captured and logged at info level.
+/// Hook commands exceeding the timeout are killed, but background processes
+/// spawned by them are not.
+/// Failures of hooks with [`FailurePolicy::Warn`] are logged, and the
+/// remaining hooks are run.
+/// A failure of a hook with [`FailurePolicy::Abort`] is returned as an error
+/// immediately.
+pub fn run(context: &Context, event: HookEvent, env: &HookEnv) -> Result<(), HookError> {
+    for hook in context.config().main().hooks().get(event) {
+        if let Err(e) = run_hook(hook, event, env) {
+            match hook.on_failure() {
+                FailurePolicy::Abort => return Err(e),
+                FailurePolicy::Warn => log::warn!("{}", e),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs the hook command and waits for it.
+fn run_hook(hook: &HookConfig, event: HookEvent, env: &HookEnv) -> Result<(), HookError> {
+    let command = hook.command();
+    let io_error = |source| HookError::Io {
+        event,
+        command: command.to_owned(),
+        source,
+    };
+    let (program, args) = command.split_first().ok_or_else(|| {
+        io_error(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Empty hook command",
+        ))
+    })?;
+    log::debug!("Running {} hook {:?}", event, command);
+
+    let mut cmd = Command::new(program);
+    cmd.args(args)
+        .env("MAGRO_HOOK", event.as_str())
+        .envs(env.vars.iter().map(|(k, v)| (k, v)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(cwd) = &env.cwd {
+        cmd.current_dir(cwd);
+    }
+    let mut child = cmd.spawn().map_err(io_error)?;
+
+    // Read outputs in background, so that the child is not blocked on full pipes.
+    let stdout = child.stdout.take().map(spawn_reader);
+    let stderr = child.stderr.take().map(spawn_reader);
+    let readers = stdout.into_iter().chain(stderr).collect::<Vec<_>>();
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(io_error)? {
+            break Some(status);
+        }
+        if let Some(timeout) = hook.timeout() {
+            if started.elapsed() >= timeout {
+                // The process may have exited just now, so errors are ignored.
+                let _ = child.kill();
+                child.wait().map_err(io_error)?;
+                break None;
+            }
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    // Readers still running after the grace period are left detached.
+    // A reader has finished when its thread has dropped the buffer.
+    let grace_deadline = Instant::now() + OUTPUT_GRACE_PERIOD;
+    while readers.iter().any(|output| Arc::strong_count(output) > 1)
+        && Instant::now() < grace_deadline
+    {
+        thread::sleep(POLL_INTERVAL);
+    }
+    for output in &readers {
+        let output = output.lock().unwrap_or_else(PoisonError::into_inner);
+        for line in String::from_utf8_lossy(&output).lines() {
+            log::info!("[{} hook] {}", event, line);
+        }
+    }
+
+    match status {
+        Some(status) if status.success() => Ok(()),
+        Some(status) => Err(HookError::Failed {
+            event,
+            command: command.to_owned(),
+            status,
+        }),
+        None => Err(HookError::Timeout {
+            event,
+            command: command.to_owned(),
+            timeout: hook.timeout().expect("Should never fail: timed out"),
+        }),
+    }
+}
+
+/// Spawns a thread which reads all the output into the returned buffer.
+///
+/// The buffer has the output read so far even while the thread is running.
+/// The thread drops its reference to the buffer when it finishes.
+fn spawn_reader<R: Read + Send + 'static>(mut reader: R) -> Arc<Mutex<Vec<u8>>> {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let buf = Arc::clone(&output);
+    thread::spawn(move || {
+        let mut chunk = [0; 4096];
+        loop {
+            match reader.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(len) => buf
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .extend_from_slice(&chunk[..len]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                // Partial output is still useful even if reading fails.
+                Err(_) => break,
+            }
+        }
+    });
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    /// Creates a context with the main config.
+    fn context_with_config(dir: &Path, main_config: &str) -> Context {
+        fs::write(dir.join("config.toml"), main_config).unwrap();
+        Context::with_dirs(Some(dir.to_owned()), Some(dir.join("cache"))).unwrap()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn timeout_with_background_process() {
+        // The background process keeps the output pipes open after the hook
+        // process is killed.
+        let hook: HookConfig = toml::from_str(
+            r#"command = ["sh", "-c", "sleep 10 & sleep 10"]
+timeout = 1"#,
+        )
+        .unwrap();
+        let started = Instant::now();
+        let result = run_hook(&hook, HookEvent::PostClone, &HookEnv::new());
+        assert!(
+            matches!(result, Err(HookError::Timeout { .. })),
+            "{:?}",
+            result
+        );
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn warn_policy_runs_remaining_hooks() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let context = context_with_config(
+            tempdir.path(),
+            r#"
+[[hooks.post-clone]]
+command = ["false"]
+on-failure = "warn"
+
+[[hooks.post-clone]]
+command = ["touch", "ran"]
+"#,
+        );
+        let env = HookEnv::new().with_cwd(tempdir.path());
+        run(&context, HookEvent::PostClone, &env).unwrap();
+        assert!(tempdir.path().join("ran").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn abort_policy_stops_remaining_hooks() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let context = context_with_config(
+            tempdir.path(),
+            r#"
+[[hooks.post-clone]]
+command = ["false"]
+
+[[hooks.post-clone]]
+command = ["touch", "ran"]
+"#,
+        );
+        let env = HookEnv::new().with_cwd(tempdir.path());
+        let result = run(&context, HookEvent::PostClone, &env);
+        assert!(
+            matches!(result, Err(HookError::Failed { .. })),
+            "{:?}",
+            result
+        );
+        assert!(!tempdir.path().join("ran").exists());
+    }
+}
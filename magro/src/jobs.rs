@@ -0,0 +1,263 @@
+//! Progress of bulk operations.
+//!
+//! Bulk operations over many repositories (such as backups and fetches) can
+//! record completed jobs into a journal file in the cache directory, so that
+//! an interrupted run can resume where it left off.
+//!
+//! A journal file consists of a header line with the fingerprint of the whole
+//! set of jobs, and lines of completed job keys. The journal is reused only
+//! when the set of jobs is unchanged.
+
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
+};
+
+use crate::permissions::{self, DirMode};
+
+/// Prefix of the header line of journal files.
+const HEADER_PREFIX: &str = "magro-jobs v1 ";
+
+/// Journal of completed jobs of a bulk operation.
+///
+/// This is `Sync`, so jobs run in parallel can record their completion.
+#[derive(Debug)]
+pub struct JobJournal {
+    /// Path to the journal file.
+    path: PathBuf,
+    /// Keys of jobs completed by the interrupted runs.
+    completed: HashSet<String>,
+    /// Journal file opened for appending.
+    file: Mutex<File>,
+}
+
+impl JobJournal {
+    /// Opens the journal of the operation on the given jobs in the directory.
+    ///
+    /// If `resume` is true and the journal of an interrupted run on the same
+    /// set of jobs exists, jobs completed by that run are loaded.
+    /// Otherwise, a new journal is started.
+    pub fn open<I, S>(
+        dir: &Path,
+        operation: &str,
+        jobs: I,
+        resume: bool,
+        dir_mode: Option<DirMode>,
+    ) -> io::Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let path = dir.join(format!("{}.log", operation));
+        let header = format!("{}{:016x}", HEADER_PREFIX, fingerprint(operation, jobs));
+
+        let completed = if resume {
+            load_completed(&path, &header)?
+        } else {
+            None
+        };
+        let completed = match completed {
+            Some(completed) => {
+                log::debug!("Resuming the journal {:?}", path);
+                completed
+            }
+            None => HashSet::new(),
+        };
+        // Rewrite the journal, to drop the incomplete last line if exists.
+        permissions::create_dir_all(dir, dir_mode)?;
+        let mut file = File::create(&path)?;
+        writeln!(file, "{}", header)?;
+        for job in &completed {
+            writeln!(file, "{}", escape(job))?;
+        }
+        file.flush()?;
+
+        Ok(Self {
+            path,
+            completed,
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Returns true if the job is completed by the interrupted runs.
+    #[inline]
+    #[must_use]
+    pub fn is_completed(&self, job: &str) -> bool {
+        self.completed.contains(job)
+    }
+
+    /// Returns the number of jobs completed by the interrupted runs.
+    #[inline]
+    #[must_use]
+    pub fn num_completed(&self) -> usize {
+        self.completed.len()
+    }
+
+    /// Records the completion of the job.
+    ///
+    /// The record is written immediately, so it survives interruptions.
+    pub fn complete(&self, job: &str) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        writeln!(file, "{}", escape(job))?;
+        file.flush()
+    }
+
+    /// Removes the journal, as all jobs are completed.
+    pub fn finish(self) -> io::Result<()> {
+        drop(self.file);
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Loads the completed jobs from the journal file.
+///
+/// Returns `None` if the journal does not exist or is for other jobs.
+fn load_completed(path: &Path, header: &str) -> io::Result<Option<HashSet<String>>> {
+    let file = match File::open(path) {
+        Ok(v) => v,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let mut reader = BufReader::new(file);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if line.strip_suffix('\n') != Some(header) {
+        log::debug!("Discarding the journal {:?} for other jobs", path);
+        return Ok(None);
+    }
+
+    let mut completed = HashSet::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        // The last line is incomplete if interrupted during a write.
+        if let Some(job) = line.strip_suffix('\n') {
+            completed.insert(unescape(job));
+        }
+    }
+    Ok(Some(completed))
+}
+
+/// Returns the fingerprint of the set of jobs.
+///
+/// This is 64-bit FNV-1a hash of the operation name and the sorted job keys,
+/// which is stable across builds unlike `std::hash`.
+fn fingerprint<I, S>(operation: &str, jobs: I) -> u64
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    /// FNV offset basis.
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    /// FNV prime.
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let mut jobs = jobs
+        .into_iter()
+        .map(|job| job.as_ref().to_owned())
+        .collect::<Vec<_>>();
+    jobs.sort_unstable();
+    jobs.dedup();
+
+    let mut hash = OFFSET_BASIS;
+    for part in Some(operation)
+        .into_iter()
+        .chain(jobs.iter().map(String::as_str))
+    {
+        // Terminate each part by NUL, so that concatenations are distinguished.
+        for &b in part.as_bytes().iter().chain(&[0]) {
+            hash ^= u64::from(b);
+            hash = hash.wrapping_mul(PRIME);
+        }
+    }
+    hash
+}
+
+/// Escapes the job key to be written as a line.
+fn escape(job: &str) -> String {
+    job.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+/// Unescapes the job key written as a line.
+fn unescape(line: &str) -> String {
+    let mut job = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            job.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => job.push('\n'),
+            Some(c) => job.push(c),
+            None => job.push('\\'),
+        }
+    }
+    job
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resume_completed_jobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let jobs = ["a", "b\nc", "d\\e"];
+
+        let journal = JobJournal::open(dir, "op", jobs, true, None).unwrap();
+        assert_eq!(journal.num_completed(), 0);
+        journal.complete("a").unwrap();
+        journal.complete("b\nc").unwrap();
+        // Interrupted.
+        drop(journal);
+
+        let journal = JobJournal::open(dir, "op", jobs, true, None).unwrap();
+        assert!(journal.is_completed("a"));
+        assert!(journal.is_completed("b\nc"));
+        assert!(!journal.is_completed("d\\e"));
+        journal.complete("d\\e").unwrap();
+        journal.finish().unwrap();
+
+        let journal = JobJournal::open(dir, "op", jobs, true, None).unwrap();
+        assert_eq!(journal.num_completed(), 0);
+    }
+
+    #[test]
+    fn restart_on_other_jobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+
+        let journal = JobJournal::open(dir, "op", ["a", "b"], true, None).unwrap();
+        journal.complete("a").unwrap();
+        drop(journal);
+
+        // Order of the jobs does not matter.
+        let journal = JobJournal::open(dir, "op", ["b", "a"], true, None).unwrap();
+        assert_eq!(journal.num_completed(), 1);
+        drop(journal);
+        // Other operations and other jobs are not resumed.
+        let journal = JobJournal::open(dir, "other", ["a", "b"], true, None).unwrap();
+        assert_eq!(journal.num_completed(), 0);
+        drop(journal);
+        let journal = JobJournal::open(dir, "op", ["a", "b", "c"], true, None).unwrap();
+        assert_eq!(journal.num_completed(), 0);
+        drop(journal);
+
+        // Not resumed if requested.
+        let journal = JobJournal::open(dir, "op", ["a", "b"], true, None).unwrap();
+        journal.complete("a").unwrap();
+        drop(journal);
+        let journal = JobJournal::open(dir, "op", ["a", "b"], false, None).unwrap();
+        assert_eq!(journal.num_completed(), 0);
+    }
+}
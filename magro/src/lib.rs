@@ -6,10 +6,40 @@
 
 pub use self::{config::Config, context::Context};
 
+pub mod backup;
+pub mod branch;
 pub mod cache;
 pub mod collection;
 pub mod config;
 pub mod context;
+pub mod credential;
 pub mod discovery;
+#[cfg(feature = "forge")]
+pub mod forge;
+pub mod glob;
+pub mod health;
+pub mod hooks;
+pub mod jobs;
+pub mod locate;
 mod lock_fs;
+pub mod metadata;
+pub mod permissions;
+pub mod profile;
+pub mod query;
+pub mod relocate;
+pub mod remote;
+pub mod runner;
+pub mod safety;
+pub mod scaffold;
+pub mod snapshot;
+pub mod status;
+pub mod submodule;
+pub mod tag;
+pub mod template;
+pub mod timing;
+pub mod trash;
+pub mod upstream;
 pub mod vcs;
+pub mod verify;
+pub mod view;
+pub mod worktree;
@@ -0,0 +1,119 @@
+//! Locating the repository containing a path.
+//!
+//! This uses only the config and the cache (i.e. repositories and collection
+//! directories are not scanned), so that it can be called frequently (for
+//! example, from shell prompts).
+
+use std::{
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{collection::Collection, view::RepoView, Context};
+
+/// Returns the cached repository containing the given absolute path.
+///
+/// If the path is inside a collection directory but not inside any cached
+/// repository, or is not inside any collection directory, returns `Ok(None)`.
+///
+/// The working directory of the returned repository is set without opening
+/// the repository: the one resolved beforehand is used if available, and
+/// otherwise `.git` directories are assumed to reside at the top of working
+/// directories.
+pub fn locate<P: AsRef<Path>>(context: &Context, path: P) -> io::Result<Option<RepoView>> {
+    locate_impl(context, path.as_ref())
+}
+
+/// Monomorphized internal implementation of `locate()`.
+fn locate_impl(context: &Context, path: &Path) -> io::Result<Option<RepoView>> {
+    let (collection, relpath) = match find_collection(context, path) {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+    log::trace!(
+        "Path {:?} is in the collection {:?} (relative path: {:?})",
+        path,
+        collection.name(),
+        relpath
+    );
+
+    let coll_cache = match context
+        .get_or_load_cache()?
+        .collection_repos(collection.name())
+    {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+    let entry = coll_cache
+        .repositories()
+        .filter(|entry| relpath.starts_with(repo_root(entry.path())))
+        .max_by_key(|entry| entry.path().components().count());
+    let entry = match entry {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+
+    let mut view = RepoView::new(context, collection, entry);
+    if let Some(resolved) = entry.metadata().resolved() {
+        view.set_workdir(resolved.workdir().map(ToOwned::to_owned));
+    } else if entry
+        .path()
+        .file_name()
+        .map_or(false, |name| name == ".git")
+    {
+        let workdir = view.path().parent().map(ToOwned::to_owned);
+        view.set_workdir(workdir);
+    }
+    Ok(Some(view))
+}
+
+/// Returns the collection containing the given absolute path, and the path
+/// relative to the collection directory.
+///
+/// If collections are nested, the innermost collection is returned.
+/// Returns `None` if the path is not inside any collection directory.
+pub fn locate_collection<P: AsRef<Path>>(
+    context: &Context,
+    path: P,
+) -> Option<(&Collection, PathBuf)> {
+    find_collection(context, path.as_ref())
+}
+
+/// Returns the collection containing the path and the path relative to the
+/// collection directory.
+///
+/// If collections are nested, the innermost collection is returned.
+fn find_collection<'a>(context: &'a Context, path: &Path) -> Option<(&'a Collection, PathBuf)> {
+    let collections = context.config().collections();
+    let find = |canonicalize: bool| {
+        collections
+            .iter()
+            .filter_map(|collection| {
+                let abspath = collection.abspath(context);
+                let abspath = if canonicalize {
+                    fs::canonicalize(&abspath).ok()?
+                } else {
+                    abspath.into_owned()
+                };
+                let relpath = path.strip_prefix(&abspath).ok()?.to_owned();
+                Some((abspath.components().count(), collection, relpath))
+            })
+            .max_by_key(|(depth, _, _)| *depth)
+            .map(|(_, collection, relpath)| (collection, relpath))
+    };
+
+    // Collection directories can be symlinks, but usually not.
+    // Try cheaper comparison first.
+    find(false).or_else(|| find(true))
+}
+
+/// Returns the top directory of the repository, relative to the collection directory.
+///
+/// For `foo/.git`, this returns `foo`.
+/// For bare repositories (such as `foo.git`), this returns the path as is.
+pub(crate) fn repo_root(relpath: &Path) -> &Path {
+    match relpath.components().next_back() {
+        Some(Component::Normal(name)) if name == ".git" => relpath.parent().unwrap_or(relpath),
+        _ => relpath,
+    }
+}
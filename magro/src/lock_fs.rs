@@ -1,8 +1,9 @@
 //! Filesystem ops.
 
-use std::fs::File;
-use std::io::{Read, Result, Write};
-use std::path::Path;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Error, ErrorKind, Read, Result, Write};
+use std::path::{Path, PathBuf};
 
 use fd_lock::RwLock;
 
@@ -13,9 +14,17 @@ pub(crate) fn open<P: AsRef<Path>>(path: P) -> Result<RwLock<File>> {
 }
 
 /// Opens or creates a file and returns it in a lockable form.
+///
+/// The file is not truncated, since other processes may hold the lock.
 #[inline]
 pub(crate) fn create<P: AsRef<Path>>(path: P) -> Result<RwLock<File>> {
-    File::create(path).map(RwLock::new)
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .map(RwLock::new)
 }
 
 /// Reads the file exclusively from the given flie into a string.
@@ -55,24 +64,176 @@ fn read_to_string_impl(path: &Path) -> Result<String> {
 
 /// Writes the given content exclusively to the file at the given path.
 ///
-/// During the write, the file is locked.
+/// See [`write_with`] for details.
 #[inline]
 pub(crate) fn write<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> Result<()> {
-    write_impl(path.as_ref(), contents.as_ref())
+    write_with(path, |out| out.write_all(contents.as_ref()))
 }
 
-/// Writes the given content exclusively to the file at the given path.
+/// Writes content exclusively to the file at the given path using the given function.
 ///
-/// During the write, the file is locked.
-fn write_impl(path: &Path, contents: &[u8]) -> Result<()> {
-    let mut file = create(path)?;
+/// The content is written to a temporary file in the same directory, synced
+/// to the disk, and then renamed to the target, so that the file is never
+/// left partially written even if the function or the write fails.
+/// During the write, the lock file next to the target is locked.
+/// Writes are buffered.
+pub(crate) fn write_with<P, F>(path: P, f: F) -> Result<()>
+where
+    P: AsRef<Path>,
+    F: FnOnce(&mut dyn Write) -> Result<()>,
+{
+    let path = resolve_target(path.as_ref())?;
+    let mut lock_file = create(lock_path(&path)?)?;
     log::trace!("Locking file {} for write", path.display());
     {
-        let mut lock = file.write()?;
+        let _lock = lock_file.write()?;
         log::trace!("Successfully locked file {} for write", path.display());
-        lock.write_all(contents)?;
+        replace_with(&path, f)?;
+    }
+    log::trace!("Unlocked file {}", path.display());
+
+    Ok(())
+}
+
+/// Reads and rewrites the file at the given path exclusively.
+///
+/// The function receives the current content (empty if the file did not
+/// exist) and writes the new content.
+/// The lock file is locked during the whole read-modify-write, so that updates
+/// by other processes are never lost. The file is replaced in the same way as
+/// [`write_with`]. Writes are buffered.
+pub(crate) fn update_with<P, F>(path: P, f: F) -> Result<()>
+where
+    P: AsRef<Path>,
+    F: FnOnce(&str, &mut dyn Write) -> Result<()>,
+{
+    let path = resolve_target(path.as_ref())?;
+    let mut lock_file = create(lock_path(&path)?)?;
+    log::trace!("Locking file {} for update", path.display());
+    {
+        let _lock = lock_file.write()?;
+        log::trace!("Successfully locked file {} for update", path.display());
+        let content = match fs::read_to_string(&path) {
+            Ok(v) => v,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        replace_with(&path, |out| f(&content, out))?;
     }
     log::trace!("Unlocked file {}", path.display());
 
     Ok(())
 }
+
+/// Returns the path to the file to be actually replaced.
+///
+/// Symbolic links are resolved, so that the linked file is updated instead of
+/// the link being replaced with a regular file.
+fn resolve_target(path: &Path) -> Result<PathBuf> {
+    match fs::canonicalize(path) {
+        Ok(v) => Ok(v),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(path.to_owned()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Returns the path to the hidden file next to the target with the given suffix.
+fn sibling_path(path: &Path, suffix: &str) -> Result<PathBuf> {
+    let file_name = path.file_name().ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("Attempt to write to invalid path {:?}", path),
+        )
+    })?;
+    let mut name = OsString::from(".");
+    name.push(file_name);
+    name.push(suffix);
+    Ok(path.with_file_name(name))
+}
+
+/// Returns the path to the lock file for the target.
+///
+/// The target itself cannot be used for locking since it is replaced.
+#[inline]
+fn lock_path(path: &Path) -> Result<PathBuf> {
+    sibling_path(path, ".lock")
+}
+
+/// Replaces the file with the content written by the given function.
+///
+/// The caller should hold the lock for the target.
+fn replace_with<F>(path: &Path, f: F) -> Result<()>
+where
+    F: FnOnce(&mut dyn Write) -> Result<()>,
+{
+    // Other processes never use the same temporary file, since the lock is held.
+    let temp_path = sibling_path(path, ".tmp")?;
+    let result = write_temp_and_rename(path, &temp_path, f);
+    if result.is_err() {
+        if let Err(e) = fs::remove_file(&temp_path) {
+            if e.kind() != ErrorKind::NotFound {
+                log::warn!("Failed to remove temporary file {:?}: {}", temp_path, e);
+            }
+        }
+    }
+    result
+}
+
+/// Writes the content to the temporary file and renames it to the target.
+fn write_temp_and_rename<F>(path: &Path, temp_path: &Path, f: F) -> Result<()>
+where
+    F: FnOnce(&mut dyn Write) -> Result<()>,
+{
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(temp_path)?;
+    // Keep the permissions of the existing file.
+    match fs::metadata(path) {
+        Ok(metadata) => file.set_permissions(metadata.permissions())?,
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    {
+        let mut writer = BufWriter::new(&mut file);
+        f(&mut writer)?;
+        writer.flush()?;
+    }
+    file.sync_all()?;
+    drop(file);
+    fs::rename(temp_path, path)?;
+    // Make the rename durable.
+    #[cfg(unix)]
+    {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            File::open(dir)?.sync_all()?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_write_keeps_file() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("file.toml");
+        write(&path, "old").unwrap();
+
+        let result = update_with(&path, |content, out| {
+            assert_eq!(content, "old");
+            out.write_all(b"partial")?;
+            Err(Error::new(ErrorKind::Other, "failure"))
+        });
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        assert!(!sibling_path(&path, ".tmp").unwrap().exists());
+
+        update_with(&path, |content, out| write!(out, "{}new", content)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "oldnew");
+    }
+}
@@ -0,0 +1,746 @@
+//! Repository metadata.
+//!
+//! Metadata is information about the repository stored in the cache, mainly
+//! derived from the repository content. Derived metadata is updated only when
+//! explicitly requested (for example, `refresh --metadata`), since it can be
+//! expensive to compute.
+
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cache::path_serde,
+    vcs::{Vcs, VcsError},
+};
+
+/// Repository metadata.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub struct RepoMetadata {
+    /// Dominant language.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    language: Option<String>,
+    /// License (SPDX license expression).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    license: Option<String>,
+    /// Latest commit time of HEAD and the local branches in seconds since the
+    /// UNIX epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_commit_at: Option<u64>,
+    /// Expiry time of the scratch clone in seconds since the UNIX epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<u64>,
+    /// Whether the repository is frozen (pinned at the current state).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    frozen: bool,
+    /// Disk usage.
+    ///
+    /// This should be placed after non-table fields to be serialized as TOML.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    disk_usage: Option<DiskUsage>,
+    /// Derived data resolved beforehand, to avoid opening the repository.
+    ///
+    /// This should be placed after non-table fields to be serialized as TOML.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    resolved: Option<Resolved>,
+    /// Metadata of the upstream repository on a forge.
+    ///
+    /// This should be placed after non-table fields to be serialized as TOML.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    upstream: Option<Upstream>,
+}
+
+impl RepoMetadata {
+    /// Refreshes metadata derived from the repository content (such as the
+    /// language, the license, and the last commit time).
+    ///
+    /// Expensive metadata such as disk usage is not refreshed by this method.
+    pub fn refresh(&mut self, vcs: Vcs, repo_path: &Path) -> Result<(), VcsError> {
+        let files = vcs.tracked_files(repo_path)?;
+        self.language = detect_language(files.iter().map(AsRef::as_ref)).map(ToOwned::to_owned);
+
+        let mut licenses = Vec::new();
+        for file in files.iter().filter(|file| is_license_file(file)) {
+            let content = match vcs.head_file_content(repo_path, file)? {
+                Some(v) => v,
+                None => continue,
+            };
+            if let Some(license) = detect_license(&String::from_utf8_lossy(&content)) {
+                licenses.push(license.to_owned());
+            }
+        }
+        self.license = license_expression(licenses);
+        self.last_commit_at = vcs.last_commit_time(repo_path)?;
+
+        Ok(())
+    }
+
+    /// Copies metadata not set in `self` from the old metadata.
+    ///
+    /// This is useful to keep metadata not derived from the repository content
+    /// (such as the expiry time and the frozen flag) when the derived metadata
+    /// is refreshed.
+    pub fn inherit_from(&mut self, old: &Self) {
+        if self.is_empty() {
+            *self = old.clone();
+            return;
+        }
+        if self.expires_at.is_none() {
+            self.expires_at = old.expires_at;
+        }
+        self.frozen |= old.frozen;
+        if self.disk_usage.is_none() {
+            self.disk_usage = old.disk_usage;
+        }
+        if self.resolved.is_none() {
+            self.resolved = old.resolved.clone();
+        }
+        if self.upstream.is_none() {
+            self.upstream = old.upstream.clone();
+        }
+    }
+
+    /// Returns true if no metadata is available.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Returns the dominant language.
+    #[inline]
+    #[must_use]
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    /// Returns the license as an SPDX license expression (such as `MIT` or
+    /// `Apache-2.0 OR MIT`).
+    #[inline]
+    #[must_use]
+    pub fn license(&self) -> Option<&str> {
+        self.license.as_deref()
+    }
+
+    /// Returns the latest commit time of HEAD and the local branches in
+    /// seconds since the UNIX epoch.
+    #[inline]
+    #[must_use]
+    pub fn last_commit_at(&self) -> Option<u64> {
+        self.last_commit_at
+    }
+
+    /// Returns the disk usage measured last time.
+    #[inline]
+    #[must_use]
+    pub fn disk_usage(&self) -> Option<&DiskUsage> {
+        self.disk_usage.as_ref()
+    }
+
+    /// Sets the disk usage.
+    #[inline]
+    pub fn set_disk_usage(&mut self, disk_usage: DiskUsage) {
+        self.disk_usage = Some(disk_usage);
+    }
+
+    /// Returns the derived data resolved beforehand, if available.
+    ///
+    /// This is set by `magro cache warm`.
+    #[inline]
+    #[must_use]
+    pub fn resolved(&self) -> Option<&Resolved> {
+        self.resolved.as_ref()
+    }
+
+    /// Sets the derived data resolved beforehand.
+    #[inline]
+    pub fn set_resolved(&mut self, resolved: Option<Resolved>) {
+        self.resolved = resolved;
+    }
+
+    /// Returns the metadata of the upstream repository on a forge, if available.
+    ///
+    /// This is set by `magro forge enrich`.
+    #[inline]
+    #[must_use]
+    pub fn upstream(&self) -> Option<&Upstream> {
+        self.upstream.as_ref()
+    }
+
+    /// Sets the metadata of the upstream repository on a forge.
+    #[inline]
+    pub fn set_upstream(&mut self, upstream: Option<Upstream>) {
+        self.upstream = upstream;
+    }
+
+    /// Returns the expiry time of the scratch clone in seconds since the UNIX epoch.
+    ///
+    /// This is set only for scratch clones (created by `magro try`).
+    #[inline]
+    #[must_use]
+    pub fn expires_at(&self) -> Option<u64> {
+        self.expires_at
+    }
+
+    /// Sets the expiry time of the scratch clone in seconds since the UNIX epoch.
+    #[inline]
+    pub fn set_expires_at(&mut self, expires_at: Option<u64>) {
+        self.expires_at = expires_at;
+    }
+
+    /// Returns true if the repository is frozen.
+    ///
+    /// Frozen repositories are pinned at a specific state, and skipped by bulk
+    /// operations modifying repositories (such as `magro fetch`) by default.
+    #[inline]
+    #[must_use]
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Sets whether the repository is frozen.
+    #[inline]
+    pub fn set_frozen(&mut self, frozen: bool) {
+        self.frozen = frozen;
+    }
+
+    /// Returns true if the expiry time is set and has passed.
+    #[must_use]
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .map_or(false, |expires_at| expires_at <= unix_now())
+    }
+}
+
+/// Returns the current time in seconds since the UNIX epoch.
+#[must_use]
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Disk usage of a repository.
+///
+/// Sizes are apparent sizes (i.e. sums of file lengths) in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct DiskUsage {
+    /// Size of the repository directory (such as `.git` directory).
+    git_dir: u64,
+    /// Size of the working directory, excluding the repository directory.
+    worktree: u64,
+    /// Measured time in seconds since the UNIX epoch.
+    measured_at: u64,
+}
+
+impl DiskUsage {
+    /// Measures the disk usage of the repository.
+    ///
+    /// `repo_path` is the repository directory (such as `.git` directory),
+    /// and `workdir` is the working directory if available.
+    /// Symbolic links are not followed.
+    pub fn measure(repo_path: &Path, workdir: Option<&Path>) -> io::Result<Self> {
+        let git_dir = dir_size(repo_path, None)?;
+        let worktree = match workdir {
+            Some(workdir) => dir_size(workdir, Some(repo_path))?,
+            None => 0,
+        };
+        let measured_at = unix_now();
+
+        Ok(Self {
+            git_dir,
+            worktree,
+            measured_at,
+        })
+    }
+
+    /// Returns the size of the repository directory.
+    #[inline]
+    #[must_use]
+    pub fn git_dir(&self) -> u64 {
+        self.git_dir
+    }
+
+    /// Returns the size of the working directory, excluding the repository directory.
+    #[inline]
+    #[must_use]
+    pub fn worktree(&self) -> u64 {
+        self.worktree
+    }
+
+    /// Returns the total size.
+    #[inline]
+    #[must_use]
+    pub fn total(&self) -> u64 {
+        self.git_dir + self.worktree
+    }
+
+    /// Returns the measured time in seconds since the UNIX epoch.
+    #[inline]
+    #[must_use]
+    pub fn measured_at(&self) -> u64 {
+        self.measured_at
+    }
+}
+
+/// Derived data of a repository resolved beforehand.
+///
+/// This is stored in the cache so that frequently called operations (such as
+/// `list --workdir` and `locate` from shell prompts) need not open the
+/// repository. The data can be stale until resolved again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct Resolved {
+    /// Absolute path of the working directory.
+    ///
+    /// This is `None` if the repository has no working directory.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "path_serde::option"
+    )]
+    workdir: Option<PathBuf>,
+    /// Name of the branch checked out at HEAD.
+    ///
+    /// This is `None` if HEAD is detached.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    head_branch: Option<String>,
+    /// Resolved time in seconds since the UNIX epoch.
+    resolved_at: u64,
+}
+
+impl Resolved {
+    /// Resolves the derived data of the repository.
+    pub fn resolve(vcs: Vcs, repo_path: &Path) -> Result<Self, VcsError> {
+        let workdir = vcs.workdir(repo_path)?.map(Into::into);
+        let head_branch = vcs.head_branch(repo_path)?;
+
+        Ok(Self {
+            workdir,
+            head_branch,
+            resolved_at: unix_now(),
+        })
+    }
+
+    /// Returns the absolute path of the working directory, if available.
+    #[inline]
+    #[must_use]
+    pub fn workdir(&self) -> Option<&Path> {
+        self.workdir.as_deref()
+    }
+
+    /// Returns the name of the branch checked out at HEAD, if available.
+    #[inline]
+    #[must_use]
+    pub fn head_branch(&self) -> Option<&str> {
+        self.head_branch.as_deref()
+    }
+
+    /// Returns the resolved time in seconds since the UNIX epoch.
+    #[inline]
+    #[must_use]
+    pub fn resolved_at(&self) -> u64 {
+        self.resolved_at
+    }
+}
+
+/// Metadata of the upstream repository on a forge (such as GitHub).
+///
+/// This is fetched from the forge API, and can be stale until fetched again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct Upstream {
+    /// Full name of the repository on the forge (such as `owner/name`).
+    full_name: String,
+    /// Description.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    /// Number of stars.
+    #[serde(default)]
+    stars: u64,
+    /// Whether the repository is archived.
+    #[serde(default)]
+    archived: bool,
+    /// Whether the repository is a fork.
+    #[serde(default)]
+    fork: bool,
+    /// Fetched time in seconds since the UNIX epoch.
+    fetched_at: u64,
+}
+
+impl Upstream {
+    /// Creates the metadata from the repository fetched from the forge.
+    #[cfg(feature = "forge")]
+    #[must_use]
+    pub fn from_remote(repo: &crate::forge::RemoteRepo) -> Self {
+        Self {
+            full_name: repo.full_name().to_owned(),
+            description: repo.description().map(ToOwned::to_owned),
+            stars: repo.stars(),
+            archived: repo.is_archived(),
+            fork: repo.is_fork(),
+            fetched_at: unix_now(),
+        }
+    }
+
+    /// Returns the full name of the repository on the forge (such as `owner/name`).
+    #[inline]
+    #[must_use]
+    pub fn full_name(&self) -> &str {
+        &self.full_name
+    }
+
+    /// Returns the description.
+    #[inline]
+    #[must_use]
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Returns the number of stars.
+    #[inline]
+    #[must_use]
+    pub fn stars(&self) -> u64 {
+        self.stars
+    }
+
+    /// Returns whether the repository is archived.
+    #[inline]
+    #[must_use]
+    pub fn is_archived(&self) -> bool {
+        self.archived
+    }
+
+    /// Returns whether the repository is a fork.
+    #[inline]
+    #[must_use]
+    pub fn is_fork(&self) -> bool {
+        self.fork
+    }
+
+    /// Returns the fetched time in seconds since the UNIX epoch.
+    #[inline]
+    #[must_use]
+    pub fn fetched_at(&self) -> u64 {
+        self.fetched_at
+    }
+}
+
+/// Returns the total size of files under the directory.
+///
+/// If `exclude` is given, the directory is skipped.
+fn dir_size(dir: &Path, exclude: Option<&Path>) -> io::Result<u64> {
+    let mut size = 0;
+    let mut walker = walkdir::WalkDir::new(dir).into_iter();
+    while let Some(entry) = walker.next() {
+        let entry = entry.map_err(io::Error::from)?;
+        if exclude == Some(entry.path()) {
+            walker.skip_current_dir();
+            continue;
+        }
+        if entry.file_type().is_file() {
+            size += entry.metadata().map_err(io::Error::from)?.len();
+        }
+    }
+    Ok(size)
+}
+
+/// Detects the dominant language from the file paths.
+///
+/// The language with the most files is the dominant one.
+/// Files not recognized as source code (such as documents and config files)
+/// are ignored.
+///
+/// # Examples
+///
+/// ```
+/// # use std::path::Path;
+/// # use magro::metadata::detect_language;
+/// let files = ["README.md", "Cargo.toml", "src/main.rs", "src/lib.rs", "build.sh"];
+/// assert_eq!(detect_language(files.iter().map(Path::new)), Some("Rust"));
+/// ```
+#[must_use]
+pub fn detect_language<'a, I>(paths: I) -> Option<&'static str>
+where
+    I: IntoIterator<Item = &'a Path>,
+{
+    let mut counts: HashMap<&'static str, usize> = HashMap::new();
+    for path in paths {
+        let lang = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(language_for_extension);
+        if let Some(lang) = lang {
+            *counts.entry(lang).or_default() += 1;
+        }
+    }
+    // Break ties by the name, to make the result deterministic.
+    counts
+        .into_iter()
+        .max_by(|(lang_l, count_l), (lang_r, count_r)| {
+            count_l.cmp(count_r).then(lang_r.cmp(lang_l))
+        })
+        .map(|(lang, _)| lang)
+}
+
+/// Returns the language for the file extension.
+fn language_for_extension(ext: &str) -> Option<&'static str> {
+    let lang = match ext {
+        "c" | "h" => "C",
+        "cc" | "cpp" | "cxx" | "hh" | "hpp" | "hxx" => "C++",
+        "cs" => "C#",
+        "clj" | "cljs" | "cljc" => "Clojure",
+        "dart" => "Dart",
+        "el" => "Emacs Lisp",
+        "ex" | "exs" => "Elixir",
+        "erl" | "hrl" => "Erlang",
+        "fs" | "fsi" | "fsx" => "F#",
+        "go" => "Go",
+        "hs" | "lhs" => "Haskell",
+        "java" => "Java",
+        "js" | "mjs" | "cjs" | "jsx" => "JavaScript",
+        "jl" => "Julia",
+        "kt" | "kts" => "Kotlin",
+        "lua" => "Lua",
+        "m" | "mm" => "Objective-C",
+        "ml" | "mli" => "OCaml",
+        "nim" => "Nim",
+        "php" => "PHP",
+        "pl" | "pm" => "Perl",
+        "py" | "pyi" => "Python",
+        "r" | "R" => "R",
+        "rb" => "Ruby",
+        "rs" => "Rust",
+        "scala" => "Scala",
+        "sh" | "bash" | "zsh" => "Shell",
+        "swift" => "Swift",
+        "ts" | "tsx" | "mts" | "cts" => "TypeScript",
+        "vim" => "Vim script",
+        "vue" => "Vue",
+        "zig" => "Zig",
+        _ => return None,
+    };
+    Some(lang)
+}
+
+/// Returns true if the path looks like a license file at the repository root.
+fn is_license_file(path: &Path) -> bool {
+    if path
+        .parent()
+        .map_or(false, |parent| parent != Path::new(""))
+    {
+        return false;
+    }
+    let name = match path.file_name().and_then(|name| name.to_str()) {
+        Some(v) => v.to_ascii_lowercase(),
+        None => return false,
+    };
+    ["license", "licence", "copying", "unlicense"]
+        .iter()
+        .any(|prefix| match name.strip_prefix(prefix) {
+            Some(rest) => rest.is_empty() || rest.starts_with(|c| c == '.' || c == '-'),
+            None => false,
+        })
+}
+
+/// Detects the license from the content of a license file.
+///
+/// An SPDX license identifier in the content is preferred if exists.
+/// Otherwise, well-known phrases of popular licenses are searched.
+///
+/// # Examples
+///
+/// ```
+/// # use magro::metadata::detect_license;
+/// let text = "MIT License\n\nPermission is hereby granted, free of charge, ...";
+/// assert_eq!(detect_license(text), Some("MIT"));
+/// ```
+#[must_use]
+pub fn detect_license(text: &str) -> Option<&str> {
+    /// SPDX license identifier tag.
+    const SPDX_TAG: &str = "SPDX-License-Identifier:";
+
+    if let Some(pos) = text.find(SPDX_TAG) {
+        let id = text[(pos + SPDX_TAG.len())..]
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim();
+        if !id.is_empty() {
+            return Some(id);
+        }
+    }
+
+    // Normalize whitespaces and cases, since license texts are often rewrapped.
+    let text = text
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_ascii_lowercase();
+    let has = |phrase: &str| text.contains(phrase);
+    let license = if has("gnu affero general public license") {
+        "AGPL-3.0"
+    } else if has("gnu lesser general public license") {
+        if has("version 2.1") {
+            "LGPL-2.1"
+        } else {
+            "LGPL-3.0"
+        }
+    } else if has("gnu general public license") {
+        if has("version 3") {
+            "GPL-3.0"
+        } else {
+            "GPL-2.0"
+        }
+    } else if has("apache license") && has("version 2.0") {
+        "Apache-2.0"
+    } else if has("mozilla public license") && has("2.0") {
+        "MPL-2.0"
+    } else if has("boost software license") {
+        "BSL-1.0"
+    } else if has("cc0 1.0 universal") {
+        "CC0-1.0"
+    } else if has("this is free and unencumbered software released into the public domain") {
+        "Unlicense"
+    } else if has("do what the fuck you want to public license") {
+        "WTFPL"
+    } else if has("permission is hereby granted, free of charge") {
+        "MIT"
+    } else if has(
+        "permission to use, copy, modify, and/or distribute this software for any purpose",
+    ) || has(
+        "permission to use, copy, modify, and distribute this software for any purpose",
+    ) {
+        "ISC"
+    } else if has("redistribution and use in source and binary forms") {
+        if has("neither the name") || has("may be used to endorse or promote") {
+            "BSD-3-Clause"
+        } else {
+            "BSD-2-Clause"
+        }
+    } else if has("altered source versions must be plainly marked") {
+        "Zlib"
+    } else {
+        return None;
+    };
+
+    Some(license)
+}
+
+/// Combines the licenses detected from license files into an SPDX license expression.
+///
+/// Multiple license files (such as `LICENSE-APACHE` and `LICENSE-MIT`) are
+/// treated as alternatives.
+fn license_expression(mut licenses: Vec<String>) -> Option<String> {
+    licenses.sort_unstable();
+    licenses.dedup();
+    // LGPL projects usually ship the GPL text (`COPYING`) together.
+    for (lgpl, gpl) in [("LGPL-3.0", "GPL-3.0"), ("LGPL-2.1", "GPL-2.0")] {
+        if licenses.iter().any(|license| license == lgpl) {
+            licenses.retain(|license| license != gpl);
+        }
+    }
+    match licenses.as_slice() {
+        [] => None,
+        [license] => Some(license.clone()),
+        licenses => Some(
+            licenses
+                .iter()
+                .map(|license| {
+                    if license.contains(' ') {
+                        format!("({})", license)
+                    } else {
+                        license.clone()
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" OR "),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn license_files() {
+        for name in &[
+            "LICENSE",
+            "license.md",
+            "LICENSE-MIT",
+            "COPYING.LESSER",
+            "UNLICENSE",
+        ] {
+            assert!(is_license_file(Path::new(name)), "{}", name);
+        }
+        for name in &["src/LICENSE", "licenses.txt", "README.md"] {
+            assert!(!is_license_file(Path::new(name)), "{}", name);
+        }
+    }
+
+    #[test]
+    fn detect_licenses() {
+        assert_eq!(
+            detect_license("// SPDX-License-Identifier: MPL-2.0 OR MIT\n"),
+            Some("MPL-2.0 OR MIT")
+        );
+        assert_eq!(
+            detect_license("Apache License\n  Version 2.0, January 2004"),
+            Some("Apache-2.0")
+        );
+        assert_eq!(
+            detect_license("GNU GENERAL PUBLIC\nLICENSE\nVersion 3, 29 June 2007"),
+            Some("GPL-3.0")
+        );
+        assert_eq!(detect_license("All rights reserved."), None);
+    }
+
+    #[test]
+    fn combine_licenses() {
+        let combine = |licenses: &[&str]| {
+            license_expression(licenses.iter().map(|&license| license.to_owned()).collect())
+        };
+        assert_eq!(combine(&[]), None);
+        assert_eq!(
+            combine(&["MIT", "Apache-2.0", "MIT"]).as_deref(),
+            Some("Apache-2.0 OR MIT")
+        );
+        assert_eq!(
+            combine(&["GPL-3.0", "LGPL-3.0"]).as_deref(),
+            Some("LGPL-3.0")
+        );
+    }
+
+    #[test]
+    fn inherit_non_derived_metadata() {
+        let mut old = RepoMetadata {
+            language: Some("C".to_owned()),
+            ..Default::default()
+        };
+        old.set_frozen(true);
+        old.set_expires_at(Some(42));
+
+        // Refreshed derived metadata is kept, and others are inherited.
+        let mut refreshed = RepoMetadata {
+            language: Some("Rust".to_owned()),
+            ..Default::default()
+        };
+        refreshed.inherit_from(&old);
+        assert_eq!(refreshed.language(), Some("Rust"));
+        assert!(refreshed.is_frozen());
+        assert_eq!(refreshed.expires_at(), Some(42));
+
+        let mut empty = RepoMetadata::default();
+        empty.inherit_from(&old);
+        assert_eq!(empty, old);
+    }
+}
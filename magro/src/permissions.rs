@@ -0,0 +1,141 @@
+//! Permissions of directories created by magro.
+
+use std::{convert::TryFrom, fmt, fs, io, path::Path, str};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+/// Permission bits of directories created by magro.
+///
+/// This is serialized as an octal string (such as `"2775"`).
+/// The mode is only applied on Unix, and ignored on other platforms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct DirMode(u32);
+
+impl DirMode {
+    /// Maximum value of the mode (all permission bits, setuid, setgid, and sticky).
+    const MAX: u32 = 0o7777;
+
+    /// Creates a new `DirMode` from the permission bits.
+    pub fn new(bits: u32) -> Result<Self, DirModeError> {
+        if bits > Self::MAX {
+            return Err(DirModeError::OutOfRange(bits));
+        }
+        Ok(Self(bits))
+    }
+
+    /// Returns the permission bits.
+    #[inline]
+    #[must_use]
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Creates a directory with the mode.
+    ///
+    /// The mode is set explicitly after creation, so it is not masked by umask.
+    fn create_dir(self, path: &Path) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+            fs::DirBuilder::new().mode(self.0).create(path)?;
+            fs::set_permissions(path, fs::Permissions::from_mode(self.0))
+        }
+        #[cfg(not(unix))]
+        {
+            fs::DirBuilder::new().create(path)
+        }
+    }
+}
+
+impl fmt::Display for DirMode {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04o}", self.0)
+    }
+}
+
+impl str::FromStr for DirMode {
+    type Err = DirModeError;
+
+    /// Parses an octal mode, optionally prefixed by `0o`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s.strip_prefix("0o").unwrap_or(s);
+        if digits.is_empty() || !digits.bytes().all(|b| (b'0'..=b'7').contains(&b)) {
+            return Err(DirModeError::Invalid(s.to_owned()));
+        }
+        let bits =
+            u32::from_str_radix(digits, 8).map_err(|_| DirModeError::Invalid(s.to_owned()))?;
+        Self::new(bits)
+    }
+}
+
+impl TryFrom<String> for DirMode {
+    type Error = DirModeError;
+
+    #[inline]
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<DirMode> for String {
+    #[inline]
+    fn from(mode: DirMode) -> Self {
+        mode.to_string()
+    }
+}
+
+/// Error on creating a directory mode.
+#[derive(Debug, Clone, PartialEq, Eq, ThisError)]
+#[non_exhaustive]
+pub enum DirModeError {
+    /// The string is not an octal number.
+    #[error("Invalid directory mode {0:?} (expected octal number such as `2775`)")]
+    Invalid(String),
+    /// The mode has bits other than permission bits.
+    #[error("Directory mode {0:o} is out of range (expected 0 to 7777)")]
+    OutOfRange(u32),
+}
+
+/// Recursively creates a directory and all of its missing parents.
+///
+/// If the mode is given, it is applied to every directory newly created.
+/// Existing directories are left as is.
+pub fn create_dir_all(path: &Path, mode: Option<DirMode>) -> io::Result<()> {
+    let mode = match mode {
+        Some(mode) => mode,
+        None => return fs::DirBuilder::new().recursive(true).create(path),
+    };
+    if path.as_os_str().is_empty() || path.is_dir() {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent, Some(mode))?;
+    }
+    match mode.create_dir(path) {
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists && path.is_dir() => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_mode() {
+        assert_eq!("2775".parse::<DirMode>().map(DirMode::bits), Ok(0o2775));
+        assert_eq!("0o755".parse::<DirMode>().map(DirMode::bits), Ok(0o755));
+        assert_eq!(DirMode(0o755).to_string(), "0755");
+        assert!("".parse::<DirMode>().is_err());
+        assert!("0x755".parse::<DirMode>().is_err());
+        assert!("+755".parse::<DirMode>().is_err());
+        assert_eq!(
+            "17777".parse::<DirMode>(),
+            Err(DirModeError::OutOfRange(0o17777))
+        );
+    }
+}
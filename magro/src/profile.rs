@@ -0,0 +1,182 @@
+//! Configuration profiles.
+//!
+//! A profile is a separate set of collections, tags, and cache, such as
+//! `work` and `personal`.
+//! The main config is shared among profiles.
+//! Files of the profile are stored in `profiles/<name>/` under the config and
+//! cache directories.
+
+use std::{
+    convert::TryFrom,
+    fmt, ops,
+    path::{Path, PathBuf},
+    str,
+};
+
+use thiserror::Error as ThisError;
+
+/// Directory of profiles relative to the config and cache directories.
+const PROFILES_RELPATH: &str = "profiles";
+
+/// Profile name error.
+#[derive(Debug, Clone, ThisError)]
+#[error("Invalid profile name: {message}")]
+pub struct ProfileNameError {
+    /// Message.
+    message: String,
+}
+
+impl ProfileNameError {
+    /// Creates a new error with the given message.
+    #[inline]
+    #[must_use]
+    fn with_message(s: impl fmt::Display) -> Self {
+        Self {
+            message: s.to_string(),
+        }
+    }
+}
+
+/// Profile name.
+///
+/// Profile name should satisfy all restrictions below:
+///
+/// * Should not be empty.
+/// * Should consist of ASCII alphanumeric, ASCII hyphen, ASCII underscore,
+///   or ASCII period.
+/// * Should not start with an ASCII hyphen or an ASCII period.
+///
+/// # Examples
+///
+/// ```
+/// use std::convert::TryFrom;
+/// # use magro::profile::ProfileName;
+///
+/// assert_eq!(ProfileName::try_from("work").unwrap(), "work");
+/// assert_eq!(ProfileName::try_from("client_a.v2").unwrap(), "client_a.v2");
+///
+/// assert!(ProfileName::try_from("").is_err());
+/// assert!(ProfileName::try_from("-work").is_err());
+/// assert!(ProfileName::try_from("..").is_err());
+/// assert!(ProfileName::try_from("work/a").is_err());
+/// ```
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProfileName(String);
+
+impl ProfileName {
+    /// Returns the string slice for the profile name.
+    #[inline]
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the directory for the profile under the given base directory.
+    ///
+    /// The base directory is the config directory or the cache directory.
+    #[must_use]
+    pub fn dir(&self, base: &Path) -> PathBuf {
+        base.join(PROFILES_RELPATH).join(&self.0)
+    }
+
+    /// Validates the given string as a profile name.
+    fn validate(s: &str) -> Result<(), ProfileNameError> {
+        if s.is_empty() {
+            return Err(ProfileNameError::with_message("Empty profile name"));
+        }
+
+        match s.as_bytes()[0] {
+            b'-' => {
+                return Err(ProfileNameError::with_message(
+                    "Profile name starts with '-'",
+                ))
+            }
+            b'.' => {
+                return Err(ProfileNameError::with_message(
+                    "Profile name starts with '.'",
+                ))
+            }
+            _ => {}
+        }
+
+        if let Some(c) = s
+            .chars()
+            .find(|&c| !(c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.'))
+        {
+            return Err(ProfileNameError::with_message(format!(
+                "Invalid character {:?}",
+                c
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl PartialEq<&'_ str> for ProfileName {
+    #[inline]
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<str> for ProfileName {
+    #[inline]
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl fmt::Debug for ProfileName {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for ProfileName {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl ops::Deref for ProfileName {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl TryFrom<&'_ str> for ProfileName {
+    type Error = ProfileNameError;
+
+    #[inline]
+    fn try_from(s: &'_ str) -> Result<Self, Self::Error> {
+        Self::validate(s)?;
+
+        Ok(Self(s.into()))
+    }
+}
+
+impl TryFrom<String> for ProfileName {
+    type Error = ProfileNameError;
+
+    #[inline]
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::validate(&s)?;
+
+        Ok(Self(s))
+    }
+}
+
+impl str::FromStr for ProfileName {
+    type Err = ProfileNameError;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::try_from(s)
+    }
+}
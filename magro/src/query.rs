@@ -0,0 +1,133 @@
+//! Repository query.
+//!
+//! A query is matched against repository names (see [`RepoView::name`]) in a
+//! fuzzy manner, so that users can find repositories by short strings.
+
+use crate::view::RepoView;
+
+/// Score for the exact match.
+const SCORE_EXACT: u32 = 1000;
+/// Score for the match of the last components.
+const SCORE_SUFFIX_COMPONENTS: u32 = 800;
+/// Score for the match of the prefix of the basename.
+const SCORE_BASENAME_PREFIX: u32 = 600;
+/// Score for the substring match.
+const SCORE_SUBSTRING: u32 = 400;
+/// Maximum score for the subsequence match.
+const SCORE_SUBSEQUENCE_MAX: u32 = 200;
+
+/// Returns the match score of the name for the query, if matched.
+///
+/// Larger score means better match.
+/// Matching is case-insensitive unless the query contains uppercase characters
+/// ("smart case").
+/// Path separators in the name should be `/`.
+///
+/// # Examples
+///
+/// ```
+/// # use magro::query::score;
+/// assert!(score("github.com/foo/bar", "bar").is_some());
+/// assert!(score("github.com/foo/bar", "fbar").is_some());
+/// assert!(score("github.com/foo/bar", "baz").is_none());
+///
+/// // Exact match is the best.
+/// assert!(score("bar", "bar") > score("foo/bar", "bar"));
+/// // Basename match is better than substring match.
+/// assert!(score("foo/bar", "bar") > score("bar/foo", "bar"));
+/// ```
+#[must_use]
+pub fn score(name: &str, query: &str) -> Option<u32> {
+    if query.is_empty() {
+        return Some(0);
+    }
+    let case_sensitive = query.chars().any(char::is_uppercase);
+    let (name, query) = if case_sensitive {
+        (name.to_owned(), query.to_owned())
+    } else {
+        (name.to_lowercase(), query.to_lowercase())
+    };
+
+    if name == query {
+        return Some(SCORE_EXACT);
+    }
+    if name.ends_with(&query) && name[..(name.len() - query.len())].ends_with('/') {
+        return Some(SCORE_SUFFIX_COMPONENTS);
+    }
+    let basename = name.rsplit('/').next().unwrap_or(&name);
+    if basename.starts_with(&query) {
+        return Some(SCORE_BASENAME_PREFIX);
+    }
+    if name.contains(&query) {
+        return Some(SCORE_SUBSTRING);
+    }
+
+    // Subsequence match. Fewer gaps are better.
+    let mut name_chars = name.chars();
+    let mut gaps = 0_u32;
+    for q in query.chars() {
+        let mut skipped = 0;
+        loop {
+            match name_chars.next() {
+                Some(c) if c == q => break,
+                Some(_) => skipped += 1,
+                None => return None,
+            }
+        }
+        if skipped != 0 {
+            gaps += 1;
+        }
+    }
+    Some(SCORE_SUBSEQUENCE_MAX.saturating_sub(gaps))
+}
+
+/// Filters repositories by the query, and sorts them from the best match.
+///
+/// Repositories with the same score are sorted by shorter names first.
+#[must_use]
+pub fn find<'a>(repos: &'a [RepoView], query: &str) -> Vec<&'a RepoView> {
+    let mut matched = repos
+        .iter()
+        .filter_map(|repo| {
+            let name = repo.name_string();
+            score(&name, query).map(|score| (score, name.len(), repo))
+        })
+        .collect::<Vec<_>>();
+    matched.sort_by(|(score_l, len_l, _), (score_r, len_r, _)| {
+        score_r.cmp(score_l).then(len_l.cmp(len_r))
+    });
+    matched.into_iter().map(|(_, _, repo)| repo).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smart_case() {
+        assert!(score("Foo/Bar", "bar").is_some());
+        assert!(score("foo/bar", "Bar").is_none());
+        assert!(score("foo/Bar", "Bar").is_some());
+    }
+
+    #[test]
+    fn suffix_components() {
+        assert_eq!(
+            score("example.com/foo/bar", "foo/bar"),
+            Some(SCORE_SUFFIX_COMPONENTS)
+        );
+        // Suffix match at non-boundary is not a component match.
+        assert_ne!(
+            score("example.com/foo/xbar", "bar"),
+            Some(SCORE_SUFFIX_COMPONENTS)
+        );
+    }
+
+    #[test]
+    fn subsequence_prefers_fewer_gaps() {
+        let contiguous = score("magro-cli", "mgcl");
+        let scattered = score("m-a-g-c-l", "mgcl");
+        assert!(contiguous.is_some());
+        assert!(contiguous > scattered);
+    }
+}
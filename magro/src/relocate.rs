@@ -0,0 +1,262 @@
+//! Moving directories, possibly across filesystems.
+
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+use walkdir::WalkDir;
+
+/// Moves the directory `src` to `dest`.
+///
+/// The directory is renamed if possible.
+/// If renaming fails because `src` and `dest` are on different filesystems,
+/// the directory tree is copied and synced to the disk, the copy is verified,
+/// and then `src` is removed.
+/// Other errors on renaming are returned as is.
+///
+/// If copying fails, the incomplete copy is removed and `src` is kept as is.
+/// Once the copy is verified, the move is considered successful: if `src`
+/// cannot be removed completely, the remaining files are left with a warning.
+///
+/// `dest` should not exist, and its parent directory should exist.
+pub fn move_dir(src: &Path, dest: &Path) -> io::Result<()> {
+    if dest.symlink_metadata().is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("Destination {:?} already exists", dest),
+        ));
+    }
+    match fs::rename(src, dest) {
+        Ok(()) => return Ok(()),
+        Err(e) if is_cross_device(&e) => log::debug!(
+            "Failed to rename {:?} to {:?} ({}), copying instead",
+            src,
+            dest,
+            e
+        ),
+        Err(e) => return Err(e),
+    }
+
+    copy_and_remove(src, dest)
+}
+
+/// Returns true if the error is caused by renaming across filesystems.
+fn is_cross_device(e: &io::Error) -> bool {
+    /// `EXDEV` (the same value on Linux, macOS, and BSDs).
+    #[cfg(unix)]
+    const CROSS_DEVICE: i32 = 18;
+    /// `ERROR_NOT_SAME_DEVICE`.
+    #[cfg(windows)]
+    const CROSS_DEVICE: i32 = 17;
+
+    #[cfg(any(unix, windows))]
+    {
+        e.raw_os_error() == Some(CROSS_DEVICE)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = e;
+        false
+    }
+}
+
+/// Copies the directory tree `src` to `dest`, verifies the copy, and removes `src`.
+///
+/// If copying or verification fails, the incomplete copy is removed and
+/// `src` is kept.
+fn copy_and_remove(src: &Path, dest: &Path) -> io::Result<()> {
+    if let Err(e) = copy_tree(src, dest).and_then(|_| verify_copy(src, dest)) {
+        if let Err(remove_err) = fs::remove_dir_all(dest) {
+            log::error!(
+                "Failed to remove the incomplete copy {:?}: {}",
+                dest,
+                remove_err
+            );
+        }
+        return Err(e);
+    }
+    if let Some(parent) = dest.parent() {
+        sync_dir(parent)?;
+    }
+    log::debug!("Copied {:?} to {:?}, removing the source", src, dest);
+
+    // Rename the source first, so that a partially removed source is not
+    // mistaken for the original.
+    let removing = removing_path(src);
+    let to_remove = match fs::rename(src, &removing) {
+        Ok(()) => &removing,
+        Err(e) => {
+            log::debug!("Failed to rename {:?} to {:?}: {}", src, removing, e);
+            src
+        }
+    };
+    if let Err(e) = fs::remove_dir_all(to_remove) {
+        log::warn!(
+            "Moved {:?} to {:?}, but failed to remove {:?} completely: {}",
+            src,
+            dest,
+            to_remove,
+            e
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns the temporary path to rename the source to before removal.
+fn removing_path(src: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(src.file_name().unwrap_or_default());
+    name.push(".magro-removing");
+    src.with_file_name(name)
+}
+
+/// Copies the directory tree, preserving permissions and symbolic links.
+///
+/// Copied files and directories are synced to the disk.
+fn copy_tree(src: &Path, dest: &Path) -> io::Result<()> {
+    // Permissions of directories are set after their contents are copied,
+    // since read-only directories cannot have new entries.
+    let mut dirs: Vec<(PathBuf, fs::Permissions)> = Vec::new();
+    for entry in WalkDir::new(src) {
+        let entry = entry?;
+        let target = dest.join(
+            entry
+                .path()
+                .strip_prefix(src)
+                .expect("Should never fail: walked paths are under the root"),
+        );
+        let file_type = entry.file_type();
+        if file_type.is_dir() {
+            fs::create_dir(&target)?;
+            dirs.push((target, entry.metadata()?.permissions()));
+        } else if file_type.is_symlink() {
+            copy_symlink(entry.path(), &target)?;
+        } else {
+            copy_file(entry.path(), &target)?;
+        }
+    }
+    for (dir, permissions) in dirs.into_iter().rev() {
+        sync_dir(&dir)?;
+        fs::set_permissions(dir, permissions)?;
+    }
+
+    Ok(())
+}
+
+/// Copies the file with its permissions, and syncs the copy to the disk.
+fn copy_file(src: &Path, dest: &Path) -> io::Result<()> {
+    fs::copy(src, dest)?;
+    // Files may be read-only, so open the copy without write access.
+    File::open(dest)?.sync_all()
+}
+
+/// Syncs the directory entries to the disk.
+///
+/// This is no-op on non-Unix platforms.
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        File::open(dir)?.sync_all()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = dir;
+        Ok(())
+    }
+}
+
+/// Creates a symbolic link at `target` with the same destination as `link`.
+fn copy_symlink(link: &Path, target: &Path) -> io::Result<()> {
+    let points_to = fs::read_link(link)?;
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(points_to, target)
+    }
+    #[cfg(windows)]
+    {
+        if fs::metadata(link).map_or(false, |meta| meta.is_dir()) {
+            std::os::windows::fs::symlink_dir(points_to, target)
+        } else {
+            std::os::windows::fs::symlink_file(points_to, target)
+        }
+    }
+}
+
+/// Checks that `dest` has the same entries with the same types and sizes as `src`.
+fn verify_copy(src: &Path, dest: &Path) -> io::Result<()> {
+    for entry in WalkDir::new(src) {
+        let entry = entry?;
+        let target = dest.join(
+            entry
+                .path()
+                .strip_prefix(src)
+                .expect("Should never fail: walked paths are under the root"),
+        );
+        let src_meta = entry.metadata()?;
+        let dest_meta = target.symlink_metadata()?;
+        let matches = src_meta.file_type() == dest_meta.file_type()
+            && (!src_meta.is_file() || src_meta.len() == dest_meta.len());
+        if !matches {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Copy {:?} does not match the source {:?}",
+                    target,
+                    entry.path()
+                ),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copy_and_remove_tree() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let src = tempdir.path().join("src");
+        fs::create_dir_all(src.join("foo/.git/objects")).unwrap();
+        fs::write(src.join("foo/.git/HEAD"), "ref: refs/heads/main\n").unwrap();
+        fs::write(src.join("foo/README"), "hello").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("README", src.join("foo/README.md")).unwrap();
+
+        let dest = tempdir.path().join("dest");
+        copy_and_remove(&src, &dest).unwrap();
+        assert!(!src.exists());
+        assert!(!removing_path(&src).exists());
+        assert_eq!(
+            fs::read_to_string(dest.join("foo/.git/HEAD")).unwrap(),
+            "ref: refs/heads/main\n"
+        );
+        assert!(dest.join("foo/.git/objects").is_dir());
+        #[cfg(unix)]
+        assert_eq!(
+            fs::read_link(dest.join("foo/README.md")).unwrap(),
+            Path::new("README")
+        );
+        assert!(move_dir(&dest, &dest.join("foo")).is_err());
+    }
+
+    #[test]
+    fn rename_error_without_copy() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let src = tempdir.path().join("src");
+        fs::create_dir_all(src.join("foo")).unwrap();
+        fs::write(src.join("foo/README"), "hello").unwrap();
+
+        // Moving a directory into itself is not a cross-device error, and
+        // should fail without copying.
+        assert!(move_dir(&src, &src.join("foo/inner")).is_err());
+        assert!(!src.join("foo/inner").exists());
+        assert_eq!(fs::read_to_string(src.join("foo/README")).unwrap(), "hello");
+    }
+}
@@ -0,0 +1,268 @@
+//! Remotes of repositories.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// URLs of a remote.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteUrls {
+    /// Remote name.
+    name: String,
+    /// Fetch URL.
+    url: Option<String>,
+    /// Push URL, if set separately from the fetch URL.
+    push_url: Option<String>,
+}
+
+impl RemoteUrls {
+    /// Creates a new `RemoteUrls`.
+    #[inline]
+    #[must_use]
+    pub fn new(name: String, url: Option<String>, push_url: Option<String>) -> Self {
+        Self {
+            name,
+            url,
+            push_url,
+        }
+    }
+
+    /// Returns the remote name.
+    #[inline]
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the fetch URL.
+    #[inline]
+    #[must_use]
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    /// Returns the push URL, if set separately from the fetch URL.
+    #[inline]
+    #[must_use]
+    pub fn push_url(&self) -> Option<&str> {
+        self.push_url.as_deref()
+    }
+}
+
+/// Rule to rewrite remote URLs.
+///
+/// The first occurrence of the pattern in the URL is replaced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UrlRewriteRule {
+    /// Pattern to be replaced.
+    from: String,
+    /// Replacement.
+    to: String,
+}
+
+impl UrlRewriteRule {
+    /// Creates a new rule.
+    #[inline]
+    #[must_use]
+    pub fn new(from: String, to: String) -> Self {
+        Self { from, to }
+    }
+
+    /// Returns the rewritten URL, or `None` if the rule does not change the URL.
+    #[must_use]
+    pub fn apply(&self, url: &str) -> Option<String> {
+        if self.from.is_empty() || !url.contains(self.from.as_str()) {
+            return None;
+        }
+        let rewritten = url.replacen(self.from.as_str(), &self.to, 1);
+        if rewritten == url {
+            return None;
+        }
+        Some(rewritten)
+    }
+}
+
+/// Returns the URL rewritten by the first matching rule.
+///
+/// Returns `None` if no rules change the URL.
+#[must_use]
+pub fn rewrite_url(rules: &[UrlRewriteRule], url: &str) -> Option<String> {
+    rules.iter().find_map(|rule| rule.apply(url))
+}
+
+/// Protocol of remote URLs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum Protocol {
+    /// SSH (such as `git@example.com:foo/bar.git`).
+    Ssh,
+    /// HTTPS (such as `https://example.com/foo/bar.git`).
+    Https,
+}
+
+impl Protocol {
+    /// Returns the protocol name in lower case.
+    #[inline]
+    #[must_use]
+    pub fn name_lower(&self) -> &'static str {
+        match self {
+            Self::Ssh => "ssh",
+            Self::Https => "https",
+        }
+    }
+
+    /// Returns the URL converted to this protocol.
+    ///
+    /// HTTP(S) URLs, `ssh://` URLs, and scp-like syntax (`[user@]host:path`)
+    /// are supported. The user name `git` is used for SSH URLs, and ports are
+    /// dropped.
+    /// Returns `None` if the URL already uses this protocol or is not
+    /// supported (for example, local paths).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use magro::remote::Protocol;
+    /// assert_eq!(
+    ///     Protocol::Ssh.convert_url("https://example.com/foo/bar.git").as_deref(),
+    ///     Some("git@example.com:foo/bar.git")
+    /// );
+    /// assert_eq!(
+    ///     Protocol::Https.convert_url("git@example.com:foo/bar.git").as_deref(),
+    ///     Some("https://example.com/foo/bar.git")
+    /// );
+    /// ```
+    #[must_use]
+    pub fn convert_url(&self, url: &str) -> Option<String> {
+        let (protocol, host, path) = split_remote_url(url)?;
+        if protocol == *self {
+            return None;
+        }
+        Some(match self {
+            Self::Ssh => format!("git@{}:{}", host, path),
+            Self::Https => format!("https://{}/{}", host, path),
+        })
+    }
+}
+
+impl fmt::Display for Protocol {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name_lower())
+    }
+}
+
+/// Returns the host of the remote URL.
+///
+/// URLs with schemes (such as `https://` and `ssh://`) and scp-like syntax
+/// (`[user@]host:path`) are supported.
+/// Returns `None` for local paths and `file://` URLs.
+///
+/// # Examples
+///
+/// ```
+/// # use magro::remote::url_host;
+/// assert_eq!(url_host("https://example.com/foo/bar.git"), Some("example.com"));
+/// assert_eq!(url_host("git@example.com:foo/bar.git"), Some("example.com"));
+/// assert_eq!(url_host("/srv/git/foo.git"), None);
+/// ```
+#[must_use]
+pub fn url_host(url: &str) -> Option<&str> {
+    split_url(url).map(|(_scheme, host, _path)| host)
+}
+
+/// Splits the remote URL into the protocol, the host, and the path.
+///
+/// The path does not have the leading slash.
+fn split_remote_url(url: &str) -> Option<(Protocol, &str, &str)> {
+    let (scheme, host, path) = split_url(url)?;
+    let protocol = match scheme {
+        Some("http") | Some("https") => Protocol::Https,
+        Some("ssh") | Some("git+ssh") | Some("ssh+git") | None => Protocol::Ssh,
+        Some(_) => return None,
+    };
+    Some((protocol, host, path))
+}
+
+/// Splits the remote URL into the scheme, the host, and the path.
+///
+/// The scheme is `None` for scp-like syntax (`[user@]host:path`).
+/// The path does not have the leading slash.
+fn split_url(url: &str) -> Option<(Option<&str>, &str, &str)> {
+    if let Some((scheme, rest)) = url.split_once("://") {
+        if scheme == "file" {
+            return None;
+        }
+        let (authority, path) = rest.split_once('/')?;
+        let host = authority
+            .rsplit_once('@')
+            .map_or(authority, |(_, host)| host);
+        let host = host.split_once(':').map_or(host, |(host, _port)| host);
+        let path = path.trim_start_matches('/');
+        if host.is_empty() || path.is_empty() {
+            return None;
+        }
+        return Some((Some(scheme), host, path));
+    }
+
+    // scp-like syntax `[user@]host:path`.
+    let (userhost, path) = url.split_once(':')?;
+    // Windows paths such as `C:\foo` and local paths such as `./foo:bar`.
+    if userhost.len() <= 1 || userhost.contains('/') || userhost.contains('\\') {
+        return None;
+    }
+    let host = userhost.rsplit_once('@').map_or(userhost, |(_, host)| host);
+    let path = path.trim_start_matches('/');
+    if host.is_empty() || path.is_empty() {
+        return None;
+    }
+    Some((None, host, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrite_first_matching_rule() {
+        let rules = [
+            UrlRewriteRule::new(
+                "old-gitlab.company.com".to_owned(),
+                "gitlab.company.com".to_owned(),
+            ),
+            UrlRewriteRule::new("gitlab".to_owned(), "forgejo".to_owned()),
+        ];
+        assert_eq!(
+            rewrite_url(&rules, "git@old-gitlab.company.com:team/foo.git").as_deref(),
+            Some("git@gitlab.company.com:team/foo.git")
+        );
+        assert_eq!(
+            rewrite_url(&rules, "https://gitlab.example.com/foo").as_deref(),
+            Some("https://forgejo.example.com/foo")
+        );
+        assert_eq!(rewrite_url(&rules, "https://example.com/foo"), None);
+    }
+
+    #[test]
+    fn convert_protocols() {
+        let ssh = Protocol::Ssh;
+        let https = Protocol::Https;
+        assert_eq!(
+            ssh.convert_url("https://user@example.com:8443/foo/bar")
+                .as_deref(),
+            Some("git@example.com:foo/bar")
+        );
+        assert_eq!(
+            https
+                .convert_url("ssh://git@example.com:2222/foo/bar.git")
+                .as_deref(),
+            Some("https://example.com/foo/bar.git")
+        );
+        assert_eq!(ssh.convert_url("git@example.com:foo/bar.git"), None);
+        assert_eq!(https.convert_url("https://example.com/foo"), None);
+        assert_eq!(ssh.convert_url("/srv/git/foo.git"), None);
+        assert_eq!(ssh.convert_url("C:\\git\\foo"), None);
+        assert_eq!(ssh.convert_url("file:///srv/git/foo.git"), None);
+    }
+}
@@ -0,0 +1,271 @@
+//! Bounded-concurrency runner for per-repository operations.
+
+use std::{
+    collections::HashMap,
+    num::NonZeroUsize,
+    panic,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Condvar, Mutex, PoisonError,
+    },
+    thread,
+};
+
+/// Runner of operations on multiple items with bounded concurrency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Runner {
+    /// Maximum number of parallel jobs.
+    jobs: NonZeroUsize,
+}
+
+impl Default for Runner {
+    #[inline]
+    fn default() -> Self {
+        Self::sequential()
+    }
+}
+
+impl Runner {
+    /// Creates a new runner with the given maximum number of parallel jobs.
+    #[inline]
+    #[must_use]
+    pub fn new(jobs: NonZeroUsize) -> Self {
+        Self { jobs }
+    }
+
+    /// Creates a new runner which runs operations one by one on the current thread.
+    #[inline]
+    #[must_use]
+    pub fn sequential() -> Self {
+        Self {
+            jobs: NonZeroUsize::new(1).expect("1 is nonzero"),
+        }
+    }
+
+    /// Returns the maximum number of parallel jobs.
+    #[inline]
+    #[must_use]
+    pub fn jobs(&self) -> NonZeroUsize {
+        self.jobs
+    }
+
+    /// Runs `f` for each item, and returns items and results in the input order.
+    #[inline]
+    pub fn run<T, R, F>(&self, items: Vec<T>, f: F) -> Vec<(T, R)>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(&T) -> R + Send + Sync + 'static,
+    {
+        self.run_until(items, f, |_| false)
+    }
+
+    /// Runs `f` for each item until `stop` returns true for some result, and
+    /// returns items and results in the input order.
+    ///
+    /// Once `stop` returns true, no more items are started, and items not
+    /// started are not included in the returned vector.
+    /// Operations already running are completed.
+    ///
+    /// If `f` panics, the panic is propagated after all workers finish.
+    pub fn run_until<T, R, F, P>(&self, items: Vec<T>, f: F, stop: P) -> Vec<(T, R)>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(&T) -> R + Send + Sync + 'static,
+        P: Fn(&R) -> bool + Send + Sync + 'static,
+    {
+        let jobs = self.jobs.get().min(items.len());
+        if jobs <= 1 {
+            let mut results = Vec::with_capacity(items.len());
+            for item in items {
+                let result = f(&item);
+                let stopped = stop(&result);
+                results.push((item, result));
+                if stopped {
+                    break;
+                }
+            }
+            return results;
+        }
+
+        let f = Arc::new(f);
+        let stop = Arc::new(stop);
+        let queue = Arc::new(Mutex::new(items.into_iter().enumerate()));
+        let stopped = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = mpsc::channel();
+        let workers = (0..jobs)
+            .map(|_| {
+                let f = Arc::clone(&f);
+                let stop = Arc::clone(&stop);
+                let queue = Arc::clone(&queue);
+                let stopped = Arc::clone(&stopped);
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    while !stopped.load(Ordering::Acquire) {
+                        // Lock is held only while taking an item, so the
+                        // queue is never left inconsistent even if poisoned.
+                        let next = queue.lock().unwrap_or_else(PoisonError::into_inner).next();
+                        let (index, item) = match next {
+                            Some(v) => v,
+                            None => break,
+                        };
+                        let result = f(&item);
+                        if stop(&result) {
+                            stopped.store(true, Ordering::Release);
+                        }
+                        if tx.send((index, item, result)).is_err() {
+                            break;
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        drop(tx);
+
+        let mut results = rx.into_iter().collect::<Vec<_>>();
+        for worker in workers {
+            if let Err(e) = worker.join() {
+                panic::resume_unwind(e);
+            }
+        }
+        results.sort_by_key(|(index, _, _)| *index);
+        results
+            .into_iter()
+            .map(|(_, item, result)| (item, result))
+            .collect()
+    }
+}
+
+/// Limiter of concurrent operations per host.
+///
+/// This is used together with [`Runner`], to avoid tripping rate limits of
+/// forges by too many connections to the same host.
+#[derive(Debug)]
+pub struct HostLimiter {
+    /// Maximum number of concurrent operations per host.
+    limit: NonZeroUsize,
+    /// Number of running operations for each host.
+    running: Mutex<HashMap<String, usize>>,
+    /// Notified when an operation finishes.
+    released: Condvar,
+}
+
+impl HostLimiter {
+    /// Creates a new limiter with the given maximum number of concurrent
+    /// operations per host.
+    #[must_use]
+    pub fn new(limit: NonZeroUsize) -> Self {
+        Self {
+            limit,
+            running: Mutex::new(HashMap::new()),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Returns the maximum number of concurrent operations per host.
+    #[inline]
+    #[must_use]
+    pub fn limit(&self) -> NonZeroUsize {
+        self.limit
+    }
+
+    /// Waits until an operation for the host can be started, and returns the
+    /// permit to be held during the operation.
+    ///
+    /// Host names are compared case insensitively.
+    pub fn acquire(&self, host: &str) -> HostPermit<'_> {
+        let host = host.to_ascii_lowercase();
+        let mut running = self.running.lock().unwrap_or_else(PoisonError::into_inner);
+        while running.get(&host).copied().unwrap_or(0) >= self.limit.get() {
+            log::trace!("Waiting for an operation for {:?} to finish", host);
+            running = self
+                .released
+                .wait(running)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        *running.entry(host.clone()).or_default() += 1;
+
+        HostPermit {
+            limiter: self,
+            host,
+        }
+    }
+}
+
+/// Permit of an operation for a host, released on drop.
+#[derive(Debug)]
+pub struct HostPermit<'a> {
+    /// Limiter.
+    limiter: &'a HostLimiter,
+    /// Host name in lower case.
+    host: String,
+}
+
+impl Drop for HostPermit<'_> {
+    fn drop(&mut self) {
+        let mut running = self
+            .limiter
+            .running
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(count) = running.get_mut(&self.host) {
+            *count -= 1;
+            if *count == 0 {
+                running.remove(&self.host);
+            }
+        }
+        self.limiter.released.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_are_ordered() {
+        let runner = Runner::new(NonZeroUsize::new(4).unwrap());
+        let results = runner.run((0..100).collect(), |i| i * 2);
+        assert_eq!(results.len(), 100);
+        assert!(results
+            .iter()
+            .enumerate()
+            .all(|(i, &(item, result))| item == i && result == i * 2));
+    }
+
+    #[test]
+    fn sequential_stops() {
+        let results = Runner::sequential().run_until((0..10).collect(), |&i| i, |&i| i == 3);
+        assert_eq!(results, [(0, 0), (1, 1), (2, 2), (3, 3)]);
+    }
+
+    #[test]
+    fn host_limiter_bounds_concurrency() {
+        let limiter = Arc::new(HostLimiter::new(NonZeroUsize::new(2).unwrap()));
+        let running = Arc::new(Mutex::new(0_usize));
+        let max_running = Arc::new(Mutex::new(0_usize));
+        let runner = Runner::new(NonZeroUsize::new(8).unwrap());
+        let results = {
+            let (running, max_running) = (Arc::clone(&running), Arc::clone(&max_running));
+            runner.run((0..32).collect(), move |&i| {
+                let host = if i % 2 == 0 {
+                    "example.com"
+                } else {
+                    "EXAMPLE.COM"
+                };
+                let _permit = limiter.acquire(host);
+                {
+                    let mut running = running.lock().unwrap();
+                    *running += 1;
+                    let mut max_running = max_running.lock().unwrap();
+                    *max_running = (*max_running).max(*running);
+                }
+                thread::sleep(std::time::Duration::from_millis(1));
+                *running.lock().unwrap() -= 1;
+            })
+        };
+        assert_eq!(results.len(), 32);
+        assert!(*max_running.lock().unwrap() <= 2);
+    }
+}
@@ -0,0 +1,211 @@
+//! Safety checks before removing repositories.
+//!
+//! Destructive operations use [`check_removable`] to refuse removing
+//! repositories with work which would be lost.
+
+use std::{fmt, str};
+
+use thiserror::Error as ThisError;
+
+use crate::{
+    health::{HealthIssue, TagCheck},
+    vcs::VcsError,
+    view::RepoView,
+};
+
+/// Category of work which would be lost by removal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum RiskCategory {
+    /// Uncommitted changes (including untracked files) in the working tree.
+    Dirty,
+    /// Stashes.
+    Stashes,
+    /// Branches with unpushed commits or without upstreams, and repositories
+    /// without remotes.
+    UnpushedBranches,
+    /// Tags not pushed to any remote, and tags which could not be compared
+    /// with the remotes.
+    UnpushedTags,
+}
+
+impl RiskCategory {
+    /// Returns the category names.
+    #[inline]
+    #[must_use]
+    pub fn names() -> &'static [&'static str] {
+        &["dirty", "stashes", "unpushed-branches", "unpushed-tags"]
+    }
+
+    /// Returns the category name.
+    #[inline]
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Dirty => "dirty",
+            Self::Stashes => "stashes",
+            Self::UnpushedBranches => "unpushed-branches",
+            Self::UnpushedTags => "unpushed-tags",
+        }
+    }
+
+    /// Returns the category of the health issue, or `None` if no work will be
+    /// lost by the issue.
+    #[must_use]
+    pub fn of(issue: &HealthIssue) -> Option<Self> {
+        match issue {
+            HealthIssue::UncommittedChanges { .. } => Some(Self::Dirty),
+            HealthIssue::Stashes { .. } => Some(Self::Stashes),
+            HealthIssue::NoRemotes
+            | HealthIssue::UnpushedCommits { .. }
+            | HealthIssue::NoUpstream { .. } => Some(Self::UnpushedBranches),
+            HealthIssue::UnpushedTags { .. } | HealthIssue::RemoteTagsUnknown { .. } => {
+                Some(Self::UnpushedTags)
+            }
+            HealthIssue::DetachedHead => None,
+        }
+    }
+}
+
+impl fmt::Display for RiskCategory {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl str::FromStr for RiskCategory {
+    type Err = RiskCategoryParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dirty" => Ok(Self::Dirty),
+            "stashes" => Ok(Self::Stashes),
+            "unpushed-branches" => Ok(Self::UnpushedBranches),
+            "unpushed-tags" => Ok(Self::UnpushedTags),
+            _ => Err(RiskCategoryParseError(s.to_owned())),
+        }
+    }
+}
+
+/// Risk category parse error.
+#[derive(Debug, Clone, PartialEq, Eq, ThisError)]
+#[error(
+    "Unknown risk category {0:?} (expected `dirty`, `stashes`, `unpushed-branches`, or \
+     `unpushed-tags`)"
+)]
+pub struct RiskCategoryParseError(String);
+
+/// Result of the safety check before removal.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Verdict {
+    /// Issues which would lose work, with their categories.
+    risks: Vec<(RiskCategory, HealthIssue)>,
+}
+
+impl Verdict {
+    /// Creates a verdict from the health issues of the repository.
+    #[must_use]
+    pub fn from_issues(issues: Vec<HealthIssue>) -> Self {
+        let risks = issues
+            .into_iter()
+            .filter_map(|issue| RiskCategory::of(&issue).map(|category| (category, issue)))
+            .collect();
+        Self { risks }
+    }
+
+    /// Returns true if the repository can be removed without losing work.
+    #[inline]
+    #[must_use]
+    pub fn is_removable(&self) -> bool {
+        self.risks.is_empty()
+    }
+
+    /// Returns the issues which would lose work, with their categories.
+    #[inline]
+    #[must_use]
+    pub fn risks(&self) -> &[(RiskCategory, HealthIssue)] {
+        &self.risks
+    }
+
+    /// Returns the issues not in the overridden categories.
+    ///
+    /// The repository can be removed with the overrides if this is empty.
+    pub fn blocking<'a>(
+        &'a self,
+        overrides: &'a [RiskCategory],
+    ) -> impl Iterator<Item = &'a HealthIssue> + 'a {
+        self.risks
+            .iter()
+            .filter(move |(category, _)| !overrides.contains(category))
+            .map(|(_, issue)| issue)
+    }
+}
+
+/// Checks whether the repository can be removed without losing work.
+///
+/// Uncommitted changes, stashes, unpushed branches, and tags not pushed to any
+/// remote are checked. Tags are compared with the tags on the remotes, so the
+/// remotes are contacted unless [`RiskCategory::UnpushedTags`] is in
+/// `overrides`. Remote-tracking branches are not fetched, so run `git fetch`
+/// beforehand for accurate results.
+///
+/// Note that the returned verdict can still have risks in the `overrides`
+/// categories. Use [`Verdict::blocking`] to ignore them.
+pub fn check_removable(repo: &RepoView, overrides: &[RiskCategory]) -> Result<Verdict, VcsError> {
+    let tags = if overrides.contains(&RiskCategory::UnpushedTags) {
+        TagCheck::Skip
+    } else {
+        TagCheck::Remote
+    };
+    let issues = repo.vcs().health_issues(repo.path(), tags)?;
+    Ok(Verdict::from_issues(issues))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_are_per_category() {
+        let verdict = Verdict::from_issues(vec![
+            HealthIssue::DetachedHead,
+            HealthIssue::Stashes { count: 1 },
+            HealthIssue::NoUpstream {
+                branch: "topic".to_owned(),
+            },
+        ]);
+        assert!(!verdict.is_removable());
+        assert_eq!(verdict.risks().len(), 2);
+        assert_eq!(
+            verdict
+                .blocking(&[RiskCategory::Stashes])
+                .collect::<Vec<_>>(),
+            [&HealthIssue::NoUpstream {
+                branch: "topic".to_owned()
+            }]
+        );
+        assert_eq!(
+            verdict
+                .blocking(&[RiskCategory::Stashes, RiskCategory::UnpushedBranches])
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn unknown_remote_tags_are_risks() {
+        let verdict = Verdict::from_issues(vec![HealthIssue::RemoteTagsUnknown {
+            remotes: vec!["origin".to_owned()],
+        }]);
+        assert!(!verdict.is_removable());
+        assert_eq!(verdict.blocking(&[RiskCategory::UnpushedTags]).count(), 0);
+    }
+
+    #[test]
+    fn category_names_roundtrip() {
+        for name in RiskCategory::names() {
+            assert_eq!(name.parse::<RiskCategory>().unwrap().name(), *name);
+        }
+    }
+}
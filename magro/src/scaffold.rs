@@ -0,0 +1,144 @@
+//! Creating repositories from template repositories.
+
+use std::{borrow::Cow, cmp, collections::BTreeMap, fs, io, path::Path};
+
+use crate::{
+    collection::CollectionName,
+    template::{self, TemplateError},
+};
+
+/// Expands the replacement templates of the placeholders for the new repository.
+///
+/// Available variables are:
+///
+/// * `{collection}`: collection name.
+/// * `{path}`: path of the new repository relative to the collection directory.
+/// * `{name}`: the last component of `{path}`.
+///
+/// Empty placeholders are ignored. The returned replacements are sorted by
+/// the placeholder length (longest first), so that a placeholder containing
+/// another one is replaced first.
+pub fn replacements(
+    placeholders: &BTreeMap<String, String>,
+    collection: &CollectionName,
+    relpath: &Path,
+) -> Result<Vec<(String, String)>, TemplateError> {
+    let mut replacements = placeholders
+        .iter()
+        .filter(|(placeholder, _)| !placeholder.is_empty())
+        .map(|(placeholder, replacement)| {
+            let value = template::expand(replacement, |var| match var {
+                "collection" => Some(Cow::Borrowed(collection.as_str())),
+                "path" => Some(relpath.to_string_lossy()),
+                "name" => Some(relpath.file_name().unwrap_or_default().to_string_lossy()),
+                _ => None,
+            })?;
+            Ok((placeholder.clone(), value))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    replacements.sort_by_key(|(placeholder, _)| cmp::Reverse(placeholder.len()));
+
+    Ok(replacements)
+}
+
+/// Replaces the placeholders in file contents and file names under the working directory.
+///
+/// `.git` directories are not modified, and contents of files which are not
+/// valid UTF-8 are left unmodified.
+///
+/// Returns the number of modified files and directories.
+pub fn replace_placeholders(
+    workdir: &Path,
+    replacements: &[(String, String)],
+) -> io::Result<usize> {
+    if replacements.is_empty() {
+        return Ok(0);
+    }
+
+    let mut num_modified = 0;
+    // Visit children first, so that the paths of the children are not
+    // affected by the renames of their parents.
+    let walker = walkdir::WalkDir::new(workdir)
+        .min_depth(1)
+        .contents_first(true)
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != ".git");
+    for entry in walker {
+        let entry = entry?;
+        let mut modified = false;
+
+        if entry.file_type().is_file() {
+            if let Ok(content) = String::from_utf8(fs::read(entry.path())?) {
+                if let Cow::Owned(content) = replace_all(&content, replacements) {
+                    log::trace!("Replacing placeholders in {:?}", entry.path());
+                    fs::write(entry.path(), content)?;
+                    modified = true;
+                }
+            }
+        }
+
+        if let Some(name) = entry.file_name().to_str() {
+            if let Cow::Owned(new_name) = replace_all(name, replacements) {
+                let new_path = entry.path().with_file_name(new_name);
+                log::trace!("Renaming {:?} to {:?}", entry.path(), new_path);
+                fs::rename(entry.path(), new_path)?;
+                modified = true;
+            }
+        }
+
+        if modified {
+            num_modified += 1;
+        }
+    }
+
+    Ok(num_modified)
+}
+
+/// Replaces all occurrences of the placeholders in the string.
+///
+/// Returns `Cow::Borrowed` if nothing is replaced.
+fn replace_all<'a>(s: &'a str, replacements: &[(String, String)]) -> Cow<'a, str> {
+    let mut s = Cow::Borrowed(s);
+    for (placeholder, value) in replacements {
+        if s.contains(placeholder.as_str()) {
+            s = Cow::Owned(s.replace(placeholder.as_str(), value));
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    #[test]
+    fn expand_and_replace() {
+        let placeholders = vec![
+            ("my-template".to_owned(), "{name}".to_owned()),
+            ("my-template-owner".to_owned(), "{collection}".to_owned()),
+        ]
+        .into_iter()
+        .collect();
+        let collection = CollectionName::try_from("work").unwrap();
+        let replacements =
+            replacements(&placeholders, &collection, Path::new("example.com/foo")).unwrap();
+        assert_eq!(
+            replacements,
+            [
+                ("my-template-owner".to_owned(), "work".to_owned()),
+                ("my-template".to_owned(), "foo".to_owned()),
+            ]
+        );
+
+        assert_eq!(
+            replace_all("my-template by my-template-owner", &replacements),
+            "foo by work"
+        );
+        assert!(matches!(
+            replace_all("nothing", &replacements),
+            Cow::Borrowed(_)
+        ));
+    }
+}
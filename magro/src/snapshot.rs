@@ -0,0 +1,232 @@
+//! Snapshots of repository states.
+//!
+//! A snapshot manifest records the remote URL and the commit checked out at
+//! HEAD of each repository, so that the same state of multiple repositories
+//! can be reproduced later (for example, for releases).
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cache::path_serde,
+    collection::CollectionName,
+    lock_fs,
+    metadata::unix_now,
+    permissions::{self, DirMode},
+    vcs::{Vcs, VcsError},
+    view::RepoView,
+};
+
+/// Returns true if the name can be used as a snapshot name.
+///
+/// Snapshot names are used as file names, so they should not be empty, should
+/// not contain path separators, and should not start with `.`.
+///
+/// # Examples
+///
+/// ```
+/// # use magro::snapshot::is_valid_name;
+/// assert!(is_valid_name("v1.0"));
+/// assert!(!is_valid_name(""));
+/// assert!(!is_valid_name("../v1.0"));
+/// assert!(!is_valid_name(".hidden"));
+/// ```
+#[must_use]
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('.') && !name.contains(|c| c == '/' || c == '\\')
+}
+
+/// Snapshot of repository states.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Snapshot {
+    /// Creation time in seconds since the UNIX epoch.
+    created_at: u64,
+    /// Repositories.
+    #[serde(default, rename = "repo")]
+    repos: Vec<SnapshotRepo>,
+}
+
+impl Snapshot {
+    /// Creates a new snapshot of the repositories, created at the current time.
+    #[must_use]
+    pub fn new(repos: Vec<SnapshotRepo>) -> Self {
+        Self {
+            created_at: unix_now(),
+            repos,
+        }
+    }
+
+    /// Loads the snapshot from the given path.
+    pub fn from_path(path: &Path) -> io::Result<Self> {
+        let content = lock_fs::read_to_string(path)?;
+        toml::from_str(&content).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid snapshot file {:?}: {}", path, e),
+            )
+        })
+    }
+
+    /// Saves the snapshot to the given path.
+    ///
+    /// The parent directory is created if it does not exist.
+    pub fn save(&self, path: &Path, dir_mode: Option<DirMode>) -> io::Result<()> {
+        let content =
+            toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if let Some(parent) = path.parent() {
+            permissions::create_dir_all(parent, dir_mode)?;
+        }
+        lock_fs::write(path, content)
+    }
+
+    /// Returns the creation time in seconds since the UNIX epoch.
+    #[inline]
+    #[must_use]
+    pub fn created_at(&self) -> u64 {
+        self.created_at
+    }
+
+    /// Returns the repositories.
+    #[inline]
+    #[must_use]
+    pub fn repos(&self) -> &[SnapshotRepo] {
+        &self.repos
+    }
+}
+
+/// State of a repository in a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SnapshotRepo {
+    /// Collection name.
+    collection: CollectionName,
+    /// Path of the repository relative to the collection directory.
+    #[serde(with = "path_serde")]
+    path: PathBuf,
+    /// VCS type.
+    vcs: Vcs,
+    /// URL of the default remote (or the source URI if the remote is missing).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    /// Commit checked out at HEAD, or `None` if the repository has no commits.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    commit: Option<String>,
+}
+
+impl SnapshotRepo {
+    /// Records the current state of the repository.
+    ///
+    /// The URL of `origin` remote (`default` path for Mercurial) is recorded,
+    /// and the source URI recorded on clone is used if the remote is missing.
+    pub fn capture(repo: &RepoView) -> Result<Self, VcsError> {
+        let vcs = repo.vcs();
+        let remote = match vcs {
+            Vcs::Hg => "default",
+            _ => "origin",
+        };
+        let url = vcs
+            .remote_url(repo.path(), remote)?
+            .or_else(|| repo.source_uri().map(ToOwned::to_owned));
+        let commit = vcs.head_commit(repo.path())?;
+
+        Ok(Self {
+            collection: repo.collection().clone(),
+            path: repo.relative_path().to_owned(),
+            vcs,
+            url,
+            commit,
+        })
+    }
+
+    /// Returns the collection name.
+    #[inline]
+    #[must_use]
+    pub fn collection(&self) -> &CollectionName {
+        &self.collection
+    }
+
+    /// Returns the path of the repository relative to the collection directory.
+    #[inline]
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the VCS type.
+    #[inline]
+    #[must_use]
+    pub fn vcs(&self) -> Vcs {
+        self.vcs
+    }
+
+    /// Returns the remote URL.
+    #[inline]
+    #[must_use]
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    /// Returns the commit checked out at HEAD.
+    #[inline]
+    #[must_use]
+    pub fn commit(&self) -> Option<&str> {
+        self.commit.as_deref()
+    }
+
+    /// Returns the path of the working directory relative to the collection
+    /// directory, or `None` for bare repositories.
+    #[must_use]
+    pub fn workdir_path(&self) -> Option<&Path> {
+        let vcs_dir = match self.vcs {
+            Vcs::Hg => ".hg",
+            Vcs::Jj => ".jj",
+            Vcs::Pijul => ".pijul",
+            Vcs::Darcs => "_darcs",
+            _ => ".git",
+        };
+        if self.path.file_name()? == vcs_dir {
+            Some(self.path.parent().unwrap_or_else(|| Path::new("")))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    #[test]
+    fn toml_roundtrip() {
+        let collection = CollectionName::try_from("src").unwrap();
+        let snapshot = Snapshot::new(vec![
+            SnapshotRepo {
+                collection: collection.clone(),
+                path: PathBuf::from("a/.git"),
+                vcs: Vcs::Git,
+                url: Some("https://example.com/a.git".to_owned()),
+                commit: Some("0123456789abcdef0123456789abcdef01234567".to_owned()),
+            },
+            SnapshotRepo {
+                collection,
+                path: PathBuf::from("b.git"),
+                vcs: Vcs::Git,
+                url: None,
+                commit: None,
+            },
+        ]);
+
+        let content = toml::to_string(&snapshot).unwrap();
+        let loaded: Snapshot = toml::from_str(&content).unwrap();
+        assert_eq!(loaded, snapshot);
+        assert_eq!(loaded.repos()[0].workdir_path(), Some(Path::new("a")));
+        assert_eq!(loaded.repos()[1].workdir_path(), None);
+    }
+}
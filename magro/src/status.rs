@@ -0,0 +1,109 @@
+//! Working tree status.
+
+use std::fmt;
+
+use serde::Serialize;
+
+/// State of a working tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum StatusKind {
+    /// The working tree has no changes and no untracked files.
+    Clean,
+    /// The working tree has untracked files, but no changes to tracked files.
+    Untracked,
+    /// The working tree has changes to tracked files.
+    Dirty,
+}
+
+impl StatusKind {
+    /// Returns the state for the given numbers of files.
+    #[inline]
+    #[must_use]
+    pub fn from_counts(changed: usize, untracked: usize) -> Self {
+        match (changed, untracked) {
+            (0, 0) => Self::Clean,
+            (0, _) => Self::Untracked,
+            _ => Self::Dirty,
+        }
+    }
+
+    /// Returns the state name in lower case.
+    #[inline]
+    #[must_use]
+    pub fn name_lower(&self) -> &'static str {
+        match self {
+            Self::Clean => "clean",
+            Self::Untracked => "untracked",
+            Self::Dirty => "dirty",
+        }
+    }
+}
+
+impl fmt::Display for StatusKind {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name_lower())
+    }
+}
+
+/// Status of a working tree.
+///
+/// Ignored files are not counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct WorktreeStatus {
+    /// Number of tracked files with changes (staged or not).
+    changed: usize,
+    /// Number of untracked files.
+    untracked: usize,
+    /// State.
+    state: StatusKind,
+}
+
+impl WorktreeStatus {
+    /// Creates a new `WorktreeStatus`.
+    #[inline]
+    #[must_use]
+    pub fn new(changed: usize, untracked: usize) -> Self {
+        Self {
+            changed,
+            untracked,
+            state: StatusKind::from_counts(changed, untracked),
+        }
+    }
+
+    /// Returns the number of tracked files with changes.
+    #[inline]
+    #[must_use]
+    pub fn changed(&self) -> usize {
+        self.changed
+    }
+
+    /// Returns the number of untracked files.
+    #[inline]
+    #[must_use]
+    pub fn untracked(&self) -> usize {
+        self.untracked
+    }
+
+    /// Returns the state.
+    #[inline]
+    #[must_use]
+    pub fn state(&self) -> StatusKind {
+        self.state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_from_counts() {
+        assert_eq!(StatusKind::from_counts(0, 0), StatusKind::Clean);
+        assert_eq!(StatusKind::from_counts(0, 3), StatusKind::Untracked);
+        assert_eq!(StatusKind::from_counts(2, 0), StatusKind::Dirty);
+        assert_eq!(StatusKind::from_counts(2, 3), StatusKind::Dirty);
+    }
+}
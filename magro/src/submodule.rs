@@ -0,0 +1,92 @@
+//! Submodules of repositories.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+/// State of a submodule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum SubmoduleState {
+    /// The submodule is checked out at the commit recorded in the superproject.
+    UpToDate,
+    /// The submodule is not initialized or not cloned.
+    Uninitialized,
+    /// The checked out commit differs from the one recorded in the superproject.
+    OutOfSync,
+    /// The working directory of the submodule has uncommitted changes.
+    Modified,
+}
+
+impl SubmoduleState {
+    /// Returns the state name in lower case.
+    #[inline]
+    #[must_use]
+    pub fn name_lower(&self) -> &'static str {
+        match self {
+            Self::UpToDate => "up-to-date",
+            Self::Uninitialized => "uninitialized",
+            Self::OutOfSync => "out-of-sync",
+            Self::Modified => "modified",
+        }
+    }
+
+    /// Returns true if the submodule needs attention.
+    #[inline]
+    #[must_use]
+    pub fn is_problem(&self) -> bool {
+        *self != Self::UpToDate
+    }
+}
+
+impl fmt::Display for SubmoduleState {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name_lower())
+    }
+}
+
+/// Submodule of a repository.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Submodule {
+    /// Path of the submodule relative to the working directory of the superproject.
+    path: PathBuf,
+    /// URL of the submodule.
+    url: Option<String>,
+    /// State.
+    state: SubmoduleState,
+}
+
+impl Submodule {
+    /// Creates a new `Submodule`.
+    #[inline]
+    #[must_use]
+    pub fn new(path: PathBuf, url: Option<String>, state: SubmoduleState) -> Self {
+        Self { path, url, state }
+    }
+
+    /// Returns the path relative to the working directory of the superproject.
+    #[inline]
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the URL.
+    #[inline]
+    #[must_use]
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    /// Returns the state.
+    #[inline]
+    #[must_use]
+    pub fn state(&self) -> SubmoduleState {
+        self.state
+    }
+}
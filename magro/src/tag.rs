@@ -0,0 +1,471 @@
+//! Repository tags.
+//!
+//! Tags are labels attached to repositories by users, such as `wip` and
+//! `fork`.
+//! Unlike the cache, tags are not regenerated from the storage, so they are
+//! stored in a separate file in the config directory.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
+    fmt, io, ops,
+    path::{Path, PathBuf},
+    str,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use crate::{cache::path_serde, collection::CollectionName, lock_fs};
+
+/// Tag name error.
+#[derive(Debug, Clone, ThisError)]
+#[error("Invalid tag name: {message}")]
+pub struct TagError {
+    /// Message.
+    message: String,
+}
+
+impl TagError {
+    /// Creates a new error with the given message.
+    #[inline]
+    #[must_use]
+    fn with_message(s: impl fmt::Display) -> Self {
+        Self {
+            message: s.to_string(),
+        }
+    }
+}
+
+/// Tag name.
+///
+/// Tag name should satisfy all restrictions below:
+///
+/// * Should not be empty.
+/// * Should consist of ASCII alphanumeric, ASCII hyphen, ASCII underscore,
+///   or ASCII period.
+/// * Should not start with an ASCII hyphen.
+///
+/// # Examples
+///
+/// ```
+/// use std::convert::TryFrom;
+/// # use magro::tag::Tag;
+///
+/// assert_eq!(Tag::try_from("wip").unwrap(), "wip");
+/// assert_eq!(Tag::try_from("archive-candidate").unwrap(), "archive-candidate");
+/// assert_eq!(Tag::try_from("v1.0").unwrap(), "v1.0");
+///
+/// assert!(Tag::try_from("").is_err());
+/// assert!(Tag::try_from("-wip").is_err());
+/// assert!(Tag::try_from("foo bar").is_err());
+/// assert!(Tag::try_from("foo,bar").is_err());
+/// ```
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String")]
+#[serde(into = "String")]
+pub struct Tag(String);
+
+impl Tag {
+    /// Returns the string slice for the tag name.
+    #[inline]
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Validates the given string as a tag name.
+    fn validate(s: &str) -> Result<(), TagError> {
+        if s.is_empty() {
+            return Err(TagError::with_message("Empty tag name"));
+        }
+
+        if s.as_bytes()[0] == b'-' {
+            return Err(TagError::with_message("Tag name starts with '-'"));
+        }
+
+        if let Some(c) = s
+            .chars()
+            .find(|&c| !(c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.'))
+        {
+            return Err(TagError::with_message(format!("Invalid character {:?}", c)));
+        }
+
+        Ok(())
+    }
+}
+
+impl PartialEq<&'_ str> for Tag {
+    #[inline]
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<str> for Tag {
+    #[inline]
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl fmt::Debug for Tag {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for Tag {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl ops::Deref for Tag {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl TryFrom<&'_ str> for Tag {
+    type Error = TagError;
+
+    #[inline]
+    fn try_from(s: &'_ str) -> Result<Self, Self::Error> {
+        Self::validate(s)?;
+
+        Ok(Self(s.into()))
+    }
+}
+
+impl TryFrom<String> for Tag {
+    type Error = TagError;
+
+    #[inline]
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::validate(&s)?;
+
+        Ok(Self(s))
+    }
+}
+
+impl str::FromStr for Tag {
+    type Err = TagError;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::validate(s)?;
+
+        Ok(Self(s.into()))
+    }
+}
+
+impl From<Tag> for String {
+    #[inline]
+    fn from(s: Tag) -> Self {
+        s.0
+    }
+}
+
+/// Tags of repositories.
+///
+/// Repositories are identified by the collection names and the paths
+/// relative to the collection directories (same as the cache).
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Tags {
+    /// Tags for repositories in collections.
+    collections: BTreeMap<CollectionName, BTreeMap<PathBuf, BTreeSet<Tag>>>,
+}
+
+impl Tags {
+    /// Loads tags from the given path.
+    ///
+    /// Returns empty tags if the file does not exist.
+    pub(crate) fn from_path(path: &Path) -> io::Result<Self> {
+        let mut file = match lock_fs::open(path) {
+            Ok(v) => v,
+            Err(e) => match e.kind() {
+                io::ErrorKind::NotFound => return Ok(Self::default()),
+                _ => return Err(e),
+            },
+        };
+        let content = lock_fs::read_to_string_from_lockable_file(path, &mut file)?;
+        Self::from_file_content(path, &content)
+    }
+
+    /// Loads the latest tags from the given path, modifies them, and saves them.
+    ///
+    /// The tags file is locked during the whole operation, so concurrent
+    /// updates by other processes are not lost.
+    /// Returns the updated tags and the result of the function.
+    pub(crate) fn update_path<F, R>(path: &Path, f: F) -> io::Result<(Self, R)>
+    where
+        F: FnOnce(&mut Self) -> R,
+    {
+        let mut updated = None;
+        lock_fs::update_with(path, |content, out| {
+            let mut tags = Self::from_file_content(path, content)?;
+            let result = f(&mut tags);
+            let content = tags
+                .to_toml_string()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            out.write_all(content.as_bytes())?;
+            updated = Some((tags, result));
+            Ok(())
+        })?;
+        Ok(updated.expect("Should never fail: the tags are updated on success"))
+    }
+
+    /// Parses the content of the tags file at the given path.
+    fn from_file_content(path: &Path, content: &str) -> io::Result<Self> {
+        // Unlike the cache, tags cannot be regenerated. Never discard them.
+        Self::from_toml_str(content).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid tags file {:?}: {}", path, e),
+            )
+        })
+    }
+
+    /// Parses the content of a tags file.
+    fn from_toml_str(s: &str) -> Result<Self, toml::de::Error> {
+        let file: TagsFile = toml::from_str(s)?;
+        let mut tags = Self::default();
+        for entry in file.repos {
+            tags.collections
+                .entry(entry.collection)
+                .or_default()
+                .entry(entry.path)
+                .or_default()
+                .extend(entry.tags);
+        }
+        Ok(tags)
+    }
+
+    /// Serializes the tags as the content of a tags file.
+    fn to_toml_string(&self) -> Result<String, toml::ser::Error> {
+        let file = TagsFile {
+            repos: self
+                .iter()
+                .map(|(collection, path, tags)| RepoTagsEntry {
+                    collection: collection.clone(),
+                    path: path.to_owned(),
+                    tags: tags.clone(),
+                })
+                .collect(),
+        };
+        toml::to_string(&file)
+    }
+
+    /// Returns the tags of the repository.
+    #[must_use]
+    pub fn get(&self, collection: &CollectionName, path: &Path) -> Option<&BTreeSet<Tag>> {
+        self.collections.get(collection)?.get(path)
+    }
+
+    /// Returns true if the repository has any of the given tags.
+    #[must_use]
+    pub fn has_any(&self, collection: &CollectionName, path: &Path, tags: &[Tag]) -> bool {
+        self.get(collection, path).map_or(false, |repo_tags| {
+            tags.iter().any(|tag| repo_tags.contains(tag))
+        })
+    }
+
+    /// Adds the tag to the repository.
+    ///
+    /// Returns `false` if the repository already has the tag.
+    pub fn add(&mut self, collection: &CollectionName, path: &Path, tag: Tag) -> bool {
+        self.collections
+            .entry(collection.clone())
+            .or_default()
+            .entry(path.to_owned())
+            .or_default()
+            .insert(tag)
+    }
+
+    /// Removes the tag from the repository.
+    ///
+    /// Returns `false` if the repository does not have the tag.
+    pub fn remove(&mut self, collection: &CollectionName, path: &Path, tag: &Tag) -> bool {
+        let repos = match self.collections.get_mut(collection) {
+            Some(v) => v,
+            None => return false,
+        };
+        let tags = match repos.get_mut(path) {
+            Some(v) => v,
+            None => return false,
+        };
+        let removed = tags.remove(tag);
+        if tags.is_empty() {
+            repos.remove(path);
+            if repos.is_empty() {
+                self.collections.remove(collection);
+            }
+        }
+        removed
+    }
+
+    /// Removes all tags of the repository.
+    ///
+    /// Returns `false` if the repository has no tags.
+    pub fn remove_repo(&mut self, collection: &CollectionName, path: &Path) -> bool {
+        let repos = match self.collections.get_mut(collection) {
+            Some(v) => v,
+            None => return false,
+        };
+        let removed = repos.remove(path).is_some();
+        if repos.is_empty() {
+            self.collections.remove(collection);
+        }
+        removed
+    }
+
+    /// Renames the collection.
+    ///
+    /// Returns `false` if no repositories in the collection have tags.
+    pub(crate) fn rename_collection(&mut self, old_name: &str, new_name: CollectionName) -> bool {
+        match self.collections.remove(old_name) {
+            Some(repos) => {
+                self.collections.insert(new_name, repos);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves the tags of the repositories in the collection `from` into the
+    /// collection `into`, prepending `prefix` to the repository paths.
+    ///
+    /// Returns `false` if no repositories in the collection `from` have tags.
+    pub(crate) fn merge_collection(
+        &mut self,
+        from: &str,
+        into: &CollectionName,
+        prefix: &Path,
+    ) -> bool {
+        let repos = match self.collections.remove(from) {
+            Some(v) => v,
+            None => return false,
+        };
+        let into_repos = self.collections.entry(into.clone()).or_default();
+        for (path, tags) in repos {
+            into_repos
+                .entry(prefix.join(path))
+                .or_default()
+                .extend(tags);
+        }
+        true
+    }
+
+    /// Returns an iterator of the tagged repositories, sorted by the
+    /// collection names and the paths.
+    pub fn iter(&self) -> impl Iterator<Item = (&CollectionName, &Path, &BTreeSet<Tag>)> {
+        self.collections.iter().flat_map(|(collection, repos)| {
+            repos
+                .iter()
+                .map(move |(path, tags)| (collection, path.as_path(), tags))
+        })
+    }
+}
+
+/// Content of a tags file.
+#[derive(Default, Serialize, Deserialize)]
+struct TagsFile {
+    /// Tagged repositories.
+    #[serde(default, rename = "repo")]
+    repos: Vec<RepoTagsEntry>,
+}
+
+/// Tags of a repository in a tags file.
+#[derive(Serialize, Deserialize)]
+struct RepoTagsEntry {
+    /// Collection name.
+    collection: CollectionName,
+    /// Path of the repository relative to the collection directory.
+    #[serde(with = "path_serde")]
+    path: PathBuf,
+    /// Tags.
+    tags: BTreeSet<Tag>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toml_roundtrip() {
+        let collection = CollectionName::try_from("src").unwrap();
+        let mut tags = Tags::default();
+        assert!(tags.add(&collection, Path::new("a/.git"), "wip".parse().unwrap()));
+        assert!(tags.add(&collection, Path::new("a/.git"), "fork".parse().unwrap()));
+        assert!(!tags.add(&collection, Path::new("a/.git"), "wip".parse().unwrap()));
+        assert!(tags.add(&collection, Path::new("b.git"), "wip".parse().unwrap()));
+
+        let content = tags.to_toml_string().unwrap();
+        let mut loaded = Tags::from_toml_str(&content).unwrap();
+        assert_eq!(loaded, tags);
+
+        assert!(loaded.remove(&collection, Path::new("b.git"), &"wip".parse().unwrap()));
+        assert!(!loaded.remove(&collection, Path::new("b.git"), &"wip".parse().unwrap()));
+        assert_eq!(loaded.iter().count(), 1);
+    }
+
+    #[test]
+    fn merge_collection_with_prefix() {
+        let src = CollectionName::try_from("src").unwrap();
+        let sub = CollectionName::try_from("sub").unwrap();
+        let mut tags = Tags::default();
+        tags.add(&src, Path::new("sub/a/.git"), "wip".parse().unwrap());
+        tags.add(&sub, Path::new("a/.git"), "fork".parse().unwrap());
+
+        assert!(tags.merge_collection("sub", &src, Path::new("sub")));
+        assert!(!tags.merge_collection("sub", &src, Path::new("sub")));
+        assert_eq!(tags.iter().count(), 1);
+        assert_eq!(tags.get(&src, Path::new("sub/a/.git")).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn remove_repo() {
+        let collection = CollectionName::try_from("src").unwrap();
+        let mut tags = Tags::default();
+        tags.add(&collection, Path::new("a/.git"), "wip".parse().unwrap());
+        tags.add(&collection, Path::new("a/.git"), "fork".parse().unwrap());
+
+        assert!(tags.remove_repo(&collection, Path::new("a/.git")));
+        assert!(!tags.remove_repo(&collection, Path::new("a/.git")));
+        assert_eq!(tags.iter().count(), 0);
+    }
+
+    #[test]
+    fn concurrent_updates() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("tags.toml");
+        let collection = CollectionName::try_from("src").unwrap();
+
+        // Multiple processes tag repositories at the same time.
+        let handles = (0..8)
+            .map(|i| {
+                let path = path.clone();
+                let collection = collection.clone();
+                std::thread::spawn(move || {
+                    let repo = PathBuf::from(format!("repo{}/.git", i));
+                    Tags::update_path(&path, |tags| {
+                        tags.add(&collection, &repo, "wip".parse().unwrap())
+                    })
+                    .unwrap();
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let tags = Tags::from_path(&path).unwrap();
+        assert_eq!(tags.iter().count(), 8);
+    }
+}
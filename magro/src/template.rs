@@ -0,0 +1,168 @@
+//! String templates with `{variable}` placeholders.
+
+use std::{
+    borrow::Cow,
+    ffi::{OsStr, OsString},
+};
+
+use thiserror::Error as ThisError;
+
+/// Template expansion error.
+#[derive(Debug, Clone, PartialEq, Eq, ThisError)]
+#[non_exhaustive]
+pub enum TemplateError {
+    /// Unknown variable is used.
+    #[error("Unknown variable `{{{0}}}` in the template")]
+    UnknownVariable(String),
+    /// A brace is not closed.
+    #[error("Unclosed brace in the template")]
+    UnclosedBrace,
+}
+
+/// Part of a template.
+enum Part<'a> {
+    /// Literal text.
+    Literal(&'a str),
+    /// Name of a placeholder.
+    Variable(&'a str),
+}
+
+/// Splits the template into literal parts and placeholders, and passes them to `f`.
+///
+/// `{{` and `}}` are passed as literal `{` and `}` respectively.
+/// If a brace is not closed, returns the rest of the template from the brace.
+fn scan<'a, F>(template: &'a str, mut f: F) -> Result<(), &'a str>
+where
+    F: FnMut(Part<'a>),
+{
+    let mut rest = template;
+    while let Some(pos) = rest.find(|c| c == '{' || c == '}') {
+        f(Part::Literal(&rest[..pos]));
+        let after = &rest[(pos + 1)..];
+        if rest[pos..].starts_with("{{") || rest[pos..].starts_with("}}") {
+            f(Part::Literal(&rest[pos..=pos]));
+            rest = &after[1..];
+            continue;
+        }
+        if rest[pos..].starts_with('}') {
+            // Lone closing brace is treated literally.
+            f(Part::Literal("}"));
+            rest = after;
+            continue;
+        }
+        let end = after.find('}').ok_or(&rest[pos..])?;
+        f(Part::Variable(&after[..end]));
+        rest = &after[(end + 1)..];
+    }
+    f(Part::Literal(rest));
+
+    Ok(())
+}
+
+/// Expands `{variable}` placeholders in the template.
+///
+/// `{{` and `}}` are expanded to `{` and `}` respectively.
+/// `resolve` should return `None` for unknown variables.
+pub fn expand<'a, F>(template: &str, mut resolve: F) -> Result<String, TemplateError>
+where
+    F: FnMut(&str) -> Option<Cow<'a, str>>,
+{
+    let mut expanded = String::with_capacity(template.len());
+    let mut unknown = None;
+    scan(template, |part| match part {
+        Part::Literal(s) => expanded.push_str(s),
+        Part::Variable(name) => match resolve(name) {
+            Some(value) => expanded.push_str(&value),
+            None => {
+                unknown.get_or_insert(name);
+            }
+        },
+    })
+    .map_err(|_| TemplateError::UnclosedBrace)?;
+    if let Some(name) = unknown {
+        return Err(TemplateError::UnknownVariable(name.to_owned()));
+    }
+
+    Ok(expanded)
+}
+
+/// Expands `{variable}` placeholders in the template into an OS string,
+/// keeping unknown placeholders as is.
+///
+/// Unlike [`expand`], placeholders for which `resolve` returns `None` and
+/// unclosed braces are kept literally, so that braces meant for other tools
+/// (such as `find -exec {} ;` and awk programs) pass through.
+/// `{{` and `}}` are still expanded to `{` and `}` respectively.
+pub fn expand_os_lenient<'a, F>(template: &str, mut resolve: F) -> OsString
+where
+    F: FnMut(&str) -> Option<Cow<'a, OsStr>>,
+{
+    let mut expanded = OsString::with_capacity(template.len());
+    let result = scan(template, |part| match part {
+        Part::Literal(s) => expanded.push(s),
+        Part::Variable(name) => match resolve(name) {
+            Some(value) => expanded.push(value),
+            None => {
+                expanded.push("{");
+                expanded.push(name);
+                expanded.push("}");
+            }
+        },
+    });
+    if let Err(rest) = result {
+        expanded.push(rest);
+    }
+
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(name: &str) -> Option<Cow<'static, str>> {
+        match name {
+            "foo" => Some(Cow::Borrowed("FOO")),
+            "bar" => Some(Cow::Borrowed("BAR")),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn expand_variables() {
+        assert_eq!(expand("{foo}/{bar}.git", resolve).unwrap(), "FOO/BAR.git");
+        assert_eq!(expand("no variables", resolve).unwrap(), "no variables");
+    }
+
+    #[test]
+    fn escaped_braces() {
+        assert_eq!(expand("{{foo}}", resolve).unwrap(), "{foo}");
+        assert_eq!(expand("{{{foo}}}", resolve).unwrap(), "{FOO}");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            expand("{baz}", resolve),
+            Err(TemplateError::UnknownVariable("baz".to_owned()))
+        );
+        assert_eq!(expand("{foo", resolve), Err(TemplateError::UnclosedBrace));
+    }
+
+    #[test]
+    fn lenient_unknown_variables() {
+        let resolve_os = |name: &str| {
+            resolve(name).map(|value| match value {
+                Cow::Borrowed(s) => Cow::Borrowed(OsStr::new(s)),
+                Cow::Owned(s) => Cow::Owned(OsString::from(s)),
+            })
+        };
+        assert_eq!(expand_os_lenient("{foo}/{baz}", resolve_os), "FOO/{baz}");
+        assert_eq!(expand_os_lenient("{}", resolve_os), "{}");
+        assert_eq!(
+            expand_os_lenient("{print $1} {foo", resolve_os),
+            "{print $1} {foo"
+        );
+        assert_eq!(expand_os_lenient("{{foo}}", resolve_os), "{foo}");
+    }
+}
@@ -0,0 +1,138 @@
+//! Timing instrumentation.
+//!
+//! Durations of named spans are recorded process-wide once [`enable`] is
+//! called, and the breakdown can be taken by [`take`].
+//! Recording is disabled by default, and spans cost almost nothing then.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+
+use once_cell::sync::Lazy;
+
+/// Whether the recording is enabled.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Recorded timings, in order of the first record.
+static RECORDS: Lazy<Mutex<Vec<Timing>>> = Lazy::new(Default::default);
+
+/// Total duration of spans with the same name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Timing {
+    /// Name of the spans.
+    name: String,
+    /// Number of the spans.
+    count: usize,
+    /// Total duration of the spans.
+    total: Duration,
+}
+
+impl Timing {
+    /// Returns the name of the spans.
+    #[inline]
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the number of the spans.
+    #[inline]
+    #[must_use]
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns the total duration of the spans.
+    ///
+    /// Note that this can be longer than the wall-clock time if the spans run
+    /// in parallel.
+    #[inline]
+    #[must_use]
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+}
+
+/// Timing span, which records the duration from creation to drop.
+#[derive(Debug)]
+#[must_use = "The span is recorded when dropped"]
+pub struct Span {
+    /// Name and start time, or `None` if the recording is disabled.
+    inner: Option<(String, Instant)>,
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if let Some((name, start)) = self.inner.take() {
+            record(name, start.elapsed());
+        }
+    }
+}
+
+/// Enables the recording.
+#[inline]
+pub fn enable() {
+    ENABLED.store(true, Ordering::Release);
+}
+
+/// Returns true if the recording is enabled.
+#[inline]
+#[must_use]
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Acquire)
+}
+
+/// Starts a span with the given name.
+///
+/// Spans with the same name are summed up.
+#[inline]
+pub fn span<S: Into<String>>(name: S) -> Span {
+    Span {
+        inner: is_enabled().then(|| (name.into(), Instant::now())),
+    }
+}
+
+/// Records the duration for the name.
+fn record(name: String, elapsed: Duration) {
+    let mut records = RECORDS.lock().unwrap_or_else(PoisonError::into_inner);
+    match records.iter_mut().find(|timing| timing.name == name) {
+        Some(timing) => {
+            timing.count += 1;
+            timing.total += elapsed;
+        }
+        None => records.push(Timing {
+            name,
+            count: 1,
+            total: elapsed,
+        }),
+    }
+}
+
+/// Takes the recorded timings, in order of the first record.
+#[must_use]
+pub fn take() -> Vec<Timing> {
+    let mut records = RECORDS.lock().unwrap_or_else(PoisonError::into_inner);
+    std::mem::take(&mut *records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spans_with_same_name_are_summed() {
+        enable();
+        drop(span("timing test"));
+        drop(span("timing test"));
+
+        let timing = take()
+            .into_iter()
+            .find(|timing| timing.name() == "timing test")
+            .expect("Should be recorded");
+        assert_eq!(timing.count(), 2);
+    }
+}
@@ -0,0 +1,332 @@
+//! Trash of removed repositories.
+//!
+//! Removed repositories can be moved to the trash directory in the cache
+//! directory instead of being deleted immediately, and restored later.
+//! The trash directory consists of an index file and the directories of
+//! removed repositories.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cache::{path_serde, RepoCacheEntry},
+    collection::CollectionName,
+    lock_fs,
+    metadata::unix_now,
+    relocate,
+};
+
+/// Index file path relative to the trash directory.
+const INDEX_RELPATH: &str = "index.toml";
+
+/// Directory of removed repositories relative to the trash directory.
+const ITEMS_RELPATH: &str = "items";
+
+/// Seconds in a day.
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Removed repository in the trash.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TrashItem {
+    /// ID of the item.
+    id: String,
+    /// Collection the repository was removed from.
+    collection: CollectionName,
+    /// Removed directory relative to the collection directory.
+    ///
+    /// This is the working directory for non-bare repositories.
+    #[serde(with = "path_serde")]
+    dir: PathBuf,
+    /// Time of removal in seconds since the UNIX epoch.
+    removed_at: u64,
+    /// Cache entry of the removed repository.
+    ///
+    /// This should be placed after non-table fields to be serialized as TOML.
+    entry: RepoCacheEntry,
+}
+
+impl TrashItem {
+    /// Returns the ID.
+    #[inline]
+    #[must_use]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns the collection the repository was removed from.
+    #[inline]
+    #[must_use]
+    pub fn collection(&self) -> &CollectionName {
+        &self.collection
+    }
+
+    /// Returns the removed directory relative to the collection directory.
+    #[inline]
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the time of removal in seconds since the UNIX epoch.
+    #[inline]
+    #[must_use]
+    pub fn removed_at(&self) -> u64 {
+        self.removed_at
+    }
+
+    /// Returns the cache entry of the removed repository.
+    #[inline]
+    #[must_use]
+    pub fn entry(&self) -> &RepoCacheEntry {
+        &self.entry
+    }
+
+    /// Returns true if the item is kept longer than the retention period.
+    #[must_use]
+    pub fn is_expired(&self, retention_days: u64) -> bool {
+        let retention = retention_days.saturating_mul(SECS_PER_DAY);
+        self.removed_at.saturating_add(retention) <= unix_now()
+    }
+}
+
+/// Trash of removed repositories.
+#[derive(Debug, Clone)]
+pub struct Trash {
+    /// Trash directory.
+    dir: PathBuf,
+    /// Items, in order of removal.
+    items: Vec<TrashItem>,
+}
+
+impl Trash {
+    /// Loads the trash in the given directory.
+    ///
+    /// If the index file does not exist, returns an empty trash.
+    pub(crate) fn from_dir(dir: &Path) -> io::Result<Self> {
+        let path = dir.join(INDEX_RELPATH);
+        let mut file = match lock_fs::open(&path) {
+            Ok(v) => v,
+            Err(e) => match e.kind() {
+                io::ErrorKind::NotFound => {
+                    return Ok(Self {
+                        dir: dir.to_owned(),
+                        items: Vec::new(),
+                    })
+                }
+                _ => return Err(e),
+            },
+        };
+        let content = lock_fs::read_to_string_from_lockable_file(&path, &mut file)?;
+        Ok(Self {
+            dir: dir.to_owned(),
+            items: parse_index(&path, &content)?,
+        })
+    }
+
+    /// Loads the latest index in the given directory, modifies the trash, and
+    /// saves the index.
+    ///
+    /// The index file is locked during the whole operation, so concurrent
+    /// updates by other processes (for example, `rm` running at the same
+    /// time) are not lost.
+    /// Returns the updated trash and the result of the function.
+    pub(crate) fn update_dir<F, R>(dir: &Path, f: F) -> io::Result<(Self, R)>
+    where
+        F: FnOnce(&mut Self) -> R,
+    {
+        if !dir.is_dir() {
+            fs::DirBuilder::new().recursive(true).create(dir)?;
+        }
+        let path = dir.join(INDEX_RELPATH);
+        let mut updated = None;
+        lock_fs::update_with(&path, |content, out| {
+            let mut trash = Self {
+                dir: dir.to_owned(),
+                items: parse_index(&path, content)?,
+            };
+            let result = f(&mut trash);
+            let index = TrashIndex {
+                items: trash.items.clone(),
+            };
+            let content = toml::to_string(&index)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            out.write_all(content.as_bytes())?;
+            updated = Some((trash, result));
+            Ok(())
+        })?;
+        Ok(updated.expect("Should never fail: the trash is updated on success"))
+    }
+
+    /// Returns the items, in order of removal.
+    #[inline]
+    #[must_use]
+    pub fn items(&self) -> &[TrashItem] {
+        &self.items
+    }
+
+    /// Returns the item with the given ID.
+    #[must_use]
+    pub fn get(&self, id: &str) -> Option<&TrashItem> {
+        self.items.iter().find(|item| item.id == id)
+    }
+
+    /// Returns the path to the content of the item.
+    #[inline]
+    #[must_use]
+    pub fn item_path(&self, item: &TrashItem) -> PathBuf {
+        self.dir.join(ITEMS_RELPATH).join(&item.id)
+    }
+
+    /// Moves the repository directory `source` into the trash.
+    ///
+    /// `dir` is the directory relative to the collection directory, and
+    /// `entry` is the cache entry of the repository.
+    ///
+    /// If the trash is on another filesystem, the directory is copied and
+    /// then removed (see [`relocate::move_dir`]).
+    pub fn put(
+        &mut self,
+        collection: CollectionName,
+        dir: PathBuf,
+        source: &Path,
+        entry: RepoCacheEntry,
+    ) -> io::Result<&TrashItem> {
+        let removed_at = unix_now();
+        let items_dir = self.dir.join(ITEMS_RELPATH);
+        let id = (0_u32..)
+            .map(|n| format!("{}-{}", removed_at, n))
+            .find(|id| self.get(id).is_none() && !items_dir.join(id).exists())
+            .expect("Should never fail: there are enough candidates");
+        fs::DirBuilder::new().recursive(true).create(&items_dir)?;
+        relocate::move_dir(source, &items_dir.join(&id))?;
+
+        self.items.push(TrashItem {
+            id,
+            collection,
+            dir,
+            removed_at,
+            entry,
+        });
+        Ok(self
+            .items
+            .last()
+            .expect("Should never fail: pushed just now"))
+    }
+
+    /// Moves the content of the item to `dest`, and removes the item from the trash.
+    ///
+    /// Fails if `dest` already exists.
+    pub fn restore(&mut self, id: &str, dest: &Path) -> io::Result<TrashItem> {
+        let index = self.position(id)?;
+        if dest.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("Restore destination {:?} already exists", dest),
+            ));
+        }
+        if let Some(parent) = dest.parent() {
+            fs::DirBuilder::new().recursive(true).create(parent)?;
+        }
+        relocate::move_dir(&self.item_path(&self.items[index]), dest)?;
+        Ok(self.items.remove(index))
+    }
+
+    /// Deletes the content of the item permanently, and removes the item from the trash.
+    pub fn purge(&mut self, id: &str) -> io::Result<TrashItem> {
+        let index = self.position(id)?;
+        let path = self.item_path(&self.items[index]);
+        match fs::remove_dir_all(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                log::debug!("Trash item directory {:?} does not exist", path);
+            }
+            Err(e) => return Err(e),
+        }
+        Ok(self.items.remove(index))
+    }
+
+    /// Returns the index of the item with the given ID.
+    fn position(&self, id: &str) -> io::Result<usize> {
+        self.items
+            .iter()
+            .position(|item| item.id == id)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Trash item {:?} is not found", id),
+                )
+            })
+    }
+}
+
+/// Parses the content of the trash index file at the given path.
+fn parse_index(path: &Path, content: &str) -> io::Result<Vec<TrashItem>> {
+    // Removed repositories are not discoverable without the index. Never discard it.
+    let index: TrashIndex = toml::from_str(content).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid trash index file {:?}: {}", path, e),
+        )
+    })?;
+    Ok(index.items)
+}
+
+/// Content of a trash index file.
+#[derive(Serialize, Deserialize)]
+struct TrashIndex {
+    /// Removed repositories.
+    #[serde(default, rename = "item")]
+    items: Vec<TrashItem>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{convert::TryFrom, thread};
+
+    use crate::vcs::Vcs;
+
+    #[test]
+    fn concurrent_updates() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let trash_dir = tempdir.path().join("trash");
+        let collection = CollectionName::try_from("src").unwrap();
+
+        // Multiple `rm` put repositories into the trash at the same time.
+        let handles = (0..8)
+            .map(|i| {
+                let name = format!("repo{}", i);
+                let source = tempdir.path().join(&name);
+                fs::create_dir_all(source.join(".git")).unwrap();
+                let trash_dir = trash_dir.clone();
+                let collection = collection.clone();
+                thread::spawn(move || {
+                    let entry = RepoCacheEntry::new(Vcs::Git, format!("{}/.git", name));
+                    Trash::update_dir(&trash_dir, |trash| {
+                        trash
+                            .put(collection, PathBuf::from(&name), &source, entry)
+                            .map(drop)
+                    })
+                    .unwrap()
+                    .1
+                    .unwrap();
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let trash = Trash::from_dir(&trash_dir).unwrap();
+        assert_eq!(trash.items().len(), 8);
+        for item in trash.items() {
+            assert!(trash.item_path(item).join(".git").is_dir());
+        }
+    }
+}
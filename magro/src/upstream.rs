@@ -0,0 +1,131 @@
+//! Comparison of local branches with their upstreams.
+
+use std::fmt;
+
+use serde::Serialize;
+
+/// State of a local branch compared to its upstream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum UpstreamState {
+    /// The branch points to the same commit as the upstream.
+    UpToDate,
+    /// The branch has commits not in the upstream.
+    Ahead,
+    /// The upstream has commits not in the branch.
+    Behind,
+    /// Both the branch and the upstream have commits not in the other.
+    Diverged,
+}
+
+impl UpstreamState {
+    /// Returns the state for the given numbers of commits.
+    #[inline]
+    #[must_use]
+    pub fn from_counts(ahead: usize, behind: usize) -> Self {
+        match (ahead, behind) {
+            (0, 0) => Self::UpToDate,
+            (_, 0) => Self::Ahead,
+            (0, _) => Self::Behind,
+            _ => Self::Diverged,
+        }
+    }
+
+    /// Returns the state name in lower case.
+    #[inline]
+    #[must_use]
+    pub fn name_lower(&self) -> &'static str {
+        match self {
+            Self::UpToDate => "up-to-date",
+            Self::Ahead => "ahead",
+            Self::Behind => "behind",
+            Self::Diverged => "diverged",
+        }
+    }
+}
+
+impl fmt::Display for UpstreamState {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name_lower())
+    }
+}
+
+/// Local branch compared to its upstream.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BranchUpstream {
+    /// Local branch name.
+    branch: String,
+    /// Upstream branch name (such as `origin/main`).
+    upstream: String,
+    /// Number of commits in the branch but not in the upstream.
+    ahead: usize,
+    /// Number of commits in the upstream but not in the branch.
+    behind: usize,
+    /// State.
+    state: UpstreamState,
+}
+
+impl BranchUpstream {
+    /// Creates a new `BranchUpstream`.
+    #[inline]
+    #[must_use]
+    pub fn new(branch: String, upstream: String, ahead: usize, behind: usize) -> Self {
+        Self {
+            branch,
+            upstream,
+            ahead,
+            behind,
+            state: UpstreamState::from_counts(ahead, behind),
+        }
+    }
+
+    /// Returns the local branch name.
+    #[inline]
+    #[must_use]
+    pub fn branch(&self) -> &str {
+        &self.branch
+    }
+
+    /// Returns the upstream branch name.
+    #[inline]
+    #[must_use]
+    pub fn upstream(&self) -> &str {
+        &self.upstream
+    }
+
+    /// Returns the number of commits in the branch but not in the upstream.
+    #[inline]
+    #[must_use]
+    pub fn ahead(&self) -> usize {
+        self.ahead
+    }
+
+    /// Returns the number of commits in the upstream but not in the branch.
+    #[inline]
+    #[must_use]
+    pub fn behind(&self) -> usize {
+        self.behind
+    }
+
+    /// Returns the state.
+    #[inline]
+    #[must_use]
+    pub fn state(&self) -> UpstreamState {
+        self.state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_from_counts() {
+        assert_eq!(UpstreamState::from_counts(0, 0), UpstreamState::UpToDate);
+        assert_eq!(UpstreamState::from_counts(2, 0), UpstreamState::Ahead);
+        assert_eq!(UpstreamState::from_counts(0, 3), UpstreamState::Behind);
+        assert_eq!(UpstreamState::from_counts(2, 3), UpstreamState::Diverged);
+    }
+}
@@ -1,11 +1,39 @@
 //! Repository.
 
-use std::{borrow::Cow, convert::TryFrom, iter, mem, path::Path, str};
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::BTreeMap,
+    convert::TryFrom,
+    io, iter, mem,
+    path::{Path, PathBuf},
+    rc::Rc,
+    str,
+};
 
 use serde::{Deserialize, Serialize};
 use thiserror::Error as ThisError;
 
+use crate::{
+    branch::Branch,
+    health::{HealthIssue, TagCheck},
+    remote::RemoteUrls,
+    status::WorktreeStatus,
+    submodule::Submodule,
+    upstream::BranchUpstream,
+    verify::IntegrityProblem,
+    worktree::Worktree,
+};
+
+mod darcs;
+#[cfg(feature = "libgit2")]
 mod git;
+mod git_cli;
+mod hg;
+mod jj;
+mod pijul;
+#[cfg(not(feature = "libgit2"))]
+use git_cli as git;
 
 /// VCS parse error.
 #[derive(Debug, Clone, PartialEq, Eq, ThisError)]
@@ -21,18 +49,171 @@ impl VcsParseError {
     }
 }
 
+thread_local! {
+    /// Environment variables for external VCS commands run on the current thread.
+    static COMMAND_ENV: RefCell<BTreeMap<String, String>> = RefCell::new(BTreeMap::new());
+}
+
+/// Runs `f` with the environment variables set for external VCS commands run
+/// on the current thread during it.
+///
+/// This is intended for per-collection environment variables such as
+/// `GIT_SSH_COMMAND`.
+/// Since libgit2 does not run external commands, remote operations of git
+/// (clones, fetches, and pushes) run the `git` command instead of libgit2
+/// while any variables are set.
+pub fn with_env<R>(vars: &BTreeMap<String, String>, f: impl FnOnce() -> R) -> R {
+    /// Guard to restore the previous variables even on panic.
+    struct Restore(BTreeMap<String, String>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let prev = mem::take(&mut self.0);
+            COMMAND_ENV.with(|env| *env.borrow_mut() = prev);
+        }
+    }
+
+    let _restore = Restore(COMMAND_ENV.with(|env| env.replace(vars.clone())));
+    f()
+}
+
+/// Callback for transfer progress of remote operations.
+type ProgressCallback = Rc<dyn Fn(u32)>;
+
+thread_local! {
+    /// Callback for transfer progress of remote operations on the current thread.
+    static PROGRESS: RefCell<Option<ProgressCallback>> = RefCell::new(None);
+}
+
+/// Runs `f` with the callback called on progress of remote operations (such
+/// as clones and fetches) run on the current thread during it.
+///
+/// The callback receives the percentage (0 to 100) of the received objects,
+/// and is called only when the percentage changes.
+/// Note that progress is reported only by libgit2, so the callback is never
+/// called without `libgit2` feature.
+pub fn with_progress<R>(callback: impl Fn(u32) + 'static, f: impl FnOnce() -> R) -> R {
+    /// Guard to restore the previous callback even on panic.
+    struct Restore(Option<ProgressCallback>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let prev = self.0.take();
+            PROGRESS.with(|progress| *progress.borrow_mut() = prev);
+        }
+    }
+
+    let callback: ProgressCallback = Rc::new(callback);
+    let _restore = Restore(PROGRESS.with(|progress| progress.replace(Some(callback))));
+    f()
+}
+
+/// Returns the callback for transfer progress on the current thread.
+#[cfg(feature = "libgit2")]
+fn progress_callback() -> Option<ProgressCallback> {
+    PROGRESS.with(|progress| progress.borrow().clone())
+}
+
+/// Returns the environment variables for external VCS commands.
+fn command_env() -> BTreeMap<String, String> {
+    COMMAND_ENV.with(|env| env.borrow().clone())
+}
+
+/// Returns true if remote operations of git should run the `git` command
+/// instead of libgit2.
+///
+/// This is true when environment variables are set by [`with_env`], since
+/// they take effect only on external commands.
+fn uses_git_command() -> bool {
+    cfg!(feature = "libgit2") && COMMAND_ENV.with(|env| !env.borrow().is_empty())
+}
+
+/// Boxed error from a VCS backend.
+pub type BackendError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
 /// VCS operation error.
 #[derive(Debug, ThisError)]
-#[error(transparent)]
-pub struct VcsError {
-    /// Source error.
-    source: anyhow::Error,
+#[non_exhaustive]
+pub enum VcsError {
+    /// The repository or a file was not found.
+    #[error("Not found")]
+    NotFound {
+        /// Source error.
+        source: BackendError,
+    },
+    /// Permission denied.
+    #[error("Permission denied")]
+    PermissionDenied {
+        /// Source error.
+        source: BackendError,
+    },
+    /// Network error.
+    #[error("Network error")]
+    Network {
+        /// Source error.
+        source: BackendError,
+    },
+    /// Authentication error.
+    #[error("Authentication failed")]
+    Auth {
+        /// Source error.
+        source: BackendError,
+    },
+    /// The destination path exists but is not a directory.
+    #[error("Destination path {} is not a directory", path.display())]
+    DestinationNotDirectory {
+        /// Destination path.
+        path: PathBuf,
+    },
+    /// Failed to create a directory.
+    #[error("Failed to create directory {}", path.display())]
+    CreateDir {
+        /// Path to the directory.
+        path: PathBuf,
+        /// Source error.
+        #[source]
+        source: io::Error,
+    },
+    /// The operation is not supported by this build.
+    ///
+    /// Some operations need libgit2, and are unavailable when `magro` is
+    /// built without `libgit2` feature.
+    #[error("Unsupported operation in this build (built without libgit2): {operation}")]
+    Unsupported {
+        /// Operation name.
+        operation: &'static str,
+    },
+    /// The operation is not supported for the VCS.
+    #[error("Unsupported operation for {}: {operation}", vcs.name_lower())]
+    UnsupportedByVcs {
+        /// VCS type.
+        vcs: Vcs,
+        /// Operation name.
+        operation: &'static str,
+    },
+    /// Other errors reported by the VCS backend.
+    #[error(transparent)]
+    Other(BackendError),
 }
 
 impl VcsError {
-    /// Creates a new error.
-    fn new(e: impl Into<anyhow::Error>) -> Self {
-        Self { source: e.into() }
+    /// Creates a new error from the given I/O error.
+    #[must_use]
+    fn from_io(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => Self::NotFound { source: e.into() },
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied { source: e.into() },
+            _ => Self::Other(e.into()),
+        }
+    }
+
+    /// Returns the path related to the error, if available.
+    #[must_use]
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::DestinationNotDirectory { path } | Self::CreateDir { path, .. } => Some(path),
+            _ => None,
+        }
     }
 }
 
@@ -47,8 +228,16 @@ impl VcsError {
 // `<VcsVariants as ExactSizeIterator>::len()` when variants are changed.
 // NOTE: Variants should be ordered alphabetically.
 pub enum Vcs {
+    /// Darcs.
+    Darcs,
     /// Git.
     Git,
+    /// Mercurial.
+    Hg,
+    /// Jujutsu.
+    Jj,
+    /// Pijul.
+    Pijul,
 }
 
 impl Vcs {
@@ -58,11 +247,19 @@ impl Vcs {
     ///
     /// ```
     /// # use magro::vcs::Vcs;
+    /// assert_eq!(Vcs::Darcs.name_lower(), "darcs");
     /// assert_eq!(Vcs::Git.name_lower(), "git");
+    /// assert_eq!(Vcs::Hg.name_lower(), "hg");
+    /// assert_eq!(Vcs::Jj.name_lower(), "jj");
+    /// assert_eq!(Vcs::Pijul.name_lower(), "pijul");
     /// ```
     pub fn name_lower(&self) -> &'static str {
         match self {
+            Self::Darcs => "darcs",
             Self::Git => "git",
+            Self::Hg => "hg",
+            Self::Jj => "jj",
+            Self::Pijul => "pijul",
         }
     }
 
@@ -72,14 +269,22 @@ impl Vcs {
     ///
     /// ```
     /// # use magro::vcs::Vcs;
+    /// assert_eq!(Vcs::try_from_name_lower("darcs"), Ok(Vcs::Darcs));
     /// assert_eq!(Vcs::try_from_name_lower("git"), Ok(Vcs::Git));
+    /// assert_eq!(Vcs::try_from_name_lower("hg"), Ok(Vcs::Hg));
+    /// assert_eq!(Vcs::try_from_name_lower("jj"), Ok(Vcs::Jj));
+    /// assert_eq!(Vcs::try_from_name_lower("pijul"), Ok(Vcs::Pijul));
     ///
     /// assert!(Vcs::try_from_name_lower("Git").is_err());
     /// assert!(Vcs::try_from_name_lower("no-such-vcs").is_err());
     /// ```
     pub fn try_from_name_lower(s: &str) -> Result<Self, VcsParseError> {
         match s {
+            "darcs" => Ok(Self::Darcs),
             "git" => Ok(Self::Git),
+            "hg" => Ok(Self::Hg),
+            "jj" => Ok(Self::Jj),
+            "pijul" => Ok(Self::Pijul),
             _ => Err(VcsParseError::new()),
         }
     }
@@ -89,7 +294,7 @@ impl Vcs {
     #[must_use]
     pub fn variants() -> VcsVariants {
         VcsVariants {
-            next: Some(Self::Git),
+            next: Some(Self::Darcs),
         }
     }
 
@@ -97,14 +302,429 @@ impl Vcs {
     #[inline]
     pub fn workdir<'a>(&self, repo_path: &'a Path) -> Result<Option<Cow<'a, Path>>, VcsError> {
         match self {
-            Self::Git => git::workdir(repo_path).map_err(VcsError::new),
+            Self::Darcs => darcs::workdir(repo_path),
+            Self::Git => git::workdir(repo_path),
+            Self::Hg => hg::workdir(repo_path),
+            Self::Jj => jj::workdir(repo_path),
+            Self::Pijul => pijul::workdir(repo_path),
+        }
+    }
+
+    /// Returns the name of the branch checked out at HEAD.
+    ///
+    /// Returns `Ok(None)` if HEAD is detached.
+    /// The branch name is returned even if the branch has no commits yet.
+    pub fn head_branch(&self, repo_path: &Path) -> Result<Option<String>, VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("reading the current branch")),
+            Self::Git => git::head_branch(repo_path),
+            Self::Hg => hg::head_branch(repo_path),
+            Self::Jj => Err(jj::unsupported("reading the current bookmark")),
+            Self::Pijul => Err(pijul::unsupported("reading the current channel")),
         }
     }
 
     /// Clones a repository at the given URI into local directory.
     pub fn clone(&self, uri: &str, dest: &Path, bare: bool) -> Result<(), VcsError> {
         match self {
-            Self::Git => git::clone(uri, dest, bare).map_err(VcsError::new),
+            Self::Darcs => darcs::clone(uri, dest, bare),
+            Self::Git if uses_git_command() => git_cli::clone(uri, dest, bare),
+            Self::Git => git::clone(uri, dest, bare),
+            Self::Hg => hg::clone(uri, dest, bare),
+            Self::Jj => jj::clone(uri, dest, bare),
+            Self::Pijul => pijul::clone(uri, dest, bare),
+        }
+    }
+
+    /// Clones a local repository into another local directory.
+    ///
+    /// Objects are hardlinked where possible.
+    /// Local branches, remotes (with remote-tracking branches), and upstreams
+    /// of branches are copied from the source, so the copy does not have the
+    /// remote pointing to the source.
+    pub fn clone_local(&self, src_path: &Path, dest: &Path, bare: bool) -> Result<(), VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("local clone")),
+            Self::Git => git::clone_local(src_path, dest, bare),
+            Self::Hg => Err(hg::unsupported("local clone")),
+            Self::Jj => Err(jj::unsupported("local clone")),
+            Self::Pijul => Err(pijul::unsupported("local clone")),
+        }
+    }
+
+    /// Clones a repository at the given URI into local directory, checking out
+    /// only the files in the given directories (sparse checkout in cone mode).
+    ///
+    /// Directories are relative to the repository root (for example,
+    /// `docs` or `crates/foo`). Files directly in the root directory and in
+    /// the ancestors of the directories are also checked out, same as
+    /// `git sparse-checkout set`.
+    /// Sparse checkout is also configured for the repository, so that git
+    /// keeps the same partial working tree.
+    pub fn clone_sparse(&self, uri: &str, dest: &Path, dirs: &[String]) -> Result<(), VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("sparse checkout")),
+            Self::Git if uses_git_command() => git_cli::clone_sparse(uri, dest, dirs),
+            Self::Git => git::clone_sparse(uri, dest, dirs),
+            Self::Hg => Err(hg::unsupported("sparse checkout")),
+            Self::Jj => Err(jj::unsupported("sparse checkout")),
+            Self::Pijul => Err(pijul::unsupported("sparse checkout")),
+        }
+    }
+
+    /// Removes the history of the repository and initializes a new empty
+    /// repository in the working directory.
+    ///
+    /// Files in the working directory are kept.
+    pub fn reinit(&self, workdir: &Path) -> Result<(), VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("reinitialization")),
+            Self::Git => git::reinit(workdir),
+            Self::Hg => Err(hg::unsupported("reinitialization")),
+            Self::Jj => Err(jj::unsupported("reinitialization")),
+            Self::Pijul => Err(pijul::unsupported("reinitialization")),
+        }
+    }
+
+    /// Sets the URL of the remote, creating the remote if it does not exist.
+    ///
+    /// Returns `Ok(true)` if the remote is created or modified.
+    pub fn set_remote_url(
+        &self,
+        repo_path: &Path,
+        remote: &str,
+        url: &str,
+    ) -> Result<bool, VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("setting remote URLs")),
+            Self::Git => git::set_remote_url(repo_path, remote, url),
+            Self::Hg => Err(hg::unsupported("setting remote URLs")),
+            Self::Jj => Err(jj::unsupported("setting remote URLs")),
+            Self::Pijul => Err(pijul::unsupported("setting remote URLs")),
+        }
+    }
+
+    /// Returns the URL of the remote.
+    ///
+    /// Returns `Ok(None)` if the remote does not exist.
+    pub fn remote_url(&self, repo_path: &Path, remote: &str) -> Result<Option<String>, VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("reading remotes")),
+            Self::Git => git::remote_url(repo_path, remote),
+            Self::Hg => hg::remote_url(repo_path, remote),
+            Self::Jj => Err(jj::unsupported("reading remotes")),
+            Self::Pijul => Err(pijul::unsupported("reading remotes")),
+        }
+    }
+
+    /// Returns the remotes of the repository.
+    pub fn remotes(&self, repo_path: &Path) -> Result<Vec<RemoteUrls>, VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("reading remotes")),
+            Self::Git => git::remotes(repo_path),
+            Self::Hg => hg::remotes(repo_path),
+            Self::Jj => Err(jj::unsupported("reading remotes")),
+            Self::Pijul => Err(pijul::unsupported("reading remotes")),
+        }
+    }
+
+    /// Sets the push URL of the existing remote.
+    pub fn set_remote_push_url(
+        &self,
+        repo_path: &Path,
+        remote: &str,
+        url: &str,
+    ) -> Result<(), VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("setting remote push URLs")),
+            Self::Git => git::set_remote_push_url(repo_path, remote, url),
+            Self::Hg => Err(hg::unsupported("setting remote push URLs")),
+            Self::Jj => Err(jj::unsupported("setting remote push URLs")),
+            Self::Pijul => Err(pijul::unsupported("setting remote push URLs")),
+        }
+    }
+
+    /// Pushes all local branches and tags to the remote, overwriting remote refs.
+    pub fn push_all(&self, repo_path: &Path, remote: &str) -> Result<(), VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("pushing all branches")),
+            Self::Git if uses_git_command() => git_cli::push_all(repo_path, remote),
+            Self::Git => git::push_all(repo_path, remote),
+            Self::Hg => Err(hg::unsupported("pushing all branches")),
+            Self::Jj => Err(jj::unsupported("pushing all branches")),
+            Self::Pijul => Err(pijul::unsupported("pushing all branches")),
+        }
+    }
+
+    /// Returns names of local branches which are missing or different on the remote.
+    ///
+    /// Note that this fetches the remote to get the latest state.
+    pub fn unsynced_branches(
+        &self,
+        repo_path: &Path,
+        remote: &str,
+    ) -> Result<Vec<String>, VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("comparing branches with remotes")),
+            Self::Git if uses_git_command() => git_cli::unsynced_branches(repo_path, remote),
+            Self::Git => git::unsynced_branches(repo_path, remote),
+            Self::Hg => Err(hg::unsupported("comparing branches with remotes")),
+            Self::Jj => Err(jj::unsupported("comparing branches with remotes")),
+            Self::Pijul => Err(pijul::unsupported("comparing branches with remotes")),
+        }
+    }
+
+    /// Fetches all remotes of the repository.
+    pub fn fetch(&self, repo_path: &Path) -> Result<(), VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("fetch")),
+            Self::Git if uses_git_command() => git_cli::fetch(repo_path),
+            Self::Git => git::fetch(repo_path),
+            Self::Hg => hg::fetch(repo_path),
+            Self::Jj => Err(jj::unsupported("fetch")),
+            Self::Pijul => Err(pijul::unsupported("fetch")),
+        }
+    }
+
+    /// Fetches all remotes of the repository, and prunes remote-tracking
+    /// branches deleted on the remotes.
+    ///
+    /// Returns the number of pruned remote-tracking branches.
+    pub fn fetch_and_prune(&self, repo_path: &Path) -> Result<usize, VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("fetch")),
+            Self::Git if uses_git_command() => git_cli::fetch_and_prune(repo_path),
+            Self::Git => git::fetch_and_prune(repo_path),
+            Self::Hg => hg::fetch_and_prune(repo_path),
+            Self::Jj => Err(jj::unsupported("fetch")),
+            Self::Pijul => Err(pijul::unsupported("fetch")),
+        }
+    }
+
+    /// Returns the ID of the commit at HEAD, or `None` if the repository has
+    /// no commits.
+    pub fn head_commit(&self, repo_path: &Path) -> Result<Option<String>, VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("reading the current state")),
+            Self::Git => git::head_commit(repo_path),
+            Self::Hg => hg::head_commit(repo_path),
+            Self::Jj => Err(jj::unsupported("reading the current state")),
+            Self::Pijul => Err(pijul::unsupported("reading the current state")),
+        }
+    }
+
+    /// Returns true if the repository has the commit.
+    pub fn has_commit(&self, repo_path: &Path, commit: &str) -> Result<bool, VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("reading the history")),
+            Self::Git => git::has_commit(repo_path, commit),
+            Self::Hg => hg::has_commit(repo_path, commit),
+            Self::Jj => Err(jj::unsupported("reading the history")),
+            Self::Pijul => Err(pijul::unsupported("reading the history")),
+        }
+    }
+
+    /// Checks out the commit (with detached HEAD for git).
+    ///
+    /// Local changes conflicting with the commit are not overwritten, and
+    /// result in an error.
+    pub fn checkout_commit(&self, repo_path: &Path, commit: &str) -> Result<(), VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("checking out a state")),
+            Self::Git => git::checkout_commit(repo_path, commit),
+            Self::Hg => hg::checkout_commit(repo_path, commit),
+            Self::Jj => Err(jj::unsupported("checking out a state")),
+            Self::Pijul => Err(pijul::unsupported("checking out a state")),
+        }
+    }
+
+    /// Returns the local branches.
+    ///
+    /// Returns an empty list if the repository has no commits.
+    pub fn branches(&self, repo_path: &Path) -> Result<Vec<Branch>, VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("listing branches")),
+            Self::Git => git::branches(repo_path),
+            Self::Hg => Err(hg::unsupported("listing branches")),
+            Self::Jj => Err(jj::unsupported("listing branches")),
+            Self::Pijul => Err(pijul::unsupported("listing branches")),
+        }
+    }
+
+    /// Compares the local branches with their upstreams.
+    ///
+    /// This does not access remotes, so the results are based on the
+    /// remote-tracking branches fetched last time.
+    /// Branches without upstreams (or with upstreams deleted on the remote)
+    /// are not included.
+    pub fn upstream_statuses(&self, repo_path: &Path) -> Result<Vec<BranchUpstream>, VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("comparing branches with upstreams")),
+            Self::Git => git::upstream_statuses(repo_path),
+            Self::Hg => Err(hg::unsupported("comparing branches with upstreams")),
+            Self::Jj => Err(jj::unsupported("comparing branches with upstreams")),
+            Self::Pijul => Err(pijul::unsupported("comparing branches with upstreams")),
+        }
+    }
+
+    /// Returns paths of the files in the HEAD commit.
+    ///
+    /// Returns an empty list if the repository has no commits.
+    pub fn tracked_files(&self, repo_path: &Path) -> Result<Vec<PathBuf>, VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("listing tracked files")),
+            Self::Git => git::tracked_files(repo_path),
+            Self::Hg => Err(hg::unsupported("listing tracked files")),
+            Self::Jj => Err(jj::unsupported("listing tracked files")),
+            Self::Pijul => Err(pijul::unsupported("listing tracked files")),
+        }
+    }
+
+    /// Returns the latest commit time of HEAD and the local branches in
+    /// seconds since the UNIX epoch.
+    ///
+    /// Returns `None` if the repository has no commits.
+    pub fn last_commit_time(&self, repo_path: &Path) -> Result<Option<u64>, VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("reading the history")),
+            Self::Git => git::last_commit_time(repo_path),
+            Self::Hg => hg::last_commit_time(repo_path),
+            Self::Jj => Err(jj::unsupported("reading the history")),
+            Self::Pijul => Err(pijul::unsupported("reading the history")),
+        }
+    }
+
+    /// Returns the content of the file in the HEAD commit.
+    ///
+    /// Returns `None` if the repository has no commits, or the file does not
+    /// exist in the HEAD commit.
+    pub fn head_file_content(
+        &self,
+        repo_path: &Path,
+        path: &Path,
+    ) -> Result<Option<Vec<u8>>, VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("reading files")),
+            Self::Git => git::head_file_content(repo_path, path),
+            Self::Hg => Err(hg::unsupported("reading files")),
+            Self::Jj => Err(jj::unsupported("reading files")),
+            Self::Pijul => Err(pijul::unsupported("reading files")),
+        }
+    }
+
+    /// Checks the health of the repository.
+    ///
+    /// Remote-tracking branches are not fetched, so the results are based on
+    /// the remote-tracking branches fetched last time.
+    /// Remotes are accessed only if `tags` is [`TagCheck::Remote`].
+    pub fn health_issues(
+        &self,
+        repo_path: &Path,
+        tags: TagCheck,
+    ) -> Result<Vec<HealthIssue>, VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("health check")),
+            Self::Git if tags == TagCheck::Remote && uses_git_command() => {
+                git_cli::health_issues(repo_path, tags)
+            }
+            Self::Git => git::health_issues(repo_path, tags),
+            Self::Hg => Err(hg::unsupported("health check")),
+            Self::Jj => Err(jj::unsupported("health check")),
+            Self::Pijul => Err(pijul::unsupported("health check")),
+        }
+    }
+
+    /// Returns the status of the working tree, or `None` for bare
+    /// repositories.
+    ///
+    /// Ignored files are not counted.
+    pub fn worktree_status(&self, repo_path: &Path) -> Result<Option<WorktreeStatus>, VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("reading the working tree status")),
+            Self::Git => git::worktree_status(repo_path),
+            Self::Hg => Err(hg::unsupported("reading the working tree status")),
+            Self::Jj => Err(jj::unsupported("reading the working tree status")),
+            Self::Pijul => Err(pijul::unsupported("reading the working tree status")),
+        }
+    }
+
+    /// Returns the submodules of the repository with their states.
+    ///
+    /// Returns an empty list for bare repositories.
+    pub fn submodules(&self, repo_path: &Path) -> Result<Vec<Submodule>, VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("listing submodules")),
+            Self::Git => git::submodules(repo_path),
+            Self::Hg => Err(hg::unsupported("listing submodules")),
+            Self::Jj => Err(jj::unsupported("listing submodules")),
+            Self::Pijul => Err(pijul::unsupported("listing submodules")),
+        }
+    }
+
+    /// Returns the linked worktrees of the repository.
+    pub fn worktrees(&self, repo_path: &Path) -> Result<Vec<Worktree>, VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("listing worktrees")),
+            Self::Git => git::worktrees(repo_path),
+            Self::Hg => Err(hg::unsupported("listing worktrees")),
+            Self::Jj => Err(jj::unsupported("listing worktrees")),
+            Self::Pijul => Err(pijul::unsupported("listing worktrees")),
+        }
+    }
+
+    /// Creates a linked worktree at `path` with the branch checked out.
+    ///
+    /// If the local branch does not exist, it is created from the branch of
+    /// the same name on `origin` (with the upstream set) if available, or from
+    /// `HEAD` otherwise.
+    pub fn add_worktree(
+        &self,
+        repo_path: &Path,
+        path: &Path,
+        branch: &str,
+    ) -> Result<(), VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("adding worktrees")),
+            Self::Git => git::add_worktree(repo_path, path, branch),
+            Self::Hg => Err(hg::unsupported("adding worktrees")),
+            Self::Jj => Err(jj::unsupported("adding worktrees")),
+            Self::Pijul => Err(pijul::unsupported("adding worktrees")),
+        }
+    }
+
+    /// Removes the linked worktree at `path`.
+    ///
+    /// Worktrees with uncommitted changes are not removed unless `force` is
+    /// true.
+    /// If the working directory is already missing, only the administrative
+    /// files in the repository are removed.
+    pub fn remove_worktree(
+        &self,
+        repo_path: &Path,
+        path: &Path,
+        force: bool,
+    ) -> Result<(), VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("removing worktrees")),
+            Self::Git => git::remove_worktree(repo_path, path, force),
+            Self::Hg => Err(hg::unsupported("removing worktrees")),
+            Self::Jj => Err(jj::unsupported("removing worktrees")),
+            Self::Pijul => Err(pijul::unsupported("removing worktrees")),
+        }
+    }
+
+    /// Checks the integrity of the repository database.
+    ///
+    /// All stored objects are read and verified, and objects reachable from
+    /// the references are checked to exist.
+    /// This can take a long time for large repositories.
+    pub fn verify(&self, repo_path: &Path) -> Result<Vec<IntegrityProblem>, VcsError> {
+        match self {
+            Self::Darcs => Err(darcs::unsupported("integrity check")),
+            Self::Git => git::verify(repo_path),
+            Self::Hg => Err(hg::unsupported("integrity check")),
+            Self::Jj => Err(jj::unsupported("integrity check")),
+            Self::Pijul => Err(pijul::unsupported("integrity check")),
         }
     }
 }
@@ -152,7 +772,11 @@ impl Iterator for VcsVariants {
 
     fn next(&mut self) -> Option<Self::Item> {
         let new_next = match self.next? {
-            Vcs::Git => None,
+            Vcs::Darcs => Some(Vcs::Git),
+            Vcs::Git => Some(Vcs::Hg),
+            Vcs::Hg => Some(Vcs::Jj),
+            Vcs::Jj => Some(Vcs::Pijul),
+            Vcs::Pijul => None,
         };
         mem::replace(&mut self.next, new_next)
     }
@@ -167,7 +791,14 @@ impl Iterator for VcsVariants {
 impl ExactSizeIterator for VcsVariants {
     #[inline]
     fn len(&self) -> usize {
-        1
+        match self.next {
+            Some(Vcs::Darcs) => 5,
+            Some(Vcs::Git) => 4,
+            Some(Vcs::Hg) => 3,
+            Some(Vcs::Jj) => 2,
+            Some(Vcs::Pijul) => 1,
+            None => 0,
+        }
     }
 }
 
@@ -235,7 +866,7 @@ mod vcs_tests {
 
     #[test]
     fn ordered_alphabetically() {
-        for (current, next) in Vcs::variants().zip(Vcs::variants().cycle().skip(1)) {
+        for (current, next) in Vcs::variants().zip(Vcs::variants().skip(1)) {
             assert!(current <= next, "Variants must be ordered alphabetically");
             assert!(
                 current.name_lower() <= next.name_lower(),
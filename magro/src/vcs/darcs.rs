@@ -0,0 +1,119 @@
+//! Darcs functionalities.
+//!
+//! Repositories are detected by filesystem heuristics, and clones are done by
+//! running the `darcs` command.
+//! Other operations are not supported yet.
+
+use std::{
+    borrow::Cow,
+    fs, io,
+    path::Path,
+    process::{Command, Output},
+};
+
+use crate::{
+    timing,
+    vcs::{Vcs, VcsError},
+};
+
+/// Returns true if the directory looks like a `_darcs` directory.
+///
+/// A `_darcs` directory has `patches` directory, which contains the patches
+/// of the repository.
+fn is_darcs_dir(path: &Path) -> bool {
+    path.join("patches").is_dir()
+}
+
+/// Returns the working directory for the given repository.
+///
+/// Note that `_darcs` directory should be passed as `repo` parameter.
+/// Darcs has no bare repositories, so the working directory is always
+/// available.
+pub(super) fn workdir(repo_path: &Path) -> Result<Option<Cow<'_, Path>>, VcsError> {
+    if !is_darcs_dir(repo_path) {
+        return Err(VcsError::NotFound {
+            source: format!("{:?} is not a Darcs directory", repo_path).into(),
+        });
+    }
+
+    Ok(repo_path.parent().map(Cow::Borrowed))
+}
+
+/// Runs the `darcs` command and returns its output on success.
+fn run(command: &mut Command) -> Result<Output, VcsError> {
+    log::trace!("Running {:?}", command);
+    let output = command
+        .output()
+        .map_err(|e| VcsError::Other(format!("Failed to run the darcs command: {}", e).into()))?;
+    if output.status.success() {
+        return Ok(output);
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+    Err(VcsError::Other(
+        format!("darcs command failed ({}): {}", output.status, stderr).into(),
+    ))
+}
+
+/// Returns the error for the operation unsupported for Darcs.
+#[must_use]
+pub(super) fn unsupported(operation: &'static str) -> VcsError {
+    VcsError::UnsupportedByVcs {
+        vcs: Vcs::Darcs,
+        operation,
+    }
+}
+
+/// Clones the repository at `uri` as a local directory `dest`.
+///
+/// Darcs has no bare repositories, so `bare` clones are not supported.
+pub(super) fn clone(uri: &str, dest: &Path, bare: bool) -> Result<(), VcsError> {
+    if bare {
+        return Err(unsupported("bare clone"));
+    }
+    log::trace!("Cloning {:?} into {:?}", uri, dest);
+
+    prepare_clone_dest(dest)?;
+    let mut command = Command::new("darcs");
+    command
+        .envs(super::command_env())
+        .arg("clone")
+        .arg("--")
+        .arg(uri)
+        .arg(dest);
+    {
+        let _span = timing::span("network: clone");
+        run(&mut command)?;
+    }
+    log::trace!("Successfully cloned {:?} into {:?}", uri, dest);
+
+    Ok(())
+}
+
+/// Checks the destination of a clone, and creates the parent directory.
+///
+/// Darcs creates the destination directory by itself, so an empty directory
+/// at the destination is removed beforehand.
+fn prepare_clone_dest(dest: &Path) -> Result<(), VcsError> {
+    match dest.metadata() {
+        Ok(meta) => {
+            if !meta.is_dir() {
+                return Err(VcsError::DestinationNotDirectory {
+                    path: dest.to_owned(),
+                });
+            }
+            // This fails if the directory is not empty.
+            fs::remove_dir(dest).map_err(VcsError::from_io)?;
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(VcsError::from_io(e)),
+    }
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).map_err(|e| VcsError::CreateDir {
+            path: parent.to_owned(),
+            source: e,
+        })?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,999 @@
+//! Git functionalities without libgit2.
+//!
+//! Repositories are detected by filesystem heuristics, and operations which
+//! need to read or write the repository database are done by running the
+//! `git` command.
+//! Operations which cannot be easily done by the command are not supported.
+//!
+//! With `libgit2` feature, this is used only for remote operations with
+//! environment variables set by [`with_env`][`super::with_env`].
+#![cfg_attr(feature = "libgit2", allow(dead_code))]
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashSet},
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+use crate::{
+    branch::Branch,
+    health::{HealthIssue, TagCheck},
+    remote::RemoteUrls,
+    status::WorktreeStatus,
+    submodule::Submodule,
+    timing,
+    upstream::BranchUpstream,
+    vcs::VcsError,
+    verify::IntegrityProblem,
+    worktree::Worktree,
+};
+
+/// Returns true if the directory looks like a git directory.
+///
+/// A git directory has `HEAD` file, `objects` directory, and `refs` directory.
+fn is_git_dir(path: &Path) -> bool {
+    path.join("HEAD").is_file() && path.join("objects").is_dir() && path.join("refs").is_dir()
+}
+
+/// Returns true if the git directory is configured as a bare repository.
+///
+/// This reads `core.bare` from the repository config without fully parsing it.
+fn is_configured_bare(gitdir: &Path) -> bool {
+    let config = match fs::read_to_string(gitdir.join("config")) {
+        Ok(v) => v,
+        Err(_) => return false,
+    };
+    let mut in_core = false;
+    for line in config.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_core = line.eq_ignore_ascii_case("[core]");
+        } else if in_core {
+            let mut kv = line.splitn(2, '=').map(str::trim);
+            if let (Some(key), Some(value)) = (kv.next(), kv.next()) {
+                if key.eq_ignore_ascii_case("bare") {
+                    return value.eq_ignore_ascii_case("true");
+                }
+            }
+        }
+    }
+
+    false
+}
+
+/// Returns the working directory for the given repository if available.
+///
+/// Note that `.git` directory should be passed for normal repsoitory as `repo` parameter.
+pub(super) fn workdir(repo_path: &Path) -> Result<Option<Cow<'_, Path>>, VcsError> {
+    if !is_git_dir(repo_path) {
+        return Err(VcsError::NotFound {
+            source: format!("{:?} is not a git directory", repo_path).into(),
+        });
+    }
+    if repo_path.file_name() != Some(OsStr::new(".git")) || is_configured_bare(repo_path) {
+        return Ok(None);
+    }
+
+    Ok(repo_path.parent().map(Cow::Borrowed))
+}
+
+/// Returns the name of the branch checked out at HEAD, or `None` if detached.
+///
+/// This reads `HEAD` file directly.
+pub(super) fn head_branch(repo_path: &Path) -> Result<Option<String>, VcsError> {
+    let head = fs::read_to_string(repo_path.join("HEAD")).map_err(VcsError::from_io)?;
+    Ok(head
+        .trim_end()
+        .strip_prefix("ref: refs/heads/")
+        .map(ToOwned::to_owned))
+}
+
+/// Runs the `git` command and returns its output on success.
+fn run(command: &mut Command) -> Result<Output, VcsError> {
+    log::trace!("Running {:?}", command);
+    let output = command
+        .output()
+        .map_err(|e| VcsError::Other(format!("Failed to run the git command: {}", e).into()))?;
+    if output.status.success() {
+        return Ok(output);
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+    let source = format!("git command failed ({}): {}", output.status, stderr).into();
+    Err(
+        if stderr.contains("Authentication failed") || stderr.contains("Permission denied") {
+            VcsError::Auth { source }
+        } else if stderr.contains("Could not resolve host") || stderr.contains("unable to access") {
+            VcsError::Network { source }
+        } else {
+            VcsError::Other(source)
+        },
+    )
+}
+
+/// Returns the `git` command with the environment variables set by
+/// [`with_env`][`super::with_env`].
+fn git_command() -> Command {
+    let mut command = Command::new("git");
+    command.envs(super::command_env());
+    command
+}
+
+/// Returns the `git` command for the repository.
+fn git(repo_path: &Path) -> Command {
+    let mut command = git_command();
+    command.arg("--git-dir").arg(repo_path);
+    command
+}
+
+/// Returns the stdout of the output as a string.
+fn stdout_string(output: Output) -> Result<String, VcsError> {
+    String::from_utf8(output.stdout)
+        .map_err(|e| VcsError::Other(format!("git command output is not UTF-8: {}", e).into()))
+}
+
+/// Returns the error for the operation unsupported without libgit2.
+#[must_use]
+fn unsupported(operation: &'static str) -> VcsError {
+    VcsError::Unsupported { operation }
+}
+
+/// Clones the repository at `uri` as a local directory `dest`.
+pub(super) fn clone(uri: &str, dest: &Path, bare: bool) -> Result<(), VcsError> {
+    log::trace!("Cloning {:?} into {:?}", uri, dest);
+
+    prepare_clone_dest(dest)?;
+    let mut command = git_command();
+    command.arg("clone");
+    if bare {
+        command.arg("--bare");
+    }
+    command.arg("--").arg(uri).arg(dest);
+    {
+        let _span = timing::span("network: clone");
+        run(&mut command)?;
+    }
+    log::trace!("Successfully cloned {:?} into {:?}", uri, dest);
+
+    Ok(())
+}
+
+/// Clones the local repository at `src_path` as a local directory `dest`,
+/// copying local branches, remotes, and upstreams of branches.
+pub(super) fn clone_local(src_path: &Path, dest: &Path, bare: bool) -> Result<(), VcsError> {
+    log::trace!("Cloning local repository {:?} into {:?}", src_path, dest);
+
+    prepare_clone_dest(dest)?;
+    let mut command = git_command();
+    command.args(["clone", "--local"]);
+    if bare {
+        command.arg("--bare");
+    }
+    run(command.arg("--").arg(src_path).arg(dest))?;
+
+    // Replace the remote pointing to the source with the remotes of the source.
+    let in_dest = || {
+        let mut command = git_command();
+        command.arg("-C").arg(dest);
+        command
+    };
+    run(in_dest().args(["remote", "remove", "origin"]))?;
+    run(in_dest()
+        .args(["fetch", "--no-tags", "--update-head-ok", "--"])
+        .arg(src_path)
+        .args([
+            "+refs/heads/*:refs/heads/*",
+            "+refs/remotes/*:refs/remotes/*",
+        ]))?;
+    for remote in remotes(src_path)? {
+        let url = match remote.url() {
+            Some(url) => url,
+            None => continue,
+        };
+        run(in_dest().args(["remote", "add", "--", remote.name(), url]))?;
+        if let Some(push_url) = remote.push_url() {
+            run(in_dest().args(["remote", "set-url", "--push", "--", remote.name(), push_url]))?;
+        }
+    }
+    let output = git(src_path)
+        .args([
+            "config",
+            "--local",
+            "--get-regexp",
+            r"^branch\..*\.(remote|merge)$",
+        ])
+        .output()
+        .map_err(|e| VcsError::Other(format!("Failed to run the git command: {}", e).into()))?;
+    // `git config --get-regexp` exits with 1 if no keys match.
+    if output.status.success() {
+        for line in stdout_string(output)?.lines() {
+            if let Some((key, value)) = line.split_once(' ') {
+                run(in_dest().args(["config", key, value]))?;
+            }
+        }
+    }
+    log::trace!(
+        "Successfully cloned local repository {:?} into {:?}",
+        src_path,
+        dest
+    );
+
+    Ok(())
+}
+
+/// Clones the repository at `uri` without checkout, and checks out the given
+/// directories by `git sparse-checkout`.
+pub(super) fn clone_sparse(uri: &str, dest: &Path, dirs: &[String]) -> Result<(), VcsError> {
+    log::trace!(
+        "Cloning {:?} into {:?} with sparse checkout {:?}",
+        uri,
+        dest,
+        dirs
+    );
+
+    prepare_clone_dest(dest)?;
+    {
+        let _span = timing::span("network: clone");
+        run(git_command()
+            .args(["clone", "--no-checkout", "--"])
+            .arg(uri)
+            .arg(dest))?;
+    }
+    run(git_command()
+        .arg("-C")
+        .arg(dest)
+        .args(["sparse-checkout", "set", "--cone", "--"])
+        .args(dirs.iter().map(|dir| dir.trim_matches('/'))))?;
+    run(git_command().arg("-C").arg(dest).arg("checkout"))?;
+
+    Ok(())
+}
+
+/// Checks the destination of a clone, and creates the directory if it does not exist.
+fn prepare_clone_dest(dest: &Path) -> Result<(), VcsError> {
+    match dest.metadata() {
+        Ok(meta) => {
+            // Git accepts symlink to a directory as a destination.
+            if !meta.is_dir() {
+                return Err(VcsError::DestinationNotDirectory {
+                    path: dest.to_owned(),
+                });
+            }
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            fs::create_dir_all(dest).map_err(|e| VcsError::CreateDir {
+                path: dest.to_owned(),
+                source: e,
+            })?;
+        }
+        Err(e) => return Err(VcsError::from_io(e)),
+    }
+
+    Ok(())
+}
+
+/// Removes the `.git` directory of the working directory and initializes a new repository.
+pub(super) fn reinit(workdir: &Path) -> Result<(), VcsError> {
+    log::trace!("Reinitializing the repository at {:?}", workdir);
+
+    fs::remove_dir_all(workdir.join(".git")).map_err(VcsError::from_io)?;
+    run(git_command().arg("init").arg("--").arg(workdir))?;
+
+    Ok(())
+}
+
+/// Returns the value of the config entry, or `None` if not set.
+fn config_value(repo_path: &Path, key: &str) -> Result<Option<String>, VcsError> {
+    let output = git(repo_path)
+        .args(["config", "--get", key])
+        .output()
+        .map_err(|e| VcsError::Other(format!("Failed to run the git command: {}", e).into()))?;
+    // `git config --get` exits with 1 if the key is not set.
+    if output.status.code() == Some(1) {
+        return Ok(None);
+    }
+    if !output.status.success() {
+        return Err(VcsError::Other(
+            format!(
+                "git command failed ({}): {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .into(),
+        ));
+    }
+
+    Ok(Some(stdout_string(output)?.trim_end().to_owned()))
+}
+
+/// Sets the URL of the remote, creating the remote if it does not exist.
+///
+/// Returns `Ok(true)` if the remote is created or modified.
+pub(super) fn set_remote_url(repo_path: &Path, remote: &str, url: &str) -> Result<bool, VcsError> {
+    match remote_url(repo_path, remote)? {
+        Some(current) if current == url => return Ok(false),
+        Some(_) => run(git(repo_path).args(["remote", "set-url", "--", remote, url]))?,
+        None => run(git(repo_path).args(["remote", "add", "--", remote, url]))?,
+    };
+    log::debug!("Set the URL of the remote {:?} to {:?}", remote, url);
+
+    Ok(true)
+}
+
+/// Returns the URL of the remote.
+pub(super) fn remote_url(repo_path: &Path, remote: &str) -> Result<Option<String>, VcsError> {
+    config_value(repo_path, &format!("remote.{}.url", remote))
+}
+
+/// Returns the remotes of the repository.
+pub(super) fn remotes(repo_path: &Path) -> Result<Vec<RemoteUrls>, VcsError> {
+    let names = stdout_string(run(git(repo_path).arg("remote"))?)?;
+    names
+        .lines()
+        .map(|name| {
+            Ok(RemoteUrls::new(
+                name.to_owned(),
+                remote_url(repo_path, name)?,
+                config_value(repo_path, &format!("remote.{}.pushurl", name))?,
+            ))
+        })
+        .collect()
+}
+
+/// Sets the push URL of the existing remote.
+pub(super) fn set_remote_push_url(
+    repo_path: &Path,
+    remote: &str,
+    url: &str,
+) -> Result<(), VcsError> {
+    run(git(repo_path).args(["remote", "set-url", "--push", "--", remote, url]))?;
+    log::debug!("Set the push URL of the remote {:?} to {:?}", remote, url);
+
+    Ok(())
+}
+
+/// Pushes all local branches and tags to the remote, overwriting remote refs.
+pub(super) fn push_all(repo_path: &Path, remote: &str) -> Result<(), VcsError> {
+    let _span = timing::span("network: push");
+    run(git(repo_path).args([
+        "push",
+        "--",
+        remote,
+        "+refs/heads/*:refs/heads/*",
+        "+refs/tags/*:refs/tags/*",
+    ]))?;
+
+    Ok(())
+}
+
+/// Returns local branches which are missing or different on the remote.
+///
+/// This fetches the remote branches into the remote-tracking branches, and
+/// compares local branches with them.
+pub(super) fn unsynced_branches(repo_path: &Path, remote: &str) -> Result<Vec<String>, VcsError> {
+    {
+        let _span = timing::span("network: fetch");
+        run(git(repo_path).args(["fetch", "--prune", "--no-tags", "--", remote]))?;
+    }
+
+    let branches = stdout_string(run(git(repo_path).args([
+        "for-each-ref",
+        "--format=%(refname:lstrip=2) %(objectname)",
+        "refs/heads/",
+    ]))?)?;
+    let mut unsynced = Vec::new();
+    for line in branches.lines() {
+        let (name, target) = match line.rsplit_once(' ') {
+            Some(v) => v,
+            None => continue,
+        };
+        let tracking = stdout_string(run(git(repo_path).args([
+            "for-each-ref",
+            "--format=%(objectname)",
+            &format!("refs/remotes/{}/{}", remote, name),
+        ]))?)?;
+        if tracking.trim_end() != target {
+            unsynced.push(name.to_owned());
+        }
+    }
+
+    Ok(unsynced)
+}
+
+/// Fetches all remotes of the repository.
+pub(super) fn fetch(repo_path: &Path) -> Result<(), VcsError> {
+    let _span = timing::span("network: fetch");
+    run(git(repo_path).args(["fetch", "--all"]))?;
+
+    Ok(())
+}
+
+/// Fetches all remotes of the repository, and prunes deleted remote-tracking branches.
+pub(super) fn fetch_and_prune(repo_path: &Path) -> Result<usize, VcsError> {
+    let before = remote_tracking_refs(repo_path)?;
+    {
+        let _span = timing::span("network: fetch");
+        run(git(repo_path).args(["fetch", "--all", "--prune"]))?;
+    }
+    let after = remote_tracking_refs(repo_path)?;
+
+    Ok(before.difference(&after).count())
+}
+
+/// Returns the names of the remote-tracking references.
+fn remote_tracking_refs(repo_path: &Path) -> Result<BTreeSet<String>, VcsError> {
+    let refs = stdout_string(run(git(repo_path).args([
+        "for-each-ref",
+        "--format=%(refname)",
+        "refs/remotes/",
+    ]))?)?;
+
+    Ok(refs.lines().map(ToOwned::to_owned).collect())
+}
+
+/// Returns true if HEAD points to a commit.
+fn has_head(repo_path: &Path) -> Result<bool, VcsError> {
+    ref_exists(repo_path, "HEAD")
+}
+
+/// Returns true if the reference exists and points to a commit.
+fn ref_exists(repo_path: &Path, refname: &str) -> Result<bool, VcsError> {
+    let status = git(repo_path)
+        .args(["rev-parse", "--quiet", "--verify"])
+        .arg(format!("{}^{{commit}}", refname))
+        .output()
+        .map_err(|e| VcsError::Other(format!("Failed to run the git command: {}", e).into()))?
+        .status;
+    Ok(status.success())
+}
+
+/// Returns the local branches.
+pub(super) fn branches(repo_path: &Path) -> Result<Vec<Branch>, VcsError> {
+    if !has_head(repo_path)? {
+        // The repository has no commits.
+        return Ok(Vec::new());
+    }
+
+    let merged = stdout_string(run(git(repo_path).args([
+        "for-each-ref",
+        "--merged=HEAD",
+        "--format=%(refname:lstrip=2)",
+        "refs/heads/",
+    ]))?)?;
+    let merged = merged.lines().collect::<HashSet<_>>();
+    let refs = stdout_string(run(git(repo_path).args([
+        "for-each-ref",
+        "--format=%(refname:lstrip=2)%00%(upstream:short)%00%(upstream:track)%00%(HEAD)%00%(committerdate:unix)",
+        "refs/heads/",
+    ]))?)?;
+    let mut branches = Vec::new();
+    for line in refs.lines() {
+        let fields = line.split('\0').collect::<Vec<_>>();
+        let (name, upstream, track, head, committed_at) = match fields[..] {
+            [name, upstream, track, head, committed_at] => {
+                (name, upstream, track, head, committed_at)
+            }
+            _ => continue,
+        };
+        let upstream = if upstream.is_empty() || track == "[gone]" {
+            None
+        } else {
+            Some(upstream.to_owned())
+        };
+        branches.push(Branch::new(
+            name.to_owned(),
+            upstream,
+            head == "*",
+            merged.contains(name),
+            committed_at.parse().ok(),
+        ));
+    }
+
+    Ok(branches)
+}
+
+/// Compares the local branches with their upstreams.
+pub(super) fn upstream_statuses(repo_path: &Path) -> Result<Vec<BranchUpstream>, VcsError> {
+    let branches = stdout_string(run(git(repo_path).args([
+        "for-each-ref",
+        "--format=%(refname:lstrip=2)%00%(upstream:short)%00%(upstream:track)",
+        "refs/heads/",
+    ]))?)?;
+    let mut statuses = Vec::new();
+    for line in branches.lines() {
+        let mut fields = line.split('\0');
+        let (name, upstream, track) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(name), Some(upstream), Some(track)) => (name, upstream, track),
+            _ => continue,
+        };
+        if upstream.is_empty() || track == "[gone]" {
+            continue;
+        }
+        let counts = stdout_string(run(git(repo_path).args([
+            "rev-list",
+            "--left-right",
+            "--count",
+            &format!("refs/heads/{}...{}", name, upstream),
+            "--",
+        ]))?)?;
+        let mut counts = counts.split_whitespace().map(str::parse::<usize>);
+        if let (Some(Ok(ahead)), Some(Ok(behind))) = (counts.next(), counts.next()) {
+            statuses.push(BranchUpstream::new(
+                name.to_owned(),
+                upstream.to_owned(),
+                ahead,
+                behind,
+            ));
+        }
+    }
+
+    Ok(statuses)
+}
+
+/// Checks the health of the repository.
+pub(super) fn health_issues(
+    repo_path: &Path,
+    tags: TagCheck,
+) -> Result<Vec<HealthIssue>, VcsError> {
+    let mut issues = Vec::new();
+
+    let remotes = stdout_string(run(git(repo_path).arg("remote"))?)?;
+    let remotes = remotes.lines().collect::<Vec<_>>();
+    if remotes.is_empty() {
+        issues.push(HealthIssue::NoRemotes);
+    }
+
+    let branches = stdout_string(run(git(repo_path).args([
+        "for-each-ref",
+        "--format=%(refname:lstrip=2)%00%(upstream)%00%(upstream:track)",
+        "refs/heads/",
+    ]))?)?;
+    for line in branches.lines() {
+        let mut fields = line.split('\0');
+        let (name, upstream, track) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(name), Some(upstream), Some(track)) => (name, upstream, track),
+            _ => continue,
+        };
+        if upstream.is_empty() || track == "[gone]" {
+            issues.push(HealthIssue::NoUpstream {
+                branch: name.to_owned(),
+            });
+            continue;
+        }
+        let ahead = stdout_string(run(git(repo_path).args([
+            "rev-list",
+            "--count",
+            &format!("{}..refs/heads/{}", upstream, name),
+            "--",
+        ]))?)?;
+        match ahead.trim_end().parse::<usize>() {
+            Ok(0) | Err(_) => {}
+            Ok(count) => issues.push(HealthIssue::UnpushedCommits {
+                branch: name.to_owned(),
+                count,
+            }),
+        }
+    }
+
+    match tags {
+        TagCheck::Skip => {}
+        TagCheck::Local => {
+            let unpushed_tags = locally_unpushed_tags(repo_path)?;
+            if !unpushed_tags.is_empty() {
+                issues.push(HealthIssue::UnpushedTags {
+                    tags: unpushed_tags,
+                });
+            }
+        }
+        TagCheck::Remote => issues.extend(remote_tag_issue(repo_path, &remotes)?),
+    }
+
+    if has_head(repo_path)? && head_branch(repo_path)?.is_none() {
+        issues.push(HealthIssue::DetachedHead);
+    }
+
+    if ref_exists(repo_path, "refs/stash")? {
+        let stashes = stdout_string(run(git(repo_path).args([
+            "rev-list",
+            "--walk-reflogs",
+            "--count",
+            "refs/stash",
+            "--",
+        ]))?)?;
+        match stashes.trim_end().parse::<usize>() {
+            Ok(0) | Err(_) => {}
+            Ok(count) => issues.push(HealthIssue::Stashes { count }),
+        }
+    }
+
+    if let Some(status) = worktree_status(repo_path)? {
+        let count = status.changed() + status.untracked();
+        if count != 0 {
+            issues.push(HealthIssue::UncommittedChanges { count });
+        }
+    }
+
+    Ok(issues)
+}
+
+/// Returns the names of tags pointing to commits not reachable from any
+/// remote-tracking reference.
+///
+/// Repositories without remote-tracking references are reported by other
+/// checks, so no tags are returned for them.
+fn locally_unpushed_tags(repo_path: &Path) -> Result<Vec<String>, VcsError> {
+    let has_remote_refs = !stdout_string(run(git(repo_path).args([
+        "for-each-ref",
+        "--count=1",
+        "refs/remotes/",
+    ]))?)?
+    .is_empty();
+    if !has_remote_refs {
+        return Ok(Vec::new());
+    }
+    let unpushed = stdout_string(run(git(repo_path).args([
+        "rev-list",
+        "--tags",
+        "--not",
+        "--remotes",
+        "--",
+    ]))?)?;
+    if unpushed.is_empty() {
+        return Ok(Vec::new());
+    }
+    let unpushed = unpushed.lines().collect::<HashSet<_>>();
+
+    // Lightweight tags have the commit as `objectname`, and annotated tags
+    // have it as `*objectname`.
+    let tags = stdout_string(run(git(repo_path).args([
+        "for-each-ref",
+        "--format=%(objectname) %(*objectname) %(refname:lstrip=2)",
+        "refs/tags/",
+    ]))?)?;
+    Ok(tags
+        .lines()
+        .filter_map(|line| {
+            let (oid, rest) = line.split_once(' ')?;
+            let (peeled, name) = rest.split_once(' ')?;
+            let commit = if peeled.is_empty() { oid } else { peeled };
+            unpushed.contains(commit).then(|| name.to_owned())
+        })
+        .collect())
+}
+
+/// Returns the issue of local tags which are missing on all remotes, or point
+/// to different objects there.
+///
+/// Tags of the remotes are listed by `git ls-remote`.
+/// If some remotes cannot be listed and some tags are not found on the other
+/// remotes, [`HealthIssue::RemoteTagsUnknown`] is returned.
+/// Repositories without remotes are reported by other checks, so no issues are
+/// returned for them.
+fn remote_tag_issue(repo_path: &Path, remotes: &[&str]) -> Result<Option<HealthIssue>, VcsError> {
+    let tags = stdout_string(run(git(repo_path).args([
+        "for-each-ref",
+        "--format=%(objectname) %(refname)",
+        "refs/tags/",
+    ]))?)?;
+    if remotes.is_empty() || tags.is_empty() {
+        return Ok(None);
+    }
+
+    let mut remote_tags = HashSet::new();
+    let mut unknown_remotes = Vec::new();
+    for remote in remotes {
+        let _span = timing::span("network: list remote refs");
+        let refs =
+            run(git(repo_path).args(["ls-remote", "--tags", "--", remote])).and_then(stdout_string);
+        match refs {
+            Ok(refs) => remote_tags.extend(
+                refs.lines()
+                    .filter_map(|line| line.split_once('\t'))
+                    .map(|(oid, name)| (name.to_owned(), oid.to_owned())),
+            ),
+            Err(e) => {
+                log::warn!("Failed to list tags of the remote `{}`: {}", remote, e);
+                unknown_remotes.push((*remote).to_owned());
+            }
+        }
+    }
+
+    let unpushed = tags
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .filter(|(oid, name)| !remote_tags.contains(&((*name).to_owned(), (*oid).to_owned())))
+        .map(|(_, name)| name.trim_start_matches("refs/tags/").to_owned())
+        .collect::<Vec<_>>();
+    Ok(if unpushed.is_empty() {
+        None
+    } else if !unknown_remotes.is_empty() {
+        Some(HealthIssue::RemoteTagsUnknown {
+            remotes: unknown_remotes,
+        })
+    } else {
+        Some(HealthIssue::UnpushedTags { tags: unpushed })
+    })
+}
+
+/// Returns the status of the working tree, or `None` for bare repositories.
+pub(super) fn worktree_status(repo_path: &Path) -> Result<Option<WorktreeStatus>, VcsError> {
+    let workdir = match workdir(repo_path)? {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+    let output = stdout_string(run(git(repo_path)
+        .arg("--work-tree")
+        .arg(&*workdir)
+        .args(["status", "--porcelain"]))?)?;
+    let (untracked, changed): (Vec<_>, Vec<_>) = output
+        .lines()
+        .filter(|line| !line.is_empty())
+        .partition(|line| line.starts_with("??"));
+
+    Ok(Some(WorktreeStatus::new(changed.len(), untracked.len())))
+}
+
+/// Returns the submodules of the repository with their states.
+///
+/// Not supported without libgit2.
+pub(super) fn submodules(_repo_path: &Path) -> Result<Vec<Submodule>, VcsError> {
+    Err(unsupported("listing submodules"))
+}
+
+/// Returns the linked worktrees of the repository.
+pub(super) fn worktrees(repo_path: &Path) -> Result<Vec<Worktree>, VcsError> {
+    let output = stdout_string(run(git(repo_path).args([
+        "worktree",
+        "list",
+        "--porcelain",
+    ]))?)?;
+    // The first entry is the main worktree.
+    Ok(output
+        .split("\n\n")
+        .skip(1)
+        .filter_map(parse_worktree_entry)
+        .collect())
+}
+
+/// Parses an entry of `git worktree list --porcelain` output.
+fn parse_worktree_entry(entry: &str) -> Option<Worktree> {
+    let mut path = None;
+    let mut branch = None;
+    let mut locked = false;
+    let mut prunable = false;
+    for line in entry.lines() {
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        match key {
+            "worktree" => path = Some(PathBuf::from(value)),
+            "branch" => {
+                branch = Some(
+                    value
+                        .strip_prefix("refs/heads/")
+                        .unwrap_or(value)
+                        .to_owned(),
+                )
+            }
+            "locked" => locked = true,
+            "prunable" => prunable = true,
+            _ => {}
+        }
+    }
+    let path = path?;
+    // The name is the directory name of the administrative files, which is
+    // recorded in the `.git` file of the worktree.
+    let name = fs::read_to_string(path.join(".git"))
+        .ok()
+        .and_then(|content| {
+            let gitdir = content.trim_end().strip_prefix("gitdir: ")?.to_owned();
+            Path::new(&gitdir)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .or_else(|| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })?;
+
+    Some(Worktree::new(name, path, branch, locked, prunable))
+}
+
+/// Creates a linked worktree at `path` with the branch checked out.
+///
+/// If the local branch does not exist, it is created from `origin/<branch>`
+/// (with the upstream set) if available, or from `HEAD` otherwise.
+pub(super) fn add_worktree(repo_path: &Path, path: &Path, branch: &str) -> Result<(), VcsError> {
+    log::trace!(
+        "Adding a worktree {:?} of {:?} for {:?}",
+        path,
+        repo_path,
+        branch
+    );
+
+    let mut command = git(repo_path);
+    command.args(["worktree", "add"]);
+    if ref_exists(repo_path, &format!("refs/heads/{}", branch))? {
+        command.arg("--").arg(path).arg(branch);
+    } else {
+        let remote_branch = format!("origin/{}", branch);
+        if ref_exists(repo_path, &format!("refs/remotes/{}", remote_branch))? {
+            command
+                .args(["--track", "-b", branch, "--"])
+                .arg(path)
+                .arg(remote_branch);
+        } else {
+            command.args(["-b", branch, "--"]).arg(path);
+        }
+    }
+    run(&mut command)?;
+
+    Ok(())
+}
+
+/// Removes the linked worktree at `path`.
+///
+/// Worktrees with uncommitted changes are not removed unless `force` is true.
+/// If the working directory is already missing, only the administrative
+/// files in the repository are removed.
+pub(super) fn remove_worktree(repo_path: &Path, path: &Path, force: bool) -> Result<(), VcsError> {
+    log::trace!("Removing the worktree {:?} of {:?}", path, repo_path);
+
+    let worktree = worktrees(repo_path)?
+        .into_iter()
+        .find(|worktree| worktree.path() == path)
+        .ok_or_else(|| VcsError::NotFound {
+            source: format!("No worktree found at {:?}", path).into(),
+        })?;
+    if worktree.is_prunable() {
+        run(git(repo_path).args(["worktree", "prune"]))?;
+        return Ok(());
+    }
+
+    let mut command = git(repo_path);
+    command.args(["worktree", "remove"]);
+    if force {
+        // Twice to remove locked worktrees.
+        command.args(["--force", "--force"]);
+    }
+    command.arg("--").arg(path);
+    run(&mut command)?;
+
+    Ok(())
+}
+
+/// Checks the integrity of the repository database.
+///
+/// Not supported without libgit2.
+pub(super) fn verify(_repo_path: &Path) -> Result<Vec<IntegrityProblem>, VcsError> {
+    Err(unsupported("integrity check"))
+}
+
+/// Returns paths of the files in the HEAD commit.
+pub(super) fn tracked_files(repo_path: &Path) -> Result<Vec<PathBuf>, VcsError> {
+    if !has_head(repo_path)? {
+        // The repository has no commits.
+        return Ok(Vec::new());
+    }
+
+    let output = run(git(repo_path).args(["ls-tree", "-r", "-z", "--name-only", "HEAD"]))?;
+    Ok(stdout_string(output)?
+        .split_terminator('\0')
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Returns the latest commit time of HEAD and the local branches.
+pub(super) fn last_commit_time(repo_path: &Path) -> Result<Option<u64>, VcsError> {
+    let mut latest = None;
+    if has_head(repo_path)? {
+        let head = stdout_string(run(git(repo_path).args([
+            "log",
+            "-1",
+            "--format=%ct",
+            "HEAD",
+        ]))?)?;
+        latest = head.trim().parse().ok();
+    }
+    let branches = stdout_string(run(git(repo_path).args([
+        "for-each-ref",
+        "--sort=-committerdate",
+        "--count=1",
+        "--format=%(committerdate:unix)",
+        "refs/heads/",
+    ]))?)?;
+
+    Ok(latest.max(branches.trim().parse().ok()))
+}
+
+/// Returns the ID of the commit at HEAD, or `None` if the repository has no commits.
+pub(super) fn head_commit(repo_path: &Path) -> Result<Option<String>, VcsError> {
+    if !has_head(repo_path)? {
+        return Ok(None);
+    }
+    let commit = stdout_string(run(git(repo_path).args([
+        "rev-parse",
+        "--verify",
+        "HEAD^{commit}",
+    ]))?)?;
+
+    Ok(Some(commit.trim().to_owned()))
+}
+
+/// Returns true if the repository has the commit.
+pub(super) fn has_commit(repo_path: &Path, commit: &str) -> Result<bool, VcsError> {
+    ref_exists(repo_path, commit)
+}
+
+/// Checks out the commit with detached HEAD.
+///
+/// Local changes conflicting with the commit are not overwritten, and result
+/// in an error. For bare repositories, only HEAD is updated.
+pub(super) fn checkout_commit(repo_path: &Path, commit: &str) -> Result<(), VcsError> {
+    if commit.starts_with('-') {
+        return Err(VcsError::Other(
+            format!("Invalid commit {:?}", commit).into(),
+        ));
+    }
+    match workdir(repo_path)? {
+        Some(workdir) => run(git(repo_path)
+            .arg("--work-tree")
+            .arg(&*workdir)
+            .args(["checkout", "--quiet", "--detach", commit]))?,
+        None => run(git(repo_path).args(["update-ref", "--no-deref", "HEAD", commit]))?,
+    };
+
+    Ok(())
+}
+
+/// Returns the content of the file in the HEAD commit.
+pub(super) fn head_file_content(
+    repo_path: &Path,
+    path: &Path,
+) -> Result<Option<Vec<u8>>, VcsError> {
+    if !has_head(repo_path)? {
+        // The repository has no commits.
+        return Ok(None);
+    }
+    let path = match path.to_str() {
+        Some(v) => v.replace('\\', "/"),
+        None => return Ok(None),
+    };
+    let object = format!("HEAD:{}", path);
+    let exists = git(repo_path)
+        .args(["cat-file", "-e", &object])
+        .output()
+        .map_err(|e| VcsError::Other(format!("Failed to run the git command: {}", e).into()))?
+        .status
+        .success();
+    if !exists {
+        return Ok(None);
+    }
+    let output = run(git(repo_path).args(["cat-file", "blob", &object]))?;
+
+    Ok(Some(output.stdout))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_git_directories() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        for gitdir in &["work/.git", "bare.git"] {
+            let gitdir = root.join(gitdir);
+            fs::create_dir_all(gitdir.join("objects")).unwrap();
+            fs::create_dir_all(gitdir.join("refs")).unwrap();
+            fs::write(gitdir.join("HEAD"), "ref: refs/heads/master\n").unwrap();
+        }
+        fs::write(root.join("bare.git/config"), "[core]\n\tbare = true\n").unwrap();
+        fs::create_dir_all(root.join("other/.git")).unwrap();
+
+        let workdir_of = |path: &str| workdir(&root.join(path)).map(|v| v.map(Cow::into_owned));
+        assert_eq!(workdir_of("work/.git").unwrap(), Some(root.join("work")));
+        assert_eq!(workdir_of("bare.git").unwrap(), None);
+        assert!(workdir_of("other/.git").is_err());
+    }
+}
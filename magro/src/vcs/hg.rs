@@ -0,0 +1,284 @@
+//! Mercurial functionalities.
+//!
+//! Repositories are detected by filesystem heuristics, and operations which
+//! need to read or write the repository database are done by running the
+//! `hg` command.
+//! Operations which cannot be easily done by the command are not supported.
+
+use std::{
+    borrow::Cow,
+    fs, io,
+    path::Path,
+    process::{Command, Output},
+};
+
+use crate::{
+    remote::RemoteUrls,
+    timing,
+    vcs::{Vcs, VcsError},
+};
+
+/// Name of the branch used when no branch is explicitly set.
+const DEFAULT_BRANCH: &str = "default";
+
+/// Returns true if the directory looks like a `.hg` directory.
+///
+/// A `.hg` directory has `requires` file (or `store` directory for
+/// repositories created by very old versions of Mercurial).
+fn is_hg_dir(path: &Path) -> bool {
+    path.join("requires").is_file() || path.join("store").is_dir()
+}
+
+/// Returns the working directory for the given repository.
+///
+/// Note that `.hg` directory should be passed as `repo` parameter.
+/// Mercurial has no bare repositories, so the working directory is always
+/// available (although it can be empty).
+pub(super) fn workdir(repo_path: &Path) -> Result<Option<Cow<'_, Path>>, VcsError> {
+    if !is_hg_dir(repo_path) {
+        return Err(VcsError::NotFound {
+            source: format!("{:?} is not a Mercurial directory", repo_path).into(),
+        });
+    }
+
+    Ok(repo_path.parent().map(Cow::Borrowed))
+}
+
+/// Returns the name of the branch of the working directory.
+///
+/// This reads `branch` file directly.
+pub(super) fn head_branch(repo_path: &Path) -> Result<Option<String>, VcsError> {
+    match fs::read_to_string(repo_path.join("branch")) {
+        Ok(branch) => Ok(Some(branch.trim_end().to_owned())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Some(DEFAULT_BRANCH.to_owned())),
+        Err(e) => Err(VcsError::from_io(e)),
+    }
+}
+
+/// Runs the `hg` command and returns its output on success.
+fn run(command: &mut Command) -> Result<Output, VcsError> {
+    log::trace!("Running {:?}", command);
+    let output = command
+        .output()
+        .map_err(|e| VcsError::Other(format!("Failed to run the hg command: {}", e).into()))?;
+    if output.status.success() {
+        return Ok(output);
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+    let source = format!("hg command failed ({}): {}", output.status, stderr).into();
+    Err(
+        if stderr.contains("authorization failed") || stderr.contains("Permission denied") {
+            VcsError::Auth { source }
+        } else if stderr.contains("error: ") && stderr.contains("abort:") {
+            // Network errors are reported as `abort: error: <reason>`.
+            VcsError::Network { source }
+        } else {
+            VcsError::Other(source)
+        },
+    )
+}
+
+/// Returns the `hg` command with the environment variables set by
+/// [`with_env`][`super::with_env`].
+fn hg_command() -> Command {
+    let mut command = Command::new("hg");
+    command.envs(super::command_env());
+    // Make the output independent from the user configuration and locale.
+    command.env("HGPLAIN", "1");
+    command
+}
+
+/// Returns the `hg` command for the repository.
+///
+/// Note that `.hg` directory should be passed as `repo_path` parameter.
+fn hg(repo_path: &Path) -> Command {
+    let mut command = hg_command();
+    command
+        .arg("--repository")
+        .arg(repo_path.parent().unwrap_or(repo_path));
+    command
+}
+
+/// Returns the stdout of the output as a string.
+fn stdout_string(output: Output) -> Result<String, VcsError> {
+    String::from_utf8(output.stdout)
+        .map_err(|e| VcsError::Other(format!("hg command output is not UTF-8: {}", e).into()))
+}
+
+/// Returns the error for the operation unsupported for Mercurial.
+#[must_use]
+pub(super) fn unsupported(operation: &'static str) -> VcsError {
+    VcsError::UnsupportedByVcs {
+        vcs: Vcs::Hg,
+        operation,
+    }
+}
+
+/// Clones the repository at `uri` as a local directory `dest`.
+///
+/// If `bare` is true, the working directory is left empty.
+pub(super) fn clone(uri: &str, dest: &Path, bare: bool) -> Result<(), VcsError> {
+    log::trace!("Cloning {:?} into {:?}", uri, dest);
+
+    prepare_clone_dest(dest)?;
+    let mut command = hg_command();
+    command.arg("clone");
+    if bare {
+        command.arg("--noupdate");
+    }
+    command.arg("--").arg(uri).arg(dest);
+    {
+        let _span = timing::span("network: clone");
+        run(&mut command)?;
+    }
+    log::trace!("Successfully cloned {:?} into {:?}", uri, dest);
+
+    Ok(())
+}
+
+/// Checks the destination of a clone, and creates the directory if it does not exist.
+///
+/// Mercurial accepts an empty directory as a destination.
+fn prepare_clone_dest(dest: &Path) -> Result<(), VcsError> {
+    match dest.metadata() {
+        Ok(meta) => {
+            if !meta.is_dir() {
+                return Err(VcsError::DestinationNotDirectory {
+                    path: dest.to_owned(),
+                });
+            }
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            fs::create_dir_all(dest).map_err(|e| VcsError::CreateDir {
+                path: dest.to_owned(),
+                source: e,
+            })?;
+        }
+        Err(e) => return Err(VcsError::from_io(e)),
+    }
+
+    Ok(())
+}
+
+/// Returns the URL of the path (remote), or `None` if the path does not exist.
+pub(super) fn remote_url(repo_path: &Path, remote: &str) -> Result<Option<String>, VcsError> {
+    Ok(remotes(repo_path)?
+        .into_iter()
+        .find(|path| path.name() == remote)
+        .and_then(|path| path.url().map(ToOwned::to_owned)))
+}
+
+/// Returns the paths (remotes) of the repository.
+///
+/// `default` path corresponds to `origin` remote of git, and `default-push`
+/// path is treated as the push URL of `default` path.
+pub(super) fn remotes(repo_path: &Path) -> Result<Vec<RemoteUrls>, VcsError> {
+    let paths = stdout_string(run(hg(repo_path).arg("paths"))?)?;
+    let mut default_push = None;
+    let mut entries = Vec::new();
+    for line in paths.lines() {
+        let (name, url) = match line.split_once(" = ") {
+            Some(v) => v,
+            None => continue,
+        };
+        if name == "default-push" {
+            default_push = Some(url.to_owned());
+        } else {
+            entries.push((name.to_owned(), url.to_owned()));
+        }
+    }
+
+    Ok(entries
+        .into_iter()
+        .map(|(name, url)| {
+            let push_url = if name == "default" {
+                default_push.take()
+            } else {
+                None
+            };
+            RemoteUrls::new(name, Some(url), push_url)
+        })
+        .collect())
+}
+
+/// Pulls changes from the default path of the repository.
+///
+/// The working directory is not updated.
+pub(super) fn fetch(repo_path: &Path) -> Result<(), VcsError> {
+    let _span = timing::span("network: fetch");
+    run(hg(repo_path).arg("pull"))?;
+
+    Ok(())
+}
+
+/// Pulls changes from the default path of the repository.
+///
+/// Mercurial has no remote-tracking branches, so nothing is pruned and this
+/// always returns `Ok(0)`.
+pub(super) fn fetch_and_prune(repo_path: &Path) -> Result<usize, VcsError> {
+    fetch(repo_path)?;
+
+    Ok(0)
+}
+
+/// Returns the latest commit time of the repository in seconds since the
+/// UNIX epoch.
+///
+/// Returns `None` if the repository has no commits.
+pub(super) fn last_commit_time(repo_path: &Path) -> Result<Option<u64>, VcsError> {
+    let output = stdout_string(run(hg(repo_path).args([
+        "log",
+        "--rev",
+        "sort(all(), -date)",
+        "--limit",
+        "1",
+        "--template",
+        "{date|hgdate}",
+    ]))?)?;
+    // `hgdate` is `<seconds> <timezone offset>`.
+    Ok(output
+        .split_whitespace()
+        .next()
+        .and_then(|secs| secs.parse().ok()))
+}
+
+/// Returns the ID of the changeset of the working directory, or `None` if the
+/// repository has no changesets.
+pub(super) fn head_commit(repo_path: &Path) -> Result<Option<String>, VcsError> {
+    let node = stdout_string(run(hg(repo_path).args([
+        "log",
+        "--rev",
+        ".",
+        "--template",
+        "{node}",
+    ]))?)?;
+    // The null revision is all zeros.
+    if node.is_empty() || node.bytes().all(|b| b == b'0') {
+        return Ok(None);
+    }
+
+    Ok(Some(node))
+}
+
+/// Returns true if the repository has the changeset.
+pub(super) fn has_commit(repo_path: &Path, commit: &str) -> Result<bool, VcsError> {
+    let status = hg(repo_path)
+        .args(["log", "--template", "x", "--rev"])
+        .arg(commit)
+        .output()
+        .map_err(|e| VcsError::Other(format!("Failed to run the hg command: {}", e).into()))?
+        .status;
+    Ok(status.success())
+}
+
+/// Updates the working directory to the changeset.
+///
+/// Uncommitted changes result in an error.
+pub(super) fn checkout_commit(repo_path: &Path, commit: &str) -> Result<(), VcsError> {
+    run(hg(repo_path)
+        .args(["update", "--check", "--rev"])
+        .arg(commit))?;
+
+    Ok(())
+}
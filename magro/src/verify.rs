@@ -0,0 +1,54 @@
+//! Repository integrity checks.
+//!
+//! Integrity checks detect corrupt or missing objects in the repository
+//! database, similar to `git fsck`.
+
+use std::fmt;
+
+use serde::Serialize;
+
+/// Integrity problem of a repository.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum IntegrityProblem {
+    /// The object is stored but cannot be read, or its content does not match its ID.
+    CorruptObject {
+        /// Object ID.
+        oid: String,
+        /// Reason reported by the VCS backend.
+        reason: String,
+    },
+    /// The object is referenced but not stored.
+    MissingObject {
+        /// Object ID.
+        oid: String,
+        /// Reference name or ID of the object referencing the missing object.
+        referenced_by: String,
+    },
+    /// The reference cannot be read.
+    BrokenRef {
+        /// Reference name.
+        name: String,
+        /// Reason reported by the VCS backend.
+        reason: String,
+    },
+}
+
+impl fmt::Display for IntegrityProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CorruptObject { oid, reason } => {
+                write!(f, "corrupt object {}: {}", oid, reason)
+            }
+            Self::MissingObject { oid, referenced_by } => {
+                write!(
+                    f,
+                    "missing object {} (referenced by {})",
+                    oid, referenced_by
+                )
+            }
+            Self::BrokenRef { name, reason } => write!(f, "broken ref `{}`: {}", name, reason),
+        }
+    }
+}
@@ -0,0 +1,286 @@
+//! Serializable views of repositories and collections.
+//!
+//! These types are snapshots of the information magro knows about
+//! repositories and collections, intended to be serialized as machine-readable
+//! outputs (such as JSON).
+//!
+//! Paths are serialized in the same way as the cache: paths which are not
+//! valid UTF-8 are serialized in platform-specific forms (such as
+//! `{ "unix": "..." }` with percent-encoded bytes) instead of failing.
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::{
+    cache::{path_serde, Provenance, RepoCacheEntry},
+    collection::{Collection, CollectionName},
+    metadata::RepoMetadata,
+    vcs::Vcs,
+    Context,
+};
+
+/// View of a repository.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RepoView {
+    /// Name of the collection the repository belongs to.
+    collection: CollectionName,
+    /// VCS type.
+    vcs: Vcs,
+    /// Absolute path of the repository.
+    ///
+    /// For git, `.git` directory or `*.git` directory.
+    #[serde(with = "path_serde")]
+    path: PathBuf,
+    /// Path of the repository relative to the collection directory.
+    #[serde(with = "path_serde")]
+    relative_path: PathBuf,
+    /// Name of the repository.
+    ///
+    /// See [`RepoView::name`].
+    #[serde(with = "path_serde")]
+    name: PathBuf,
+    /// Absolute path of the working directory.
+    ///
+    /// This is `None` if the repository has no working directory, or the
+    /// working directory is not resolved.
+    #[serde(with = "path_serde::option")]
+    workdir: Option<PathBuf>,
+    /// Metadata.
+    #[serde(flatten)]
+    metadata: RepoMetadata,
+    /// URI the repository was cloned from by magro.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source_uri: Option<String>,
+    /// How the repository was added.
+    provenance: Provenance,
+}
+
+impl RepoView {
+    /// Creates a new view from the collection and the cache entry.
+    ///
+    /// Note that the working directory is not resolved by this function.
+    /// Use [`resolve_workdir`][`Self::resolve_workdir`] to set it.
+    #[must_use]
+    pub fn new(context: &Context, collection: &Collection, entry: &RepoCacheEntry) -> Self {
+        Self {
+            collection: collection.name().clone(),
+            vcs: entry.vcs(),
+            path: collection.abspath(context).join(entry.path()),
+            relative_path: entry.path().to_owned(),
+            name: repo_name(entry.path()),
+            workdir: None,
+            metadata: entry.metadata().clone(),
+            source_uri: entry.source_uri().map(ToOwned::to_owned),
+            provenance: entry.provenance(),
+        }
+    }
+
+    /// Returns the name of the collection the repository belongs to.
+    #[inline]
+    #[must_use]
+    pub fn collection(&self) -> &CollectionName {
+        &self.collection
+    }
+
+    /// Returns the VCS type.
+    #[inline]
+    #[must_use]
+    pub fn vcs(&self) -> Vcs {
+        self.vcs
+    }
+
+    /// Returns the absolute path of the repository.
+    #[inline]
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the path of the repository relative to the collection directory.
+    #[inline]
+    #[must_use]
+    pub fn relative_path(&self) -> &Path {
+        &self.relative_path
+    }
+
+    /// Returns the name of the repository.
+    ///
+    /// The name is the path relative to the collection directory, without
+    /// `.git` component or `.git` extension.
+    /// For example, `foo/bar/.git` and `foo/bar.git` are both named `foo/bar`.
+    #[inline]
+    #[must_use]
+    pub fn name(&self) -> &Path {
+        &self.name
+    }
+
+    /// Returns the name of the repository as a string with `/` separators.
+    ///
+    /// `/` is used as separators regardless of the platform.
+    #[must_use]
+    pub fn name_string(&self) -> String {
+        self.name
+            .iter()
+            .map(|c| c.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Returns the absolute path of the working directory, if resolved and available.
+    #[inline]
+    #[must_use]
+    pub fn workdir(&self) -> Option<&Path> {
+        self.workdir.as_deref()
+    }
+
+    /// Returns the metadata.
+    #[inline]
+    #[must_use]
+    pub fn metadata(&self) -> &RepoMetadata {
+        &self.metadata
+    }
+
+    /// Returns the URI the repository was cloned from by magro, if recorded.
+    #[inline]
+    #[must_use]
+    pub fn source_uri(&self) -> Option<&str> {
+        self.source_uri.as_deref()
+    }
+
+    /// Returns how the repository was added.
+    #[inline]
+    #[must_use]
+    pub fn provenance(&self) -> Provenance {
+        self.provenance
+    }
+
+    /// Sets the working directory of the repository.
+    #[inline]
+    pub(crate) fn set_workdir(&mut self, workdir: Option<PathBuf>) {
+        self.workdir = workdir;
+    }
+
+    /// Resolves the working directory of the repository.
+    ///
+    /// Returns `Ok(true)` if the repository has a working directory.
+    /// If the working directory is resolved beforehand (see
+    /// [`RepoMetadata::resolved`]), it is used without opening the repository.
+    pub fn resolve_workdir(&mut self) -> Result<bool, crate::vcs::VcsError> {
+        self.workdir = match self.metadata.resolved() {
+            Some(resolved) => resolved.workdir().map(ToOwned::to_owned),
+            None => self.vcs.workdir(&self.path)?.map(Into::into),
+        };
+        Ok(self.workdir.is_some())
+    }
+}
+
+/// Returns the repository name for the path relative to the collection directory.
+///
+/// For example, `foo/bar/.git` and `foo/bar.git` are both named `foo/bar`.
+#[must_use]
+pub fn repo_name(relpath: &Path) -> PathBuf {
+    if relpath.file_name().map_or(false, |name| name == ".git") {
+        return relpath.parent().unwrap_or(relpath).to_owned();
+    }
+    if relpath.extension().map_or(false, |ext| ext == "git") {
+        return relpath.with_extension("");
+    }
+    relpath.to_owned()
+}
+
+/// View of a collection.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CollectionView {
+    /// Collection name.
+    name: CollectionName,
+    /// Absolute path of the collection directory.
+    #[serde(with = "path_serde")]
+    path: PathBuf,
+    /// Whether the collection is the default collection.
+    is_default: bool,
+}
+
+impl CollectionView {
+    /// Creates a new view from the collection.
+    #[must_use]
+    pub fn new(context: &Context, collection: &Collection) -> Self {
+        let name = collection.name();
+        let is_default = context.config().default_collection() == Some(name);
+        Self {
+            name: name.clone(),
+            path: collection.abspath(context).into_owned(),
+            is_default,
+        }
+    }
+
+    /// Returns the collection name.
+    #[inline]
+    #[must_use]
+    pub fn name(&self) -> &CollectionName {
+        &self.name
+    }
+
+    /// Returns the absolute path of the collection directory.
+    #[inline]
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns whether the collection is the default collection.
+    #[inline]
+    #[must_use]
+    pub fn is_default(&self) -> bool {
+        self.is_default
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repo_name_strips_dotgit() {
+        assert_eq!(repo_name(Path::new("foo/bar/.git")), Path::new("foo/bar"));
+        assert_eq!(repo_name(Path::new("foo/bar.git")), Path::new("foo/bar"));
+        assert_eq!(repo_name(Path::new("bar.git")), Path::new("bar"));
+        assert_eq!(repo_name(Path::new("foo/bar")), Path::new("foo/bar"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn serialize_non_utf8_path() {
+        use std::{convert::TryFrom, ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        use serde_test::{assert_ser_tokens, Token};
+
+        let view = CollectionView {
+            name: CollectionName::try_from("src").unwrap(),
+            path: PathBuf::from(OsStr::from_bytes(b"/src/\xff")),
+            is_default: false,
+        };
+        assert_ser_tokens(
+            &view,
+            &[
+                Token::Struct {
+                    name: "CollectionView",
+                    len: 3,
+                },
+                Token::Str("name"),
+                Token::Str("src"),
+                Token::Str("path"),
+                Token::Struct {
+                    name: "PathRepr",
+                    len: 1,
+                },
+                Token::Str("unix"),
+                Token::Str("/src/%FF"),
+                Token::StructEnd,
+                Token::Str("is_default"),
+                Token::Bool(false),
+                Token::StructEnd,
+            ],
+        );
+    }
+}
@@ -0,0 +1,79 @@
+//! Linked worktrees of repositories.
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+/// Linked worktree of a repository.
+///
+/// The main working directory of a repository is not a linked worktree.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Worktree {
+    /// Name of the worktree in the repository.
+    name: String,
+    /// Absolute path of the working directory.
+    path: PathBuf,
+    /// Branch checked out in the worktree, or `None` if detached.
+    branch: Option<String>,
+    /// Whether the worktree is locked.
+    locked: bool,
+    /// Whether the working directory is missing, and the worktree can be pruned.
+    prunable: bool,
+}
+
+impl Worktree {
+    /// Creates a new `Worktree`.
+    #[inline]
+    #[must_use]
+    pub fn new(
+        name: String,
+        path: PathBuf,
+        branch: Option<String>,
+        locked: bool,
+        prunable: bool,
+    ) -> Self {
+        Self {
+            name,
+            path,
+            branch,
+            locked,
+            prunable,
+        }
+    }
+
+    /// Returns the name of the worktree in the repository.
+    #[inline]
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the absolute path of the working directory.
+    #[inline]
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the branch checked out in the worktree, or `None` if detached.
+    #[inline]
+    #[must_use]
+    pub fn branch(&self) -> Option<&str> {
+        self.branch.as_deref()
+    }
+
+    /// Returns true if the worktree is locked.
+    #[inline]
+    #[must_use]
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Returns true if the working directory is missing, and the worktree can
+    /// be pruned.
+    #[inline]
+    #[must_use]
+    pub fn is_prunable(&self) -> bool {
+        self.prunable
+    }
+}